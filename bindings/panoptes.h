@@ -0,0 +1,50 @@
+/* SPDX-License-Identifier: MIT */
+/* SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath> */
+
+/*
+ * C interface to the Panoptes analysis engine (libpanoptes)
+ *
+ * Open an engine once, then ask it for suggestions:
+ *
+ *     PanoptesEngine *engine = panoptes_engine_new("config.toml");
+ *     char *json = panoptes_analyze(engine, "scan.pdf");
+ *     if (json == NULL)
+ *         fprintf(stderr, "%s\n", panoptes_last_error());
+ *     panoptes_string_free(json);
+ *     panoptes_engine_free(engine);
+ */
+
+#ifndef PANOPTES_H
+#define PANOPTES_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct FfiEngine PanoptesEngine;
+
+/* Open an engine with a configuration file, or the default configuration if
+ * config_path is NULL. Returns NULL on failure. */
+PanoptesEngine *panoptes_engine_new(const char *config_path);
+
+/* Analyze a file without recording or renaming it. Returns the analysis as
+ * JSON (suggested_name, confidence, category, tags, file_hash, metadata,
+ * analyzer, model), to be freed with panoptes_string_free, or NULL on
+ * failure. */
+char *panoptes_analyze(const PanoptesEngine *engine, const char *path);
+
+/* Why the last call on this thread returned NULL, or NULL if none failed.
+ * Owned by the library; valid until the next failing call. */
+const char *panoptes_last_error(void);
+
+/* Free a string returned by panoptes_analyze. */
+void panoptes_string_free(char *s);
+
+/* Close an engine. */
+void panoptes_engine_free(PanoptesEngine *engine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PANOPTES_H */
@@ -0,0 +1,89 @@
+# SPDX-License-Identifier: MIT
+# SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+"""Python bindings to the Panoptes analysis engine, through libpanoptes
+
+    from panoptes import Engine
+
+    with Engine("config.toml") as engine:
+        result = engine.analyze("scan.pdf")
+        print(result["suggested_name"], result["confidence"])
+
+The library is looked for in PANOPTES_LIBRARY, then where the system looks
+for libraries (build it with `cargo build --release --lib`).
+"""
+
+import ctypes
+import ctypes.util
+import json
+import os
+import sys
+
+
+class PanoptesError(Exception):
+    """An analysis or the engine failed"""
+
+
+def _load():
+    path = os.environ.get("PANOPTES_LIBRARY") or ctypes.util.find_library("panoptes")
+    if path is None:
+        suffix = {"win32": ".dll", "darwin": ".dylib"}.get(sys.platform, ".so")
+        prefix = "" if sys.platform == "win32" else "lib"
+        path = prefix + "panoptes" + suffix
+    lib = ctypes.CDLL(path)
+    lib.panoptes_engine_new.argtypes = [ctypes.c_char_p]
+    lib.panoptes_engine_new.restype = ctypes.c_void_p
+    lib.panoptes_analyze.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
+    lib.panoptes_analyze.restype = ctypes.c_void_p
+    lib.panoptes_last_error.argtypes = []
+    lib.panoptes_last_error.restype = ctypes.c_char_p
+    lib.panoptes_string_free.argtypes = [ctypes.c_void_p]
+    lib.panoptes_string_free.restype = None
+    lib.panoptes_engine_free.argtypes = [ctypes.c_void_p]
+    lib.panoptes_engine_free.restype = None
+    return lib
+
+
+_lib = _load()
+
+
+def _error():
+    message = _lib.panoptes_last_error()
+    return PanoptesError(message.decode() if message else "Unknown error")
+
+
+class Engine:
+    """An analysis engine with the configuration file config_path, or the
+    default configuration"""
+
+    def __init__(self, config_path=None):
+        self._engine = _lib.panoptes_engine_new(os.fsencode(config_path) if config_path else None)
+        if not self._engine:
+            raise _error()
+
+    def analyze(self, path):
+        """The analysis of the file at path, as a dict, without recording or
+        renaming it"""
+        if not self._engine:
+            raise PanoptesError("Engine is closed")
+        result = _lib.panoptes_analyze(self._engine, os.fsencode(path))
+        if not result:
+            raise _error()
+        try:
+            return json.loads(ctypes.string_at(result).decode())
+        finally:
+            _lib.panoptes_string_free(result)
+
+    def close(self):
+        if self._engine:
+            _lib.panoptes_engine_free(self._engine)
+            self._engine = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        self.close()
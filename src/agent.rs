@@ -0,0 +1,293 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Agents forwarding their files to a central server
+//!
+//! A laptop without Ollama, or whose files belong in a shared index, runs
+//! `panoptes watch` as an agent of a Panoptes server. Registered with
+//!
+//! ```text
+//! panoptes agent register --server https://panoptes.lan:8080 --token <editor token>
+//! ```
+//!
+//! it forwards each file turning up in its watch directories to the server's
+//! `/api/ingest` instead of analyzing it: the text it extracts from documents,
+//! or the file itself (up to `agent.max_upload_mb`). The server analyzes and
+//! records it, and replies with what to name it, which the agent does,
+//! recording the rename in its own history so `panoptes history undo` works
+//! there as usual.
+//!
+//! Forwarding is a job in the agent's queue: files found while the server is
+//! unreachable wait there, without using up their attempts, until it is back.
+//!
+//! Registering gives the agent a token of its own, `agent:<name>`, which is
+//! kept in the `secrets.json` next to its config file. The server lists its
+//! agents with `panoptes agent list` and `/api/agents`, and
+//! `panoptes agent remove` revokes one.
+
+use chrono::{DateTime, Utc};
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info;
+
+use crate::analyzers::{calculate_file_hash, extract_text};
+use crate::config::secrets;
+use crate::history::{create_entry, History};
+use crate::renamer::{move_recorded, target_path, Disposition};
+use crate::{AppConfig, PanoptesError, Result};
+
+/// Most characters of a document's text sent to the server
+const MAX_TEXT: usize = 20_000;
+
+/// Prefix of the names of agents' API tokens
+pub const TOKEN_PREFIX: &str = "agent:";
+
+/// A machine registered to forward its files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Agent {
+    pub name: String,
+    pub hostname: Option<String>,
+    /// Panoptes version it registered with
+    pub version: Option<String>,
+    pub registered_at: DateTime<Utc>,
+    /// When it last forwarded a file
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Files it has forwarded
+    pub files: i64,
+}
+
+/// Name of the API token of the agent `name`
+pub fn token_name(name: &str) -> String {
+    format!("{}{}", TOKEN_PREFIX, name)
+}
+
+/// The agent an API token named `token` belongs to, if it is an agent's
+pub fn agent_of_token(token: &str) -> Option<&str> {
+    token.strip_prefix(TOKEN_PREFIX)
+}
+
+/// The server files are forwarded to, when this is an agent
+pub fn server(config: &AppConfig) -> Option<&str> {
+    config.agent.server.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// This machine's name
+pub fn hostname() -> String {
+    std::env::var("HOSTNAME").or_else(|_| std::env::var("COMPUTERNAME")).ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "agent".to_string())
+}
+
+/// What registering returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registration {
+    pub agent: Agent,
+    /// The agent's own API token
+    pub token: String,
+}
+
+/// What the server says to do with a forwarded file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Instruction {
+    /// The file's record on the server
+    pub file_id: String,
+    /// Name to give it, extension included
+    pub suggested_filename: String,
+    pub disposition: Disposition,
+    pub suggested_name: String,
+    pub confidence: f64,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub file_hash: String,
+}
+
+fn client(config: &AppConfig) -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.agent.timeout_secs))
+        .build()?)
+}
+
+fn url(server: &str, path: &str) -> String {
+    format!("{}{}", server.trim_end_matches('/'), path)
+}
+
+/// Failures that mean the server can't be reached right now
+fn unavailable(server: &str, e: reqwest::Error) -> PanoptesError {
+    if e.is_connect() || e.is_timeout() || e.is_request() {
+        PanoptesError::ServerUnavailable(format!("{}: {}", server, e))
+    } else {
+        PanoptesError::Api(e)
+    }
+}
+
+/// The server's reply, or why it refused
+async fn check(server: &str, response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    let message = body.get("error").and_then(|e| e.as_str()).unwrap_or_else(|| status.as_str()).to_string();
+    Err(match status.as_u16() {
+        401 | 403 => PanoptesError::Config(format!("{} refused the agent's token ({}); register again", server, message)),
+        502..=504 => PanoptesError::ServerUnavailable(format!("{}: {}", server, message)),
+        _ => PanoptesError::Analysis(format!("{}: {}", server, message)),
+    })
+}
+
+/// Register with `server` as `name`, using a token of the server's with the
+/// editor role
+pub async fn register(server: &str, token: &str, name: &str, config: &AppConfig) -> Result<Registration> {
+    let response = client(config)?
+        .post(url(server, "/api/agents/register"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({
+            "name": name,
+            "hostname": hostname(),
+            "version": env!("CARGO_PKG_VERSION"),
+        }))
+        .send().await
+        .map_err(|e| unavailable(server, e))?;
+    Ok(check(server, response).await?.json().await?)
+}
+
+/// Keep the server and the agent's name and token in the secrets file of the
+/// project whose config file is `project`; returns the secrets file
+pub fn save(project: &Path, server: &str, registration: &Registration) -> Result<PathBuf> {
+    let path = secrets::file_for(project);
+    let mut secrets: serde_json::Value = match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => serde_json::json!({}),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(object) = secrets.as_object_mut() else {
+        return Err(PanoptesError::Config(format!("{} is not a JSON object", path.display())));
+    };
+    object.insert("agent".to_string(), serde_json::json!({
+        "server": server,
+        "name": registration.agent.name,
+        "token": registration.token,
+    }));
+    std::fs::write(&path, serde_json::to_string_pretty(&secrets)?)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(path)
+}
+
+fn credentials(config: &AppConfig) -> Result<(&str, &str)> {
+    let server = server(config)
+        .ok_or_else(|| PanoptesError::Config("agent.server is not set; run `panoptes agent register`".to_string()))?;
+    let token = config.agent.token.as_deref().filter(|t| !t.is_empty())
+        .ok_or_else(|| PanoptesError::Config("agent.token is not set; run `panoptes agent register`".to_string()))?;
+    Ok((server, token))
+}
+
+/// How the server knows this agent
+pub async fn whoami(config: &AppConfig) -> Result<Agent> {
+    let (server, token) = credentials(config)?;
+    let response = client(config)?
+        .get(url(server, "/api/agents/me"))
+        .bearer_auth(token)
+        .send().await
+        .map_err(|e| unavailable(server, e))?;
+    Ok(check(server, response).await?.json().await?)
+}
+
+/// Have the server analyze the file at `path`
+pub async fn forward(path: &Path, config: &AppConfig) -> Result<Instruction> {
+    let (server, token) = credentials(config)?;
+    let filename = path.file_name().and_then(|n| n.to_str())
+        .ok_or_else(|| PanoptesError::Analysis(format!("{} has no file name", path.display())))?
+        .to_string();
+    let file_hash = calculate_file_hash(path)?;
+    let name = config.agent.name.clone().unwrap_or_else(hostname);
+    let client_path = format!("{}:{}", name, path.display());
+
+    let request = client(config)?.post(url(server, "/api/ingest")).bearer_auth(token);
+    let request = match extract_text(path) {
+        // Only the text leaves the machine
+        Ok(text) if !text.trim().is_empty() => request.json(&serde_json::json!({
+            "filename": filename,
+            "text": text.chars().take(MAX_TEXT).collect::<String>(),
+            "path": client_path,
+            "file_hash": file_hash,
+        })),
+        _ => {
+            let size = std::fs::metadata(path)?.len();
+            if size > config.agent.max_upload_mb as u64 * 1024 * 1024 {
+                return Err(PanoptesError::Analysis(format!(
+                    "{} is larger than agent.max_upload_mb ({} MB)", path.display(), config.agent.max_upload_mb
+                )));
+            }
+            let bytes = tokio::fs::read(path).await?;
+            let form = Form::new()
+                .part("file", Part::bytes(bytes).file_name(filename))
+                .text("path", client_path);
+            request.multipart(form)
+        }
+    };
+    let response = request.send().await.map_err(|e| unavailable(server, e))?;
+    Ok(check(server, response).await?.json().await?)
+}
+
+/// Rename the file at `path` as the server said, recording it in `history`;
+/// returns its new path, or None when it is left alone
+pub fn apply(
+    path: &Path,
+    instruction: &Instruction,
+    config: &AppConfig,
+    history: &History,
+    session_id: Option<&str>,
+    dry_run: bool,
+) -> Result<Option<PathBuf>> {
+    match instruction.disposition {
+        Disposition::Apply => {}
+        Disposition::Review => {
+            info!("Server suggests {} for {:?} ({:.0}%), awaiting review", instruction.suggested_filename, path,
+                instruction.confidence * 100.0);
+            return Ok(None);
+        }
+        Disposition::Skip => {
+            info!("Confidence too low ({:.0}%), skipping rename", instruction.confidence * 100.0);
+            return Ok(None);
+        }
+    }
+    // Only the final component, so a reply can't send the file elsewhere
+    let suggested = Path::new(&instruction.suggested_filename);
+    let Some(stem) = suggested.file_stem().and_then(|s| s.to_str()).filter(|s| !s.is_empty()) else {
+        return Err(PanoptesError::Analysis(format!("Unusable name from server: {}", instruction.suggested_filename)));
+    };
+    if path.file_stem().and_then(|s| s.to_str()) == Some(stem) {
+        return Ok(None);
+    }
+    let Some(new_path) = target_path(path, stem, &instruction.file_hash, config)? else {
+        return Ok(None);
+    };
+    if dry_run {
+        info!("DRY RUN: Would rename {:?} to {:?}", path, new_path);
+        return Ok(None);
+    }
+
+    let entry = create_entry(
+        uuid::Uuid::new_v4().to_string(),
+        path.to_path_buf(),
+        new_path.clone(),
+        instruction.suggested_name.clone(),
+        instruction.category.clone(),
+        instruction.tags.clone(),
+        instruction.file_hash.clone(),
+        session_id.map(String::from),
+    );
+    move_recorded(path, &new_path, || history.append(&entry))?;
+    info!("Renamed to: {:?}", new_path);
+    Ok(Some(new_path))
+}
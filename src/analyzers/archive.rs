@@ -47,13 +47,11 @@ fn list_zip(path: &Path) -> Result<ArchiveContents> {
         Ok(contents)
     }
 
-    /// List contents of a TAR file
-    fn list_tar(path: &Path) -> Result<ArchiveContents> {
+    /// List contents of a TAR file, gzipped or not
+    fn list_tar(path: &Path, gzipped: bool) -> Result<ArchiveContents> {
         let file = std::fs::File::open(path)?;
 
-        // Check if gzipped
-        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-        let reader: Box<dyn std::io::Read> = if ext == "gz" || ext == "tgz" {
+        let reader: Box<dyn std::io::Read> = if gzipped {
             Box::new(flate2::read::GzDecoder::new(file))
         } else {
             Box::new(file)
@@ -99,11 +97,34 @@ fn get_contents(path: &Path) -> Result<ArchiveContents> {
 
         match ext.as_str() {
             "zip" | "jar" | "war" | "ear" => Self::list_zip(path),
-            "tar" | "tgz" | "gz" => Self::list_tar(path),
-            _ => Err(PanoptesError::UnsupportedFileType(ext)),
+            "tar" => Self::list_tar(path, false),
+            "tgz" | "gz" => Self::list_tar(path, true),
+            // Archives under other names, such as .cbz comic books sent here by `file_types`
+            _ => match Self::sniff(path)? {
+                Some("zip") => Self::list_zip(path),
+                Some(kind) => Self::list_tar(path, kind == "gz"),
+                None => Err(PanoptesError::UnsupportedFileType(ext)),
+            },
         }
     }
 
+    /// The kind of archive `path` is by its first bytes: `zip`, `gz` or `tar`
+    fn sniff(path: &Path) -> Result<Option<&'static str>> {
+        use std::io::Read;
+
+        let mut header = Vec::with_capacity(512);
+        std::fs::File::open(path)?.take(512).read_to_end(&mut header)?;
+        Ok(if header.starts_with(b"PK\x03\x04") {
+            Some("zip")
+        } else if header.starts_with(&[0x1f, 0x8b]) {
+            Some("gz")
+        } else if header.get(257..262) == Some(b"ustar") {
+            Some("tar")
+        } else {
+            None
+        })
+    }
+
     /// Detect archive type from contents
     fn detect_archive_type(contents: &ArchiveContents) -> Option<&'static str> {
         let exts = &contents.extensions;
@@ -214,9 +235,11 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             archive_type
         );
 
-        let suggested_name = match client.generate(&config.ai_engine.models.text, &prompt).await {
+        let response = client.generate(&config.ai_engine.models.text, &prompt).await;
+        let model = response.is_ok().then(|| config.ai_engine.models.text.clone());
+        let suggested_name = match response {
             Ok(response) => {
-                let name = clean_filename(&response);
+                let name = clean_filename(&response, &config.rules.sanitizer);
                 if name.is_empty() {
                     // Fallback based on detected type
                     match archive_type {
@@ -237,7 +260,7 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
         };
 
         let category = Some("Archives".to_string());
-        let mut tags = extract_tags(&suggested_name, &metadata);
+        let mut tags = extract_tags(&suggested_name, &metadata, &config.rules.sanitizer);
 
         // Add archive type as tag
         if let Some(t) = archive_type {
@@ -251,6 +274,8 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             tags,
             file_hash,
             metadata,
+            analyzer: None,
+            model,
         })
     }
 }
@@ -2,6 +2,13 @@
 // SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
 
 //! Audio file analyzer using metadata and optional transcription
+//!
+//! Recordings with chapters (read from ID3 `CHAP` frames, or by FFprobe as
+//! in M4B files), an audiobook genre, or lasting an hour or more are
+//! audiobooks, named `author_title`, or `author_title_part_03` for one part
+//! of a set; other recordings are music, named `artist_album_03_title` when
+//! their tags give the album and track. Numbers have as many digits as the
+//! set's largest, and at least two, so the parts sort in order.
 
 use async_trait::async_trait;
 use id3::TagLike;
@@ -10,7 +17,15 @@
 
 use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, infer_category, extract_tags};
 use crate::{AppConfig, Result, PanoptesError};
+use crate::db::Database;
 use crate::ollama::OllamaClient;
+use crate::transcribe::{self, Transcript};
+
+/// Characters of a transcript the text model is asked to name a recording by
+const TRANSCRIPT_EXCERPT: usize = 3000;
+
+/// Recordings at least this long are audiobooks
+const AUDIOBOOK_SECS: f64 = 3600.0;
 
 /// Analyzer for audio files
 pub struct AudioAnalyzer;
@@ -24,16 +39,54 @@ pub fn new() -> Self {
     fn extract_mp3_metadata(path: &Path) -> Option<AudioMetadata> {
         let tag = id3::Tag::read_from_path(path).ok()?;
 
+        let chapters = tag.chapters()
+            .map(|chapter| Chapter {
+                title: chapter.frames.iter()
+                    .find(|frame| frame.id() == "TIT2")
+                    .and_then(|frame| frame.content().text())
+                    .map(String::from),
+                start_secs: chapter.start_time as f64 / 1000.0,
+            })
+            .collect();
+
         Some(AudioMetadata {
             title: tag.title().map(String::from),
             artist: tag.artist().map(String::from),
             album: tag.album().map(String::from),
+            album_artist: tag.album_artist().map(String::from),
             year: tag.year(),
             genre: tag.genre().map(String::from),
-            duration_secs: None, // ID3 doesn't store duration directly
+            track: tag.track(),
+            track_total: tag.total_tracks(),
+            disc: tag.disc(),
+            disc_total: tag.total_discs(),
+            // TLEN, when there is one
+            duration_secs: tag.duration().map(|ms| ms as f64 / 1000.0),
+            chapters,
         })
     }
 
+    /// Chapters and length of the recording at `path`, as FFprobe tells them
+    fn probe_chapters(path: &Path) -> Option<(Vec<Chapter>, Option<f64>)> {
+        let output = std::process::Command::new("ffprobe")
+            .args(["-v", "quiet", "-print_format", "json", "-show_format", "-show_chapters"])
+            .arg(path)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+        let seconds = |value: &serde_json::Value| value.as_str().and_then(|s| s.parse::<f64>().ok());
+        let chapters = json["chapters"].as_array().into_iter().flatten()
+            .map(|chapter| Chapter {
+                title: chapter["tags"]["title"].as_str().map(String::from),
+                start_secs: seconds(&chapter["start_time"]).unwrap_or_default(),
+            })
+            .collect();
+        Some((chapters, seconds(&json["format"]["duration"])))
+    }
+
     /// Extract metadata using symphonia (supports many formats)
     fn extract_generic_metadata(path: &Path) -> Option<AudioMetadata> {
         use symphonia::core::formats::FormatOptions;
@@ -81,6 +134,26 @@ fn extract_generic_metadata(path: &Path) -> Option<AudioMetadata> {
                         Some(symphonia::core::meta::StandardTagKey::Album) => {
                             metadata.album = Some(tag.value.to_string());
                         }
+                        Some(symphonia::core::meta::StandardTagKey::AlbumArtist) => {
+                            metadata.album_artist = Some(tag.value.to_string());
+                        }
+                        // As `3` or `3/12`
+                        Some(symphonia::core::meta::StandardTagKey::TrackNumber) => {
+                            let (number, total) = numbered(&tag.value.to_string());
+                            metadata.track = number;
+                            metadata.track_total = metadata.track_total.or(total);
+                        }
+                        Some(symphonia::core::meta::StandardTagKey::TrackTotal) => {
+                            metadata.track_total = numbered(&tag.value.to_string()).0;
+                        }
+                        Some(symphonia::core::meta::StandardTagKey::DiscNumber) => {
+                            let (number, total) = numbered(&tag.value.to_string());
+                            metadata.disc = number;
+                            metadata.disc_total = metadata.disc_total.or(total);
+                        }
+                        Some(symphonia::core::meta::StandardTagKey::DiscTotal) => {
+                            metadata.disc_total = numbered(&tag.value.to_string()).0;
+                        }
                         Some(symphonia::core::meta::StandardTagKey::Genre) => {
                             metadata.genre = Some(tag.value.to_string());
                         }
@@ -97,6 +170,12 @@ fn extract_generic_metadata(path: &Path) -> Option<AudioMetadata> {
 
         Some(metadata)
     }
+
+    /// The transcript of the recording at `path`, made before or now
+    async fn transcribe(path: &Path, file_hash: &str, duration: Option<f64>, config: &AppConfig) -> Result<Transcript> {
+        let db = Database::open(&config.database.path)?;
+        transcribe::transcript(path, file_hash, duration, &db, &config.analyzers.audio).await
+    }
 }
 
 #[derive(Default, Debug)]
@@ -104,9 +183,63 @@ struct AudioMetadata {
     title: Option<String>,
     artist: Option<String>,
     album: Option<String>,
+    album_artist: Option<String>,
     year: Option<i32>,
     genre: Option<String>,
+    track: Option<u32>,
+    track_total: Option<u32>,
+    disc: Option<u32>,
+    disc_total: Option<u32>,
     duration_secs: Option<f64>,
+    chapters: Vec<Chapter>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Chapter {
+    title: Option<String>,
+    start_secs: f64,
+}
+
+impl AudioMetadata {
+    /// Whether this is an audiobook rather than music
+    fn is_audiobook(&self, extension: &str) -> bool {
+        let genre = self.genre.as_deref().unwrap_or_default().to_lowercase();
+        extension.eq_ignore_ascii_case("m4b")
+            || ["audiobook", "audio book", "spoken", "hörbuch", "livre audio"].iter().any(|g| genre.contains(g))
+            || self.chapters.len() > 1
+            || self.duration_secs.is_some_and(|secs| secs >= AUDIOBOOK_SECS)
+    }
+
+    /// The name of a part of a set: `author_title_part_03` for audiobooks,
+    /// `artist_album_03_title` for music; None when the tags don't say
+    fn part_name(&self, audiobook: bool) -> Option<String> {
+        let author = self.album_artist.as_ref().or(self.artist.as_ref())?;
+        if audiobook {
+            let title = self.album.as_ref().or(self.title.as_ref())?;
+            return Some(match self.track.filter(|_| self.track_total != Some(1)) {
+                Some(part) => format!("{} {} part {}", author, title, pad(part, self.track_total)),
+                None => format!("{} {}", author, title),
+            });
+        }
+        let (album, track, title) = (self.album.as_ref()?, self.track?, self.title.as_ref()?);
+        let number = match self.disc.filter(|_| self.disc_total.is_some_and(|total| total > 1)) {
+            Some(disc) => format!("{}-{}", disc, pad(track, self.track_total)),
+            None => pad(track, self.track_total),
+        };
+        Some(format!("{} {} {} {}", author, album, number, title))
+    }
+}
+
+/// `n` with as many digits as `total`, and at least two
+fn pad(n: u32, total: Option<u32>) -> String {
+    let width = total.map_or(2, |total| total.to_string().len().max(2));
+    format!("{:0width$}", n, width = width)
+}
+
+/// The number and total of a tag like `3/12`
+fn numbered(value: &str) -> (Option<u32>, Option<u32>) {
+    let mut parts = value.split('/').map(|part| part.trim().parse().ok());
+    (parts.next().flatten(), parts.next().flatten())
 }
 
 impl Default for AudioAnalyzer {
@@ -122,7 +255,7 @@ fn name(&self) -> &'static str {
     }
 
     fn supported_extensions(&self) -> &[&str] {
-        &["mp3", "wav", "flac", "ogg", "m4a", "aac", "wma", "opus", "aiff"]
+        &["mp3", "wav", "flac", "ogg", "m4a", "m4b", "aac", "wma", "opus", "aiff"]
     }
 
     fn priority(&self) -> u8 {
@@ -135,37 +268,101 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
         let file_hash = calculate_file_hash(path)?;
 
         // Try MP3-specific first, then generic
-        let audio_meta = if path.extension().and_then(|e| e.to_str()) == Some("mp3") {
+        let mut audio_meta = if path.extension().and_then(|e| e.to_str()) == Some("mp3") {
             Self::extract_mp3_metadata(path).or_else(|| Self::extract_generic_metadata(path))
         } else {
             Self::extract_generic_metadata(path)
         };
+        if let Some(ref mut meta) = audio_meta {
+            if meta.chapters.is_empty() || meta.duration_secs.is_none() {
+                if let Some((chapters, duration)) = Self::probe_chapters(path) {
+                    if meta.chapters.is_empty() {
+                        meta.chapters = chapters;
+                    }
+                    meta.duration_secs = meta.duration_secs.or(duration);
+                }
+            }
+        }
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("mp3");
+        let audiobook = audio_meta.as_ref().is_some_and(|meta| meta.is_audiobook(extension));
 
-        let metadata = match &audio_meta {
+        let mut metadata = match &audio_meta {
             Some(meta) => serde_json::json!({
                 "title": meta.title,
                 "artist": meta.artist,
                 "album": meta.album,
                 "year": meta.year,
                 "genre": meta.genre,
+                "album_artist": meta.album_artist,
+                "track": meta.track,
+                "track_total": meta.track_total,
+                "disc": meta.disc,
+                "disc_total": meta.disc_total,
                 "duration_secs": meta.duration_secs,
+                "chapters": meta.chapters,
+                "kind": if audiobook { "audiobook" } else { "music" },
             }),
             None => serde_json::json!({}),
         };
 
-        // Build suggested name from metadata
-        let suggested_name = if let Some(ref meta) = audio_meta {
+        // What is said, when the tags don't say what it is
+        let untitled = audio_meta.as_ref().map_or(true, |m| m.title.is_none());
+        let transcript = if config.analyzers.audio.transcribe && untitled {
+            let duration = audio_meta.as_ref().and_then(|m| m.duration_secs);
+            match Self::transcribe(path, &file_hash, duration, config).await {
+                Ok(transcript) => Some(transcript).filter(|t| !t.text.is_empty()),
+                Err(e) => {
+                    warn!("Failed to transcribe {:?}: {}", path, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let (Some(transcript), Some(fields)) = (&transcript, metadata.as_object_mut()) {
+            fields.insert("transcribed".to_string(), true.into());
+            fields.insert("transcript_model".to_string(), transcript.model.clone().into());
+        }
+
+        // Build suggested name from metadata, or from what is said
+        let mut model = None;
+        let from_transcript = match transcript {
+            Some(ref transcript) => {
+                let excerpt: String = transcript.text.chars().take(TRANSCRIPT_EXCERPT).collect();
+                let prompt = format!("This recording says:\n\n{}\n\n{}", excerpt, config.prompts.audio);
+                let client = OllamaClient::new(&config.ai_engine.url);
+                match client.generate(&config.ai_engine.models.text, &prompt).await {
+                    Ok(response) => {
+                        model = Some(config.ai_engine.models.text.clone());
+                        Some(clean_filename(&response, &config.rules.sanitizer)).filter(|name| !name.is_empty())
+                    }
+                    Err(e) => {
+                        debug!("No name from the transcript of {:?}: {}", path, e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let part_name = audio_meta.as_ref().and_then(|meta| meta.part_name(audiobook));
+        let suggested_name = if let Some(ref name) = from_transcript {
+            name.clone()
+        } else if let Some(ref name) = part_name {
+            clean_filename(name, &config.rules.sanitizer)
+        } else if let Some(ref meta) = audio_meta {
             // Prefer artist - title format
             match (&meta.artist, &meta.title) {
                 (Some(artist), Some(title)) => {
-                    clean_filename(&format!("{} - {}", artist, title))
+                    clean_filename(&format!("{} - {}", artist, title), &config.rules.sanitizer)
                 }
-                (None, Some(title)) => clean_filename(title),
+                (None, Some(title)) => clean_filename(title, &config.rules.sanitizer),
                 (Some(artist), None) => {
                     if let Some(album) = &meta.album {
-                        clean_filename(&format!("{} - {}", artist, album))
+                        clean_filename(&format!("{} - {}", artist, album), &config.rules.sanitizer)
                     } else {
-                        clean_filename(artist)
+                        clean_filename(artist, &config.rules.sanitizer)
                     }
                 }
                 (None, None) => {
@@ -181,8 +378,11 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
                     );
 
                     match client.generate(&config.ai_engine.models.text, &prompt).await {
-                        Ok(response) => clean_filename(&response),
-                        Err(_) => clean_filename(filename),
+                        Ok(response) => {
+                            model = Some(config.ai_engine.models.text.clone());
+                            clean_filename(&response, &config.rules.sanitizer)
+                        }
+                        Err(_) => clean_filename(filename, &config.rules.sanitizer),
                     }
                 }
             }
@@ -191,16 +391,17 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             let filename = path.file_stem()
                 .and_then(|s| s.to_str())
                 .unwrap_or("audio");
-            clean_filename(filename)
+            clean_filename(filename, &config.rules.sanitizer)
         };
 
-        let extension = path.extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("mp3");
-        let category = infer_category(&suggested_name, extension);
+        let category = if audiobook {
+            Some("Audiobooks".to_string())
+        } else {
+            infer_category(&suggested_name, extension)
+        };
 
         // Build tags from metadata
-        let mut tags = Vec::new();
+        let mut tags = vec![if audiobook { "audiobook" } else { "music" }.to_string()];
         if let Some(ref meta) = audio_meta {
             if let Some(ref genre) = meta.genre {
                 tags.push(genre.clone());
@@ -209,12 +410,14 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
                 tags.push(artist.clone());
             }
         }
-        tags.extend(extract_tags(&suggested_name, &metadata));
+        tags.extend(extract_tags(&suggested_name, &metadata, &config.rules.sanitizer));
         tags.sort();
         tags.dedup();
 
-        let confidence = if audio_meta.as_ref().and_then(|m| m.title.as_ref()).is_some() {
+        let confidence = if part_name.is_some() || audio_meta.as_ref().and_then(|m| m.title.as_ref()).is_some() {
             0.95 // High confidence from metadata
+        } else if from_transcript.is_some() {
+            0.80 // From what is said
         } else {
             0.60 // Lower confidence from filename
         };
@@ -226,6 +429,8 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             tags,
             file_hash,
             metadata,
+            analyzer: None,
+            model,
         })
     }
 }
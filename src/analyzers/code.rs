@@ -242,9 +242,11 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             content.lines().take(50).collect::<Vec<_>>().join("\n")
         );
 
-        let suggested_name = match client.generate(&config.ai_engine.models.code, &prompt).await {
+        let response = client.generate(&config.ai_engine.models.code, &prompt).await;
+        let model = response.is_ok().then(|| config.ai_engine.models.code.clone());
+        let suggested_name = match response {
             Ok(response) => {
-                let name = clean_filename(&response);
+                let name = clean_filename(&response, &config.rules.sanitizer);
                 if name.is_empty() {
                     // Fallback: use primary function name or language
                     structure.functions.first()
@@ -271,7 +273,7 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
         if structure.has_main {
             tags.push("executable".to_string());
         }
-        tags.extend(extract_tags(&suggested_name, &metadata));
+        tags.extend(extract_tags(&suggested_name, &metadata, &config.rules.sanitizer));
 
         Ok(AnalysisResult {
             suggested_name,
@@ -280,6 +282,8 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             tags,
             file_hash,
             metadata,
+            analyzer: None,
+            model,
         })
     }
 }
@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Comic book archive analyzer
+//!
+//! Comics (CBZ, zipped, and CBR, RARed; read with `unrar` or `bsdtar`) are
+//! named `series_issue` from their ComicInfo.xml, as comic managers write it,
+//! or else from what the vision model reads on the cover, the first page.
+//! Issue numbers get three digits, so issues sort in order.
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use std::cmp::Ordering;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+use tracing::{debug, info, warn};
+
+use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, extract_tags};
+use crate::ollama::OllamaClient;
+use crate::{AppConfig, PanoptesError, Result};
+
+/// Images the pages can be
+const PAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp"];
+
+/// Analyzer for comic book archives
+pub struct ComicAnalyzer;
+
+/// What a ComicInfo.xml says
+#[derive(Debug, Default)]
+struct ComicInfo {
+    series: Option<String>,
+    number: Option<String>,
+    volume: Option<String>,
+    title: Option<String>,
+    year: Option<String>,
+    writer: Option<String>,
+    publisher: Option<String>,
+    genre: Option<String>,
+    page_count: Option<String>,
+}
+
+impl ComicInfo {
+    fn parse(xml: &str) -> Result<Self> {
+        let mut info = Self::default();
+        crate::sources::walk_xml(xml, |open, text| {
+            let (Some(text), [root, field]) = (text, open) else { return };
+            if root != "ComicInfo" {
+                return;
+            }
+            let slot = match field.as_str() {
+                "Series" => &mut info.series,
+                "Number" => &mut info.number,
+                "Volume" => &mut info.volume,
+                "Title" => &mut info.title,
+                "Year" => &mut info.year,
+                "Writer" => &mut info.writer,
+                "Publisher" => &mut info.publisher,
+                "Genre" => &mut info.genre,
+                "PageCount" => &mut info.page_count,
+                _ => return,
+            };
+            *slot = Some(text.trim().to_string()).filter(|t| !t.is_empty());
+        })?;
+        Ok(info)
+    }
+}
+
+impl ComicAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Whether `path` is a RAR archive, whatever its extension says
+    fn is_rar(path: &Path) -> Result<bool> {
+        let mut header = Vec::with_capacity(7);
+        std::fs::File::open(path)?.take(7).read_to_end(&mut header)?;
+        Ok(header.starts_with(b"Rar!\x1a\x07"))
+    }
+
+    /// Names of the files in the comic
+    fn entries(path: &Path) -> Result<Vec<String>> {
+        if Self::is_rar(path)? {
+            let listing = Self::rar(&[&["lb", "-p-"], &["-tf"]], path, None)?;
+            return Ok(String::from_utf8_lossy(&listing).lines().map(String::from).collect());
+        }
+        let archive = zip::ZipArchive::new(std::fs::File::open(path)?)
+            .map_err(|e| PanoptesError::Archive(format!("Failed to open {}: {}", path.display(), e)))?;
+        Ok(archive.file_names().map(String::from).collect())
+    }
+
+    /// The file `name` in the comic
+    fn read_entry(path: &Path, name: &str) -> Result<Vec<u8>> {
+        if Self::is_rar(path)? {
+            return Self::rar(&[&["p", "-inul", "-p-"], &["-xOf"]], path, Some(name));
+        }
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)
+            .map_err(|e| PanoptesError::Archive(format!("Failed to open {}: {}", path.display(), e)))?;
+        let mut entry = archive.by_name(name)
+            .map_err(|e| PanoptesError::Archive(format!("No {} in {}: {}", name, path.display(), e)))?;
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Output of `unrar`, or failing that of `bsdtar`, run with their
+    /// `args` on the RAR archive `path` (and the file `name` in it)
+    fn rar(args: &[&[&str]; 2], path: &Path, name: Option<&str>) -> Result<Vec<u8>> {
+        for (program, args) in ["unrar", "bsdtar"].into_iter().zip(args) {
+            let output = Command::new(program).args(*args).arg(path).args(name).output();
+            match output {
+                Ok(output) if output.status.success() => return Ok(output.stdout),
+                Ok(output) => debug!("{} failed on {:?}: {}", program, path, String::from_utf8_lossy(&output.stderr).trim()),
+                Err(e) => debug!("No {}: {}", program, e),
+            }
+        }
+        Err(PanoptesError::Archive(format!("Can't read {}: install unrar or bsdtar", path.display())))
+    }
+
+    /// The comic's pages, in reading order
+    fn pages(entries: &[String]) -> Vec<&String> {
+        let mut pages: Vec<&String> = entries.iter()
+            .filter(|name| !name.ends_with('/') && !name.starts_with("__MACOSX"))
+            .filter(|name| Path::new(name).extension().and_then(|e| e.to_str())
+                .is_some_and(|e| PAGE_EXTENSIONS.contains(&e.to_lowercase().as_str())))
+            .collect();
+        pages.sort_by(|a, b| natural_cmp(a, b));
+        pages
+    }
+
+    /// The cover, shrunk and as JPEG, in base64 for the vision model
+    fn encode_cover(data: &[u8]) -> Result<String> {
+        let cover = image::load_from_memory(data)?;
+        let cover = if cover.width() > 1024 || cover.height() > 1024 {
+            cover.resize(1024, 1024, image::imageops::FilterType::Triangle)
+        } else {
+            cover
+        };
+        let mut buffer = Vec::new();
+        cover.to_rgb8().write_to(&mut std::io::Cursor::new(&mut buffer), image::ImageFormat::Jpeg)?;
+        Ok(general_purpose::STANDARD.encode(&buffer))
+    }
+}
+
+/// `a` and `b` compared with their runs of digits as numbers, so `page2`
+/// comes before `page10`
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (number(&mut a), number(&mut b));
+                let order = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// `number` with three digits, when it is a whole number
+fn pad_issue(number: &str) -> String {
+    match number.parse::<u32>() {
+        Ok(n) => format!("{:03}", n),
+        Err(_) => number.to_string(),
+    }
+}
+
+impl Default for ComicAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FileAnalyzer for ComicAnalyzer {
+    fn name(&self) -> &'static str {
+        "comic"
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &["cbz", "cbr"]
+    }
+
+    fn priority(&self) -> u8 {
+        60
+    }
+
+    async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResult> {
+        info!("Analyzing comic: {:?}", path);
+
+        let file_hash = calculate_file_hash(path)?;
+        let entries = Self::entries(path)?;
+        let pages = Self::pages(&entries);
+
+        let comic_info = entries.iter()
+            .find(|name| Path::new(name).file_name().is_some_and(|n| n.eq_ignore_ascii_case("ComicInfo.xml")))
+            .and_then(|name| Self::read_entry(path, name).ok())
+            .and_then(|xml| match ComicInfo::parse(&String::from_utf8_lossy(&xml)) {
+                Ok(info) => Some(info),
+                Err(e) => {
+                    warn!("Unreadable ComicInfo.xml in {:?}: {}", path, e);
+                    None
+                }
+            });
+        let info = comic_info.unwrap_or_default();
+
+        // ComicInfo.xml first, then the cover
+        let mut model = None;
+        let mut confidence = 0.5;
+        let mut suggested_name = match (&info.series, &info.number) {
+            (Some(series), Some(number)) => {
+                confidence = 0.95;
+                clean_filename(&format!("{} {}", series, pad_issue(number)), &config.rules.sanitizer)
+            }
+            (Some(series), None) => {
+                confidence = 0.8;
+                clean_filename(&format!("{} {}", series, info.title.as_deref().unwrap_or_default()), &config.rules.sanitizer)
+            }
+            _ => String::new(),
+        };
+        if suggested_name.is_empty() && config.analyzers.comic.read_cover {
+            let cover = pages.first().map(|cover| Self::read_entry(path, cover).and_then(|data| Self::encode_cover(&data)));
+            match cover {
+                Some(Ok(cover)) => {
+                    let client = OllamaClient::new(&config.ai_engine.url);
+                    match client.generate_with_image(&config.ai_engine.models.vision, &config.prompts.comic, &cover).await {
+                        Ok(text) => {
+                            model = Some(config.ai_engine.models.vision.clone());
+                            confidence = 0.7;
+                            // Issue numbers as the ComicInfo.xml ones
+                            let words: Vec<String> = text.split_whitespace()
+                                .map(|word| pad_issue(word.trim_start_matches('#')))
+                                .collect();
+                            suggested_name = clean_filename(&words.join(" "), &config.rules.sanitizer);
+                        }
+                        Err(e) => warn!("Vision model failed on the cover of {:?}: {}", path, e),
+                    }
+                }
+                Some(Err(e)) => warn!("Unreadable cover in {:?}: {}", path, e),
+                None => debug!("No pages in {:?}", path),
+            }
+        }
+        if suggested_name.is_empty() {
+            confidence = 0.5;
+            suggested_name = clean_filename(&path.file_stem().unwrap_or_default().to_string_lossy(), &config.rules.sanitizer);
+        }
+
+        let metadata = serde_json::json!({
+            "series": info.series,
+            "number": info.number,
+            "volume": info.volume,
+            "title": info.title,
+            "year": info.year,
+            "writer": info.writer,
+            "publisher": info.publisher,
+            "page_count": info.page_count.as_deref().and_then(|n| n.parse::<usize>().ok()).unwrap_or(pages.len()),
+            "comic_info": info.series.is_some() || info.number.is_some(),
+        });
+
+        let mut tags = vec!["comic".to_string()];
+        tags.extend(info.publisher.iter().cloned());
+        tags.extend(info.genre.iter().flat_map(|genres| genres.split(',').map(|g| g.trim().to_string())).filter(|g| !g.is_empty()));
+        tags.extend(extract_tags(&suggested_name, &metadata, &config.rules.sanitizer));
+        tags.sort();
+        tags.dedup();
+
+        Ok(AnalysisResult {
+            suggested_name,
+            confidence,
+            category: Some("Comics".to_string()),
+            tags,
+            file_hash,
+            metadata,
+            analyzer: None,
+            model,
+        })
+    }
+}
@@ -109,7 +109,7 @@ fn extract_docx(path: &Path) -> Result<String> {
     }
 
     /// Extract content based on file type
-    fn extract_content(path: &Path) -> Result<String> {
+    pub(crate) fn extract_content(path: &Path) -> Result<String> {
         let ext = path.extension()
             .and_then(|e| e.to_str())
             .map(|e| e.to_lowercase())
@@ -163,78 +163,82 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             }
         };
 
-        let content_preview = if content.len() > 2000 {
-            format!("{}...", &content[..2000])
-        } else {
-            content.clone()
-        };
-
-        let line_count = content.lines().count();
-        let word_count = content.split_whitespace().count();
-
-        let metadata = serde_json::json!({
-            "line_count": line_count,
-            "word_count": word_count,
-            "char_count": content.len(),
-        });
-
-        // Use text model for summarization
-        let client = OllamaClient::new(&config.ai_engine.url);
-        let prompt = format!(
-            "{}\n\nDocument content:\n{}",
-            config.prompts.document,
-            content_preview
-        );
-
-        let suggested_name = if !content.is_empty() {
-            match client.generate(&config.ai_engine.models.text, &prompt).await {
-                Ok(response) => {
-                    let name = clean_filename(&response);
-                    if name.is_empty() || name.len() < 3 {
-                        // Fallback: use first line or file stem
-                        content.lines().next()
-                            .map(|l| clean_filename(l))
-                            .filter(|n| !n.is_empty())
-                            .unwrap_or_else(|| {
-                                path.file_stem()
-                                    .and_then(|s| s.to_str())
-                                    .map(|s| clean_filename(s))
-                                    .unwrap_or_else(|| "document".to_string())
-                            })
-                    } else {
-                        name
-                    }
-                }
-                Err(e) => {
-                    warn!("LLM failed: {}", e);
-                    path.file_stem()
-                        .and_then(|s| s.to_str())
-                        .map(|s| clean_filename(s))
-                        .unwrap_or_else(|| "document".to_string())
-                }
-            }
-        } else {
-            path.file_stem()
-                .and_then(|s| s.to_str())
-                .map(|s| clean_filename(s))
-                .unwrap_or_else(|| "document".to_string())
-        };
-
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
         let extension = path.extension()
             .and_then(|e| e.to_str())
             .unwrap_or("txt");
-        let category = infer_category(&suggested_name, extension);
-        let tags = extract_tags(&suggested_name, &metadata);
-
-        let confidence = if content.len() > 100 { 0.75 } else { 0.50 };
-
-        Ok(AnalysisResult {
-            suggested_name,
-            confidence,
-            category,
-            tags,
-            file_hash,
-            metadata,
-        })
+        Ok(analyze_text(&content, stem, extension, file_hash, config).await)
+    }
+}
+
+/// Suggest a name for a document's text, falling back to its first line and then
+/// to `stem`, the current file name without extension
+pub async fn analyze_text(content: &str, stem: &str, extension: &str, file_hash: String, config: &AppConfig) -> AnalysisResult {
+    // At most 2000 characters, cut on a character boundary
+    let content_preview = match content.char_indices().nth(2000) {
+        Some((end, _)) => format!("{}...", &content[..end]),
+        None => content.to_string(),
+    };
+
+    let line_count = content.lines().count();
+    let word_count = content.split_whitespace().count();
+
+    let metadata = serde_json::json!({
+        "line_count": line_count,
+        "word_count": word_count,
+        "char_count": content.len(),
+    });
+
+    let fallback = || Some(clean_filename(stem, &config.rules.sanitizer))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "document".to_string());
+
+    // Use text model for summarization
+    let client = OllamaClient::new(&config.ai_engine.url);
+    let prompt = format!(
+        "{}\n\nDocument content:\n{}",
+        config.prompts.document,
+        content_preview
+    );
+
+    let mut model = None;
+    let suggested_name = if !content.is_empty() {
+        match client.generate(&config.ai_engine.models.text, &prompt).await {
+            Ok(response) => {
+                model = Some(config.ai_engine.models.text.clone());
+                let name = clean_filename(&response, &config.rules.sanitizer);
+                if name.is_empty() || name.len() < 3 {
+                    // Fallback: use first line or file stem
+                    content.lines().next()
+                        .map(|line| clean_filename(line, &config.rules.sanitizer))
+                        .filter(|n| !n.is_empty())
+                        .unwrap_or_else(fallback)
+                } else {
+                    name
+                }
+            }
+            Err(e) => {
+                warn!("LLM failed: {}", e);
+                fallback()
+            }
+        }
+    } else {
+        fallback()
+    };
+
+    let category = infer_category(&suggested_name, extension);
+    let tags = extract_tags(&suggested_name, &metadata, &config.rules.sanitizer);
+
+    let confidence = if content.len() > 100 { 0.75 } else { 0.50 };
+
+    AnalysisResult {
+        suggested_name,
+        confidence,
+        category,
+        tags,
+        file_hash,
+        metadata,
+        analyzer: Some("document".to_string()),
+        model,
     }
 }
@@ -0,0 +1,582 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Ebook analyzer
+//!
+//! EPUBs are named `author_title` from their OPF package metadata, MOBI and
+//! AZW3 (Kindle) books from their EXTH header. A book without a title there
+//! is named by the text model from its first chapter: the first document of
+//! the EPUB's reading order, or section of the Kindle book's text, long
+//! enough not to be a cover, title page or copyright notice.
+
+use async_trait::async_trait;
+use quick_xml::events::Event;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, extract_tags};
+use crate::ollama::OllamaClient;
+use crate::{AppConfig, PanoptesError, Result};
+
+/// Characters of text a chapter has at least, so front matter is passed over
+const CHAPTER_MIN: usize = 500;
+
+/// Characters of the first chapter the text model is asked to name a book by
+const CHAPTER_EXCERPT: usize = 2000;
+
+/// Bytes of a Kindle book's text decompressed at most, looking for a chapter
+const MOBI_TEXT_MAX: usize = 256 * 1024;
+
+/// Analyzer for EPUB, MOBI and AZW3 ebooks
+pub struct EbookAnalyzer;
+
+/// What a book says of itself
+#[derive(Debug, Default)]
+struct BookInfo {
+    title: Option<String>,
+    authors: Vec<String>,
+    language: Option<String>,
+    publisher: Option<String>,
+    date: Option<String>,
+    isbn: Option<String>,
+    series: Option<String>,
+    series_index: Option<String>,
+    subjects: Vec<String>,
+}
+
+impl BookInfo {
+    /// The year of its publication date
+    fn year(&self) -> Option<&str> {
+        self.date.as_deref().and_then(|date| date.get(..4)).filter(|year| year.chars().all(|c| c.is_ascii_digit()))
+    }
+}
+
+/// An EPUB's package: its metadata, and its documents in reading order
+#[derive(Debug, Default)]
+struct Package {
+    info: BookInfo,
+    spine: Vec<String>,
+}
+
+/// Attributes of an element, by name without namespace prefix
+fn attributes(element: &quick_xml::events::BytesStart) -> HashMap<String, String> {
+    element.attributes()
+        .flatten()
+        .map(|attribute| (
+            String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned(),
+            quick_xml::escape::unescape(&String::from_utf8_lossy(&attribute.value))
+                .map(|v| v.into_owned())
+                .unwrap_or_default(),
+        ))
+        .collect()
+}
+
+fn xml_error(e: impl std::fmt::Display) -> PanoptesError {
+    PanoptesError::Analysis(format!("Unreadable ebook XML: {}", e))
+}
+
+impl Package {
+    /// Read an OPF package document, whose hrefs are relative to `base`
+    fn parse(xml: &str, base: &str) -> Result<Self> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        reader.trim_text(true);
+        let mut package = Self::default();
+        let mut manifest = HashMap::new();
+        let mut idrefs = Vec::new();
+        // The metadata element being read, and its attributes
+        let mut current: Option<(String, HashMap<String, String>)> = None;
+        loop {
+            let (element, empty) = match reader.read_event().map_err(xml_error)? {
+                Event::Start(element) => (element, false),
+                Event::Empty(element) => (element, true),
+                Event::Text(text) => {
+                    let Some((ref name, ref attrs)) = current else { continue };
+                    package.read_text(name, attrs, text.unescape().map_err(xml_error)?.trim());
+                    continue;
+                }
+                Event::End(_) => {
+                    current = None;
+                    continue;
+                }
+                Event::Eof => break,
+                _ => continue,
+            };
+            let attrs = attributes(&element);
+            match element.local_name().as_ref() {
+                b"item" => {
+                    if let (Some(id), Some(href)) = (attrs.get("id"), attrs.get("href")) {
+                        manifest.insert(id.clone(), (href.clone(), attrs.get("media-type").cloned().unwrap_or_default()));
+                    }
+                }
+                b"itemref" => idrefs.extend(attrs.get("idref").cloned()),
+                // Calibre's series, as `<meta name="calibre:series" content="..."/>`
+                b"meta" => match (attrs.get("name").map(String::as_str), attrs.get("content")) {
+                    (Some("calibre:series"), Some(content)) => package.info.series = Some(content.clone()),
+                    (Some("calibre:series_index"), Some(content)) => package.info.series_index = Some(content.clone()),
+                    _ => {}
+                },
+                _ => {}
+            }
+            if !empty {
+                current = Some((String::from_utf8_lossy(element.local_name().as_ref()).into_owned(), attrs));
+            }
+        }
+        package.spine = idrefs.iter()
+            .filter_map(|id| manifest.get(id))
+            .filter(|(_, media_type)| media_type.is_empty() || media_type.contains("html"))
+            .map(|(href, _)| resolve(base, href))
+            .collect();
+        Ok(package)
+    }
+
+    /// Take in `text`, that of the metadata element `name` with `attrs`
+    fn read_text(&mut self, name: &str, attrs: &HashMap<String, String>, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let info = &mut self.info;
+        let text = text.to_string();
+        match name {
+            "title" => {
+                info.title.get_or_insert(text);
+            }
+            // Authors rather than editors or illustrators, where the role is given
+            "creator" if attrs.get("role").map_or(true, |role| role == "aut") => info.authors.push(text),
+            "language" => {
+                info.language.get_or_insert(text);
+            }
+            "publisher" => info.publisher = Some(text),
+            "date" => {
+                info.date.get_or_insert(text);
+            }
+            "subject" => info.subjects.push(text),
+            "identifier" => {
+                let digits: String = text.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+                let digits = digits.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+                let is_isbn = attrs.get("scheme").is_some_and(|s| s.eq_ignore_ascii_case("isbn"))
+                    || text.to_lowercase().contains("isbn");
+                if is_isbn && matches!(digits.len(), 10 | 13) {
+                    info.isbn = Some(digits.to_string());
+                }
+            }
+            // EPUB 3's `<meta property="belongs-to-collection">`
+            "meta" if attrs.get("property").is_some_and(|p| p == "belongs-to-collection") => {
+                info.series.get_or_insert(text);
+            }
+            "meta" if attrs.get("property").is_some_and(|p| p == "group-position") => {
+                info.series_index.get_or_insert(text);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `href`, relative to the directory `base` in the archive, as an archive path
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = percent_decode(href);
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// `%20` and the like in an href decoded
+fn percent_decode(href: &str) -> String {
+    let bytes = href.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%').then(|| href.get(i + 1..i + 3)).flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The text of HTML markup, without its tags, scripts and styles
+pub(super) fn html_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len() / 2);
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let lower = rest.get(..8).unwrap_or(rest).to_ascii_lowercase();
+        let skip_to = if lower.starts_with("<script") {
+            Some("</script>")
+        } else if lower.starts_with("<style") {
+            Some("</style>")
+        } else {
+            None
+        };
+        let end = match skip_to {
+            Some(close) => rest.to_ascii_lowercase().find(close).map(|i| i + close.len()),
+            None => rest.find('>').map(|i| i + 1),
+        };
+        let Some(end) = end else {
+            rest = "";
+            break;
+        };
+        // Block elements part words
+        text.push(' ');
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    let text = text.replace("&nbsp;", " ").replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">")
+        .replace("&quot;", "\"").replace("&#39;", "'").replace("&apos;", "'");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The first of `sections` long enough to be a chapter, or else all of them
+fn first_chapter(sections: impl IntoIterator<Item = String>) -> String {
+    let mut all = String::new();
+    for section in sections {
+        if section.chars().count() >= CHAPTER_MIN {
+            return section;
+        }
+        if !section.is_empty() {
+            all.push_str(&section);
+            all.push(' ');
+        }
+    }
+    all.trim_end().to_string()
+}
+
+/// A Kindle book: its metadata, and the start of its text
+struct Mobi {
+    info: BookInfo,
+    text: String,
+}
+
+impl Mobi {
+    /// Read a MOBI or AZW3 file, a Palm database of records
+    fn parse(data: &[u8]) -> Result<Self> {
+        let invalid = |what: &str| PanoptesError::Analysis(format!("Not a Kindle book: {}", what));
+        if data.get(60..68) != Some(b"BOOKMOBI".as_slice()) {
+            return Err(invalid("no BOOKMOBI header"));
+        }
+        let u16_at = |at: usize| data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize);
+        let u32_at = |at: usize| data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+        let records = u16_at(76).ok_or_else(|| invalid("no record list"))?;
+        let offsets: Vec<usize> = (0..records).filter_map(|i| u32_at(78 + i * 8)).collect();
+        let record = |i: usize| -> Option<&[u8]> {
+            let start = *offsets.get(i)?;
+            let end = offsets.get(i + 1).copied().unwrap_or(data.len());
+            data.get(start..end.max(start))
+        };
+        let header = record(0).ok_or_else(|| invalid("no header record"))?;
+        let h16 = |at: usize| header.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize);
+        let h32 = |at: usize| header.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+        if header.get(16..20) != Some(b"MOBI".as_slice()) {
+            return Err(invalid("no MOBI header"));
+        }
+        let mobi_length = h32(20).unwrap_or_default();
+        let utf8 = h32(28) == Some(65001);
+        // Windows-1252 otherwise, near enough Latin-1 for naming
+        let decode = |bytes: &[u8]| if utf8 {
+            String::from_utf8_lossy(bytes).into_owned()
+        } else {
+            bytes.iter().map(|&b| b as char).collect()
+        };
+
+        let mut info = BookInfo::default();
+        if let (Some(offset), Some(length)) = (h32(84), h32(88)) {
+            info.title = header.get(offset..offset + length).map(decode).filter(|t| !t.trim().is_empty());
+        }
+        let has_exth = h32(128).is_some_and(|flags| flags & 0x40 != 0);
+        let exth = 16 + mobi_length;
+        if has_exth && header.get(exth..exth + 4) == Some(b"EXTH".as_slice()) {
+            let count = h32(exth + 8).unwrap_or_default();
+            let mut at = exth + 12;
+            for _ in 0..count {
+                let (Some(kind), Some(length)) = (h32(at), h32(at + 4)) else { break };
+                if length < 8 {
+                    break;
+                }
+                let value = header.get(at + 8..at + length).map(decode).unwrap_or_default().trim().to_string();
+                at += length;
+                if value.is_empty() {
+                    continue;
+                }
+                match kind {
+                    100 => info.authors.push(value),
+                    101 => info.publisher = Some(value),
+                    104 => info.isbn = Some(value.replace('-', "")),
+                    105 => info.subjects.push(value),
+                    106 => info.date = Some(value),
+                    503 => info.title = Some(value),
+                    524 => info.language = Some(value),
+                    _ => {}
+                }
+            }
+        }
+
+        let compression = h16(0).unwrap_or_default();
+        let text_records = h16(8).unwrap_or_default();
+        // Bytes at the end of each text record that aren't text, by the flags' bits
+        let trailing_flags = if mobi_length >= 0xE4 { h16(0xF2).unwrap_or_default() } else { 0 };
+        let mut raw = Vec::new();
+        if h16(12).unwrap_or_default() != 0 {
+            debug!("Kindle book text is encrypted");
+        } else if !matches!(compression, 1 | 2) {
+            debug!("Kindle book text compressed with HUFF/CDIC, not read");
+        } else {
+            for i in 1..=text_records {
+                let Some(record) = record(i) else { break };
+                let record = &record[..record.len().saturating_sub(trailing_size(record, trailing_flags))];
+                if compression == 2 {
+                    palmdoc_decompress(record, &mut raw);
+                } else {
+                    raw.extend_from_slice(record);
+                }
+                if raw.len() >= MOBI_TEXT_MAX {
+                    break;
+                }
+            }
+        }
+        Ok(Self { info, text: decode(&raw) })
+    }
+}
+
+/// Bytes of extra data at the end of a text record, as `flags` says there are
+fn trailing_size(record: &[u8], flags: usize) -> usize {
+    let mut size = 0;
+    for bit in 1..16 {
+        if flags & (1 << bit) != 0 {
+            // A size written backwards, 7 bits a byte, the first byte marked
+            let mut value = 0;
+            let mut shift = 0;
+            for &byte in record[..record.len().saturating_sub(size)].iter().rev().take(4) {
+                value |= ((byte & 0x7F) as usize) << shift;
+                shift += 7;
+                if byte & 0x80 != 0 {
+                    break;
+                }
+            }
+            size += value;
+        }
+    }
+    if flags & 1 != 0 {
+        if let Some(&byte) = record.len().checked_sub(size + 1).and_then(|i| record.get(i)) {
+            size += (byte & 0x3) as usize + 1;
+        }
+    }
+    size.min(record.len())
+}
+
+/// Decompress a PalmDOC record onto `out`
+fn palmdoc_decompress(data: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < data.len() {
+        let byte = data[i];
+        i += 1;
+        match byte {
+            1..=8 => {
+                let end = (i + byte as usize).min(data.len());
+                out.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            0x80..=0xBF => {
+                let Some(&next) = data.get(i) else { break };
+                i += 1;
+                let pair = ((byte as usize) << 8) | next as usize;
+                let distance = (pair >> 3) & 0x7FF;
+                let length = (pair & 0x7) + 3;
+                if distance == 0 || distance > out.len() {
+                    continue;
+                }
+                let start = out.len() - distance;
+                for j in 0..length {
+                    out.push(out[start + j]);
+                }
+            }
+            0xC0..=0xFF => {
+                out.push(b' ');
+                out.push(byte ^ 0x80);
+            }
+            _ => out.push(byte),
+        }
+    }
+}
+
+impl EbookAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The metadata and first chapter of an EPUB
+    fn read_epub(path: &Path) -> Result<(BookInfo, String)> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)
+            .map_err(|e| PanoptesError::Archive(format!("Failed to open {}: {}", path.display(), e)))?;
+        let mut read = |name: &str| -> Result<String> {
+            let mut entry = archive.by_name(name)
+                .map_err(|e| PanoptesError::Archive(format!("No {} in {}: {}", name, path.display(), e)))?;
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            Ok(String::from_utf8_lossy(&data).into_owned())
+        };
+
+        let container = read("META-INF/container.xml")?;
+        let mut opf_path = None;
+        let mut reader = quick_xml::Reader::from_str(&container);
+        loop {
+            match reader.read_event().map_err(xml_error)? {
+                Event::Start(element) | Event::Empty(element) if element.local_name().as_ref() == b"rootfile" => {
+                    opf_path = attributes(&element).remove("full-path");
+                    break;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        let opf_path = opf_path.ok_or_else(|| PanoptesError::Analysis(format!("No package document in {}", path.display())))?;
+        let base = opf_path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+        let package = Package::parse(&read(&opf_path)?, base)?;
+
+        let sections = package.spine.iter().filter_map(|document| match read(document) {
+            Ok(html) => Some(html_text(&html)),
+            Err(e) => {
+                debug!("{}", e);
+                None
+            }
+        });
+        let chapter = first_chapter(sections);
+        Ok((package.info, chapter))
+    }
+
+    /// The metadata and first chapter of a MOBI or AZW3 book
+    fn read_mobi(path: &Path) -> Result<(BookInfo, String)> {
+        let mobi = Mobi::parse(&std::fs::read(path)?)?;
+        let sections = mobi.text.split("<mbp:pagebreak")
+            .enumerate()
+            // Past the rest of the page break's tag
+            .map(|(i, section)| if i == 0 { section } else { section.split_once('>').map_or(section, |(_, rest)| rest) })
+            .map(html_text)
+            .collect::<Vec<_>>();
+        Ok((mobi.info, first_chapter(sections)))
+    }
+}
+
+impl Default for EbookAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FileAnalyzer for EbookAnalyzer {
+    fn name(&self) -> &'static str {
+        "ebook"
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &["epub", "mobi", "azw3"]
+    }
+
+    fn priority(&self) -> u8 {
+        60
+    }
+
+    async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResult> {
+        info!("Analyzing ebook: {:?}", path);
+
+        let file_hash = calculate_file_hash(path)?;
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        let read = if extension == "epub" { Self::read_epub(path) } else { Self::read_mobi(path) };
+        let (info, chapter) = read.unwrap_or_else(|e| {
+            warn!("Unreadable ebook {:?}: {}", path, e);
+            (BookInfo::default(), String::new())
+        });
+
+        debug!("First chapter of {:?}: {}...", path, chapter.chars().take(60).collect::<String>());
+
+        // The book's metadata first, then its first chapter
+        let mut model = None;
+        let mut confidence = 0.5;
+        let mut suggested_name = match (&info.title, info.authors.first()) {
+            (Some(title), Some(author)) => {
+                confidence = 0.9;
+                clean_filename(&format!("{} {}", author, title), &config.rules.sanitizer)
+            }
+            (Some(title), None) => {
+                confidence = 0.8;
+                clean_filename(title, &config.rules.sanitizer)
+            }
+            _ => String::new(),
+        };
+        if suggested_name.is_empty() && config.analyzers.ebook.read_text && !chapter.is_empty() {
+            let excerpt = match chapter.char_indices().nth(CHAPTER_EXCERPT) {
+                Some((end, _)) => &chapter[..end],
+                None => &chapter,
+            };
+            let prompt = format!("{}\n\nFirst chapter:\n{}", config.prompts.ebook, excerpt);
+            let client = OllamaClient::new(&config.ai_engine.url);
+            match client.generate(&config.ai_engine.models.text, &prompt).await {
+                Ok(text) => {
+                    model = Some(config.ai_engine.models.text.clone());
+                    confidence = 0.7;
+                    suggested_name = clean_filename(&text, &config.rules.sanitizer);
+                }
+                Err(e) => warn!("Text model failed on {:?}: {}", path, e),
+            }
+        }
+        if suggested_name.is_empty() {
+            confidence = 0.5;
+            suggested_name = clean_filename(&path.file_stem().unwrap_or_default().to_string_lossy(), &config.rules.sanitizer);
+        }
+
+        let metadata = serde_json::json!({
+            "title": info.title,
+            "author": info.authors.first(),
+            "authors": info.authors,
+            "language": info.language,
+            "publisher": info.publisher,
+            "year": info.year(),
+            "isbn": info.isbn,
+            "series": info.series,
+            "series_index": info.series_index,
+            "format": extension,
+        });
+
+        let mut tags = vec!["ebook".to_string()];
+        tags.extend(info.language.iter().cloned());
+        tags.extend(info.series.iter().cloned());
+        tags.extend(info.subjects.iter().cloned());
+        tags.extend(extract_tags(&suggested_name, &metadata, &config.rules.sanitizer));
+        tags.sort();
+        tags.dedup();
+
+        Ok(AnalysisResult {
+            suggested_name,
+            confidence,
+            category: Some("Books".to_string()),
+            tags,
+            file_hash,
+            metadata,
+            analyzer: None,
+            model,
+        })
+    }
+}
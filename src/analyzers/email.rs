@@ -0,0 +1,703 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Email analyzer
+//!
+//! Saved emails, as MIME messages (`.eml`) or Outlook items (`.msg`), are
+//! named `date_subject_from_sender`, like `2025-03-02_invoice_from_acme`:
+//! the day it was sent, the subject without its `Re:` and `Fwd:`, and the
+//! sender's organization, from the domain of their address, or their name
+//! when they write from a webmail domain. An email without a subject is
+//! named by the text model from its body, its plain text part or else its
+//! HTML one. Tags give the sender's domain and the types of attachments.
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use super::ebook::html_text;
+use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, extract_tags};
+use crate::ollama::OllamaClient;
+use crate::{AppConfig, PanoptesError, Result};
+
+/// Characters of the body the text model is asked to name an email by
+const BODY_EXCERPT: usize = 2000;
+
+/// Words of a subject kept, so the sender fits in the name too
+const SUBJECT_WORDS: usize = 6;
+
+/// Domains anyone can write from, so saying nothing of who the sender is
+const WEBMAIL: &[&str] = &[
+    "gmail.com", "googlemail.com", "outlook.com", "hotmail.com", "live.com", "msn.com", "yahoo.com",
+    "icloud.com", "me.com", "mac.com", "aol.com", "proton.me", "protonmail.com", "gmx.com", "gmx.de",
+    "gmx.net", "web.de", "mail.com", "yandex.com", "yandex.ru", "zoho.com", "fastmail.com",
+];
+
+/// Analyzer for saved emails
+pub struct EmailAnalyzer;
+
+/// Who sent an email
+#[derive(Debug, Default)]
+struct Mailbox {
+    name: Option<String>,
+    address: Option<String>,
+}
+
+impl Mailbox {
+    /// `Jane Doe <jane@example.com>`, `jane@example.com` or `"Doe, Jane" <jane@example.com>`
+    fn parse(value: &str) -> Self {
+        let value = value.trim();
+        match (value.rfind('<'), value.rfind('>')) {
+            (Some(start), Some(end)) if start < end => Self {
+                name: Some(value[..start].trim().trim_matches('"').trim().to_string()).filter(|n| !n.is_empty()),
+                address: Some(value[start + 1..end].trim().to_string()).filter(|a| a.contains('@')),
+            },
+            _ if value.contains('@') => Self {
+                name: None,
+                address: Some(value.split_whitespace().next().unwrap_or(value).to_string()),
+            },
+            _ => Self { name: Some(value.to_string()).filter(|n| !n.is_empty()), address: None },
+        }
+    }
+
+    fn domain(&self) -> Option<String> {
+        self.address.as_deref()
+            .and_then(|address| address.rsplit_once('@'))
+            .map(|(_, domain)| domain.trim_end_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty())
+    }
+
+    /// Who the sender is, in a word or two: their organization, from their
+    /// address's domain, or else their name
+    fn label(&self) -> Option<String> {
+        match self.domain() {
+            Some(domain) if !WEBMAIL.contains(&domain.as_str()) => Some(organization(&domain)),
+            _ => self.name.clone().or_else(|| {
+                self.address.as_deref().and_then(|a| a.split('@').next()).map(String::from)
+            }),
+        }
+    }
+}
+
+/// The organization a domain names: `acme` for `billing.acme.co.uk`
+fn organization(domain: &str) -> String {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let n = labels.len();
+    // Second-level registries, as in `co.uk` or `com.au`
+    let registry = n >= 3 && labels[n - 1].len() == 2
+        && ["co", "com", "org", "net", "ac", "gov", "edu", "ne", "or"].contains(&labels[n - 2]);
+    let at = if registry { n - 3 } else { n.saturating_sub(2) };
+    labels.get(at).unwrap_or(&domain).to_string()
+}
+
+/// What an email says of itself
+#[derive(Debug, Default)]
+struct Message {
+    from: Mailbox,
+    to: Vec<String>,
+    subject: Option<String>,
+    date: Option<DateTime<FixedOffset>>,
+    /// Plain text, or the text of the HTML
+    body: String,
+    html: bool,
+    attachments: Vec<String>,
+}
+
+impl Message {
+    /// Take in the headers `headers` of the message (or of an Outlook item's
+    /// transport headers)
+    fn read_headers(&mut self, headers: &[(String, String)]) {
+        for (name, value) in headers {
+            match name.to_ascii_lowercase().as_str() {
+                "from" if self.from.address.is_none() && self.from.name.is_none() => self.from = Mailbox::parse(value),
+                "to" if self.to.is_empty() => {
+                    self.to = split_addresses(value).into_iter().filter_map(|a| Mailbox::parse(&a).address).collect();
+                }
+                "subject" if self.subject.is_none() => self.subject = Some(value.trim().to_string()).filter(|s| !s.is_empty()),
+                "date" if self.date.is_none() => self.date = parse_date(value),
+                _ => {}
+            }
+        }
+    }
+
+    /// The subject without the `Re:`, `Fwd:` and the like of replies and forwards
+    fn topic(&self) -> Option<String> {
+        let mut subject = self.subject.as_deref()?.trim();
+        loop {
+            let lower = subject.to_lowercase();
+            let prefix = ["re:", "fw:", "fwd:", "aw:", "wg:", "sv:", "vs:", "tr:", "r:", "antw:", "[external]"]
+                .iter()
+                .find(|prefix| lower.starts_with(*prefix));
+            match prefix {
+                Some(prefix) => subject = subject[prefix.len()..].trim_start(),
+                None => break,
+            }
+        }
+        let words: Vec<&str> = subject.split_whitespace().take(SUBJECT_WORDS).collect();
+        Some(words.join(" ")).filter(|s| !s.is_empty())
+    }
+}
+
+/// The addresses of a header listing several, split at commas outside quotes
+fn split_addresses(value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let (mut current, mut quoted, mut angled) = (String::new(), false, false);
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '<' => angled = true,
+            '>' => angled = false,
+            ',' if !quoted && !angled => {
+                addresses.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    addresses.push(current);
+    addresses.into_iter().map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect()
+}
+
+/// A `Date:` header, without the comments some mailers add
+fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = match value.find('(') {
+        Some(comment) => &value[..comment],
+        None => value,
+    };
+    DateTime::parse_from_rfc2822(value.trim()).ok()
+}
+
+/// `bytes` in the character set `charset`, UTF-8 when unknown
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    match charset.and_then(|label| encoding_rs::Encoding::for_label(label.trim().as_bytes())) {
+        Some(encoding) => encoding.decode(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Quoted-printable text decoded; with `underscores`, as in headers, `_` is a space
+fn decode_quoted_printable(text: &[u8], underscores: bool) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            b'=' => {
+                let hex = text.get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                    }
+                    // A soft line break
+                    None if text.get(i + 1) == Some(&b'\r') => i += 3,
+                    None if text.get(i + 1) == Some(&b'\n') => i += 2,
+                    None => {
+                        decoded.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if underscores => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn decode_base64(text: &[u8]) -> Vec<u8> {
+    let text: Vec<u8> = text.iter().copied().filter(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/')).collect();
+    general_purpose::STANDARD_NO_PAD.decode(&text).unwrap_or_default()
+}
+
+/// A header with its `=?charset?B?...?=` encoded words decoded
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word = rest[start + 2..].splitn(3, '?').collect::<Vec<_>>();
+        let text = match word.as_slice() {
+            [charset, encoding, text] => text.find("?=").map(|end| (*charset, *encoding, &text[..end])),
+            _ => None,
+        };
+        let Some((charset, encoding, text)) = text else { break };
+        let between = &rest[..start];
+        // Whitespace between encoded words goes
+        if !(after_word && between.trim().is_empty()) {
+            decoded.push_str(between);
+        }
+        let bytes = match encoding {
+            "B" | "b" => decode_base64(text.as_bytes()),
+            _ => decode_quoted_printable(text.as_bytes(), true),
+        };
+        // RFC 2231 adds a language, as in `utf-8*en`
+        decoded.push_str(&decode_charset(&bytes, charset.split('*').next()));
+        let length = 2 + charset.len() + 1 + encoding.len() + 1 + text.len() + 2;
+        rest = &rest[start + length..];
+        after_word = true;
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The headers of a MIME entity, unfolded and decoded, and its body
+fn split_entity(raw: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let end = raw.windows(4).position(|w| w == b"\r\n\r\n").map(|i| (i, i + 4))
+        .into_iter()
+        .chain(raw.windows(2).position(|w| w == b"\n\n").map(|i| (i, i + 2)))
+        .min_by_key(|(at, _)| *at);
+    let (head, body) = match end {
+        Some((at, body)) => (&raw[..at], &raw[body..]),
+        None => (raw, &raw[raw.len()..]),
+    };
+    let mut headers: Vec<(String, String)> = Vec::new();
+    // Raw 8-bit headers are usually UTF-8
+    for line in String::from_utf8_lossy(head).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    for (_, value) in headers.iter_mut() {
+        *value = decode_words(value);
+    }
+    (headers, body)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+/// The parameter `name` of a header like `Content-Type`, also as RFC 2231's
+/// `name*=utf-8''...`
+fn parameter(value: &str, name: &str) -> Option<String> {
+    for part in value.split(';').skip(1) {
+        let Some((key, raw)) = part.split_once('=') else { continue };
+        let key = key.trim().to_ascii_lowercase();
+        let raw = raw.trim().trim_matches('"');
+        if key == name {
+            return Some(raw.to_string());
+        }
+        if key == format!("{}*", name) {
+            let mut pieces = raw.splitn(3, '\'');
+            let (charset, _, text) = (pieces.next(), pieces.next(), pieces.next());
+            let text = text.unwrap_or(raw);
+            let bytes = decode_quoted_printable(text.replace('%', "=").as_bytes(), false);
+            return Some(decode_charset(&bytes, charset.filter(|_| text != raw)));
+        }
+    }
+    None
+}
+
+/// Take in a MIME entity: the message's text, and the names of its attachments
+fn read_entity(raw: &[u8], message: &mut Message, depth: usize) {
+    let (headers, body) = split_entity(raw);
+    let content_type = header(&headers, "Content-Type").unwrap_or("text/plain");
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    let disposition = header(&headers, "Content-Disposition").unwrap_or_default();
+    let filename = parameter(disposition, "filename").or_else(|| parameter(content_type, "name"));
+
+    if mime.starts_with("multipart/") && depth < 8 {
+        let Some(boundary) = parameter(content_type, "boundary") else { return };
+        let delimiter = format!("--{}", boundary);
+        let text = body;
+        let mut parts = Vec::new();
+        let mut start = None;
+        let mut at = 0;
+        for line in text.split_inclusive(|&b| b == b'\n') {
+            let trimmed = line.strip_suffix(b"\n").unwrap_or(line);
+            let trimmed = trimmed.strip_suffix(b"\r").unwrap_or(trimmed);
+            if trimmed.starts_with(delimiter.as_bytes()) {
+                if let Some(start) = start {
+                    parts.push(&text[start..at]);
+                }
+                let closing = trimmed[delimiter.len()..].starts_with(b"--");
+                start = (!closing).then_some(at + line.len());
+                if closing {
+                    break;
+                }
+            }
+            at += line.len();
+        }
+        for part in parts {
+            read_entity(part, message, depth + 1);
+        }
+        return;
+    }
+    let attached = disposition.trim().to_ascii_lowercase().starts_with("attachment")
+        || (filename.is_some() && !mime.starts_with("text/"));
+    if attached || mime == "message/rfc822" {
+        message.attachments.push(filename.unwrap_or_else(|| if mime == "message/rfc822" { "message.eml" } else { "attachment" }.to_string()));
+        return;
+    }
+    if !mime.starts_with("text/") || (!message.body.is_empty() && (!message.html || mime != "text/plain")) {
+        return;
+    }
+    let decoded = match header(&headers, "Content-Transfer-Encoding").map(|e| e.trim().to_ascii_lowercase()) {
+        Some(e) if e == "base64" => decode_base64(body),
+        Some(e) if e == "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    };
+    let text = decode_charset(&decoded, parameter(content_type, "charset").as_deref());
+    message.html = mime == "text/html";
+    message.body = if message.html { html_text(&text) } else { text.trim().to_string() };
+}
+
+/// Read a MIME message
+fn parse_eml(raw: &[u8]) -> Message {
+    let mut message = Message::default();
+    let (headers, _) = split_entity(raw);
+    message.read_headers(&headers);
+    read_entity(raw, &mut message, 0);
+    message
+}
+
+/// An Outlook item: a compound file (the OLE format of old Office documents)
+/// holding the item's properties as streams
+struct Compound {
+    data: Vec<u8>,
+    sector_size: usize,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    entries: Vec<DirEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct DirEntry {
+    name: String,
+    left: u32,
+    right: u32,
+    child: u32,
+    start: u32,
+    size: usize,
+}
+
+/// Marks the end of a chain of sectors, or no directory entry
+const END: u32 = 0xFFFF_FFFE;
+const NONE: u32 = 0xFFFF_FFFF;
+
+impl Compound {
+    fn parse(data: Vec<u8>) -> Result<Self> {
+        let invalid = |what: &str| PanoptesError::Analysis(format!("Not an Outlook item: {}", what));
+        if data.get(..8) != Some(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+            return Err(invalid("no compound file signature"));
+        }
+        let u32_at = |data: &[u8], at: usize| data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        let shift = data.get(0x1E).copied().unwrap_or(9);
+        if !(7..=16).contains(&shift) {
+            return Err(invalid("bad sector size"));
+        }
+        let sector_size = 1usize << shift;
+        let mut compound = Self { data, sector_size, fat: Vec::new(), mini_fat: Vec::new(), mini_stream: Vec::new(), entries: Vec::new() };
+        let header = |at: usize| u32_at(&compound.data, at).unwrap_or(END);
+
+        // The sectors of the FAT: 109 listed in the header, the rest in a chain of DIFAT sectors
+        let mut fat_sectors: Vec<u32> = (0..109).map(|i| header(0x4C + i * 4)).filter(|&s| s < END - 4).collect();
+        let mut difat = header(0x44);
+        let per_sector = sector_size / 4;
+        let mut seen = 0;
+        while difat < END - 4 && seen < 1 << 16 {
+            let Some(sector) = compound.sector(difat) else { break };
+            fat_sectors.extend((0..per_sector - 1).filter_map(|i| u32_at(sector, i * 4)).filter(|&s| s < END - 4));
+            difat = u32_at(sector, (per_sector - 1) * 4).unwrap_or(END);
+            seen += 1;
+        }
+        let fat: Vec<u32> = fat_sectors.iter()
+            .filter_map(|&s| compound.sector(s))
+            .flat_map(|sector| (0..per_sector).filter_map(move |i| u32_at(sector, i * 4)))
+            .collect();
+        compound.fat = fat;
+
+        let directory = compound.chain(header(0x30));
+        compound.entries = directory.chunks_exact(128).map(|entry| {
+            let length = (u16::from_le_bytes([entry[64], entry[65]]) as usize).min(64);
+            let name: Vec<u16> = entry[..length].chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            DirEntry {
+                name: String::from_utf16_lossy(&name).trim_end_matches('\0').to_string(),
+                left: u32_at(entry, 68).unwrap_or(NONE),
+                right: u32_at(entry, 72).unwrap_or(NONE),
+                child: u32_at(entry, 76).unwrap_or(NONE),
+                start: u32_at(entry, 116).unwrap_or(END),
+                size: u32_at(entry, 120).unwrap_or_default() as usize,
+            }
+        }).collect();
+        let root = compound.entries.first().cloned().ok_or_else(|| invalid("no directory"))?;
+        let mini_fat = compound.chain(header(0x3C));
+        compound.mini_fat = mini_fat.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        let mut mini_stream = compound.chain(root.start);
+        mini_stream.truncate(root.size);
+        compound.mini_stream = mini_stream;
+        Ok(compound)
+    }
+
+    fn sector(&self, n: u32) -> Option<&[u8]> {
+        let start = (n as usize + 1) * self.sector_size;
+        self.data.get(start..start + self.sector_size)
+    }
+
+    /// The bytes of the chain of sectors starting at `start`
+    fn chain(&self, mut sector: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut steps = 0;
+        while sector < END - 4 && steps <= self.fat.len() {
+            let Some(data) = self.sector(sector) else { break };
+            bytes.extend_from_slice(data);
+            sector = self.fat.get(sector as usize).copied().unwrap_or(END);
+            steps += 1;
+        }
+        bytes
+    }
+
+    /// The contents of the stream `entry`
+    fn stream(&self, entry: &DirEntry) -> Vec<u8> {
+        let mut bytes = if entry.size < 4096 {
+            let mut bytes = Vec::new();
+            let (mut sector, mut steps) = (entry.start, 0);
+            while sector < END - 4 && steps <= self.mini_fat.len() {
+                let start = sector as usize * 64;
+                let Some(data) = self.mini_stream.get(start..start + 64) else { break };
+                bytes.extend_from_slice(data);
+                sector = self.mini_fat.get(sector as usize).copied().unwrap_or(END);
+                steps += 1;
+            }
+            bytes
+        } else {
+            self.chain(entry.start)
+        };
+        bytes.truncate(entry.size);
+        bytes
+    }
+
+    /// The entries in the storage `storage`, by index
+    fn children(&self, storage: usize) -> Vec<usize> {
+        let mut children = Vec::new();
+        let mut stack = vec![self.entries.get(storage).map_or(NONE, |e| e.child)];
+        while let Some(i) = stack.pop() {
+            let Some(entry) = self.entries.get(i as usize).filter(|_| i != NONE) else { continue };
+            if children.len() > self.entries.len() {
+                break;
+            }
+            children.push(i as usize);
+            stack.extend([entry.left, entry.right]);
+        }
+        children
+    }
+
+    /// The string property `id` (as `0037` for the subject) in `storage`,
+    /// stored as UTF-16 or in the item's 8-bit code page
+    fn string(&self, storage: usize, id: &str) -> Option<String> {
+        let children = self.children(storage);
+        let find = |kind: &str| children.iter()
+            .map(|&i| &self.entries[i])
+            .find(|e| e.name.eq_ignore_ascii_case(&format!("__substg1.0_{}{}", id, kind)));
+        let text = match (find("001F"), find("001E")) {
+            (Some(entry), _) => {
+                let units: Vec<u16> = self.stream(entry).chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+                String::from_utf16_lossy(&units)
+            }
+            (None, Some(entry)) => decode_charset(&self.stream(entry), Some("windows-1252")),
+            (None, None) => return None,
+        };
+        Some(text.trim_end_matches('\0').trim().to_string()).filter(|t| !t.is_empty())
+    }
+
+    /// The time property `id` (as `0039` for when it was sent) in the root's properties
+    fn time(&self, id: u16) -> Option<DateTime<Utc>> {
+        let properties = self.children(0).into_iter()
+            .map(|i| &self.entries[i])
+            .find(|e| e.name == "__properties_version1.0")?;
+        let data = self.stream(properties);
+        // A 32-byte header, then 16 bytes a property: type, id, flags, value
+        data.get(32..)?.chunks_exact(16).find_map(|property| {
+            let kind = u16::from_le_bytes([property[0], property[1]]);
+            let property_id = u16::from_le_bytes([property[2], property[3]]);
+            if kind != 0x0040 || property_id != id {
+                return None;
+            }
+            let filetime = u64::from_le_bytes(property[8..16].try_into().ok()?);
+            // 100 ns intervals since 1601
+            let secs = (filetime / 10_000_000) as i64 - 11_644_473_600;
+            Utc.timestamp_opt(secs, 0).single()
+        })
+    }
+}
+
+/// Read an Outlook item
+fn parse_msg(data: Vec<u8>) -> Result<Message> {
+    let item = Compound::parse(data)?;
+    let mut message = Message::default();
+    // The headers it came with, where it was received rather than drafted
+    if let Some(headers) = item.string(0, "007D") {
+        let (headers, _) = split_entity(format!("{}\r\n\r\n", headers.trim()).as_bytes());
+        message.read_headers(&headers);
+    }
+    message.subject = message.subject.take().or_else(|| item.string(0, "0037"));
+    if message.from.address.is_none() {
+        message.from = Mailbox {
+            name: item.string(0, "0C1A"),
+            // The SMTP address rather than an Exchange one
+            address: item.string(0, "5D01").or_else(|| item.string(0, "0C1F")).filter(|a| a.contains('@')),
+        };
+    }
+    if message.date.is_none() {
+        message.date = item.time(0x0039).or_else(|| item.time(0x0E06)).map(|date| date.fixed_offset());
+    }
+    match item.string(0, "1000") {
+        Some(body) => message.body = body,
+        None => {
+            let html = item.children(0).into_iter()
+                .map(|i| &item.entries[i])
+                .find(|e| e.name.eq_ignore_ascii_case("__substg1.0_10130102"))
+                .map(|entry| item.stream(entry));
+            if let Some(html) = html {
+                message.body = html_text(&String::from_utf8_lossy(&html));
+                message.html = true;
+            }
+        }
+    }
+    for i in item.children(0) {
+        if item.entries[i].name.starts_with("__attach_version1.0_") {
+            message.attachments.push(item.string(i, "3707").or_else(|| item.string(i, "3704")).unwrap_or_else(|| "attachment".to_string()));
+        }
+    }
+    Ok(message)
+}
+
+impl EmailAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for EmailAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FileAnalyzer for EmailAnalyzer {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &["eml", "msg"]
+    }
+
+    fn priority(&self) -> u8 {
+        60
+    }
+
+    async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResult> {
+        info!("Analyzing email: {:?}", path);
+
+        let file_hash = calculate_file_hash(path)?;
+        let data = std::fs::read(path)?;
+        let is_msg = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("msg"));
+        let message = if is_msg {
+            parse_msg(data).unwrap_or_else(|e| {
+                warn!("Unreadable Outlook item {:?}: {}", path, e);
+                Message::default()
+            })
+        } else {
+            parse_eml(&data)
+        };
+        debug!("Email from {:?}, {} attachment(s)", message.from, message.attachments.len());
+
+        // Colons would be taken for a chat prefix to drop
+        let part = |text: &str| text.replace(':', " ");
+        let sender = message.from.label().map(|label| format!("from {}", part(&label))).unwrap_or_default();
+        let date = message.date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default();
+
+        // The subject first, then the body
+        let mut model = None;
+        let mut confidence = 0.5;
+        let mut topic = message.topic().map(|topic| part(&topic));
+        if topic.is_some() {
+            confidence = if message.date.is_some() && !sender.is_empty() { 0.9 } else { 0.8 };
+        } else if config.analyzers.email.read_body && !message.body.trim().is_empty() {
+            let excerpt = match message.body.char_indices().nth(BODY_EXCERPT) {
+                Some((end, _)) => &message.body[..end],
+                None => &message.body,
+            };
+            let prompt = format!("{}\n\nEmail:\n{}", config.prompts.email, excerpt);
+            let client = OllamaClient::new(&config.ai_engine.url);
+            match client.generate(&config.ai_engine.models.text, &prompt).await {
+                Ok(text) => {
+                    model = Some(config.ai_engine.models.text.clone());
+                    confidence = 0.7;
+                    topic = Some(part(&text)).filter(|t| !clean_filename(t, &config.rules.sanitizer).is_empty());
+                }
+                Err(e) => warn!("Text model failed on {:?}: {}", path, e),
+            }
+        }
+        let mut suggested_name = match topic {
+            Some(topic) => clean_filename(&format!("{} {} {}", date, topic, sender), &config.rules.sanitizer),
+            None => String::new(),
+        };
+        if suggested_name.is_empty() {
+            confidence = 0.5;
+            suggested_name = clean_filename(&path.file_stem().unwrap_or_default().to_string_lossy(), &config.rules.sanitizer);
+        }
+
+        let attachment_types: Vec<String> = {
+            let mut types: Vec<String> = message.attachments.iter()
+                .filter_map(|name| Path::new(name).extension())
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .collect();
+            types.sort();
+            types.dedup();
+            types
+        };
+        let metadata = serde_json::json!({
+            "from": message.from.name,
+            "from_address": message.from.address,
+            "sender_domain": message.from.domain(),
+            "to": message.to,
+            "subject": message.subject,
+            "date": message.date.map(|date| date.to_rfc3339()),
+            "attachments": message.attachments,
+            "attachment_types": attachment_types,
+            "html": message.html,
+            "word_count": message.body.split_whitespace().count(),
+        });
+
+        let mut tags = vec!["email".to_string()];
+        tags.extend(message.from.domain());
+        tags.extend(attachment_types.iter().cloned());
+        if !message.attachments.is_empty() {
+            tags.push("attachment".to_string());
+        }
+        tags.extend(extract_tags(&suggested_name, &metadata, &config.rules.sanitizer));
+        tags.sort();
+        tags.dedup();
+
+        Ok(AnalysisResult {
+            suggested_name,
+            confidence,
+            category: Some("Email".to_string()),
+            tags,
+            file_hash,
+            metadata,
+            analyzer: None,
+            model,
+        })
+    }
+}
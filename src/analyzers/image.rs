@@ -2,17 +2,86 @@
 // SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
 
 //! Image file analyzer using vision models
+//!
+//! With `analyzers.image.ocr`, the text in an image is read first, by
+//! Tesseract or the vision model (`analyzers.image.ocr_engine`). Screenshots,
+//! receipts and whiteboards with enough of it are named by the text model
+//! from what they say (`prompts.image_text`) rather than how they look; the
+//! text goes into the metadata as `ocr_text` either way.
+//!
+//! HEIC, HEIF and AVIF images, which the image crate can't decode, are
+//! converted to PNG by libheif's `heif-convert`, or FFmpeg without it, and
+//! go on from there like any other.
 
 use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine as _};
-use image::GenericImageView;
-use std::path::Path;
+use image::{DynamicImage, GenericImageView};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
 use tracing::{debug, info, warn};
 
 use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, infer_category, extract_tags};
+use crate::config::{ImageAnalyzerConfig, OcrEngine};
 use crate::{AppConfig, Result, PanoptesError};
 use crate::ollama::OllamaClient;
 
+/// Fewest words read from an image for it to be named from its text
+const MIN_OCR_WORDS: usize = 4;
+
+/// Most characters of an image's text shown to the text model and kept in its metadata
+const OCR_EXCERPT: usize = 2000;
+
+/// Extensions of images converted before decoding
+const CONVERTED: &[&str] = &["heic", "heif", "avif"];
+
+/// Whether the image at `path` is converted before it can be decoded
+pub fn needs_conversion(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str())
+        .is_some_and(|ext| CONVERTED.iter().any(|c| ext.eq_ignore_ascii_case(c)))
+}
+
+/// Decode the image at `path`, converting it first if it needs it
+pub fn open(path: &Path) -> Result<DynamicImage> {
+    if !needs_conversion(path) {
+        return Ok(image::open(path)?);
+    }
+    let png = Converted::new(path)?;
+    Ok(image::open(&png.0)?)
+}
+
+/// A PNG copy of an image the image crate can't decode, removed when dropped
+struct Converted(PathBuf);
+
+impl Converted {
+    fn new(path: &Path) -> Result<Self> {
+        let png = Self(std::env::temp_dir().join(format!("panoptes-decode-{}.png", uuid::Uuid::new_v4().simple())));
+        let mut failures = Vec::new();
+        for program in ["heif-convert", "ffmpeg"] {
+            let mut command = std::process::Command::new(program);
+            if program == "ffmpeg" {
+                command.args(["-v", "error", "-y", "-i"]).arg(path).args(["-frames:v", "1"]).arg(&png.0);
+            } else {
+                command.arg(path).arg(&png.0);
+            }
+            match command.output() {
+                Ok(output) if output.status.success() && png.0.is_file() => {
+                    debug!("Converted {:?} with {}", path, program);
+                    return Ok(png);
+                }
+                Ok(output) => failures.push(format!("{}: {}", program, String::from_utf8_lossy(&output.stderr).trim())),
+                Err(e) => failures.push(format!("{}: {}", program, e)),
+            }
+        }
+        Err(PanoptesError::Analysis(format!("Cannot convert {}: {}", path.display(), failures.join("; "))))
+    }
+}
+
+impl Drop for Converted {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
 /// Analyzer for image files
 pub struct ImageAnalyzer;
 
@@ -28,14 +97,12 @@ fn encode_image(path: &Path) -> Result<String> {
     }
 
     /// Resize large images for faster processing
-    fn prepare_image(path: &Path) -> Result<Vec<u8>> {
-        let img = image::open(path)?;
-
+    fn prepare_image(img: &DynamicImage) -> Result<Vec<u8>> {
         // Resize if too large (max 1024px on longest side)
         let img = if img.width() > 1024 || img.height() > 1024 {
             img.resize(1024, 1024, image::imageops::FilterType::Triangle)
         } else {
-            img
+            img.clone()
         };
 
         // Convert to JPEG for consistent encoding
@@ -45,6 +112,48 @@ fn prepare_image(path: &Path) -> Result<Vec<u8>> {
 
         Ok(buffer)
     }
+
+    /// The text Tesseract reads in the image at `path`
+    async fn tesseract(path: &Path, config: &ImageAnalyzerConfig) -> Result<String> {
+        let converted = if needs_conversion(path) { Some(Converted::new(path)?) } else { None };
+        let mut command = Command::new(&config.tesseract_command);
+        command.arg(converted.as_ref().map_or(path, |png| png.0.as_path())).arg("stdout");
+        if let Some(language) = &config.ocr_language {
+            command.arg("-l").arg(language);
+        }
+        let output = command.output().await
+            .map_err(|e| PanoptesError::Analysis(format!("Failed to run {}: {}", config.tesseract_command, e)))?;
+        if !output.status.success() {
+            return Err(PanoptesError::Analysis(format!(
+                "{} failed: {}", config.tesseract_command, String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// The text in the image at `path` (encoded as `image_data`), trimmed to
+    /// [`OCR_EXCERPT`] characters; None when it couldn't be read
+    async fn read_text(path: &Path, image_data: &str, client: &OllamaClient, config: &AppConfig) -> Option<String> {
+        let image = &config.analyzers.image;
+        let text = match image.ocr_engine {
+            OcrEngine::Tesseract => Self::tesseract(path, image).await,
+            OcrEngine::Vision => client.generate_with_image(&config.ai_engine.models.vision, &config.prompts.ocr, image_data).await,
+        };
+        match text {
+            Ok(text) => {
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                debug!("Read {} words in {:?}", text.split_whitespace().count(), path);
+                Some(match text.char_indices().nth(OCR_EXCERPT) {
+                    Some((end, _)) => text[..end].to_string(),
+                    None => text,
+                })
+            }
+            Err(e) => {
+                warn!("Failed to read the text in {:?}: {}", path, e);
+                None
+            }
+        }
+    }
 }
 
 impl Default for ImageAnalyzer {
@@ -74,50 +183,80 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
         let file_hash = calculate_file_hash(path)?;
 
         // Get image metadata
-        let img = image::open(path)?;
+        let img = open(path)?;
         let (width, height) = img.dimensions();
         let format = image::ImageFormat::from_path(path)
             .map(|f| format!("{:?}", f))
             .unwrap_or_else(|_| "unknown".to_string());
 
         // Prepare image for API (resize if needed)
-        let image_data = match Self::prepare_image(path) {
+        let image_data = match Self::prepare_image(&img) {
             Ok(data) => general_purpose::STANDARD.encode(&data),
             Err(_) => Self::encode_image(path)?, // Fallback to raw
         };
 
-        // Call vision model
         let client = OllamaClient::new(&config.ai_engine.url);
-        let response = client
-            .generate_with_image(
-                &config.ai_engine.models.vision,
-                &config.prompts.image,
-                &image_data,
-            )
-            .await;
-
-        let suggested_name = match response {
-            Ok(text) => clean_filename(&text),
-            Err(e) => {
-                warn!("Vision model failed: {}, using fallback", e);
-                // Fallback: use dimensions as name
-                format!("image_{}x{}", width, height)
+        let ocr_text = if config.analyzers.image.ocr {
+            Self::read_text(path, &image_data, &client, config).await
+        } else {
+            None
+        };
+
+        // Images with enough text are named from it
+        let mut named = None;
+        if let Some(text) = ocr_text.as_ref().filter(|text| text.split_whitespace().count() >= MIN_OCR_WORDS) {
+            let prompt = format!("{}\n\nText:\n{}", config.prompts.image_text, text);
+            match client.generate(&config.ai_engine.models.text, &prompt).await {
+                Ok(answer) => {
+                    named = Some(clean_filename(&answer, &config.rules.sanitizer))
+                        .filter(|name| !name.is_empty())
+                        .map(|name| (name, config.ai_engine.models.text.clone()));
+                }
+                Err(e) => warn!("Text model failed on the text of {:?}: {}", path, e),
+            }
+        }
+
+        // Call vision model
+        let (suggested_name, model) = match named {
+            Some((name, model)) => (name, Some(model)),
+            None => {
+                let response = client
+                    .generate_with_image(
+                        &config.ai_engine.models.vision,
+                        &config.prompts.image,
+                        &image_data,
+                    )
+                    .await;
+
+                let model = response.is_ok().then(|| config.ai_engine.models.vision.clone());
+                let suggested_name = match response {
+                    Ok(text) => clean_filename(&text, &config.rules.sanitizer),
+                    Err(e) => {
+                        warn!("Vision model failed: {}, using fallback", e);
+                        // Fallback: use dimensions as name
+                        format!("image_{}x{}", width, height)
+                    }
+                };
+                (suggested_name, model)
             }
         };
 
         // Build metadata
-        let metadata = serde_json::json!({
+        let mut metadata = serde_json::json!({
             "width": width,
             "height": height,
             "format": format,
             "aspect_ratio": format!("{:.2}", width as f64 / height as f64),
         });
+        if let Some(text) = ocr_text {
+            metadata["ocr_text"] = serde_json::Value::String(text);
+        }
 
         let extension = path.extension()
             .and_then(|e| e.to_str())
             .unwrap_or("jpg");
         let category = infer_category(&suggested_name, extension);
-        let tags = extract_tags(&suggested_name, &metadata);
+        let tags = extract_tags(&suggested_name, &metadata, &config.rules.sanitizer);
 
         Ok(AnalysisResult {
             suggested_name,
@@ -126,6 +265,8 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             tags,
             file_hash,
             metadata,
+            analyzer: None,
+            model,
         })
     }
 }
@@ -6,14 +6,24 @@
 pub mod archive;
 pub mod audio;
 pub mod code;
+pub mod comic;
 pub mod document;
+pub mod ebook;
+pub mod email;
 pub mod image;
 pub mod pdf;
+pub mod plugin;
+pub mod subtitle;
+pub mod svg;
 pub mod video;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
+use unicode_normalization::char::is_combining_mark;
+use crate::calibration::Calibration;
+use crate::config::{FileTypeRule, SanitizerConfig};
 use crate::{AppConfig, Result};
 
 /// Result of file analysis
@@ -31,6 +41,12 @@ pub struct AnalysisResult {
     pub file_hash: String,
     /// Additional metadata
     pub metadata: serde_json::Value,
+    /// Name of the analyzer that made the suggestion
+    #[serde(default)]
+    pub analyzer: Option<String>,
+    /// AI model whose answer the suggestion came from, if one answered
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
 /// Trait for file analyzers
@@ -63,6 +79,38 @@ fn priority(&self) -> u8 {
 /// Registry of all file analyzers
 pub struct AnalyzerRegistry {
     analyzers: Vec<Box<dyn FileAnalyzer>>,
+    /// `file_types` of the config the registry was built for
+    file_types: BTreeMap<String, FileTypeRule>,
+    /// Correction rates suggestions are calibrated by
+    calibration: Calibration,
+}
+
+/// The analyzer chosen for a file, with the config's rule for its type
+pub struct SelectedAnalyzer<'a> {
+    analyzer: &'a dyn FileAnalyzer,
+    rule: Option<&'a FileTypeRule>,
+    calibration: &'a Calibration,
+}
+
+impl SelectedAnalyzer<'_> {
+    pub fn name(&self) -> &'static str {
+        self.analyzer.name()
+    }
+
+    /// Analyze `path`, with the prompt and category of its type's rule, and
+    /// calibrate the confidence of its suggestion
+    pub async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResult> {
+        let mut result = match self.rule.and_then(|r| r.prompt.as_deref()) {
+            Some(prompt) => self.analyzer.analyze(path, &config.with_overrides(None, Some(prompt))).await?,
+            None => self.analyzer.analyze(path, config).await?,
+        };
+        if let Some(category) = self.rule.and_then(|r| r.category.clone()) {
+            result.category = Some(category);
+        }
+        result.analyzer = Some(self.name().to_string());
+        self.calibration.apply(&mut result, &config.calibration);
+        Ok(result)
+    }
 }
 
 impl AnalyzerRegistry {
@@ -70,6 +118,8 @@ impl AnalyzerRegistry {
     pub fn new(config: &AppConfig) -> Self {
         let mut registry = Self {
             analyzers: Vec::new(),
+            file_types: config.file_types.clone(),
+            calibration: Calibration::default(),
         };
 
         // Register analyzers based on config
@@ -88,11 +138,43 @@ pub fn new(config: &AppConfig) -> Self {
         if config.analyzers.code.enabled {
             registry.register(Box::new(code::CodeAnalyzer::new()));
         }
+        if config.analyzers.comic.enabled {
+            registry.register(Box::new(comic::ComicAnalyzer::new()));
+        }
+        if config.analyzers.ebook.enabled {
+            registry.register(Box::new(ebook::EbookAnalyzer::new()));
+        }
+        if config.analyzers.email.enabled {
+            registry.register(Box::new(email::EmailAnalyzer::new()));
+        }
+        if config.analyzers.svg.enabled {
+            registry.register(Box::new(svg::SvgAnalyzer::new()));
+        }
+        if config.analyzers.subtitle.enabled {
+            registry.register(Box::new(subtitle::SubtitleAnalyzer::new()));
+        }
 
         // Always register these
         registry.register(Box::new(document::DocumentAnalyzer::new()));
         registry.register(Box::new(archive::ArchiveAnalyzer::new()));
 
+        for plugin in crate::plugins::discover(Path::new(&config.plugins.directory)) {
+            match plugin.manifest {
+                Ok(manifest) if plugin.enabled => {
+                    registry.register(Box::new(plugin::PluginAnalyzer::new(plugin.dir, manifest)));
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Skipping plugin in {}: {}", plugin.dir.display(), e),
+            }
+        }
+
+        for (file_type, rule) in &config.file_types {
+            if let Some(ref name) = rule.analyzer {
+                if !registry.analyzers.iter().any(|a| a.name() == name) {
+                    tracing::warn!("file_types[{}]: no analyzer called {}; using the usual one", file_type, name);
+                }
+            }
+        }
         registry
     }
 
@@ -102,11 +184,22 @@ pub fn register(&mut self, analyzer: Box<dyn FileAnalyzer>) {
         self.analyzers.sort_by_key(|a| std::cmp::Reverse(a.priority()));
     }
 
-    /// Find the best analyzer for a file
-    pub fn find_analyzer(&self, path: &Path) -> Option<&dyn FileAnalyzer> {
-        self.analyzers.iter()
-            .find(|a| a.can_handle(path))
-            .map(|a| a.as_ref())
+    /// Find the best analyzer for a file: the one its type's rule names, or
+    /// else the preferred one for its extension; none when the rule skips it
+    pub fn find_analyzer(&self, path: &Path) -> Option<SelectedAnalyzer<'_>> {
+        let rule = FileTypeRule::find(&self.file_types, path);
+        if rule.is_some_and(|r| r.skip) {
+            return None;
+        }
+        let named = rule.and_then(|r| r.analyzer.as_deref())
+            .and_then(|name| self.analyzers.iter().find(|a| a.name() == name));
+        named.or_else(|| self.analyzers.iter().find(|a| a.can_handle(path)))
+            .map(|a| SelectedAnalyzer { analyzer: a.as_ref(), rule, calibration: &self.calibration })
+    }
+
+    /// Calibrate suggestions by the correction rates in `db` from now on
+    pub fn load_corrections(&self, db: &crate::db::Database) {
+        self.calibration.load(db);
     }
 
     /// Get all registered analyzers
@@ -135,6 +228,8 @@ fn clone(&self) -> Self {
         // Can't clone Box<dyn FileAnalyzer>, so recreate with defaults
         Self {
             analyzers: Vec::new(),
+            file_types: self.file_types.clone(),
+            calibration: self.calibration.clone(),
         }
     }
 }
@@ -146,9 +241,19 @@ pub fn calculate_file_hash(path: &Path) -> Result<String> {
     Ok(hash.to_hex().to_string())
 }
 
-/// Clean and sanitize a suggested filename
-pub fn clean_filename(raw: &str) -> String {
-    let mut clean = raw.trim().replace(['\n', '\r'], "");
+/// The text of a PDF or of a document the document analyzer reads
+pub fn extract_text(path: &Path) -> Result<String> {
+    let is_pdf = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+    if is_pdf {
+        pdf::PdfAnalyzer::extract_text(path)
+    } else {
+        document::DocumentAnalyzer::extract_content(path)
+    }
+}
+
+/// Clean and sanitize a suggested filename as `sanitizer` says
+pub fn clean_filename(raw: &str, sanitizer: &SanitizerConfig) -> String {
+    let mut clean = crate::naming::prepare(raw.trim(), sanitizer).replace(['\n', '\r'], "");
 
     // Remove common chat prefixes
     if let Some(idx) = clean.find(':') {
@@ -160,21 +265,21 @@ pub fn clean_filename(raw: &str) -> String {
     // Remove quotes
     clean = clean.trim_matches('"').trim_matches('\'').to_string();
 
-    // Sanitize: keep only alphanumeric, underscore, hyphen
+    // Sanitize: keep only letters and digits of any script with their
+    // accents, spaces and the allowed characters
     clean = clean
         .chars()
-        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-' || *c == ' ')
+        .filter(|c| c.is_alphanumeric() || is_combining_mark(*c) || c.is_whitespace() || sanitizer.allowed.contains(*c))
         .collect::<String>();
 
-    // Convert spaces to underscores and lowercase
-    clean = clean.replace(' ', "_").to_lowercase();
-
-    // Remove consecutive underscores
-    while clean.contains("__") {
-        clean = clean.replace("__", "_");
-    }
+    // Join the words with the replacement, dropping any past the limit
+    let separator = sanitizer.replacement;
+    let words = clean.split(|c: char| c.is_whitespace() || c == separator).filter(|w| !w.is_empty());
+    let words: Vec<&str> = words.take(sanitizer.max_words.unwrap_or(usize::MAX)).collect();
+    clean = sanitizer.casing.apply(&words.join(separator.to_string().as_str()));
 
-    clean.trim_matches('_').to_string()
+    // No hidden files, nor separators left at the ends
+    clean.trim_matches(|c: char| c == '_' || c == '.' || c == separator).to_string()
 }
 
 /// Infer category from filename and content
@@ -184,7 +289,7 @@ pub fn infer_category(name: &str, extension: &str) -> Option<String> {
 
     // Category inference based on extension and name patterns
     match ext_lower.as_str() {
-        "jpg" | "jpeg" | "png" | "gif" | "webp" | "heic" | "bmp" | "tiff" => {
+        "jpg" | "jpeg" | "png" | "gif" | "webp" | "heic" | "bmp" | "tiff" | "svg" => {
             if name_lower.contains("screenshot") { Some("Screenshots") }
             else if name_lower.contains("photo") || name_lower.contains("img") { Some("Photos") }
             else if name_lower.contains("diagram") || name_lower.contains("chart") { Some("Diagrams") }
@@ -196,6 +301,10 @@ pub fn infer_category(name: &str, extension: &str) -> Option<String> {
             else if name_lower.contains("manual") || name_lower.contains("guide") { Some("Manuals") }
             else { Some("Documents") }
         }
+        "m4b" => Some("Audiobooks"),
+        "epub" | "mobi" | "azw3" => Some("Books"),
+        "eml" | "msg" => Some("Email"),
+        "srt" | "vtt" | "ass" | "ssa" => Some("Subtitles"),
         "mp3" | "wav" | "flac" | "ogg" | "m4a" => {
             if name_lower.contains("podcast") { Some("Podcasts") }
             else if name_lower.contains("voice") || name_lower.contains("recording") { Some("Recordings") }
@@ -215,13 +324,13 @@ pub fn infer_category(name: &str, extension: &str) -> Option<String> {
     }.map(String::from)
 }
 
-/// Extract tags from analysis metadata
-pub fn extract_tags(name: &str, metadata: &serde_json::Value) -> Vec<String> {
+/// Extract tags from analysis metadata, leaving out `sanitizer`'s stop words
+pub fn extract_tags(name: &str, metadata: &serde_json::Value, sanitizer: &SanitizerConfig) -> Vec<String> {
     let mut tags = Vec::new();
 
     // Extract words from name as potential tags
-    for word in name.split('_') {
-        if word.len() >= 3 && !is_stop_word(word) {
+    for word in name.split(['_', sanitizer.replacement]) {
+        if word.len() >= 3 && !is_stop_word(word, &sanitizer.stop_words) {
             tags.push(word.to_string());
         }
     }
@@ -243,8 +352,8 @@ pub fn extract_tags(name: &str, metadata: &serde_json::Value) -> Vec<String> {
     tags
 }
 
-fn is_stop_word(word: &str) -> bool {
+fn is_stop_word(word: &str, custom: &[String]) -> bool {
     matches!(word.to_lowercase().as_str(),
         "the" | "and" | "for" | "with" | "from" | "this" | "that" | "are" | "was" | "were"
-    )
+    ) || custom.iter().any(|stop| stop.to_lowercase() == word.to_lowercase())
 }
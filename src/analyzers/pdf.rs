@@ -20,7 +20,7 @@ pub fn new() -> Self {
     }
 
     /// Extract text from PDF
-    fn extract_text(path: &Path) -> Result<String> {
+    pub(crate) fn extract_text(path: &Path) -> Result<String> {
         let bytes = std::fs::read(path)?;
         pdf_extract::extract_text_from_mem(&bytes)
             .map_err(|e| PanoptesError::Pdf(format!("Text extraction failed: {}", e)))
@@ -101,10 +101,10 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
         // Try to use document title first
         if let Some(title) = metadata.get("title").and_then(|t| t.as_str()) {
             if !title.is_empty() && title.len() < 100 {
-                let suggested_name = clean_filename(title);
+                let suggested_name = clean_filename(title, &config.rules.sanitizer);
                 if !suggested_name.is_empty() {
                     let category = infer_category(&suggested_name, "pdf");
-                    let tags = extract_tags(&suggested_name, &metadata);
+                    let tags = extract_tags(&suggested_name, &metadata, &config.rules.sanitizer);
 
                     return Ok(AnalysisResult {
                         suggested_name,
@@ -113,6 +113,8 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
                         tags,
                         file_hash,
                         metadata,
+                        analyzer: None,
+                        model: None,
                     });
                 }
             }
@@ -134,8 +136,10 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             text_preview
         );
 
-        let suggested_name = match client.generate(&config.ai_engine.models.text, &prompt).await {
-            Ok(response) => clean_filename(&response),
+        let response = client.generate(&config.ai_engine.models.text, &prompt).await;
+        let model = response.is_ok().then(|| config.ai_engine.models.text.clone());
+        let suggested_name = match response {
+            Ok(response) => clean_filename(&response, &config.rules.sanitizer),
             Err(e) => {
                 warn!("LLM failed for PDF: {}", e);
                 // Fallback: use page count
@@ -147,7 +151,7 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
         };
 
         let category = infer_category(&suggested_name, "pdf");
-        let tags = extract_tags(&suggested_name, &metadata);
+        let tags = extract_tags(&suggested_name, &metadata, &config.rules.sanitizer);
 
         Ok(AnalysisResult {
             suggested_name,
@@ -156,6 +160,8 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             tags,
             file_hash,
             metadata,
+            analyzer: None,
+            model,
         })
     }
 }
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Analyzer running an installed plugin (see [`crate::plugins`])
+//!
+//! The plugin's command gets the file's path as its last argument, and the AI
+//! engine's URL and models in `PANOPTES_AI_URL`, `PANOPTES_TEXT_MODEL` and
+//! `PANOPTES_VISION_MODEL`. It prints one JSON object:
+//!
+//! ```json
+//! {"suggested_name": "beach_sunset", "confidence": 0.8, "category": "Images", "tags": ["beach"], "metadata": {}}
+//! ```
+//!
+//! Only `suggested_name` and `confidence` are required; the category and tags
+//! are inferred from the name as for built-in analyzers when missing. A
+//! command that fails or runs longer than `ai_engine.timeout_secs` fails the
+//! analysis.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, infer_category, extract_tags};
+use crate::plugins::{Manifest, METADATA_KEY};
+use crate::{AppConfig, Result, PanoptesError};
+
+/// Characters of a failed command's error output kept in the error
+const STDERR_LIMIT: usize = 500;
+
+/// What a plugin prints
+#[derive(Debug, Deserialize)]
+struct PluginOutput {
+    suggested_name: String,
+    confidence: f64,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+/// Analyzer for the files a plugin handles
+pub struct PluginAnalyzer {
+    dir: PathBuf,
+    manifest: Manifest,
+    // The analyzer trait hands out `'static` strings; plugins are loaded once
+    // per process, so their few strings are leaked for it
+    name: &'static str,
+    extensions: Vec<&'static str>,
+}
+
+impl PluginAnalyzer {
+    /// The analyzer for the plugin in `dir` with a checked `manifest`
+    pub fn new(dir: PathBuf, manifest: Manifest) -> Self {
+        let leak = |s: &str| -> &'static str { Box::leak(s.to_string().into_boxed_str()) };
+        Self {
+            name: leak(&manifest.name),
+            extensions: manifest.extensions.iter().map(|e| leak(e)).collect(),
+            dir,
+            manifest,
+        }
+    }
+
+    /// Run the plugin's command on `path`; returns what it printed
+    async fn run(&self, path: &Path, config: &AppConfig) -> Result<PluginOutput> {
+        let (program, args) = self.manifest.command.split_first()
+            .ok_or_else(|| PanoptesError::Analysis(format!("Plugin {} has no command", self.name)))?;
+        let program = if program.contains('/') { self.dir.join(program) } else { PathBuf::from(program) };
+        // The command runs in the plugin's directory, so the file's path must not be relative
+        let path = std::fs::canonicalize(path)?;
+
+        let child = tokio::process::Command::new(&program)
+            .args(args)
+            .arg(&path)
+            .current_dir(&self.dir)
+            .env("PANOPTES_AI_URL", &config.ai_engine.url)
+            .env("PANOPTES_TEXT_MODEL", &config.ai_engine.models.text)
+            .env("PANOPTES_VISION_MODEL", &config.ai_engine.models.vision)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| PanoptesError::Analysis(format!("Cannot run plugin {} ({}): {}", self.name, program.display(), e)))?;
+
+        let timeout = Duration::from_secs(config.ai_engine.timeout_secs);
+        let output = tokio::time::timeout(timeout, child.wait_with_output()).await
+            .map_err(|_| PanoptesError::Analysis(format!("Plugin {} took longer than {}s", self.name, timeout.as_secs())))??;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr: String = stderr.trim().chars().take(STDERR_LIMIT).collect();
+            return Err(PanoptesError::Analysis(format!("Plugin {} failed ({}): {}", self.name, output.status, stderr)));
+        }
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| PanoptesError::Analysis(format!("Plugin {} printed invalid output: {}", self.name, e)))
+    }
+}
+
+#[async_trait]
+impl FileAnalyzer for PluginAnalyzer {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+
+    async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResult> {
+        info!("Analyzing with plugin {}: {:?}", self.name, path);
+        let file_hash = calculate_file_hash(path)?;
+        let output = self.run(path, config).await?;
+        debug!("Plugin {} suggested {:?}", self.name, output.suggested_name);
+
+        let suggested_name = clean_filename(&output.suggested_name, &config.rules.sanitizer);
+        if suggested_name.is_empty() {
+            return Err(PanoptesError::Analysis(format!("Plugin {} suggested no usable name", self.name)));
+        }
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+        let mut metadata = match output.metadata {
+            serde_json::Value::Object(map) => map,
+            _ => serde_json::Map::new(),
+        };
+        // Which plugin analyzed the file, for `panoptes plugin info`
+        metadata.insert(METADATA_KEY.to_string(), serde_json::json!({
+            "name": self.manifest.name,
+            "version": self.manifest.version,
+        }));
+        let metadata = serde_json::Value::Object(metadata);
+
+        let tags = if output.tags.is_empty() { extract_tags(&suggested_name, &metadata, &config.rules.sanitizer) } else { output.tags };
+        Ok(AnalysisResult {
+            category: output.category.or_else(|| infer_category(&suggested_name, ext)),
+            tags,
+            confidence: output.confidence.clamp(0.0, 1.0),
+            suggested_name,
+            file_hash,
+            metadata,
+            analyzer: None,
+            model: None,
+        })
+    }
+
+    fn priority(&self) -> u8 {
+        self.manifest.priority
+    }
+}
@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Subtitle analyzer
+//!
+//! SubRip (`.srt`), WebVTT (`.vtt`) and SubStation Alpha (`.ass`, `.ssa`)
+//! subtitles are read for their dialogue: the text of the first [`MAX_CUES`]
+//! cues, without timings or styling. The text model sums it up
+//! (`prompts.subtitle_summary`), then names the film or episode from that
+//! summary, the file's current name and how long the subtitles run
+//! (`prompts.subtitle`). The language of the dialogue becomes a tag.
+
+use async_trait::async_trait;
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, infer_category, extract_tags};
+use crate::ollama::OllamaClient;
+use crate::{AppConfig, Result};
+
+/// Cues read from a subtitle file
+const MAX_CUES: usize = 300;
+
+/// Most characters of the dialogue shown to the text model
+const DIALOGUE_EXCERPT: usize = 4000;
+
+/// Analyzer for subtitle files
+pub struct SubtitleAnalyzer;
+
+/// What a subtitle file holds
+#[derive(Debug, Default)]
+struct Subtitles {
+    /// Text of the first [`MAX_CUES`] cues
+    lines: Vec<String>,
+    /// Cues in the whole file
+    cues: usize,
+    /// When the last cue ends, in seconds
+    duration_secs: Option<u64>,
+}
+
+impl Subtitles {
+    /// Read SubRip or WebVTT subtitles: blocks of a timing line, the text
+    /// after it, and before it a cue number or identifier
+    fn parse_blocks(text: &str) -> Self {
+        let mut subtitles = Self::default();
+        let mut timing_seen = false;
+        let mut cue = Vec::new();
+        for line in text.lines().chain(std::iter::once("")) {
+            let line = line.trim();
+            if line.is_empty() {
+                if timing_seen {
+                    subtitles.add(cue.join(" "));
+                }
+                timing_seen = false;
+                cue.clear();
+            } else if let Some((_, end)) = line.split_once("-->") {
+                timing_seen = true;
+                let end = end.split_whitespace().next().unwrap_or("");
+                subtitles.duration_secs = timestamp(end).or(subtitles.duration_secs);
+            } else if timing_seen {
+                cue.push(strip_markup(line));
+            }
+        }
+        subtitles
+    }
+
+    /// Read SubStation Alpha subtitles: the `Dialogue:` lines of their
+    /// `[Events]`, whose text comes after the ninth comma
+    fn parse_ass(text: &str) -> Self {
+        let mut subtitles = Self::default();
+        for line in text.lines() {
+            let Some(fields) = line.trim().strip_prefix("Dialogue:") else {
+                continue;
+            };
+            let fields: Vec<&str> = fields.splitn(10, ',').collect();
+            if fields.len() < 10 {
+                continue;
+            }
+            subtitles.duration_secs = timestamp(fields[2].trim()).or(subtitles.duration_secs);
+            let dialogue = fields[9].replace("\\N", " ").replace("\\n", " ");
+            subtitles.add(strip_markup(&dialogue));
+        }
+        subtitles
+    }
+
+    fn add(&mut self, text: String) {
+        self.cues += 1;
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if self.lines.len() < MAX_CUES && !text.is_empty() {
+            self.lines.push(text);
+        }
+    }
+}
+
+/// Seconds into the video of a timestamp like `01:02:03,456`, `02:03.456`
+/// or `1:02:03.45`
+fn timestamp(value: &str) -> Option<u64> {
+    let whole = value.split(['.', ',']).next()?;
+    whole.split(':').try_fold(0u64, |secs, part| Some(secs * 60 + part.parse::<u64>().ok()?))
+}
+
+/// `text` without HTML-like tags (`<i>`, `<font ...>`, `<v Speaker>`) nor
+/// SubStation override blocks (`{\an8}`)
+fn strip_markup(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut closing = None;
+    for c in text.chars() {
+        match (closing, c) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (None, c) => plain.push(c),
+            (Some(end), c) if c == end => closing = None,
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// The text of a subtitle file, which is often in a legacy encoding
+fn decode(bytes: &[u8]) -> String {
+    if let Some((encoding, bom)) = encoding_rs::Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom..]).0.into_owned();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes).0.into_owned(),
+    }
+}
+
+impl SubtitleAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for SubtitleAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FileAnalyzer for SubtitleAnalyzer {
+    fn name(&self) -> &'static str {
+        "subtitle"
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &["srt", "vtt", "ass", "ssa"]
+    }
+
+    fn priority(&self) -> u8 {
+        60
+    }
+
+    async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResult> {
+        info!("Analyzing subtitles: {:?}", path);
+
+        let file_hash = calculate_file_hash(path)?;
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        let text = decode(&std::fs::read(path)?);
+        let subtitles = match extension.as_str() {
+            "ass" | "ssa" => Subtitles::parse_ass(&text),
+            _ => Subtitles::parse_blocks(&text),
+        };
+        let dialogue = subtitles.lines.join("\n");
+        debug!("Read {} of {} cues in {:?}", subtitles.lines.len(), subtitles.cues, path);
+
+        let language = whatlang::detect(&dialogue).filter(|info| info.is_reliable()).map(|info| info.lang());
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        // Sum the dialogue up, then name the film or episode from that
+        let client = OllamaClient::new(&config.ai_engine.url);
+        let text_model = &config.ai_engine.models.text;
+        let mut summary = None;
+        if !dialogue.is_empty() {
+            let excerpt = match dialogue.char_indices().nth(DIALOGUE_EXCERPT) {
+                Some((end, _)) => &dialogue[..end],
+                None => &dialogue,
+            };
+            let prompt = format!("{}\n\nDialogue:\n{}", config.prompts.subtitle_summary, excerpt);
+            match client.generate(text_model, &prompt).await {
+                Ok(text) => summary = Some(text.trim().to_string()).filter(|s| !s.is_empty()),
+                Err(e) => warn!("Text model failed on the dialogue of {:?}: {}", path, e),
+            }
+        }
+
+        let mut model = None;
+        let mut suggested_name = String::new();
+        if let Some(summary) = &summary {
+            let mut prompt = format!("{}\n\nFile name: {}\nSummary: {}", config.prompts.subtitle, stem, summary);
+            if let Some(secs) = subtitles.duration_secs {
+                prompt.push_str(&format!("\nRuns about {} minutes", secs.div_ceil(60)));
+            }
+            match client.generate(text_model, &prompt).await {
+                Ok(text) => {
+                    suggested_name = clean_filename(&text, &config.rules.sanitizer);
+                    model = Some(text_model.clone()).filter(|_| !suggested_name.is_empty());
+                }
+                Err(e) => warn!("Text model failed on {:?}: {}", path, e),
+            }
+        }
+        let confidence = if suggested_name.is_empty() {
+            suggested_name = clean_filename(&stem, &config.rules.sanitizer);
+            0.5
+        } else {
+            0.75
+        };
+
+        let metadata = serde_json::json!({
+            "format": extension,
+            "cues": subtitles.cues,
+            "duration_secs": subtitles.duration_secs,
+            "word_count": dialogue.split_whitespace().count(),
+            "language": language.map(|lang| lang.code()),
+            "language_name": language.map(|lang| lang.eng_name()),
+            "summary": summary,
+        });
+
+        let mut tags = vec!["subtitles".to_string()];
+        tags.extend(language.map(|lang| lang.eng_name().to_lowercase()));
+        tags.extend(extract_tags(&suggested_name, &metadata, &config.rules.sanitizer));
+        tags.sort();
+        tags.dedup();
+
+        Ok(AnalysisResult {
+            category: infer_category(&suggested_name, &extension),
+            suggested_name,
+            confidence,
+            tags,
+            file_hash,
+            metadata,
+            analyzer: None,
+            model,
+        })
+    }
+}
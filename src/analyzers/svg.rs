@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! SVG analyzer
+//!
+//! SVGs are XML rather than pixels, so rather than being rasterized they are
+//! read: their `<title>` and `<desc>` (or the Dublin Core title and
+//! description editors like Inkscape keep in `<metadata>`) and the text of
+//! their `<text>` elements go to the text model (`prompts.svg`) to name them.
+//! An SVG with no text at all keeps its name.
+
+use async_trait::async_trait;
+use quick_xml::events::Event;
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use super::{AnalysisResult, FileAnalyzer, calculate_file_hash, clean_filename, infer_category, extract_tags};
+use crate::ollama::OllamaClient;
+use crate::{AppConfig, PanoptesError, Result};
+
+/// Most characters of an SVG's text shown to the text model
+const TEXT_EXCERPT: usize = 2000;
+
+/// Analyzer for SVG vector images
+pub struct SvgAnalyzer;
+
+/// What an SVG says in words
+#[derive(Debug, Default)]
+struct SvgText {
+    title: Option<String>,
+    description: Option<String>,
+    /// Its text elements' text, in document order
+    text: Vec<String>,
+    width: Option<String>,
+    height: Option<String>,
+    view_box: Option<String>,
+}
+
+impl SvgText {
+    /// Read the SVG document `xml`
+    fn parse(xml: &str) -> Result<Self> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        reader.trim_text(true);
+        let mut svg = Self::default();
+        // Local names of the elements the reader is in
+        let mut open: Vec<Vec<u8>> = Vec::new();
+        loop {
+            let text = match reader.read_event().map_err(xml_error)? {
+                Event::Start(element) => {
+                    if open.is_empty() {
+                        svg.read_root(&element);
+                    }
+                    open.push(element.local_name().as_ref().to_vec());
+                    continue;
+                }
+                Event::Empty(element) => {
+                    if open.is_empty() {
+                        svg.read_root(&element);
+                    }
+                    continue;
+                }
+                Event::End(_) => {
+                    open.pop();
+                    continue;
+                }
+                Event::Text(text) => text.unescape()
+                    .map(|t| t.into_owned())
+                    .unwrap_or_else(|_| String::from_utf8_lossy(&text).into_owned()),
+                Event::CData(data) => String::from_utf8_lossy(&data.into_inner()).into_owned(),
+                Event::Eof => break,
+                _ => continue,
+            };
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if text.is_empty() || open.iter().any(|name| name == b"style" || name == b"script") {
+                continue;
+            }
+            match open.last().map(Vec::as_slice) {
+                Some(b"title") => {
+                    svg.title.get_or_insert(text);
+                }
+                Some(b"desc" | b"description") => {
+                    svg.description.get_or_insert(text);
+                }
+                // The rest of the metadata is licenses, formats and the like
+                _ if open.iter().any(|name| name == b"metadata") => {}
+                _ => svg.text.push(text),
+            }
+        }
+        Ok(svg)
+    }
+
+    /// Take the size of the root `<svg>` element
+    fn read_root(&mut self, element: &quick_xml::events::BytesStart) {
+        if element.local_name().as_ref() != b"svg" {
+            return;
+        }
+        for attribute in element.attributes().flatten() {
+            let value = String::from_utf8_lossy(&attribute.value).into_owned();
+            match attribute.key.local_name().as_ref() {
+                b"width" => self.width = Some(value),
+                b"height" => self.height = Some(value),
+                b"viewBox" => self.view_box = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    /// Its words as told to the text model, empty if it has none
+    fn details(&self) -> String {
+        let mut details = Vec::new();
+        if let Some(title) = &self.title {
+            details.push(format!("Title: {}", title));
+        }
+        if let Some(description) = &self.description {
+            details.push(format!("Description: {}", description));
+        }
+        if !self.text.is_empty() {
+            let text = self.text.join(" ");
+            let text = match text.char_indices().nth(TEXT_EXCERPT) {
+                Some((end, _)) => &text[..end],
+                None => &text,
+            };
+            details.push(format!("Text: {}", text));
+        }
+        details.join("\n")
+    }
+}
+
+fn xml_error(e: impl std::fmt::Display) -> PanoptesError {
+    PanoptesError::Analysis(format!("Unreadable SVG: {}", e))
+}
+
+impl SvgAnalyzer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for SvgAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FileAnalyzer for SvgAnalyzer {
+    fn name(&self) -> &'static str {
+        "svg"
+    }
+
+    fn supported_extensions(&self) -> &[&str] {
+        &["svg"]
+    }
+
+    fn priority(&self) -> u8 {
+        100
+    }
+
+    async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResult> {
+        info!("Analyzing SVG: {:?}", path);
+
+        let file_hash = calculate_file_hash(path)?;
+        let xml = std::fs::read(path)?;
+        let svg = SvgText::parse(&String::from_utf8_lossy(&xml)).unwrap_or_else(|e| {
+            warn!("{:?}: {}", path, e);
+            SvgText::default()
+        });
+        let details = svg.details();
+        debug!("Text of {:?}: {}", path, details.replace('\n', "; "));
+
+        let mut model = None;
+        let mut confidence = 0.4;
+        let mut suggested_name = String::new();
+        if !details.is_empty() {
+            let prompt = format!("{}\n\n{}", config.prompts.svg, details);
+            let client = OllamaClient::new(&config.ai_engine.url);
+            match client.generate(&config.ai_engine.models.text, &prompt).await {
+                Ok(text) => {
+                    suggested_name = clean_filename(&text, &config.rules.sanitizer);
+                    if !suggested_name.is_empty() {
+                        model = Some(config.ai_engine.models.text.clone());
+                        confidence = if svg.title.is_some() || svg.description.is_some() { 0.8 } else { 0.65 };
+                    }
+                }
+                Err(e) => warn!("Text model failed on {:?}: {}", path, e),
+            }
+        }
+        // Without the model: its title, or else its current name
+        if suggested_name.is_empty() {
+            if let Some(title) = &svg.title {
+                suggested_name = clean_filename(title, &config.rules.sanitizer);
+                confidence = 0.6;
+            }
+        }
+        if suggested_name.is_empty() {
+            confidence = 0.4;
+            suggested_name = clean_filename(&path.file_stem().unwrap_or_default().to_string_lossy(), &config.rules.sanitizer);
+        }
+
+        let text = svg.text.join(" ");
+        let metadata = serde_json::json!({
+            "title": svg.title,
+            "description": svg.description,
+            "text": text.chars().take(TEXT_EXCERPT).collect::<String>(),
+            "word_count": text.split_whitespace().count(),
+            "width": svg.width,
+            "height": svg.height,
+            "view_box": svg.view_box,
+            "format": "svg",
+        });
+
+        let category = infer_category(&suggested_name, "svg");
+        let mut tags = vec!["vector".to_string()];
+        tags.extend(extract_tags(&suggested_name, &metadata, &config.rules.sanitizer));
+        tags.sort();
+        tags.dedup();
+
+        Ok(AnalysisResult {
+            suggested_name,
+            confidence,
+            category,
+            tags,
+            file_hash,
+            metadata,
+            analyzer: None,
+            model,
+        })
+    }
+}
@@ -186,13 +186,13 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
         // Try to use title from metadata first
         if let Some(ref meta) = video_meta {
             if let Some(ref title) = meta.title {
-                let suggested_name = clean_filename(title);
+                let suggested_name = clean_filename(title, &config.rules.sanitizer);
                 if !suggested_name.is_empty() && suggested_name.len() > 3 {
                     let extension = path.extension()
                         .and_then(|e| e.to_str())
                         .unwrap_or("mp4");
                     let category = infer_category(&suggested_name, extension);
-                    let tags = extract_tags(&suggested_name, &metadata);
+                    let tags = extract_tags(&suggested_name, &metadata, &config.rules.sanitizer);
 
                     return Ok(AnalysisResult {
                         suggested_name,
@@ -201,12 +201,15 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
                         tags,
                         file_hash,
                         metadata,
+                        analyzer: None,
+                        model: None,
                     });
                 }
             }
         }
 
         // If FFmpeg is available, extract keyframes and analyze
+        let mut model = None;
         let suggested_name = if Self::ffmpeg_available() {
             let temp_dir = std::env::temp_dir().join("panoptes_frames");
             std::fs::create_dir_all(&temp_dir)?;
@@ -234,7 +237,10 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
                 }
 
                 match result {
-                    Ok(response) => clean_filename(&response),
+                    Ok(response) => {
+                        model = Some(config.ai_engine.models.vision.clone());
+                        clean_filename(&response, &config.rules.sanitizer)
+                    }
                     Err(e) => {
                         warn!("Vision model failed for video: {}", e);
                         // Fallback
@@ -262,7 +268,7 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             .and_then(|e| e.to_str())
             .unwrap_or("mp4");
         let category = infer_category(&suggested_name, extension);
-        let tags = extract_tags(&suggested_name, &metadata);
+        let tags = extract_tags(&suggested_name, &metadata, &config.rules.sanitizer);
 
         Ok(AnalysisResult {
             suggested_name,
@@ -271,6 +277,8 @@ async fn analyze(&self, path: &Path, config: &AppConfig) -> Result<AnalysisResul
             tags,
             file_hash,
             metadata,
+            analyzer: None,
+            model,
         })
     }
 }
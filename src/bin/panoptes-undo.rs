@@ -3,21 +3,28 @@
 
 //! Panoptes Undo Utility
 //!
-//! Reverses file renames recorded in the history log.
+//! Reverses file renames recorded in the Panoptes history.
 
 use clap::Parser;
-use serde::Deserialize;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 
+use panoptes::config::AppConfig;
+use panoptes::db::Database;
+use panoptes::discard::{Deletion, Discard};
+use panoptes::history::{revert_with, History, HistoryAction, UndoOutcome};
+use panoptes::paths;
+
 #[derive(Parser, Debug)]
 #[command(name = "panoptes-undo")]
-#[command(version = "1.0.0")]
+#[command(version = "1.1.0")]
 #[command(about = "Undo Panoptes file renames")]
 struct Args {
-    /// Path to history file
-    #[arg(short, long, default_value = "panoptes_history.jsonl")]
+    /// Path to configuration file (used to locate the database)
+    #[arg(short = 'C', long, default_value_os_t = paths::default_config())]
+    config: PathBuf,
+
+    /// Legacy JSONL history file to import before undoing
+    #[arg(long, default_value = "panoptes_history.jsonl")]
     history_file: PathBuf,
 
     /// Number of renames to undo (default: 1, use 0 for all)
@@ -33,62 +40,52 @@ struct Args {
     list: bool,
 }
 
-#[derive(Deserialize, Debug)]
-struct HistoryEntry {
-    timestamp: String,
-    original_path: String,
-    new_path: String,
-    ai_suggestion: String,
-}
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    if !args.history_file.exists() {
-        eprintln!("History file not found: {:?}", args.history_file);
-        eprintln!("No renames to undo.");
-        return Ok(());
-    }
-
-    let file = File::open(&args.history_file)?;
-    let reader = BufReader::new(file);
-
-    let mut entries: Vec<HistoryEntry> = Vec::new();
-    for line in reader.lines() {
-        let line = line?;
-        if line.trim().is_empty() {
-            continue;
-        }
-        match serde_json::from_str(&line) {
-            Ok(entry) => entries.push(entry),
-            Err(e) => eprintln!("Warning: Failed to parse history entry: {}", e),
-        }
-    }
-
-    if entries.is_empty() {
-        println!("No history entries found.");
-        return Ok(());
+    let config = AppConfig::load(&args.config)?;
+    let history = History::new(Database::open(&config.database.path)?);
+    let imported = history.import_jsonl(&args.history_file)?;
+    if imported > 0 {
+        println!("Imported {} entries from {:?}", imported, args.history_file);
     }
 
     if args.list {
+        let entries = history.read_all()?;
+        if entries.is_empty() {
+            println!("No history entries found.");
+            return Ok(());
+        }
         println!("Rename History ({} entries):", entries.len());
         println!("{:-<80}", "");
         for (i, entry) in entries.iter().rev().enumerate() {
+            let to = match entry.action {
+                HistoryAction::Trash => "(trash)".to_string(),
+                HistoryAction::Delete => "(deleted)".to_string(),
+                _ => entry.new_path.display().to_string(),
+            };
             println!(
-                "{:3}. [{}] {} -> {}",
+                "{:3}. [{}] {} -> {}{}",
                 i + 1,
-                &entry.timestamp[..19], // Trim timezone
-                entry.original_path,
-                entry.new_path
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                entry.original_path.display(),
+                to,
+                if entry.undone { " [UNDONE]" } else { "" }
             );
             println!("     AI suggestion: {}", entry.ai_suggestion);
         }
         return Ok(());
     }
 
-    // Reverse entries to undo most recent first
+    // Most recent first
+    let mut entries = history.get_undoable()?;
     entries.reverse();
 
+    if entries.is_empty() {
+        println!("No renames to undo.");
+        return Ok(());
+    }
+
     let count = if args.count == 0 {
         entries.len()
     } else {
@@ -103,41 +100,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut undone = 0;
     let mut failed = 0;
+    // Files overwritten go to the trash
+    let discard = Discard::new(history.clone(), Deletion::Trash);
 
     for entry in entries.iter().take(count) {
-        let new_path = PathBuf::from(&entry.new_path);
-        let original_path = PathBuf::from(&entry.original_path);
-
-        if !new_path.exists() {
-            eprintln!(
-                "  Skip: {} (file not found, may have been moved/deleted)",
-                entry.new_path
-            );
-            failed += 1;
-            continue;
-        }
-
-        if original_path.exists() {
-            eprintln!(
-                "  Skip: {} (original path already exists)",
-                entry.original_path
-            );
-            failed += 1;
+        if args.dry_run {
+            if !entry.new_path.exists() {
+                eprintln!("  Skip: {} (file not found, may have been moved/deleted)", entry.new_path.display());
+                failed += 1;
+            } else if entry.original_path.exists() {
+                eprintln!("  Skip: {} (original path already exists)", entry.original_path.display());
+                failed += 1;
+            } else {
+                println!("  Would rename: {} -> {}", entry.new_path.display(), entry.original_path.display());
+            }
             continue;
         }
 
-        if args.dry_run {
-            println!("  Would rename: {} -> {}", entry.new_path, entry.original_path);
-        } else {
-            match fs::rename(&new_path, &original_path) {
-                Ok(()) => {
-                    println!("  Undone: {} -> {}", entry.new_path, entry.original_path);
-                    undone += 1;
-                }
-                Err(e) => {
-                    eprintln!("  Failed: {} ({})", entry.new_path, e);
-                    failed += 1;
-                }
+        match revert_with(entry, config.history.undo_conflict, &discard) {
+            Ok(UndoOutcome::Reverted(restored)) => {
+                history.mark_undone(&entry.id, &restored)?;
+                println!("  Undone: {} -> {}", entry.new_path.display(), restored.display());
+                undone += 1;
+            }
+            Ok(UndoOutcome::Missing) => {
+                eprintln!("  Skip: {} (file not found, may have been moved/deleted)", entry.new_path.display());
+                failed += 1;
+            }
+            Ok(UndoOutcome::Conflict) => {
+                eprintln!("  Skip: {} (original path already exists)", entry.original_path.display());
+                failed += 1;
+            }
+            Err(e) => {
+                eprintln!("  Failed: {} ({})", entry.new_path.display(), e);
+                failed += 1;
             }
         }
     }
@@ -146,13 +142,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     if args.dry_run {
         println!("Dry run complete. {} rename(s) would be undone.", count - failed);
     } else {
-        println!(
-            "Done. {} undone, {} failed/skipped.",
-            undone, failed
-        );
-        if undone > 0 {
-            println!("Note: History file not modified. Run again to undo more.");
-        }
+        println!("Done. {} undone, {} failed/skipped.", undone, failed);
     }
 
     Ok(())
@@ -11,6 +11,7 @@
 
 use panoptes::config::AppConfig;
 use panoptes::db::Database;
+use panoptes::paths;
 use panoptes::Result;
 
 #[derive(Parser, Debug)]
@@ -20,7 +21,7 @@
 #[command(about = "Panoptes Web Dashboard Server")]
 struct Args {
     /// Path to configuration file
-    #[arg(short, long, default_value = "config.json")]
+    #[arg(short, long, default_value_os_t = paths::default_config())]
     config: PathBuf,
 
     /// Host to bind to
@@ -68,12 +69,11 @@ async fn main() -> Result<()> {
     let db = Database::open(&config.database.path)?;
     info!("Database: {}", config.database.path);
 
-    let addr = format!("{}:{}", config.web.host, config.web.port);
-    info!("Starting web server at https://{}", addr);
+    let url = config.web.url();
+    info!("Starting web server at {}", url);
 
     // Open browser if requested
     if args.open {
-        let url = format!("https://{}", addr);
         if let Err(e) = open_browser(&url) {
             error!("Failed to open browser: {}", e);
         }
@@ -81,7 +81,7 @@ async fn main() -> Result<()> {
 
     // Start web server
     // Import the web module's start function
-    panoptes::web::start_server(config, db).await
+    panoptes::web::start_server(config, args.config, db).await
 }
 
 fn open_browser(url: &str) -> std::io::Result<()> {
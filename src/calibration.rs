@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Calibrated confidence
+//!
+//! Analyzers rate their suggestions with fixed figures: an image the vision
+//! model named is 0.85 however little it made of it. So that thresholds like
+//! `rules.auto_rename_threshold` say how often a name is right, each
+//! suggestion's confidence is calibrated before it is used:
+//!
+//! - by history (`calibration.history`): how often the suggestions of the
+//!   same analyzer and model were corrected (see [`crate::feedback`]) draws it
+//!   toward the share of them left as they were, the more so the more files
+//!   there are to go on (`calibration.prior_files`)
+//! - by its signals (`calibration.signals`): a fallback name saying nothing
+//!   of the file, or little text to go on, lower it; a title in the file's
+//!   metadata, plenty of text and a name in keeping with the file's own text
+//!   raise it
+//!
+//! The analyzer's own figure is kept in the metadata as `raw_confidence`.
+//! Correction rates are read from the database when a command starts.
+
+use std::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::analyzers::AnalysisResult;
+use crate::config::CalibrationConfig;
+use crate::db::Database;
+use crate::feedback::CorrectionRate;
+
+/// Words of the names analyzers fall back on, which say nothing of the file
+const GENERIC: &[&str] = &[
+    "archive", "audio", "book", "code", "comic", "document", "email", "file", "image", "img",
+    "scan", "unknown", "untitled", "video",
+];
+
+/// Files with fewer words of text than this are little to go on
+const FEW_WORDS: u64 = 20;
+
+/// Files with at least this many words of text are plenty to go on
+const MANY_WORDS: u64 = 200;
+
+/// Correction rates of analyzers and models, to calibrate confidence by
+#[derive(Debug, Default)]
+pub struct Calibration {
+    rates: RwLock<Vec<CorrectionRate>>,
+}
+
+impl Clone for Calibration {
+    fn clone(&self) -> Self {
+        Self { rates: RwLock::new(self.rates()) }
+    }
+}
+
+impl Calibration {
+    /// Take up the correction rates in `db`, logging failures
+    pub fn load(&self, db: &Database) {
+        match db.get_correction_rates() {
+            Ok(rates) => {
+                debug!("Calibrating confidence by {} correction rates", rates.len());
+                if let Ok(mut current) = self.rates.write() {
+                    *current = rates;
+                }
+            }
+            Err(e) => warn!("Failed to read correction rates: {}", e),
+        }
+    }
+
+    fn rates(&self) -> Vec<CorrectionRate> {
+        self.rates.read().map(|rates| rates.clone()).unwrap_or_default()
+    }
+
+    /// Calibrate the confidence of `result`, whose analyzer and model are set
+    pub fn apply(&self, result: &mut AnalysisResult, config: &CalibrationConfig) {
+        if !config.history && !config.signals {
+            return;
+        }
+        let raw = result.confidence;
+        let mut confidence = raw;
+        if config.history {
+            let rates = self.rates.read();
+            let rate = rates.as_ref().ok().and_then(|rates| {
+                rates.iter().find(|r| r.analyzer == result.analyzer && r.model == result.model).cloned()
+            });
+            if let Some(rate) = rate.filter(|r| r.files > 0) {
+                let files = rate.files as f64;
+                let weight = files / (files + f64::from(config.prior_files));
+                confidence = confidence * (1.0 - weight) + (1.0 - rate.rate()) * weight;
+            }
+        }
+        if config.signals {
+            confidence *= signal_factor(result);
+        }
+        result.confidence = confidence.clamp(0.0, 1.0);
+        if let Some(metadata) = result.metadata.as_object_mut() {
+            metadata.insert("raw_confidence".to_string(), raw.into());
+        }
+    }
+}
+
+/// How much more or less sure of `result` its signals make for
+fn signal_factor(result: &AnalysisResult) -> f64 {
+    let words = name_words(&result.suggested_name);
+    if words.iter().all(|word| is_generic(word)) {
+        return 0.6;
+    }
+
+    let mut factor = 1.0;
+    let metadata = &result.metadata;
+    if metadata.get("title").and_then(|t| t.as_str()).is_some_and(|t| !t.trim().is_empty()) {
+        factor *= 1.05;
+    }
+    let word_count = metadata.get("word_count").and_then(|n| n.as_u64())
+        .or_else(|| metadata.get("char_count").and_then(|n| n.as_u64()).map(|chars| chars / 6))
+        .or_else(|| metadata.get("ocr_text").and_then(|t| t.as_str()).map(|t| t.split_whitespace().count() as u64));
+    match word_count {
+        Some(n) if n < FEW_WORDS => factor *= 0.85,
+        Some(n) if n >= MANY_WORDS => factor *= 1.05,
+        _ => {}
+    }
+
+    // The name's words found in the file's own text and metadata
+    let text = strings(metadata).join(" ").to_lowercase();
+    let telling: Vec<&String> = words.iter().filter(|word| word.chars().count() >= 4).collect();
+    if !text.is_empty() && !telling.is_empty() {
+        let found = telling.iter().filter(|word| text.contains(word.as_str())).count();
+        if found * 2 >= telling.len() {
+            factor *= 1.05;
+        }
+    }
+    factor
+}
+
+/// The lowercase words of a suggested name
+fn name_words(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Whether `word` is a fallback word, a number or dimensions like `1920x1080`
+fn is_generic(word: &str) -> bool {
+    GENERIC.contains(&word) || word.split('x').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// The strings in `value`, however deep
+fn strings(value: &serde_json::Value) -> Vec<&str> {
+    match value {
+        serde_json::Value::String(s) => vec![s.as_str()],
+        serde_json::Value::Array(values) => values.iter().flat_map(strings).collect(),
+        serde_json::Value::Object(map) => map.values().flat_map(strings).collect(),
+        _ => Vec::new(),
+    }
+}
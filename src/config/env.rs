@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Settings overridden by environment variables
+//!
+//! `PANOPTES_<SECTION>__<KEY>` sets the setting `section.key` on top of the
+//! config files (see [`super::layers`]): `PANOPTES_AI_ENGINE__URL`, `PANOPTES_DATABASE__PATH`,
+//! `PANOPTES_WEB__PORT`. Sections nest with further `__`s. A setting that
+//! holds text takes the variable's value as it is; any other takes it as
+//! JSON (`PANOPTES_WATCH_PATHS='["/data"]'`), falling back to text. Variables
+//! naming no section of the config, such as the `PANOPTES_AI_URL` plugins
+//! get, are left alone.
+
+use serde_json::Value;
+
+const PREFIX: &str = "PANOPTES_";
+
+const SEPARATOR: &str = "__";
+
+/// A setting set by an environment variable
+#[derive(Debug, Clone)]
+pub struct EnvOverride {
+    pub var: String,
+    /// Keys from the top of the config down
+    pub path: Vec<String>,
+    pub raw: String,
+}
+
+/// The overrides in the environment for a config with the top-level keys of `config`
+pub fn overrides(config: &Value) -> Vec<EnvOverride> {
+    let mut overrides: Vec<EnvOverride> = std::env::vars()
+        .filter_map(|(var, raw)| {
+            let path: Vec<String> = var.strip_prefix(PREFIX)?
+                .split(SEPARATOR)
+                .map(str::to_lowercase)
+                .collect();
+            if path.iter().any(String::is_empty) || config.get(&path[0]).is_none() {
+                return None;
+            }
+            Some(EnvOverride { var, path, raw })
+        })
+        .collect();
+    // Applied shallowest first, so `PANOPTES_WEB__AUTH__ENABLED` lands in a `PANOPTES_WEB__AUTH` object
+    overrides.sort_by(|a, b| a.path.len().cmp(&b.path.len()).then_with(|| a.var.cmp(&b.var)));
+    overrides
+}
+
+/// The value at `path` in `value`
+pub fn get<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+/// Set `path` in `value` to `new`, making the objects on the way
+pub fn set(value: &mut Value, path: &[String], new: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        *value = new;
+        return;
+    };
+    let mut current = value;
+    for key in parents {
+        if !current.get(key).is_some_and(Value::is_object) {
+            current[key.as_str()] = Value::Object(serde_json::Map::new());
+        }
+        current = &mut current[key.as_str()];
+    }
+    if let Value::Object(map) = current {
+        map.insert(last.clone(), new);
+    }
+}
+
+/// Apply `overrides` to `config`
+pub fn apply(config: &mut Value, overrides: &[EnvOverride]) {
+    for o in overrides {
+        let value = match get(config, &o.path) {
+            Some(Value::String(_)) => Value::String(o.raw.clone()),
+            _ => serde_json::from_str(&o.raw).unwrap_or_else(|_| Value::String(o.raw.clone())),
+        };
+        set(config, &o.path, value);
+    }
+}
@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Configuration merged from several files and the environment
+//!
+//! Settings come from these layers, each overriding the ones before:
+//!
+//! 1. the defaults
+//! 2. `/etc/panoptes/config.json`, for everyone on the machine
+//! 3. `$XDG_CONFIG_HOME/panoptes/config.json` (`~/.config/panoptes/config.json`), for the user
+//!    (see [`crate::paths`] for other platforms)
+//! 4. the config file given with `--config`, for the project
+//! 5. `secrets.json` next to it (see [`super::secrets`])
+//! 6. `PANOPTES_*` environment variables (see [`super::env`])
+//!
+//! Objects merge key by key; any other value, arrays included, replaces the
+//! one before. So a machine-wide AI engine URL needn't be repeated in each
+//! project, whose file only has to hold what differs. Layers that don't
+//! exist are skipped. Changes saved from the web UI go to the project's file.
+//!
+//! A config file can also take settings from others with `"include":
+//! ["prompts.json", "destinations.json"]`, paths being relative to it. They
+//! are merged in order just before the file itself, so long prompts or
+//! destination maps can be kept apart and shared between profiles.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::{env, secrets, AppConfig};
+use crate::organizer::expand_home;
+use crate::{paths, PanoptesError, Result};
+
+const FILE_NAME: &str = "config.json";
+
+/// Key of the files a config file includes
+const INCLUDE_KEY: &str = "include";
+
+/// The machine-wide config file
+fn system_file() -> Option<PathBuf> {
+    if cfg!(unix) {
+        Some(PathBuf::from("/etc/panoptes").join(FILE_NAME))
+    } else {
+        std::env::var_os("PROGRAMDATA").map(|dir| PathBuf::from(dir).join("panoptes").join(FILE_NAME))
+    }
+}
+
+/// The machine's and the user's config files that exist, without `project`
+fn shared_files(project: &Path) -> Vec<PathBuf> {
+    let project = std::fs::canonicalize(project).ok();
+    [system_file(), paths::user_config()].into_iter()
+        .flatten()
+        .filter(|path| path.is_file())
+        .filter(|path| project.is_none() || std::fs::canonicalize(path).ok() != project)
+        .collect()
+}
+
+fn read(path: &Path) -> Result<Value> {
+    let content = std::fs::read_to_string(path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| PanoptesError::Config(format!("Failed to parse config {}: {}", path.display(), e)))
+}
+
+/// The config file at `path` as layers, added to `layers`: what it includes,
+/// in order, then the file itself without `include`. `including` are the
+/// files on the way to it, so a file including itself is caught.
+fn read_layers(path: &Path, including: &mut Vec<PathBuf>, layers: &mut Vec<(Value, PathBuf)>) -> Result<()> {
+    let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if including.contains(&canonical) {
+        return Err(PanoptesError::Config(format!("{} includes itself", path.display())));
+    }
+    let mut value = read(path)?;
+    let includes = match value.as_object_mut().and_then(|map| map.remove(INCLUDE_KEY)) {
+        None => Vec::new(),
+        Some(Value::Array(items)) if items.iter().all(Value::is_string) => {
+            items.iter().filter_map(Value::as_str).map(String::from).collect()
+        }
+        Some(_) => {
+            return Err(PanoptesError::Config(format!("{} in {} must be a list of files", INCLUDE_KEY, path.display())));
+        }
+    };
+
+    including.push(canonical);
+    for include in includes {
+        let included = expand_home(&include);
+        let included = match path.parent() {
+            Some(dir) if included.is_relative() => dir.join(included),
+            _ => included,
+        };
+        if !included.is_file() {
+            return Err(PanoptesError::Config(format!(
+                "{} includes {}, which does not exist", path.display(), included.display()
+            )));
+        }
+        read_layers(&included, including, layers)?;
+    }
+    including.pop();
+    layers.push((value, path.to_path_buf()));
+    Ok(())
+}
+
+/// The layers of the project whose file is `project` that exist, from the
+/// bottom up, the environment aside
+fn layers(project: &Path) -> Result<Vec<(Value, PathBuf)>> {
+    let mut layers = Vec::new();
+    for path in shared_files(project) {
+        read_layers(&path, &mut Vec::new(), &mut layers)?;
+    }
+    if project.exists() {
+        read_layers(project, &mut Vec::new(), &mut layers)?;
+    }
+    let secrets_file = secrets::file_for(project);
+    if secrets_file.is_file() {
+        secrets::check_permissions(&secrets_file);
+        layers.push((read(&secrets_file)?, secrets_file));
+    }
+    Ok(layers)
+}
+
+/// The files the configuration of the project whose file is `project` is
+/// read from, included ones too
+pub fn files(project: &Path) -> Result<Vec<PathBuf>> {
+    Ok(layers(project)?.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Merge `layer` into `base`
+fn merge(base: &mut Value, layer: &Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer.clone(),
+    }
+}
+
+/// Where a setting's value comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(var) => write!(f, "${}", var),
+        }
+    }
+}
+
+impl Serialize for Source {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A setting of the effective configuration, for `panoptes config show --resolved`
+#[derive(Debug, Clone, Serialize)]
+pub struct Setting {
+    /// Dotted path, e.g. `rules.max_length`
+    pub path: String,
+    pub value: Value,
+    pub source: Source,
+    /// From the secrets file or a reference, so not to be shown
+    #[serde(skip)]
+    pub secret: bool,
+}
+
+/// Call `visit` with each setting of `value`; arrays and empty objects are one setting
+fn flatten(path: &mut Vec<String>, value: &Value, visit: &mut impl FnMut(&[String], &Value)) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                path.push(key.clone());
+                flatten(path, child, visit);
+                path.pop();
+            }
+        }
+        _ => visit(path, value),
+    }
+}
+
+/// The configuration of the project whose file is `project`, with where each
+/// of its settings comes from
+pub fn resolve(project: &Path) -> Result<(AppConfig, Vec<Setting>)> {
+    if !project.exists() {
+        tracing::info!("Config file not found at {:?}, using defaults", project);
+    }
+    let files = layers(project)?;
+    let secrets_file = secrets::file_for(project);
+
+    let mut value = serde_json::to_value(AppConfig::default())?;
+    for (layer, _) in &files {
+        merge(&mut value, layer);
+    }
+    serde_json::from_value::<AppConfig>(value.clone())
+        .map_err(|e| PanoptesError::Config(format!("Failed to parse config: {}", e)))?;
+
+    let overrides = env::overrides(&value);
+    env::apply(&mut value, &overrides);
+    // Text for text, so the references can't make the config invalid
+    let referenced = secrets::resolve_references(&mut value)?;
+    let config = serde_json::from_value(value.clone()).map_err(|e| {
+        let vars: Vec<&str> = overrides.iter().map(|o| o.var.as_str()).collect();
+        PanoptesError::Config(format!("Invalid environment override ({}): {}", vars.join(", "), e))
+    })?;
+
+    let mut settings = Vec::new();
+    flatten(&mut Vec::new(), &value, &mut |path, value| {
+        let source = if let Some(o) = overrides.iter().rev().find(|o| path.starts_with(&o.path)) {
+            Source::Env(o.var.clone())
+        } else if let Some((_, file)) = files.iter().rev().find(|(layer, _)| env::get(layer, path).is_some()) {
+            Source::File(file.clone())
+        } else {
+            Source::Default
+        };
+        let secret = source == Source::File(secrets_file.clone()) || referenced.iter().any(|r| path.starts_with(r));
+        settings.push(Setting { path: path.join("."), value: value.clone(), source, secret });
+    });
+    Ok((config, settings))
+}
+
+/// What to write to the project's file at `project` for `edited`, a change
+/// of the `current` configuration: the settings the file has and those
+/// differing from the layers below it. Settings at the `fixed` paths, set on
+/// the command line, those from the environment or the secrets file and
+/// those with references keep the file's value unless they were changed, so
+/// secrets aren't written into it.
+pub fn project_file(edited: &AppConfig, current: &AppConfig, project: &Path, fixed: &[&str]) -> Result<Value> {
+    let mut lower = Vec::new();
+    for path in shared_files(project) {
+        read_layers(&path, &mut Vec::new(), &mut lower)?;
+    }
+    let (file, includes) = if project.exists() {
+        read_layers(project, &mut Vec::new(), &mut lower)?;
+        let (file, _) = lower.pop().expect("the project's file is its last layer");
+        (file, read(project)?.get(INCLUDE_KEY).cloned())
+    } else {
+        (Value::Null, None)
+    };
+    let mut below = serde_json::to_value(AppConfig::default())?;
+    for (layer, _) in &lower {
+        merge(&mut below, layer);
+    }
+    // With the defaults of what the files add, such as another category's naming rule
+    let below = serde_json::from_value::<AppConfig>(below)
+        .and_then(serde_json::to_value)
+        .map_err(|e| PanoptesError::Config(format!("Failed to parse config: {}", e)))?;
+    let edited = serde_json::to_value(edited)?;
+    let current = serde_json::to_value(current)?;
+
+    let mut kept: Vec<Vec<String>> = env::overrides(&edited).into_iter().map(|o| o.path).collect();
+    kept.extend(fixed.iter().map(|path| path.split('.').map(String::from).collect()));
+    kept.extend(secrets::resolve_references(&mut file.clone())?);
+    kept.extend(secrets::resolve_references(&mut below.clone())?);
+    let secrets_file = secrets::file_for(project);
+    if secrets_file.is_file() {
+        // From where the secrets file adds to the others, with the defaults filled in below it
+        flatten(&mut Vec::new(), &read(&secrets_file)?, &mut |path, _| {
+            let added = (1..=path.len())
+                .find(|&len| [&file, &below].iter().all(|layer| matches!(env::get(layer, &path[..len]), None | Some(Value::Null))))
+                .unwrap_or(path.len());
+            kept.push(path[..added].to_vec());
+        });
+    }
+
+    // The includes stay at the top of the file
+    let mut saved = Value::Object(serde_json::Map::new());
+    if let Some(includes) = includes {
+        saved[INCLUDE_KEY] = includes;
+    }
+    flatten(&mut Vec::new(), &edited, &mut |path, value| {
+        let value = if kept.iter().any(|k| path.starts_with(k)) && env::get(&current, path) == Some(value) {
+            env::get(&file, path)
+        } else if env::get(&file, path).is_some() || env::get(&below, path) != Some(value) {
+            Some(value)
+        } else {
+            None
+        };
+        if let Some(value) = value {
+            env::set(&mut saved, path, value.clone());
+        }
+    });
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write(path: &Path, value: Value) {
+        std::fs::write(path, value.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = json!({ "rules": { "max_length": 80, "date_prefix": true }, "watch_paths": ["/a", "/b"] });
+        merge(&mut base, &json!({ "rules": { "max_length": 40 }, "watch_paths": ["/c"], "new": 1 }));
+        // Objects key by key, anything else replaced
+        assert_eq!(base, json!({ "rules": { "max_length": 40, "date_prefix": true }, "watch_paths": ["/c"], "new": 1 }));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(FILE_NAME);
+        write(&project, json!({ "rules": { "max_length": 42 } }));
+
+        let (config, settings) = resolve(&project).unwrap();
+        assert_eq!(config.rules.max_length, 42);
+        let source = |path: &str| settings.iter().find(|s| s.path == path).map(|s| s.source.clone());
+        assert_eq!(source("rules.max_length"), Some(Source::File(project.clone())));
+        assert_eq!(files(&project).unwrap().last(), Some(&project));
+    }
+
+    #[test]
+    fn test_project_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(FILE_NAME);
+        write(&project, json!({ "rules": { "max_length": 42 } }));
+        let (current, _) = resolve(&project).unwrap();
+
+        let mut edited = current.clone();
+        edited.rules.date_prefix = !current.rules.date_prefix;
+        let saved = project_file(&edited, &current, &project, &[]).unwrap();
+        // What the file had and what was changed, not the rest of the defaults
+        assert_eq!(saved, json!({ "rules": { "max_length": 42, "date_prefix": edited.rules.date_prefix } }));
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(FILE_NAME);
+        std::fs::create_dir(dir.path().join("shared")).unwrap();
+        write(&dir.path().join("shared/prompts.json"), json!({ "rules": { "max_length": 30, "date_prefix": false } }));
+        write(&dir.path().join("lengths.json"), json!({ "rules": { "max_length": 50 } }));
+        write(&project, json!({ "include": ["shared/prompts.json", "lengths.json"], "rules": { "date_prefix": true } }));
+
+        let mut layers = Vec::new();
+        read_layers(&project, &mut Vec::new(), &mut layers).unwrap();
+        let paths: Vec<&Path> = layers.iter().map(|(_, path)| path.as_path()).collect();
+        assert_eq!(paths, [dir.path().join("shared/prompts.json"), dir.path().join("lengths.json"), project.clone()]);
+        // Merged in order, the file itself last and without its includes
+        assert!(layers[2].0.get(INCLUDE_KEY).is_none());
+        let (config, _) = resolve(&project).unwrap();
+        assert_eq!((config.rules.max_length, config.rules.date_prefix), (50, true));
+
+        // And saved with them
+        let saved = project_file(&config, &config, &project, &[]).unwrap();
+        assert_eq!(saved[INCLUDE_KEY], json!(["shared/prompts.json", "lengths.json"]));
+        assert_eq!(saved["rules"], json!({ "date_prefix": true }));
+    }
+
+    #[test]
+    fn test_bad_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.json"), dir.path().join("b.json"));
+        let read = |path: &Path| read_layers(path, &mut Vec::new(), &mut Vec::new()).unwrap_err().to_string();
+
+        write(&a, json!({ "include": ["b.json"] }));
+        write(&b, json!({ "include": ["a.json"] }));
+        assert!(read(&a).contains("includes itself"), "{}", read(&a));
+
+        write(&a, json!({ "include": ["missing.json"] }));
+        assert!(read(&a).contains("which does not exist"), "{}", read(&a));
+
+        write(&a, json!({ "include": "b.json" }));
+        assert!(read(&a).contains("must be a list of files"), "{}", read(&a));
+    }
+}
@@ -3,9 +3,22 @@
 
 //! Configuration management for Panoptes
 
+pub mod env;
+pub mod layers;
+pub mod secrets;
+pub mod templates;
+
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::db::Role;
+use crate::history::UndoConflict;
+use crate::notifications::NotificationConfig;
+use crate::rules::ActionRule;
+use crate::schedule::ScheduleEntry;
+use crate::selection::{FileSelection, Selector};
+use crate::webhooks::WebhookEvent;
 
 /// Main application configuration
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -13,6 +26,10 @@ pub struct AppConfig {
     /// Directories to watch
     pub watch_paths: Vec<String>,
 
+    /// Per-directory options, keyed by entries of `watch_paths`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub watch_options: BTreeMap<String, WatchOptions>,
+
     /// AI engine configuration
     pub ai_engine: EngineConfig,
 
@@ -33,6 +50,144 @@ pub struct AppConfig {
     /// Database settings
     #[serde(default)]
     pub database: DatabaseConfig,
+
+    /// History and undo settings
+    #[serde(default)]
+    pub history: HistoryConfig,
+
+    /// Review queue settings
+    #[serde(default)]
+    pub review: ReviewConfig,
+
+    /// Thumbnail cache settings
+    #[serde(default)]
+    pub thumbnails: ThumbnailConfig,
+
+    /// Sorting files into per-category directories
+    #[serde(default)]
+    pub organize: OrganizeConfig,
+
+    /// URLs notified of processing events
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Desktop, ntfy, Slack, Discord and email notifications
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+
+    /// Background job queue
+    #[serde(default)]
+    pub jobs: JobConfig,
+
+    /// Tasks run at set times by watch and serve
+    #[serde(default)]
+    pub schedule: Vec<ScheduleEntry>,
+
+    /// Finding files alike (`panoptes similar`, "More like this")
+    #[serde(default)]
+    pub similarity: SimilarityConfig,
+
+    /// Calibrating analyzers' confidence by their signals and correction rates
+    #[serde(default)]
+    pub calibration: CalibrationConfig,
+
+    /// Moving files into a content-addressed vault instead of renaming them
+    #[serde(default)]
+    pub vault: VaultConfig,
+
+    /// Trees of symlinks to recorded files, by category, tag, date or any template
+    #[serde(default)]
+    pub views: ViewsConfig,
+
+    /// Forwarding files to a central server instead of analyzing them here
+    #[serde(default)]
+    pub agent: AgentConfig,
+
+    /// S3 buckets, WebDAV shares and SFTP directories whose new files are analyzed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<SourceConfig>,
+
+    /// Publishing events to an MQTT broker and taking commands from it
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+
+    /// The `org.panoptes.Scanner` D-Bus service, on Linux
+    #[serde(default)]
+    pub dbus: DbusConfig,
+
+    /// Metadata files written next to analyzed files
+    #[serde(default)]
+    pub sidecars: SidecarConfig,
+
+    /// Tags and category written to analyzed files' extended attributes
+    #[serde(default)]
+    pub xattrs: XattrConfig,
+
+    /// Tags and category shown by the operating system's file manager
+    #[serde(default)]
+    pub native_tags: NativeTagConfig,
+
+    /// Analyzer plugins
+    #[serde(default)]
+    pub plugins: PluginConfig,
+
+    /// How files of a type are analyzed, keyed by extension (`svg`) or MIME
+    /// type (`image/svg+xml`, `image/*`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub file_types: BTreeMap<String, FileTypeRule>,
+}
+
+/// One changed setting
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigChange {
+    /// Dotted path, e.g. `rules.max_length`
+    pub path: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+    /// Read once at startup, so only takes effect after a restart
+    pub restart_required: bool,
+}
+
+/// Settings the web server only reads at startup; everything else is read per request
+const RESTART_REQUIRED: &[&str] = &[
+    "web.enabled", "web.host", "web.port", "web.tls", "web.cors_origins",
+    "web.max_upload_mb", "web.auth.oidc", "web.base_path", "web.templates_dir", "database", "thumbnails",
+    "ai_engine.max_concurrent", "plugins", "jobs.workers", "jobs.retry_delay_secs", "jobs.keep_days", "schedule",
+];
+
+fn restart_required(path: &str) -> bool {
+    let under = |prefix: &str| path == prefix || path.starts_with(&format!("{}.", prefix));
+    RESTART_REQUIRED.iter().any(|p| under(p))
+        // The analyzer registry is built once
+        || (path.starts_with("analyzers.") && path.ends_with(".enabled"))
+        || path == "file_types" || path.starts_with("file_types.")
+}
+
+/// Collect changed leaves between two JSON values; arrays compare as a whole
+fn diff_values(path: &str, old: &serde_json::Value, new: &serde_json::Value, changes: &mut Vec<ConfigChange>) {
+    use serde_json::Value;
+
+    if let (Value::Object(old_map), Value::Object(new_map)) = (old, new) {
+        let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+            diff_values(
+                &child,
+                old_map.get(key).unwrap_or(&Value::Null),
+                new_map.get(key).unwrap_or(&Value::Null),
+                changes,
+            );
+        }
+    } else if old != new {
+        changes.push(ConfigChange {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+            restart_required: restart_required(path),
+        });
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -43,6 +198,9 @@ pub struct EngineConfig {
     pub timeout_secs: u64,
     #[serde(default = "default_retries")]
     pub retries: u32,
+    /// Most requests sent to the engine at once; further analyses wait their turn
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -52,6 +210,9 @@ pub struct ModelConfig {
     pub text: String,
     #[serde(default = "default_code_model")]
     pub code: String,
+    /// Turns documents' text into embeddings, for finding documents alike
+    #[serde(default = "default_embedding_model")]
+    pub embedding: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -61,8 +222,216 @@ pub struct RuleConfig {
     pub max_length: usize,
     #[serde(default)]
     pub auto_categorize: bool,
+    /// Name copies of recorded files as those were, without analyzing them
+    /// (see [`crate::duplicates`])
     #[serde(default)]
     pub duplicate_detection: bool,
+    /// What else becomes of copies
+    #[serde(default)]
+    pub duplicates: DuplicatePolicy,
+    /// Directory copies are moved into when `duplicates` is `move`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicates_dir: Option<String>,
+    /// Suggestions at or above this confidence are renamed automatically
+    #[serde(default = "default_auto_rename_threshold")]
+    pub auto_rename_threshold: f64,
+    /// Suggestions below this confidence are discarded rather than queued
+    /// for review or listed by `analyze`
+    #[serde(default)]
+    pub suggest_threshold: f64,
+    /// Where watch mode moves renamed files, by category or tag; the first
+    /// rule that matches wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<DestinationRule>,
+    /// Naming by category, e.g. `"Screenshots": {"template": "{date}_{time}_screenshot_{name}"}`,
+    /// applied once the analyzer has assigned a category
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub categories: BTreeMap<String, NamingRule>,
+    /// How analyzers clean up the names they suggest
+    #[serde(default)]
+    pub sanitizer: SanitizerConfig,
+    /// Keep names Windows and macOS take, even elsewhere: no reserved names
+    /// nor trailing dots, and none differing from another only in case (see
+    /// [`crate::portable`])
+    #[serde(default = "default_true")]
+    pub portable_names: bool,
+    /// What happens when a file's new name is taken (see [`crate::renamer`])
+    #[serde(default)]
+    pub collision: CollisionPolicy,
+    /// Leave files whose names already have the shape Panoptes gives out of
+    /// watch mode (see [`crate::skiplist`])
+    #[serde(default = "default_true")]
+    pub skip_conformant: bool,
+    /// What to do with analyzed files matching conditions (see [`crate::rules`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<ActionRule>,
+}
+
+/// How suggested names are cleaned up (see [`crate::analyzers::clean_filename`])
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct SanitizerConfig {
+    /// Characters kept besides letters, digits and spaces, e.g. `_-.` to keep
+    /// the dots of version numbers
+    #[serde(default = "default_sanitizer_allowed")]
+    pub allowed: String,
+    /// Joins the words in place of spaces; runs of it collapse to one
+    #[serde(default = "default_sanitizer_replacement")]
+    pub replacement: char,
+    #[serde(default = "default_sanitizer_casing")]
+    pub casing: Casing,
+    /// Words never made into tags, besides the built-in English ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_words: Vec<String>,
+    /// Most words of a suggestion kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_words: Option<usize>,
+    #[serde(default)]
+    pub normalization: Normalization,
+    /// Spell names in ASCII, `Ελένη` as `Elene`, but for `keep_scripts`
+    #[serde(default)]
+    pub transliterate: bool,
+    /// Scripts left as they are when transliterating, by their Unicode names,
+    /// e.g. `Latin` to keep accents, `Cyrillic` or `Han`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keep_scripts: Vec<String>,
+}
+
+impl Default for SanitizerConfig {
+    fn default() -> Self {
+        Self {
+            allowed: default_sanitizer_allowed(),
+            replacement: default_sanitizer_replacement(),
+            casing: default_sanitizer_casing(),
+            stop_words: Vec::new(),
+            max_words: None,
+            normalization: Normalization::default(),
+            transliterate: false,
+            keep_scripts: Vec::new(),
+        }
+    }
+}
+
+/// How files of one category are named
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct NamingRule {
+    /// Name built from `{name}`, `{date}`, `{time}`, `{category}` and the
+    /// analyzer's metadata, like `{artist}`, `{title}` or `{year}`; without
+    /// one the date prefix goes as `rules.date_prefix` says. `{counter}` is
+    /// where a taken name is numbered, at the end without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Where `{date}` and `{time}` come from
+    #[serde(default)]
+    pub date_source: DateSource,
+    /// In place of `rules.max_length`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub casing: Casing,
+}
+
+/// What happens when a file's new name is taken; never is the file there replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Numbered `_01`, `_02`..., or as `{counter}` in the template says
+    #[default]
+    Counter,
+    /// The start of the file's hash, `_3fa2c19b`, in place of the number;
+    /// numbered after it too if that is taken
+    Hash,
+    /// Leave the file as it is
+    Skip,
+    /// Fail the rename
+    Fail,
+}
+
+/// What becomes of a copy of a recorded file, besides taking its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    /// Nothing more
+    #[default]
+    Reuse,
+    /// Tagged `duplicate`
+    Tag,
+    /// Moved into `rules.duplicates_dir`
+    Move,
+    /// Replaced by a hard link to the earlier copy
+    Hardlink,
+}
+
+/// The date a name carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateSource {
+    /// When the file is renamed
+    #[default]
+    Now,
+    /// When the file was last modified
+    Modified,
+    /// When the file was created, or modified where that isn't known
+    Created,
+}
+
+/// How a name is cased
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Casing {
+    /// As the analyzer and template give it
+    #[default]
+    Keep,
+    Lower,
+    Upper,
+    /// `Each_Word_Capitalized`
+    Title,
+    /// Hyphens in place of underscores
+    Kebab,
+}
+
+impl Casing {
+    /// `name` cased this way
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            Casing::Keep => name.to_string(),
+            Casing::Lower => name.to_lowercase(),
+            Casing::Upper => name.to_uppercase(),
+            Casing::Kebab => name.replace('_', "-"),
+            Casing::Title => name.split_inclusive(['_', '-', ' '])
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The Unicode normalization form of names (see [`crate::naming`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Composed, as most systems write names
+    #[default]
+    Nfc,
+    /// Decomposed, as HFS+ stored names
+    Nfd,
+    /// Composed, with ligatures, full-width letters and the like replaced
+    Nfkc,
+    /// As given
+    Keep,
+}
+
+/// Files of a category, or with a tag, go to a directory (both must match
+/// when both are given)
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct DestinationRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Where the files go; a leading `~` is the home directory
+    pub directory: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -77,6 +446,37 @@ pub struct PromptConfig {
     pub code: String,
     #[serde(default = "default_archive_prompt")]
     pub archive: String,
+    /// Asked of the vision model with a comic's cover
+    #[serde(default = "default_comic_prompt")]
+    pub comic: String,
+    /// Asked of the text model with the first chapter of an ebook without a title
+    #[serde(default = "default_ebook_prompt")]
+    pub ebook: String,
+    /// Asked of the text model with the body of an email without a subject
+    #[serde(default = "default_email_prompt")]
+    pub email: String,
+    /// Asked of the vision model to read an image's text, with
+    /// `analyzers.image.ocr_engine = "vision"`
+    #[serde(default = "default_ocr_prompt")]
+    pub ocr: String,
+    /// Asked of the text model with the text read from an image
+    #[serde(default = "default_image_text_prompt")]
+    pub image_text: String,
+    /// Asked of the text model with the names suggested for a directory's files
+    #[serde(default = "default_folder_prompt")]
+    pub folder: String,
+    /// Asked of the text model for an overview of a directory's contents
+    #[serde(default = "default_summary_prompt")]
+    pub summary: String,
+    /// Asked of the text model with the title, description and text of an SVG
+    #[serde(default = "default_svg_prompt")]
+    pub svg: String,
+    /// Asked of the text model to sum up the dialogue of subtitles
+    #[serde(default = "default_subtitle_summary_prompt")]
+    pub subtitle_summary: String,
+    /// Asked of the text model with that summary, to name the film or episode
+    #[serde(default = "default_subtitle_prompt")]
+    pub subtitle: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -91,6 +491,16 @@ pub struct AnalyzerConfig {
     pub video: VideoAnalyzerConfig,
     #[serde(default)]
     pub code: CodeAnalyzerConfig,
+    #[serde(default)]
+    pub comic: ComicAnalyzerConfig,
+    #[serde(default)]
+    pub ebook: EbookAnalyzerConfig,
+    #[serde(default)]
+    pub email: EmailAnalyzerConfig,
+    #[serde(default)]
+    pub svg: SvgAnalyzerConfig,
+    #[serde(default)]
+    pub subtitle: SubtitleAnalyzerConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -99,6 +509,29 @@ pub struct ImageAnalyzerConfig {
     pub enabled: bool,
     #[serde(default)]
     pub formats: Vec<String>,
+    /// Read the text in images, naming screenshots and scans from it
+    #[serde(default)]
+    pub ocr: bool,
+    /// What reads the text
+    #[serde(default)]
+    pub ocr_engine: OcrEngine,
+    /// Tesseract's command line program
+    #[serde(default = "default_tesseract_command")]
+    pub tesseract_command: String,
+    /// Tesseract's languages to read, e.g. `eng+deu`; its default when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ocr_language: Option<String>,
+}
+
+/// What reads the text in images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrEngine {
+    /// Tesseract, run as `analyzers.image.tesseract_command`
+    #[default]
+    Tesseract,
+    /// The vision model, asked `prompts.ocr`
+    Vision,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -117,8 +550,22 @@ pub struct AudioAnalyzerConfig {
     pub enabled: bool,
     #[serde(default = "default_true")]
     pub use_metadata: bool,
+    /// Transcribe recordings with whisper.cpp (see [`crate::transcribe`])
     #[serde(default)]
     pub transcribe: bool,
+    /// whisper.cpp's command line program
+    #[serde(default = "default_whisper_command")]
+    pub whisper_command: String,
+    /// The ggml model file whisper.cpp transcribes with; a leading `~` is the
+    /// home directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whisper_model: Option<String>,
+    /// Language spoken, e.g. `en`; detected when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Seconds of a recording transcribed at a time
+    #[serde(default = "default_chunk_secs")]
+    pub chunk_secs: u32,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -137,6 +584,46 @@ pub struct CodeAnalyzerConfig {
     pub languages: Vec<String>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ComicAnalyzerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Show the vision model the cover of comics without a ComicInfo.xml
+    #[serde(default = "default_true")]
+    pub read_cover: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EbookAnalyzerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Show the text model the first chapter of ebooks without a title in
+    /// their metadata
+    #[serde(default = "default_true")]
+    pub read_text: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EmailAnalyzerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Show the text model the body of emails without a subject
+    #[serde(default = "default_true")]
+    pub read_body: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SvgAnalyzerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SubtitleAnalyzerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct WebConfig {
     #[serde(default = "default_true")]
@@ -145,6 +632,270 @@ pub struct WebConfig {
     pub host: String,
     #[serde(default = "default_web_port")]
     pub port: u16,
+    /// Directory uploads are saved into (under their suggested name) when requested
+    #[serde(default)]
+    pub inbox: Option<String>,
+    /// Largest accepted upload, in megabytes
+    #[serde(default = "default_max_upload_mb")]
+    pub max_upload_mb: usize,
+    /// Origins allowed to call the API cross-origin (none by default)
+    #[serde(default)]
+    pub cors_origins: Vec<String>,
+    /// Authentication settings
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Serve HTTPS instead of plain HTTP
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Path prefix the UI is served under behind a reverse proxy, e.g. `/panoptes`
+    #[serde(default)]
+    pub base_path: String,
+    /// Templates here (and assets under its `static/`) replace the built-in ones
+    #[serde(default = "default_templates_dir")]
+    pub templates_dir: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TlsConfig {
+    /// PEM certificate chain
+    pub cert_path: String,
+    /// PEM private key
+    pub key_path: String,
+    /// Generate a self-signed certificate at these paths if they don't exist yet
+    #[serde(default)]
+    pub self_signed: bool,
+    /// Host names and IP addresses a generated certificate is valid for
+    #[serde(default = "default_tls_names")]
+    pub self_signed_names: Vec<String>,
+    /// Also listen for plain HTTP on this port and redirect it to HTTPS
+    #[serde(default)]
+    pub redirect_port: Option<u16>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AuthConfig {
+    /// Require a token or login session for the UI and API
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Static API tokens (in addition to ones created with `panoptes token create`)
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// Let requests from the loopback interface through without credentials.
+    /// Disable this when running behind a reverse proxy on the same host.
+    #[serde(default = "default_true")]
+    pub allow_localhost: bool,
+    /// How long a login session lasts, in hours
+    #[serde(default = "default_session_hours")]
+    pub session_hours: u64,
+    /// Optional OpenID Connect login
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OidcConfig {
+    /// Issuer URL; `/.well-known/openid-configuration` is fetched from here
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Must point at `/auth/oidc/callback` on this server and be registered with the provider
+    pub redirect_url: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// Shown on the login button
+    #[serde(default = "default_oidc_provider_name")]
+    pub provider_name: String,
+    /// Create a Panoptes user on first login; otherwise identities must be linked already
+    #[serde(default = "default_true")]
+    pub auto_create: bool,
+    /// Role given to users created on login (the very first user is always an admin)
+    #[serde(default = "default_oidc_role")]
+    pub default_role: Role,
+    /// Only accept accounts whose verified email is in one of these domains (empty = any)
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+}
+
+/// How files in a watch directory are handled
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct WatchOptions {
+    /// Also watch subdirectories
+    #[serde(default)]
+    pub recursive: bool,
+    /// Analyze and record files without renaming them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Move renamed files to this directory instead of renaming them in place
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    /// Sort renamed files into category directories (see [`OrganizeConfig`])
+    #[serde(default)]
+    pub organize: bool,
+    /// Which files to process: `extensions`, `exclude`, `min_size`,
+    /// `max_size`, `newer_than` and `max_depth`
+    #[serde(flatten)]
+    pub select: FileSelection,
+}
+
+/// Sidecar files carrying a file's suggestion, tags and category with it (see
+/// [`crate::sidecar`])
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SidecarConfig {
+    /// Write a sidecar next to each file analyzed
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub format: SidecarFormat,
+}
+
+/// How sidecars are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarFormat {
+    /// `photo.jpg.panoptes.json`
+    #[default]
+    Json,
+    /// `photo.jpg.xmp` for images, read by photo managers; other files get JSON
+    Xmp,
+}
+
+/// Extended attributes carrying a file's tags and category (see [`crate::xattrs`])
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct XattrConfig {
+    /// Write tags and category to each file analyzed, and check them in `panoptes verify`
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Where analyzer plugins are installed (see [`crate::plugins`])
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PluginConfig {
+    /// Directory holding a subdirectory per plugin
+    #[serde(default = "default_plugin_dir")]
+    pub directory: String,
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self { directory: default_plugin_dir() }
+    }
+}
+
+/// How files of one type are analyzed, overriding the usual choices
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FileTypeRule {
+    /// Analyzer used instead of the one for the extension, e.g. `code` or a plugin's name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analyzer: Option<String>,
+    /// Category given instead of the analyzer's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Leave the files alone: no analysis, renaming or moving
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip: bool,
+    /// Prompt the analyzer asks the AI engine with instead of its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+}
+
+impl FileTypeRule {
+    /// The rule of `rules` for `path`: the one for its extension, else its
+    /// MIME type, else all types like it (`image/*`)
+    pub fn find<'a>(rules: &'a BTreeMap<String, FileTypeRule>, path: &Path) -> Option<&'a FileTypeRule> {
+        if rules.is_empty() {
+            return None;
+        }
+        let keyed = |key: &str| rules.iter()
+            .find(|(k, _)| k.trim_start_matches('.').eq_ignore_ascii_case(key))
+            .map(|(_, rule)| rule);
+        let ext = path.extension()?.to_str()?;
+        keyed(ext)
+            .or_else(|| {
+                let mime = mime_guess::from_ext(ext).first()?;
+                keyed(mime.essence_str()).or_else(|| keyed(&format!("{}/*", mime.type_())))
+            })
+    }
+}
+
+/// Finder tags on macOS and file properties on Windows (see [`crate::native_tags`])
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct NativeTagConfig {
+    /// Tag each file analyzed
+    #[serde(default)]
+    pub enabled: bool,
+    /// How each category is tagged, e.g. `"Screenshot": {"color": "blue"}`;
+    /// categories not listed get a plain tag named after them
+    #[serde(default)]
+    pub categories: BTreeMap<String, CategoryTags>,
+}
+
+/// How files in one category are tagged
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct CategoryTags {
+    /// Finder color of the category's tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<TagColor>,
+    /// Tags in place of the category's name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Windows Comments property in place of the category's name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Finder tag colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagColor {
+    Gray,
+    Green,
+    Purple,
+    Blue,
+    Yellow,
+    Red,
+    Orange,
+}
+
+impl TagColor {
+    /// Finder's number for the color
+    pub fn index(self) -> u8 {
+        match self {
+            Self::Gray => 1,
+            Self::Green => 2,
+            Self::Purple => 3,
+            Self::Blue => 4,
+            Self::Yellow => 5,
+            Self::Red => 6,
+            Self::Orange => 7,
+        }
+    }
+}
+
+/// Where `panoptes organize` and the watch `organize` option put each category
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct OrganizeConfig {
+    /// Category → directory, e.g. `"Screenshot": "Screenshots"`. Relative
+    /// directories are inside the directory being organized; categories not
+    /// listed go to a directory named after the category.
+    #[serde(default)]
+    pub destinations: BTreeMap<String, String>,
+    /// Directory for files without a category (left in place when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uncategorized: Option<String>,
+    /// What to do when the destination already has a file of the same name
+    #[serde(default)]
+    pub on_collision: OrganizeCollision,
+}
+
+/// Handling of a name already taken in the destination directory
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrganizeCollision {
+    /// Add a numeric suffix: `name_2.ext`, `name_3.ext`, ...
+    #[default]
+    Suffix,
+    /// Leave the file where it is
+    Skip,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -153,16 +904,94 @@ pub struct DatabaseConfig {
     pub path: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct HistoryConfig {
+    /// How undo resolves an occupied original path (skip, overwrite, suffix, prompt)
+    #[serde(default)]
+    pub undo_conflict: UndoConflict,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReviewConfig {
+    /// Queue low-confidence suggestions for approval instead of skipping them
+    #[serde(default)]
+    pub enabled: bool,
+    /// Suggestions at or above this confidence are applied without review
+    /// while the review queue is active, in place of
+    /// `rules.auto_rename_threshold`; `null` goes by that instead
+    #[serde(default = "default_auto_apply_threshold")]
+    pub auto_apply_threshold: Option<f64>,
+    /// Move files queued for review into this directory until they are
+    /// approved or rejected; relative to each file's directory (e.g.
+    /// `_review`) unless absolute. Setting it queues for review without `enabled`.
+    #[serde(default)]
+    pub quarantine_dir: Option<String>,
+    /// Send a `review_queue` webhook each time this many more files are
+    /// waiting for review; 0 never does
+    #[serde(default)]
+    pub remind_every: usize,
+}
+
+impl ReviewConfig {
+    /// Whether low-confidence suggestions go to the review queue rather than being skipped
+    pub fn is_active(&self) -> bool {
+        self.enabled || self.quarantine_dir.is_some()
+    }
+}
+
 // Default value functions
 fn default_timeout() -> u64 { 120 }
 fn default_retries() -> u32 { 3 }
+fn default_max_concurrent() -> usize { 2 }
 fn default_text_model() -> String { "llama3.2:3b".to_string() }
 fn default_code_model() -> String { "deepseek-coder:1.3b".to_string() }
+fn default_embedding_model() -> String { "nomic-embed-text".to_string() }
 fn default_true() -> bool { true }
 fn default_keyframes() -> u32 { 5 }
+fn default_whisper_command() -> String { "whisper-cli".to_string() }
+fn default_tesseract_command() -> String { "tesseract".to_string() }
+fn default_chunk_secs() -> u32 { 600 }
 fn default_web_host() -> String { "127.0.0.1".to_string() }
 fn default_web_port() -> u16 { 8080 }
-fn default_db_path() -> String { "panoptes.db".to_string() }
+fn default_max_upload_mb() -> usize { 100 }
+fn default_templates_dir() -> String { "templates".to_string() }
+fn default_session_hours() -> u64 { 24 * 7 }
+fn default_tls_names() -> Vec<String> {
+    vec!["localhost".to_string(), "127.0.0.1".to_string()]
+}
+fn default_oidc_provider_name() -> String { "single sign-on".to_string() }
+fn default_oidc_role() -> Role { Role::Viewer }
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".to_string(), "profile".to_string(), "email".to_string()]
+}
+fn default_db_path() -> String { crate::paths::data_path("panoptes.db") }
+fn default_auto_rename_threshold() -> f64 { 0.5 }
+fn default_auto_apply_threshold() -> Option<f64> { Some(0.8) }
+fn default_thumbnail_dir() -> String { crate::paths::data_path("thumbnails") }
+fn default_sanitizer_allowed() -> String { "_-".to_string() }
+fn default_sanitizer_replacement() -> char { '_' }
+fn default_sanitizer_casing() -> Casing { Casing::Lower }
+fn default_plugin_dir() -> String { crate::paths::data_path("plugins") }
+fn default_thumbnail_size() -> u32 { 256 }
+fn default_thumbnail_cache_mb() -> u64 { 200 }
+fn default_webhook_retries() -> u32 { 5 }
+fn default_job_workers() -> usize { 2 }
+fn default_job_attempts() -> u32 { 5 }
+fn default_job_retry_delay() -> u64 { 30 }
+fn default_job_keep_days() -> u32 { 7 }
+fn default_similarity_min_score() -> f64 { 0.85 }
+fn default_similarity_limit() -> usize { 10 }
+fn default_calibration_prior_files() -> u32 { 20 }
+fn default_views_refresh_secs() -> u64 { 60 }
+fn default_agent_max_upload_mb() -> u32 { 50 }
+fn default_agent_timeout_secs() -> u64 { 300 }
+fn default_source_region() -> String { "us-east-1".to_string() }
+fn default_source_interval_mins() -> u64 { 15 }
+fn default_source_max_size_mb() -> u64 { 100 }
+fn default_mqtt_topic_prefix() -> String { "panoptes".to_string() }
+fn default_mqtt_discovery_prefix() -> String { "homeassistant".to_string() }
+fn default_mqtt_state_interval_secs() -> u64 { 60 }
 
 fn default_audio_prompt() -> String {
     "Based on this audio metadata, suggest a descriptive filename (max 5 words). \
@@ -184,19 +1013,81 @@ fn default_archive_prompt() -> String {
      Use snake_case. Return ONLY the filename.".to_string()
 }
 
+fn default_comic_prompt() -> String {
+    "This is the cover of a comic book. Give its series and issue number, \
+     like: Saga 12. Return ONLY the series and number.".to_string()
+}
+
+fn default_ebook_prompt() -> String {
+    "This is the start of a book. Give its author, if it says, and a title \
+     for it (max 6 words), like: jane_austen_pride_and_prejudice. Use snake_case. \
+     Return ONLY the filename.".to_string()
+}
+
+fn default_email_prompt() -> String {
+    "This is the body of an email. Give what it is about (max 5 words), \
+     like: invoice_for_march. Use snake_case. Return ONLY the filename.".to_string()
+}
+
+fn default_ocr_prompt() -> String {
+    "Transcribe all the text visible in this image, line by line. \
+     Return ONLY the text, or nothing if there is none.".to_string()
+}
+
+fn default_image_text_prompt() -> String {
+    "This is the text read from an image, such as a screenshot, receipt or \
+     whiteboard. Give what it is (max 5 words), like: receipt_hardware_store or \
+     error_disk_full. Use snake_case. Return ONLY the filename.".to_string()
+}
+
+fn default_folder_prompt() -> String {
+    "These are names suggested for the files in a folder. Give the folder a name \
+     saying what it holds (max 5 words), like: wedding_photos_lisbon or \
+     rust_web_server. Use snake_case. Return ONLY the name.".to_string()
+}
+
+fn default_summary_prompt() -> String {
+    "This describes the contents of a directory. Write a short overview of it \
+     (3-5 sentences) for someone taking it over: what it seems to be for, the \
+     main kinds of files, notable documents and the period it covers. \
+     Return ONLY the overview.".to_string()
+}
+
+fn default_svg_prompt() -> String {
+    "This is the title, description and text of a vector image, such as an \
+     icon, logo or diagram. Give what it shows (max 5 words), like: \
+     network_diagram_office or logo_acme. Use snake_case. Return ONLY the filename.".to_string()
+}
+
+fn default_subtitle_summary_prompt() -> String {
+    "This is dialogue from the subtitles of a film or TV episode. Sum up in \
+     2-3 sentences who is in it and what happens, naming the characters and \
+     places. Return ONLY the summary.".to_string()
+}
+
+fn default_subtitle_prompt() -> String {
+    "This summarizes the subtitles of a film or TV episode. Give the title of \
+     the film with its year, or of the show with its season and episode, if \
+     you can tell, like: the_matrix_1999 or breaking_bad_s01e03; otherwise \
+     what it is about (max 5 words). Use snake_case. Return ONLY the filename.".to_string()
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             watch_paths: vec!["./watch".to_string()],
+            watch_options: BTreeMap::new(),
             ai_engine: EngineConfig {
                 url: "http://localhost:11434/api/generate".to_string(),
                 models: ModelConfig {
                     vision: "moondream".to_string(),
                     text: default_text_model(),
                     code: default_code_model(),
+                    embedding: default_embedding_model(),
                 },
                 timeout_secs: default_timeout(),
                 retries: default_retries(),
+                max_concurrent: default_max_concurrent(),
             },
             rules: RuleConfig {
                 sanitize: true,
@@ -204,6 +1095,17 @@ fn default() -> Self {
                 max_length: 50,
                 auto_categorize: true,
                 duplicate_detection: true,
+                duplicates: DuplicatePolicy::default(),
+                duplicates_dir: None,
+                auto_rename_threshold: default_auto_rename_threshold(),
+                suggest_threshold: 0.0,
+                destinations: Vec::new(),
+                categories: BTreeMap::new(),
+                sanitizer: SanitizerConfig::default(),
+                portable_names: true,
+                collision: CollisionPolicy::default(),
+                skip_conformant: true,
+                actions: Vec::new(),
             },
             prompts: PromptConfig {
                 image: "Analyze this image and generate a concise, descriptive filename \
@@ -215,10 +1117,41 @@ fn default() -> Self {
                 video: default_video_prompt(),
                 code: default_code_prompt(),
                 archive: default_archive_prompt(),
+                comic: default_comic_prompt(),
+                ebook: default_ebook_prompt(),
+                email: default_email_prompt(),
+                ocr: default_ocr_prompt(),
+                image_text: default_image_text_prompt(),
+                folder: default_folder_prompt(),
+                summary: default_summary_prompt(),
+                svg: default_svg_prompt(),
+                subtitle_summary: default_subtitle_summary_prompt(),
+                subtitle: default_subtitle_prompt(),
             },
             analyzers: AnalyzerConfig::default(),
             web: WebConfig::default(),
             database: DatabaseConfig::default(),
+            history: HistoryConfig::default(),
+            review: ReviewConfig::default(),
+            thumbnails: ThumbnailConfig::default(),
+            organize: OrganizeConfig::default(),
+            webhooks: Vec::new(),
+            notifications: NotificationConfig::default(),
+            jobs: JobConfig::default(),
+            schedule: Vec::new(),
+            similarity: SimilarityConfig::default(),
+            calibration: CalibrationConfig::default(),
+            vault: VaultConfig::default(),
+            views: ViewsConfig::default(),
+            agent: AgentConfig::default(),
+            sources: Vec::new(),
+            mqtt: MqttConfig::default(),
+            dbus: DbusConfig::default(),
+            sidecars: SidecarConfig::default(),
+            xattrs: XattrConfig::default(),
+            native_tags: NativeTagConfig::default(),
+            plugins: PluginConfig::default(),
+            file_types: BTreeMap::new(),
         }
     }
 }
@@ -231,6 +1164,10 @@ fn default() -> Self {
                 "jpg", "jpeg", "png", "webp", "gif", "bmp", "tiff", "tif",
                 "heic", "heif", "avif", "svg"
             ].into_iter().map(String::from).collect(),
+            ocr: false,
+            ocr_engine: OcrEngine::default(),
+            tesseract_command: default_tesseract_command(),
+            ocr_language: None,
         }
     }
 }
@@ -251,6 +1188,10 @@ fn default() -> Self {
             enabled: true,
             use_metadata: true,
             transcribe: false,
+            whisper_command: default_whisper_command(),
+            whisper_model: None,
+            language: None,
+            chunk_secs: default_chunk_secs(),
         }
     }
 }
@@ -274,12 +1215,83 @@ fn default() -> Self {
     }
 }
 
+impl Default for ComicAnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            read_cover: true,
+        }
+    }
+}
+
+impl Default for EbookAnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            read_text: true,
+        }
+    }
+}
+
+impl Default for EmailAnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            read_body: true,
+        }
+    }
+}
+
+impl Default for SvgAnalyzerConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl Default for SubtitleAnalyzerConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
 impl Default for WebConfig {
     fn default() -> Self {
         Self {
             enabled: true,
             host: default_web_host(),
             port: default_web_port(),
+            inbox: None,
+            max_upload_mb: default_max_upload_mb(),
+            cors_origins: Vec::new(),
+            auth: AuthConfig::default(),
+            tls: None,
+            base_path: String::new(),
+            templates_dir: default_templates_dir(),
+        }
+    }
+}
+
+impl WebConfig {
+    /// Address the dashboard is reachable at, e.g. `https://127.0.0.1:8080/`
+    pub fn url(&self) -> String {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        format!("{}://{}:{}{}/", scheme, self.host, self.port, self.base_path())
+    }
+
+    /// `base_path` without a trailing slash; empty when served from the root
+    pub fn base_path(&self) -> &str {
+        self.base_path.trim_end_matches('/')
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tokens: Vec::new(),
+            allow_localhost: true,
+            session_hours: default_session_hours(),
+            oidc: None,
         }
     }
 }
@@ -292,24 +1304,588 @@ fn default() -> Self {
     }
 }
 
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            auto_apply_threshold: default_auto_apply_threshold(),
+            quarantine_dir: None,
+            remind_every: 0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ThumbnailConfig {
+    /// Directory generated thumbnails are cached in
+    #[serde(default = "default_thumbnail_dir")]
+    pub cache_dir: String,
+    /// Longest edge of a thumbnail, in pixels
+    #[serde(default = "default_thumbnail_size")]
+    pub size: u32,
+    /// Least recently used thumbnails are removed beyond this size
+    #[serde(default = "default_thumbnail_cache_mb")]
+    pub max_cache_mb: u64,
+    /// Make thumbnails of analyzed files in the background, rather than
+    /// when they are first shown
+    #[serde(default)]
+    pub pregenerate: bool,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            cache_dir: default_thumbnail_dir(),
+            size: default_thumbnail_size(),
+            max_cache_mb: default_thumbnail_cache_mb(),
+            pregenerate: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct JobConfig {
+    /// Jobs done at once by `panoptes watch` and `panoptes serve`
+    #[serde(default = "default_job_workers")]
+    pub workers: usize,
+    /// Attempts at a job before it is given up as dead
+    #[serde(default = "default_job_attempts")]
+    pub max_attempts: u32,
+    /// Wait before the first retry of a failed job, doubling with each
+    /// further one, up to an hour
+    #[serde(default = "default_job_retry_delay")]
+    pub retry_delay_secs: u64,
+    /// Days finished and cancelled jobs are kept; dead ones stay until retried or cleared
+    #[serde(default = "default_job_keep_days")]
+    pub keep_days: u32,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        Self {
+            workers: default_job_workers(),
+            max_attempts: default_job_attempts(),
+            retry_delay_secs: default_job_retry_delay(),
+            keep_days: default_job_keep_days(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SimilarityConfig {
+    /// Compare documents by embeddings of their text from
+    /// `ai_engine.models.embedding`, besides by contents and looks
+    #[serde(default = "default_true")]
+    pub embeddings: bool,
+    /// How alike, from 0 to 1, files must be to be listed
+    #[serde(default = "default_similarity_min_score")]
+    pub min_score: f64,
+    /// Most files listed
+    #[serde(default = "default_similarity_limit")]
+    pub limit: usize,
+}
+
+impl Default for SimilarityConfig {
+    fn default() -> Self {
+        Self {
+            embeddings: true,
+            min_score: default_similarity_min_score(),
+            limit: default_similarity_limit(),
+        }
+    }
+}
+
+/// Calibrated confidence (see [`crate::calibration`])
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CalibrationConfig {
+    /// Adjust confidence by how much the analyzer had to go on
+    #[serde(default = "default_true")]
+    pub signals: bool,
+    /// Draw confidence toward how often the analyzer and model's suggestions
+    /// were left uncorrected
+    #[serde(default = "default_true")]
+    pub history: bool,
+    /// Files of an analyzer and model for their correction rate to count as
+    /// much as the analyzer's own confidence
+    #[serde(default = "default_calibration_prior_files")]
+    pub prior_files: u32,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            signals: true,
+            history: true,
+            prior_files: default_calibration_prior_files(),
+        }
+    }
+}
+
+/// A content-addressed vault (see [`crate::vault`])
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct VaultConfig {
+    /// Directory files are moved into under their hash instead of being
+    /// renamed; no vault when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// Make files read-only once in the vault
+    #[serde(default = "default_true")]
+    pub read_only: bool,
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        Self { directory: None, read_only: true }
+    }
+}
+
+/// Trees of symlinks to recorded files (see [`crate::views`])
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ViewsConfig {
+    /// Directory the trees are built in; no views when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// The trees, each in a directory of its name; by category, tag and year when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trees: Vec<ViewTree>,
+    /// Seconds between bringing the views up to date while watching, when the
+    /// database changed; 0 leaves it to `panoptes views build`
+    #[serde(default = "default_views_refresh_secs")]
+    pub refresh_secs: u64,
+}
+
+impl Default for ViewsConfig {
+    fn default() -> Self {
+        Self { directory: None, trees: Vec::new(), refresh_secs: default_views_refresh_secs() }
+    }
+}
+
+/// Running as an agent of a central server (see [`crate::agent`])
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AgentConfig {
+    /// Server files are forwarded to, e.g. `https://panoptes.lan:8080`; not
+    /// an agent when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// The agent's name on the server; the host name when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The agent's API token, given by `panoptes agent register`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Largest file uploaded whole, in MB; documents only send their text
+    #[serde(default = "default_agent_max_upload_mb")]
+    pub max_upload_mb: u32,
+    /// Seconds to wait for the server to analyze a file
+    #[serde(default = "default_agent_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            server: None,
+            name: None,
+            token: None,
+            max_upload_mb: default_agent_max_upload_mb(),
+            timeout_secs: default_agent_timeout_secs(),
+        }
+    }
+}
+
+/// An MQTT broker for home automation (see [`crate::mqtt`])
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MqttConfig {
+    /// Broker to connect to, e.g. `mqtt://homeassistant.lan:1883`; off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broker: Option<String>,
+    /// Topics are published and taken under this
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+    /// Client ID; `panoptes-` and the host name when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Announce Panoptes to Home Assistant as a device
+    #[serde(default = "default_true")]
+    pub discovery: bool,
+    /// Home Assistant's discovery topic prefix
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+    /// Seconds between publications of the queue depth and files today
+    #[serde(default = "default_mqtt_state_interval_secs")]
+    pub state_interval_secs: u64,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            topic_prefix: default_mqtt_topic_prefix(),
+            client_id: None,
+            username: None,
+            password: None,
+            discovery: true,
+            discovery_prefix: default_mqtt_discovery_prefix(),
+            state_interval_secs: default_mqtt_state_interval_secs(),
+        }
+    }
+}
+
+/// The D-Bus service of `panoptes watch` (see [`crate::dbus`])
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct DbusConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub bus: DbusBus,
+}
+
+/// The bus the D-Bus service is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbusBus {
+    /// The desktop session's, for the user's own files
+    #[default]
+    Session,
+    /// The system bus, for a system service; needs a policy allowing the name
+    System,
+}
+
+/// A remote directory whose new files are analyzed (see [`crate::sources`])
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SourceConfig {
+    /// Name it goes by in `panoptes sources` and the database
+    pub name: String,
+    /// `s3://bucket/prefix`, `sftp://user@host/path`, or the `https://` URL
+    /// of a WebDAV directory
+    pub url: String,
+    /// S3 endpoint other than AWS's, e.g. `https://minio.lan:9000`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// S3 region
+    #[serde(default = "default_source_region")]
+    pub region: String,
+    /// S3 access key; `AWS_ACCESS_KEY_ID` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key: Option<String>,
+    /// S3 secret key; `AWS_SECRET_ACCESS_KEY` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key: Option<String>,
+    /// WebDAV user name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// WebDAV password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// SSH key for SFTP; ssh's own keys and agent otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<String>,
+    /// Include subdirectories
+    #[serde(default)]
+    pub recursive: bool,
+    /// Rename files on the source; otherwise renames are only planned, to be
+    /// seen with `panoptes sources plan` and done with `panoptes sources apply`
+    #[serde(default)]
+    pub rename: bool,
+    /// Minutes between looks for new files while watching; 0 leaves it to
+    /// `panoptes sources sync`
+    #[serde(default = "default_source_interval_mins")]
+    pub interval_mins: u64,
+    /// Larger files are left alone, in MB
+    #[serde(default = "default_source_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Where files are downloaded to for analysis; the temporary directory when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staging_dir: Option<String>,
+}
+
+/// One tree of symlinks
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ViewTree {
+    /// Directory of the tree in `views.directory`, e.g. `ByCategory`
+    pub name: String,
+    /// Path of each file's link in the tree, e.g. `{category}/{year}-{month}_{name}`
+    pub template: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events sent to this URL (renamed, low_confidence, error, review_queue, rule); all of them when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// Key for the HMAC-SHA256 `X-Panoptes-Signature` header; unsigned without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// Retries after a failed delivery, waiting twice as long each time
+    #[serde(default = "default_webhook_retries")]
+    pub max_retries: u32,
+}
+
 impl AppConfig {
-    /// Load configuration from a JSON file
+    /// Load the configuration of the project whose JSON file is `path`, over
+    /// the machine's and the user's and under the environment's (see [`layers`])
     pub fn load(path: &Path) -> crate::Result<Self> {
-        if path.exists() {
-            let content = std::fs::read_to_string(path)?;
-            let config: Self = serde_json::from_str(&content)
-                .map_err(|e| crate::PanoptesError::Config(format!("Failed to parse config: {}", e)))?;
-            Ok(config)
+        Ok(layers::resolve(path)?.0)
+    }
+
+    /// Options for the watch directory `path`
+    pub fn watch_options(&self, path: &str) -> WatchOptions {
+        self.watch_options.get(path).cloned().unwrap_or_default()
+    }
+
+    /// Where renamed files from `file`'s watch directory go, when not renamed in place
+    pub fn watch_destination(&self, file: &Path) -> Option<PathBuf> {
+        self.watch_paths.iter()
+            .map(|dir| (Path::new(dir), self.watch_options(dir)))
+            .filter(|(dir, options)| if options.recursive { file.starts_with(dir) } else { file.parent() == Some(*dir) })
+            .max_by_key(|(dir, _)| dir.components().count())
+            .and_then(|(_, options)| options.destination.map(PathBuf::from))
+    }
+
+    /// Copy of this configuration using `model` for every analyzer and/or `prompt` for every file type
+    pub fn with_overrides(&self, model: Option<&str>, prompt: Option<&str>) -> Self {
+        let mut config = self.clone();
+        if let Some(model) = model {
+            let models = &mut config.ai_engine.models;
+            models.vision = model.to_string();
+            models.text = model.to_string();
+            models.code = model.to_string();
+        }
+        if let Some(prompt) = prompt {
+            let prompts = &mut config.prompts;
+            for p in [&mut prompts.image, &mut prompts.document, &mut prompts.audio,
+                      &mut prompts.video, &mut prompts.code, &mut prompts.archive, &mut prompts.comic,
+                      &mut prompts.ebook, &mut prompts.email, &mut prompts.image_text,
+                      &mut prompts.folder, &mut prompts.svg, &mut prompts.subtitle] {
+                *p = prompt.to_string();
+            }
+        }
+        config
+    }
+
+    /// Everything wrong with this configuration (empty when it is usable)
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, message: &str| {
+            if !ok {
+                problems.push(message.to_string());
+            }
+        };
+
+        check(self.watch_paths.iter().all(|p| !p.trim().is_empty()), "watch_paths must not contain empty paths");
+        check(self.watch_options.keys().all(|p| self.watch_paths.contains(p)),
+            "watch_options must only name directories in watch_paths");
+        check(self.watch_options.values().all(|o| !o.destination.as_ref().is_some_and(|d| d.trim().is_empty())),
+            "watch_options[].destination must not be empty");
+        for (dir, options) in &self.watch_options {
+            if let Err(crate::PanoptesError::Config(message)) = Selector::new(&options.select) {
+                check(false, &format!("watch_options[{}]: {}", dir, message));
+            }
+        }
+
+        let engine = &self.ai_engine;
+        check(engine.url.starts_with("http://") || engine.url.starts_with("https://"),
+            "ai_engine.url must be an http:// or https:// URL");
+        check(!engine.models.vision.trim().is_empty(), "ai_engine.models.vision must not be empty");
+        check(!engine.models.text.trim().is_empty(), "ai_engine.models.text must not be empty");
+        check(!engine.models.code.trim().is_empty(), "ai_engine.models.code must not be empty");
+        check(!engine.models.embedding.trim().is_empty(), "ai_engine.models.embedding must not be empty");
+        check(engine.timeout_secs > 0, "ai_engine.timeout_secs must be greater than 0");
+        check(engine.max_concurrent > 0, "ai_engine.max_concurrent must be greater than 0");
+
+        check(self.rules.max_length >= 8, "rules.max_length must be at least 8");
+        check(self.rules.duplicates != DuplicatePolicy::Move || self.rules.duplicates_dir.as_ref().is_some_and(|d| !d.trim().is_empty()),
+            "rules.duplicates_dir must be set to move duplicates");
+        check((0.0..=1.0).contains(&self.rules.auto_rename_threshold), "rules.auto_rename_threshold must be between 0 and 1");
+        check((0.0..=1.0).contains(&self.rules.suggest_threshold), "rules.suggest_threshold must be between 0 and 1");
+        check(self.rules.suggest_threshold <= self.rules.auto_rename_threshold,
+            "rules.suggest_threshold must not be above rules.auto_rename_threshold");
+        check(self.rules.destinations.iter().all(|d| d.category.is_some() || d.tag.is_some()),
+            "rules.destinations[] need a category or a tag");
+        check(self.rules.destinations.iter().all(|d| !d.directory.trim().is_empty()),
+            "rules.destinations[].directory must not be empty");
+        for (category, rule) in &self.rules.categories {
+            check(!rule.template.as_ref().is_some_and(|t| t.trim().is_empty()),
+                &format!("rules.categories[{}].template must not be empty", category));
+            check(rule.max_length.map_or(true, |max| max >= 8),
+                &format!("rules.categories[{}].max_length must be at least 8", category));
+        }
+
+        // Characters file systems refuse, as in `renamer`
+        let refused = |c: char| c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|');
+        let sanitizer = &self.rules.sanitizer;
+        check(!sanitizer.allowed.chars().any(|c| refused(c) || c.is_whitespace()),
+            "rules.sanitizer.allowed must not include whitespace or any of / \\ : * ? \" < > |");
+        check(!refused(sanitizer.replacement) && !sanitizer.replacement.is_alphanumeric(),
+            "rules.sanitizer.replacement must be a punctuation character or a space, and not one of / \\ : * ? \" < > |");
+        check(sanitizer.max_words.map_or(true, |max| max > 0), "rules.sanitizer.max_words must be greater than 0");
+        for script in &sanitizer.keep_scripts {
+            check(crate::naming::script(script).is_some(),
+                &format!("rules.sanitizer.keep_scripts: {} is not a Unicode script name", script));
+        }
+        for problem in crate::rules::problems(&self.rules.actions) {
+            check(false, &problem);
+        }
+
+        let prompts = &self.prompts;
+        for (name, prompt) in [("image", &prompts.image), ("document", &prompts.document), ("audio", &prompts.audio),
+                               ("video", &prompts.video), ("code", &prompts.code), ("archive", &prompts.archive),
+                               ("comic", &prompts.comic), ("ebook", &prompts.ebook),
+                               ("email", &prompts.email), ("ocr", &prompts.ocr),
+                               ("image_text", &prompts.image_text), ("folder", &prompts.folder),
+                               ("summary", &prompts.summary), ("svg", &prompts.svg),
+                               ("subtitle_summary", &prompts.subtitle_summary), ("subtitle", &prompts.subtitle)] {
+            check(!prompt.trim().is_empty(), &format!("prompts.{} must not be empty", name));
+        }
+
+        let audio = &self.analyzers.audio;
+        check(!audio.transcribe || audio.whisper_model.is_some(),
+            "analyzers.audio.whisper_model must be set to transcribe");
+        check(audio.chunk_secs >= 30, "analyzers.audio.chunk_secs must be at least 30");
+        check(self.analyzers.video.keyframes > 0, "analyzers.video.keyframes must be greater than 0");
+
+        let web = &self.web;
+        check(web.port > 0, "web.port must be greater than 0");
+        check(web.max_upload_mb > 0, "web.max_upload_mb must be greater than 0");
+        check(web.cors_origins.iter().all(|o| o.starts_with("http://") || o.starts_with("https://")),
+            "web.cors_origins must be http:// or https:// origins");
+        check(web.base_path.is_empty() || (web.base_path.starts_with('/')
+            && !web.base_path.contains(|c: char| c.is_whitespace() || matches!(c, '?' | '#' | '"' | '\\'))),
+            "web.base_path must be a path starting with /, like /panoptes");
+        check(web.auth.session_hours > 0, "web.auth.session_hours must be greater than 0");
+        check(web.auth.tokens.iter().all(|t| t.len() >= 16), "web.auth.tokens must be at least 16 characters long");
+        if let Some(ref oidc) = web.auth.oidc {
+            check(!oidc.issuer.is_empty() && !oidc.client_id.is_empty() && !oidc.redirect_url.is_empty(),
+                "web.auth.oidc needs issuer, client_id and redirect_url");
+        }
+        if let Some(ref tls) = web.tls {
+            check(!tls.cert_path.is_empty() && !tls.key_path.is_empty(), "web.tls needs cert_path and key_path");
+            check(tls.redirect_port != Some(web.port), "web.tls.redirect_port must differ from web.port");
+        }
+
+        check(!self.database.path.trim().is_empty(), "database.path must not be empty");
+        check(self.review.auto_apply_threshold.map_or(true, |t| (0.0..=1.0).contains(&t)),
+            "review.auto_apply_threshold must be between 0 and 1");
+        check(!self.review.quarantine_dir.as_ref().is_some_and(|d| d.trim().is_empty()),
+            "review.quarantine_dir must not be empty");
+        check(self.organize.destinations.iter().all(|(category, dir)| !category.trim().is_empty() && !dir.trim().is_empty()),
+            "organize.destinations must map categories to non-empty directories");
+        check(!self.organize.uncategorized.as_ref().is_some_and(|d| d.trim().is_empty()),
+            "organize.uncategorized must not be empty");
+        check((16..=2048).contains(&self.thumbnails.size), "thumbnails.size must be between 16 and 2048");
+        check(self.webhooks.iter().all(|h| h.url.starts_with("http://") || h.url.starts_with("https://")),
+            "webhooks[].url must be an http:// or https:// URL");
+        check(self.webhooks.iter().all(|h| h.max_retries <= 20), "webhooks[].max_retries must be at most 20");
+        check(self.jobs.workers > 0, "jobs.workers must be greater than 0");
+        check(self.jobs.max_attempts > 0, "jobs.max_attempts must be greater than 0");
+        check(self.jobs.keep_days > 0, "jobs.keep_days must be greater than 0");
+        check((0.0..=1.0).contains(&self.similarity.min_score), "similarity.min_score must be between 0 and 1");
+        check(self.similarity.limit > 0, "similarity.limit must be greater than 0");
+        check(self.calibration.prior_files > 0, "calibration.prior_files must be greater than 0");
+        check(!self.vault.directory.as_ref().is_some_and(|d| d.trim().is_empty()), "vault.directory must not be empty");
+        check(!self.views.directory.as_ref().is_some_and(|d| d.trim().is_empty()), "views.directory must not be empty");
+        check(self.agent.server.as_ref().map_or(true, |s| s.starts_with("http://") || s.starts_with("https://")),
+            "agent.server must be an http:// or https:// URL");
+        check(self.agent.timeout_secs > 0, "agent.timeout_secs must be greater than 0");
+        check(self.sources.iter().all(|s| !s.name.trim().is_empty()), "sources[].name must not be empty");
+        check(self.sources.iter().all(|s| ["s3://", "sftp://", "http://", "https://"].iter().any(|p| s.url.starts_with(p))),
+            "sources[].url must be an s3://, sftp://, http:// or https:// URL");
+        check(self.sources.iter().enumerate().all(|(i, s)| self.sources[..i].iter().all(|t| t.name != s.name)),
+            "sources[].name must differ between sources");
+        check(self.mqtt.broker.as_ref().map_or(true, |b| b.starts_with("mqtt://") || b.starts_with("tcp://")),
+            "mqtt.broker must be an mqtt:// URL");
+        check(!self.mqtt.topic_prefix.trim_matches('/').is_empty(), "mqtt.topic_prefix must not be empty");
+        check(self.mqtt.state_interval_secs > 0, "mqtt.state_interval_secs must be greater than 0");
+        check(self.views.trees.iter().all(|t| !t.name.trim().is_empty() && !t.template.trim().is_empty()),
+            "views.trees[] need a name and a template");
+        let names: std::collections::HashSet<&str> = self.views.trees.iter().map(|t| t.name.trim()).collect();
+        check(names.len() == self.views.trees.len(), "views.trees[] must have different names");
+        for problem in crate::notifications::problems(&self.notifications) {
+            check(false, &problem);
+        }
+        for problem in crate::schedule::problems(&self.schedule) {
+            check(false, &problem);
+        }
+        for (file_type, rule) in &self.file_types {
+            check(!file_type.trim_start_matches('.').trim().is_empty(), "file_types must not have an empty key");
+            check(!rule.prompt.as_ref().is_some_and(|p| p.trim().is_empty()),
+                &format!("file_types[{}].prompt must not be empty", file_type));
+            check(!rule.category.as_ref().is_some_and(|c| c.trim().is_empty()),
+                &format!("file_types[{}].category must not be empty", file_type));
+            check(!rule.skip || (rule.analyzer.is_none() && rule.category.is_none() && rule.prompt.is_none()),
+                &format!("file_types[{}] skips its files, so it can't also set an analyzer, category or prompt", file_type));
+        }
+
+        problems
+    }
+
+    /// Check the configuration, failing with every problem found
+    pub fn validate(&self) -> crate::Result<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            Ok(())
         } else {
-            tracing::info!("Config file not found at {:?}, using defaults", path);
-            Ok(Self::default())
+            Err(crate::PanoptesError::Config(problems.join("; ")))
         }
     }
 
-    /// Save configuration to a JSON file
+    /// Settings that differ in `other`
+    pub fn diff(&self, other: &AppConfig) -> crate::Result<Vec<ConfigChange>> {
+        let mut changes = Vec::new();
+        diff_values("", &serde_json::to_value(self)?, &serde_json::to_value(other)?, &mut changes);
+        Ok(changes)
+    }
+
+    /// What a running watcher changes to when its config becomes `updated`:
+    /// `updated`, but with settings that need a restart left as they are
+    /// here, and the settings that differ
+    pub fn reloaded(&self, updated: &AppConfig) -> crate::Result<(AppConfig, Vec<ConfigChange>)> {
+        let changes = self.diff(updated)?;
+        let mut applied = serde_json::to_value(updated)?;
+        let current = serde_json::to_value(self)?;
+        for change in changes.iter().filter(|c| c.restart_required) {
+            let path: Vec<String> = change.path.split('.').map(String::from).collect();
+            match env::get(&current, &path) {
+                Some(value) => env::set(&mut applied, &path, value.clone()),
+                None => {
+                    let (last, parents) = path.split_last().expect("changes name a setting");
+                    if let Some(serde_json::Value::Object(map)) = parents.iter().try_fold(&mut applied, |v, key| v.get_mut(key)) {
+                        map.remove(last);
+                    }
+                }
+            }
+        }
+        Ok((serde_json::from_value(applied)?, changes))
+    }
+
+    /// Save configuration to a JSON file. The file is replaced atomically, so a
+    /// crash mid-write never leaves a truncated config behind.
     pub fn save(&self, path: &Path) -> crate::Result<()> {
-        let content = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, content)?;
-        Ok(())
+        save_json(&serde_json::to_value(self)?, path)
+    }
+}
+
+/// Save `value` to the JSON file at `path`, replacing it atomically
+pub fn save_json(value: &serde_json::Value, path: &Path) -> crate::Result<()> {
+    let content = serde_json::to_string_pretty(value)?;
+    let mut temp_name = path.file_name()
+        .ok_or_else(|| crate::PanoptesError::Config(format!("Invalid config path {:?}", path)))?
+        .to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(&temp, content)?;
+    if let Err(e) = std::fs::rename(&temp, path) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
     }
+    Ok(())
 }
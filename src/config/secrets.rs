@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Secrets kept out of the config file
+//!
+//! Tokens and keys needn't be written into a config that is kept in git:
+//!
+//! - Any text setting may refer to an environment variable or a file, as in
+//!   `"secret": "${env:WEBHOOK_SECRET}"` or `"client_secret": "${file:/run/secrets/oidc}"`,
+//!   resolved when the config is loaded. Saving from the web UI keeps the reference.
+//! - `secrets.json`, next to the project's config file, is merged over it
+//!   (see [`super::layers`]), so it can hold `web.auth.tokens` and the like
+//!   while the config itself is shared.
+//!
+//! `panoptes config show` prints [`REDACTED`] in place of the known secret
+//! settings and of anything from a reference or the secrets file.
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::env;
+use super::layers::Setting;
+use crate::{PanoptesError, Result};
+
+/// Name of the secrets file, in the directory of the project's config file
+pub const SECRETS_FILE: &str = "secrets.json";
+
+/// Shown in place of a secret
+pub const REDACTED: &str = "********";
+
+/// Settings holding secrets; array elements are passed through, so
+/// `webhooks.secret` is the secret of each webhook
+const SECRET_PATHS: &[&str] = &[
+    "web.auth.tokens", "web.auth.oidc.client_secret", "webhooks.secret", "agent.token",
+    "sources.secret_key", "sources.password", "mqtt.password",
+    // Slack and Discord URLs carry their tokens
+    "notifications.channels.url", "notifications.channels.token", "notifications.channels.password",
+];
+
+/// The secrets file of the project whose config file is `project`
+pub fn file_for(project: &Path) -> PathBuf {
+    project.with_file_name(SECRETS_FILE)
+}
+
+/// Warn, once, when the secrets file at `path` can be read by other users
+pub fn check_permissions(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        if let Ok(metadata) = std::fs::metadata(path) {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                WARNED.call_once(|| tracing::warn!("{} can be read by other users; chmod 600 it", path.display()));
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// `text` with its `${env:VAR}` and `${file:PATH}` references replaced;
+/// `None` when it has none
+fn substitute(text: &str) -> Result<Option<String>> {
+    if !text.contains("${env:") && !text.contains("${file:") {
+        return Ok(None);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            out.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let reference = &rest[start + 2..start + len];
+        if let Some(var) = reference.strip_prefix("env:") {
+            let value = std::env::var(var).map_err(|_| {
+                PanoptesError::Config(format!("${{env:{}}} is used in the config, but {} is not set", var, var))
+            })?;
+            out.push_str(&value);
+        } else if let Some(file) = reference.strip_prefix("file:") {
+            let value = std::fs::read_to_string(file).map_err(|e| {
+                PanoptesError::Config(format!("${{file:{}}} is used in the config, but can't be read: {}", file, e))
+            })?;
+            out.push_str(value.trim_end_matches(['\n', '\r']));
+        } else {
+            out.push_str(&rest[start..start + len + 1]);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(Some(out))
+}
+
+/// Resolve the references in `value`; returns the settings that had any,
+/// an array being one setting as in [`super::layers`]
+pub fn resolve_references(value: &mut Value) -> Result<Vec<Vec<String>>> {
+    fn walk(value: &mut Value, path: &mut Vec<String>, found: &mut Vec<Vec<String>>) -> Result<()> {
+        match value {
+            Value::String(text) => {
+                if let Some(resolved) = substitute(text)? {
+                    *text = resolved;
+                    found.push(path.clone());
+                }
+            }
+            Value::Array(items) => {
+                let mut inside = Vec::new();
+                for item in items {
+                    walk(item, &mut Vec::new(), &mut inside)?;
+                }
+                if !inside.is_empty() {
+                    found.push(path.clone());
+                }
+            }
+            Value::Object(map) => {
+                for (key, child) in map {
+                    path.push(key.clone());
+                    walk(child, path, found)?;
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    let mut found = Vec::new();
+    walk(value, &mut Vec::new(), &mut found)?;
+    Ok(found)
+}
+
+/// Replace the text at `path` in `value` with [`REDACTED`], through arrays
+fn redact_at(value: &mut Value, path: &[&str]) {
+    match (value, path.split_first()) {
+        (Value::Array(items), _) => {
+            for item in items {
+                redact_at(item, path);
+            }
+        }
+        (Value::Object(map), Some((key, rest))) => {
+            if let Some(child) = map.get_mut(*key) {
+                redact_at(child, rest);
+            }
+        }
+        (value @ Value::String(_), None) => *value = Value::String(REDACTED.to_string()),
+        (Value::Object(map), None) => {
+            for child in map.values_mut() {
+                redact_at(child, &[]);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `config` with its secrets redacted, those being the known secret
+/// settings and the `secret` ones of `settings`
+pub fn redact(config: &mut Value, settings: &[Setting]) {
+    for path in SECRET_PATHS {
+        redact_at(config, &path.split('.').collect::<Vec<_>>());
+    }
+    for setting in settings.iter().filter(|s| s.secret) {
+        redact_at(config, &setting.path.split('.').collect::<Vec<_>>());
+    }
+}
+
+/// Put back the secrets of `current` that are still [`REDACTED`] in `edited`,
+/// an edit of a redacted copy of it
+pub fn restore(edited: &mut Value, current: &Value) {
+    match (edited, current) {
+        (edited @ Value::String(_), current) if edited.as_str() == Some(REDACTED) => *edited = current.clone(),
+        (Value::Array(items), Value::Array(current)) => {
+            for (item, current) in items.iter_mut().zip(current) {
+                restore(item, current);
+            }
+        }
+        (Value::Object(map), Value::Object(current)) => {
+            for (key, child) in map.iter_mut() {
+                if let Some(current) = current.get(key) {
+                    restore(child, current);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `settings` with their secrets redacted
+pub fn redact_settings(settings: &mut [Setting]) {
+    for setting in settings {
+        let path: Vec<String> = setting.path.split('.').map(String::from).collect();
+        let mut whole = Value::Object(serde_json::Map::new());
+        env::set(&mut whole, &path, setting.value.take());
+        if setting.secret {
+            redact_at(&mut whole, &path.iter().map(String::as_str).collect::<Vec<_>>());
+        } else {
+            // Only the secret parts of it, such as each webhook's `secret`
+            redact(&mut whole, &[]);
+        }
+        setting.value = env::get(&whole, &path).cloned().unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::layers::{self, Source};
+    use serde_json::json;
+
+    #[test]
+    fn test_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("oidc");
+        std::fs::write(&secret, "s3cret\n").unwrap();
+
+        let mut value = json!({
+            "web": { "auth": { "oidc": { "client_secret": format!("${{file:{}}}", secret.display()) } } },
+            "webhooks": [{ "url": "https://example.com", "secret": format!("pre-${{file:{}}}-post", secret.display()) }],
+            "rules": { "note": "${other:thing} and ${unterminated" },
+        });
+        let found = resolve_references(&mut value).unwrap();
+        assert_eq!(value["web"]["auth"]["oidc"]["client_secret"], "s3cret");
+        assert_eq!(value["webhooks"][0]["secret"], "pre-s3cret-post");
+        assert_eq!(value["rules"]["note"], "${other:thing} and ${unterminated");
+        let found: Vec<String> = found.iter().map(|path| path.join(".")).collect();
+        assert_eq!(found, ["web.auth.oidc.client_secret", "webhooks"]);
+
+        let mut unset = json!({ "mqtt": { "password": "${env:PANOPTES_TEST_UNSET_SECRET}" } });
+        assert!(resolve_references(&mut unset).unwrap_err().to_string().contains("PANOPTES_TEST_UNSET_SECRET is not set"));
+        let mut unreadable = json!({ "mqtt": { "password": "${file:/nonexistent/secret}" } });
+        assert!(resolve_references(&mut unreadable).is_err());
+    }
+
+    #[test]
+    fn test_redact_and_restore() {
+        let current = json!({
+            "web": { "auth": { "tokens": ["pt_one", "pt_two"], "enabled": true } },
+            "webhooks": [{ "url": "https://example.com", "secret": "hook" }],
+            "database": { "path": "panoptes.db" },
+        });
+        let mut shown = current.clone();
+        let from_secrets_file = Setting { path: "database.path".to_string(), value: Value::Null, source: Source::Default, secret: true };
+        redact(&mut shown, &[from_secrets_file]);
+        assert_eq!(shown, json!({
+            "web": { "auth": { "tokens": [REDACTED, REDACTED], "enabled": true } },
+            "webhooks": [{ "url": "https://example.com", "secret": REDACTED }],
+            "database": { "path": REDACTED },
+        }));
+
+        // Edits of the redacted copy keep the secrets they didn't touch
+        shown["webhooks"][0]["url"] = json!("https://example.org");
+        shown["web"]["auth"]["tokens"][1] = json!("pt_new");
+        restore(&mut shown, &current);
+        assert_eq!(shown["web"]["auth"]["tokens"], json!(["pt_one", "pt_new"]));
+        assert_eq!(shown["webhooks"][0], json!({ "url": "https://example.org", "secret": "hook" }));
+        assert_eq!(shown["database"]["path"], "panoptes.db");
+    }
+
+    #[test]
+    fn test_secrets_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("config.json");
+        std::fs::write(&project, json!({ "web": { "auth": { "allow_localhost": false } } }).to_string()).unwrap();
+        std::fs::write(file_for(&project), json!({ "web": { "auth": { "tokens": ["pt_secret"] } } }).to_string()).unwrap();
+
+        let (config, settings) = layers::resolve(&project).unwrap();
+        assert_eq!(config.web.auth.tokens, ["pt_secret"]);
+        assert!(!config.web.auth.allow_localhost);
+        let secret = |path: &str| settings.iter().find(|s| s.path == path).map(|s| s.secret);
+        assert_eq!(secret("web.auth.tokens"), Some(true));
+        assert_eq!(secret("web.auth.allow_localhost"), Some(false));
+
+        // Saving leaves the secrets file's settings out of the config
+        let saved = layers::project_file(&config, &config, &project, &[]).unwrap();
+        assert_eq!(saved, json!({ "web": { "auth": { "allow_localhost": false } } }));
+    }
+}
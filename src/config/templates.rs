@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Starting configurations for common setups (`panoptes init --template`)
+//!
+//! Each template watches the usual directory for its files, analyzes only
+//! those, asks the AI engine for names suited to them and sends categories to
+//! their own directories. Thresholds are stricter than the defaults, with
+//! the review queue catching the rest, as a new setup hasn't earned trust yet.
+
+use std::collections::BTreeMap;
+
+use super::{AppConfig, DateSource, DestinationRule, NamingRule, SidecarFormat, WatchOptions};
+use crate::organizer::expand_home;
+use crate::selection::FileSelection;
+
+/// Names of the templates
+pub const TEMPLATES: &[&str] = &["photos", "downloads", "documents", "code"];
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+/// Watch `dir` (under the home directory when it starts with `~`) with `options`
+fn watch(config: &mut AppConfig, dir: &str, options: WatchOptions) {
+    let dir = expand_home(dir).to_string_lossy().into_owned();
+    config.watch_paths = vec![dir.clone()];
+    config.watch_options = BTreeMap::from([(dir, options)]);
+}
+
+fn route(category: &str, directory: &str) -> DestinationRule {
+    DestinationRule { category: Some(category.to_string()), tag: None, directory: directory.to_string() }
+}
+
+fn dated(template: &str, date_source: DateSource) -> NamingRule {
+    NamingRule { template: Some(template.to_string()), date_source, ..Default::default() }
+}
+
+/// The configuration of the template called `name`
+pub fn template(name: &str) -> Option<AppConfig> {
+    let mut config = AppConfig::default();
+    config.review.enabled = true;
+    // Each template's rules.auto_rename_threshold holds with review on too
+    config.review.auto_apply_threshold = None;
+
+    match name {
+        "photos" => {
+            watch(&mut config, "~/Pictures", WatchOptions {
+                recursive: true,
+                select: FileSelection {
+                    extensions: strings(&["jpg", "jpeg", "png", "heic", "heif", "webp", "gif", "tif", "tiff", "mp4", "mov"]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            config.analyzers.pdf.enabled = false;
+            config.analyzers.audio.enabled = false;
+            config.analyzers.code.enabled = false;
+            config.prompts.image = "Describe this photo for its filename: the subject and the place or \
+                                    occasion if it shows (max 5 words). Use snake_case. Do not include the \
+                                    file extension. Return ONLY the filename.".to_string();
+            config.rules.auto_rename_threshold = 0.7;
+            config.rules.max_length = 60;
+            // Dated by when the photo was taken rather than when it was renamed
+            for category in ["Photos", "Images", "Diagrams", "Videos"] {
+                config.rules.categories.insert(category.to_string(), dated("{date}_{name}", DateSource::Created));
+            }
+            config.rules.categories.insert("Screenshots".to_string(),
+                dated("{date}_{time}_screenshot_{name}", DateSource::Created));
+            config.rules.destinations = vec![
+                route("Screenshots", "~/Pictures/Screenshots"),
+                route("Videos", "~/Videos"),
+            ];
+            // Photo managers read XMP sidecars
+            config.sidecars.enabled = true;
+            config.sidecars.format = SidecarFormat::Xmp;
+        }
+        "downloads" => {
+            watch(&mut config, "~/Downloads", WatchOptions::default());
+            config.rules.auto_rename_threshold = 0.8;
+            config.rules.destinations = vec![
+                route("Finance", "~/Documents/Finance"),
+                route("Career", "~/Documents/Career"),
+                route("Manuals", "~/Documents/Manuals"),
+                route("Documents", "~/Documents"),
+                route("Spreadsheets", "~/Documents"),
+                route("Presentations", "~/Documents"),
+                route("Photos", "~/Pictures"),
+                route("Images", "~/Pictures"),
+                route("Screenshots", "~/Pictures/Screenshots"),
+                route("Music", "~/Music"),
+                route("Podcasts", "~/Music/Podcasts"),
+                route("Videos", "~/Videos"),
+                route("Archives", "~/Downloads/Archives"),
+            ];
+        }
+        "documents" => {
+            watch(&mut config, "~/Documents", WatchOptions {
+                recursive: true,
+                select: FileSelection {
+                    extensions: strings(&["pdf", "doc", "docx", "odt", "txt", "md", "xls", "xlsx", "ods", "csv", "ppt", "pptx", "odp"]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            config.analyzers.image.enabled = false;
+            config.analyzers.audio.enabled = false;
+            config.analyzers.video.enabled = false;
+            config.analyzers.code.enabled = false;
+            config.prompts.document = "Name this document by what it is, who it is from or about and its \
+                                       date if it gives one, e.g. acme_invoice_2024_03 (max 6 words). Use \
+                                       snake_case. Return ONLY the filename.".to_string();
+            config.rules.auto_rename_threshold = 0.75;
+            config.rules.max_length = 80;
+            // The document's own date is usually in the name already
+            config.rules.date_prefix = false;
+            config.rules.categories.insert("Finance".to_string(), dated("{date}_{name}", DateSource::Modified));
+            config.rules.destinations = vec![
+                route("Finance", "~/Documents/Finance"),
+                route("Career", "~/Documents/Career"),
+                route("Manuals", "~/Documents/Manuals"),
+            ];
+        }
+        "code" => {
+            watch(&mut config, "~/Code/inbox", WatchOptions {
+                select: FileSelection {
+                    extensions: strings(&["rs", "py", "js", "ts", "go", "java", "c", "cpp", "h", "rb", "sh", "sql"]),
+                    exclude: strings(&[".git", "node_modules", "target", "__pycache__"]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            config.analyzers.image.enabled = false;
+            config.analyzers.pdf.enabled = false;
+            config.analyzers.audio.enabled = false;
+            config.analyzers.video.enabled = false;
+            config.analyzers.code.languages = strings(&[
+                "rust", "python", "javascript", "typescript", "go", "java", "c", "cpp", "ruby", "shell", "sql",
+            ]);
+            config.prompts.code = "Suggest a filename saying what this code does, e.g. parse_csv_report \
+                                   (max 5 words). Use snake_case. Return ONLY the filename.".to_string();
+            config.rules.auto_rename_threshold = 0.6;
+            config.rules.max_length = 40;
+            config.rules.date_prefix = false;
+            config.rules.destinations = vec![route("Code", "~/Code/snippets")];
+        }
+        _ => return None,
+    }
+    Some(config)
+}
@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Running the watcher as a background service
+//!
+//! - A PID file, locked while the watcher runs, so only one instance watches
+//!   for a given config file.
+//! - `watch --daemon` detaches by starting the same command again in a new
+//!   session, with its output going to a log file.
+//! - Under systemd (`Type=notify`), readiness, watchdog pings and shutdown are
+//!   reported on `$NOTIFY_SOCKET`.
+//! - The log file is reopened on SIGHUP, for log rotation.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{PanoptesError, Result};
+
+/// Where the PID file for a watcher using `config_path` goes by default: the
+/// user's runtime directory, named after the config so each config gets one
+fn runtime_path(config_path: &Path, extension: &str) -> PathBuf {
+    let config = config_path.canonicalize().unwrap_or_else(|_| config_path.to_path_buf());
+    let key = blake3::hash(config.to_string_lossy().as_bytes()).to_hex();
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    dir.join(format!("panoptes-watch-{}.{}", &key[..16], extension))
+}
+
+/// Default PID file for a watcher using `config_path`
+pub fn default_pid_file(config_path: &Path) -> PathBuf {
+    runtime_path(config_path, "pid")
+}
+
+/// Default log file for a detached watcher using `config_path`
+pub fn default_log_file(config_path: &Path) -> PathBuf {
+    runtime_path(config_path, "log")
+}
+
+/// A PID file held for as long as the process runs; removed on drop
+pub struct PidFile {
+    path: PathBuf,
+    _file: File,
+}
+
+impl PidFile {
+    /// Lock `path` and write our PID to it, failing if another process holds it
+    pub fn acquire(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if !try_lock(&file)? {
+            let pid = std::fs::read_to_string(path).unwrap_or_default();
+            return Err(PanoptesError::Config(format!(
+                "Another watcher (PID {}) is already running with this config; PID file {}",
+                pid.trim(), path.display()
+            )));
+        }
+        // Only truncate once we hold the lock, so the running watcher's PID survives a failed attempt
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+        Ok(Self { path: path.to_path_buf(), _file: file })
+    }
+
+    /// PID of the process holding `path`, if one does
+    pub fn holder(path: &Path) -> Option<u32> {
+        let file = File::open(path).ok()?;
+        if try_lock(&file).unwrap_or(true) {
+            return None;
+        }
+        std::fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Take an exclusive lock on `file` without waiting; false if someone else has it
+#[cfg(unix)]
+fn try_lock(file: &File) -> Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: flock only reads the descriptor, which `file` keeps open
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = std::io::Error::last_os_error();
+    if error.kind() == std::io::ErrorKind::WouldBlock {
+        Ok(false)
+    } else {
+        Err(error.into())
+    }
+}
+
+/// Without file locks, only a PID file left by a live process blocks
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> Result<bool> {
+    Ok(true)
+}
+
+/// Start the current command again, minus `--daemon`, detached from the
+/// terminal in a new session with output going to `log_file`. Returns the
+/// child's PID.
+#[cfg(unix)]
+pub fn detach(log_file: &Path) -> Result<u32> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let exe = std::env::current_exe()?;
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().skip(1).filter(|a| a != "--daemon").collect();
+    if !args.iter().any(|a| a.to_string_lossy().starts_with("--log-file")) {
+        args.push("--log-file".into());
+        args.push(log_file.into());
+    }
+
+    let mut command = Command::new(exe);
+    command.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // SAFETY: setsid is async-signal-safe, as required between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+
+    // Report a watcher that fails to start (a second instance, a bad config)
+    std::thread::sleep(Duration::from_secs(1));
+    if let Some(status) = child.try_wait()? {
+        return Err(PanoptesError::Config(format!(
+            "Watcher exited during startup ({}); see {}", status, log_file.display()
+        )));
+    }
+    Ok(child.id())
+}
+
+#[cfg(not(unix))]
+pub fn detach(_log_file: &Path) -> Result<u32> {
+    Err(PanoptesError::Config("--daemon is only supported on Unix; run as a service instead".to_string()))
+}
+
+/// Log file output goes to instead of stdout, with its path for reopening
+static LOG_FILE: Mutex<Option<(PathBuf, File)>> = Mutex::new(None);
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Send log output to `path` from now on
+pub fn open_log(path: &Path) -> Result<()> {
+    let file = open_append(path)?;
+    if let Ok(mut log) = LOG_FILE.lock() {
+        *log = Some((path.to_path_buf(), file));
+    }
+    Ok(())
+}
+
+/// Reopen the log file, e.g. after it was rotated
+pub fn reopen_log() -> Result<()> {
+    let mut log = LOG_FILE.lock().map_err(|_| PanoptesError::Config("Log file lock poisoned".to_string()))?;
+    if let Some((path, file)) = log.as_mut() {
+        *file = open_append(path)?;
+    }
+    Ok(())
+}
+
+/// Write log output to the log file; `None` when there is none
+pub fn write_log(buf: &[u8]) -> Option<std::io::Result<usize>> {
+    let mut log = LOG_FILE.lock().ok()?;
+    log.as_mut().map(|(_, file)| file.write(buf))
+}
+
+/// systemd service notifications (`sd_notify`); all do nothing when not run
+/// by systemd with `Type=notify`
+pub mod notify {
+    use std::time::Duration;
+
+    /// Send `state` to the service manager
+    fn send(state: &str) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::net::UnixDatagram;
+
+            let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+                return;
+            };
+            let Ok(sender) = UnixDatagram::unbound() else {
+                return;
+            };
+            let socket = socket.to_string_lossy().into_owned();
+            let sent = match socket.strip_prefix('@') {
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Some(name) => {
+                    use std::os::linux::net::SocketAddrExt;
+                    std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())
+                        .and_then(|addr| sender.send_to_addr(state.as_bytes(), &addr))
+                }
+                _ => sender.send_to(state.as_bytes(), &socket),
+            };
+            if let Err(e) = sent {
+                tracing::debug!("sd_notify failed: {}", e);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = state;
+    }
+
+    /// Startup is complete
+    pub fn ready() {
+        send(&format!("READY=1\nMAINPID={}", std::process::id()));
+    }
+
+    /// Shutting down
+    pub fn stopping() {
+        send("STOPPING=1");
+    }
+
+    /// Tell the watchdog we're alive
+    pub fn watchdog() {
+        send("WATCHDOG=1");
+    }
+
+    /// How often to call [`watchdog`]: half the service's `WatchdogSec`, if it has one
+    pub fn watchdog_interval() -> Option<Duration> {
+        let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+        // WATCHDOG_PID, when set, names the process the watchdog is meant for
+        if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|p| p.parse::<u32>().ok()) {
+            if pid != std::process::id() {
+                return None;
+            }
+        }
+        Some(Duration::from_micros(usec / 2))
+    }
+}
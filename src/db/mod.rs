@@ -3,13 +3,25 @@
 
 //! Database module for file metadata, tags, and categories
 
-use chrono::{DateTime, Utc};
-use rusqlite::{Connection, params};
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use rusqlite::types::Value as SqlValue;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
+use crate::agent::Agent;
+use crate::analyzers::AnalysisResult;
+use crate::feedback::{Correction, CorrectionRate, CorrectionSource};
+use crate::history::{HistoryAction, HistoryEntry};
+use crate::jobs::{Job, JobState, Task};
+use crate::selection::FileSelection;
+use crate::similarity::Fingerprint;
+use crate::sources::{RemoteRecord, RemoteState};
+use crate::summary::Summary;
+use crate::transcribe::Transcript;
 use crate::{PanoptesError, Result};
 
 /// Database manager for Panoptes (thread-safe wrapper)
@@ -23,6 +35,7 @@ pub struct Database {
 pub struct FileRecord {
     pub id: String,
     pub original_path: String,
+    /// Where the file currently lives (after renames/moves and undos)
     pub new_path: String,
     pub suggested_name: String,
     pub file_hash: String,
@@ -30,6 +43,641 @@ pub struct FileRecord {
     pub confidence: f64,
     pub metadata: serde_json::Value,
     pub created_at: DateTime<Utc>,
+    /// Most recent rename event for this file, if any
+    #[serde(default)]
+    pub rename_id: Option<String>,
+    /// Whether the most recent rename has been undone
+    #[serde(default)]
+    pub undone: bool,
+    /// Review queue state, if the suggestion needed approval
+    #[serde(default)]
+    pub status: Option<ReviewStatus>,
+    /// Name chosen by the user when it differs from the suggestion
+    #[serde(default)]
+    pub corrected_name: Option<String>,
+    /// When `panoptes verify --fix` found the file gone
+    #[serde(default)]
+    pub missing_since: Option<DateTime<Utc>>,
+    /// Analyzer and model the suggestion came from
+    #[serde(default)]
+    pub analyzer: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl FileRecord {
+    /// Whether the file lives on another machine, submitted through remote
+    /// ingest; its paths then name nothing here
+    pub fn is_remote(&self) -> bool {
+        self.metadata.get("remote").and_then(|v| v.as_bool()).unwrap_or(false)
+    }
+}
+
+/// Review queue state of a suggestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    /// Waiting for approval
+    Pending,
+    /// Approved and renamed
+    Approved,
+    /// Rejected; the file keeps its name
+    Rejected,
+}
+
+impl ReviewStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(Self::Pending),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// Columns selected for a `FileRecord`, in the order `file_from_row` expects
+const FILE_COLUMNS: &str = r#"f.id, f.original_path, COALESCE(f.current_path, f.original_path), f.suggested_name,
+    f.file_hash, f.category, f.confidence, f.metadata, f.created_at,
+    (SELECT r.id FROM renames r WHERE r.file_id = f.id AND r.action IN ('rename', 'move') ORDER BY r.timestamp DESC LIMIT 1),
+    COALESCE((SELECT r.undone FROM renames r WHERE r.file_id = f.id AND r.action IN ('rename', 'move') ORDER BY r.timestamp DESC LIMIT 1), 0),
+    f.status, f.corrected_name, f.missing_since, f.analyzer, f.model"#;
+
+/// How file listings are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileSort {
+    #[default]
+    Date,
+    Confidence,
+    Name,
+}
+
+/// Filters and ordering for [`Database::query_files`]; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    pub category: Option<String>,
+    /// Only files without a category
+    pub uncategorized: bool,
+    pub tag: Option<String>,
+    pub min_confidence: Option<f64>,
+    pub max_confidence: Option<f64>,
+    /// Processed on or after this day
+    pub since: Option<NaiveDate>,
+    /// Processed on or before this day
+    pub until: Option<NaiveDate>,
+    pub sort: FileSort,
+    /// Oldest, lowest or A-Z first instead of the reverse
+    pub ascending: bool,
+}
+
+impl FileFilter {
+    /// SQL `WHERE` clause and its parameters
+    fn where_clause(&self) -> (String, Vec<SqlValue>) {
+        let mut conditions = vec!["f.deleted_at IS NULL"];
+        let mut values = Vec::new();
+
+        if let Some(ref category) = self.category {
+            conditions.push("f.category = ?");
+            values.push(SqlValue::Text(category.clone()));
+        }
+        if self.uncategorized {
+            conditions.push("f.category IS NULL");
+        }
+        if let Some(ref tag) = self.tag {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_id = f.id AND t.name = ?)"
+            );
+            values.push(SqlValue::Text(tag.clone()));
+        }
+        if let Some(min) = self.min_confidence {
+            conditions.push("f.confidence >= ?");
+            values.push(SqlValue::Real(min));
+        }
+        if let Some(max) = self.max_confidence {
+            conditions.push("f.confidence <= ?");
+            values.push(SqlValue::Real(max));
+        }
+        if let Some(since) = self.since {
+            conditions.push("date(f.created_at) >= ?");
+            values.push(SqlValue::Text(since.format("%Y-%m-%d").to_string()));
+        }
+        if let Some(until) = self.until {
+            conditions.push("date(f.created_at) <= ?");
+            values.push(SqlValue::Text(until.format("%Y-%m-%d").to_string()));
+        }
+
+        (format!("WHERE {}", conditions.join(" AND ")), values)
+    }
+
+    fn order_clause(&self) -> String {
+        let column = match self.sort {
+            FileSort::Date => "f.created_at",
+            FileSort::Confidence => "f.confidence",
+            FileSort::Name => "COALESCE(f.corrected_name, f.suggested_name) COLLATE NOCASE",
+        };
+        let direction = if self.ascending { "ASC" } else { "DESC" };
+        // rowid keeps pages stable when the sort column has ties
+        format!("ORDER BY {} {}, f.rowid {}", column, direction, direction)
+    }
+}
+
+/// The fields of a file that faceted search groups by
+#[derive(Debug, Clone)]
+pub struct FacetRow {
+    pub id: String,
+    /// Where the file currently lives
+    pub path: String,
+    pub category: Option<String>,
+    pub confidence: f64,
+    pub created_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+}
+
+/// Columns selected for a `HistoryEntry`, in the order `rename_from_row` expects
+const RENAME_COLUMNS: &str = r#"id, timestamp, original_path, new_path, ai_suggestion, category, tags,
+    file_hash, undone, session_id, action, file_id,
+    (SELECT f.confidence FROM files f WHERE f.id = renames.file_id)"#;
+
+/// Schema migrations, applied in order; entry N brings `user_version` to N + 1
+const MIGRATIONS: &[&str] = &[
+    // 1: rename/move events as the canonical history, linked to file records
+    r#"
+        ALTER TABLE files ADD COLUMN current_path TEXT;
+
+        CREATE TABLE IF NOT EXISTS renames (
+            id TEXT PRIMARY KEY,
+            file_id TEXT,
+            timestamp TEXT NOT NULL,
+            original_path TEXT NOT NULL,
+            new_path TEXT NOT NULL,
+            ai_suggestion TEXT NOT NULL,
+            category TEXT,
+            tags TEXT NOT NULL DEFAULT '[]',
+            file_hash TEXT NOT NULL,
+            undone INTEGER NOT NULL DEFAULT 0,
+            session_id TEXT,
+            action TEXT NOT NULL DEFAULT 'rename'
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_renames_file ON renames(file_id);
+        CREATE INDEX IF NOT EXISTS idx_renames_session ON renames(session_id);
+    "#,
+    // 2: review queue for suggestions below the auto-apply threshold
+    r#"
+        ALTER TABLE files ADD COLUMN status TEXT;
+        ALTER TABLE files ADD COLUMN corrected_name TEXT;
+
+        CREATE INDEX IF NOT EXISTS idx_files_status ON files(status);
+    "#,
+    // 3: web authentication (API tokens and login sessions, both stored hashed)
+    r#"
+        CREATE TABLE IF NOT EXISTS api_tokens (
+            name TEXT PRIMARY KEY,
+            token_hash TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL,
+            last_used TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS web_sessions (
+            id_hash TEXT PRIMARY KEY,
+            subject TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL
+        );
+    "#,
+    // 4: user accounts and the external (OIDC) identities linked to them
+    r#"
+        CREATE TABLE IF NOT EXISTS users (
+            id TEXT PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            email TEXT,
+            display_name TEXT,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS user_identities (
+            issuer TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            PRIMARY KEY (issuer, subject)
+        );
+    "#,
+    // 5: roles for users and tokens (existing tokens keep full access), and the audit log
+    r#"
+        ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'viewer';
+        ALTER TABLE api_tokens ADD COLUMN role TEXT NOT NULL DEFAULT 'admin';
+
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT,
+            details TEXT NOT NULL DEFAULT '{}'
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_audit_timestamp ON audit_log(timestamp);
+    "#,
+    // 6: soft deletion; deleted records are hidden everywhere but duplicate detection
+    r#"
+        ALTER TABLE files ADD COLUMN deleted_at TEXT;
+    "#,
+    // 7: log of outbound webhook delivery attempts
+    r#"
+        CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            delivery_id TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            url TEXT NOT NULL,
+            event TEXT NOT NULL,
+            attempt INTEGER NOT NULL,
+            status INTEGER,
+            error TEXT,
+            success INTEGER NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_timestamp ON webhook_deliveries(timestamp);
+    "#,
+    // 8: watch directory status, reported by the watch daemon
+    r#"
+        CREATE TABLE IF NOT EXISTS watch_status (
+            path TEXT PRIMARY KEY,
+            error TEXT,
+            last_event_at TEXT,
+            updated_at TEXT NOT NULL
+        );
+    "#,
+    // 9: batch analysis runs and the files each has finished, so a run can be resumed
+    r#"
+        CREATE TABLE IF NOT EXISTS scan_runs (
+            id TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            recursive INTEGER NOT NULL,
+            dry_run INTEGER NOT NULL,
+            min_confidence REAL NOT NULL,
+            started_at TEXT NOT NULL,
+            finished_at TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS scan_progress (
+            run_id TEXT NOT NULL REFERENCES scan_runs(id) ON DELETE CASCADE,
+            file_hash TEXT NOT NULL,
+            processed_at TEXT NOT NULL,
+            PRIMARY KEY (run_id, file_hash)
+        );
+    "#,
+    // 10: runs over a list of files (`--files-from`) keep the list for resuming
+    r#"
+        ALTER TABLE scan_runs ADD COLUMN files TEXT;
+    "#,
+    // 11: what a batch analysis did with each file, for run reports
+    r#"
+        CREATE TABLE IF NOT EXISTS scan_results (
+            run_id TEXT NOT NULL REFERENCES scan_runs(id) ON DELETE CASCADE,
+            path TEXT NOT NULL,
+            outcome TEXT NOT NULL,
+            suggested_name TEXT,
+            new_path TEXT,
+            confidence REAL,
+            category TEXT,
+            error TEXT,
+            elapsed_ms INTEGER NOT NULL,
+            recorded_at TEXT NOT NULL,
+            PRIMARY KEY (run_id, path)
+        );
+    "#,
+    // 12: files found gone by `panoptes verify --fix`
+    r#"
+        ALTER TABLE files ADD COLUMN missing_since TEXT;
+    "#,
+    // 13: who attached each tag, so re-analysis keeps the ones users added
+    r#"
+        ALTER TABLE file_tags ADD COLUMN source TEXT NOT NULL DEFAULT 'analysis';
+    "#,
+    // 14: the file filters of a batch analysis, for resuming it
+    r#"
+        ALTER TABLE scan_runs ADD COLUMN selection TEXT;
+    "#,
+    // 15: the background job queue
+    r#"
+        CREATE TABLE IF NOT EXISTS jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            state TEXT NOT NULL,
+            priority INTEGER NOT NULL DEFAULT 0,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            max_attempts INTEGER NOT NULL,
+            last_error TEXT,
+            run_at TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            started_at TEXT,
+            finished_at TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_jobs_state ON jobs(state, priority, run_at);
+    "#,
+    // 16: the last run of each scheduled task
+    r#"
+        CREATE TABLE IF NOT EXISTS scheduled_runs (
+            name TEXT PRIMARY KEY,
+            started_at TEXT NOT NULL,
+            finished_at TEXT NOT NULL,
+            summary TEXT,
+            error TEXT
+        );
+    "#,
+    // 17: correlation IDs, linking jobs to the logs and traces of their files
+    r#"
+        ALTER TABLE jobs ADD COLUMN correlation_id TEXT;
+        UPDATE jobs SET correlation_id = lower(hex(randomblob(6)));
+    "#,
+    // 18: where suggestions came from, and the names users chose instead
+    r#"
+        ALTER TABLE files ADD COLUMN analyzer TEXT;
+        ALTER TABLE files ADD COLUMN model TEXT;
+
+        CREATE TABLE IF NOT EXISTS feedback (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            file_id TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            source TEXT NOT NULL,
+            suggested_name TEXT NOT NULL,
+            corrected_name TEXT NOT NULL,
+            analyzer TEXT,
+            model TEXT,
+            category TEXT,
+            confidence REAL NOT NULL DEFAULT 0.0
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_feedback_file ON feedback(file_id);
+    "#,
+    // 19: image hashes and text embeddings, by file hash, for finding files alike
+    r#"
+        CREATE TABLE IF NOT EXISTS fingerprints (
+            file_hash TEXT PRIMARY KEY,
+            image_hash TEXT,
+            embedding TEXT,
+            embedding_model TEXT,
+            created_at TEXT NOT NULL
+        );
+    "#,
+    // 20: machines forwarding their files for analysis
+    r#"
+        CREATE TABLE IF NOT EXISTS agents (
+            name TEXT PRIMARY KEY,
+            hostname TEXT,
+            version TEXT,
+            registered_at TEXT NOT NULL,
+            last_seen TEXT,
+            files INTEGER NOT NULL DEFAULT 0
+        );
+    "#,
+    // 21: files seen on remote sources, and what became of them
+    r#"
+        CREATE TABLE IF NOT EXISTS remote_objects (
+            source TEXT NOT NULL,
+            key TEXT NOT NULL,
+            version TEXT,
+            state TEXT NOT NULL,
+            file_id TEXT,
+            new_key TEXT,
+            confidence REAL,
+            error TEXT,
+            seen_at TEXT NOT NULL,
+            PRIMARY KEY (source, key)
+        );
+    "#,
+    // 22: transcripts of recordings by file hash, and the chunks of those unfinished
+    r#"
+        CREATE TABLE IF NOT EXISTS transcripts (
+            file_hash TEXT PRIMARY KEY,
+            model TEXT NOT NULL,
+            language TEXT,
+            text TEXT NOT NULL,
+            duration_secs REAL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS transcript_chunks (
+            file_hash TEXT NOT NULL,
+            model TEXT NOT NULL,
+            chunk_secs INTEGER NOT NULL,
+            chunk INTEGER NOT NULL,
+            text TEXT NOT NULL,
+            PRIMARY KEY (file_hash, model, chunk_secs, chunk)
+        );
+    "#,
+    // 23: summaries of directories' contents
+    r#"
+        CREATE TABLE IF NOT EXISTS summaries (
+            id TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            data TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_summaries_path ON summaries(path, created_at);
+    "#,
+    // 24: which process is running a job, and when it last said it still was
+    r#"
+        ALTER TABLE jobs ADD COLUMN owner TEXT;
+        ALTER TABLE jobs ADD COLUMN heartbeat_at TEXT;
+    "#,
+];
+
+fn agent_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Agent> {
+    let registered_at: String = row.get(3)?;
+    let last_seen: Option<String> = row.get(4)?;
+    Ok(Agent {
+        name: row.get(0)?,
+        hostname: row.get(1)?,
+        version: row.get(2)?,
+        registered_at: parse_timestamp(&registered_at),
+        last_seen: last_seen.as_deref().map(parse_timestamp),
+        files: row.get(5)?,
+    })
+}
+
+fn remote_object_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RemoteRecord> {
+    let state: String = row.get(3)?;
+    let seen_at: String = row.get(8)?;
+    Ok(RemoteRecord {
+        source: row.get(0)?,
+        key: row.get(1)?,
+        version: row.get(2)?,
+        state: state.parse().unwrap_or(RemoteState::Failed),
+        file_id: row.get(4)?,
+        new_key: row.get(5)?,
+        confidence: row.get(6)?,
+        error: row.get(7)?,
+        seen_at: parse_timestamp(&seen_at),
+    })
+}
+
+const REMOTE_OBJECT_COLUMNS: &str = "source, key, version, state, file_id, new_key, confidence, error, seen_at";
+
+fn fingerprint_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Fingerprint> {
+    let image_hash: Option<String> = row.get(1)?;
+    let embedding: Option<String> = row.get(2)?;
+    Ok(Fingerprint {
+        file_hash: row.get(0)?,
+        image_hash: image_hash.and_then(|h| u64::from_str_radix(&h, 16).ok()),
+        embedding: embedding.and_then(|e| serde_json::from_str(&e).ok()),
+        embedding_model: row.get(3)?,
+    })
+}
+
+fn transcript_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Transcript> {
+    Ok(Transcript {
+        file_hash: row.get(0)?,
+        model: row.get(1)?,
+        language: row.get(2)?,
+        text: row.get(3)?,
+        duration_secs: row.get(4)?,
+    })
+}
+
+/// Columns selected for a `Correction`, in the order `correction_from_row` expects
+const CORRECTION_COLUMNS: &str =
+    "id, file_id, timestamp, source, suggested_name, corrected_name, analyzer, model, category, confidence";
+
+fn correction_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Correction> {
+    let timestamp: String = row.get(2)?;
+    let source: String = row.get(3)?;
+    Ok(Correction {
+        id: row.get(0)?,
+        file_id: row.get(1)?,
+        timestamp: parse_timestamp(&timestamp),
+        source: CorrectionSource::parse(&source),
+        suggested_name: row.get(4)?,
+        corrected_name: row.get(5)?,
+        analyzer: row.get(6)?,
+        model: row.get(7)?,
+        category: row.get(8)?,
+        confidence: row.get(9)?,
+    })
+}
+
+/// Columns selected for a `Job`, in the order `job_from_row` expects
+const JOB_COLUMNS: &str =
+    "id, payload, state, priority, attempts, max_attempts, last_error, run_at, created_at, started_at, finished_at, correlation_id";
+
+fn job_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Job> {
+    let payload: String = row.get(1)?;
+    let state: String = row.get(2)?;
+    let run_at: String = row.get(7)?;
+    let created_at: String = row.get(8)?;
+    let started_at: Option<String> = row.get(9)?;
+    let finished_at: Option<String> = row.get(10)?;
+    Ok(Job {
+        id: row.get(0)?,
+        task: serde_json::from_str(&payload)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e)))?,
+        state: state.parse().unwrap_or(JobState::Dead),
+        priority: row.get(3)?,
+        attempts: row.get(4)?,
+        max_attempts: row.get(5)?,
+        last_error: row.get(6)?,
+        run_at: parse_timestamp(&run_at),
+        created_at: parse_timestamp(&created_at),
+        started_at: started_at.as_deref().map(parse_timestamp),
+        finished_at: finished_at.as_deref().map(parse_timestamp),
+        correlation_id: row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+    })
+}
+
+/// Columns selected for a `ScanRun`, in the order `scan_run_from_row` expects
+const SCAN_RUN_COLUMNS: &str = "id, path, recursive, dry_run, min_confidence, started_at, finished_at, files, selection";
+
+fn scan_run_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ScanRun> {
+    let started_at: String = row.get(5)?;
+    let finished_at: Option<String> = row.get(6)?;
+    let files: Option<String> = row.get(7)?;
+    let selection: Option<String> = row.get(8)?;
+    Ok(ScanRun {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        files: files.and_then(|f| serde_json::from_str(&f).ok()),
+        selection: selection.and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default(),
+        recursive: row.get(2)?,
+        dry_run: row.get(3)?,
+        min_confidence: row.get(4)?,
+        started_at: parse_timestamp(&started_at),
+        finished_at: finished_at.as_deref().map(parse_timestamp),
+    })
+}
+
+/// Parse a timestamp stored either as RFC 3339 or as SQLite's `datetime('now')`
+fn parse_timestamp(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .map(|dt| dt.and_utc())
+        })
+        .unwrap_or_else(|_| Utc::now())
+}
+
+fn file_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<FileRecord> {
+    let metadata_str: String = row.get(7)?;
+    let created_str: String = row.get(8)?;
+    let status: Option<String> = row.get(11)?;
+    Ok(FileRecord {
+        id: row.get(0)?,
+        original_path: row.get(1)?,
+        new_path: row.get(2)?,
+        suggested_name: row.get(3)?,
+        file_hash: row.get(4)?,
+        category: row.get(5)?,
+        confidence: row.get(6)?,
+        metadata: serde_json::from_str(&metadata_str).unwrap_or(serde_json::json!({})),
+        created_at: parse_timestamp(&created_str),
+        rename_id: row.get(9)?,
+        undone: row.get(10)?,
+        status: status.as_deref().and_then(ReviewStatus::parse),
+        corrected_name: row.get(12)?,
+        missing_since: row.get::<_, Option<String>>(13)?.as_deref().map(parse_timestamp),
+        analyzer: row.get(14)?,
+        model: row.get(15)?,
+    })
+}
+
+fn rename_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<HistoryEntry> {
+    let timestamp: String = row.get(1)?;
+    let original_path: String = row.get(2)?;
+    let new_path: String = row.get(3)?;
+    let tags: String = row.get(6)?;
+    let action: String = row.get(10)?;
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        timestamp: parse_timestamp(&timestamp),
+        original_path: PathBuf::from(original_path),
+        new_path: PathBuf::from(new_path),
+        ai_suggestion: row.get(4)?,
+        category: row.get(5)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        file_hash: row.get(7)?,
+        undone: row.get(8)?,
+        session_id: row.get(9)?,
+        action: match action.as_str() {
+            "move" => HistoryAction::Move,
+            "trash" => HistoryAction::Trash,
+            "delete" => HistoryAction::Delete,
+            _ => HistoryAction::Rename,
+        },
+        file_id: row.get(11)?,
+        confidence: row.get(12)?,
+    })
 }
 
 /// A tag
@@ -40,6 +688,32 @@ pub struct Tag {
     pub category: Option<String>,
 }
 
+/// Who attached a tag to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagSource {
+    /// Suggested by analysis; replaced when the file is analyzed again
+    Analysis,
+    /// Added by a user; kept when the file is analyzed again
+    User,
+}
+
+impl TagSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Analysis => "analysis",
+            Self::User => "user",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "user" => Self::User,
+            _ => Self::Analysis,
+        }
+    }
+}
+
 /// A category with statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Category {
@@ -48,6 +722,194 @@ pub struct Category {
     pub file_count: i64,
 }
 
+/// Access level of a user or token; each role includes the ones below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Browse and search
+    Viewer,
+    /// Approve renames, undo, re-analyze and edit tags
+    Editor,
+    /// Change configuration, watch directories and users
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Editor => "editor",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = PanoptesError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "viewer" => Ok(Self::Viewer),
+            "editor" => Ok(Self::Editor),
+            "admin" => Ok(Self::Admin),
+            other => Err(PanoptesError::Config(format!("Unknown role: {}", other))),
+        }
+    }
+}
+
+fn role_from_str(value: &str) -> Role {
+    value.parse().unwrap_or(Role::Viewer)
+}
+
+/// An API token (the secret itself is never stored)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub name: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// A user account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    pub role: Role,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    /// Who acted, e.g. `user:alice`, `token:ci` or `local`
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: serde_json::Value,
+}
+
+/// What the watch daemon last reported about a directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchStatus {
+    pub path: String,
+    /// Why the directory couldn't be watched, if it couldn't
+    pub error: Option<String>,
+    /// When a file last appeared in it
+    pub last_event_at: Option<DateTime<Utc>>,
+    /// When the daemon last confirmed this status
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The last run of a task of `schedule`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledRun {
+    /// The schedule entry's name
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// What the task did
+    pub summary: Option<String>,
+    /// Why it failed, if it did
+    pub error: Option<String>,
+}
+
+/// A batch analysis (`panoptes analyze`), recorded so it can be resumed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanRun {
+    /// Also the history session of the run's renames
+    pub id: String,
+    /// File or directory analyzed, or where the file list came from
+    pub path: String,
+    /// Files given with `--files-from`, instead of walking `path`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
+    /// Which of the files to analyze
+    #[serde(default, skip_serializing_if = "FileSelection::is_empty")]
+    pub selection: FileSelection,
+    pub recursive: bool,
+    pub dry_run: bool,
+    pub min_confidence: f64,
+    pub started_at: DateTime<Utc>,
+    /// Set once every file was processed without errors
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// What a batch analysis did, or in a dry run would do, with a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanOutcome {
+    /// Renamed, confident enough to apply
+    Rename,
+    /// Queued for review
+    Review,
+    /// Left alone, confidence too low
+    Skip,
+    /// Below the run's `--min-confidence`
+    BelowMinimum,
+    /// Analysis or the rename failed
+    Failed,
+}
+
+impl ScanOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rename => "rename",
+            Self::Review => "review",
+            Self::Skip => "skip",
+            Self::BelowMinimum => "below_minimum",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "rename" => Some(Self::Rename),
+            "review" => Some(Self::Review),
+            "skip" => Some(Self::Skip),
+            "below_minimum" => Some(Self::BelowMinimum),
+            "failed" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// The outcome for one file of a batch analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub path: String,
+    pub outcome: ScanOutcome,
+    /// File name the suggestion comes to, naming rules and extension applied
+    pub suggested_name: Option<String>,
+    /// Where the file was renamed to
+    pub new_path: Option<String>,
+    pub confidence: Option<f64>,
+    pub category: Option<String>,
+    pub error: Option<String>,
+    /// Time spent analyzing the file
+    pub elapsed_ms: u64,
+}
+
+/// One attempt to deliver a webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    /// Shared by all attempts to deliver the same event to the same URL
+    pub delivery_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub url: String,
+    pub event: String,
+    /// 1 for the first try
+    pub attempt: u32,
+    /// HTTP status of the response, if one was received
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub success: bool,
+}
+
 /// Database statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DbStats {
@@ -56,9 +918,21 @@ pub struct DbStats {
     pub category_count: i64,
 }
 
+/// How many rows [`Database::prune`] removed
+#[derive(Debug, Clone, Default)]
+pub struct PruneCounts {
+    pub records: usize,
+    pub history: usize,
+    pub tags: usize,
+}
+
 impl Database {
     /// Open or create the database
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
         let conn = Connection::open(path)?;
         let db = Self {
             conn: Arc::new(Mutex::new(conn)),
@@ -83,7 +957,7 @@ fn lock_conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
 
     /// Initialize database schema
     fn initialize(&self) -> Result<()> {
-        let conn = self.lock_conn()?;
+        let mut conn = self.lock_conn()?;
         conn.execute_batch(r#"
             CREATE TABLE IF NOT EXISTS files (
                 id TEXT PRIMARY KEY,
@@ -112,7 +986,8 @@ fn initialize(&self) -> Result<()> {
             CREATE INDEX IF NOT EXISTS idx_files_hash ON files(file_hash);
             CREATE INDEX IF NOT EXISTS idx_files_category ON files(category);
         "#)?;
-        Ok(())
+
+        migrate(&mut conn, MIGRATIONS)
     }
 
     /// Insert a new file record
@@ -137,27 +1012,97 @@ pub fn insert_file(
         Ok(())
     }
 
-    /// Add a tag
+    /// Store an analysis result and its tags, returning the new record ID
+    pub fn record_analysis(&self, path: &Path, result: &AnalysisResult) -> Result<String> {
+        let file_id = new_file_id();
+        self.insert_file(
+            &file_id,
+            path.to_str().unwrap_or(""),
+            &result.suggested_name,
+            &result.file_hash,
+            result.category.as_deref(),
+            result.confidence,
+            &result.metadata,
+        )?;
+        self.lock_conn()?.execute(
+            "UPDATE files SET analyzer = ?2, model = ?3 WHERE id = ?1",
+            params![file_id, result.analyzer, result.model],
+        )?;
+
+        for tag in &result.tags {
+            if let Err(e) = self.add_tag(&file_id, tag, result.category.as_deref()) {
+                tracing::debug!("Failed to add tag '{}': {}", tag, e);
+            }
+        }
+
+        Ok(file_id)
+    }
+
+    /// Set `key` in a file's metadata to `value`
+    pub fn set_metadata_field(&self, file_id: &str, key: &str, value: &serde_json::Value) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE files SET metadata = json_set(COALESCE(metadata, '{}'), '$.' || ?2, json(?3)) WHERE id = ?1",
+            params![file_id, key, serde_json::to_string(value)?],
+        )?;
+        Ok(())
+    }
+
+    /// Replace a file's analysis (suggestion, hash, category, metadata and tags)
+    /// with a new result; tags users added stay
+    pub fn update_analysis(&self, file_id: &str, result: &AnalysisResult) -> Result<()> {
+        {
+            let conn = self.lock_conn()?;
+            let metadata_json = serde_json::to_string(&result.metadata)?;
+            conn.execute(
+                r#"UPDATE files SET suggested_name = ?2, file_hash = ?3, category = ?4, confidence = ?5, metadata = ?6,
+                       analyzer = ?7, model = ?8
+                   WHERE id = ?1"#,
+                params![file_id, result.suggested_name, result.file_hash, result.category, result.confidence, metadata_json,
+                    result.analyzer, result.model],
+            )?;
+            conn.execute("DELETE FROM file_tags WHERE file_id = ?1 AND source != 'user'", params![file_id])?;
+        }
+
+        for tag in &result.tags {
+            if let Err(e) = self.add_tag(file_id, tag, result.category.as_deref()) {
+                tracing::debug!("Failed to add tag '{}': {}", tag, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a tag suggested by analysis
     pub fn add_tag(&self, file_id: &str, tag_name: &str, category: Option<&str>) -> Result<()> {
+        self.link_tag(file_id, tag_name, category, TagSource::Analysis)
+    }
+
+    /// Attach a tag to a file, creating the tag if needed. A user adding a tag
+    /// analysis already attached makes it theirs.
+    fn link_tag(&self, file_id: &str, tag_name: &str, category: Option<&str>, source: TagSource) -> Result<()> {
         let conn = self.lock_conn()?;
 
-        // Insert tag if not exists
+        // Insert tag if not exists (UNIQUE doesn't catch a NULL category);
+        // without a category, a tag of that name in any category will do
         conn.execute(
-            "INSERT OR IGNORE INTO tags (name, category) VALUES (?1, ?2)",
+            r#"INSERT INTO tags (name, category) SELECT ?1, ?2
+               WHERE NOT EXISTS (SELECT 1 FROM tags WHERE name = ?1 AND (category IS ?2 OR ?2 IS NULL))"#,
             params![tag_name, category],
         )?;
 
-        // Get tag id
+        // Get tag id, preferring the one in this category
         let tag_id: i64 = conn.query_row(
-            "SELECT id FROM tags WHERE name = ?1",
-            params![tag_name],
+            "SELECT id FROM tags WHERE name = ?1 ORDER BY category IS ?2 DESC, id LIMIT 1",
+            params![tag_name, category],
             |row| row.get(0),
         )?;
 
         // Link to file
         conn.execute(
-            "INSERT OR IGNORE INTO file_tags (file_id, tag_id) VALUES (?1, ?2)",
-            params![file_id, tag_id],
+            r#"INSERT INTO file_tags (file_id, tag_id, source) VALUES (?1, ?2, ?3)
+               ON CONFLICT (file_id, tag_id) DO UPDATE SET source = excluded.source
+               WHERE excluded.source = 'user'"#,
+            params![file_id, tag_id, source.as_str()],
         )?;
 
         Ok(())
@@ -182,7 +1127,7 @@ pub fn get_all_categories(&self) -> Result<Vec<Category>> {
         let conn = self.lock_conn()?;
         let mut stmt = conn.prepare(
             r#"SELECT COALESCE(category, 'Uncategorized') as cat, COUNT(*) as cnt
-               FROM files GROUP BY category ORDER BY cnt DESC"#
+               FROM files WHERE deleted_at IS NULL GROUP BY category ORDER BY cnt DESC"#
         )?;
         let cats = stmt.query_map([], |row| {
             Ok(Category {
@@ -196,32 +1141,47 @@ pub fn get_all_categories(&self) -> Result<Vec<Category>> {
 
     /// Search files
     pub fn search_files(&self, query: &str, limit: usize) -> Result<Vec<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let pattern = format!("%{}%", query);
+        let mut stmt = conn.prepare(&format!(
+            r#"SELECT {} FROM files f
+               WHERE f.deleted_at IS NULL
+                 AND (f.suggested_name LIKE ?1 OR f.original_path LIKE ?1 OR f.current_path LIKE ?1)
+               ORDER BY f.created_at DESC LIMIT ?2"#,
+            FILE_COLUMNS
+        ))?;
+
+        let files = stmt.query_map(params![pattern, limit as i64], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(files)
+    }
+
+    /// Facet fields of every file whose name or path contains `query`, newest first
+    pub fn get_facet_rows(&self, query: &str) -> Result<Vec<FacetRow>> {
         let conn = self.lock_conn()?;
         let pattern = format!("%{}%", query);
         let mut stmt = conn.prepare(
-            r#"SELECT id, original_path, suggested_name, file_hash, category, confidence, metadata, created_at
-               FROM files WHERE suggested_name LIKE ?1 OR original_path LIKE ?1
-               ORDER BY created_at DESC LIMIT ?2"#
+            r#"SELECT f.id, COALESCE(f.current_path, f.original_path), f.category, f.confidence, f.created_at,
+                      (SELECT group_concat(t.name, char(31)) FROM file_tags ft JOIN tags t ON t.id = ft.tag_id
+                       WHERE ft.file_id = f.id)
+               FROM files f
+               WHERE f.deleted_at IS NULL
+                 AND (f.suggested_name LIKE ?1 OR f.original_path LIKE ?1 OR f.current_path LIKE ?1)
+               ORDER BY f.created_at DESC, f.rowid DESC"#
         )?;
-
-        let files = stmt.query_map(params![pattern, limit as i64], |row| {
-            let metadata_str: String = row.get(6)?;
-            let created_str: String = row.get(7)?;
-            Ok(FileRecord {
+        let rows = stmt.query_map(params![pattern], |row| {
+            let created: String = row.get(4)?;
+            let tags: Option<String> = row.get(5)?;
+            Ok(FacetRow {
                 id: row.get(0)?,
-                original_path: row.get(1)?,
-                new_path: row.get(1)?,
-                suggested_name: row.get(2)?,
-                file_hash: row.get(3)?,
-                category: row.get(4)?,
-                confidence: row.get(5)?,
-                metadata: serde_json::from_str(&metadata_str).unwrap_or(serde_json::json!({})),
-                created_at: DateTime::parse_from_rfc3339(&created_str)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now()),
+                path: row.get(1)?,
+                category: row.get(2)?,
+                confidence: row.get(3)?,
+                created_at: parse_timestamp(&created),
+                tags: tags.map(|t| t.split('\u{1f}').map(str::to_string).collect()).unwrap_or_default(),
             })
         })?.collect::<rusqlite::Result<Vec<_>>>()?;
-        Ok(files)
+        Ok(rows)
     }
 
     /// Get all files
@@ -229,19 +1189,101 @@ pub fn get_all_files(&self) -> Result<Vec<FileRecord>> {
         self.search_files("", 1000)
     }
 
+    /// Every file record that hasn't been deleted, however many there are
+    pub fn get_live_files(&self) -> Result<Vec<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE f.deleted_at IS NULL ORDER BY f.created_at", FILE_COLUMNS
+        ))?;
+        let files = stmt.query_map([], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(files)
+    }
+
+    /// Records hidden by [`Self::soft_delete_file`]
+    pub fn get_deleted_files(&self) -> Result<Vec<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE f.deleted_at IS NOT NULL ORDER BY f.created_at", FILE_COLUMNS
+        ))?;
+        let files = stmt.query_map([], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(files)
+    }
+
+    /// Point a record at where its file is now, e.g. after it was moved outside Panoptes
+    pub fn relink_file(&self, file_id: &str, path: &Path) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let updated = conn.execute(
+            "UPDATE files SET current_path = ?2, missing_since = NULL WHERE id = ?1",
+            params![file_id, path.to_string_lossy()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Mark a record's file as gone, or as present again
+    pub fn set_missing(&self, file_id: &str, missing: bool) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let since = missing.then(|| Utc::now().to_rfc3339());
+        let updated = conn.execute(
+            "UPDATE files SET missing_since = CASE WHEN ?2 IS NULL THEN NULL ELSE COALESCE(missing_since, ?2) END WHERE id = ?1",
+            params![file_id, since],
+        )?;
+        Ok(updated > 0)
+    }
+
     /// Get database statistics
     pub fn get_stats(&self) -> Result<DbStats> {
         let conn = self.lock_conn()?;
-        let file_count: i64 = conn.query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0))?;
+        let file_count: i64 = conn.query_row("SELECT COUNT(*) FROM files WHERE deleted_at IS NULL", [], |row| row.get(0))?;
         let tag_count: i64 = conn.query_row("SELECT COUNT(*) FROM tags", [], |row| row.get(0))?;
         let category_count: i64 = conn.query_row(
-            "SELECT COUNT(DISTINCT category) FROM files WHERE category IS NOT NULL",
+            "SELECT COUNT(DISTINCT category) FROM files WHERE category IS NOT NULL AND deleted_at IS NULL",
             [],
             |row| row.get(0),
         )?;
         Ok(DbStats { file_count, tag_count, category_count })
     }
 
+    /// Delete the file records `file_ids`, rename events from before
+    /// `history_before` and, with `tags`, tags no file has. With `dry_run`
+    /// nothing is deleted, but the counts are what would be.
+    pub fn prune(
+        &self,
+        file_ids: &[String],
+        history_before: Option<DateTime<Utc>>,
+        tags: bool,
+        dry_run: bool,
+    ) -> Result<PruneCounts> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        let mut counts = PruneCounts::default();
+
+        for id in file_ids {
+            tx.execute("DELETE FROM file_tags WHERE file_id = ?1", params![id])?;
+            counts.records += tx.execute("DELETE FROM files WHERE id = ?1", params![id])?;
+        }
+        if let Some(before) = history_before {
+            counts.history = tx.execute(
+                "DELETE FROM renames WHERE julianday(timestamp) < julianday(?1)",
+                params![before.to_rfc3339()],
+            )?;
+        }
+        if tags {
+            tx.execute("DELETE FROM file_tags WHERE file_id NOT IN (SELECT id FROM files)", [])?;
+            counts.tags = tx.execute("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM file_tags)", [])?;
+        }
+
+        // Counting inside the transaction sees each step's effect on the next,
+        // e.g. tags left without files once their records go
+        if dry_run {
+            tx.rollback()?;
+        } else {
+            tx.commit()?;
+        }
+        Ok(counts)
+    }
+
     /// Vacuum database
     pub fn vacuum(&self) -> Result<()> {
         let conn = self.lock_conn()?;
@@ -249,53 +1291,58 @@ pub fn vacuum(&self) -> Result<()> {
         Ok(())
     }
 
-    /// Check for duplicate by hash
-    pub fn find_duplicate(&self, hash: &str) -> Result<Option<String>> {
+    /// Records of files whose contents hash to `hash`, oldest first
+    pub fn find_files_by_hash(&self, hash: &str) -> Result<Vec<FileRecord>> {
         let conn = self.lock_conn()?;
-        let result: rusqlite::Result<String> = conn.query_row(
-            "SELECT id FROM files WHERE file_hash = ?1 LIMIT 1",
-            params![hash],
-            |row| row.get(0),
-        );
-        match result {
-            Ok(id) => Ok(Some(id)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e.into()),
-        }
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE f.file_hash = ?1 AND f.deleted_at IS NULL ORDER BY f.created_at",
+            FILE_COLUMNS
+        ))?;
+        let files = stmt.query_map(params![hash], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(files)
     }
 
     // Methods for web UI compatibility
     pub fn get_recent_files(&self, limit: usize) -> Result<Vec<FileRecord>> {
         let conn = self.lock_conn()?;
-        let mut stmt = conn.prepare(
-            r#"SELECT id, original_path, suggested_name, file_hash, category, confidence, metadata, created_at
-               FROM files ORDER BY created_at DESC LIMIT ?1"#
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE f.deleted_at IS NULL ORDER BY f.created_at DESC LIMIT ?1",
+            FILE_COLUMNS
+        ))?;
 
-        let files = stmt.query_map(params![limit as i64], |row| {
-            let metadata_str: String = row.get(6)?;
-            let created_str: String = row.get(7)?;
-            Ok(FileRecord {
-                id: row.get(0)?,
-                original_path: row.get(1)?,
-                new_path: row.get(1)?,
-                suggested_name: row.get(2)?,
-                file_hash: row.get(3)?,
-                category: row.get(4)?,
-                confidence: row.get(5)?,
-                metadata: serde_json::from_str(&metadata_str).unwrap_or(serde_json::json!({})),
-                created_at: DateTime::parse_from_rfc3339(&created_str)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now()),
-            })
-        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        let files = stmt.query_map(params![limit as i64], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
         Ok(files)
     }
 
+    /// A page of files matching `filter`, with the total number of matches
+    pub fn query_files(&self, filter: &FileFilter, limit: usize, offset: usize) -> Result<(Vec<FileRecord>, i64)> {
+        let conn = self.lock_conn()?;
+        let (where_clause, mut values) = filter.where_clause();
+
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM files f {}", where_clause),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+
+        values.push(SqlValue::Integer(limit as i64));
+        values.push(SqlValue::Integer(offset as i64));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f {} {} LIMIT ? OFFSET ?",
+            FILE_COLUMNS, where_clause, filter.order_clause()
+        ))?;
+        let files = stmt.query_map(params_from_iter(values.iter()), file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok((files, total))
+    }
+
     pub fn get_category_stats(&self) -> Result<Vec<(String, i64)>> {
         let conn = self.lock_conn()?;
         let mut stmt = conn.prepare(
-            r#"SELECT COALESCE(category, 'Uncategorized'), COUNT(*) FROM files GROUP BY category"#
+            r#"SELECT COALESCE(category, 'Uncategorized'), COUNT(*) FROM files
+               WHERE deleted_at IS NULL GROUP BY category"#
         )?;
         let stats = stmt.query_map([], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
@@ -303,55 +1350,1488 @@ pub fn get_category_stats(&self) -> Result<Vec<(String, i64)>> {
         Ok(stats)
     }
 
-    pub fn get_file_count(&self) -> Result<i64> {
+    /// Files processed per day and category on or after `since`, oldest day first
+    pub fn get_daily_category_counts(&self, since: NaiveDate) -> Result<Vec<(NaiveDate, String, i64)>> {
         let conn = self.lock_conn()?;
-        conn.query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0))
-            .map_err(Into::into)
+        let mut stmt = conn.prepare(
+            r#"SELECT date(created_at), COALESCE(category, 'Uncategorized'), COUNT(*) FROM files
+               WHERE date(created_at) >= ?1 AND deleted_at IS NULL
+               GROUP BY 1, 2 ORDER BY 1"#
+        )?;
+        let rows = stmt.query_map(params![since.format("%Y-%m-%d").to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows.into_iter()
+            .filter_map(|(day, category, count)| {
+                NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok().map(|day| (day, category, count))
+            })
+            .collect())
     }
 
-    pub fn get_files_by_category(&self, category: &str, limit: usize) -> Result<Vec<FileRecord>> {
+    /// Number of files processed on or after `since` in each of `buckets` equal
+    /// confidence ranges from 0 to 1
+    pub fn get_confidence_histogram(&self, since: NaiveDate, buckets: usize) -> Result<Vec<i64>> {
+        let buckets = buckets.max(1);
         let conn = self.lock_conn()?;
         let mut stmt = conn.prepare(
-            r#"SELECT id, original_path, suggested_name, file_hash, category, confidence, metadata, created_at
-               FROM files WHERE category = ?1 ORDER BY created_at DESC LIMIT ?2"#
+            r#"SELECT MIN(MAX(CAST(confidence * ?1 AS INTEGER), 0), ?1 - 1), COUNT(*) FROM files
+               WHERE date(created_at) >= ?2 AND deleted_at IS NULL
+               GROUP BY 1"#
         )?;
+        let mut counts = vec![0; buckets];
+        let rows = stmt.query_map(params![buckets as i64, since.format("%Y-%m-%d").to_string()], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (bucket, count) = row?;
+            counts[bucket as usize] = count;
+        }
+        Ok(counts)
+    }
 
-        let files = stmt.query_map(params![category, limit as i64], |row| {
-            let metadata_str: String = row.get(6)?;
-            let created_str: String = row.get(7)?;
-            Ok(FileRecord {
-                id: row.get(0)?,
-                original_path: row.get(1)?,
-                new_path: row.get(1)?,
-                suggested_name: row.get(2)?,
-                file_hash: row.get(3)?,
-                category: row.get(4)?,
-                confidence: row.get(5)?,
-                metadata: serde_json::from_str(&metadata_str).unwrap_or(serde_json::json!({})),
-                created_at: DateTime::parse_from_rfc3339(&created_str)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now()),
-            })
-        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    /// Files analyzed, and renames and moves done, since `since`
+    pub fn count_activity_since(&self, since: DateTime<Utc>) -> Result<(i64, i64)> {
+        let conn = self.lock_conn()?;
+        let since = since.format("%Y-%m-%d %H:%M:%S").to_string();
+        let analyzed = conn.query_row(
+            "SELECT COUNT(*) FROM files WHERE datetime(created_at) >= datetime(?1)",
+            params![since],
+            |row| row.get(0),
+        )?;
+        let renamed = conn.query_row(
+            "SELECT COUNT(*) FROM renames WHERE datetime(timestamp) >= datetime(?1) AND undone = 0 AND action IN ('rename', 'move')",
+            params![since],
+            |row| row.get(0),
+        )?;
+        Ok((analyzed, renamed))
+    }
+
+    pub fn get_file_count(&self) -> Result<i64> {
+        let conn = self.lock_conn()?;
+        conn.query_row("SELECT COUNT(*) FROM files WHERE deleted_at IS NULL", [], |row| row.get(0))
+            .map_err(Into::into)
+    }
+
+    pub fn get_files_by_category(&self, category: &str, limit: usize) -> Result<Vec<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE f.category = ?1 AND f.deleted_at IS NULL ORDER BY f.created_at DESC LIMIT ?2",
+            FILE_COLUMNS
+        ))?;
+
+        let files = stmt.query_map(params![category, limit as i64], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
         Ok(files)
     }
 
+    /// Add a tag on a user's behalf; re-analysis keeps it
     pub fn add_tag_to_file(&self, file_id: &str, tag_name: &str) -> Result<()> {
-        self.add_tag(file_id, tag_name, None)
+        self.link_tag(file_id, tag_name, None, TagSource::User)
     }
 
-    pub fn remove_tag_from_file(&self, file_id: &str, tag_name: &str) -> Result<()> {
+    /// Remove a tag from a file; returns whether the file had it
+    pub fn remove_tag_from_file(&self, file_id: &str, tag_name: &str) -> Result<bool> {
         let conn = self.lock_conn()?;
-        conn.execute(
+        let removed = conn.execute(
             r#"DELETE FROM file_tags WHERE file_id = ?1
-               AND tag_id = (SELECT id FROM tags WHERE name = ?2)"#,
+               AND tag_id IN (SELECT id FROM tags WHERE name = ?2)"#,
             params![file_id, tag_name],
         )?;
+        Ok(removed > 0)
+    }
+
+    /// Tag names with the number of files carrying each
+    pub fn get_tag_counts(&self) -> Result<Vec<(String, i64)>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT t.name, COUNT(DISTINCT f.id) FROM tags t
+               LEFT JOIN file_tags ft ON ft.tag_id = t.id
+               LEFT JOIN files f ON f.id = ft.file_id AND f.deleted_at IS NULL
+               GROUP BY t.name ORDER BY t.name"#
+        )?;
+        let counts = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(counts)
+    }
+
+    /// Whether any tag has this name
+    pub fn tag_exists(&self, name: &str) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM tags WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Fold the `sources` tags into `into` on every file, deleting the sources.
+    /// `into` is created if needed, so renaming a tag is a merge with one source.
+    /// Returns the number of files that carried a source tag.
+    pub fn merge_tags(&self, sources: &[String], into: &str) -> Result<usize> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+
+        let target: i64 = match tx.query_row(
+            "SELECT id FROM tags WHERE name = ?1 ORDER BY id LIMIT 1",
+            params![into],
+            |row| row.get(0),
+        ).optional()? {
+            Some(id) => id,
+            None => {
+                // Keep the category of the tag being renamed
+                let category: Option<String> = match sources.first() {
+                    Some(source) => tx.query_row(
+                        "SELECT category FROM tags WHERE name = ?1 ORDER BY id LIMIT 1",
+                        params![source],
+                        |row| row.get(0),
+                    ).optional()?.flatten(),
+                    None => None,
+                };
+                tx.execute("INSERT INTO tags (name, category) VALUES (?1, ?2)", params![into, category])?;
+                tx.last_insert_rowid()
+            }
+        };
+
+        let mut files = 0;
+        for source in sources.iter().filter(|s| s.as_str() != into) {
+            files += tx.query_row(
+                r#"SELECT COUNT(DISTINCT ft.file_id) FROM file_tags ft
+                   JOIN tags t ON t.id = ft.tag_id WHERE t.name = ?1"#,
+                params![source],
+                |row| row.get::<_, i64>(0),
+            )? as usize;
+            tx.execute(
+                r#"INSERT INTO file_tags (file_id, tag_id, source)
+                   SELECT ft.file_id, ?2, ft.source FROM file_tags ft
+                   JOIN tags t ON t.id = ft.tag_id WHERE t.name = ?1
+                   ON CONFLICT (file_id, tag_id) DO UPDATE SET source = excluded.source
+                   WHERE excluded.source = 'user'"#,
+                params![source, target],
+            )?;
+            tx.execute(
+                "DELETE FROM file_tags WHERE tag_id IN (SELECT id FROM tags WHERE name = ?1)",
+                params![source],
+            )?;
+            tx.execute("DELETE FROM tags WHERE name = ?1", params![source])?;
+        }
+
+        tx.commit()?;
+        Ok(files)
+    }
+
+    /// Get a single file record by ID
+    pub fn get_file(&self, id: &str) -> Result<Option<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE f.id = ?1 AND f.deleted_at IS NULL",
+            FILE_COLUMNS
+        ))?;
+        let mut files = stmt.query_map(params![id], file_from_row)?;
+        Ok(files.next().transpose()?)
+    }
+
+    /// The record for the file now at `path`, newest first if it was analyzed more than once
+    pub fn find_file_by_path(&self, path: &Path) -> Result<Option<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE COALESCE(f.current_path, f.original_path) = ?1 AND f.deleted_at IS NULL
+             ORDER BY f.created_at DESC LIMIT 1",
+            FILE_COLUMNS
+        ))?;
+        let mut files = stmt.query_map(params![path.to_string_lossy()], file_from_row)?;
+        Ok(files.next().transpose()?)
+    }
+
+    /// Names of the tags attached to a file
+    pub fn get_file_tags(&self, file_id: &str) -> Result<Vec<String>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT t.name FROM tags t
+               JOIN file_tags ft ON ft.tag_id = t.id
+               WHERE ft.file_id = ?1
+               ORDER BY t.name"#
+        )?;
+        let tags = stmt.query_map(params![file_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(tags)
+    }
+
+    /// Names of the tags of every file that has any, by file ID
+    pub fn get_all_file_tags(&self) -> Result<HashMap<String, Vec<String>>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT ft.file_id, t.name FROM tags t
+               JOIN file_tags ft ON ft.tag_id = t.id
+               ORDER BY t.name"#
+        )?;
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for row in stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))? {
+            let (file_id, tag) = row?;
+            tags.entry(file_id).or_default().push(tag);
+        }
+        Ok(tags)
+    }
+
+    /// Tags attached to a file with who attached each
+    pub fn get_file_tag_sources(&self, file_id: &str) -> Result<Vec<(String, TagSource)>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT t.name, ft.source FROM tags t
+               JOIN file_tags ft ON ft.tag_id = t.id
+               WHERE ft.file_id = ?1
+               ORDER BY t.name"#
+        )?;
+        let tags = stmt.query_map(params![file_id], |row| {
+            Ok((row.get(0)?, TagSource::parse(&row.get::<_, String>(1)?)))
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(tags)
+    }
+
+    /// Find a file record by its ID (a unique prefix of the ID is accepted)
+    pub fn find_file(&self, id: &str) -> Result<Option<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE substr(f.id, 1, length(?1)) = ?1 AND f.deleted_at IS NULL",
+            FILE_COLUMNS
+        ))?;
+        let files = stmt.query_map(params![id], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        match files.len() {
+            0 | 1 => Ok(files.into_iter().next()),
+            n => match files.into_iter().find(|f| f.id == id) {
+                Some(exact) => Ok(Some(exact)),
+                None => Err(PanoptesError::Config(format!("File ID prefix '{}' is ambiguous ({} matches)", id, n))),
+            },
+        }
+    }
+
+    /// Number of files with the given review status
+    pub fn count_files_by_status(&self, status: ReviewStatus) -> Result<i64> {
+        let conn = self.lock_conn()?;
+        let count = conn.query_row(
+            "SELECT COUNT(*) FROM files WHERE status = ?1 AND deleted_at IS NULL",
+            params![status.as_str()],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    /// Files with the given review status (oldest first, so the queue is worked in order)
+    pub fn get_files_by_status(&self, status: ReviewStatus, limit: usize) -> Result<Vec<FileRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files f WHERE f.status = ?1 AND f.deleted_at IS NULL ORDER BY f.created_at ASC LIMIT ?2",
+            FILE_COLUMNS
+        ))?;
+        let files = stmt.query_map(params![status.as_str(), limit as i64], file_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(files)
+    }
+
+    /// Set a file's review status
+    pub fn set_review_status(&self, file_id: &str, status: ReviewStatus) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE files SET status = ?2 WHERE id = ?1",
+            params![file_id, status.as_str()],
+        )?;
+        Ok(())
+    }
+
+    /// Set or clear a file's category
+    pub fn set_category(&self, file_id: &str, category: Option<&str>) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let updated = conn.execute(
+            "UPDATE files SET category = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+            params![file_id, category],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Hide a file record from listings, search and stats. The row is kept so
+    /// duplicate detection still recognises the file if it is scanned again.
+    pub fn soft_delete_file(&self, file_id: &str) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let updated = conn.execute(
+            "UPDATE files SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+            params![file_id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Record the name a user chose instead of the suggestion
+    pub fn set_corrected_name(&self, file_id: &str, name: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE files SET corrected_name = ?2 WHERE id = ?1",
+            params![file_id, name],
+        )?;
+        Ok(())
+    }
+
+    /// Record the name a user chose instead of the suggestion as a correction
+    /// of the suggestion, with where it came from; false when there is no such file
+    pub fn record_correction(&self, file_id: &str, name: &str, source: CorrectionSource) -> Result<bool> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        let inserted = tx.execute(
+            r#"INSERT INTO feedback (file_id, timestamp, source, suggested_name, corrected_name, analyzer, model, category, confidence)
+               SELECT id, ?2, ?3, suggested_name, ?4, analyzer, model, category, COALESCE(confidence, 0.0)
+               FROM files WHERE id = ?1"#,
+            params![file_id, Utc::now().to_rfc3339(), source.as_str(), name],
+        )?;
+        tx.execute("UPDATE files SET corrected_name = ?2 WHERE id = ?1", params![file_id, name])?;
+        tx.commit()?;
+        Ok(inserted > 0)
+    }
+
+    /// Store the fingerprint of the files with its hash, replacing any before
+    pub fn set_fingerprint(&self, fingerprint: &Fingerprint) -> Result<()> {
+        let conn = self.lock_conn()?;
+        let embedding = fingerprint.embedding.as_ref().map(serde_json::to_string).transpose()?;
+        conn.execute(
+            r#"INSERT OR REPLACE INTO fingerprints (file_hash, image_hash, embedding, embedding_model, created_at)
+               VALUES (?1, ?2, ?3, ?4, ?5)"#,
+            params![
+                fingerprint.file_hash,
+                fingerprint.image_hash.map(|h| format!("{:016x}", h)),
+                embedding,
+                fingerprint.embedding_model,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The fingerprint of the files with hash `file_hash`, if made
+    pub fn get_fingerprint(&self, file_hash: &str) -> Result<Option<Fingerprint>> {
+        let conn = self.lock_conn()?;
+        Ok(conn.query_row(
+            "SELECT file_hash, image_hash, embedding, embedding_model FROM fingerprints WHERE file_hash = ?1",
+            params![file_hash],
+            fingerprint_from_row,
+        ).optional()?)
+    }
+
+    /// Every fingerprint made
+    pub fn get_fingerprints(&self) -> Result<Vec<Fingerprint>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT file_hash, image_hash, embedding, embedding_model FROM fingerprints")?;
+        let fingerprints = stmt.query_map([], fingerprint_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(fingerprints)
+    }
+
+    /// Store the transcript of the recordings with its hash, done with
+    /// their chunks
+    pub fn set_transcript(&self, transcript: &Transcript) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            r#"INSERT OR REPLACE INTO transcripts (file_hash, model, language, text, duration_secs, created_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#,
+            params![
+                transcript.file_hash,
+                transcript.model,
+                transcript.language,
+                transcript.text,
+                transcript.duration_secs,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        conn.execute("DELETE FROM transcript_chunks WHERE file_hash = ?1", params![transcript.file_hash])?;
         Ok(())
     }
+
+    /// The transcript of the recordings with hash `file_hash`, if made
+    pub fn get_transcript(&self, file_hash: &str) -> Result<Option<Transcript>> {
+        let conn = self.lock_conn()?;
+        Ok(conn.query_row(
+            "SELECT file_hash, model, language, text, duration_secs FROM transcripts WHERE file_hash = ?1",
+            params![file_hash],
+            transcript_from_row,
+        ).optional()?)
+    }
+
+    /// Store the text of chunk `chunk` of a transcription in progress
+    pub fn set_transcript_chunk(&self, file_hash: &str, model: &str, chunk_secs: u32, chunk: usize, text: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            r#"INSERT OR REPLACE INTO transcript_chunks (file_hash, model, chunk_secs, chunk, text)
+               VALUES (?1, ?2, ?3, ?4, ?5)"#,
+            params![file_hash, model, chunk_secs, chunk as i64, text],
+        )?;
+        Ok(())
+    }
+
+    /// The chunks transcribed so far of the recordings with hash `file_hash`,
+    /// by `model` in chunks of `chunk_secs`, by number
+    pub fn get_transcript_chunks(&self, file_hash: &str, model: &str, chunk_secs: u32) -> Result<HashMap<usize, String>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT chunk, text FROM transcript_chunks WHERE file_hash = ?1 AND model = ?2 AND chunk_secs = ?3",
+        )?;
+        let chunks = stmt.query_map(params![file_hash, model, chunk_secs], |row| {
+            Ok((row.get::<_, i64>(0)? as usize, row.get(1)?))
+        })?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(chunks)
+    }
+
+    /// Store a summary of a directory's contents
+    pub fn insert_summary(&self, summary: &Summary) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT INTO summaries (id, path, created_at, data) VALUES (?1, ?2, ?3, ?4)",
+            params![summary.id, summary.path, summary.created_at.to_rfc3339(), serde_json::to_string(summary)?],
+        )?;
+        Ok(())
+    }
+
+    /// The latest summary of the directory at `path`, if it was summarized
+    pub fn latest_summary(&self, path: &str) -> Result<Option<Summary>> {
+        let conn = self.lock_conn()?;
+        let data: Option<String> = conn.query_row(
+            "SELECT data FROM summaries WHERE path = ?1 ORDER BY created_at DESC LIMIT 1",
+            params![path],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+    }
+
+    /// Recorded corrections, newest first
+    pub fn get_corrections(&self, limit: usize, offset: usize) -> Result<Vec<Correction>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM feedback ORDER BY id DESC LIMIT ?1 OFFSET ?2", CORRECTION_COLUMNS
+        ))?;
+        let corrections = stmt.query_map(params![limit as i64, offset as i64], correction_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(corrections)
+    }
+
+    /// How many of the recorded files of each analyzer and model had their name corrected
+    pub fn get_correction_rates(&self) -> Result<Vec<CorrectionRate>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT f.analyzer, f.model, COUNT(*),
+                   SUM(EXISTS (SELECT 1 FROM feedback fb WHERE fb.file_id = f.id))
+               FROM files f WHERE f.deleted_at IS NULL
+               GROUP BY f.analyzer, f.model"#
+        )?;
+        let rates = stmt.query_map([], |row| {
+            Ok(CorrectionRate { analyzer: row.get(0)?, model: row.get(1)?, files: row.get(2)?, corrected: row.get(3)? })
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rates)
+    }
+
+    /// Record a rename/move event, updating the linked file's current path;
+    /// deletions are recorded as they are
+    pub fn insert_rename(&self, entry: &HistoryEntry) -> Result<()> {
+        let conn = self.lock_conn()?;
+
+        conn.execute(
+            r#"INSERT OR REPLACE INTO renames (id, file_id, timestamp, original_path, new_path, ai_suggestion,
+                   category, tags, file_hash, undone, session_id, action)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"#,
+            params![
+                entry.id,
+                entry.file_id,
+                entry.timestamp.to_rfc3339(),
+                entry.original_path.to_string_lossy(),
+                entry.new_path.to_string_lossy(),
+                entry.ai_suggestion,
+                entry.category,
+                serde_json::to_string(&entry.tags)?,
+                entry.file_hash,
+                entry.undone,
+                entry.session_id,
+                entry.action.as_str(),
+            ],
+        )?;
+
+        if let Some(ref file_id) = entry.file_id {
+            if !entry.undone && !entry.action.is_removal() {
+                conn.execute(
+                    "UPDATE files SET current_path = ?2 WHERE id = ?1",
+                    params![file_id, entry.new_path.to_string_lossy()],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All rename events, oldest first
+    pub fn get_renames(&self) -> Result<Vec<HistoryEntry>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM renames ORDER BY timestamp ASC, rowid ASC",
+            RENAME_COLUMNS
+        ))?;
+        let entries = stmt.query_map([], rename_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Get a page of rename events (newest first)
+    pub fn get_renames_page(&self, limit: usize, offset: usize) -> Result<Vec<HistoryEntry>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM renames ORDER BY timestamp DESC, rowid DESC LIMIT ?1 OFFSET ?2",
+            RENAME_COLUMNS
+        ))?;
+        let entries = stmt.query_map(params![limit as i64, offset as i64], rename_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Total number of rename events
+    pub fn get_rename_count(&self) -> Result<i64> {
+        let conn = self.lock_conn()?;
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM renames", [], |row| row.get(0))?;
+        Ok(count)
+    }
+
+    /// Whether a rename event with this ID exists
+    pub fn rename_exists(&self, id: &str) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM renames WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Mark rename events as undone, each with the path its file was put
+    /// back at, which becomes the file's current path
+    pub fn mark_renames_undone(&self, undone: &[(&str, &Path)]) -> Result<()> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        for (id, restored) in undone {
+            tx.execute("UPDATE renames SET undone = 1 WHERE id = ?1", params![id])?;
+            tx.execute(
+                "UPDATE files SET current_path = ?2 WHERE id = (SELECT file_id FROM renames WHERE id = ?1)",
+                params![id, restored.to_string_lossy()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Delete all rename events
+    pub fn clear_renames(&self) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute("DELETE FROM renames", [])?;
+        Ok(())
+    }
+
+    /// Store a new API token by its hash
+    pub fn create_api_token(&self, name: &str, token_hash: &str, role: Role) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT INTO api_tokens (name, token_hash, created_at, role) VALUES (?1, ?2, ?3, ?4)",
+            params![name, token_hash, Utc::now().to_rfc3339(), role.as_str()],
+        )?;
+        Ok(())
+    }
+
+    /// List API tokens
+    pub fn list_api_tokens(&self) -> Result<Vec<ApiToken>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT name, created_at, last_used, role FROM api_tokens ORDER BY name")?;
+        let tokens = stmt.query_map([], |row| {
+            let created: String = row.get(1)?;
+            let last_used: Option<String> = row.get(2)?;
+            let role: String = row.get(3)?;
+            Ok(ApiToken {
+                name: row.get(0)?,
+                role: role_from_str(&role),
+                created_at: parse_timestamp(&created),
+                last_used: last_used.as_deref().map(parse_timestamp),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(tokens)
+    }
+
+    /// Delete an API token; returns whether it existed
+    pub fn revoke_api_token(&self, name: &str) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let deleted = conn.execute("DELETE FROM api_tokens WHERE name = ?1", params![name])?;
+        Ok(deleted > 0)
+    }
+
+    /// Name and role of the API token with this hash, recording its use
+    pub fn use_api_token(&self, token_hash: &str) -> Result<Option<(String, Role)>> {
+        let conn = self.lock_conn()?;
+        let token: Option<(String, String)> = conn.query_row(
+            "SELECT name, role FROM api_tokens WHERE token_hash = ?1",
+            params![token_hash],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+        if token.is_some() {
+            conn.execute(
+                "UPDATE api_tokens SET last_used = ?2 WHERE token_hash = ?1",
+                params![token_hash, Utc::now().to_rfc3339()],
+            )?;
+        }
+        Ok(token.map(|(name, role)| (name, role_from_str(&role))))
+    }
+
+    /// Role of a named API token
+    pub fn get_api_token_role(&self, name: &str) -> Result<Option<Role>> {
+        let conn = self.lock_conn()?;
+        let role: Option<String> = conn.query_row(
+            "SELECT role FROM api_tokens WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(role.as_deref().map(role_from_str))
+    }
+
+    /// Register the agent `name` with the hash of its new token, replacing
+    /// the token of an agent registered under that name before
+    pub fn register_agent(&self, name: &str, hostname: Option<&str>, version: Option<&str>, token_hash: &str) -> Result<Agent> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        let now = Utc::now().to_rfc3339();
+        let token_name = crate::agent::token_name(name);
+        tx.execute("DELETE FROM api_tokens WHERE name = ?1", params![token_name])?;
+        tx.execute(
+            "INSERT INTO api_tokens (name, token_hash, created_at, role) VALUES (?1, ?2, ?3, ?4)",
+            params![token_name, token_hash, now, Role::Editor.as_str()],
+        )?;
+        tx.execute(
+            r#"INSERT INTO agents (name, hostname, version, registered_at) VALUES (?1, ?2, ?3, ?4)
+               ON CONFLICT(name) DO UPDATE SET hostname = ?2, version = ?3, registered_at = ?4"#,
+            params![name, hostname, version, now],
+        )?;
+        let agent = tx.query_row(
+            "SELECT name, hostname, version, registered_at, last_seen, files FROM agents WHERE name = ?1",
+            params![name],
+            agent_from_row,
+        )?;
+        tx.commit()?;
+        Ok(agent)
+    }
+
+    /// Registered agents, by name
+    pub fn get_agents(&self) -> Result<Vec<Agent>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT name, hostname, version, registered_at, last_seen, files FROM agents ORDER BY name"
+        )?;
+        let agents = stmt.query_map([], agent_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(agents)
+    }
+
+    pub fn get_agent(&self, name: &str) -> Result<Option<Agent>> {
+        let conn = self.lock_conn()?;
+        Ok(conn.query_row(
+            "SELECT name, hostname, version, registered_at, last_seen, files FROM agents WHERE name = ?1",
+            params![name],
+            agent_from_row,
+        ).optional()?)
+    }
+
+    /// Note the agent `name` forwarding a file
+    pub fn touch_agent(&self, name: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE agents SET last_seen = ?2, files = files + 1 WHERE name = ?1",
+            params![name, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Forget the agent `name` and revoke its token; returns whether it was registered
+    pub fn remove_agent(&self, name: &str) -> Result<bool> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM api_tokens WHERE name = ?1", params![crate::agent::token_name(name)])?;
+        let removed = tx.execute("DELETE FROM agents WHERE name = ?1", params![name])?;
+        tx.commit()?;
+        Ok(removed > 0)
+    }
+
+    /// The versions of the files of the source `source` already dealt with,
+    /// by key; None for ones renamed there, whose version isn't known yet.
+    /// Files that failed are left out, to be tried again.
+    pub fn remote_versions(&self, source: &str) -> Result<HashMap<String, Option<String>>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT key, version FROM remote_objects WHERE source = ?1 AND state != 'failed'")?;
+        let versions = stmt.query_map(params![source], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(versions)
+    }
+
+    /// Record what became of a file of a remote source
+    pub fn record_remote_object(&self, record: &RemoteRecord) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            &format!("INSERT OR REPLACE INTO remote_objects ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)", REMOTE_OBJECT_COLUMNS),
+            params![
+                record.source, record.key, record.version, record.state.as_str(), record.file_id, record.new_key,
+                record.confidence, record.error, record.seen_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Note the version of a file renamed on its source, now it has been listed
+    pub fn set_remote_version(&self, source: &str, key: &str, version: Option<&str>) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE remote_objects SET version = ?3 WHERE source = ?1 AND key = ?2",
+            params![source, key, version],
+        )?;
+        Ok(())
+    }
+
+    /// Files of remote sources in `state`, of the source `source` or of all,
+    /// by source and key
+    pub fn get_remote_objects(&self, source: Option<&str>, state: RemoteState) -> Result<Vec<RemoteRecord>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM remote_objects WHERE (?1 IS NULL OR source = ?1) AND state = ?2 ORDER BY source, key",
+            REMOTE_OBJECT_COLUMNS
+        ))?;
+        let records = stmt.query_map(params![source, state.as_str()], remote_object_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    /// How many files of the source `source` are in each state
+    pub fn remote_object_counts(&self, source: &str) -> Result<HashMap<RemoteState, i64>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT state, COUNT(*) FROM remote_objects WHERE source = ?1 GROUP BY state")?;
+        let counts = stmt.query_map(params![source], |row| {
+            let state: String = row.get(0)?;
+            Ok((state.parse().unwrap_or(RemoteState::Failed), row.get(1)?))
+        })?.collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(counts)
+    }
+
+    /// Start a login session for `subject`
+    pub fn create_web_session(&self, id_hash: &str, subject: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "DELETE FROM web_sessions WHERE expires_at < ?1",
+            params![Utc::now().to_rfc3339()],
+        )?;
+        conn.execute(
+            "INSERT INTO web_sessions (id_hash, subject, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+            params![id_hash, subject, Utc::now().to_rfc3339(), expires_at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Subject of an unexpired login session
+    pub fn get_web_session(&self, id_hash: &str) -> Result<Option<String>> {
+        let conn = self.lock_conn()?;
+        let subject = conn.query_row(
+            "SELECT subject FROM web_sessions WHERE id_hash = ?1 AND expires_at > ?2",
+            params![id_hash, Utc::now().to_rfc3339()],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(subject)
+    }
+
+    /// Username linked to an external identity
+    pub fn find_user_by_identity(&self, issuer: &str, subject: &str) -> Result<Option<String>> {
+        let conn = self.lock_conn()?;
+        let username = conn.query_row(
+            r#"SELECT u.username FROM users u
+               JOIN user_identities i ON i.user_id = u.id
+               WHERE i.issuer = ?1 AND i.subject = ?2"#,
+            params![issuer, subject],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(username)
+    }
+
+    /// Create a user, deriving a unique username from `base`; returns the username.
+    /// The first user ever created becomes an admin.
+    pub fn create_user(&self, base: &str, email: Option<&str>, display_name: Option<&str>, role: Role) -> Result<String> {
+        let conn = self.lock_conn()?;
+        let base: String = base.chars()
+            .map(|c| if c.is_alphanumeric() || "._@-".contains(c) { c } else { '_' })
+            .collect();
+
+        let mut username = base.clone();
+        let mut n = 2;
+        loop {
+            let taken: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM users WHERE username = ?1",
+                params![username],
+                |row| row.get(0),
+            )?;
+            if taken == 0 {
+                break;
+            }
+            username = format!("{}-{}", base, n);
+            n += 1;
+        }
+
+        let existing: i64 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
+        let role = if existing == 0 { Role::Admin } else { role };
+
+        conn.execute(
+            "INSERT INTO users (id, username, email, display_name, created_at, role) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![Uuid::new_v4().to_string(), username, email, display_name, Utc::now().to_rfc3339(), role.as_str()],
+        )?;
+        Ok(username)
+    }
+
+    /// All users
+    pub fn list_users(&self) -> Result<Vec<User>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT username, role, email, display_name, created_at FROM users ORDER BY username"
+        )?;
+        let users = stmt.query_map([], |row| {
+            let role: String = row.get(1)?;
+            let created: String = row.get(4)?;
+            Ok(User {
+                username: row.get(0)?,
+                role: role_from_str(&role),
+                email: row.get(2)?,
+                display_name: row.get(3)?,
+                created_at: parse_timestamp(&created),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(users)
+    }
+
+    /// Role of a user
+    pub fn get_user_role(&self, username: &str) -> Result<Option<Role>> {
+        let conn = self.lock_conn()?;
+        let role: Option<String> = conn.query_row(
+            "SELECT role FROM users WHERE username = ?1",
+            params![username],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(role.as_deref().map(role_from_str))
+    }
+
+    /// Change a user's role; returns whether the user exists
+    pub fn set_user_role(&self, username: &str, role: Role) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let updated = conn.execute(
+            "UPDATE users SET role = ?2 WHERE username = ?1",
+            params![username, role.as_str()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Append to the audit log
+    pub fn record_audit(&self, actor: &str, action: &str, target: Option<&str>, details: &serde_json::Value) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT INTO audit_log (timestamp, actor, action, target, details) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![Utc::now().to_rfc3339(), actor, action, target, serde_json::to_string(details)?],
+        )?;
+        Ok(())
+    }
+
+    /// A page of the audit log (newest first)
+    pub fn get_audit_log(&self, limit: usize, offset: usize) -> Result<Vec<AuditEntry>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT id, timestamp, actor, action, target, details FROM audit_log
+               ORDER BY id DESC LIMIT ?1 OFFSET ?2"#
+        )?;
+        let entries = stmt.query_map(params![limit as i64, offset as i64], |row| {
+            let timestamp: String = row.get(1)?;
+            let details: String = row.get(5)?;
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                timestamp: parse_timestamp(&timestamp),
+                actor: row.get(2)?,
+                action: row.get(3)?,
+                target: row.get(4)?,
+                details: serde_json::from_str(&details).unwrap_or(serde_json::json!({})),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Log a webhook delivery attempt
+    pub fn record_webhook_delivery(
+        &self,
+        delivery_id: &str,
+        url: &str,
+        event: &str,
+        attempt: u32,
+        status: Option<u16>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        let conn = self.lock_conn()?;
+        let success = error.is_none() && status.is_some_and(|s| (200..300).contains(&s));
+        conn.execute(
+            r#"INSERT INTO webhook_deliveries (delivery_id, timestamp, url, event, attempt, status, error, success)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"#,
+            params![delivery_id, Utc::now().to_rfc3339(), url, event, attempt, status, error, success],
+        )?;
+        Ok(())
+    }
+
+    /// A page of webhook delivery attempts (newest first)
+    pub fn get_webhook_deliveries(&self, limit: usize, offset: usize) -> Result<Vec<WebhookDelivery>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT id, delivery_id, timestamp, url, event, attempt, status, error, success
+               FROM webhook_deliveries ORDER BY id DESC LIMIT ?1 OFFSET ?2"#
+        )?;
+        let deliveries = stmt.query_map(params![limit as i64, offset as i64], |row| {
+            let timestamp: String = row.get(2)?;
+            Ok(WebhookDelivery {
+                id: row.get(0)?,
+                delivery_id: row.get(1)?,
+                timestamp: parse_timestamp(&timestamp),
+                url: row.get(3)?,
+                event: row.get(4)?,
+                attempt: row.get(5)?,
+                status: row.get(6)?,
+                error: row.get(7)?,
+                success: row.get(8)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(deliveries)
+    }
+
+    /// Report whether the daemon is watching `path` (`error` is why not)
+    pub fn set_watch_status(&self, path: &str, error: Option<&str>) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            r#"INSERT INTO watch_status (path, error, updated_at) VALUES (?1, ?2, ?3)
+               ON CONFLICT(path) DO UPDATE SET error = excluded.error, updated_at = excluded.updated_at"#,
+            params![path, error, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Note that a file appeared in the watch directory `path`
+    pub fn record_watch_event(&self, path: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE watch_status SET last_event_at = ?2 WHERE path = ?1",
+            params![path, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Forget a directory the daemon no longer watches
+    pub fn remove_watch_status(&self, path: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute("DELETE FROM watch_status WHERE path = ?1", params![path])?;
+        Ok(())
+    }
+
+    /// Everything the daemon has reported about watch directories
+    pub fn get_watch_statuses(&self) -> Result<Vec<WatchStatus>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT path, error, last_event_at, updated_at FROM watch_status ORDER BY path")?;
+        let statuses = stmt.query_map([], |row| {
+            let last_event_at: Option<String> = row.get(2)?;
+            let updated_at: String = row.get(3)?;
+            Ok(WatchStatus {
+                path: row.get(0)?,
+                error: row.get(1)?,
+                last_event_at: last_event_at.as_deref().map(parse_timestamp),
+                updated_at: parse_timestamp(&updated_at),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(statuses)
+    }
+
+    /// Record a run of a scheduled task, replacing its last one
+    pub fn record_scheduled_run(&self, run: &ScheduledRun) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            r#"INSERT OR REPLACE INTO scheduled_runs (name, started_at, finished_at, summary, error)
+               VALUES (?1, ?2, ?3, ?4, ?5)"#,
+            params![run.name, run.started_at.to_rfc3339(), run.finished_at.to_rfc3339(), run.summary, run.error],
+        )?;
+        Ok(())
+    }
+
+    /// The last run of each scheduled task that has run
+    pub fn get_scheduled_runs(&self) -> Result<Vec<ScheduledRun>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT name, started_at, finished_at, summary, error FROM scheduled_runs ORDER BY name")?;
+        let runs = stmt.query_map([], |row| {
+            let started_at: String = row.get(1)?;
+            let finished_at: String = row.get(2)?;
+            Ok(ScheduledRun {
+                name: row.get(0)?,
+                started_at: parse_timestamp(&started_at),
+                finished_at: parse_timestamp(&finished_at),
+                summary: row.get(3)?,
+                error: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(runs)
+    }
+
+    /// Copy the database to `path`, which must not exist yet
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// A value that changes whenever anything is written to the database, by
+    /// this connection or another
+    pub fn change_count(&self) -> Result<(i64, i64)> {
+        let conn = self.lock_conn()?;
+        let data_version: i64 = conn.query_row("PRAGMA data_version", [], |row| row.get(0))?;
+        let total_changes: i64 = conn.query_row("SELECT total_changes()", [], |row| row.get(0))?;
+        Ok((data_version, total_changes))
+    }
+
+    /// Check the database for corruption, update the statistics queries are
+    /// planned with and vacuum it; fails with what is wrong if it is corrupt
+    pub fn maintain(&self) -> Result<()> {
+        let conn = self.lock_conn()?;
+        let problems: Vec<String> = conn.prepare("PRAGMA quick_check")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        if problems.iter().any(|p| p != "ok") {
+            return Err(PanoptesError::Config(format!("The database is damaged: {}", problems.join("; "))));
+        }
+        conn.execute_batch("ANALYZE; VACUUM;")?;
+        Ok(())
+    }
+
+    /// Record the start of a batch analysis
+    pub fn create_scan_run(&self, run: &ScanRun) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            r#"INSERT INTO scan_runs (id, path, recursive, dry_run, min_confidence, started_at, finished_at, files, selection)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#,
+            params![
+                run.id,
+                run.path,
+                run.recursive,
+                run.dry_run,
+                run.min_confidence,
+                run.started_at.to_rfc3339(),
+                run.finished_at.map(|t| t.to_rfc3339()),
+                run.files.as_ref().map(serde_json::to_string).transpose()?,
+                (!run.selection.is_empty()).then(|| serde_json::to_string(&run.selection)).transpose()?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Find a batch analysis by its ID (a unique prefix of the ID is accepted)
+    pub fn find_scan_run(&self, id: &str) -> Result<Option<ScanRun>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM scan_runs WHERE substr(id, 1, length(?1)) = ?1 ORDER BY started_at DESC",
+            SCAN_RUN_COLUMNS
+        ))?;
+        let runs = stmt.query_map(params![id], scan_run_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        match runs.len() {
+            0 | 1 => Ok(runs.into_iter().next()),
+            n => match runs.into_iter().find(|r| r.id == id) {
+                Some(exact) => Ok(Some(exact)),
+                None => Err(PanoptesError::Config(format!("Run ID prefix '{}' is ambiguous ({} matches)", id, n))),
+            },
+        }
+    }
+
+    /// The most recently started batch analysis
+    pub fn latest_scan_run(&self) -> Result<Option<ScanRun>> {
+        let conn = self.lock_conn()?;
+        let run = conn.query_row(
+            &format!("SELECT {} FROM scan_runs ORDER BY started_at DESC LIMIT 1", SCAN_RUN_COLUMNS),
+            [],
+            scan_run_from_row,
+        ).optional()?;
+        Ok(run)
+    }
+
+    /// Record the outcome for a file of a run, replacing an earlier attempt's
+    pub fn record_scan_result(&self, run_id: &str, result: &ScanResult) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            r#"INSERT OR REPLACE INTO scan_results
+               (run_id, path, outcome, suggested_name, new_path, confidence, category, error, elapsed_ms, recorded_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"#,
+            params![
+                run_id,
+                result.path,
+                result.outcome.as_str(),
+                result.suggested_name,
+                result.new_path,
+                result.confidence,
+                result.category,
+                result.error,
+                result.elapsed_ms as i64,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Outcomes recorded for a run, by path
+    pub fn get_scan_results(&self, run_id: &str) -> Result<Vec<ScanResult>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT path, outcome, suggested_name, new_path, confidence, category, error, elapsed_ms
+               FROM scan_results WHERE run_id = ?1 ORDER BY path"#
+        )?;
+        let results = stmt.query_map(params![run_id], |row| {
+            let outcome: String = row.get(1)?;
+            let elapsed_ms: i64 = row.get(7)?;
+            Ok(ScanResult {
+                path: row.get(0)?,
+                outcome: ScanOutcome::parse(&outcome).unwrap_or(ScanOutcome::Failed),
+                suggested_name: row.get(2)?,
+                new_path: row.get(3)?,
+                confidence: row.get(4)?,
+                category: row.get(5)?,
+                error: row.get(6)?,
+                elapsed_ms: elapsed_ms.max(0) as u64,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(results)
+    }
+
+    /// Files whose latest batch analysis failed and that haven't been
+    /// recorded since, most recent failure first
+    pub fn get_failed_scan_paths(&self, limit: usize) -> Result<Vec<String>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT r.path FROM scan_results r
+               WHERE r.outcome = 'failed'
+                 AND NOT EXISTS (SELECT 1 FROM scan_results s
+                                 WHERE s.path = r.path AND julianday(s.recorded_at) > julianday(r.recorded_at))
+                 AND NOT EXISTS (SELECT 1 FROM files f
+                                 WHERE f.deleted_at IS NULL
+                                   AND (f.original_path = r.path OR f.current_path = r.path)
+                                   AND julianday(f.created_at) > julianday(r.recorded_at))
+               GROUP BY r.path ORDER BY MAX(r.recorded_at) DESC LIMIT ?1"#
+        )?;
+        let paths = stmt.query_map(params![limit as i64], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(paths)
+    }
+
+    /// Note that a run has finished with the file whose contents hash to `file_hash`
+    pub fn mark_scanned(&self, run_id: &str, file_hash: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO scan_progress (run_id, file_hash, processed_at) VALUES (?1, ?2, ?3)",
+            params![run_id, file_hash, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Hashes of the files a run has finished with
+    pub fn get_scanned_hashes(&self, run_id: &str) -> Result<HashSet<String>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT file_hash FROM scan_progress WHERE run_id = ?1")?;
+        let hashes = stmt.query_map(params![run_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(hashes)
+    }
+
+    /// Mark a run as complete
+    pub fn finish_scan_run(&self, run_id: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE scan_runs SET finished_at = ?2 WHERE id = ?1",
+            params![run_id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Add a job to the queue, unless the same task is already waiting there;
+    /// returns its ID
+    pub fn enqueue_job(&self, task: &Task, priority: i32, max_attempts: u32, correlation_id: &str) -> Result<i64> {
+        let payload = serde_json::to_string(task)?;
+        let conn = self.lock_conn()?;
+        let waiting: Option<i64> = conn.query_row(
+            "SELECT id FROM jobs WHERE payload = ?1 AND state = 'queued'",
+            params![payload],
+            |row| row.get(0),
+        ).optional()?;
+        if let Some(id) = waiting {
+            return Ok(id);
+        }
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            r#"INSERT INTO jobs (kind, payload, state, priority, max_attempts, run_at, created_at, correlation_id)
+               VALUES (?1, ?2, 'queued', ?3, ?4, ?5, ?5, ?6)"#,
+            params![task.kind().as_str(), payload, priority, max_attempts, now, correlation_id],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Take the next job that is due, highest priority and then oldest
+    /// first, counting the attempt
+    pub fn claim_job(&self, owner: &str) -> Result<Option<Job>> {
+        let conn = self.lock_conn()?;
+        let job = conn.query_row(
+            &format!(
+                r#"UPDATE jobs SET state = 'running', attempts = attempts + 1, started_at = ?1,
+                                  owner = ?2, heartbeat_at = ?1
+                   WHERE id = (SELECT id FROM jobs WHERE state = 'queued' AND julianday(run_at) <= julianday(?1)
+                               ORDER BY priority DESC, id LIMIT 1)
+                   RETURNING {}"#,
+                JOB_COLUMNS
+            ),
+            params![Utc::now().to_rfc3339(), owner],
+            job_from_row,
+        ).optional()?;
+        Ok(job)
+    }
+
+    /// Say that the jobs `owner` is running still are; returns how many
+    pub fn heartbeat_jobs(&self, owner: &str) -> Result<usize> {
+        let conn = self.lock_conn()?;
+        Ok(conn.execute(
+            "UPDATE jobs SET heartbeat_at = ?2 WHERE state = 'running' AND owner = ?1",
+            params![owner, Utc::now().to_rfc3339()],
+        )?)
+    }
+
+    /// Mark a job `owner` is running as done. These return whether `owner`
+    /// still held the job: once requeued as stale, it belongs to whoever took
+    /// it next and is left to them.
+    pub fn finish_job(&self, id: i64, owner: &str) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let updated = conn.execute(
+            "UPDATE jobs SET state = 'done', finished_at = ?3 WHERE id = ?1 AND owner = ?2 AND state = 'running'",
+            params![id, owner, Utc::now().to_rfc3339()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Record a failed attempt at a job `owner` is running: it is queued again
+    /// for `retry_at`, or dead without one
+    pub fn fail_job(&self, id: i64, owner: &str, error: &str, retry_at: Option<DateTime<Utc>>) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let updated = match retry_at {
+            Some(at) => conn.execute(
+                r#"UPDATE jobs SET state = 'queued', last_error = ?3, run_at = ?4, owner = NULL, heartbeat_at = NULL
+                   WHERE id = ?1 AND owner = ?2 AND state = 'running'"#,
+                params![id, owner, error, at.to_rfc3339()],
+            )?,
+            None => conn.execute(
+                r#"UPDATE jobs SET state = 'dead', last_error = ?3, finished_at = ?4
+                   WHERE id = ?1 AND owner = ?2 AND state = 'running'"#,
+                params![id, owner, error, Utc::now().to_rfc3339()],
+            )?,
+        };
+        Ok(updated > 0)
+    }
+
+    /// Queue a job `owner` is running again for `at` without counting the
+    /// attempt it just made, for failures that are nobody's fault, like a
+    /// server being down
+    pub fn defer_job(&self, id: i64, owner: &str, error: &str, at: DateTime<Utc>) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let updated = conn.execute(
+            r#"UPDATE jobs SET state = 'queued', attempts = MAX(attempts - 1, 0), last_error = ?3, run_at = ?4,
+                               owner = NULL, heartbeat_at = NULL
+               WHERE id = ?1 AND owner = ?2 AND state = 'running'"#,
+            params![id, owner, error, at.to_rfc3339()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Queue jobs left running by a process that stopped, those whose
+    /// heartbeat is older than `cutoff`; returns how many
+    pub fn requeue_stale_jobs(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let conn = self.lock_conn()?;
+        Ok(conn.execute(
+            r#"UPDATE jobs SET state = 'queued', owner = NULL, heartbeat_at = NULL
+               WHERE state = 'running'
+                 AND (heartbeat_at IS NULL OR julianday(heartbeat_at) < julianday(?1))"#,
+            params![cutoff.to_rfc3339()],
+        )?)
+    }
+
+    pub fn get_job(&self, id: i64) -> Result<Option<Job>> {
+        let conn = self.lock_conn()?;
+        let job = conn.query_row(
+            &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+            params![id],
+            job_from_row,
+        ).optional()?;
+        Ok(job)
+    }
+
+    /// The latest jobs, in `state` if given, newest first
+    pub fn list_jobs(&self, state: Option<JobState>, limit: usize) -> Result<Vec<Job>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM jobs WHERE ?1 IS NULL OR state = ?1 ORDER BY id DESC LIMIT ?2",
+            JOB_COLUMNS
+        ))?;
+        let jobs = stmt.query_map(params![state.map(|s| s.as_str()), limit as i64], job_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(jobs)
+    }
+
+    /// How many jobs are in each state, those with none left out
+    pub fn count_jobs(&self) -> Result<Vec<(JobState, i64)>> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn.prepare("SELECT state, COUNT(*) FROM jobs GROUP BY state")?;
+        let counts = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, i64)>>>()?;
+        Ok(counts.into_iter().filter_map(|(state, n)| Some((state.parse().ok()?, n))).collect())
+    }
+
+    /// Queue a dead or cancelled job again, its attempts starting over;
+    /// returns whether it was one
+    pub fn retry_job(&self, id: i64) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let changed = conn.execute(
+            r#"UPDATE jobs SET state = 'queued', attempts = 0, run_at = ?2, finished_at = NULL
+               WHERE id = ?1 AND state IN ('dead', 'cancelled')"#,
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Queue every dead job again; returns how many there were
+    pub fn retry_dead_jobs(&self) -> Result<usize> {
+        let conn = self.lock_conn()?;
+        Ok(conn.execute(
+            "UPDATE jobs SET state = 'queued', attempts = 0, run_at = ?1, finished_at = NULL WHERE state = 'dead'",
+            params![Utc::now().to_rfc3339()],
+        )?)
+    }
+
+    /// Cancel a job that hasn't started; returns whether it was waiting
+    pub fn cancel_job(&self, id: i64) -> Result<bool> {
+        let conn = self.lock_conn()?;
+        let changed = conn.execute(
+            "UPDATE jobs SET state = 'cancelled', finished_at = ?2 WHERE id = ?1 AND state = 'queued'",
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Remove done and cancelled jobs, and dead ones with `dead`; returns how many
+    pub fn clear_jobs(&self, dead: bool) -> Result<usize> {
+        let conn = self.lock_conn()?;
+        let states = if dead { "('done', 'cancelled', 'dead')" } else { "('done', 'cancelled')" };
+        Ok(conn.execute(&format!("DELETE FROM jobs WHERE state IN {}", states), [])?)
+    }
+
+    /// Remove done and cancelled jobs that finished before `cutoff`; returns how many
+    pub fn prune_jobs(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let conn = self.lock_conn()?;
+        Ok(conn.execute(
+            "DELETE FROM jobs WHERE state IN ('done', 'cancelled') AND julianday(finished_at) < julianday(?1)",
+            params![cutoff.to_rfc3339()],
+        )?)
+    }
+
+    /// Link an external identity to a user
+    pub fn link_identity(&self, issuer: &str, subject: &str, username: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            r#"INSERT OR REPLACE INTO user_identities (issuer, subject, user_id)
+               SELECT ?1, ?2, id FROM users WHERE username = ?3"#,
+            params![issuer, subject, username],
+        )?;
+        Ok(())
+    }
+
+    /// End a login session
+    pub fn delete_web_session(&self, id_hash: &str) -> Result<()> {
+        let conn = self.lock_conn()?;
+        conn.execute("DELETE FROM web_sessions WHERE id_hash = ?1", params![id_hash])?;
+        Ok(())
+    }
+}
+
+/// Run the `migrations` past the schema version `conn` is at
+fn migrate(conn: &mut Connection, migrations: &[&str]) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    // Each with its version bump, so one failing partway is tried afresh
+    for (i, migration) in migrations.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
 }
 
 /// Generate a new UUID for file records
 pub fn new_file_id() -> String {
     Uuid::new_v4().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(conn: &Connection) -> usize {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+    }
+
+    fn has_table(conn: &Connection, name: &str) -> bool {
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", params![name], |row| row.get::<_, i64>(0))
+            .unwrap() > 0
+    }
+
+    #[test]
+    fn test_migrations_run_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("panoptes.db");
+        let db = Database::open(&path).unwrap();
+        let file_id = db.record_analysis(Path::new("/tmp/scan.pdf"), &crate::analyzers::AnalysisResult {
+            suggested_name: "invoice".to_string(),
+            confidence: 0.9,
+            category: None,
+            tags: Vec::new(),
+            file_hash: "abc".to_string(),
+            metadata: serde_json::json!({}),
+            analyzer: None,
+            model: None,
+        }).unwrap();
+        drop(db);
+
+        // Opening again finds nothing to do and keeps what is there
+        let db = Database::open(&path).unwrap();
+        assert_eq!(user_version(&db.lock_conn().unwrap()), MIGRATIONS.len());
+        assert!(db.get_file(&file_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_failed_migration_is_rolled_back() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn, &["CREATE TABLE a (x);"]).unwrap();
+
+        // The second statement fails, so the first is undone and the version stays
+        let migrations = ["CREATE TABLE a (x);", "CREATE TABLE b (x); CREATE TABLE a (y);"];
+        assert!(migrate(&mut conn, &migrations).is_err());
+        assert_eq!(user_version(&conn), 1);
+        assert!(!has_table(&conn, "b"));
+
+        // and it is tried afresh once fixed
+        migrate(&mut conn, &["CREATE TABLE a (x);", "CREATE TABLE b (x);"]).unwrap();
+        assert_eq!(user_version(&conn), 2);
+        assert!(has_table(&conn, "b"));
+    }
+}
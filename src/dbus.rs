@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! The `org.panoptes.Scanner` D-Bus service, on Linux
+//!
+//! With `dbus.enabled`, `panoptes watch` takes the name `org.panoptes.Scanner`
+//! on the session bus (or the system bus, with `dbus.bus = "system"`) and
+//! serves `/org/panoptes/Scanner`, so desktop integrations (Nautilus scripts,
+//! GNOME extensions...) needn't run the CLI. Its methods:
+//!
+//! - `AnalyzePath(s path) -> t`: queue a file in a watch directory for
+//!   analysis; returns the job's ID, or fails for files outside them
+//! - `Pause()`, `Resume()`: stop and restart the job workers
+//! - `Rescan() -> s`: queue the files the watcher missed; returns what was done
+//! - `Status() -> (xxb)`: the queue depth, files analyzed today and whether paused
+//!
+//! And its signals, sent as the events webhooks get happen:
+//!
+//! - `FileRenamed(s file_id, s from, s to)`
+//! - `LowConfidence(s file_id, s path, s suggested_name, d confidence)`
+//! - `Failed(s path, s error)`
+//!
+//! ```sh
+//! busctl --user call org.panoptes.Scanner /org/panoptes/Scanner org.panoptes.Scanner AnalyzePath s ~/Downloads/scan.pdf
+//! ```
+
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+use zbus::object_server::SignalContext;
+use zbus::{fdo, interface, Connection};
+
+use crate::config::{DbusBus, DbusConfig};
+use crate::db::Database;
+use crate::jobs::Queue;
+use crate::mqtt::State;
+use crate::webhooks::{EventSink, WebhookEvent};
+use crate::{PanoptesError, Result};
+
+/// Well-known name of the service, and name of its interface
+pub const NAME: &str = "org.panoptes.Scanner";
+
+pub const PATH: &str = "/org/panoptes/Scanner";
+
+/// Queues a file for analysis, returning the job's ID
+pub type Analyze = Arc<dyn Fn(&Path) -> Result<i64> + Send + Sync>;
+
+/// Queues the files the watcher missed, returning what was done
+pub type Rescan = Arc<dyn Fn() -> Result<String> + Send + Sync>;
+
+struct Scanner {
+    queue: Queue,
+    db: Database,
+    analyze: Analyze,
+    rescan: Rescan,
+}
+
+fn method_error(e: impl std::fmt::Display) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+#[interface(name = "org.panoptes.Scanner")]
+impl Scanner {
+    async fn analyze_path(&self, path: String) -> fdo::Result<u64> {
+        let analyze = self.analyze.clone();
+        let id = tokio::task::spawn_blocking(move || analyze(Path::new(&path))).await.map_err(method_error)?;
+        id.map(|id| id as u64).map_err(method_error)
+    }
+
+    fn pause(&self) {
+        info!("Paused over D-Bus");
+        self.queue.pause();
+    }
+
+    fn resume(&self) {
+        info!("Resumed over D-Bus");
+        self.queue.resume();
+    }
+
+    async fn rescan(&self) -> fdo::Result<String> {
+        let rescan = self.rescan.clone();
+        tokio::task::spawn_blocking(move || rescan()).await.map_err(method_error)?.map_err(method_error)
+    }
+
+    fn status(&self) -> fdo::Result<(i64, i64, bool)> {
+        let state = State::collect(&self.db, self.queue.is_paused()).map_err(method_error)?;
+        Ok((state.queue_depth, state.files_today, state.paused))
+    }
+
+    #[zbus(signal)]
+    async fn file_renamed(ctxt: &SignalContext<'_>, file_id: &str, from: &str, to: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn low_confidence(
+        ctxt: &SignalContext<'_>,
+        file_id: &str,
+        path: &str,
+        suggested_name: &str,
+        confidence: f64,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn failed(ctxt: &SignalContext<'_>, path: &str, error: &str) -> zbus::Result<()>;
+}
+
+/// The service, on its bus for as long as it is kept
+pub struct Dbus {
+    connection: Connection,
+}
+
+impl Dbus {
+    /// Take [`NAME`] on the configured bus, if `dbus.enabled`
+    pub async fn start(config: &DbusConfig, queue: Queue, db: Database, analyze: Analyze, rescan: Rescan) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let scanner = Scanner { queue, db, analyze, rescan };
+        let builder = match config.bus {
+            DbusBus::Session => zbus::connection::Builder::session(),
+            DbusBus::System => zbus::connection::Builder::system(),
+        };
+        let connection = async { builder?.name(NAME)?.serve_at(PATH, scanner)?.build().await }.await
+            .map_err(|e| PanoptesError::Config(format!("Failed to start the D-Bus service: {}", e)))?;
+        info!("D-Bus service {} on the {:?} bus", NAME, config.bus);
+        Ok(Some(Self { connection }))
+    }
+}
+
+impl EventSink for Dbus {
+    fn publish(&self, event: WebhookEvent, data: &serde_json::Value) {
+        let (connection, data) = (self.connection.clone(), data.clone());
+        tokio::spawn(async move {
+            if let Err(e) = signal(&connection, event, &data).await {
+                debug!("Failed to send the D-Bus signal for {}: {}", event.as_str(), e);
+            }
+        });
+    }
+}
+
+/// Send the signal for `event`, if it has one
+async fn signal(connection: &Connection, event: WebhookEvent, data: &serde_json::Value) -> zbus::Result<()> {
+    let ctxt = SignalContext::new(connection, PATH)?;
+    let text = |key: &str| data[key].as_str().unwrap_or_default().to_string();
+    match event {
+        WebhookEvent::Renamed => Scanner::file_renamed(&ctxt, &text("file_id"), &text("original_path"), &text("new_path")).await,
+        WebhookEvent::LowConfidence => {
+            let confidence = data["confidence"].as_f64().unwrap_or_default();
+            Scanner::low_confidence(&ctxt, &text("file_id"), &text("path"), &text("suggested_name"), confidence).await
+        }
+        WebhookEvent::Error => Scanner::failed(&ctxt, &text("path"), &text("error")).await,
+        WebhookEvent::ReviewQueue | WebhookEvent::Rule => Ok(()),
+    }
+}
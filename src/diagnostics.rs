@@ -0,0 +1,333 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Checks of a configuration against the machine it runs on
+//!
+//! `panoptes config validate` goes beyond [`AppConfig::problems`], which
+//! only looks at the settings themselves: watch directories must exist and
+//! be writable, directories files are sent to must be creatable, URLs must
+//! parse, naming and page templates must compile, and the models must be on
+//! the AI engine. Each finding names its setting, and the file and line it
+//! is set on when it comes from a file.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::analyzers::AnalyzerRegistry;
+use crate::config::layers::{self, Setting, Source};
+use crate::ollama::OllamaClient;
+use crate::organizer::expand_home;
+use crate::web::templates::Templates;
+use crate::{plugins, AppConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The configuration won't work as meant
+    Error,
+    /// It works, though likely not as meant
+    Warning,
+}
+
+/// A finding about one setting
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Dotted path of the setting, e.g. `rules.max_length`
+    pub field: String,
+    /// Starts with the setting, as in `rules.max_length must be at least 8`
+    pub message: String,
+    /// The config file setting it, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+/// Findings, gathered before their files and lines are known
+#[derive(Default)]
+struct Findings(Vec<(Severity, String)>);
+
+impl Findings {
+    fn error(&mut self, message: String) {
+        self.0.push((Severity::Error, message));
+    }
+
+    fn warning(&mut self, message: String) {
+        self.0.push((Severity::Warning, message));
+    }
+}
+
+/// Check `config`, the configuration of the project whose file is
+/// `config_path`; the AI engine's models are only checked with `engine`
+pub async fn check(config: &AppConfig, config_path: &Path, engine: bool) -> Vec<Diagnostic> {
+    let mut findings = Findings::default();
+    for problem in config.problems() {
+        findings.error(problem);
+    }
+    check_urls(config, &mut findings);
+    check_directories(config, &mut findings);
+    check_templates(config, &mut findings);
+    for plugin in plugins::discover(Path::new(&config.plugins.directory)) {
+        if let Err(e) = &plugin.manifest {
+            findings.warning(format!("plugins.directory: plugin {} is not used: {}", plugin.name(), e));
+        }
+    }
+    check_file_types(config, &mut findings);
+    if engine {
+        check_models(config, &mut findings).await;
+    }
+
+    // Where each setting is set, for the lines
+    let settings = layers::resolve(config_path).map(|(_, settings)| settings).unwrap_or_default();
+    findings.0.into_iter()
+        .map(|(severity, message)| {
+            let field = field_of(&message);
+            let (file, line) = locate(&field, &settings);
+            Diagnostic { severity, field: field.join("."), message, file, line }
+        })
+        .collect()
+}
+
+fn check_urls(config: &AppConfig, findings: &mut Findings) {
+    let mut url = |setting: &str, value: &str| {
+        if let Err(e) = reqwest::Url::parse(value) {
+            findings.error(format!("{} '{}' is not a valid URL: {}", setting, value, e));
+        }
+    };
+    url("ai_engine.url", &config.ai_engine.url);
+    for hook in &config.webhooks {
+        url("webhooks[].url", &hook.url);
+    }
+    if let Some(ref oidc) = config.web.auth.oidc {
+        url("web.auth.oidc.issuer", &oidc.issuer);
+        url("web.auth.oidc.redirect_url", &oidc.redirect_url);
+    }
+}
+
+/// Whether this process may create files in the directory `dir`
+fn writable(dir: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+            return false;
+        };
+        // SAFETY: `path` is a valid NUL-terminated string for the duration of the call
+        unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::metadata(dir).is_ok_and(|m| !m.permissions().readonly())
+    }
+}
+
+/// A directory that must exist and be writable
+fn existing_dir(setting: &str, dir: &str, findings: &mut Findings) {
+    let path = Path::new(dir);
+    if !path.exists() {
+        findings.error(format!("{}: {} does not exist", setting, dir));
+    } else if !path.is_dir() {
+        findings.error(format!("{}: {} is not a directory", setting, dir));
+    } else if !writable(path) {
+        findings.error(format!("{}: {} is not writable, so its files can't be renamed", setting, dir));
+    }
+}
+
+/// A directory files are put in, created when first needed
+fn target_dir(setting: &str, dir: &str, findings: &mut Findings) {
+    let path = expand_home(dir);
+    if path.exists() {
+        if !path.is_dir() {
+            findings.error(format!("{}: {} is not a directory", setting, dir));
+        } else if !writable(&path) {
+            findings.error(format!("{}: {} is not writable", setting, dir));
+        }
+        return;
+    }
+    // Relative directories are under the one files are renamed in; only
+    // absolute ones can be checked
+    if !path.is_absolute() {
+        return;
+    }
+    match path.ancestors().skip(1).find(|dir| dir.exists()) {
+        Some(parent) if !parent.is_dir() => {
+            findings.error(format!("{}: {} can't be created, as {} is a file", setting, dir, parent.display()));
+        }
+        Some(parent) if !writable(parent) => {
+            findings.error(format!("{}: {} can't be created in {}, which is not writable", setting, dir, parent.display()));
+        }
+        _ => {}
+    }
+}
+
+fn check_directories(config: &AppConfig, findings: &mut Findings) {
+    for dir in config.watch_paths.iter().filter(|d| !d.trim().is_empty()) {
+        existing_dir("watch_paths", dir, findings);
+    }
+    for (dir, options) in &config.watch_options {
+        if let Some(ref destination) = options.destination {
+            target_dir(&format!("watch_options[{}].destination", dir), destination, findings);
+        }
+    }
+    for rule in &config.rules.destinations {
+        target_dir("rules.destinations[].directory", &rule.directory, findings);
+    }
+    for (category, dir) in &config.organize.destinations {
+        target_dir(&format!("organize.destinations[{}]", category), dir, findings);
+    }
+    for (setting, dir) in [
+        ("organize.uncategorized", &config.organize.uncategorized),
+        ("review.quarantine_dir", &config.review.quarantine_dir),
+        ("web.inbox", &config.web.inbox),
+        ("vault.directory", &config.vault.directory),
+        ("views.directory", &config.views.directory),
+    ] {
+        if let Some(dir) = dir {
+            target_dir(setting, dir, findings);
+        }
+    }
+
+    let database = Path::new(&config.database.path);
+    let dir = database.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if !dir.is_dir() {
+        findings.error(format!("database.path: directory {} does not exist", dir.display()));
+    } else if !writable(dir) {
+        findings.error(format!("database.path: directory {} is not writable", dir.display()));
+    }
+}
+
+fn check_templates(config: &AppConfig, findings: &mut Findings) {
+    for (category, rule) in &config.rules.categories {
+        let Some(ref template) = rule.template else {
+            continue;
+        };
+        let setting = format!("rules.categories[{}].template", category);
+        let mut rest = template.as_str();
+        let mut closed = true;
+        while let Some(start) = rest.find('{') {
+            match rest[start..].find('}') {
+                Some(len) if rest[start + 1..start + len].is_empty() || rest[start + 1..start + len].contains('{') => {
+                    findings.error(format!("{} '{}' has a {{}} without a placeholder name", setting, template));
+                    closed = false;
+                    break;
+                }
+                Some(len) => rest = &rest[start + len + 1..],
+                None => {
+                    findings.error(format!("{} '{}' has a {{ without a closing }}", setting, template));
+                    closed = false;
+                    break;
+                }
+            }
+        }
+        if closed && !template.contains("{name}") {
+            findings.warning(format!("{} '{}' has no {{name}}, so files may get the same name", setting, template));
+        }
+    }
+
+    let templates = Templates::new(&config.web.templates_dir, config.web.base_path());
+    for (name, error) in templates.problems() {
+        findings.error(format!("web.templates_dir: {} does not compile: {}", name, error));
+    }
+}
+
+fn check_file_types(config: &AppConfig, findings: &mut Findings) {
+    if config.file_types.values().all(|rule| rule.analyzer.is_none()) {
+        return;
+    }
+    let registry = AnalyzerRegistry::new(config);
+    let names = registry.analyzer_names();
+    for (file_type, rule) in &config.file_types {
+        if let Some(ref name) = rule.analyzer {
+            if !names.contains(&name.as_str()) {
+                findings.error(format!("file_types[{}].analyzer '{}' is not an enabled analyzer (enabled: {})",
+                    file_type, name, names.join(", ")));
+            }
+        }
+    }
+}
+
+async fn check_models(config: &AppConfig, findings: &mut Findings) {
+    let client = OllamaClient::new(&config.ai_engine.url);
+    let models = match client.health_check().await {
+        Ok(()) => client.list_models().await,
+        Err(e) => Err(e),
+    };
+    let models = match models {
+        Ok(models) => models,
+        Err(e) => {
+            findings.warning(format!("ai_engine.url: models not checked, as the AI engine is unavailable: {}", e));
+            return;
+        }
+    };
+    let wanted = &config.ai_engine.models;
+    for (setting, model) in [("vision", &wanted.vision), ("text", &wanted.text), ("code", &wanted.code)] {
+        if !models.iter().any(|m| m.starts_with(model.as_str())) {
+            findings.error(format!(
+                "ai_engine.models.{} '{}' is not on the AI engine (available: {}); try: ollama pull {}",
+                setting, model, if models.is_empty() { "none".to_string() } else { models.join(", ") }, model
+            ));
+        }
+    }
+    let embedding = &wanted.embedding;
+    if config.similarity.embeddings && !models.iter().any(|m| m.starts_with(embedding.as_str())) {
+        findings.warning(format!(
+            "ai_engine.models.embedding '{}' is not on the AI engine, so documents are only compared by contents; try: ollama pull {}",
+            embedding, embedding
+        ));
+    }
+}
+
+/// The keys of the setting a message starts with: `rules.categories[Photos].template`
+/// gives `rules`, `categories`, `Photos`, `template`
+fn field_of(message: &str) -> Vec<String> {
+    let mut field = String::new();
+    let mut depth = 0;
+    for c in message.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ' ' | ':' | '\'' if depth == 0 => break,
+            _ => {}
+        }
+        field.push(c);
+    }
+    let mut keys = Vec::new();
+    for part in field.split('.') {
+        keys.extend(part.split(['[', ']']).filter(|p| !p.is_empty()).map(str::to_string));
+    }
+    keys
+}
+
+/// The file setting `field` and its line there
+fn locate(field: &[String], settings: &[Setting]) -> (Option<PathBuf>, Option<usize>) {
+    let dotted = field.join(".");
+    let source = settings.iter()
+        .find(|s| s.path == dotted || s.path.starts_with(&format!("{}.", dotted)) || dotted.starts_with(&format!("{}.", s.path)))
+        .map(|s| &s.source);
+    let Some(Source::File(file)) = source else {
+        return (None, None);
+    };
+    let line = std::fs::read_to_string(file).ok().and_then(|text| line_of(&text, field));
+    (Some(file.clone()), line)
+}
+
+/// Line of the innermost of `keys`, found one after the other in JSON `text`
+fn line_of(text: &str, keys: &[String]) -> Option<usize> {
+    let mut found = None;
+    let mut from = 0;
+    for key in keys {
+        let quoted = serde_json::to_string(key).ok()?;
+        let at = text[from..].match_indices(&quoted)
+            .map(|(i, _)| from + i + quoted.len())
+            .find(|&end| text[end..].trim_start().starts_with(':'));
+        match at {
+            Some(end) => {
+                found = Some(end);
+                from = end;
+            }
+            None => break,
+        }
+    }
+    found.map(|end| text[..end].lines().count())
+}
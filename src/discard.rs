@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Deleting files, through the trash
+//!
+//! Files Panoptes gets rid of, such as one occupying the path an undo puts a
+//! file back at, go to the platform's trash (the freedesktop.org trash on
+//! Linux, the Finder's on macOS, the Recycle Bin on Windows) unless deleted
+//! with `--permanent`. Either way the deletion is recorded in history, as a
+//! `trash` or `delete` entry; those are listed but not undone, trashed files
+//! being restored from the trash.
+
+use std::path::Path;
+
+use crate::analyzers::calculate_file_hash;
+use crate::history::{History, HistoryAction, HistoryEntry};
+use crate::{PanoptesError, Result};
+
+/// How files are deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Deletion {
+    /// Moved to the trash
+    #[default]
+    Trash,
+    /// Gone for good
+    Permanent,
+}
+
+impl Deletion {
+    /// `Permanent` when `permanent`, as given by a `--permanent` flag
+    pub fn from_flag(permanent: bool) -> Self {
+        if permanent { Self::Permanent } else { Self::Trash }
+    }
+}
+
+/// Deletes files as configured, recording each deletion in history
+#[derive(Clone)]
+pub struct Discard {
+    history: History,
+    deletion: Deletion,
+}
+
+impl Discard {
+    pub fn new(history: History, deletion: Deletion) -> Self {
+        Self { history, deletion }
+    }
+
+    /// Delete `path`, a file or directory; returns the history entry recording it
+    pub fn remove(&self, path: &Path) -> Result<HistoryEntry> {
+        self.remove_moved(path, path)
+    }
+
+    /// Delete `path`, where the file or directory at `original` was moved
+    /// aside to; its deletion is recorded as that of `original`
+    pub fn remove_moved(&self, path: &Path, original: &Path) -> Result<HistoryEntry> {
+        // Hashed first, for the record of what went
+        let file_hash = if path.is_file() { calculate_file_hash(path).unwrap_or_default() } else { String::new() };
+        let action = match self.deletion {
+            Deletion::Trash => {
+                trash::delete(path).map_err(|e| PanoptesError::FileSystem(std::io::Error::other(
+                    format!("Failed to move {} to the trash: {}", path.display(), e),
+                )))?;
+                HistoryAction::Trash
+            }
+            Deletion::Permanent if path.is_dir() => {
+                std::fs::remove_dir_all(path)?;
+                HistoryAction::Delete
+            }
+            Deletion::Permanent => {
+                std::fs::remove_file(path)?;
+                HistoryAction::Delete
+            }
+        };
+        self.history.record_removal(original, action, file_hash)
+    }
+}
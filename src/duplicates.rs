@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Copies of files already recorded
+//!
+//! With `rules.duplicate_detection`, a file whose contents hash the same as
+//! a recorded file still where it was recorded isn't analyzed again: it takes
+//! that file's name (the user's, if they corrected it), category and tags,
+//! and `rules.duplicates` says what else becomes of it:
+//!
+//! - `reuse`: nothing more; it's renamed as any file would be
+//! - `tag`: it's tagged `duplicate`
+//! - `move`: it's moved into `rules.duplicates_dir`
+//! - `hardlink`: it's replaced by a hard link to the earlier copy, which
+//!   frees its space, then renamed
+//!
+//! Its metadata says which file it copies, as `duplicate_of` and
+//! `duplicate_path`, and its analyzer is recorded as `duplicate`.
+
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::analyzers::{calculate_file_hash, AnalysisResult};
+use crate::config::DuplicatePolicy;
+use crate::db::{Database, FileRecord, ReviewStatus};
+use crate::{AppConfig, PanoptesError, Result};
+
+/// What to make of the file at `path` if it is a copy of a recorded file and
+/// `rules.duplicate_detection` is on
+pub fn check(db: &Database, path: &Path, config: &AppConfig) -> Result<Option<AnalysisResult>> {
+    if !config.rules.duplicate_detection {
+        return Ok(None);
+    }
+    let hash = calculate_file_hash(path)?;
+    let Some(original) = find(db, path, &hash)? else {
+        return Ok(None);
+    };
+    info!("{:?} is a copy of {}", path, original.new_path);
+    Ok(Some(reuse(db, &original, hash, config)))
+}
+
+/// The recorded file, elsewhere than `path`, that the file at `path`, whose
+/// contents hash to `hash`, is a copy of
+fn find(db: &Database, path: &Path, hash: &str) -> Result<Option<FileRecord>> {
+    let path = std::fs::canonicalize(path)?;
+    let records = db.find_files_by_hash(hash).unwrap_or_else(|e| {
+        warn!("Failed to look for copies of {:?}: {}", path, e);
+        Vec::new()
+    });
+    // A file moved since it was recorded is no copy of itself, and one on
+    // another machine no original here
+    Ok(records.into_iter().find(|record| {
+        !record.is_remote() && std::fs::canonicalize(&record.new_path).is_ok_and(|original| original != path)
+    }))
+}
+
+/// What analyzing a copy of `original` with `file_hash` would make of it
+fn reuse(db: &Database, original: &FileRecord, file_hash: String, config: &AppConfig) -> AnalysisResult {
+    let mut tags = db.get_file_tags(&original.id).unwrap_or_else(|e| {
+        warn!("Failed to read the tags of {}: {}", original.new_path, e);
+        Vec::new()
+    });
+    if config.rules.duplicates == DuplicatePolicy::Tag {
+        tags.push("duplicate".to_string());
+        tags.sort();
+        tags.dedup();
+    }
+    // A name the user chose or approved is as sure as names get
+    let settled = original.corrected_name.is_some() || original.status == Some(ReviewStatus::Approved);
+    let mut metadata = original.metadata.clone();
+    if let Some(map) = metadata.as_object_mut() {
+        map.insert("duplicate_of".to_string(), original.id.clone().into());
+        map.insert("duplicate_path".to_string(), original.new_path.clone().into());
+    }
+    AnalysisResult {
+        suggested_name: original.corrected_name.clone().unwrap_or_else(|| original.suggested_name.clone()),
+        confidence: if settled { 1.0 } else { original.confidence },
+        category: original.category.clone(),
+        tags,
+        file_hash,
+        metadata,
+        analyzer: Some("duplicate".to_string()),
+        model: None,
+    }
+}
+
+/// The earlier copy's path, if `result` is of a duplicate
+fn original_path(result: &AnalysisResult) -> Option<&str> {
+    result.metadata.get("duplicate_path").and_then(|p| p.as_str())
+}
+
+/// The directory a copy goes to when `rules.duplicates` is `move`
+pub fn destination(result: &AnalysisResult, config: &AppConfig) -> Option<PathBuf> {
+    if config.rules.duplicates != DuplicatePolicy::Move || original_path(result).is_none() {
+        return None;
+    }
+    config.rules.duplicates_dir.as_ref().map(PathBuf::from)
+}
+
+/// Replace the file at `path` with a hard link to the earlier copy, when
+/// `result` is of a copy and `rules.duplicates` is `hardlink`; logs failures,
+/// leaving the file as it was
+pub fn link_or_warn(path: &Path, result: &AnalysisResult, config: &AppConfig) {
+    if config.rules.duplicates != DuplicatePolicy::Hardlink {
+        return;
+    }
+    let Some(original) = original_path(result) else {
+        return;
+    };
+    match link(path, Path::new(original), &result.file_hash) {
+        Ok(()) => info!("Linked {:?} to its copy {}", path, original),
+        Err(e) => warn!("Failed to link {:?} to its copy {}: {}", path, original, e),
+    }
+}
+
+fn link(path: &Path, original: &Path, file_hash: &str) -> Result<()> {
+    // Its contents must still be the same, or they would be lost
+    if calculate_file_hash(original)? != file_hash {
+        return Err(PanoptesError::Analysis(format!("{} has changed", original.display())));
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let temporary = dir.join(format!(".panoptes-link-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::hard_link(original, &temporary)?;
+    // Renamed over the copy, so it is never missing
+    let renamed = std::fs::rename(&temporary, path);
+    // Left behind when the copy was a link to it already
+    if temporary.exists() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    Ok(renamed?)
+}
@@ -0,0 +1,568 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Panoptes as a library
+//!
+//! [`PanoptesEngine`] drives the pipeline the CLI does, for other Rust
+//! programs to embed: analyzing files ([`PanoptesEngine::analyze_path`]),
+//! renaming them, or queuing them for review, as the rules say
+//! ([`PanoptesEngine::process_and_rename`]), and following watched
+//! directories as a stream of [`EngineEvent`]s ([`PanoptesEngine::watch`]).
+//! It is set up with [`PanoptesEngine::builder`]:
+//!
+//! ```no_run
+//! # async fn run() -> panoptes::Result<()> {
+//! use panoptes::engine::PanoptesEngine;
+//!
+//! let engine = PanoptesEngine::builder()
+//!     .config_file("config.toml")?
+//!     .model("llava")
+//!     .dry_run(true)
+//!     .build()?;
+//! if let Some(processed) = engine.process_and_rename("scan.pdf").await? {
+//!     println!("{}", processed.result.suggested_name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The functions the engine is made of are the ones the CLI uses too, and
+//! public for programs wanting finer control, as over which configuration
+//! each file is processed with.
+
+use futures_util::stream::{Stream, StreamExt};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::analyzers::{calculate_file_hash, AnalysisResult, AnalyzerRegistry};
+use crate::db::{Database, ReviewStatus};
+use crate::duplicates;
+use crate::feedback;
+use crate::history::History;
+use crate::notifications::{self, NotificationEvent, Notifier};
+use crate::organizer;
+use crate::renamer::{is_quarantined, quarantine_file, rename_file, Disposition};
+use crate::rules;
+use crate::sidecar;
+use crate::skiplist;
+use crate::vault;
+use crate::views;
+use crate::watcher::{should_process, wait_for_stable, FileWatcher, WatchEvent};
+use crate::webhooks::{self, WebhookEvent, Webhooks};
+use crate::xattrs;
+use crate::{AppConfig, PanoptesError, Result};
+
+/// How long a new file may take to be written before it is analyzed anyway
+const SETTLE: Duration = Duration::from_secs(10);
+
+/// What became of a processed file
+#[derive(Debug, Clone, Serialize)]
+pub struct Processed {
+    /// Where it was analyzed
+    pub path: PathBuf,
+    pub result: AnalysisResult,
+    /// Its record, if it was recorded
+    pub file_id: Option<String>,
+    /// What was to be done with it; only planned in a dry run
+    pub disposition: Disposition,
+    /// The rule that decided its disposition, if one did
+    pub decided_by: Option<String>,
+    /// Where it went, if it was renamed, moved or quarantined
+    pub new_path: Option<PathBuf>,
+}
+
+/// What happened to a file turning up in a watched directory
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EngineEvent {
+    /// It was processed
+    Processed(Box<Processed>),
+    /// It was left alone: Panoptes' own output, named already, or gone
+    Ignored { path: PathBuf },
+    /// Processing it failed
+    Failed { path: PathBuf, error: String },
+    /// It was renamed by hand, and the new name learnt from
+    Corrected { from: PathBuf, to: PathBuf },
+    /// The watcher failed
+    Error { message: String },
+}
+
+/// Settings of a [`PanoptesEngine`] to build
+#[derive(Default)]
+pub struct EngineBuilder {
+    config: Option<AppConfig>,
+    model: Option<String>,
+    prompt: Option<String>,
+    database: Option<Database>,
+    session_id: Option<String>,
+    destination: Option<PathBuf>,
+    organize_root: Option<PathBuf>,
+    dry_run: bool,
+}
+
+impl EngineBuilder {
+    /// Use `config` rather than the default configuration
+    pub fn config(mut self, config: AppConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Use the configuration in the file `path`, with its layers and includes
+    pub fn config_file(self, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(self.config(AppConfig::load(path.as_ref())?))
+    }
+
+    /// Have `model` do all the analysis, as `--model` does
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Ask every model `prompt`, as `--prompt` does
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Record analyses in `db` rather than the one `database.path` names
+    pub fn database(mut self, db: Database) -> Self {
+        self.database = Some(db);
+        self
+    }
+
+    /// Group the renames in the history under `session_id`, to be undone
+    /// together; a new one by default
+    pub fn session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Move renamed files into `dir`, as a watch directory's `destination` does
+    pub fn destination(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.destination = Some(dir.into());
+        self
+    }
+
+    /// Move renamed files into their category's directory under `root`
+    pub fn organize(mut self, root: impl Into<PathBuf>) -> Self {
+        self.organize_root = Some(root.into());
+        self
+    }
+
+    /// Only say what would be done with files
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// The engine, once its configuration is found valid
+    pub fn build(self) -> Result<PanoptesEngine> {
+        let config = self.config.unwrap_or_default().with_overrides(self.model.as_deref(), self.prompt.as_deref());
+        config.validate()?;
+        let db = match self.database {
+            Some(db) => db,
+            None => Database::open(&config.database.path)?,
+        };
+        let history = open_history(&db)?;
+        let registry = AnalyzerRegistry::new(&config);
+        registry.load_corrections(&db);
+        info!("Loaded {} analyzers: {:?}", registry.len(), registry.analyzer_names());
+        Ok(PanoptesEngine {
+            webhooks: Webhooks::new(db.clone()),
+            notifier: Notifier::new(),
+            config: Arc::new(config),
+            registry: Arc::new(registry),
+            db,
+            history,
+            session_id: self.session_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            destination: self.destination,
+            organize_root: self.organize_root,
+            dry_run: self.dry_run,
+        })
+    }
+}
+
+/// The analysis and renaming pipeline, for embedding Panoptes
+pub struct PanoptesEngine {
+    config: Arc<AppConfig>,
+    registry: Arc<AnalyzerRegistry>,
+    db: Database,
+    history: History,
+    webhooks: Webhooks,
+    notifier: Notifier,
+    session_id: String,
+    destination: Option<PathBuf>,
+    organize_root: Option<PathBuf>,
+    dry_run: bool,
+}
+
+impl PanoptesEngine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// The session the engine's renames are recorded under
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// What the analyzer for `path` makes of it, without recording or renaming it
+    pub async fn analyze_path(&self, path: impl AsRef<Path>) -> Result<AnalysisResult> {
+        let path = path.as_ref();
+        let analyzer = self.registry.find_analyzer(path)
+            .ok_or_else(|| PanoptesError::UnsupportedFileType(path.display().to_string()))?;
+        analyzer.analyze(path, &self.config).await
+    }
+
+    /// Analyze `path`, record it, and rename it, queue it for review or leave
+    /// it as the rules say. None when it was left alone unanalyzed: no
+    /// analyzer takes it, or someone reviewed it already.
+    pub async fn process_and_rename(&self, path: impl AsRef<Path>) -> Result<Option<Processed>> {
+        process_file(
+            path.as_ref().to_path_buf(),
+            &self.config,
+            &self.registry,
+            &self.db,
+            &self.history,
+            &self.webhooks,
+            &self.notifier,
+            &self.session_id,
+            self.dry_run,
+            self.destination.as_deref(),
+            self.organize_root.as_deref(),
+        ).await
+    }
+
+    /// Process the files `events` say turned up, as watch mode does, one at
+    /// a time; files Panoptes put there, or named already, are left alone
+    pub fn watch<'a>(&'a self, events: impl Stream<Item = WatchEvent> + 'a) -> impl Stream<Item = EngineEvent> + 'a {
+        events.filter_map(move |event| self.on_event(event))
+    }
+
+    /// [`watch`](Self::watch) the directories `dirs` (and whether
+    /// recursively), creating any missing
+    pub fn watch_dirs<'a>(&'a self, dirs: &[(PathBuf, bool)]) -> Result<impl Stream<Item = EngineEvent> + 'a> {
+        let mut watcher = FileWatcher::new()?;
+        for (dir, recursive) in dirs {
+            watcher.watch(dir, *recursive)?;
+        }
+        Ok(self.watch(watcher.into_stream()))
+    }
+
+    /// Wait for the webhooks and notifications still being sent
+    pub async fn flush(&self) {
+        self.webhooks.flush().await;
+        self.notifier.flush().await;
+    }
+
+    async fn on_event(&self, event: WatchEvent) -> Option<EngineEvent> {
+        match event {
+            WatchEvent::FileCreated(path) => {
+                let config = &self.config;
+                let ours = vault::contains(&path, config) || views::contains(&path, config)
+                    || is_quarantined(&path, config) || skiplist::is_recent(&path);
+                let named = config.rules.skip_conformant && skiplist::is_conformant(&path, config);
+                if !should_process(&path) || ours || named || !wait_for_stable(&path, SETTLE).await {
+                    return Some(EngineEvent::Ignored { path });
+                }
+                Some(match self.process_and_rename(&path).await {
+                    Ok(Some(processed)) => EngineEvent::Processed(Box::new(processed)),
+                    Ok(None) => EngineEvent::Ignored { path },
+                    Err(e) => {
+                        if !self.dry_run {
+                            self.webhooks.emit(&config.webhooks, WebhookEvent::Error, webhooks::failed(&path, &e.to_string()));
+                        }
+                        EngineEvent::Failed { path, error: e.to_string() }
+                    }
+                })
+            }
+            // Renamed by Panoptes
+            WatchEvent::FileRenamed { to, .. } if skiplist::is_recent(&to) => None,
+            WatchEvent::FileRenamed { from, to } => match self.db.find_file_by_path(&from) {
+                Ok(Some(file)) => match feedback::renamed(&self.db, &file, &to) {
+                    Ok(true) => Some(EngineEvent::Corrected { from, to }),
+                    Ok(false) => None,
+                    Err(e) => Some(EngineEvent::Error { message: format!("Failed to follow rename of {}: {}", from.display(), e) }),
+                },
+                Ok(None) => None,
+                Err(e) => Some(EngineEvent::Error { message: format!("Failed to look up {}: {}", from.display(), e) }),
+            },
+            WatchEvent::Error(message) => Some(EngineEvent::Error { message }),
+            _ => None,
+        }
+    }
+}
+
+/// Process a single file. With an `organize_root`, renamed files go to their
+/// category's directory there instead of `destination`. Returns what became
+/// of it, unless it was left alone; failures are left to the caller to report.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_file(
+    path: PathBuf,
+    config: &AppConfig,
+    registry: &AnalyzerRegistry,
+    db: &Database,
+    history: &History,
+    webhooks: &Webhooks,
+    notifier: &Notifier,
+    session_id: &str,
+    dry_run: bool,
+    destination: Option<&Path>,
+    organize_root: Option<&Path>,
+) -> Result<Option<Processed>> {
+    // A file someone reviewed (e.g. one leaving quarantine) keeps the name they settled on
+    let absolute = if path.is_relative() { std::env::current_dir()?.join(&path) } else { path.clone() };
+    let absolute: PathBuf = absolute.components().collect();
+    if let Ok(Some(record)) = db.find_file_by_path(&absolute) {
+        let reviewed = matches!(record.status, Some(ReviewStatus::Approved | ReviewStatus::Rejected));
+        if reviewed && calculate_file_hash(&path).is_ok_and(|hash| hash == record.file_hash) {
+            debug!("{:?} was reviewed; leaving it alone", path);
+            return Ok(None);
+        }
+    }
+
+    info!("Analyzing: {:?}", path);
+
+    // Find appropriate analyzer
+    let analyzer = match registry.find_analyzer(&path) {
+        Some(a) => a,
+        None => {
+            debug!("No analyzer for: {:?}", path);
+            return Ok(None);
+        }
+    };
+
+    info!("Using analyzer: {}", analyzer.name());
+
+    // Copies of recorded files are named as those were, without analysis
+    let mut result = match duplicates::check(db, &path, config)? {
+        Some(copy) => copy,
+        None => analyzer.analyze(&path, config).instrument(info_span!("analyze", analyzer = %analyzer.name())).await?,
+    };
+
+    info!("Suggestion: {} (confidence: {:.0}%)", result.suggested_name, result.confidence * 100.0);
+
+    if let Some(ref cat) = result.category {
+        info!("Category: {}", cat);
+    }
+    if !result.tags.is_empty() {
+        info!("Tags: {:?}", result.tags);
+    }
+
+    // Routing rules go before organizing, which goes before the watch
+    // destination; `rules.actions` can override any of them
+    let plan = rules::plan(&path, &mut result, config, |result| {
+        duplicates::destination(result, config)
+            .or_else(|| organizer::route(result.category.as_deref(), &result.tags, &config.rules.destinations))
+            .or_else(|| organize_root.and_then(|root| organizer::category_dir(root, result.category.as_deref(), &config.organize)))
+            .or_else(|| destination.map(Path::to_path_buf))
+    });
+
+    // Store in database
+    let file_id = info_span!("record").in_scope(|| {
+        let file_id = record_analysis(db, &path, &result);
+        if !dry_run {
+            duplicates::link_or_warn(&path, &result, config);
+            sidecar::write_or_warn(&path, &result, file_id.as_deref(), config);
+            if let Some(id) = &file_id {
+                xattrs::tag_or_warn(db, id, config);
+            }
+        }
+        file_id
+    });
+
+    // Rename file
+    let destination = plan.destination.as_deref();
+    let rename_span = info_span!("rename", disposition = ?plan.disposition);
+    let entered = rename_span.enter();
+    let mut now_at = path.clone();
+    match plan.disposition {
+        Disposition::Apply if dry_run => {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            match destination {
+                _ if vault::root(config).is_some() => info!("DRY RUN: Would move {:?} into the vault", path),
+                Some(dir) => info!("DRY RUN: Would move {:?} to {:?} as {}.{}", path, dir, result.suggested_name, ext),
+                None => info!("DRY RUN: Would rename {:?} to {}.{}", path, result.suggested_name, ext),
+            }
+        }
+        Disposition::Apply => {
+            let new_path = rename_file(&path, destination, &result, config, history, Some(session_id), file_id.as_deref())?;
+            webhooks.emit(&config.webhooks, WebhookEvent::Renamed,
+                webhooks::renamed(file_id.as_deref(), &path, &new_path, &result));
+            notifier.send(&config.notifications, NotificationEvent::Renamed, notifications::renamed(&path, &new_path));
+            now_at = new_path;
+        }
+        Disposition::Review => {
+            match plan.decided_by {
+                Some(ref rule) => info!("Queued for review by rule {}", rule),
+                None => info!("Confidence {:.0}% below auto-rename threshold, queued for review", result.confidence * 100.0),
+            }
+            if !dry_run {
+                if let Some(moved) = send_to_review(&path, &result, file_id.as_deref(), config, db, history, webhooks, Some(session_id)) {
+                    now_at = moved;
+                }
+                notifier.send(&config.notifications, NotificationEvent::Queued,
+                    notifications::queued(&path, &result.suggested_name, result.confidence));
+                check_quarantine_limit(config, db, notifier);
+            }
+        }
+        Disposition::Skip => match plan.decided_by {
+            Some(ref rule) => info!("Skipped by rule {}", rule),
+            None => {
+                info!("Confidence too low ({:.0}%), skipping rename", result.confidence * 100.0);
+                if !dry_run {
+                    webhooks.emit(&config.webhooks, WebhookEvent::LowConfidence,
+                        webhooks::low_confidence(file_id.as_deref(), &path, &result, false));
+                }
+            }
+        },
+    }
+    drop(entered);
+    drop(rename_span);
+
+    if dry_run {
+        for command in &plan.commands {
+            info!("DRY RUN: Would run {:?} for rule {}", command.command, command.rule);
+        }
+    } else {
+        follow_up(&plan, &path, &now_at, &result, file_id.as_deref(), config, webhooks, notifier)
+            .instrument(info_span!("follow_up"))
+            .await;
+    }
+
+    Ok(Some(Processed {
+        new_path: (now_at != path).then_some(now_at),
+        path,
+        result,
+        file_id,
+        disposition: plan.disposition,
+        decided_by: plan.decided_by,
+    }))
+}
+
+/// Notify when the review queue has just grown past `notifications.quarantine_limit`
+pub fn check_quarantine_limit(config: &AppConfig, db: &Database, notifier: &Notifier) {
+    let Some(limit) = config.notifications.quarantine_limit else {
+        return;
+    };
+    match db.count_files_by_status(ReviewStatus::Pending) {
+        Ok(pending) if pending == limit + 1 => {
+            let review_url = config.web.enabled.then(|| format!("{}review", config.web.url()));
+            notifier.send(&config.notifications, NotificationEvent::QuarantineFull,
+                notifications::quarantine_full(pending, review_url));
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to count the review queue: {}", e),
+    }
+}
+
+/// Send the notifications and run the commands of the rules that matched a
+/// file analyzed at `original` and now at `path`
+#[allow(clippy::too_many_arguments)]
+pub async fn follow_up(
+    plan: &rules::Plan,
+    original: &Path,
+    path: &Path,
+    result: &AnalysisResult,
+    file_id: Option<&str>,
+    config: &AppConfig,
+    webhooks: &Webhooks,
+    notifier: &Notifier,
+) {
+    for notice in &plan.notices {
+        webhooks.emit(&config.webhooks, WebhookEvent::Rule,
+            webhooks::rule(file_id, &notice.rule, notice.message.as_deref(), path, result));
+        notifier.send(&config.notifications, NotificationEvent::Rule,
+            notifications::rule(&notice.rule, notice.message.as_deref(), path));
+    }
+    for command in &plan.commands {
+        rules::run(command, path, original, result).await;
+    }
+}
+
+/// Store an analysis result in the database, returning the new record ID
+pub fn record_analysis(db: &Database, path: &Path, result: &AnalysisResult) -> Option<String> {
+    match db.record_analysis(path, result) {
+        Ok(file_id) => Some(file_id),
+        Err(e) => {
+            warn!("Failed to store in database: {}", e);
+            None
+        }
+    }
+}
+
+/// Mark a recorded file as awaiting approval in the review queue
+fn queue_for_review(db: &Database, file_id: Option<&str>) {
+    if let Some(id) = file_id {
+        if let Err(e) = db.set_review_status(id, ReviewStatus::Pending) {
+            warn!("Failed to queue for review: {}", e);
+        }
+    }
+}
+
+/// Queue a file for review, quarantining it when `review.quarantine_dir` is
+/// set, and send the low-confidence webhook and any queue reminder. Returns
+/// where the file went if it was moved.
+#[allow(clippy::too_many_arguments)]
+pub fn send_to_review(
+    path: &Path,
+    result: &AnalysisResult,
+    file_id: Option<&str>,
+    config: &AppConfig,
+    db: &Database,
+    history: &History,
+    webhooks: &Webhooks,
+    session_id: Option<&str>,
+) -> Option<PathBuf> {
+    queue_for_review(db, file_id);
+    let moved = match quarantine_file(path, &result.file_hash, config, history, session_id, file_id) {
+        Ok(moved) => moved,
+        Err(e) => {
+            warn!("Failed to quarantine {}: {}", path.display(), e);
+            None
+        }
+    };
+    webhooks.emit(&config.webhooks, WebhookEvent::LowConfidence,
+        webhooks::low_confidence(file_id, path, result, true));
+
+    let every = config.review.remind_every as i64;
+    if every > 0 && file_id.is_some() {
+        match db.count_files_by_status(ReviewStatus::Pending) {
+            Ok(pending) if pending % every == 0 => {
+                let review_url = config.web.enabled.then(|| format!("{}review", config.web.url()));
+                webhooks.emit(&config.webhooks, WebhookEvent::ReviewQueue, webhooks::review_queue(pending, review_url));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to count the review queue: {}", e),
+        }
+    }
+    moved
+}
+
+/// Legacy JSONL history log, imported into the database on first use
+const LEGACY_HISTORY_FILE: &str = "panoptes_history.jsonl";
+
+/// Open the rename history, migrating a legacy JSONL log if one is present
+pub fn open_history(db: &Database) -> Result<History> {
+    let history = History::new(db.clone());
+    let imported = history.import_jsonl(Path::new(LEGACY_HISTORY_FILE))?;
+    if imported > 0 {
+        info!("Imported {} entries from legacy {}", imported, LEGACY_HISTORY_FILE);
+    }
+    Ok(history)
+}
@@ -8,6 +8,20 @@
 /// Result type alias for Panoptes operations
 pub type Result<T> = std::result::Result<T, PanoptesError>;
 
+/// Exit codes of the `panoptes` command, so scripts can tell outcomes apart
+pub mod exit_code {
+    /// Everything asked for was done
+    pub const SUCCESS: u8 = 0;
+    /// Nothing could be done: bad configuration, unreachable database, ...
+    pub const FATAL: u8 = 1;
+    /// Invalid arguments (as reported by the argument parser)
+    pub const USAGE: u8 = 2;
+    /// Some files or items failed; the rest were done
+    pub const PARTIAL: u8 = 3;
+    /// There was nothing to do, or the user declined
+    pub const NOTHING_TO_DO: u8 = 4;
+}
+
 /// Panoptes error types
 #[derive(Error, Debug)]
 pub enum PanoptesError {
@@ -26,6 +40,10 @@ pub enum PanoptesError {
     #[error("Ollama not available: {0}")]
     OllamaUnavailable(String),
 
+    /// The server an agent forwards its files to can't be reached
+    #[error("Server not available: {0}")]
+    ServerUnavailable(String),
+
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
 
@@ -49,4 +67,27 @@ pub enum PanoptesError {
 
     #[error("Audio error: {0}")]
     Audio(String),
+
+    /// Listing, fetching or renaming files of a remote source failed
+    #[error("Remote source error: {0}")]
+    Source(String),
+
+    /// Some items of a batch failed while the others were done
+    #[error("{0}")]
+    Partial(String),
+
+    /// Not a failure, but nothing was done
+    #[error("{0}")]
+    NothingToDo(String),
+}
+
+impl PanoptesError {
+    /// The process exit code for a command failing with this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Partial(_) => exit_code::PARTIAL,
+            Self::NothingToDo(_) => exit_code::NOTHING_TO_DO,
+            _ => exit_code::FATAL,
+        }
+    }
 }
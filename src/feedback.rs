@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Corrections of suggested names
+//!
+//! Whenever a user names a file differently from what Panoptes suggested
+//! (editing the suggestion in the review queue or at the `analyze --ask`
+//! prompt, or renaming a file Panoptes had named while `panoptes watch` is
+//! running), the suggestion and the user's name are kept in the `feedback`
+//! table, with the analyzer and model the suggestion came from.
+//!
+//! How often each analyzer and model gets corrected, shown by `panoptes stats`
+//! and `/api/feedback`, points at the prompts and thresholds worth tuning.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::db::{Database, FileRecord};
+use crate::Result;
+
+/// Where a correction was made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CorrectionSource {
+    /// Editing a suggestion in the web UI's review queue
+    Review,
+    /// Editing a suggestion at the `analyze --ask` prompt
+    Prompt,
+    /// Renaming a file Panoptes had named
+    Rename,
+}
+
+impl CorrectionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Review => "review",
+            Self::Prompt => "prompt",
+            Self::Rename => "rename",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Self {
+        match value {
+            "review" => Self::Review,
+            "prompt" => Self::Prompt,
+            _ => Self::Rename,
+        }
+    }
+}
+
+/// A suggested name and the one the user chose instead
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Correction {
+    pub id: i64,
+    pub file_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub source: CorrectionSource,
+    pub suggested_name: String,
+    pub corrected_name: String,
+    /// Analyzer and model the suggestion came from, if recorded
+    pub analyzer: Option<String>,
+    pub model: Option<String>,
+    pub category: Option<String>,
+    pub confidence: f64,
+}
+
+/// How often the suggestions of an analyzer and model were corrected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrectionRate {
+    /// None for files analyzed before analyzers were recorded
+    pub analyzer: Option<String>,
+    /// None when no model answered, and the analyzer's fallback named the file
+    pub model: Option<String>,
+    /// Files it suggested names for
+    pub files: i64,
+    /// Of those, files whose name was corrected
+    pub corrected: i64,
+}
+
+impl CorrectionRate {
+    /// Share of the files corrected, from 0 to 1
+    pub fn rate(&self) -> f64 {
+        if self.files == 0 { 0.0 } else { self.corrected as f64 / self.files as f64 }
+    }
+
+    /// "analyzer (model)", for display
+    pub fn label(&self) -> String {
+        let analyzer = self.analyzer.as_deref().unwrap_or("unknown");
+        match self.model {
+            Some(ref model) => format!("{} ({})", analyzer, model),
+            None => analyzer.to_string(),
+        }
+    }
+}
+
+/// The correction rates of `db`'s analyzers and models, most often corrected first
+pub fn rates(db: &Database) -> Result<Vec<CorrectionRate>> {
+    let mut rates = db.get_correction_rates()?;
+    rates.sort_by(|a, b| b.rate().total_cmp(&a.rate()).then_with(|| b.files.cmp(&a.files)));
+    Ok(rates)
+}
+
+/// Record a user naming the file of `file_id` `name`, unless that is what it
+/// was suggested or already corrected to; returns whether it was recorded
+pub fn record(db: &Database, file_id: &str, name: &str, source: CorrectionSource) -> Result<bool> {
+    let Some(file) = db.get_file(file_id)? else {
+        return Ok(false);
+    };
+    if name == file.suggested_name || file.corrected_name.as_deref() == Some(name) {
+        return Ok(false);
+    }
+    db.record_correction(file_id, name, source)
+}
+
+/// [`record`], logging failures instead of returning them
+pub fn record_or_warn(db: &Database, file_id: &str, name: &str, source: CorrectionSource) {
+    if let Err(e) = record(db, file_id, name, source) {
+        tracing::warn!("Failed to record corrected name: {}", e);
+    }
+}
+
+/// Follow the file of `file` being renamed to `to` outside Panoptes, and
+/// record a correction when it was a file Panoptes named and its name changed
+/// (not just its directory); returns whether a correction was recorded
+pub fn renamed(db: &Database, file: &FileRecord, to: &Path) -> Result<bool> {
+    db.relink_file(&file.id, to)?;
+    let named = file.rename_id.is_some() && !file.undone;
+    let before = Path::new(&file.new_path).file_stem();
+    let after = to.file_stem();
+    match after.and_then(|s| s.to_str()) {
+        Some(name) if named && before != after => record(db, &file.id, name, CorrectionSource::Rename),
+        _ => Ok(false),
+    }
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! C interface to the analysis engine
+//!
+//! The library is also built as a C dynamic library (`libpanoptes.so`,
+//! `panoptes.dll`, `libpanoptes.dylib`) exporting these functions, declared
+//! in `bindings/panoptes.h`, for file-manager plugins and scripts to analyze
+//! files without running `panoptes` once per file. `bindings/panoptes.py`
+//! wraps them for Python with `ctypes`.
+//!
+//! An engine is opened once with [`panoptes_engine_new`], which reads the
+//! configuration and opens the database, and asked for suggestions with
+//! [`panoptes_analyze`], which answers with the [`AnalysisResult`] as JSON.
+//! Strings the library returns are freed with [`panoptes_string_free`]; when a
+//! function fails it returns null and [`panoptes_last_error`] says why. A
+//! panic in an analyzer is such a failure rather than unwinding into the
+//! caller, which would abort it.
+//!
+//! [`AnalysisResult`]: crate::analyzers::AnalysisResult
+
+use std::cell::RefCell;
+use std::any::Any;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use crate::engine::PanoptesEngine;
+use crate::{PanoptesError, Result};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An engine and the runtime its analyses run on
+pub struct FfiEngine {
+    runtime: tokio::runtime::Runtime,
+    engine: PanoptesEngine,
+}
+
+/// Open an engine with the configuration file `config_path`, or the default
+/// configuration if it is null. Returns null on failure.
+///
+/// # Safety
+///
+/// `config_path` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn panoptes_engine_new(config_path: *const c_char) -> *mut FfiEngine {
+    catch(|| {
+        let config_path = match unsafe { optional_str(config_path) } {
+            Ok(path) => path,
+            Err(e) => return fail(e),
+        };
+        let opened = tokio::runtime::Runtime::new().map_err(PanoptesError::from).and_then(|runtime| {
+            let builder = PanoptesEngine::builder();
+            let builder = match config_path {
+                Some(path) => builder.config_file(path)?,
+                None => builder,
+            };
+            Ok(FfiEngine { runtime, engine: builder.build()? })
+        });
+        match opened {
+            Ok(engine) => Box::into_raw(Box::new(engine)),
+            Err(e) => fail(e),
+        }
+    })
+}
+
+/// Analyze the file at `path` without recording or renaming it, returning
+/// the analysis as JSON. Returns null on failure.
+///
+/// # Safety
+///
+/// `engine` must come from [`panoptes_engine_new`] and not have been freed,
+/// and `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn panoptes_analyze(engine: *const FfiEngine, path: *const c_char) -> *mut c_char {
+    catch(|| {
+        let Some(engine) = (unsafe { engine.as_ref() }) else {
+            return fail(PanoptesError::Config("No engine".to_string()));
+        };
+        let analyzed = unsafe { optional_str(path) }
+            .and_then(|path| path.ok_or_else(|| PanoptesError::Config("No path".to_string())))
+            .and_then(|path| engine.runtime.block_on(engine.engine.analyze_path(Path::new(path))))
+            .and_then(|result| Ok(serde_json::to_string(&result)?));
+        match analyzed {
+            Ok(json) => into_c_string(json),
+            Err(e) => fail(e),
+        }
+    })
+}
+
+/// Why the last call on this thread returned null, or null if none has
+/// failed; valid until the next call failing
+#[no_mangle]
+pub extern "C" fn panoptes_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |error| error.as_ptr()))
+}
+
+/// Free a string returned by [`panoptes_analyze`]
+///
+/// # Safety
+///
+/// `s` must be null or come from this library, and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn panoptes_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Close an engine, waiting for the webhooks and notifications it still sends
+///
+/// # Safety
+///
+/// `engine` must be null or come from [`panoptes_engine_new`], and not have
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn panoptes_engine_free(engine: *mut FfiEngine) {
+    if !engine.is_null() {
+        catch(|| {
+            let engine = unsafe { Box::from_raw(engine) };
+            engine.runtime.block_on(engine.engine.flush());
+            ptr::null_mut::<()>()
+        });
+    }
+}
+
+/// The string at `s`, unless it is null
+unsafe fn optional_str<'a>(s: *const c_char) -> Result<Option<&'a str>> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().map(Some)
+        .map_err(|_| PanoptesError::Config("String is not UTF-8".to_string()))
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // JSON escapes any NUL in a string
+    CString::new(s).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Run `f`, failing as it would on an error if it panics
+fn catch<T>(f: impl FnOnce() -> *mut T) -> *mut T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        fail(PanoptesError::Analysis(format!("Panicked: {}", panic_message(panic.as_ref()))))
+    })
+}
+
+/// What a panic was raised with, when it was a message
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic.downcast_ref::<&str>().copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Remember `error` for [`panoptes_last_error`] and return null
+fn fail<T>(error: PanoptesError) -> *mut T {
+    let message = CString::new(error.to_string().replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    ptr::null_mut()
+}
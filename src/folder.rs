@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Naming whole directories
+//!
+//! `panoptes analyze --as-dir <folder>` takes a directory, such as a photo
+//! shoot or an extracted project, as one unit: up to [`SAMPLE`] of the files
+//! in it, spread over the whole of it, are analyzed, and the text model names
+//! the directory from their suggestions (`prompts.folder`). It takes the
+//! category most of them share, and the tags they have in common.
+//!
+//! The directory is renamed in place as a file would be, under the same
+//! naming rules, and the rename recorded in history to be undone. Its
+//! "hash" is a fingerprint of the files sampled.
+
+use chrono::{DateTime, Local};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::analyzers::{clean_filename, AnalysisResult, AnalyzerRegistry};
+use crate::history::{create_entry, History};
+use crate::ollama::OllamaClient;
+use crate::renamer::{move_recorded, suggested_path};
+use crate::watcher::should_process;
+use crate::{AppConfig, PanoptesError, Result};
+
+/// Most files of a directory analyzed to name it
+pub const SAMPLE: usize = 12;
+
+/// Most tags a directory gets from its files
+const MAX_TAGS: usize = 8;
+
+/// What analyzing the files of `dir` makes of the directory as a whole
+pub async fn analyze(dir: &Path, config: &AppConfig, registry: &AnalyzerRegistry) -> Result<AnalysisResult> {
+    info!("Analyzing directory: {:?}", dir);
+    let files = files(dir, registry);
+    let sampled = sample(&files, SAMPLE);
+    debug!("Sampling {} of {} files", sampled.len(), files.len());
+
+    let mut children = Vec::new();
+    for file in &sampled {
+        let Some(analyzer) = registry.find_analyzer(file) else {
+            continue;
+        };
+        match analyzer.analyze(file, config).await {
+            Ok(result) => children.push(result),
+            Err(e) => warn!("Failed to analyze {:?}: {}", file, e),
+        }
+    }
+    if children.is_empty() {
+        return Err(PanoptesError::Analysis(format!("No file in {} could be analyzed", dir.display())));
+    }
+
+    let mut categories: BTreeMap<String, usize> = BTreeMap::new();
+    let mut tags: BTreeMap<String, usize> = BTreeMap::new();
+    for child in &children {
+        *categories.entry(child.category.clone().unwrap_or_else(|| "Uncategorized".to_string())).or_default() += 1;
+        for tag in &child.tags {
+            *tags.entry(tag.clone()).or_default() += 1;
+        }
+    }
+    // The most common, the first by name of those as common
+    let (category, shared) = categories.iter()
+        .fold(("Uncategorized", 0), |best, (name, &n)| if n > best.1 { (name.as_str(), n) } else { best });
+    let agreement = shared as f64 / children.len() as f64;
+    let mean_confidence = children.iter().map(|c| c.confidence).sum::<f64>() / children.len() as f64;
+
+    // Tags of more than one file, unless there is only one
+    let mut common: Vec<(&String, &usize)> = tags.iter().filter(|(_, &n)| n > 1 || children.len() == 1).collect();
+    common.sort_by(|a, b| b.1.cmp(a.1));
+    let mut tags: Vec<String> = common.into_iter().take(MAX_TAGS).map(|(tag, _)| tag.clone()).collect();
+    tags.push("folder".to_string());
+    tags.sort();
+    tags.dedup();
+
+    let modified: Vec<DateTime<Local>> = files.iter()
+        .filter_map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .map(DateTime::<Local>::from)
+        .collect();
+    let earliest = modified.iter().min().map(|date| date.format("%Y-%m-%d").to_string());
+    let latest = modified.iter().max().map(|date| date.format("%Y-%m-%d").to_string());
+
+    let listing: Vec<String> = children.iter()
+        .map(|child| match &child.category {
+            Some(category) => format!("- {} ({})", child.suggested_name, category),
+            None => format!("- {}", child.suggested_name),
+        })
+        .collect();
+    let mut prompt = format!(
+        "{}\n\nFolder: {}\nFiles ({} of {}):\n{}",
+        config.prompts.folder,
+        dir.file_name().unwrap_or_default().to_string_lossy(),
+        children.len(),
+        files.len(),
+        listing.join("\n"),
+    );
+    if let (Some(earliest), Some(latest)) = (&earliest, &latest) {
+        prompt.push_str(&format!("\nDated {} to {}", earliest, latest));
+    }
+
+    let client = OllamaClient::new(&config.ai_engine.url);
+    let named = match client.generate(&config.ai_engine.models.text, &prompt).await {
+        Ok(answer) => Some(clean_filename(&answer, &config.rules.sanitizer)).filter(|name| !name.is_empty()),
+        Err(e) => {
+            warn!("Text model failed on {:?}: {}", dir, e);
+            None
+        }
+    };
+    let model = named.is_some().then(|| config.ai_engine.models.text.clone());
+    // Files agreeing on what they are make for a surer name
+    let confidence = mean_confidence * (0.5 + 0.5 * agreement);
+    let (suggested_name, confidence) = match named {
+        Some(name) => (name, confidence),
+        // Without the model: the category and what its files have in common
+        None => {
+            let words: Vec<&str> = std::iter::once(category)
+                .chain(tags.iter().map(String::as_str).filter(|tag| *tag != "folder").take(2))
+                .collect();
+            (clean_filename(&words.join(" "), &config.rules.sanitizer), confidence * 0.6)
+        }
+    };
+
+    let mut fingerprint = blake3::Hasher::new();
+    for child in &children {
+        fingerprint.update(child.file_hash.as_bytes());
+    }
+
+    Ok(AnalysisResult {
+        suggested_name,
+        confidence,
+        category: Some(category.to_string()),
+        tags,
+        file_hash: fingerprint.finalize().to_hex().to_string(),
+        metadata: serde_json::json!({
+            "file_count": files.len(),
+            "sampled": sampled.len(),
+            "analyzed": children.len(),
+            "categories": categories,
+            "earliest": earliest,
+            "latest": latest,
+            "files": children.iter().map(|c| &c.suggested_name).collect::<Vec<_>>(),
+        }),
+        analyzer: Some("folder".to_string()),
+        model,
+    })
+}
+
+/// Rename the directory `dir` for `result` where it is, recording the rename
+/// in history; returns where it went, `dir` itself when its name is taken
+/// and `rules.collision` is `skip`
+pub fn rename(dir: &Path, result: &AnalysisResult, config: &AppConfig, history: &History, session_id: Option<&str>) -> Result<PathBuf> {
+    let Some(new_path) = suggested_path(result, dir, dir, config)? else {
+        return Ok(dir.to_path_buf());
+    };
+    if new_path == dir {
+        return Ok(new_path);
+    }
+    let entry = create_entry(
+        uuid::Uuid::new_v4().to_string(),
+        dir.to_path_buf(),
+        new_path.clone(),
+        result.suggested_name.clone(),
+        result.category.clone(),
+        result.tags.clone(),
+        result.file_hash.clone(),
+        session_id.map(String::from),
+    );
+    move_recorded(dir, &new_path, || history.append(&entry))?;
+    info!("Renamed to: {:?}", new_path);
+    Ok(new_path)
+}
+
+/// The files under `dir` an analyzer takes, in order
+fn files(dir: &Path, registry: &AnalyzerRegistry) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if !should_process(&path) {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.is_file() && registry.find_analyzer(&path).is_some() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// At most `count` of `files`, spread evenly over them
+fn sample(files: &[PathBuf], count: usize) -> Vec<PathBuf> {
+    if files.len() <= count {
+        return files.to_vec();
+    }
+    (0..count).map(|i| files[i * files.len() / count].clone()).collect()
+}
@@ -2,16 +2,64 @@
 // SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
 
 //! History management for undo support
+//!
+//! Every rename or move performed by Panoptes is recorded in the database's
+//! `renames` table, linked to the file record it belongs to, so it can be
+//! reverted later, individually or as a whole session. Files Panoptes
+//! deletes are recorded too (see [`crate::discard`]). Older versions kept a
+//! JSONL log; `History::import_jsonl` migrates it.
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 
-use crate::Result;
+use crate::db::Database;
+use crate::discard::Discard;
+use crate::{PanoptesError, Result};
 
-/// A single rename operation in history
+/// Kind of filesystem operation recorded in history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryAction {
+    /// Renamed within the same directory
+    #[default]
+    Rename,
+    /// Moved to a different directory (possibly also renamed)
+    Move,
+    /// Moved to the platform's trash; `new_path` is empty
+    Trash,
+    /// Deleted for good; `new_path` is empty
+    Delete,
+}
+
+impl HistoryAction {
+    /// Classify an operation from its source and destination paths
+    pub fn between(from: &Path, to: &Path) -> Self {
+        if from.parent() == to.parent() {
+            Self::Rename
+        } else {
+            Self::Move
+        }
+    }
+
+    /// Whether the entry records a deletion, which undo leaves alone
+    pub fn is_removal(&self) -> bool {
+        matches!(self, Self::Trash | Self::Delete)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rename => "rename",
+            Self::Move => "move",
+            Self::Trash => "trash",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// A single rename or move operation in history
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HistoryEntry {
     pub id: String,
@@ -23,106 +71,350 @@ pub struct HistoryEntry {
     pub tags: Vec<String>,
     pub file_hash: String,
     pub undone: bool,
+    /// Batch/session this rename belongs to (one per `analyze` or `watch` run)
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Whether this was an in-place rename or a cross-directory move
+    #[serde(default)]
+    pub action: HistoryAction,
+    /// File record this event belongs to, when the file was analyzed
+    #[serde(default)]
+    pub file_id: Option<String>,
+    /// Confidence of the suggestion, from the file record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
 }
 
-/// History manager for tracking file renames
+/// History of renames and moves, backed by the `renames` table in the database
+#[derive(Clone)]
 pub struct History {
-    path: PathBuf,
+    db: Database,
 }
 
 impl History {
-    /// Create a new history manager
-    pub fn new(path: PathBuf) -> Self {
-        Self { path }
+    /// Create a history view over the database
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// The database the history is kept in
+    pub fn db(&self) -> &Database {
+        &self.db
     }
 
     /// Append an entry to the history
     pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.path)?;
+        self.db.insert_rename(entry)
+    }
 
-        let json = serde_json::to_string(entry)?;
-        writeln!(file, "{}", json)?;
+    /// Read all history entries (oldest first)
+    pub fn read_all(&self) -> Result<Vec<HistoryEntry>> {
+        self.db.get_renames()
+    }
 
-        Ok(())
+    /// Get the most recent N entries (newest first)
+    pub fn get_recent(&self, count: usize) -> Result<Vec<HistoryEntry>> {
+        let mut entries = self.read_all()?;
+        entries.reverse();
+        entries.truncate(count);
+        Ok(entries)
     }
 
-    /// Read all history entries
-    pub fn read_all(&self) -> Result<Vec<HistoryEntry>> {
-        if !self.path.exists() {
-            return Ok(Vec::new());
+    /// Get a page of entries (newest first) along with the total entry count
+    pub fn get_page(&self, limit: usize, offset: usize) -> Result<(Vec<HistoryEntry>, i64)> {
+        Ok((self.db.get_renames_page(limit, offset)?, self.db.get_rename_count()?))
+    }
+
+    /// Mark an entry as undone, its file having been put back at `restored`
+    pub fn mark_undone(&self, id: &str, restored: &Path) -> Result<()> {
+        self.mark_undone_many(&[(id, restored)])
+    }
+
+    /// Mark several entries as undone in a single transaction, each with
+    /// where its file was put back
+    pub fn mark_undone_many(&self, undone: &[(&str, &Path)]) -> Result<()> {
+        self.db.mark_renames_undone(undone)
+    }
+
+    /// Get renames and moves that haven't been undone
+    pub fn get_undoable(&self) -> Result<Vec<HistoryEntry>> {
+        let entries = self.read_all()?;
+        Ok(entries.into_iter().filter(|e| !e.undone && !e.action.is_removal()).collect())
+    }
+
+    /// Find an entry by its ID (a unique prefix of the ID is accepted)
+    pub fn find_by_id(&self, id: &str) -> Result<Option<HistoryEntry>> {
+        let matches: Vec<HistoryEntry> = self.read_all()?
+            .into_iter()
+            .filter(|e| e.id.starts_with(id))
+            .collect();
+
+        match matches.len() {
+            0 => Ok(None),
+            1 => Ok(matches.into_iter().next()),
+            n => {
+                if let Some(exact) = matches.iter().find(|e| e.id == id) {
+                    return Ok(Some(exact.clone()));
+                }
+                Err(PanoptesError::Config(format!(
+                    "History ID prefix '{}' is ambiguous ({} matches)", id, n
+                )))
+            }
         }
+    }
+
+    /// Get the undoable entries of a session, given by ID or unique prefix (newest first)
+    pub fn get_session(&self, session_id: &str) -> Result<Vec<HistoryEntry>> {
+        let mut sessions: Vec<String> = self.read_all()?
+            .into_iter()
+            .filter_map(|e| e.session_id.filter(|s| s.starts_with(session_id)))
+            .collect();
+        sessions.sort();
+        sessions.dedup();
+        let session = match sessions.len() {
+            0 => return Ok(Vec::new()),
+            1 => sessions.remove(0),
+            _ if sessions.iter().any(|s| s == session_id) => session_id.to_string(),
+            n => {
+                return Err(PanoptesError::Config(format!(
+                    "Session ID prefix '{}' is ambiguous ({} matches)", session_id, n
+                )));
+            }
+        };
+
+        let mut entries: Vec<HistoryEntry> = self.get_undoable()?
+            .into_iter()
+            .filter(|e| e.session_id.as_deref() == Some(session.as_str()))
+            .collect();
+        entries.reverse();
+        Ok(entries)
+    }
 
-        let file = File::open(&self.path)?;
-        let reader = BufReader::new(file);
+    /// Find the most recent undoable entry whose renamed (or original) path is `path`
+    pub fn find_by_path(&self, path: &Path) -> Result<Option<HistoryEntry>> {
+        let canonical = path.canonicalize().ok();
+        let matches_path = |p: &Path| {
+            p == path || canonical.as_deref().is_some_and(|c| p.canonicalize().ok().as_deref() == Some(c))
+        };
 
-        let mut entries = Vec::new();
+        let entries = self.get_undoable()?;
+        let found = entries.iter().rev()
+            .find(|e| matches_path(&e.new_path))
+            .or_else(|| entries.iter().rev().find(|e| e.original_path == path))
+            .cloned();
+
+        Ok(found)
+    }
+
+    /// Clear all history
+    pub fn clear(&self) -> Result<()> {
+        self.db.clear_renames()
+    }
+
+    /// Import entries from a legacy JSONL history log, skipping ones already present.
+    /// The log is renamed to `<name>.imported` afterwards so it is only read once.
+    pub fn import_jsonl(&self, path: &Path) -> Result<usize> {
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let reader = BufReader::new(File::open(path)?);
+        let mut imported = 0;
         for line in reader.lines() {
             let line = line?;
             if line.trim().is_empty() {
                 continue;
             }
-            match serde_json::from_str(&line) {
-                Ok(entry) => entries.push(entry),
+            match serde_json::from_str::<HistoryEntry>(&line) {
+                Ok(entry) => {
+                    if !self.db.rename_exists(&entry.id)? {
+                        self.db.insert_rename(&entry)?;
+                        imported += 1;
+                    }
+                }
                 Err(e) => {
                     tracing::warn!("Failed to parse history entry: {}", e);
                 }
             }
         }
 
-        Ok(entries)
+        let mut done = path.as_os_str().to_owned();
+        done.push(".imported");
+        fs::rename(path, PathBuf::from(done))?;
+
+        Ok(imported)
     }
 
-    /// Get the most recent N entries (newest first)
-    pub fn get_recent(&self, count: usize) -> Result<Vec<HistoryEntry>> {
-        let mut entries = self.read_all()?;
-        entries.reverse();
-        entries.truncate(count);
-        Ok(entries)
+    /// Record a move performed outside the analysis pipeline (e.g. organizing or a manual move)
+    pub fn record_move(
+        &self,
+        from: &Path,
+        to: &Path,
+        file_hash: String,
+        file_id: Option<String>,
+        session_id: Option<String>,
+    ) -> Result<HistoryEntry> {
+        let name = to.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut entry = create_entry(
+            uuid::Uuid::new_v4().to_string(),
+            from.to_path_buf(),
+            to.to_path_buf(),
+            name,
+            None,
+            Vec::new(),
+            file_hash,
+            session_id,
+        );
+        entry.file_id = file_id;
+        self.append(&entry)?;
+        Ok(entry)
     }
 
-    /// Mark an entry as undone
-    pub fn mark_undone(&self, id: &str) -> Result<()> {
-        let entries = self.read_all()?;
+    /// Record the deletion of `path`, done as `action`
+    pub fn record_removal(&self, path: &Path, action: HistoryAction, file_hash: String) -> Result<HistoryEntry> {
+        let file = self.db.find_file_by_path(path)?;
+        let name = path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut entry = create_entry(
+            uuid::Uuid::new_v4().to_string(),
+            path.to_path_buf(),
+            PathBuf::new(),
+            name,
+            file.as_ref().and_then(|f| f.category.clone()),
+            Vec::new(),
+            file_hash,
+            None,
+        );
+        entry.action = action;
+        entry.file_id = file.map(|f| f.id);
+        self.append(&entry)?;
+        Ok(entry)
+    }
+}
 
-        // Rewrite the entire file with the updated entry
-        let file = File::create(&self.path)?;
-        let mut writer = std::io::BufWriter::new(file);
+/// What to do when an undo finds its original path already occupied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UndoConflict {
+    /// Leave both files alone and report the conflict
+    #[default]
+    Skip,
+    /// Replace the file occupying the original path
+    Overwrite,
+    /// Restore next to the occupant as `<name>_restored[_N].<ext>`
+    Suffix,
+    /// Ask the user (interactive front-ends only)
+    Prompt,
+}
 
-        for mut entry in entries {
-            if entry.id == id {
-                entry.undone = true;
-            }
-            let json = serde_json::to_string(&entry)?;
-            writeln!(writer, "{}", json)?;
+impl std::str::FromStr for UndoConflict {
+    type Err = PanoptesError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "suffix" | "restore-with-suffix" => Ok(Self::Suffix),
+            "prompt" => Ok(Self::Prompt),
+            other => Err(PanoptesError::Config(format!("Unknown undo conflict strategy: {}", other))),
         }
+    }
+}
+
+/// Result of attempting to revert a history entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UndoOutcome {
+    /// File moved back; holds the path it was restored to
+    Reverted(PathBuf),
+    /// The renamed file no longer exists
+    Missing,
+    /// The original path is occupied and the strategy didn't resolve it
+    Conflict,
+}
 
-        Ok(())
+/// Move a file back to where an entry found it, recreating missing directories
+pub fn revert(entry: &HistoryEntry, discard: &Discard) -> Result<()> {
+    match revert_with(entry, UndoConflict::Skip, discard)? {
+        UndoOutcome::Reverted(_) => Ok(()),
+        UndoOutcome::Missing => Err(PanoptesError::FileSystem(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} no longer exists", entry.new_path.display()),
+        ))),
+        UndoOutcome::Conflict => Err(PanoptesError::FileSystem(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} is occupied", entry.original_path.display()),
+        ))),
     }
+}
 
-    /// Get entries that haven't been undone
-    pub fn get_undoable(&self) -> Result<Vec<HistoryEntry>> {
-        let entries = self.read_all()?;
-        Ok(entries.into_iter().filter(|e| !e.undone).collect())
+/// Revert an entry, resolving an occupied original path with `strategy`; an
+/// occupant overwritten goes by `discard`
+pub fn revert_with(entry: &HistoryEntry, strategy: UndoConflict, discard: &Discard) -> Result<UndoOutcome> {
+    if entry.action.is_removal() {
+        return Err(PanoptesError::Config(format!(
+            "{} was deleted, not renamed; trashed files are restored from the trash", entry.original_path.display()
+        )));
+    }
+    if !entry.new_path.exists() {
+        return Ok(UndoOutcome::Missing);
     }
 
-    /// Clear all history
-    pub fn clear(&self) -> Result<()> {
-        if self.path.exists() {
-            fs::remove_file(&self.path)?;
+    let target = if entry.original_path.exists() {
+        match strategy {
+            UndoConflict::Skip | UndoConflict::Prompt => return Ok(UndoOutcome::Conflict),
+            UndoConflict::Overwrite => {
+                discard.remove(&entry.original_path)?;
+                entry.original_path.clone()
+            }
+            UndoConflict::Suffix => restored_path(&entry.original_path),
         }
-        Ok(())
+    } else {
+        entry.original_path.clone()
+    };
+
+    if let Some(parent) = target.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    crate::renamer::move_path(&entry.new_path, &target)?;
+    // Files leaving the vault can be written again
+    if crate::vault::is_address(&entry.new_path, &entry.file_hash) {
+        crate::vault::unseal(&target);
     }
+    Ok(UndoOutcome::Reverted(target))
+}
+
+/// Whether the renamed file's content differs from what was recorded at rename
+/// time; never for directories, whose hash stands for a sample of their files
+pub fn changed_since_rename(entry: &HistoryEntry) -> Result<bool> {
+    if entry.file_hash.is_empty() || !entry.new_path.is_file() {
+        return Ok(false);
+    }
+    let current = crate::analyzers::calculate_file_hash(&entry.new_path)?;
+    Ok(current != entry.file_hash)
+}
+
+/// First free `<stem>_restored[_N].<ext>` next to `path`
+fn restored_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
 
-    /// Get history file path
-    pub fn path(&self) -> &Path {
-        &self.path
+    let mut candidate = parent.join(format!("{}_restored{}", stem, ext));
+    let mut n = 2;
+    while candidate.exists() {
+        candidate = parent.join(format!("{}_restored_{}{}", stem, n, ext));
+        n += 1;
     }
+    candidate
 }
 
 /// Create a new history entry
+#[allow(clippy::too_many_arguments)]
 pub fn create_entry(
     id: String,
     original_path: PathBuf,
@@ -131,7 +423,9 @@ pub fn create_entry(
     category: Option<String>,
     tags: Vec<String>,
     file_hash: String,
+    session_id: Option<String>,
 ) -> HistoryEntry {
+    let action = HistoryAction::between(&original_path, &new_path);
     HistoryEntry {
         id,
         timestamp: Utc::now(),
@@ -142,5 +436,9 @@ pub fn create_entry(
         tags,
         file_hash,
         undone: false,
+        session_id,
+        action,
+        file_id: None,
+        confidence: None,
     }
 }
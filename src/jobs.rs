@@ -0,0 +1,392 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Persistent job queue
+//!
+//! Work done in the background (analyzing a file that turned up in a watch
+//! directory, making a thumbnail, reprocessing a record, exporting the
+//! database) is recorded in the `jobs` table before a pool of
+//! `jobs.workers` workers in `panoptes watch` or `panoptes serve` picks it
+//! up, so none is lost when the process stops. Several processes can share
+//! the queue: each marks the jobs it takes as its own and keeps saying it is
+//! running them; jobs whose process has stopped saying so for
+//! [`STALE_AFTER`] are queued again.
+//!
+//! Higher priorities go first, then older jobs. A job that fails is tried
+//! again after `jobs.retry_delay_secs`, twice as long after each failure, up
+//! to `jobs.max_attempts` times in all; then it is dead, and stays so until
+//! retried with `panoptes jobs retry` or from the web UI. Jobs failing
+//! because an agent's server is down (see [`crate::agent`]) wait for it
+//! without using up their attempts.
+//!
+//! A process's workers can be paused (see [`crate::mqtt`]): they finish the
+//! jobs they are doing and take no others until resumed.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::config::JobConfig;
+use crate::db::Database;
+use crate::telemetry;
+use crate::{PanoptesError, Result};
+
+/// How often idle workers look for jobs queued by other processes
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Longest wait before a failed job is tried again
+const MAX_RETRY_DELAY_SECS: u64 = 60 * 60;
+
+/// How long jobs wait for an agent's server to be back
+const SERVER_RETRY_DELAY: Duration = Duration::seconds(60);
+
+/// How often a process says it is still running its jobs
+const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// How long after a running job's last heartbeat its process is taken to
+/// have stopped
+const STALE_AFTER: Duration = Duration::seconds(60);
+
+/// How often finished jobs past `jobs.keep_days` are removed
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    /// Waiting for a worker, or for its next attempt
+    Queued,
+    Running,
+    Done,
+    /// Failed on every attempt
+    Dead,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Dead => "dead",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::str::FromStr for JobState {
+    type Err = PanoptesError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "queued" => Ok(Self::Queued),
+            "running" => Ok(Self::Running),
+            "done" => Ok(Self::Done),
+            "dead" => Ok(Self::Dead),
+            "cancelled" => Ok(Self::Cancelled),
+            other => Err(PanoptesError::Config(format!("Unknown job state: {}", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Analyze,
+    Thumbnail,
+    Reprocess,
+    Export,
+}
+
+/// What a job does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Task {
+    /// Analyze a file and rename, move or queue it for review
+    Analyze {
+        path: PathBuf,
+        /// Config file of the profile watching it; the main one when unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        profile: Option<PathBuf>,
+        /// The watch directory's `destination`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PathBuf>,
+        /// Where renamed files are organized by category, if they are
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        organize_root: Option<PathBuf>,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Make a recorded file's thumbnail ahead of it being shown
+    Thumbnail { file_id: String },
+    /// Analyze a recorded file again, as `panoptes reprocess` does
+    Reprocess {
+        file_id: String,
+        /// Model to use for every analyzer instead of the configured ones
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model: Option<String>,
+        /// Replace the record even when the new analysis is no more confident
+        #[serde(default)]
+        force: bool,
+    },
+    /// Write every file record to a JSON file, as `panoptes db export` does
+    Export { output: PathBuf },
+}
+
+impl Task {
+    pub fn kind(&self) -> JobKind {
+        match self {
+            Self::Analyze { .. } => JobKind::Analyze,
+            Self::Thumbnail { .. } => JobKind::Thumbnail,
+            Self::Reprocess { .. } => JobKind::Reprocess,
+            Self::Export { .. } => JobKind::Export,
+        }
+    }
+
+    /// Priority of jobs not given one: new files before reprocessing, and
+    /// thumbnails, which are made on demand anyway, last
+    pub fn default_priority(&self) -> i32 {
+        match self {
+            Self::Analyze { .. } => 10,
+            Self::Export { .. } => 5,
+            Self::Reprocess { .. } => 0,
+            Self::Thumbnail { .. } => -10,
+        }
+    }
+
+    /// The file or record the job is about
+    pub fn target(&self) -> String {
+        match self {
+            Self::Analyze { path, .. } | Self::Export { output: path } => path.display().to_string(),
+            Self::Thumbnail { file_id } | Self::Reprocess { file_id, .. } => file_id.clone(),
+        }
+    }
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Analyze => "analyze",
+            Self::Thumbnail => "thumbnail",
+            Self::Reprocess => "reprocess",
+            Self::Export => "export",
+        }
+    }
+}
+
+impl std::str::FromStr for JobKind {
+    type Err = PanoptesError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "analyze" => Ok(Self::Analyze),
+            "thumbnail" => Ok(Self::Thumbnail),
+            "reprocess" => Ok(Self::Reprocess),
+            "export" => Ok(Self::Export),
+            other => Err(PanoptesError::Config(format!("Unknown job kind: {}", other))),
+        }
+    }
+}
+
+/// A job in the queue
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: i64,
+    #[serde(flatten)]
+    pub task: Task,
+    pub state: JobState,
+    pub priority: i32,
+    /// Attempts made so far, the running one included
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// Why the last attempt failed
+    pub last_error: Option<String>,
+    /// Not started before this
+    pub run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Carried by the job's spans, and by those of the watch event that
+    /// queued it (see [`crate::telemetry`])
+    pub correlation_id: String,
+}
+
+impl Job {
+    /// Whether a failure of the running attempt makes the job dead
+    pub fn is_last_attempt(&self) -> bool {
+        self.attempts >= self.max_attempts
+    }
+}
+
+/// When a job that failed on its `attempts`th attempt is tried again
+pub fn retry_at(config: &JobConfig, attempts: u32) -> DateTime<Utc> {
+    let delay = config.retry_delay_secs.saturating_mul(1 << attempts.saturating_sub(1).min(16)).min(MAX_RETRY_DELAY_SECS);
+    Utc::now() + Duration::seconds(delay as i64)
+}
+
+/// Adds jobs to the queue and wakes this process's workers for them
+#[derive(Clone)]
+pub struct Queue {
+    db: Database,
+    wake: Arc<Notify>,
+    paused: Arc<AtomicBool>,
+    /// Marks the jobs this process's workers take
+    owner: Arc<str>,
+}
+
+impl Queue {
+    pub fn new(db: Database) -> Self {
+        let owner = format!("{}-{}", std::process::id(), uuid::Uuid::new_v4().simple());
+        Self { db, wake: Arc::new(Notify::new()), paused: Arc::new(AtomicBool::new(false)), owner: owner.into() }
+    }
+
+    /// Stop this process's workers taking jobs
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.wake.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Queue `task` at its default priority; returns the job's ID, that of
+    /// the same task when it is already waiting
+    pub fn push(&self, task: &Task, config: &JobConfig) -> Result<i64> {
+        self.enqueue(task, task.default_priority(), &telemetry::correlation_id(), config)
+    }
+
+    pub fn push_with_priority(&self, task: &Task, priority: i32, config: &JobConfig) -> Result<i64> {
+        self.enqueue(task, priority, &telemetry::correlation_id(), config)
+    }
+
+    /// Queue `task` for a file whose spans so far carry `correlation_id`
+    pub fn push_correlated(&self, task: &Task, correlation_id: &str, config: &JobConfig) -> Result<i64> {
+        self.enqueue(task, task.default_priority(), correlation_id, config)
+    }
+
+    fn enqueue(&self, task: &Task, priority: i32, correlation_id: &str, config: &JobConfig) -> Result<i64> {
+        let id = self.db.enqueue_job(task, priority, config.max_attempts, correlation_id)?;
+        self.wake.notify_one();
+        Ok(id)
+    }
+}
+
+/// Run `config.workers` workers, each doing one job at a time with `run`,
+/// for as long as the process does. `run` is given the job as claimed, so
+/// with its attempt counted.
+pub fn start_workers<F, Fut>(queue: &Queue, config: &JobConfig, run: F)
+where
+    F: Fn(Job) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let run = Arc::new(run);
+    for _ in 0..config.workers {
+        tokio::spawn(work(queue.clone(), config.clone(), run.clone()));
+    }
+    tokio::spawn(heartbeat(queue.clone()));
+    tokio::spawn(prune(queue.db.clone(), config.keep_days));
+}
+
+/// Every [`HEARTBEAT_INTERVAL`], say this process is still running its jobs,
+/// and queue again those of processes that stopped
+async fn heartbeat(queue: Queue) {
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = queue.db.heartbeat_jobs(&queue.owner) {
+            warn!("Failed to record that jobs are still running: {}", e);
+        }
+        match queue.db.requeue_stale_jobs(Utc::now() - STALE_AFTER) {
+            Ok(0) => {}
+            Ok(n) => {
+                info!("Queued {} interrupted job(s) again", n);
+                queue.wake.notify_waiters();
+            }
+            Err(e) => warn!("Failed to queue interrupted jobs again: {}", e),
+        }
+    }
+}
+
+async fn work<F, Fut>(queue: Queue, config: JobConfig, run: Arc<F>)
+where
+    F: Fn(Job) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    loop {
+        if queue.is_paused() {
+            tokio::select! {
+                _ = queue.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+            continue;
+        }
+        let job = match queue.db.claim_job(&queue.owner) {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                tokio::select! {
+                    _ = queue.wake.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+                continue;
+            }
+            Err(e) => {
+                warn!("Failed to take a job from the queue: {}", e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        let (id, kind, target) = (job.id, job.task.kind(), job.task.target());
+        let last_attempt = job.is_last_attempt();
+        let attempts = job.attempts;
+        let span = info_span!("job", id, kind = %kind.as_str(), cid = %job.correlation_id, attempt = attempts);
+        let result = run(job).instrument(span.clone()).await;
+        span.in_scope(|| {
+            let outcome = match result {
+                Ok(()) => queue.db.finish_job(id, &queue.owner),
+                Err(e @ PanoptesError::ServerUnavailable(_)) => {
+                    let at = Utc::now() + SERVER_RETRY_DELAY;
+                    warn!("Job {} ({} {}) waits for the server until {}: {}", id, kind.as_str(), target, at.format("%H:%M:%S"), e);
+                    queue.db.defer_job(id, &queue.owner, &e.to_string(), at)
+                }
+                Err(e) if last_attempt => {
+                    warn!("Job {} ({} {}) failed for good after {} attempt(s): {}", id, kind.as_str(), target, attempts, e);
+                    queue.db.fail_job(id, &queue.owner, &e.to_string(), None)
+                }
+                Err(e) => {
+                    let at = retry_at(&config, attempts);
+                    warn!("Job {} ({} {}) failed, retrying at {}: {}", id, kind.as_str(), target, at.format("%H:%M:%S"), e);
+                    queue.db.fail_job(id, &queue.owner, &e.to_string(), Some(at))
+                }
+            };
+            match outcome {
+                Ok(true) => {}
+                Ok(false) => warn!("Job {} was requeued while it ran, so its outcome here is dropped", id),
+                Err(e) => warn!("Failed to record the outcome of job {}: {}", id, e),
+            }
+        });
+    }
+}
+
+/// Remove finished jobs older than `keep_days` every [`PRUNE_INTERVAL`]
+async fn prune(db: Database, keep_days: u32) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match db.prune_jobs(Utc::now() - Duration::days(keep_days as i64)) {
+            Ok(0) => {}
+            Ok(n) => info!("Removed {} finished job(s)", n),
+            Err(e) => warn!("Failed to remove finished jobs: {}", e),
+        }
+    }
+}
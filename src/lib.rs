@@ -6,14 +6,58 @@
 //! A comprehensive file analysis and organization system using local AI models.
 //! Version 3.0 - Full plugin architecture with web UI and database support.
 
+pub mod agent;
 pub mod analyzers;
+pub mod calibration;
 pub mod config;
+pub mod daemon;
+#[cfg(target_os = "linux")]
+pub mod dbus;
 pub mod db;
+pub mod diagnostics;
+pub mod engine;
+pub mod discard;
+pub mod duplicates;
 pub mod error;
+pub mod feedback;
+pub mod ffi;
+pub mod folder;
 pub mod history;
+pub mod jobs;
+pub mod live;
+pub mod mover;
+pub mod mqtt;
+pub mod naming;
+pub mod notifications;
+pub mod native_tags;
 pub mod ollama;
+pub mod organizer;
+pub mod paths;
+pub mod plugins;
+pub mod portable;
+pub mod prune;
+pub mod renamer;
+pub mod report;
+pub mod rules;
+pub mod schedule;
+pub mod selection;
+pub mod service;
+pub mod sidecar;
+pub mod similarity;
+pub mod skiplist;
+pub mod sources;
+pub mod stats;
+pub mod summary;
+pub mod telemetry;
+pub mod thumbnails;
+pub mod transcribe;
+pub mod vault;
+pub mod verify;
+pub mod views;
 pub mod watcher;
+pub mod webhooks;
 pub mod web;
+pub mod xattrs;
 
 pub use config::AppConfig;
 pub use error::{PanoptesError, Result};
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Live status of a watcher running in the same process as the web UI
+//!
+//! `panoptes serve` shares one [`LiveStatus`] between its watcher, its AI
+//! engine health monitor and the web UI, which shows it on the watch page and
+//! at `/api/watch/live`. A separate `panoptes watch` only reports what the
+//! database holds: the watch directories and when each last saw a file.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// The watcher's state at one moment
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveSnapshot {
+    pub started_at: DateTime<Utc>,
+    pub dry_run: bool,
+    /// Files being analyzed right now
+    pub in_progress: Vec<String>,
+    pub processed: u64,
+    pub failed: u64,
+    pub last_file: Option<String>,
+    pub last_processed_at: Option<DateTime<Utc>>,
+    /// Whether the AI engine answered its last health check; unknown before the first
+    pub engine_available: Option<bool>,
+    pub engine_error: Option<String>,
+    pub engine_checked_at: Option<DateTime<Utc>>,
+}
+
+/// Status the watcher and health monitor update and the web UI reads
+#[derive(Debug)]
+pub struct LiveStatus(Mutex<LiveSnapshot>);
+
+impl LiveStatus {
+    pub fn new(dry_run: bool) -> Self {
+        Self(Mutex::new(LiveSnapshot {
+            started_at: Utc::now(),
+            dry_run,
+            in_progress: Vec::new(),
+            processed: 0,
+            failed: 0,
+            last_file: None,
+            last_processed_at: None,
+            engine_available: None,
+            engine_error: None,
+            engine_checked_at: None,
+        }))
+    }
+
+    pub fn snapshot(&self) -> LiveSnapshot {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn update(&self, change: impl FnOnce(&mut LiveSnapshot)) {
+        change(&mut self.0.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    /// The watcher started analyzing `path`
+    pub fn started(&self, path: &Path) {
+        let path = path.to_string_lossy().into_owned();
+        self.update(|s| s.in_progress.push(path));
+    }
+
+    /// The watcher is done with `path`, which `succeeded` or not
+    pub fn finished(&self, path: &Path, succeeded: bool) {
+        let path = path.to_string_lossy().into_owned();
+        self.update(|s| {
+            if let Some(i) = s.in_progress.iter().position(|p| *p == path) {
+                s.in_progress.remove(i);
+            }
+            if succeeded {
+                s.processed += 1;
+            } else {
+                s.failed += 1;
+            }
+            s.last_file = Some(path);
+            s.last_processed_at = Some(Utc::now());
+        });
+    }
+
+    /// Record the outcome of an AI engine health check
+    pub fn engine_checked(&self, error: Option<String>) {
+        self.update(|s| {
+            s.engine_available = Some(error.is_none());
+            s.engine_error = error;
+            s.engine_checked_at = Some(Utc::now());
+        });
+    }
+}
@@ -6,21 +6,73 @@
 //! A comprehensive file analysis and organization system using local AI models.
 //! Version 3.0 - Full plugin architecture with web UI and database support.
 
-use chrono::Local;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use futures_util::stream::{self, StreamExt};
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
+use std::collections::HashSet;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::time::Duration;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use tokio::signal;
 use tokio::sync::watch;
-use tracing::{debug, error, info, warn};
-
-use panoptes::analyzers::{AnalyzerRegistry, AnalysisResult};
-use panoptes::config::AppConfig;
-use panoptes::db::Database;
-use panoptes::history::{History, create_entry};
-use panoptes::ollama::OllamaClient;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+use panoptes::agent;
+use panoptes::analyzers::{calculate_file_hash, clean_filename, AnalyzerRegistry, AnalysisResult};
+use panoptes::config::{layers, secrets, templates, AppConfig, JobConfig, SanitizerConfig, SourceConfig, WatchOptions};
+use panoptes::daemon::{self, PidFile};
+use panoptes::diagnostics::{self, Severity};
+use panoptes::discard::{Deletion, Discard};
+use panoptes::duplicates;
+use panoptes::engine::{follow_up, open_history, process_file, record_analysis, send_to_review};
+use panoptes::error::exit_code;
+use panoptes::feedback::{self, CorrectionSource};
+use panoptes::folder;
+use panoptes::db::{
+    Database, FileFilter, FileRecord, FileSort, ReviewStatus, Role, ScanOutcome, ScanResult, ScanRun, TagSource,
+};
+use panoptes::history::{
+    History, HistoryAction, HistoryEntry, UndoConflict, UndoOutcome,
+    changed_since_rename, revert_with,
+};
+use panoptes::jobs::{self, Job, JobKind, JobState, Queue, Task};
+use panoptes::live::LiveStatus;
+use panoptes::mqtt::{self, Mqtt};
+use panoptes::notifications::{self, NotificationConfig, NotificationEvent, Notifier};
+use panoptes::ollama::{self, OllamaClient};
+use panoptes::organizer::{self, Organizer, Placement};
+use panoptes::paths;
+use panoptes::plugins;
+use panoptes::prune::{self, PruneOptions};
+use panoptes::stats;
+use panoptes::summary;
+use panoptes::telemetry;
+use panoptes::renamer::{
+    auto_rename_threshold, disposition, final_name, move_path, is_quarantine_dir, is_quarantined, release_dir, rename_file, suggested_path,
+    Disposition,
+};
+use panoptes::report::{Report, ReportFormat};
+use panoptes::rules;
+use panoptes::schedule::{self, Cron, ScheduledTask};
+use panoptes::selection::{FileSelection, Selector};
+use panoptes::service;
+use panoptes::sidecar;
+use panoptes::sources::{self, RemoteState};
+use panoptes::similarity;
+use panoptes::skiplist;
+use panoptes::xattrs;
+use panoptes::thumbnails::ThumbnailCache;
+use panoptes::vault;
+use panoptes::verify::{self, Finding};
+use panoptes::views;
 use panoptes::watcher::{FileWatcher, WatchEvent, should_process, wait_for_stable};
+use panoptes::web::{self, auth};
+use panoptes::webhooks::{self, WebhookEvent, Webhooks};
 use panoptes::{PanoptesError, Result};
 
 /// Panoptes CLI - Local AI File Scanner & Renamer
@@ -30,9 +82,11 @@
 #[command(version = "3.0.0")]
 #[command(about = "Local AI-powered file scanner and renamer", long_about = None)]
 #[command(propagate_version = true)]
+#[command(after_help = "Exit codes: 0 done, 1 error, 2 invalid arguments, 3 some files failed, 4 nothing to do")]
 struct Cli {
-    /// Path to configuration file (JSON format)
-    #[arg(short, long, default_value = "config.json", global = true)]
+    /// Path to configuration file (JSON format); config.json in the current
+    /// directory if there is one, or else the user's
+    #[arg(short, long, default_value_os_t = paths::default_config(), global = true)]
     config: PathBuf,
 
     /// Enable verbose logging (debug level)
@@ -51,6 +105,25 @@ struct Cli {
     #[arg(short, long, global = true)]
     quiet: bool,
 
+    /// Answer yes to confirmations and never prompt; other prompts take
+    /// their default answer
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Write log output to this file instead of stdout; reopened on SIGHUP
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Log line format: text, or JSON with the spans of each line
+    #[arg(long, global = true, default_value = "text", value_parser = ["text", "json"])]
+    log_format: String,
+
+    /// Export tracing spans to this OpenTelemetry collector, given as its
+    /// OTLP/HTTP base URL (http://localhost:4318); OTEL_EXPORTER_OTLP_ENDPOINT
+    /// works too
+    #[arg(long, global = true, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -78,12 +151,81 @@ enum Commands {
         /// Enable recursive directory watching
         #[arg(short, long)]
         recursive: bool,
+
+        /// Sort renamed files into category directories
+        #[arg(long)]
+        organize: bool,
+
+        /// Detach and keep running in the background
+        #[arg(long)]
+        daemon: bool,
+
+        /// PID file, locked while watching so only one watcher runs per config
+        /// (default: in $XDG_RUNTIME_DIR, named after the config)
+        #[arg(long, value_name = "PATH")]
+        pid_file: Option<PathBuf>,
+
+        /// Also watch the directories of another config file, with its rules,
+        /// prompts and analyzers (repeatable)
+        #[arg(short = 'P', long, value_name = "CONFIG")]
+        profile: Vec<PathBuf>,
+    },
+
+    /// Run the watcher and the web UI in one process, sharing the database,
+    /// with the AI engine checked every minute and routine upkeep done hourly
+    Serve {
+        /// Directories to watch (overrides config)
+        #[arg(short, long)]
+        dir: Vec<PathBuf>,
+
+        /// Dry run mode (don't actually rename files)
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Skip Ollama health check on startup
+        #[arg(long)]
+        skip_health_check: bool,
+
+        /// Process existing files in directories on startup
+        #[arg(long)]
+        process_existing: bool,
+
+        /// Enable recursive directory watching
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Sort renamed files into category directories
+        #[arg(long)]
+        organize: bool,
+
+        /// Host for the web UI to bind to (overrides config)
+        #[arg(short = 'H', long)]
+        host: Option<String>,
+
+        /// Port for the web UI to listen on (overrides config)
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Detach and keep running in the background
+        #[arg(long)]
+        daemon: bool,
+
+        /// PID file, locked while watching so only one watcher runs per config
+        /// (default: in $XDG_RUNTIME_DIR, named after the config)
+        #[arg(long, value_name = "PATH")]
+        pid_file: Option<PathBuf>,
+
+        /// Also watch the directories of another config file, with its rules,
+        /// prompts and analyzers (repeatable)
+        #[arg(short = 'P', long, value_name = "CONFIG")]
+        profile: Vec<PathBuf>,
     },
 
     /// Analyze a single file or directory
     Analyze {
         /// File or directory to analyze
-        path: PathBuf,
+        #[arg(required_unless_present_any = ["resume", "files_from"])]
+        path: Option<PathBuf>,
 
         /// Dry run mode (show suggestions without renaming)
         #[arg(long)]
@@ -93,9 +235,229 @@ enum Commands {
         #[arg(short, long)]
         recursive: bool,
 
-        /// Minimum confidence threshold (0.0-1.0)
-        #[arg(long, default_value = "0.5")]
-        min_confidence: f64,
+        /// Analyze the files listed in this file, one per line (`-` reads standard input)
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["path", "recursive"])]
+        files_from: Option<PathBuf>,
+
+        /// File names in the list are separated by NUL characters (as from `find -print0`)
+        #[arg(short = '0', long = "null", conflicts_with_all = ["path", "resume"])]
+        null: bool,
+
+        /// Continue an interrupted run, skipping the files it already processed
+        #[arg(long, value_name = "RUN_ID", conflicts_with_all = ["path", "dry_run", "recursive", "files_from"])]
+        resume: Option<String>,
+
+        /// Leave out suggestions below this confidence (0.0-1.0) instead of
+        /// queuing or listing them [default: rules.suggest_threshold]
+        #[arg(long, value_parser = parse_confidence)]
+        min_confidence: Option<f64>,
+
+        /// Files analyzed in parallel; requests to the AI engine are still
+        /// limited by `ai_engine.max_concurrent`
+        #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=64))]
+        jobs: u16,
+
+        /// Write a report of the run to this file (Markdown for .md, HTML otherwise)
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+
+        /// Only files with these extensions, e.g. `jpg,png`
+        #[arg(long, value_name = "EXT", value_delimiter = ',', conflicts_with = "resume")]
+        ext: Vec<String>,
+
+        /// Leave out files and directories matching this glob, e.g. '*.iso' (repeatable)
+        #[arg(long, value_name = "GLOB", conflicts_with = "resume")]
+        exclude: Vec<String>,
+
+        /// Only files at least this big, e.g. 100K or 1.5MB
+        #[arg(long, value_name = "SIZE", conflicts_with = "resume")]
+        min_size: Option<String>,
+
+        /// Only files at most this big, e.g. 2GB
+        #[arg(long, value_name = "SIZE", conflicts_with = "resume")]
+        max_size: Option<String>,
+
+        /// Only files modified within this long, e.g. 7d or 12h
+        #[arg(long, value_name = "AGE", conflicts_with = "resume")]
+        newer_than: Option<String>,
+
+        /// Search at most this many directories deep (implies --recursive)
+        #[arg(long, value_name = "N", conflicts_with_all = ["resume", "files_from"])]
+        max_depth: Option<usize>,
+
+        /// Ask before each rename: [y]es, [n]o, [e]dit the name, [s]kip the
+        /// rest (the run can be resumed), or [a]ccept the rest
+        #[arg(short, long, conflicts_with = "dry_run")]
+        interactive: bool,
+
+        /// Name the directory itself, from a sample of the files in it,
+        /// rather than each file
+        #[arg(long, requires = "path", conflicts_with_all = ["resume", "files_from", "interactive", "report"])]
+        as_dir: bool,
+    },
+
+    /// Analyze recorded files again, e.g. low-confidence ones with a better model
+    #[command(group(clap::ArgGroup::new("selection").required(true).multiple(true)
+        .args(["max_confidence", "category", "tag", "failed"])))]
+    Reprocess {
+        /// Files whose confidence is at most this (0.0-1.0)
+        #[arg(long)]
+        max_confidence: Option<f64>,
+
+        /// Files in this category (`Uncategorized` for files without one)
+        #[arg(long)]
+        category: Option<String>,
+
+        /// Files with this tag
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Files whose batch analysis failed, instead of recorded files
+        #[arg(long, conflicts_with_all = ["max_confidence", "category", "tag"])]
+        failed: bool,
+
+        /// Model to use for every analyzer instead of the configured ones
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Replace records even when the new analysis is no more confident
+        #[arg(long)]
+        force: bool,
+
+        /// Show the new suggestions without updating records or renaming
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Maximum number of files, least confident first
+        #[arg(short = 'n', long, default_value = "100")]
+        limit: usize,
+
+        /// Leave the files to the watcher (`panoptes watch` or `serve`) as
+        /// background jobs
+        #[arg(long, conflicts_with_all = ["failed", "dry_run"])]
+        queue: bool,
+    },
+
+    /// Check the database against the files in the watch directories
+    Verify {
+        /// Re-link moved files and mark missing ones
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Remove data no longer needed; everything unless flags pick what
+    Prune {
+        /// Records of files deleted from disk
+        #[arg(long)]
+        records: bool,
+
+        /// History entries older than --older-than (they can no longer be undone)
+        #[arg(long)]
+        history: bool,
+
+        /// Tags no file has
+        #[arg(long)]
+        tags: bool,
+
+        /// Thumbnails of files no record has
+        #[arg(long)]
+        thumbnails: bool,
+
+        /// Cached thumbnails not used for --older-than
+        #[arg(long)]
+        cache: bool,
+
+        /// Age in days for history and cache entries
+        #[arg(long, value_name = "DAYS", default_value = "90")]
+        older_than: u32,
+
+        /// Show what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show categories, confidence, files processed per day and top tags as bar charts
+    Stats {
+        /// How many days of confidence and throughput to show, including today
+        #[arg(long, value_name = "N", default_value_t = 14, value_parser = clap::value_parser!(u32).range(1..=366))]
+        days: u32,
+
+        /// How many of the most used tags to show
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        top: usize,
+    },
+
+    /// List recorded files like a file: the same contents, similar-looking
+    /// images or documents with similar text
+    Similar {
+        /// File to find others like
+        file: PathBuf,
+
+        /// Most files to list (default: similarity.limit)
+        #[arg(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// How alike files must be, from 0 to 1 (default: similarity.min_score)
+        #[arg(long, value_name = "SCORE")]
+        min_score: Option<f64>,
+    },
+
+    /// Report on a batch analysis: renames, confidence, categories and failures
+    Report {
+        /// Run ID or unique prefix (default: the latest run)
+        run: Option<String>,
+
+        /// Write to this file (Markdown for .md, HTML otherwise) instead of
+        /// printing Markdown
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Overview of what a directory holds: counts by category, dates and
+    /// notable documents, described by the text model
+    Summarize {
+        /// Directory to summarize
+        dir: PathBuf,
+
+        /// Write the summary to this Markdown file instead of printing it
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Export the last summary stored for the directory rather than making a new one
+        #[arg(long)]
+        stored: bool,
+    },
+
+    /// Sort files into category directories
+    Organize {
+        /// Directory to organize
+        dir: PathBuf,
+
+        /// Show where files would go without moving them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Include files in subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+    },
+
+    /// The content-addressed vault (`vault.directory`)
+    Vault {
+        #[command(subcommand)]
+        action: VaultCommands,
+    },
+
+    /// Trees of symlinks to recorded files (`views`)
+    Views {
+        #[command(subcommand)]
+        action: ViewCommands,
+    },
+
+    /// S3 buckets, WebDAV shares and SFTP directories analyzed from afar (`sources`)
+    Sources {
+        #[command(subcommand)]
+        action: SourceCommands,
     },
 
     /// Database operations
@@ -104,18 +466,60 @@ enum Commands {
         action: DbCommands,
     },
 
+    /// Background jobs of the watcher: list, add, retry and cancel them
+    Jobs {
+        #[command(subcommand)]
+        action: JobCommands,
+    },
+
     /// History and undo operations
     History {
         #[command(subcommand)]
         action: HistoryCommands,
     },
 
+    /// Tag files by hand; re-analysis keeps these tags
+    Tag {
+        #[command(subcommand)]
+        action: TagCommands,
+    },
+
+    /// Manage analyzer plugins
+    Plugin {
+        #[command(subcommand)]
+        action: PluginCommands,
+    },
+
+    /// Forward files to a central server, or manage the agents doing so
+    Agent {
+        #[command(subcommand)]
+        action: AgentCommands,
+    },
+
+    /// Manage web API tokens
+    Token {
+        #[command(subcommand)]
+        action: TokenCommands,
+    },
+
+    /// Manage web users and their roles
+    User {
+        #[command(subcommand)]
+        action: UserCommands,
+    },
+
     /// Configuration management
     Config {
         #[command(subcommand)]
         action: ConfigCommands,
     },
 
+    /// Run the watcher as a systemd service
+    Service {
+        #[command(subcommand)]
+        action: ServiceCommands,
+    },
+
     /// Show AI engine status
     Status {
         /// Check specific model availability
@@ -132,6 +536,135 @@ enum Commands {
         /// Force overwrite existing configuration
         #[arg(long)]
         force: bool,
+
+        /// Start from a preset: watch directory, prompts, naming, destinations
+        /// and thresholds for photos, downloads, documents or code
+        #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(templates::TEMPLATES))]
+        template: Option<String>,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Write man pages for panoptes and each of its subcommands
+    Man {
+        /// Directory to write the pages to
+        #[arg(short, long, default_value = "man")]
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum JobCommands {
+    /// List jobs, newest first
+    List {
+        /// Only jobs in this state: queued, running, done, dead or cancelled
+        #[arg(long)]
+        state: Option<JobState>,
+
+        /// Maximum number of jobs
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+    },
+
+    /// Show a job, with why it last failed
+    Show {
+        id: i64,
+    },
+
+    /// Queue a job for the watcher
+    Add {
+        /// analyze, thumbnail, reprocess or export
+        kind: JobKind,
+
+        /// The file to analyze, the record ID to make a thumbnail of or
+        /// reprocess, or the file to export to
+        target: String,
+
+        /// Higher goes first; by default analyze 10, export 5, reprocess 0, thumbnail -10
+        #[arg(long, allow_hyphen_values = true)]
+        priority: Option<i32>,
+    },
+
+    /// Queue a dead or cancelled job again, its attempts starting over
+    #[command(group(clap::ArgGroup::new("which").required(true).args(["id", "dead"])))]
+    Retry {
+        id: Option<i64>,
+
+        /// Every dead job
+        #[arg(long)]
+        dead: bool,
+    },
+
+    /// Cancel a job that hasn't started
+    Cancel {
+        id: i64,
+    },
+
+    /// Remove done and cancelled jobs
+    Clear {
+        /// Dead jobs too
+        #[arg(long)]
+        dead: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum VaultCommands {
+    /// Lay out the vault as symlinks by category, named as files would be renamed
+    Export {
+        /// Directory for the symlinks, brought up to date if exported to before
+        dir: PathBuf,
+
+        /// Show what would change without touching the directory
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ViewCommands {
+    /// Bring the views in `views.directory` up to date with the database
+    Build {
+        /// Show what would change without touching the views
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SourceCommands {
+    /// List the sources and what became of their files
+    List,
+
+    /// Analyze the new files of the sources, renaming them there or planning to
+    Sync {
+        /// Only this source
+        name: Option<String>,
+
+        /// Show what would be analyzed without downloading anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show the renames planned on the sources
+    Plan {
+        /// Only this source
+        name: Option<String>,
+    },
+
+    /// Do the renames planned on the sources
+    Apply {
+        /// Only this source
+        name: Option<String>,
+
+        /// Show what would be renamed without renaming anything
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
@@ -142,8 +675,8 @@ enum DbCommands {
 
     /// List all tags
     Tags {
-        /// Filter by category
-        #[arg(short, long)]
+        /// Filter by category (no `-c`, which is the global --config)
+        #[arg(long)]
         category: Option<String>,
 
         /// Maximum number to show
@@ -183,16 +716,36 @@ enum HistoryCommands {
     /// List recent history entries
     List {
         /// Number of entries to show
-        #[arg(short, long, default_value = "10")]
+        #[arg(short = 'n', long, default_value = "10")]
         count: usize,
     },
 
     /// Undo recent renames
     Undo {
         /// Number of renames to undo
-        #[arg(short, long, default_value = "1")]
+        #[arg(short = 'n', long, default_value = "1")]
         count: usize,
 
+        /// Undo a specific entry by ID (or unique ID prefix)
+        #[arg(long, conflicts_with_all = ["count", "path"])]
+        id: Option<String>,
+
+        /// Undo the most recent rename of a specific file
+        #[arg(long, conflicts_with = "count")]
+        path: Option<PathBuf>,
+
+        /// Undo every rename of a session/batch, rolling back on the first conflict
+        #[arg(long, conflicts_with_all = ["count", "id", "path"])]
+        session: Option<String>,
+
+        /// How to handle an occupied original path (overrides history.undo_conflict)
+        #[arg(long, value_parser = ["skip", "overwrite", "suffix", "prompt"])]
+        on_conflict: Option<String>,
+
+        /// Delete files overwritten for good instead of moving them to the trash
+        #[arg(long)]
+        permanent: bool,
+
         /// Dry run (show what would be undone)
         #[arg(long)]
         dry_run: bool,
@@ -207,99 +760,728 @@ enum HistoryCommands {
 }
 
 #[derive(Subcommand, Debug)]
-enum ConfigCommands {
-    /// Show current configuration
-    Show,
+enum TagCommands {
+    /// Add tags to a file
+    Add {
+        /// File path, or record ID or unique prefix
+        file: String,
+
+        /// Tags to add
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
 
-    /// Generate default configuration file
-    Generate {
-        /// Output file path
-        #[arg(short, long, default_value = "config.json")]
-        output: PathBuf,
+    /// Remove tags from a file
+    Remove {
+        /// File path, or record ID or unique prefix
+        file: String,
 
-        /// Include all options with defaults
-        #[arg(long)]
-        full: bool,
+        /// Tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
     },
 
-    /// Validate configuration file
-    Validate,
+    /// List a file's tags; ones added by hand are marked
+    List {
+        /// File path, or record ID or unique prefix
+        file: String,
+    },
 
-    /// Edit configuration interactively
-    Edit,
+    /// List the files with a tag
+    Files {
+        tag: String,
+
+        /// Maximum number to show
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+    },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Subcommand, Debug)]
+enum PluginCommands {
+    /// List installed plugins with how many recent files each analyzed
+    List,
 
-    // Initialize tracing
-    let filter = if cli.trace {
-        "trace"
-    } else if cli.verbose {
-        "debug"
-    } else if cli.quiet {
-        "warn"
-    } else {
-        "info"
-    };
+    /// Show a plugin's manifest and the recent files it analyzed
+    Info {
+        name: String,
+    },
 
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
-        .init();
+    /// Use a disabled plugin again
+    Enable {
+        name: String,
+    },
 
-    if !cli.quiet {
-        info!("Panoptes v3.0.0 - Local AI File Scanner");
-    }
+    /// Stop using a plugin without removing it
+    Disable {
+        name: String,
+    },
 
-    // Load configuration
-    let config = AppConfig::load(&cli.config)?;
+    /// Install a plugin from its directory or a .zip/.tar.gz archive (path or URL)
+    Install {
+        /// Directory, archive or http(s) URL of an archive
+        source: String,
 
-    match cli.command {
-        Some(Commands::Watch { dir, dry_run, skip_health_check, process_existing, recursive: _ }) => {
-            run_watch(config, dir, dry_run, skip_health_check, process_existing).await
-        }
-        Some(Commands::Analyze { path, dry_run, recursive, min_confidence }) => {
-            run_analyze(config, path, dry_run, recursive, min_confidence, &cli.format).await
-        }
-        Some(Commands::Db { action }) => {
-            run_db_command(config, action).await
-        }
-        Some(Commands::History { action }) => {
-            run_history_command(config, action).await
-        }
-        Some(Commands::Config { action }) => {
-            run_config_command(config, action, &cli.config).await
-        }
-        Some(Commands::Status { model }) => {
-            run_status(config, model).await
-        }
-        Some(Commands::Init { dir, force }) => {
-            run_init(dir, force).await
-        }
-        None => {
-            // Default: run watch mode
-            run_watch(config, vec![], false, false, false).await
+        /// Replace an installed plugin of the same name
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AgentCommands {
+    /// Register this machine with a server to forward its files to
+    Register {
+        /// The server's address, e.g. https://panoptes.lan:8080
+        #[arg(long)]
+        server: String,
+
+        /// An API token of the server's with the editor role
+        #[arg(long)]
+        token: String,
+
+        /// Name to register as; the host name by default
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Check the connection to the server this machine forwards to
+    Status,
+
+    /// List the agents registered with this server
+    List,
+
+    /// Remove an agent from this server, revoking its token
+    Remove {
+        /// Name of the agent
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TokenCommands {
+    /// Create a new API token (shown once)
+    Create {
+        /// Name to identify the token by
+        name: String,
+
+        /// Access level: viewer, editor or admin
+        #[arg(long, default_value = "viewer")]
+        role: Role,
+    },
+
+    /// List API tokens
+    List,
+
+    /// Revoke an API token
+    Revoke {
+        /// Name of the token
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceCommands {
+    /// Install a unit running `panoptes watch` with this binary and config
+    Install {
+        /// Install for your user's service manager instead of system-wide
+        #[arg(long)]
+        user: bool,
+
+        /// Enable the service and start it now
+        #[arg(long)]
+        enable: bool,
+
+        /// Replace a unit of the same name not generated by panoptes
+        #[arg(long)]
+        force: bool,
+
+        /// Print the unit instead of installing it
+        #[arg(long, conflicts_with_all = ["enable", "force"])]
+        print: bool,
+    },
+
+    /// Show the service's status
+    Status {
+        /// The user service rather than the system one
+        #[arg(long)]
+        user: bool,
+    },
+
+    /// Stop and disable the service and remove its unit
+    Uninstall {
+        /// The user service rather than the system one
+        #[arg(long)]
+        user: bool,
+
+        /// Remove the unit even if it was not generated by panoptes
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum UserCommands {
+    /// List users and their roles
+    List,
+
+    /// Change a user's role
+    SetRole {
+        /// Username
+        username: String,
+
+        /// New role: viewer, editor or admin
+        role: Role,
+    },
+
+    /// Link an external login (OIDC issuer and subject) to a user, creating it if needed
+    Link {
+        /// Username
+        username: String,
+
+        /// OIDC issuer URL
+        #[arg(long)]
+        issuer: String,
+
+        /// Subject (`sub` claim) of the identity
+        #[arg(long)]
+        subject: String,
+
+        /// Role for a newly created user
+        #[arg(long, default_value = "viewer")]
+        role: Role,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Show current configuration, with secrets redacted
+    Show {
+        /// Show each setting's effective value and where it comes from: a
+        /// config file, a PANOPTES_* environment variable or the default
+        #[arg(long)]
+        resolved: bool,
+    },
+
+    /// Generate default configuration file
+    Generate {
+        /// Output file path
+        #[arg(short, long, default_value_os_t = paths::default_config())]
+        output: PathBuf,
+
+        /// Include all options with defaults
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Validate configuration file: the settings, the directories they name,
+    /// templates and the AI engine's models
+    Validate {
+        /// Don't ask the AI engine which models it has
+        #[arg(long)]
+        offline: bool,
+    },
+
+    /// Edit configuration interactively
+    Edit,
+
+    /// Move the database, thumbnail cache and plugins from the current
+    /// directory, where they used to go, to the user's data directory
+    Migrate {
+        /// Show what would be moved without moving it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// A confidence given on the command line, from 0 to 1
+fn parse_confidence(value: &str) -> std::result::Result<f64, String> {
+    let confidence: f64 = value.parse().map_err(|_| format!("'{}' is not a number", value))?;
+    if (0.0..=1.0).contains(&confidence) {
+        Ok(confidence)
+    } else {
+        Err(format!("{} is not between 0 and 1", value))
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = run(cli).await;
+    // Waits for the exporter, which runs on the runtime
+    let _ = tokio::task::spawn_blocking(telemetry::shutdown).await;
+    match result {
+        Ok(()) => ExitCode::from(exit_code::SUCCESS),
+        Err(PanoptesError::NothingToDo(message)) => {
+            eprintln!("{}", message);
+            ExitCode::from(exit_code::NOTHING_TO_DO)
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    // Neither needs a config, and completion scripts go to stdout without log lines
+    match &cli.command {
+        Some(Commands::Completions { shell }) => {
+            // Buffered, as clap_complete panics on write errors such as a closed pipe
+            let mut script = Vec::new();
+            clap_complete::generate(*shell, &mut Cli::command(), "panoptes", &mut script);
+            std::io::stdout().write_all(&script)?;
+            return Ok(());
+        }
+        Some(Commands::Man { out_dir }) => return run_man(out_dir),
+        _ => {}
+    }
+
+    // Initialize tracing
+    let filter = if cli.trace {
+        "trace"
+    } else if cli.verbose {
+        "debug"
+    } else if cli.quiet {
+        "warn"
+    } else {
+        "info"
+    };
+
+    if let Some(ref log_file) = cli.log_file {
+        daemon::open_log(log_file)?;
+    }
+    // Spans' durations are logged as they close when verbose
+    let span_events = if cli.verbose || cli.trace { FmtSpan::CLOSE } else { FmtSpan::NONE };
+    let log = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_ansi(cli.log_file.is_none() && cli.log_format == "text")
+        .with_span_events(span_events)
+        .with_writer(|| LogWriter);
+    let log = if cli.log_format == "json" { log.json().boxed() } else { log.boxed() };
+    let otlp = if telemetry::otlp_enabled(cli.otlp_endpoint.as_deref()) {
+        Some(tracing_opentelemetry::layer().with_tracer(telemetry::otlp_tracer(cli.otlp_endpoint.as_deref())?))
+    } else {
+        None
+    };
+    tracing_subscriber::registry()
+        .with(EnvFilter::new(filter))
+        .with(log)
+        .with(otlp)
+        .init();
+
+    if !cli.quiet {
+        info!("Panoptes v3.0.0 - Local AI File Scanner");
+    }
+
+    // Load configuration
+    let config = AppConfig::load(&cli.config)?;
+    ollama::limit_concurrency(config.ai_engine.max_concurrent);
+    if !matches!(cli.command, Some(Commands::Config { action: ConfigCommands::Migrate { .. } })) {
+        for legacy in paths::legacy(&config).iter().filter(|l| !l.pinned) {
+            warn!("Found {} in the current directory, but {} now defaults to {}; run `panoptes config migrate` to move it there",
+                legacy.from.display(), legacy.setting, legacy.to.display());
+        }
+    }
+
+    match cli.command {
+        Some(Commands::Watch {
+            dir, dry_run, skip_health_check, process_existing, recursive, organize, daemon, pid_file, profile,
+        }) => {
+            let pid_file = pid_file.unwrap_or_else(|| daemon::default_pid_file(&cli.config));
+            if daemon {
+                return start_daemon(&cli.config, &pid_file, cli.log_file.as_deref());
+            }
+            let flags = WatchOptions { recursive, organize, ..Default::default() };
+            run_watch(config, &cli.config, &profile, dir, dry_run, flags, skip_health_check, process_existing, &pid_file, None).await
+        }
+        Some(Commands::Serve {
+            dir, dry_run, skip_health_check, process_existing, recursive, organize, host, port, daemon, pid_file, profile,
+        }) => {
+            let pid_file = pid_file.unwrap_or_else(|| daemon::default_pid_file(&cli.config));
+            if daemon {
+                return start_daemon(&cli.config, &pid_file, cli.log_file.as_deref());
+            }
+            let mut config = config;
+            if let Some(host) = host {
+                config.web.host = host;
+            }
+            if let Some(port) = port {
+                config.web.port = port;
+            }
+            let flags = WatchOptions { recursive, organize, ..Default::default() };
+            run_serve(config, &cli.config, &profile, dir, dry_run, flags, skip_health_check, process_existing, &pid_file).await
+        }
+        Some(Commands::Analyze {
+            path, dry_run, recursive, files_from, null, resume, min_confidence, jobs, report,
+            ext, exclude, min_size, max_size, newer_than, max_depth, interactive, as_dir,
+        }) => {
+            // Above it, suggestions that would be renamed are left out instead
+            let auto_rename = auto_rename_threshold(&config);
+            if let Some(min) = min_confidence.filter(|&min| min > auto_rename) {
+                return Err(PanoptesError::Config(format!(
+                    "--min-confidence {} is above the auto-rename threshold {}", min, auto_rename
+                )));
+            }
+            if as_dir {
+                return run_analyze_dir(config, &path.unwrap_or_default(), dry_run, min_confidence, &cli.format).await;
+            }
+            let db = Database::open(&config.database.path)?;
+            let (run, resumed) = match resume {
+                Some(id) => {
+                    let run = db.find_scan_run(&id)?
+                        .ok_or_else(|| PanoptesError::Config(format!("No analysis run '{}'", id)))?;
+                    (run, true)
+                }
+                None => {
+                    let selection = FileSelection { extensions: ext, exclude, min_size, max_size, newer_than, max_depth };
+                    Selector::new(&selection)?;
+                    // Absolute paths, so the run can be resumed from anywhere
+                    let cwd = std::env::current_dir()?;
+                    let (path, files) = match files_from {
+                        Some(list) => {
+                            let files = read_file_list(&list, null)?.iter()
+                                .map(|file| cwd.join(file).to_string_lossy().into_owned())
+                                .collect();
+                            (list, Some(files))
+                        }
+                        None => (cwd.join(path.unwrap_or_default()), None),
+                    };
+                    if files.is_none() && !path.exists() {
+                        return Err(PanoptesError::Config(format!("No such file or directory: {}", path.display())));
+                    }
+                    let run = ScanRun {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        path: path.to_string_lossy().into_owned(),
+                        files,
+                        recursive: recursive || selection.max_depth.is_some(),
+                        selection,
+                        dry_run,
+                        min_confidence: min_confidence.unwrap_or(config.rules.suggest_threshold),
+                        started_at: chrono::Utc::now(),
+                        finished_at: None,
+                    };
+                    db.create_scan_run(&run)?;
+                    (run, false)
+                }
+            };
+            let run_id = run.id.clone();
+            // --yes answers every question, so there's nothing to ask
+            let interactive = interactive && !cli.yes;
+            let outcome = run_analyze(config, db.clone(), run, resumed, jobs.into(), interactive, &cli.format, cli.quiet).await;
+            // Failed renames are in the report too, so write it either way
+            if let Some(report) = report {
+                run_report(&db, Some(&run_id), Some(&report))?;
+            }
+            outcome
+        }
+        Some(Commands::Reprocess { max_confidence, category, tag, failed, model, force, dry_run, limit, queue }) => {
+            let filter = (!failed).then(|| {
+                let uncategorized = category.as_deref() == Some("Uncategorized");
+                FileFilter {
+                    category: category.filter(|_| !uncategorized),
+                    uncategorized,
+                    tag,
+                    max_confidence,
+                    sort: FileSort::Confidence,
+                    ascending: true,
+                    ..Default::default()
+                }
+            });
+            match filter {
+                Some(filter) if queue => queue_reprocess(&config, &filter, limit, model, force),
+                _ => run_reprocess(config.with_overrides(model.as_deref(), None), filter, limit, force, dry_run).await,
+            }
+        }
+        Some(Commands::Verify { fix }) => {
+            run_verify(config, fix, &cli.format)
+        }
+        Some(Commands::Prune { records, history, tags, thumbnails, cache, older_than, dry_run }) => {
+            let all = !(records || history || tags || thumbnails || cache);
+            let options = PruneOptions {
+                records: records || all,
+                history: history || all,
+                tags: tags || all,
+                thumbnails: thumbnails || all,
+                cache: cache || all,
+                cutoff: chrono::Utc::now() - chrono::Duration::days(older_than.into()),
+                dry_run,
+            };
+            run_prune(config, &options, &cli.format, cli.yes)
+        }
+        Some(Commands::Stats { days, top }) => {
+            let db = Database::open(&config.database.path)?;
+            let stats = stats::collect(&db, days, top)?;
+            if cli.format == "json" || cli.format == "jsonl" {
+                println!("{}", serde_json::to_string(&stats)?);
+            } else {
+                print!("{}", stats::render(&stats));
+            }
+            Ok(())
+        }
+        Some(Commands::Similar { file, limit, min_score }) => {
+            let mut config = config;
+            config.similarity.limit = limit.unwrap_or(config.similarity.limit);
+            config.similarity.min_score = min_score.unwrap_or(config.similarity.min_score);
+            config.validate()?;
+            run_similar(&config, &file, &cli.format).await
+        }
+        Some(Commands::Report { run, output }) => {
+            let db = Database::open(&config.database.path)?;
+            run_report(&db, run.as_deref(), output.as_deref())
+        }
+        Some(Commands::Summarize { dir, output, stored }) => {
+            run_summarize(config, &dir, output.as_deref(), stored, &cli.format).await
+        }
+        Some(Commands::Organize { dir, dry_run, recursive }) => {
+            run_organize(config, dir, dry_run, recursive, &cli.format).await
+        }
+        Some(Commands::Vault { action }) => {
+            run_vault_command(config, action, &cli.format)
+        }
+        Some(Commands::Views { action }) => {
+            run_view_command(config, action, &cli.format)
+        }
+        Some(Commands::Sources { action }) => {
+            run_source_command(config, action, &cli.format).await
+        }
+        Some(Commands::Db { action }) => {
+            run_db_command(config, action).await
+        }
+        Some(Commands::Jobs { action }) => {
+            run_jobs_command(config, action, &cli.format)
+        }
+        Some(Commands::History { action }) => {
+            run_history_command(config, action, cli.yes).await
+        }
+        Some(Commands::Tag { action }) => {
+            run_tag_command(config, action, &cli.format)
+        }
+        Some(Commands::Plugin { action }) => {
+            run_plugin_command(config, action, &cli.format).await
+        }
+        Some(Commands::Agent { action }) => {
+            run_agent_command(config, action, &cli.config, &cli.format).await
+        }
+        Some(Commands::Token { action }) => {
+            run_token_command(config, action).await
+        }
+        Some(Commands::User { action }) => {
+            run_user_command(config, action).await
+        }
+        Some(Commands::Config { action }) => {
+            run_config_command(config, action, &cli.config, &cli.format).await
+        }
+        Some(Commands::Service { action }) => {
+            run_service_command(action, &cli.config)
+        }
+        Some(Commands::Status { model }) => {
+            run_status(config, model).await
+        }
+        Some(Commands::Init { dir, force, template }) => {
+            run_init(dir, force, template.as_deref()).await
+        }
+        Some(Commands::Completions { .. } | Commands::Man { .. }) => unreachable!("handled before loading the config"),
+        None => {
+            // Default: run watch mode
+            let pid_file = daemon::default_pid_file(&cli.config);
+            run_watch(config, &cli.config, &[], vec![], false, WatchOptions::default(), false, false, &pid_file, None).await
+        }
+    }
+}
+
+/// How often watch mode picks up watch directory changes from the config file
+/// and reports the directories' status
+const WATCH_SYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Directories to watch with their options: those given with `--dir`, or the
+/// configured ones. `flags` are the `--recursive`/`--organize` options, which
+/// apply to every directory.
+fn watch_dirs(config: &AppConfig, dir_overrides: &[PathBuf], flags: &WatchOptions) -> Vec<(PathBuf, WatchOptions)> {
+    if !dir_overrides.is_empty() {
+        return dir_overrides.iter()
+            .map(|dir| (dir.clone(), flags.clone()))
+            .collect();
+    }
+    config.watch_paths.iter()
+        .map(|dir| {
+            let mut options = config.watch_options(dir);
+            options.recursive |= flags.recursive;
+            options.organize |= flags.organize;
+            (PathBuf::from(dir), options)
+        })
+        .collect()
+}
+
+/// Watch exactly `dirs` and record their status for the web UI. `errors` holds
+/// the previous failures, so each is only logged once.
+fn sync_watch_dirs(
+    watcher: &mut FileWatcher,
+    dirs: &[(PathBuf, WatchOptions)],
+    db: &Database,
+    errors: &mut Vec<(PathBuf, String)>,
+) {
+    let dropped: Vec<PathBuf> = watcher.watched_paths().into_iter()
+        .filter(|path| !dirs.iter().any(|(dir, _)| dir == path))
+        .map(Path::to_path_buf)
+        .collect();
+    let wanted: Vec<(PathBuf, bool)> = dirs.iter().map(|(dir, options)| (dir.clone(), options.recursive)).collect();
+    let failed = watcher.sync(&wanted);
+
+    for path in dropped {
+        if let Err(e) = db.remove_watch_status(&path.to_string_lossy()) {
+            warn!("Failed to record watch status: {}", e);
+        }
+    }
+    for (dir, _) in dirs {
+        let error = failed.iter().find(|(path, _)| path == dir).map(|(_, e)| e.as_str());
+        if let Some(e) = error {
+            if !errors.iter().any(|(path, previous)| path == dir && previous == e) {
+                error!("Cannot watch {:?}: {}", dir, e);
+            }
+        }
+        if let Err(e) = db.set_watch_status(&dir.to_string_lossy(), error) {
+            warn!("Failed to record watch status: {}", e);
+        }
+    }
+    *errors = failed;
+}
+
+/// Start the watcher in the background (`watch --daemon`)
+fn start_daemon(config_path: &Path, pid_file: &Path, log_file: Option<&Path>) -> Result<()> {
+    if let Some(pid) = PidFile::holder(pid_file) {
+        return Err(PanoptesError::Config(format!(
+            "Another watcher (PID {}) is already running with this config; PID file {}", pid, pid_file.display()
+        )));
+    }
+    let log_file = log_file.map_or_else(|| daemon::default_log_file(config_path), Path::to_path_buf);
+    let pid = daemon::detach(&log_file)?;
+    println!("Watcher started in the background (PID {})", pid);
+    println!("  Log: {}", log_file.display());
+    println!("  PID file: {}", pid_file.display());
+    Ok(())
+}
+
+/// What a watcher shares with the web UI running in the same process
+struct Shared {
+    db: Database,
+    live: Arc<LiveStatus>,
+}
+
+/// A config file a watcher follows, with the directories it watches for it
+struct Profile {
+    path: PathBuf,
+    config: AppConfig,
+    registry: Arc<AnalyzerRegistry>,
+    dirs: Vec<(PathBuf, WatchOptions)>,
+    modified: Option<std::time::SystemTime>,
+}
+
+impl Profile {
+    fn new(path: &Path, config: AppConfig, dirs: Vec<(PathBuf, WatchOptions)>) -> Self {
+        let registry = Arc::new(AnalyzerRegistry::new(&config));
+        info!("Loaded {} analyzers for {}: {:?}", registry.len(), path.display(), registry.analyzer_names());
+        Self { path: path.to_path_buf(), modified: config_modified_time(path), config, registry, dirs }
+    }
+}
+
+impl Profile {
+    /// Take up the changes to the config file, if any and if it's valid:
+    /// settings apply to files processed from now on, except those that need a
+    /// restart. The watch directories follow unless `fixed_dirs`. Returns
+    /// whether they changed.
+    fn reload(&mut self, fixed_dirs: bool, flags: &WatchOptions) -> bool {
+        let modified = config_modified_time(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+
+        let reloaded = AppConfig::load(&self.path)
+            .and_then(|updated| updated.validate().map(|()| updated))
+            .and_then(|updated| self.config.reloaded(&updated));
+        let (config, changes) = match reloaded {
+            Ok(reloaded) => reloaded,
+            Err(e) => {
+                warn!("Keeping the current configuration, as {} is invalid: {}", self.path.display(), e);
+                return false;
+            }
+        };
+        // Paths only: values may include secrets
+        let (restart, applied): (Vec<_>, Vec<_>) = changes.iter().partition(|c| c.restart_required);
+        if !applied.is_empty() {
+            let paths: Vec<&str> = applied.iter().map(|c| c.path.as_str()).collect();
+            info!("Applied changes to {}: {}", self.path.display(), paths.join(", "));
+        }
+        if !restart.is_empty() {
+            let paths: Vec<&str> = restart.iter().map(|c| c.path.as_str()).collect();
+            warn!("Changes to {} take effect after a restart: {}", self.path.display(), paths.join(", "));
+        }
+        self.config = config;
+
+        if fixed_dirs {
+            return false;
+        }
+        let dirs = watch_dirs(&self.config, &[], flags);
+        let changed = dirs != self.dirs;
+        self.dirs = dirs;
+        changed
+    }
+}
+
+/// The profile watching `dir`
+fn profile_of<'a>(profiles: &'a [Profile], dir: &Path) -> Option<&'a Profile> {
+    profiles.iter().find(|profile| profile.dirs.iter().any(|(d, _)| d == dir))
+}
+
+/// The directories of all `profiles`; fails when two watch the same one
+fn all_watch_dirs(profiles: &[Profile]) -> Result<Vec<(PathBuf, WatchOptions)>> {
+    let mut dirs: Vec<(PathBuf, WatchOptions)> = Vec::new();
+    for profile in profiles {
+        for (dir, options) in &profile.dirs {
+            if let Some(other) = profile_of(profiles, dir).filter(|other| other.path != profile.path) {
+                return Err(PanoptesError::Config(format!(
+                    "{} is watched by both {} and {}", dir.display(), other.path.display(), profile.path.display()
+                )));
+            }
+            dirs.push((dir.clone(), options.clone()));
         }
     }
+    Ok(dirs)
 }
 
-/// Run the watch mode (main scanner loop)
+/// Run the watch mode (main scanner loop). The directories of each config in
+/// `profile_paths` are watched as well, each analyzed and renamed by its own
+/// config; the database, AI engine check and web UI are the main config's.
+#[allow(clippy::too_many_arguments)]
 async fn run_watch(
     config: AppConfig,
+    config_path: &Path,
+    profile_paths: &[PathBuf],
     dir_overrides: Vec<PathBuf>,
     dry_run: bool,
+    flags: WatchOptions,
     skip_health_check: bool,
     process_existing: bool,
+    pid_file: &Path,
+    shared: Option<Shared>,
 ) -> Result<()> {
-    let watch_paths: Vec<PathBuf> = if dir_overrides.is_empty() {
-        config.watch_paths.iter().map(PathBuf::from).collect()
-    } else {
-        dir_overrides
-    };
+    let _pid_file = PidFile::acquire(pid_file)?;
+    let mut profiles = vec![Profile::new(config_path, config.clone(), watch_dirs(&config, &dir_overrides, &flags))];
+    for path in profile_paths {
+        if !path.is_file() {
+            return Err(PanoptesError::Config(format!("No such profile: {}", path.display())));
+        }
+        let profile = AppConfig::load(path)?;
+        if profile.database.path != config.database.path {
+            warn!("Profile {} has its own database; files are recorded in {}", path.display(), config.database.path);
+        }
+        let dirs = watch_dirs(&profile, &[], &flags);
+        profiles.push(Profile::new(path, profile, dirs));
+    }
+    let mut dirs = all_watch_dirs(&profiles)?;
 
-    info!("Watch directories: {:?}", watch_paths);
+    info!("Watch directories: {:?}", dirs.iter().map(|(dir, _)| dir).collect::<Vec<_>>());
 
     if dry_run {
         warn!("DRY RUN MODE - files will not be renamed");
@@ -309,10 +1491,22 @@ async fn run_watch(
     let client = OllamaClient::new(&config.ai_engine.url);
 
     // Health check
-    if !skip_health_check {
+    if let Some(server) = agent::server(&config) {
+        info!("Agent mode: forwarding files to {}", server);
+        match agent::whoami(&config).await {
+            Ok(me) => info!("Registered with {} as {}", server, me.name),
+            // Files wait in the queue until the server is back
+            Err(e) => warn!("{}", e),
+        }
+    } else if !skip_health_check {
         info!("Checking Ollama availability...");
         match client.health_check().await {
-            Ok(()) => info!("Ollama is running"),
+            Ok(()) => {
+                info!("Ollama is running");
+                if let Some(ref shared) = shared {
+                    shared.live.engine_checked(None);
+                }
+            }
             Err(e) => {
                 return Err(PanoptesError::OllamaUnavailable(format!(
                     "Failed to connect to Ollama: {}. Try: just start-engine", e
@@ -334,45 +1528,118 @@ async fn run_watch(
     }
 
     // Initialize database
-    let db = Database::open(&config.database.path)?;
+    let (db, live) = match shared {
+        Some(Shared { db, live }) => (db, Some(live)),
+        None => (Database::open(&config.database.path)?, None),
+    };
     info!("Database initialized: {}", config.database.path);
+    for profile in &profiles {
+        profile.registry.load_corrections(&db);
+    }
 
     // Initialize history
-    let history_path = PathBuf::from("panoptes_history.jsonl");
-    let history = History::new(history_path.clone());
+    let history = open_history(&db)?;
+    let session_id = uuid::Uuid::new_v4().to_string();
+    info!("Session: {}", session_id);
 
-    // Initialize analyzer registry
-    let registry = AnalyzerRegistry::new(&config);
-    info!("Loaded {} analyzers: {:?}", registry.len(), registry.analyzer_names());
+    let notifier = Arc::new(Notifier::new());
+
+    // Keep an eye on the AI engine, and send the daily summary
+    if agent::server(&config).is_none() {
+        tokio::spawn(monitor_engine(client, live.clone(), config.notifications.clone(), notifier.clone()));
+    }
+    if config.notifications.wants(NotificationEvent::DailySummary) {
+        tokio::spawn(notifications::send_daily_summaries(config.notifications.clone(), db.clone(), notifier.clone()));
+    }
 
     // Setup file watcher
     let mut watcher = FileWatcher::new()?;
-    for path in &watch_paths {
-        watcher.watch(path)?;
+    let mut watch_errors = Vec::new();
+    sync_watch_dirs(&mut watcher, &dirs, &db, &mut watch_errors);
+    if watcher.watched_paths().is_empty() && !dirs.is_empty() {
+        return Err(PanoptesError::Config("None of the watch directories could be watched".to_string()));
+    }
+
+    // Files are analyzed by the workers of the job queue, which also take
+    // jobs queued by other commands
+    let profiles = Arc::new(RwLock::new(profiles));
+    let queue = Queue::new(db.clone());
+    let scheduler = Scheduler { profiles: profiles.clone(), db: db.clone(), queue: queue.clone(), dry_run };
+
+    // Events also go to the MQTT broker and D-Bus, which can give commands
+    let mut webhooks = Webhooks::new(db.clone());
+    let (mqtt, commands) = match Mqtt::connect(&config.mqtt)? {
+        Some((mqtt, commands)) => (Some(Arc::new(mqtt)), Some(commands)),
+        None => (None, None),
+    };
+    if let Some(ref mqtt) = mqtt {
+        webhooks = webhooks.with_sink(mqtt.clone());
+    }
+    #[cfg(target_os = "linux")]
+    let _dbus = {
+        let (analyzer, rescanner) = (scheduler.clone(), scheduler.clone());
+        let dbus = panoptes::dbus::Dbus::start(
+            &config.dbus, queue.clone(), db.clone(),
+            Arc::new(move |path: &Path| analyzer.queue_file(path)),
+            Arc::new(move || rescanner.run(&ScheduledTask::Rescan)),
+        ).await?.map(Arc::new);
+        if let Some(ref dbus) = dbus {
+            webhooks = webhooks.with_sink(dbus.clone());
+        }
+        dbus
+    };
+    #[cfg(not(target_os = "linux"))]
+    if config.dbus.enabled {
+        warn!("dbus.enabled is ignored: D-Bus is only served on Linux");
+    }
+    let webhooks = Arc::new(webhooks);
+
+    let worker = Arc::new(Worker {
+        profiles: profiles.clone(),
+        db: db.clone(),
+        queue: queue.clone(),
+        history,
+        webhooks: webhooks.clone(),
+        notifier: notifier.clone(),
+        session_id,
+        live: live.clone(),
+    });
+    jobs::start_workers(&queue, &config.jobs, move |job| {
+        let worker = worker.clone();
+        async move { worker.run(job).await }
+    });
+
+    if let (Some(mqtt), Some(commands)) = (mqtt.clone(), commands) {
+        tokio::spawn(serve_mqtt(mqtt, commands, scheduler.clone(), config.mqtt.state_interval_secs));
+    }
+    schedule::start(&config.schedule, &db, move |task| scheduler.run(task));
+    if !dry_run {
+        tokio::spawn(refresh_views(profiles.clone(), db.clone()));
+        tokio::spawn(poll_sources(profiles.clone(), db.clone()));
     }
 
     // Process existing files if requested
     if process_existing {
-        info!("Processing existing files...");
-        for dir in &watch_paths {
+        let profiles = profiles.read().unwrap_or_else(|e| e.into_inner());
+        let mut queued = 0;
+        for (dir, options) in &dirs {
+            let Some(profile) = profile_of(&profiles, dir) else { continue };
+            let selector = Selector::new(&options.select).unwrap_or_default();
             if let Ok(entries) = std::fs::read_dir(dir) {
                 for entry in entries.flatten() {
                     let path = entry.path();
-                    if path.is_file() && should_process(&path) {
-                        if let Err(e) = process_file(
-                            path.clone(),
-                            &config,
-                            &registry,
-                            &db,
-                            &history,
-                            dry_run,
-                        ).await {
-                            error!("Failed to process {:?}: {}", path, e);
+                    if path.is_file() && should_process(&path) && selector.matches(&path, 0)
+                        && !(profile.config.rules.skip_conformant && skiplist::is_conformant(&path, &profile.config))
+                    {
+                        match queue.push(&analyze_task(&path, Some(dir), options, &profile.path, dry_run), &profile.config.jobs) {
+                            Ok(_) => queued += 1,
+                            Err(e) => error!("Failed to queue {:?}: {}", path, e),
                         }
                     }
                 }
             }
         }
+        info!("Queued {} existing file(s) for processing", queued);
     }
 
     // Setup graceful shutdown
@@ -402,8 +1669,28 @@ async fn run_watch(
         let _ = shutdown_tx.send(true);
     });
 
+    // Reopen the log file when asked, e.g. by logrotate
+    #[cfg(unix)]
+    if let Ok(mut hangup) = signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match daemon::reopen_log() {
+                    Ok(()) => info!("Received SIGHUP, reopened log file"),
+                    Err(e) => warn!("Failed to reopen log file: {}", e),
+                }
+            }
+        });
+    }
+
     info!("Scanner active. Press Ctrl+C to stop.");
     info!("Waiting for files...");
+    daemon::notify::ready();
+    let watchdog_interval = daemon::notify::watchdog_interval();
+    let mut last_watchdog = std::time::Instant::now();
+
+    // Config files are checked for changes, made through the web UI or
+    // otherwise, every WATCH_SYNC_INTERVAL
+    let mut last_sync = std::time::Instant::now();
 
     // Main event loop
     loop {
@@ -411,33 +1698,98 @@ async fn run_watch(
             break;
         }
 
-        if let Some(event) = watcher.next_event(Duration::from_millis(100)) {
-            match event {
-                WatchEvent::FileCreated(path) => {
-                    if should_process(&path) {
-                        let config_clone = config.clone();
-                        let db_clone = db.clone();
-                        let history_clone = History::new(history_path.clone());
-                        let registry_clone = registry.clone();
+        if watchdog_interval.is_some_and(|interval| last_watchdog.elapsed() >= interval) {
+            last_watchdog = std::time::Instant::now();
+            daemon::notify::watchdog();
+        }
 
-                        tokio::spawn(async move {
+        if last_sync.elapsed() >= WATCH_SYNC_INTERVAL {
+            last_sync = std::time::Instant::now();
+            let mut profiles = profiles.write().unwrap_or_else(|e| e.into_inner());
+            let mut changed = false;
+            for (i, profile) in profiles.iter_mut().enumerate() {
+                let fixed_dirs = i == 0 && !dir_overrides.is_empty();
+                changed |= profile.reload(fixed_dirs, &flags);
+            }
+            if changed {
+                match all_watch_dirs(&profiles) {
+                    Ok(updated) => {
+                        info!("Watch directories changed: {:?}", updated.iter().map(|(dir, _)| dir).collect::<Vec<_>>());
+                        dirs = updated;
+                    }
+                    // Files in a directory claimed twice go by the first profile claiming it
+                    Err(e) => warn!("{}", e),
+                }
+            }
+            // Also refreshes the status, and retries directories that failed
+            sync_watch_dirs(&mut watcher, &dirs, &db, &mut watch_errors);
+        }
+
+        if let Some(event) = watcher.next_event(Duration::from_millis(100)) {
+            match event {
+                WatchEvent::FileCreated(path) => {
+                    let watch_dir = watcher.watch_dir_of(&path).map(Path::to_path_buf);
+                    if let Some(ref dir) = watch_dir {
+                        if let Err(e) = db.record_watch_event(&dir.to_string_lossy()) {
+                            warn!("Failed to record watch event: {}", e);
+                        }
+                    }
+                    let options = dirs.iter()
+                        .find(|(dir, _)| Some(dir) == watch_dir.as_ref())
+                        .map(|(_, options)| options.clone())
+                        .unwrap_or_default();
+                    let profiles = profiles.read().unwrap_or_else(|e| e.into_inner());
+                    let profile = watch_dir.as_deref()
+                        .and_then(|dir| profile_of(&profiles, dir))
+                        .unwrap_or(&profiles[0]);
+                    let relative = watcher.relative_path(&path).map(Path::to_path_buf);
+                    // Files arriving in quarantine were put there for review, and
+                    // in the vault and views, or just renamed, were put there by Panoptes
+                    let ours = vault::contains(&path, &profile.config) || views::contains(&path, &profile.config)
+                        || skiplist::is_recent(&path);
+                    let named = profile.config.rules.skip_conformant && skiplist::is_conformant(&path, &profile.config);
+                    if named {
+                        debug!("{:?} is named already", path);
+                    }
+                    if should_process(&path) && !is_quarantined(&path, &profile.config) && !ours && !named {
+                        let task = analyze_task(&path, watch_dir.as_deref(), &options, &profile.path, dry_run);
+                        let jobs_config = profile.config.jobs.clone();
+                        let queue = queue.clone();
+                        let cid = telemetry::correlation_id();
+                        let span = info_span!("file", cid = %cid, path = %path.display());
+
+                        tokio::spawn(async move {
                             // Wait for file stability
-                            if !wait_for_stable(&path, Duration::from_secs(10)).await {
+                            if !wait_for_stable(&path, Duration::from_secs(10)).instrument(info_span!("settle")).await {
                                 debug!("File disappeared during stability check: {:?}", path);
                                 return;
                             }
-
-                            if let Err(e) = process_file(
-                                path.clone(),
-                                &config_clone,
-                                &registry_clone,
-                                &db_clone,
-                                &history_clone,
-                                dry_run,
-                            ).await {
-                                error!("Failed to process {:?}: {}", path, e);
+                            // Sizes and ages are only final once the file has settled
+                            let selector = Selector::new(&options.select).unwrap_or_default();
+                            let relative = relative.as_deref().unwrap_or(Path::new(""));
+                            if !selector.matches_at(&path, relative) {
+                                debug!("Not selected by the watch directory's filters: {:?}", path);
+                                return;
                             }
-                        });
+                            match queue.push_correlated(&task, &cid, &jobs_config) {
+                                Ok(id) => debug!("Queued as job {}", id),
+                                Err(e) => error!("Failed to queue {:?}: {}", path, e),
+                            }
+                        }.instrument(span));
+                    }
+                }
+                // Renamed by Panoptes
+                WatchEvent::FileRenamed { to, .. } if skiplist::is_recent(&to) => {}
+                WatchEvent::FileRenamed { from, to } => {
+                    // Renamed by hand: follow the file, and learn from a new name
+                    match db.find_file_by_path(&from) {
+                        Ok(Some(file)) => match feedback::renamed(&db, &file, &to) {
+                            Ok(true) => info!("Recorded correction: {:?} -> {:?}", from, to),
+                            Ok(false) => {}
+                            Err(e) => warn!("Failed to follow rename of {:?}: {}", from, e),
+                        },
+                        Ok(None) => {}
+                        Err(e) => warn!("Failed to look up {:?}: {}", from, e),
                     }
                 }
                 WatchEvent::Error(e) => {
@@ -448,196 +1800,879 @@ async fn run_watch(
         }
     }
 
+    daemon::notify::stopping();
+    webhooks.flush().await;
+    notifier.flush().await;
+    if let Some(ref mqtt) = mqtt {
+        mqtt.disconnect().await;
+    }
     info!("Panoptes stopped.");
     Ok(())
 }
 
-/// Process a single file
-async fn process_file(
-    path: PathBuf,
-    config: &AppConfig,
-    registry: &AnalyzerRegistry,
-    db: &Database,
-    history: &History,
+/// How often watch mode checks the AI engine is still answering
+const ENGINE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often `serve` does routine upkeep
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Run the watcher, with its AI engine health monitor, the web UI and routine
+/// upkeep in one process. Stops when the watcher does (on a signal) or the web server fails.
+#[allow(clippy::too_many_arguments)]
+async fn run_serve(
+    config: AppConfig,
+    config_path: &Path,
+    profile_paths: &[PathBuf],
+    dir_overrides: Vec<PathBuf>,
     dry_run: bool,
+    flags: WatchOptions,
+    skip_health_check: bool,
+    process_existing: bool,
+    pid_file: &Path,
 ) -> Result<()> {
-    info!("Analyzing: {:?}", path);
+    let db = Database::open(&config.database.path)?;
+    let live = Arc::new(LiveStatus::new(dry_run));
 
-    // Find appropriate analyzer
-    let analyzer = match registry.find_analyzer(&path) {
-        Some(a) => a,
-        None => {
-            debug!("No analyzer for: {:?}", path);
-            return Ok(());
+    let state = web::AppState::new(config.clone(), config_path.to_path_buf(), db.clone())
+        .with_live_status(live.clone());
+    // Its own task, as the watcher's event loop blocks between events
+    let mut web = tokio::spawn(web::serve(Arc::new(state)));
+
+    tokio::spawn(maintain(config.clone(), db.clone()));
+
+    let shared = Shared { db, live };
+    let watch = run_watch(
+        config, config_path, profile_paths, dir_overrides, dry_run, flags, skip_health_check, process_existing, pid_file,
+        Some(shared),
+    );
+    tokio::select! {
+        result = watch => result,
+        result = &mut web => result.unwrap_or_else(|e| Err(PanoptesError::Config(format!("Web server stopped: {}", e)))),
+    }
+}
+
+/// Check the AI engine every [`ENGINE_CHECK_INTERVAL`], logging and notifying
+/// when it goes away or comes back
+async fn monitor_engine(
+    client: OllamaClient,
+    live: Option<Arc<LiveStatus>>,
+    notifications: NotificationConfig,
+    notifier: Arc<Notifier>,
+) {
+    let mut interval = tokio::time::interval(ENGINE_CHECK_INTERVAL);
+    interval.tick().await; // the watcher checks at startup
+    let mut was_available = live.as_ref().and_then(|live| live.snapshot().engine_available);
+    loop {
+        interval.tick().await;
+        let error = client.health_check().await.err().map(|e| e.to_string());
+        match error {
+            None if was_available == Some(false) => {
+                info!("AI engine is available again");
+                notifier.send(&notifications, NotificationEvent::EngineUp, notifications::engine_up());
+            }
+            Some(ref e) if was_available != Some(false) => {
+                warn!("AI engine unavailable: {}", e);
+                notifier.send(&notifications, NotificationEvent::EngineDown, notifications::engine_down(e));
+            }
+            _ => {}
         }
-    };
+        was_available = Some(error.is_none());
+        if let Some(ref live) = live {
+            live.engine_checked(error);
+        }
+    }
+}
 
-    info!("Using analyzer: {}", analyzer.name());
+/// Do the commands given over MQTT, and publish the state after each and
+/// every `interval_secs`
+async fn serve_mqtt(
+    mqtt: Arc<Mqtt>,
+    mut commands: tokio::sync::mpsc::UnboundedReceiver<mqtt::Command>,
+    scheduler: Scheduler,
+    interval_secs: u64,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            command = commands.recv() => {
+                let Some(command) = command else { return };
+                info!("MQTT command: {:?}", command);
+                match command {
+                    mqtt::Command::Pause => scheduler.queue.pause(),
+                    mqtt::Command::Resume => scheduler.queue.resume(),
+                    mqtt::Command::Rescan => {
+                        let scheduler = scheduler.clone();
+                        tokio::task::spawn_blocking(move || match scheduler.run(&ScheduledTask::Rescan) {
+                            Ok(summary) => info!("Rescan: {}", summary),
+                            Err(e) => warn!("Rescan failed: {}", e),
+                        });
+                    }
+                    mqtt::Command::Analyze(path) => match scheduler.queue_file(&path) {
+                        Ok(id) => info!("Queued {:?} as job {}", path, id),
+                        Err(e) => warn!("Not analyzing {:?}: {}", path, e),
+                    },
+                }
+            }
+        }
+        match mqtt::State::collect(&scheduler.db, scheduler.queue.is_paused()) {
+            Ok(state) => mqtt.publish_state(&state),
+            Err(e) => warn!("Failed to put together the MQTT state: {}", e),
+        }
+    }
+}
 
-    // Run analysis
-    let result = analyzer.analyze(&path, config).await?;
+/// Routine upkeep every [`MAINTENANCE_INTERVAL`]: removing thumbnails no file
+/// needs any more, and ones left by interrupted generation
+async fn maintain(config: AppConfig, db: Database) {
+    let thumbnails = ThumbnailCache::new(&config.thumbnails);
+    let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let options = PruneOptions {
+            records: false,
+            history: false,
+            tags: false,
+            thumbnails: true,
+            cache: false,
+            cutoff: chrono::Utc::now(),
+            dry_run: false,
+        };
+        match prune::prune(&db, Path::new(&config.database.path), &thumbnails, &options) {
+            Ok(summary) if summary.thumbnails > 0 => {
+                info!("Removed {} stale thumbnail(s), {}", summary.thumbnails, HumanBytes(summary.thumbnail_bytes));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Routine upkeep failed: {}", e),
+        }
+    }
+}
 
-    info!("Suggestion: {} (confidence: {:.0}%)", result.suggested_name, result.confidence * 100.0);
+/// Directory renamed files from `dir` are organized under, if they are
+fn organize_root(dir: &Path, options: &WatchOptions) -> Option<PathBuf> {
+    options.organize.then(|| options.destination.as_deref().map_or_else(|| dir.to_path_buf(), PathBuf::from))
+}
 
-    if let Some(ref cat) = result.category {
-        info!("Category: {}", cat);
+/// The job analyzing `path`, which turned up in the watch directory `dir`
+/// of the profile whose config file is `profile`
+fn analyze_task(path: &Path, dir: Option<&Path>, options: &WatchOptions, profile: &Path, dry_run: bool) -> Task {
+    Task::Analyze {
+        path: path.to_path_buf(),
+        profile: Some(profile.to_path_buf()),
+        destination: options.destination.as_deref().map(PathBuf::from),
+        organize_root: dir.and_then(|dir| organize_root(dir, options)),
+        dry_run: dry_run || options.dry_run,
     }
-    if !result.tags.is_empty() {
-        info!("Tags: {:?}", result.tags);
+}
+
+/// Have the agent's server analyze `path`, and rename it as it says
+async fn forward_file(path: &Path, config: &AppConfig, history: &History, session_id: &str, dry_run: bool) -> Result<()> {
+    info!("Forwarding {:?} to {}", path, agent::server(config).unwrap_or_default());
+    let instruction = agent::forward(path, config).await?;
+    agent::apply(path, &instruction, config, history, Some(session_id), dry_run)?;
+    Ok(())
+}
+
+/// What the watcher's workers need for the jobs of the queue
+struct Worker {
+    profiles: Arc<RwLock<Vec<Profile>>>,
+    db: Database,
+    queue: Queue,
+    history: History,
+    webhooks: Arc<Webhooks>,
+    notifier: Arc<Notifier>,
+    session_id: String,
+    live: Option<Arc<LiveStatus>>,
+}
+
+impl Worker {
+    /// The configuration and analyzers of the profile whose config file is
+    /// `path`, or of the main one
+    fn profile(&self, path: Option<&Path>) -> (AppConfig, Arc<AnalyzerRegistry>) {
+        let profiles = self.profiles.read().unwrap_or_else(|e| e.into_inner());
+        let profile = path.and_then(|path| profiles.iter().find(|p| p.path == path)).unwrap_or(&profiles[0]);
+        (profile.config.clone(), profile.registry.clone())
     }
 
-    // Store in database
-    let file_id = uuid::Uuid::new_v4().to_string();
-    if let Err(e) = db.insert_file(
-        &file_id,
-        path.to_str().unwrap_or(""),
-        &result.suggested_name,
-        &result.file_hash,
-        result.category.as_deref(),
-        result.confidence,
-        &result.metadata,
-    ) {
-        warn!("Failed to store in database: {}", e);
+    /// Do `job`; an error has it tried again later
+    async fn run(&self, job: Job) -> Result<()> {
+        match job.task {
+            Task::Analyze { ref path, ref profile, ref destination, ref organize_root, dry_run } => {
+                if !path.is_file() {
+                    info!("{:?} is gone, so not analyzed", path);
+                    return Ok(());
+                }
+                let (config, registry) = self.profile(profile.as_deref());
+                if let Some(ref live) = self.live {
+                    live.started(path);
+                }
+                let result = if agent::server(&config).is_some() {
+                    forward_file(path, &config, &self.history, &self.session_id, dry_run).await.map(|()| None)
+                } else {
+                    process_file(
+                        path.clone(),
+                        &config,
+                        &registry,
+                        &self.db,
+                        &self.history,
+                        &self.webhooks,
+                        &self.notifier,
+                        &self.session_id,
+                        dry_run,
+                        destination.as_deref(),
+                        organize_root.as_deref(),
+                    ).await.map(|processed| processed.and_then(|processed| processed.file_id))
+                };
+                if let Some(ref live) = self.live {
+                    live.finished(path, result.is_ok());
+                }
+                match result {
+                    Ok(Some(file_id)) if config.thumbnails.pregenerate && !dry_run && ThumbnailCache::supports(path) => {
+                        if let Err(e) = self.queue.push(&Task::Thumbnail { file_id }, &config.jobs) {
+                            warn!("Failed to queue a thumbnail for {:?}: {}", path, e);
+                        }
+                    }
+                    Ok(_) => {}
+                    // Reported once it is given up on, not on each attempt
+                    Err(e) if job.is_last_attempt() && !dry_run => {
+                        self.webhooks.emit(&config.webhooks, WebhookEvent::Error, webhooks::failed(path, &e.to_string()));
+                        self.notifier.send(&config.notifications, NotificationEvent::Error,
+                            notifications::failed(path, &e.to_string()));
+                        return Err(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+                Ok(())
+            }
+            Task::Thumbnail { ref file_id } => {
+                let Some(file) = self.db.get_file(file_id)? else {
+                    info!("No file {}, so no thumbnail", file_id);
+                    return Ok(());
+                };
+                if file.is_remote() {
+                    return Ok(());
+                }
+                let path = PathBuf::from(&file.new_path);
+                if !ThumbnailCache::supports(&path) {
+                    return Ok(());
+                }
+                let cache = ThumbnailCache::new(&self.profile(None).0.thumbnails);
+                tokio::task::spawn_blocking(move || cache.get_or_create(&path, &file.file_hash))
+                    .await
+                    .map_err(|e| PanoptesError::Analysis(e.to_string()))??;
+                Ok(())
+            }
+            Task::Reprocess { ref file_id, ref model, force } => {
+                let Some(record) = self.db.get_file(file_id)? else {
+                    info!("No file {}, so not reprocessed", file_id);
+                    return Ok(());
+                };
+                if record.is_remote() {
+                    info!("File {} is on another machine, so not reprocessed", file_id);
+                    return Ok(());
+                }
+                let (config, registry) = self.profile(None);
+                let config = config.with_overrides(model.as_deref(), None);
+                let path = PathBuf::from(&record.new_path);
+                let (outcome, line) = reprocess_file(
+                    Some(&record), &path, &config, &registry, &self.db, &self.history, &self.webhooks, &self.session_id,
+                    force, false,
+                ).await?;
+                match outcome {
+                    Reprocessed::Failed | Reprocessed::RenameFailed => Err(PanoptesError::Analysis(line)),
+                    _ => {
+                        info!("Reprocessed {}", line);
+                        Ok(())
+                    }
+                }
+            }
+            Task::Export { ref output } => {
+                let count = export_files(&self.db, output)?;
+                info!("Exported {} files to {:?}", count, output);
+                Ok(())
+            }
+        }
     }
+}
 
-    // Add tags
-    for tag in &result.tags {
-        if let Err(e) = db.add_tag(&file_id, tag, result.category.as_deref()) {
-            debug!("Failed to add tag '{}': {}", tag, e);
+/// Bring the views up to date every `views.refresh_secs` while the database
+/// changes
+async fn refresh_views(profiles: Arc<RwLock<Vec<Profile>>>, db: Database) {
+    let mut built = None;
+    loop {
+        let config = profiles.read().unwrap_or_else(|e| e.into_inner())[0].config.clone();
+        let refresh = config.views.refresh_secs;
+        tokio::time::sleep(Duration::from_secs(if refresh == 0 { 60 } else { refresh })).await;
+        if refresh == 0 || views::root(&config).is_none() {
+            continue;
         }
+        let changes = match db.change_count() {
+            Ok(changes) => changes,
+            Err(e) => {
+                warn!("Failed to check the database for changes: {}", e);
+                continue;
+            }
+        };
+        if built == Some(changes) {
+            continue;
+        }
+        let building = db.clone();
+        match tokio::task::spawn_blocking(move || views::build(&building, &config, false)).await {
+            Ok(Ok(build)) => {
+                if build.linked + build.removed > 0 {
+                    info!("Views: {} linked, {} removed", build.linked, build.removed);
+                }
+            }
+            Ok(Err(e)) => warn!("Failed to build views: {}", e),
+            Err(e) => warn!("Failed to build views: {}", e),
+        }
+        // What building changed doesn't count
+        built = db.change_count().ok();
     }
+}
 
-    // Rename file
-    if result.confidence >= 0.5 {
-        if dry_run {
-            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-            info!("DRY RUN: Would rename {:?} to {}.{}", path, result.suggested_name, ext);
-        } else {
-            rename_file(&path, &result, config, history)?;
+/// Look at each of `sources` every `interval_mins` while watching
+async fn poll_sources(profiles: Arc<RwLock<Vec<Profile>>>, db: Database) {
+    let mut last: std::collections::HashMap<String, Instant> = std::collections::HashMap::new();
+    loop {
+        let (config, registry) = {
+            let profiles = profiles.read().unwrap_or_else(|e| e.into_inner());
+            (profiles[0].config.clone(), profiles[0].registry.clone())
+        };
+        for source in &config.sources {
+            let due = last.get(&source.name)
+                .map_or(true, |at| at.elapsed() >= Duration::from_secs(source.interval_mins * 60));
+            if source.interval_mins == 0 || !due {
+                continue;
+            }
+            last.insert(source.name.clone(), Instant::now());
+            match sources::sync(source, &config, &registry, &db, false).await {
+                Ok(pass) if pass.analyzed + pass.failed > 0 => info!("Source {}: {} analyzed, {} renamed, {} planned, {} failed",
+                    source.name, pass.analyzed, pass.renamed, pass.planned, pass.failed),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to look at source {}: {}", source.name, e),
+            }
         }
-    } else {
-        info!("Confidence too low ({:.0}%), skipping rename", result.confidence * 100.0);
+        tokio::time::sleep(Duration::from_secs(60)).await;
     }
+}
 
-    Ok(())
+/// Does the tasks of `schedule` for watch mode; analysis and reprocessing are
+/// left to the job queue
+#[derive(Clone)]
+struct Scheduler {
+    profiles: Arc<RwLock<Vec<Profile>>>,
+    db: Database,
+    queue: Queue,
+    dry_run: bool,
 }
 
-/// Rename a file with the analysis result
-fn rename_file(
-    original: &Path,
-    result: &AnalysisResult,
-    config: &AppConfig,
-    history: &History,
-) -> Result<()> {
-    let parent = original.parent()
-        .ok_or_else(|| PanoptesError::Config("Cannot determine parent directory".to_string()))?;
+impl Scheduler {
+    /// Queue `path` for analysis by the profile of the innermost watch
+    /// directory it is under; files outside every watch directory are refused,
+    /// as anyone on the MQTT broker or D-Bus may ask
+    fn queue_file(&self, path: &Path) -> Result<i64> {
+        if !path.is_file() {
+            return Err(PanoptesError::Config(format!("No such file: {}", path.display())));
+        }
+        let path = path.canonicalize()?;
+        let profiles = self.profiles.read().unwrap_or_else(|e| e.into_inner());
+        let watched = profiles.iter()
+            .flat_map(|p| p.dirs.iter().map(move |(dir, options)| (p, dir, options)))
+            .filter(|(_, dir, _)| dir.canonicalize().is_ok_and(|real| path.starts_with(real)))
+            .max_by_key(|(_, dir, _)| dir.components().count());
+        let Some((profile, dir, options)) = watched else {
+            return Err(PanoptesError::Config(format!("{} is not in a watch directory", path.display())));
+        };
+        let task = analyze_task(&path, Some(dir), options, &profile.path, self.dry_run);
+        self.queue.push(&task, &profile.config.jobs)
+    }
 
-    let ext = original.extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("");
+    /// Do `task`; returns a line saying what was done
+    fn run(&self, task: &ScheduledTask) -> Result<String> {
+        // Copied, so a reload needn't wait for a long task
+        let (config, watched) = {
+            let profiles = self.profiles.read().unwrap_or_else(|e| e.into_inner());
+            let watched: Vec<(PathBuf, WatchOptions, PathBuf, JobConfig)> = profiles.iter()
+                .flat_map(|p| p.dirs.iter().map(|(dir, options)| (dir.clone(), options.clone(), p.path.clone(), p.config.jobs.clone())))
+                .collect();
+            (profiles[0].config.clone(), watched)
+        };
 
-    let mut final_name = result.suggested_name.clone();
+        match task {
+            ScheduledTask::Rescan => {
+                let dirs: Vec<(PathBuf, WatchOptions)> = watched.iter().map(|(dir, options, ..)| (dir.clone(), options.clone())).collect();
+                let verification = verify::verify(&self.db, &dirs, config.xattrs.enabled)?;
+                let fixed = if self.dry_run { 0 } else { verify::fix(&self.db, &verification.findings)? };
+                // Found files have their real paths
+                let real: Vec<PathBuf> = watched.iter().map(|(dir, ..)| dir.canonicalize().unwrap_or_else(|_| dir.clone())).collect();
+                let mut queued = 0;
+                for finding in &verification.findings {
+                    let Finding::Unknown { path } = finding else { continue };
+                    // In the innermost watch directory it's under
+                    let Some(i) = (0..real.len()).filter(|&i| path.starts_with(&real[i])).max_by_key(|&i| real[i].components().count()) else {
+                        continue;
+                    };
+                    let (dir, options, profile, jobs) = &watched[i];
+                    self.queue.push(&analyze_task(path, Some(dir), options, profile, self.dry_run), jobs)?;
+                    queued += 1;
+                }
+                Ok(format!("{} record(s) fixed, {} file(s) the watcher missed queued", fixed, queued))
+            }
+            ScheduledTask::Maintenance => {
+                self.db.maintain()?;
+                let size = std::fs::metadata(&config.database.path).map(|m| m.len()).unwrap_or(0);
+                Ok(format!("database checked and vacuumed, now {}", HumanBytes(size)))
+            }
+            ScheduledTask::Backup { dir, keep } => {
+                let dir = Path::new(dir);
+                std::fs::create_dir_all(dir)?;
+                let path = dir.join(format!("panoptes-{}.db", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+                self.db.backup_to(&path)?;
+                let removed = remove_old_backups(dir, *keep)?;
+                Ok(format!("backed up to {}, {} old backup(s) removed", path.display(), removed))
+            }
+            ScheduledTask::Report { output } => {
+                let run = self.db.latest_scan_run()?
+                    .ok_or_else(|| PanoptesError::NothingToDo("No analysis runs yet".to_string()))?;
+                let path = PathBuf::from(chrono::Local::now().format(output).to_string());
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let results = self.db.get_scan_results(&run.id)?;
+                std::fs::write(&path, Report::new(&run, &results).render(ReportFormat::for_path(&path)))?;
+                Ok(format!("wrote the report of run {} to {}", &run.id[..8], path.display()))
+            }
+            ScheduledTask::Prune { older_than_days } => {
+                let options = PruneOptions {
+                    records: true,
+                    history: true,
+                    tags: true,
+                    thumbnails: true,
+                    cache: true,
+                    cutoff: chrono::Utc::now() - chrono::Duration::days((*older_than_days).into()),
+                    dry_run: self.dry_run,
+                };
+                let thumbnails = ThumbnailCache::new(&config.thumbnails);
+                let summary = prune::prune(&self.db, Path::new(&config.database.path), &thumbnails, &options)?;
+                Ok(format!("removed {} records, history entries, tags and thumbnails, {}",
+                    summary.removed(), HumanBytes(summary.reclaimed_bytes())))
+            }
+            ScheduledTask::Reprocess { max_confidence, limit, model, force } => {
+                let filter = FileFilter {
+                    max_confidence: Some(*max_confidence),
+                    sort: FileSort::Confidence,
+                    ascending: true,
+                    ..Default::default()
+                };
+                let (files, _) = self.db.query_files(&filter, *limit, 0)?;
+                for file in &files {
+                    self.queue.push(&Task::Reprocess { file_id: file.id.clone(), model: model.clone(), force: *force }, &config.jobs)?;
+                }
+                Ok(format!("queued {} file(s) for reprocessing", files.len()))
+            }
+        }
+    }
+}
 
-    if config.rules.date_prefix {
-        let date = Local::now().format("%Y-%m-%d").to_string();
-        final_name = format!("{}_{}", date, final_name);
+/// Remove all but the latest `keep` backups from `dir`; returns how many went
+fn remove_old_backups(dir: &Path, keep: usize) -> Result<usize> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("panoptes-") && n.ends_with(".db")))
+        .collect();
+    // Named by time, so in order
+    backups.sort();
+    let old = backups.len().saturating_sub(keep);
+    for path in &backups[..old] {
+        std::fs::remove_file(path)?;
     }
+    Ok(old)
+}
 
-    // Truncate to max length
-    if final_name.len() > config.rules.max_length {
-        final_name.truncate(config.rules.max_length);
-        final_name = final_name.trim_end_matches('_').to_string();
+/// When the config file, or one it is layered with or includes, was last
+/// changed, if it can be read
+fn config_modified_time(path: &Path) -> Option<std::time::SystemTime> {
+    let files = layers::files(path).unwrap_or_else(|_| vec![path.to_path_buf()]);
+    files.iter().filter_map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok()).max()
+}
+
+/// Record what a batch analysis did with a file, for its report
+fn record_scan_result(db: &Database, run_id: &str, result: ScanResult) {
+    if let Err(e) = db.record_scan_result(run_id, &result) {
+        warn!("Failed to record scan result: {}", e);
     }
+}
 
-    let new_path = parent.join(format!("{}.{}", final_name, ext));
+/// Progress bar being drawn, which log output has to make way for
+static ACTIVE_PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
 
-    // Handle filename collision
-    let new_path = if new_path.exists() {
-        let timestamp = Local::now().format("%H%M%S").to_string();
-        parent.join(format!("{}_{}.{}", final_name, timestamp, ext))
-    } else {
-        new_path
-    };
+/// Log output that suspends the active progress bar while writing, so log
+/// lines don't tear it
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(written) = daemon::write_log(buf) {
+            return written;
+        }
+        let bar = ACTIVE_PROGRESS.lock().ok().and_then(|active| active.clone());
+        match bar {
+            Some(bar) => bar.suspend(|| std::io::stdout().write(buf)),
+            None => std::io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
 
-    // Write history entry
-    let entry = create_entry(
-        uuid::Uuid::new_v4().to_string(),
-        original.to_path_buf(),
-        new_path.clone(),
-        result.suggested_name.clone(),
-        result.category.clone(),
-        result.tags.clone(),
-        result.file_hash.clone(),
+/// Progress bar for a batch of `len` files on stderr; hidden when `hidden`
+/// or when stderr isn't a terminal
+fn batch_progress(len: usize, hidden: bool) -> ProgressBar {
+    if hidden || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len as u64);
+    bar.set_style(
+        ProgressStyle::with_template("{spinner} [{elapsed_precise}] {wide_bar} {pos}/{len} ETA {eta} {msg}")
+            .expect("progress template is valid"),
     );
-    history.append(&entry)?;
+    bar.enable_steady_tick(Duration::from_millis(200));
+    if let Ok(mut active) = ACTIVE_PROGRESS.lock() {
+        *active = Some(bar.clone());
+    }
+    bar
+}
+
+/// Print a line above the progress bar (or plainly when there is none)
+fn progress_println(bar: &ProgressBar, line: String) {
+    if bar.is_hidden() {
+        println!("{}", line);
+    } else {
+        bar.println(line);
+    }
+}
 
-    // Perform rename
-    std::fs::rename(original, &new_path)?;
-    info!("Renamed to: {:?}", new_path);
+/// Remove the progress bar and stop routing log output around it
+fn finish_progress(bar: &ProgressBar) {
+    bar.finish_and_clear();
+    if let Ok(mut active) = ACTIVE_PROGRESS.lock() {
+        *active = None;
+    }
+}
 
-    Ok(())
+/// The file name `file` gets for `result`, with its extension
+fn proposed_name(result: &AnalysisResult, file: &Path, config: &AppConfig) -> String {
+    match file.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}", final_name(result, file, config), ext),
+        None => final_name(result, file, config),
+    }
 }
 
-/// Run single file/directory analysis
+/// Run single file/directory analysis. Up to `jobs` files are analyzed at
+/// once; results are applied and reported in file order. Each processed file
+/// is checkpointed under the run, so a `resumed` run skips those. When
+/// `interactive`, each rename is asked about first.
+#[allow(clippy::too_many_arguments)]
 async fn run_analyze(
-    config: AppConfig,
-    path: PathBuf,
-    dry_run: bool,
-    recursive: bool,
-    min_confidence: f64,
+    mut config: AppConfig,
+    db: Database,
+    run: ScanRun,
+    resumed: bool,
+    jobs: usize,
+    interactive: bool,
     format: &str,
+    quiet: bool,
 ) -> Result<()> {
-    let registry = AnalyzerRegistry::new(&config);
-    let history = History::new(PathBuf::from("panoptes_history.jsonl"));
-
-    let files: Vec<PathBuf> = if path.is_dir() {
-        if recursive {
-            walkdir(&path)
-        } else {
-            std::fs::read_dir(&path)?
-                .filter_map(|e| e.ok())
-                .map(|e| e.path())
-                .filter(|p| p.is_file())
-                .collect()
+    if interactive && !std::io::stdin().is_terminal() {
+        return Err(PanoptesError::Config("Cannot ask about renames without a terminal; pass --yes to rename without asking".to_string()));
+    }
+    // The run's minimum stands in for rules.suggest_threshold
+    config.rules.suggest_threshold = run.min_confidence;
+    let config = Arc::new(config);
+    let registry = Arc::new(AnalyzerRegistry::new(&config));
+    registry.load_corrections(&db);
+    let history = open_history(&db)?;
+    let webhooks = Webhooks::new(db.clone());
+    // Only rules notify from a batch, rather than each rename
+    let notifier = Notifier::new();
+    // Renames of every attempt at the run form one session
+    let session_id = run.id.clone();
+    let (path, dry_run, recursive, min_confidence) = (PathBuf::from(&run.path), run.dry_run, run.recursive, run.min_confidence);
+    let selector = Selector::new(&run.selection)?;
+    let processed = Arc::new(if resumed { db.get_scanned_hashes(&run.id)? } else { HashSet::new() });
+    let started = Instant::now();
+    let mut analysis_time = Duration::ZERO;
+    let (mut renamed, mut queued, mut skipped, mut failed, mut rename_failures, mut already) = (0, 0, 0, 0, 0, 0);
+    // Whether to ask about the next rename, and whether the rest were skipped
+    let (mut ask, mut stopped) = (interactive, false);
+
+    let mut files: Vec<PathBuf> = if let Some(list) = &run.files {
+        // Listed files are taken in the order given; ones renamed by an
+        // earlier attempt at the run are gone, which is expected
+        let mut seen = HashSet::new();
+        list.iter()
+            .map(PathBuf::from)
+            .filter(|file| {
+                if !file.is_file() {
+                    if !resumed {
+                        warn!("Skipping {}: not a file", file.display());
+                    }
+                    return false;
+                }
+                seen.insert(file.clone())
+            })
+            .collect()
+    } else if path.is_dir() {
+        let mut files = select_files(&path, recursive, &selector);
+        // Files waiting for review stay put unless their quarantine is what's being analyzed
+        if !is_quarantine_dir(&path, &config) {
+            files.retain(|file| !is_quarantined(file, &config));
+        }
+        if !vault::contains(&path, &config) {
+            files.retain(|file| !vault::contains(file, &config));
         }
+        files.retain(|file| !views::contains(file, &config));
+        files.sort();
+        files
     } else {
         vec![path]
     };
+    files.retain(|file| should_process(file) && selector.matches(file, 0) && registry.find_analyzer(file).is_some());
 
+    let text = format == "text";
+    if text {
+        let verb = if resumed { "Resuming" } else { "Starting" };
+        eprintln!("{} run {} (resume with `panoptes analyze --resume {}`)", verb, run.id, &run.id[..8]);
+    }
+    let progress = batch_progress(files.len(), quiet || !text || interactive);
     let mut results = Vec::new();
 
-    for file in files {
-        if !should_process(&file) {
+    // Each analysis runs as its own task; `buffered` keeps them in file order
+    let mut analyses = stream::iter(files)
+        .map(|file| {
+            let registry = registry.clone();
+            let config = config.clone();
+            let processed = processed.clone();
+            let db = db.clone();
+            let span = info_span!("file", cid = %telemetry::correlation_id(), path = %file.display());
+            tokio::spawn(async move {
+                // Renamed files are still recognised by their contents
+                if !processed.is_empty() && calculate_file_hash(&file).is_ok_and(|hash| processed.contains(&hash)) {
+                    return (file, None, Duration::ZERO);
+                }
+                let started = Instant::now();
+                let analysis = match registry.find_analyzer(&file) {
+                    Some(analyzer) => match duplicates::check(&db, &file, &config) {
+                        Ok(Some(copy)) => Ok(copy),
+                        Ok(None) => analyzer.analyze(&file, &config).await,
+                        Err(e) => Err(e),
+                    },
+                    None => Err(PanoptesError::UnsupportedFileType(file.display().to_string())),
+                };
+                (file, Some(analysis), started.elapsed())
+            }.instrument(span))
+        })
+        .buffered(jobs.max(1));
+
+    while let Some(joined) = analyses.next().await {
+        let (file, analysis, elapsed) = joined
+            .map_err(|e| PanoptesError::Analysis(format!("Analysis task failed: {}", e)))?;
+        analysis_time += elapsed;
+        progress.set_message(file.file_name().unwrap_or_default().to_string_lossy().into_owned());
+
+        let Some(analysis) = analysis else {
+            already += 1;
+            progress.inc(1);
             continue;
-        }
+        };
+        let mut result = match analysis {
+            Ok(result) => result,
+            Err(e) => {
+                if text {
+                    progress.suspend(|| eprintln!("Error analyzing {}: {}", file.display(), e));
+                }
+                if !dry_run {
+                    webhooks.emit(&config.webhooks, WebhookEvent::Error, webhooks::failed(&file, &e.to_string()));
+                }
+                record_scan_result(&db, &run.id, ScanResult {
+                    path: file.to_string_lossy().into_owned(),
+                    outcome: ScanOutcome::Failed,
+                    suggested_name: None,
+                    new_path: None,
+                    confidence: None,
+                    category: None,
+                    error: Some(e.to_string()),
+                    elapsed_ms: elapsed.as_millis() as u64,
+                });
+                failed += 1;
+                progress.inc(1);
+                continue;
+            }
+        };
 
-        if let Some(analyzer) = registry.find_analyzer(&file) {
-            match analyzer.analyze(&file, &config).await {
-                Ok(result) => {
-                    if result.confidence >= min_confidence {
-                        if format == "text" {
-                            println!("{}: {} ({:.0}%)",
-                                file.display(),
-                                result.suggested_name,
-                                result.confidence * 100.0
-                            );
+        // Files are renamed where they are, unless a rule moves them
+        let plan = rules::plan(&file, &mut result, &config, |result| duplicates::destination(result, &config));
+        let suggestion = format!("{} ({:.0}%)", result.suggested_name, result.confidence * 100.0);
+        let mut failed_rename = false;
+        let mut scan_result = ScanResult {
+            path: file.to_string_lossy().into_owned(),
+            outcome: ScanOutcome::Skip,
+            suggested_name: Some(proposed_name(&result, &file, &config)),
+            new_path: None,
+            confidence: Some(result.confidence),
+            category: result.category.clone(),
+            error: None,
+            elapsed_ms: elapsed.as_millis() as u64,
+        };
+        let status = if result.confidence < min_confidence {
+            skipped += 1;
+            scan_result.outcome = ScanOutcome::BelowMinimum;
+            "below minimum confidence".to_string()
+        } else if dry_run {
+            let status = match plan.disposition {
+                Disposition::Apply => {
+                    renamed += 1;
+                    scan_result.outcome = ScanOutcome::Rename;
+                    match plan.destination {
+                        Some(ref dir) => format!("would move to {}", dir.display()),
+                        None => "would rename".to_string(),
+                    }
+                }
+                Disposition::Review => {
+                    queued += 1;
+                    scan_result.outcome = ScanOutcome::Review;
+                    "would queue for review".to_string()
+                }
+                Disposition::Skip => {
+                    skipped += 1;
+                    "would skip".to_string()
+                }
+            };
+            match plan.decided_by {
+                Some(ref rule) => format!("{} (rule {})", status, rule),
+                None => status,
+            }
+        } else {
+            let file_id = record_analysis(&db, &file, &result);
+            duplicates::link_or_warn(&file, &result, &config);
+            sidecar::write_or_warn(&file, &result, file_id.as_deref(), &config);
+            if let Some(id) = &file_id {
+                xattrs::tag_or_warn(&db, id, &config);
+            }
+            let mut choice = RenameChoice::Yes;
+            if ask && plan.disposition == Disposition::Apply {
+                choice = loop {
+                    let Some(proposed) = suggested_path(&result, &file, &file, &config)? else {
+                        break RenameChoice::No;
+                    };
+                    match prompt_rename(&file, &proposed, &config.rules.sanitizer)? {
+                        RenameChoice::Edit(name) => {
+                            if let Some(id) = &file_id {
+                                feedback::record_or_warn(&db, id, &name, CorrectionSource::Prompt);
+                            }
+                            result.suggested_name = name;
+                            scan_result.suggested_name = Some(proposed_name(&result, &file, &config));
                         }
-
-                        if !dry_run && result.confidence >= 0.5 {
-                            rename_file(&file, &result, &config, &history)?;
+                        RenameChoice::AcceptAll => {
+                            ask = false;
+                            break RenameChoice::Yes;
                         }
-
-                        results.push((file, result));
+                        choice => break choice,
                     }
+                };
+            }
+            let mut now_at = file.clone();
+            let status = match plan.disposition {
+                Disposition::Apply if choice == RenameChoice::SkipAll => {
+                    stopped = true;
+                    skipped += 1;
+                    "skipped, and the rest with it".to_string()
                 }
-                Err(e) => {
-                    if format == "text" {
-                        eprintln!("Error analyzing {}: {}", file.display(), e);
+                Disposition::Apply if choice == RenameChoice::No => {
+                    skipped += 1;
+                    "skipped".to_string()
+                }
+                Disposition::Apply => {
+                    match rename_file(&file, plan.destination.as_deref(), &result, &config, &history, Some(&session_id), file_id.as_deref()) {
+                        Ok(new_path) => {
+                            webhooks.emit(&config.webhooks, WebhookEvent::Renamed,
+                                webhooks::renamed(file_id.as_deref(), &file, &new_path, &result));
+                            renamed += 1;
+                            scan_result.outcome = ScanOutcome::Rename;
+                            scan_result.new_path = Some(new_path.to_string_lossy().into_owned());
+                            let status = match plan.destination {
+                                Some(_) => format!("moved to {}", new_path.display()),
+                                None => format!("renamed to {}", new_path.file_name().unwrap_or_default().to_string_lossy()),
+                            };
+                            now_at = new_path;
+                            status
+                        }
+                        Err(e) => {
+                            webhooks.emit(&config.webhooks, WebhookEvent::Error, webhooks::failed(&file, &e.to_string()));
+                            failed += 1;
+                            rename_failures += 1;
+                            failed_rename = true;
+                            scan_result.outcome = ScanOutcome::Failed;
+                            scan_result.error = Some(format!("Rename failed: {}", e));
+                            format!("rename failed: {}", e)
+                        }
                     }
                 }
+                Disposition::Review => {
+                    let moved = send_to_review(&file, &result, file_id.as_deref(), &config, &db, &history, &webhooks, Some(&session_id));
+                    queued += 1;
+                    scan_result.outcome = ScanOutcome::Review;
+                    match moved {
+                        Some(to) => {
+                            scan_result.new_path = Some(to.to_string_lossy().into_owned());
+                            let status = format!("queued for review in {}", to.parent().unwrap_or(&to).display());
+                            now_at = to;
+                            status
+                        }
+                        None => "queued for review".to_string(),
+                    }
+                }
+                Disposition::Skip if plan.decided_by.is_some() => {
+                    skipped += 1;
+                    "skipped".to_string()
+                }
+                Disposition::Skip => {
+                    webhooks.emit(&config.webhooks, WebhookEvent::LowConfidence,
+                        webhooks::low_confidence(file_id.as_deref(), &file, &result, false));
+                    skipped += 1;
+                    "skipped, low confidence".to_string()
+                }
+            };
+            follow_up(&plan, &file, &now_at, &result, file_id.as_deref(), &config, &webhooks, &notifier).await;
+            match plan.decided_by {
+                Some(ref rule) => format!("{} (rule {})", status, rule),
+                None => status,
+            }
+        };
+
+        if text {
+            progress_println(&progress, format!("{}: {} - {}", file.display(), suggestion, status));
+        }
+        record_scan_result(&db, &run.id, scan_result);
+        // Failures aren't checkpointed, so resuming retries them
+        if !failed_rename {
+            if let Err(e) = db.mark_scanned(&run.id, &result.file_hash) {
+                warn!("Failed to record scan progress: {}", e);
             }
         }
+        if result.confidence >= min_confidence {
+            results.push((file, result));
+        }
+        progress.inc(1);
+        if stopped {
+            break;
+        }
     }
+    finish_progress(&progress);
 
     // Output results in requested format
     match format {
@@ -668,140 +2703,1620 @@ async fn run_analyze(
         _ => {}
     }
 
-    if !results.is_empty() && format == "text" {
-        println!("\nAnalyzed {} files", results.len());
+    webhooks.flush().await;
+    notifier.flush().await;
+
+    // A run stopped at a prompt is left to be resumed
+    if failed == 0 && !stopped {
+        db.finish_scan_run(&run.id)?;
+    }
+
+    let total = renamed + queued + skipped + failed;
+    if (total > 0 || already > 0) && text {
+        let dry = if dry_run { " (dry run)" } else { "" };
+        println!("\nAnalyzed {} files in {}{}", total, HumanDuration(started.elapsed()), dry);
+        if resumed {
+            println!("  {:<10} {:>6}", "Done before", already);
+        }
+        println!("  {:<10} {:>6}", "Renamed", renamed);
+        println!("  {:<10} {:>6}", "Queued", queued);
+        println!("  {:<10} {:>6}", "Skipped", skipped);
+        println!("  {:<10} {:>6}", "Failed", failed);
+        println!("  {:<10} {:>6.1}s", "LLM time", analysis_time.as_secs_f64());
+        if renamed > 0 && !dry_run {
+            println!("Session: {} (undo with `panoptes history undo --session {}`)", session_id, &session_id[..8]);
+        }
+        if queued > 0 && !dry_run {
+            println!("{} suggestion(s) queued for review in the web UI", queued);
+        }
+        if stopped {
+            println!("Stopped before the remaining files (resume with `panoptes analyze --resume {}`)", &run.id[..8]);
+        }
     }
 
+    if rename_failures > 0 {
+        return Err(PanoptesError::Partial(format!("{} file(s) could not be renamed", rename_failures)));
+    }
+    if failed > 0 {
+        return Err(PanoptesError::Partial(format!("{} of {} file(s) could not be analyzed", failed, total)));
+    }
+    if total == 0 {
+        let message = if already > 0 { "Every file was already analyzed" } else { "No files to analyze" };
+        return Err(PanoptesError::NothingToDo(message.to_string()));
+    }
     Ok(())
 }
 
-/// Walk directory recursively
-fn walkdir(path: &Path) -> Vec<PathBuf> {
-    let mut files = Vec::new();
+/// Analyze the directory `dir` as a whole and rename it, as `analyze --as-dir`
+/// does; directories too unsure of a name are left as they are, having no
+/// record to review
+async fn run_analyze_dir(config: AppConfig, dir: &Path, dry_run: bool, min_confidence: Option<f64>, format: &str) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(PanoptesError::Config(format!("Not a directory: {}", dir.display())));
+    }
+    let db = Database::open(&config.database.path)?;
+    let history = open_history(&db)?;
+    let registry = AnalyzerRegistry::new(&config);
+    registry.load_corrections(&db);
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let dir = dir.canonicalize()?;
+
+    let result = folder::analyze(&dir, &config, &registry).await?;
+    let mut new_path = None;
+    let status = if result.confidence < min_confidence.unwrap_or(config.rules.suggest_threshold) {
+        "below minimum confidence".to_string()
+    } else {
+        match disposition(result.confidence, &config) {
+            Disposition::Apply if dry_run => match suggested_path(&result, &dir, &dir, &config)? {
+                Some(to) => format!("would rename to {}", to.file_name().unwrap_or_default().to_string_lossy()),
+                None => "would leave it, the name is taken".to_string(),
+            },
+            Disposition::Apply => {
+                let to = folder::rename(&dir, &result, &config, &history, Some(&session_id))?;
+                let status = if to == dir {
+                    "left as it is".to_string()
+                } else {
+                    format!("renamed to {}", to.file_name().unwrap_or_default().to_string_lossy())
+                };
+                new_path = (to != dir).then_some(to);
+                status
+            }
+            Disposition::Review | Disposition::Skip => "left as it is, low confidence".to_string(),
+        }
+    };
 
-    if let Ok(entries) = std::fs::read_dir(path) {
-        for entry in entries.flatten() {
-            let p = entry.path();
-            if p.is_dir() {
-                files.extend(walkdir(&p));
-            } else if p.is_file() {
-                files.push(p);
+    if format == "json" || format == "jsonl" {
+        let output = serde_json::json!({
+            "path": dir.to_string_lossy(),
+            "suggested_name": result.suggested_name,
+            "confidence": result.confidence,
+            "category": result.category,
+            "tags": result.tags,
+            "new_path": new_path.as_ref().map(|path| path.to_string_lossy()),
+            "metadata": result.metadata,
+        });
+        println!("{}", if format == "json" { serde_json::to_string_pretty(&output)? } else { serde_json::to_string(&output)? });
+    } else {
+        println!("{}: {} ({:.0}%) - {}", dir.display(), result.suggested_name, result.confidence * 100.0, status);
+        if let Some(category) = &result.category {
+            println!("  {:<10} {}", "Category", category);
+        }
+        println!("  {:<10} {}", "Tags", result.tags.join(", "));
+        let count = |key: &str| result.metadata[key].as_u64().unwrap_or(0);
+        println!("  {:<10} {} of {} files", "Sampled", count("analyzed"), count("file_count"));
+        if new_path.is_some() {
+            println!("Session: {} (undo with `panoptes history undo --session {}`)", session_id, &session_id[..8]);
+        }
+    }
+    Ok(())
+}
+
+/// Analyze files again: recorded ones matching `filter` or, without one, those
+/// whose batch analysis failed. A record is only replaced by a more confident
+/// analysis unless `force`; replaced ones are renamed or queued for review as
+/// a new analysis would be.
+async fn run_reprocess(config: AppConfig, filter: Option<FileFilter>, limit: usize, force: bool, dry_run: bool) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    let history = open_history(&db)?;
+    let registry = AnalyzerRegistry::new(&config);
+    registry.load_corrections(&db);
+    let webhooks = Webhooks::new(db.clone());
+    let session_id = uuid::Uuid::new_v4().to_string();
+
+    let targets: Vec<(Option<FileRecord>, PathBuf)> = match filter {
+        Some(filter) => db.query_files(&filter, limit, 0)?.0.into_iter()
+            .map(|file| {
+                let path = PathBuf::from(&file.new_path);
+                (Some(file), path)
+            })
+            .collect(),
+        None => db.get_failed_scan_paths(limit)?.into_iter().map(|path| (None, PathBuf::from(path))).collect(),
+    };
+    if targets.is_empty() {
+        return Err(PanoptesError::NothingToDo("No files to reprocess".to_string()));
+    }
+
+    let (mut improved, mut renamed, mut kept, mut missing, mut failed) = (0, 0, 0, 0, 0);
+    for (record, path) in targets {
+        let (outcome, line) = reprocess_file(
+            record.as_ref(), &path, &config, &registry, &db, &history, &webhooks, &session_id, force, dry_run,
+        ).await?;
+        match outcome {
+            Reprocessed::Missing => missing += 1,
+            Reprocessed::Failed => failed += 1,
+            Reprocessed::Kept => kept += 1,
+            Reprocessed::Updated => improved += 1,
+            Reprocessed::Renamed => {
+                improved += 1;
+                renamed += 1;
+            }
+            Reprocessed::RenameFailed => {
+                improved += 1;
+                failed += 1;
+            }
+        }
+        println!("{}", line);
+    }
+    webhooks.flush().await;
+
+    let dry = if dry_run { " (dry run)" } else { "" };
+    println!("\nReprocessed {} files{}", improved + kept + failed, dry);
+    println!("  {:<10} {:>6}", "Improved", improved);
+    println!("  {:<10} {:>6}", "Renamed", renamed);
+    println!("  {:<10} {:>6}", "Kept", kept);
+    println!("  {:<10} {:>6}", "Missing", missing);
+    println!("  {:<10} {:>6}", "Failed", failed);
+    if renamed > 0 {
+        println!("Session: {} (undo with `panoptes history undo --session {}`)", session_id, &session_id[..8]);
+    }
+    if failed > 0 {
+        return Err(PanoptesError::Partial(format!("{} file(s) could not be reprocessed", failed)));
+    }
+    Ok(())
+}
+
+/// Queue the recorded files matching `filter` for the watcher to reprocess
+fn queue_reprocess(config: &AppConfig, filter: &FileFilter, limit: usize, model: Option<String>, force: bool) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    let (files, _) = db.query_files(filter, limit, 0)?;
+    if files.is_empty() {
+        return Err(PanoptesError::NothingToDo("No files to reprocess".to_string()));
+    }
+    let queue = Queue::new(db);
+    for file in &files {
+        queue.push(&Task::Reprocess { file_id: file.id.clone(), model: model.clone(), force }, &config.jobs)?;
+    }
+    println!("Queued {} files for reprocessing; `panoptes watch` or `serve` works through them", files.len());
+    println!("Follow them with `panoptes jobs list --state queued`");
+    Ok(())
+}
+
+/// What reprocessing a file came to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reprocessed {
+    Missing,
+    /// The analysis failed
+    Failed,
+    /// Not more confident, so the record was left alone
+    Kept,
+    /// The record was replaced, the file left in place or queued for review
+    Updated,
+    Renamed,
+    /// The record was replaced, but the file couldn't be renamed
+    RenameFailed,
+}
+
+/// Analyze the file at `path`, recorded as `record` if it is, again, as
+/// `panoptes reprocess` does; returns what came of it and a line saying so
+#[allow(clippy::too_many_arguments)]
+async fn reprocess_file(
+    record: Option<&FileRecord>,
+    path: &Path,
+    config: &AppConfig,
+    registry: &AnalyzerRegistry,
+    db: &Database,
+    history: &History,
+    webhooks: &Webhooks,
+    session_id: &str,
+    force: bool,
+    dry_run: bool,
+) -> Result<(Reprocessed, String)> {
+    if !path.is_file() {
+        return Ok((Reprocessed::Missing, format!("{}: no longer exists", path.display())));
+    }
+    let analysis = match registry.find_analyzer(path) {
+        Some(analyzer) => analyzer.analyze(path, config).await,
+        None => Err(PanoptesError::UnsupportedFileType(path.display().to_string())),
+    };
+    let result = match analysis {
+        Ok(result) => result,
+        Err(e) => return Ok((Reprocessed::Failed, format!("{}: analysis failed: {}", path.display(), e))),
+    };
+
+    let was = record
+        .map(|r| format!(" (was {}, {:.0}%)", r.suggested_name, r.confidence * 100.0))
+        .unwrap_or_default();
+    let suggestion = format!("{} ({:.0}%){}", result.suggested_name, result.confidence * 100.0, was);
+    if !force && record.is_some_and(|r| result.confidence <= r.confidence) {
+        return Ok((Reprocessed::Kept, format!("{}: {} - kept, not more confident", path.display(), suggestion)));
+    }
+
+    // A file already carrying the suggested name, or at its vault address, stays where it is
+    let named = path.file_stem().and_then(|s| s.to_str()) == Some(final_name(&result, path, config).as_str())
+        || (vault::contains(path, config) && vault::is_address(path, &result.file_hash));
+    let mut outcome = Reprocessed::Updated;
+    let status = if dry_run {
+        match disposition(result.confidence, config) {
+            Disposition::Apply if named => "would update, already named",
+            Disposition::Apply => "would rename",
+            Disposition::Review => "would queue for review",
+            Disposition::Skip => "would update",
+        }.to_string()
+    } else {
+        let file_id = match record {
+            Some(record) => {
+                db.update_analysis(&record.id, &result)?;
+                Some(record.id.clone())
+            }
+            None => record_analysis(db, path, &result),
+        };
+        sidecar::write_or_warn(path, &result, file_id.as_deref(), config);
+        if let Some(id) = &file_id {
+            xattrs::tag_or_warn(db, id, config);
+        }
+        match disposition(result.confidence, config) {
+            Disposition::Apply if named => "updated, already named".to_string(),
+            Disposition::Apply => {
+                // Confident enough now to leave quarantine
+                let destination = record
+                    .filter(|_| is_quarantined(path, config))
+                    .map(|r| release_dir(Path::new(&r.original_path), config));
+                match rename_file(path, destination.as_deref(), &result, config, history, Some(session_id), file_id.as_deref()) {
+                    Ok(new_path) => {
+                        webhooks.emit(&config.webhooks, WebhookEvent::Renamed,
+                            webhooks::renamed(file_id.as_deref(), path, &new_path, &result));
+                        // Confident enough now to settle a pending review
+                        if record.is_some_and(|r| r.status == Some(ReviewStatus::Pending)) {
+                            if let Some(id) = &file_id {
+                                db.set_review_status(id, ReviewStatus::Approved)?;
+                            }
+                        }
+                        outcome = Reprocessed::Renamed;
+                        format!("renamed to {}", new_path.file_name().unwrap_or_default().to_string_lossy())
+                    }
+                    Err(e) => {
+                        outcome = Reprocessed::RenameFailed;
+                        format!("rename failed: {}", e)
+                    }
+                }
+            }
+            Disposition::Review => {
+                match send_to_review(path, &result, file_id.as_deref(), config, db, history, webhooks, Some(session_id)) {
+                    Some(to) => format!("queued for review in {}", to.parent().unwrap_or(&to).display()),
+                    None => "queued for review".to_string(),
+                }
+            }
+            Disposition::Skip => "updated".to_string(),
+        }
+    };
+    Ok((outcome, format!("{}: {} - {}", path.display(), suggestion, status)))
+}
+
+/// Check the database against the watch directories, fixing what can be fixed
+/// with `fix`. Fails when inconsistencies remain.
+/// List the recorded files like `file`
+async fn run_similar(config: &AppConfig, file: &Path, format: &str) -> Result<()> {
+    if !file.is_file() {
+        return Err(PanoptesError::Config(format!("No such file: {}", file.display())));
+    }
+    let db = Database::open(&config.database.path)?;
+    let matches = similarity::similar_to_path(&db, file, config).await?;
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&matches)?),
+        "jsonl" => {
+            for m in &matches {
+                println!("{}", serde_json::to_string(m)?);
+            }
+        }
+        _ => {
+            if matches.is_empty() {
+                println!("No recorded files like {}", file.display());
+            }
+            for m in &matches {
+                println!("  {:>3.0}%  {:<12} {}", m.score * 100.0, m.likeness.as_str(), m.file.new_path);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_verify(config: AppConfig, fix: bool, format: &str) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    let dirs = watch_dirs(&config, &[], &WatchOptions::default());
+    let verification = verify::verify(&db, &dirs, config.xattrs.enabled)?;
+    let fixed = if fix { verify::fix(&db, &verification.findings)? } else { 0 };
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+            "records": verification.records,
+            "files": verification.files,
+            "already_missing": verification.already_missing,
+            "findings": verification.findings,
+            "fixed": fixed,
+        }))?),
+        "jsonl" => {
+            for finding in &verification.findings {
+                println!("{}", serde_json::to_string(finding)?);
+            }
+        }
+        _ => {
+            println!("Checked {} records and {} files in watch directories", verification.records, verification.files);
+            for finding in &verification.findings {
+                match finding {
+                    Finding::Unknown { path } => println!("  unknown    {}", path.display()),
+                    Finding::Missing { path, .. } => println!("  missing    {}", path),
+                    Finding::Moved { from, to, .. } => println!("  moved      {} -> {}", from, to.display()),
+                    Finding::Changed { path, .. } => println!("  changed    {}", path),
+                    Finding::Reappeared { path, .. } => println!("  back       {}", path),
+                    Finding::Untagged { path, .. } => println!("  untagged   {}", path),
+                    Finding::Retagged { path, added, removed, .. } => {
+                        let changes: Vec<String> = added.iter().map(|t| format!("+{}", t))
+                            .chain(removed.iter().map(|t| format!("-{}", t)))
+                            .collect();
+                        println!("  retagged   {} ({})", path, changes.join(" "));
+                    }
+                }
+            }
+            if verification.already_missing > 0 {
+                println!("{} record(s) already marked missing", verification.already_missing);
+            }
+            if verification.findings.iter().any(|f| matches!(f, Finding::Unknown { .. })) {
+                println!("Unknown files can be added with `panoptes analyze`");
+            }
+            if verification.findings.iter().any(|f| matches!(f, Finding::Changed { .. })) {
+                println!("Changed files can be analyzed again with `panoptes reprocess`");
+            }
+            if fix {
+                println!("Fixed {} record(s)", fixed);
+            } else if verification.findings.iter().any(Finding::fixable) {
+                println!("Run with --fix to re-link moved files, mark missing ones and settle tags");
+            }
+        }
+    }
+
+    let remaining = verification.findings.iter().filter(|f| !fix || !f.fixable()).count();
+    if remaining > 0 {
+        return Err(PanoptesError::Config(format!("{} inconsistencies found", remaining)));
+    }
+    Ok(())
+}
+
+/// Prune as `options` say and print what went
+fn run_prune(config: AppConfig, options: &PruneOptions, format: &str, yes: bool) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    let thumbnails = ThumbnailCache::new(&config.thumbnails);
+    let database_path = Path::new(&config.database.path);
+
+    // Show what would go and ask first, unless told not to
+    let summary = if options.dry_run || !yes {
+        let preview = prune::prune(&db, database_path, &thumbnails, &PruneOptions { dry_run: true, ..options.clone() })?;
+        if options.dry_run || preview.removed() == 0 {
+            preview
+        } else {
+            if format == "text" {
+                print_prune_summary(&preview, options);
+            }
+            if !confirm("Remove these", yes)? {
+                return Err(PanoptesError::NothingToDo("Nothing removed".to_string()));
+            }
+            prune::prune(&db, database_path, &thumbnails, options)?
+        }
+    } else {
+        prune::prune(&db, database_path, &thumbnails, options)?
+    };
+
+    if format == "json" || format == "jsonl" {
+        println!("{}", serde_json::to_string(&summary)?);
+    } else if summary.removed() > 0 {
+        print_prune_summary(&summary, options);
+    }
+    if summary.removed() == 0 {
+        return Err(PanoptesError::NothingToDo("Nothing to prune".to_string()));
+    }
+    Ok(())
+}
+
+fn print_prune_summary(summary: &prune::PruneSummary, options: &PruneOptions) {
+    println!("{}:", if summary.dry_run { "Would remove" } else { "Removed" });
+    let rows = [
+        (options.records, "records", summary.records),
+        (options.history, "history entries", summary.history),
+        (options.tags, "tags", summary.tags),
+        (options.thumbnails, "stale thumbnails", summary.thumbnails),
+        (options.cache, "expired cache entries", summary.cache),
+    ];
+    for (_, label, count) in rows.iter().filter(|(picked, _, _)| *picked) {
+        println!("  {:<22} {}", label, count);
+    }
+
+    if summary.dry_run {
+        println!("Thumbnails would free {}; the database shrinks once vacuumed", HumanBytes(summary.thumbnail_bytes));
+    } else {
+        println!(
+            "Reclaimed {} ({} thumbnails, {} database)",
+            HumanBytes(summary.reclaimed_bytes()),
+            HumanBytes(summary.thumbnail_bytes),
+            HumanBytes(summary.database_bytes),
+        );
+    }
+}
+
+/// Render the report for a batch analysis (the latest without `run_id`) to
+/// `output`, or as Markdown to stdout
+fn run_report(db: &Database, run_id: Option<&str>, output: Option<&Path>) -> Result<()> {
+    let run = match run_id {
+        Some(id) => db.find_scan_run(id)?
+            .ok_or_else(|| PanoptesError::Config(format!("No analysis run '{}'", id)))?,
+        None => db.latest_scan_run()?
+            .ok_or_else(|| PanoptesError::Config("No analysis runs yet".to_string()))?,
+    };
+    let results = db.get_scan_results(&run.id)?;
+    let report = Report::new(&run, &results);
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, report.render(ReportFormat::for_path(path)))?;
+            eprintln!("Wrote report of run {} to {}", &run.id[..8], path.display());
+        }
+        None => print!("{}", report.render(ReportFormat::Markdown)),
+    }
+    Ok(())
+}
+
+/// Summarize a directory, or export its stored summary, as Markdown (or
+/// JSON); new summaries are stored
+async fn run_summarize(config: AppConfig, dir: &Path, output: Option<&Path>, stored: bool, format: &str) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(PanoptesError::Config(format!("Not a directory: {}", dir.display())));
+    }
+    let dir = dir.canonicalize()?;
+    let db = Database::open(&config.database.path)?;
+    let summary = if stored {
+        db.latest_summary(&dir.to_string_lossy())?
+            .ok_or_else(|| PanoptesError::NothingToDo(format!("{} was never summarized", dir.display())))?
+    } else {
+        let summary = summary::summarize(&dir, &config, &db).await?;
+        db.insert_summary(&summary)?;
+        summary
+    };
+
+    let rendered = match format {
+        "json" => serde_json::to_string_pretty(&summary)? + "\n",
+        "jsonl" => serde_json::to_string(&summary)? + "\n",
+        _ => summary.to_markdown(),
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(path, rendered)?;
+            eprintln!("Wrote summary of {} to {}", dir.display(), path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Sort the files in a directory into category directories
+async fn run_organize(config: AppConfig, dir: PathBuf, dry_run: bool, recursive: bool, format: &str) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(PanoptesError::Config(format!("Not a directory: {}", dir.display())));
+    }
+    let db = Database::open(&config.database.path)?;
+    let history = open_history(&db)?;
+    let session_id = uuid::Uuid::new_v4().to_string();
+
+    let mut files: Vec<PathBuf> = if recursive {
+        walkdir(&dir)
+    } else {
+        std::fs::read_dir(&dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect()
+    };
+    files.retain(|file| should_process(file));
+    files.sort();
+
+    let mut organizer = Organizer::new(&dir, &config.organize);
+    let (mut moved, mut skipped, mut failed) = (0, 0, 0);
+    let mut results = Vec::new();
+
+    for file in files {
+        let record = organizer::find_record(&db, &file)?;
+        let category = organizer::category_of(&file, record.as_ref());
+        let placement = organizer.place(&file, category.as_deref());
+
+        let (action, target) = match &placement {
+            Placement::Move(target) if dry_run => {
+                if format == "text" {
+                    println!("Would move: {} -> {}", file.display(), target.display());
+                }
+                moved += 1;
+                ("move", Some(target))
+            }
+            Placement::Move(target) => match organizer::move_file(&file, target, record.as_ref(), &history, Some(&session_id)) {
+                Ok(()) => {
+                    if format == "text" {
+                        println!("Moved: {} -> {}", file.display(), target.display());
+                    }
+                    moved += 1;
+                    ("move", Some(target))
+                }
+                Err(e) => {
+                    if format == "text" {
+                        eprintln!("Failed to move {}: {}", file.display(), e);
+                    }
+                    failed += 1;
+                    ("failed", Some(target))
+                }
+            },
+            Placement::Collision(target) => {
+                if format == "text" {
+                    println!("Skipped: {} ({} exists)", file.display(), target.display());
+                }
+                skipped += 1;
+                ("collision", Some(target))
+            }
+            Placement::InPlace => ("in_place", None),
+            Placement::Uncategorized => ("uncategorized", None),
+        };
+
+        results.push(serde_json::json!({
+            "path": file.to_string_lossy(),
+            "category": category,
+            "action": action,
+            "target": target.map(|t| t.to_string_lossy()),
+        }));
+    }
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&results)?),
+        "jsonl" => {
+            for line in &results {
+                println!("{}", serde_json::to_string(line)?);
+            }
+        }
+        _ => {
+            let verb = if dry_run { "Would move" } else { "Moved" };
+            println!("\n{} {} of {} files ({} skipped, {} failed)", verb, moved, results.len(), skipped, failed);
+            if moved > 0 && !dry_run {
+                println!("Session: {} (undo with `panoptes history undo --session {}`)", session_id, &session_id[..8]);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(PanoptesError::Partial(format!("{} file(s) could not be moved", failed)));
+    }
+    if moved == 0 {
+        return Err(PanoptesError::NothingToDo("No files to move".to_string()));
+    }
+    Ok(())
+}
+
+fn run_vault_command(config: AppConfig, action: VaultCommands, format: &str) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    match action {
+        VaultCommands::Export { dir, dry_run } => {
+            let export = vault::export(&db, &dir, &config, dry_run)?;
+            if format == "json" || format == "jsonl" {
+                println!("{}", serde_json::to_string(&export)?);
+            } else {
+                let verb = if dry_run { "Would link" } else { "Linked" };
+                println!("{} {} files into {} ({} already linked, {} stale links removed)",
+                    verb, export.linked, dir.display(), export.kept, export.removed);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_view_command(config: AppConfig, action: ViewCommands, format: &str) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    match action {
+        ViewCommands::Build { dry_run } => {
+            let build = views::build(&db, &config, dry_run)?;
+            if format == "json" || format == "jsonl" {
+                println!("{}", serde_json::to_string(&build)?);
+            } else {
+                let dir = views::root(&config).unwrap_or_default();
+                let verb = if dry_run { "Would link" } else { "Linked" };
+                println!("{} {} files into {} ({} already linked, {} stale links removed)",
+                    verb, build.linked, dir.display(), build.kept, build.removed);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The sources `name` picks: that one, or all of them
+fn pick_sources<'a>(config: &'a AppConfig, name: Option<&str>) -> Result<Vec<&'a SourceConfig>> {
+    match name {
+        Some(name) => Ok(vec![sources::find(config, name)?]),
+        None if config.sources.is_empty() => Err(PanoptesError::NothingToDo("No sources configured".to_string())),
+        None => Ok(config.sources.iter().collect()),
+    }
+}
+
+async fn run_source_command(config: AppConfig, action: SourceCommands, format: &str) -> Result<()> {
+    let json = format == "json" || format == "jsonl";
+    let db = Database::open(&config.database.path)?;
+    match action {
+        SourceCommands::List => {
+            if config.sources.is_empty() {
+                println!("No sources.");
+            }
+            for source in &config.sources {
+                let counts = db.remote_object_counts(&source.name)?;
+                let count = |state| counts.get(&state).copied().unwrap_or_default();
+                println!("  {} {} ({} renamed, {} planned, {} kept, {} skipped, {} failed)",
+                    source.name, source.url, count(RemoteState::Renamed), count(RemoteState::Planned),
+                    count(RemoteState::Kept), count(RemoteState::Skipped), count(RemoteState::Failed));
+            }
+        }
+        SourceCommands::Sync { name, dry_run } => {
+            let registry = AnalyzerRegistry::new(&config);
+            registry.load_corrections(&db);
+            let mut failed = Vec::new();
+            for source in pick_sources(&config, name.as_deref())? {
+                match sources::sync(source, &config, &registry, &db, dry_run).await {
+                    Ok(pass) if json => println!("{}", serde_json::json!({ "source": source.name, "sync": pass })),
+                    Ok(pass) => {
+                        let verb = if dry_run { "would analyze" } else { "analyzed" };
+                        println!("{}: {} files, {} {}, {} renamed, {} planned, {} skipped, {} failed",
+                            source.name, pass.listed, verb, pass.analyzed, pass.renamed, pass.planned, pass.skipped, pass.failed);
+                    }
+                    Err(e) => {
+                        error!("{}: {}", source.name, e);
+                        failed.push(source.name.clone());
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                return Err(PanoptesError::Partial(format!("Failed to look at {}", failed.join(", "))));
+            }
+        }
+        SourceCommands::Plan { name } => {
+            let names: Vec<&str> = pick_sources(&config, name.as_deref())?.iter().map(|s| s.name.as_str()).collect();
+            let planned: Vec<_> = db.get_remote_objects(None, RemoteState::Planned)?
+                .into_iter()
+                .filter(|record| names.contains(&record.source.as_str()))
+                .collect();
+            if json {
+                println!("{}", serde_json::to_string(&planned)?);
+                return Ok(());
+            }
+            if planned.is_empty() {
+                println!("No renames planned.");
+            }
+            for record in planned {
+                println!("  {}: {} -> {} ({:.0}%)", record.source, record.key, record.new_key.unwrap_or_default(),
+                    record.confidence.unwrap_or_default() * 100.0);
+            }
+        }
+        SourceCommands::Apply { name, dry_run } => {
+            for source in pick_sources(&config, name.as_deref())? {
+                let done = sources::apply(source, &db, dry_run).await?;
+                if json {
+                    println!("{}", serde_json::to_string(&done)?);
+                    continue;
+                }
+                let verb = if dry_run { "Would rename" } else { "Renamed" };
+                for record in &done {
+                    println!("  {} {}: {} -> {}", verb, source.name, record.key, record.new_key.as_deref().unwrap_or_default());
+                }
+                println!("{}: {} renamed", source.name, done.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// File names from `list` (`-` for standard input), one per line or NUL-separated
+fn read_file_list(list: &Path, null: bool) -> Result<Vec<PathBuf>> {
+    use std::io::Read;
+
+    let mut data = Vec::new();
+    if list == Path::new("-") {
+        std::io::stdin().read_to_end(&mut data)?;
+    } else {
+        data = std::fs::read(list)?;
+    }
+
+    let separator = if null { b'\0' } else { b'\n' };
+    let files = data.split(|&b| b == separator)
+        .map(|name| if null { name } else { name.strip_suffix(b"\r").unwrap_or(name) })
+        .filter(|name| !name.is_empty())
+        .map(|name| PathBuf::from(String::from_utf8_lossy(name).into_owned()))
+        .collect();
+    Ok(files)
+}
+
+/// Walk directory recursively
+/// Files in `dir` that `selector` picks, searching subdirectories with `recursive`
+fn select_files(dir: &Path, recursive: bool, selector: &Selector) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![(dir.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if recursive && selector.descends(&path, depth + 1) {
+                    pending.push((path, depth + 1));
+                }
+            } else if path.is_file() && selector.matches(&path, depth) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+fn walkdir(path: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            let p = entry.path();
+            if p.is_dir() {
+                files.extend(walkdir(&p));
+            } else if p.is_file() {
+                files.push(p);
+            }
+        }
+    }
+
+    files
+}
+
+/// Run database commands
+async fn run_db_command(config: AppConfig, action: DbCommands) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+
+    match action {
+        DbCommands::Stats => {
+            let stats = db.get_stats()?;
+            println!("Database Statistics:");
+            println!("  Files: {}", stats.file_count);
+            println!("  Tags: {}", stats.tag_count);
+            println!("  Categories: {}", stats.category_count);
+        }
+        DbCommands::Tags { category, limit } => {
+            let tags = db.get_all_tags()?;
+            println!("Tags:");
+            for (i, tag) in tags.iter().enumerate() {
+                if i >= limit { break; }
+                if let Some(ref cat) = category {
+                    if tag.category.as_ref() == Some(cat) {
+                        println!("  {} ({})", tag.name, tag.category.as_deref().unwrap_or("-"));
+                    }
+                } else {
+                    println!("  {} ({})", tag.name, tag.category.as_deref().unwrap_or("-"));
+                }
+            }
+        }
+        DbCommands::Categories => {
+            let categories = db.get_all_categories()?;
+            println!("Categories:");
+            for cat in categories {
+                println!("  {} - {} ({} files)", cat.name, cat.description.unwrap_or_default(), cat.file_count);
+            }
+        }
+        DbCommands::Search { query, tags_only: _, limit } => {
+            let results = db.search_files(&query, limit)?;
+            println!("Search results for '{}':", query);
+            for file in results {
+                println!("  {}: {}", file.id, file.suggested_name);
+            }
+        }
+        DbCommands::Export { output } => {
+            let count = export_files(&db, &output)?;
+            println!("Exported {} files to {:?}", count, output);
+        }
+        DbCommands::Vacuum => {
+            db.vacuum()?;
+            println!("Database vacuumed successfully");
+        }
+    }
+
+    Ok(())
+}
+
+/// Write every file record to `output` as JSON; returns how many there were
+fn export_files(db: &Database, output: &Path) -> Result<usize> {
+    let files = db.get_all_files()?;
+    let json = serde_json::to_string_pretty(&files)?;
+    std::fs::write(output, json)?;
+    Ok(files.len())
+}
+
+/// Run job queue commands
+fn run_jobs_command(config: AppConfig, action: JobCommands, format: &str) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    let json = format == "json" || format == "jsonl";
+    let no_such_job = |id: i64| PanoptesError::Config(format!("No job {}", id));
+
+    match action {
+        JobCommands::List { state, limit } => {
+            let jobs = db.list_jobs(state, limit)?;
+            if json {
+                println!("{}", serde_json::to_string(&jobs)?);
+                return Ok(());
+            }
+            if jobs.is_empty() {
+                println!("No jobs");
+                return Ok(());
+            }
+            println!("{:>6}  {:<9} {:<9} {:>8} {:>8}  Target", "ID", "Kind", "State", "Priority", "Attempts");
+            for job in &jobs {
+                println!("{:>6}  {:<9} {:<9} {:>8} {:>8}  {}", job.id, job.task.kind().as_str(), job.state.as_str(),
+                    job.priority, format!("{}/{}", job.attempts, job.max_attempts), job.task.target());
+            }
+        }
+        JobCommands::Show { id } => {
+            let job = db.get_job(id)?.ok_or_else(|| no_such_job(id))?;
+            if json {
+                println!("{}", serde_json::to_string(&job)?);
+                return Ok(());
+            }
+            let time = |at: chrono::DateTime<chrono::Utc>| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string();
+            println!("Job {}: {} {}", job.id, job.task.kind().as_str(), job.task.target());
+            println!("  State:    {}", job.state.as_str());
+            println!("  Priority: {}", job.priority);
+            println!("  Attempts: {} of {}", job.attempts, job.max_attempts);
+            println!("  Correlation ID: {}", job.correlation_id);
+            println!("  Queued:   {}", time(job.created_at));
+            if job.state == JobState::Queued && job.attempts > 0 {
+                println!("  Next try: {}", time(job.run_at));
+            }
+            if let Some(at) = job.started_at {
+                println!("  Started:  {}", time(at));
+            }
+            if let Some(at) = job.finished_at {
+                println!("  Finished: {}", time(at));
+            }
+            if let Some(ref error) = job.last_error {
+                println!("  Error:    {}", error);
+            }
+        }
+        JobCommands::Add { kind, target, priority } => {
+            let task = match kind {
+                JobKind::Analyze => {
+                    let path = std::env::current_dir()?.join(&target);
+                    if !path.is_file() {
+                        return Err(PanoptesError::Config(format!("No such file: {}", target)));
+                    }
+                    // As if it turned up in its watch directory, if it is in one
+                    let dirs = watch_dirs(&config, &[], &WatchOptions::default());
+                    let watched = dirs.iter().find(|(dir, options)| {
+                        if options.recursive { path.starts_with(dir) } else { path.parent() == Some(dir.as_path()) }
+                    });
+                    match watched {
+                        Some((dir, options)) => Task::Analyze {
+                            path: path.clone(),
+                            profile: None,
+                            destination: options.destination.as_deref().map(PathBuf::from),
+                            organize_root: organize_root(dir, options),
+                            dry_run: options.dry_run,
+                        },
+                        None => Task::Analyze { path, profile: None, destination: None, organize_root: None, dry_run: false },
+                    }
+                }
+                JobKind::Thumbnail => Task::Thumbnail { file_id: find_tag_target(&db, &target)?.id },
+                JobKind::Reprocess => Task::Reprocess { file_id: find_tag_target(&db, &target)?.id, model: None, force: false },
+                JobKind::Export => Task::Export { output: std::env::current_dir()?.join(&target) },
+            };
+            let queue = Queue::new(db);
+            let id = queue.push_with_priority(&task, priority.unwrap_or_else(|| task.default_priority()), &config.jobs)?;
+            println!("Queued job {}: {} {}", id, kind.as_str(), task.target());
+        }
+        JobCommands::Retry { id: Some(id), .. } => {
+            if !db.retry_job(id)? {
+                let job = db.get_job(id)?.ok_or_else(|| no_such_job(id))?;
+                return Err(PanoptesError::Config(format!(
+                    "Job {} is {}; only dead and cancelled jobs can be retried", id, job.state.as_str()
+                )));
+            }
+            println!("Queued job {} again", id);
+        }
+        JobCommands::Retry { id: None, .. } => {
+            let count = db.retry_dead_jobs()?;
+            if count == 0 {
+                return Err(PanoptesError::NothingToDo("No dead jobs".to_string()));
+            }
+            println!("Queued {} dead job(s) again", count);
+        }
+        JobCommands::Cancel { id } => {
+            if !db.cancel_job(id)? {
+                let job = db.get_job(id)?.ok_or_else(|| no_such_job(id))?;
+                return Err(PanoptesError::Config(format!(
+                    "Job {} is {}; only queued jobs can be cancelled", id, job.state.as_str()
+                )));
+            }
+            println!("Cancelled job {}", id);
+        }
+        JobCommands::Clear { dead } => {
+            let count = db.clear_jobs(dead)?;
+            println!("Removed {} finished job(s)", count);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run history commands
+async fn run_history_command(config: AppConfig, action: HistoryCommands, yes: bool) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+    let history = open_history(&db)?;
+
+    match action {
+        HistoryCommands::List { count } => {
+            let entries = history.get_recent(count)?;
+            println!("Recent history ({} entries):", entries.len());
+            for entry in entries {
+                let status = if entry.undone { "[UNDONE]" } else { "" };
+                let session = entry.session_id.as_deref().map(|s| &s[..8.min(s.len())]).unwrap_or("-");
+                let (arrow, to) = match entry.action {
+                    HistoryAction::Rename => ("->", entry.new_path.display().to_string()),
+                    HistoryAction::Move => ("=>", entry.new_path.display().to_string()),
+                    HistoryAction::Trash => ("=>", "(trash)".to_string()),
+                    HistoryAction::Delete => ("=>", "(deleted)".to_string()),
+                };
+                println!("  {} [{}] {} {} {} {} {}",
+                    &entry.id[..8.min(entry.id.len())],
+                    session,
+                    entry.timestamp.format("%Y-%m-%d %H:%M"),
+                    entry.original_path.display(),
+                    arrow,
+                    to,
+                    status
+                );
+            }
+        }
+        HistoryCommands::Undo { count, id, path, session, on_conflict, permanent, dry_run } => {
+            let mut strategy = match on_conflict {
+                Some(s) => s.parse()?,
+                None => config.history.undo_conflict,
+            };
+            // --yes never prompts; skipping is the prompt's default answer
+            if yes && strategy == UndoConflict::Prompt {
+                strategy = UndoConflict::Skip;
+            }
+
+            let discard = Discard::new(history.clone(), Deletion::from_flag(permanent));
+            if let Some(session) = session {
+                return undo_session(&history, &session, strategy, &discard, dry_run);
+            }
+
+            let to_undo: Vec<_> = if let Some(id) = id {
+                match history.find_by_id(&id)? {
+                    Some(entry) if entry.undone => {
+                        return Err(PanoptesError::NothingToDo(format!("Entry {} has already been undone", entry.id)));
+                    }
+                    Some(entry) if entry.action.is_removal() => {
+                        return Err(PanoptesError::Config(format!("Entry {} records a deletion, which can't be undone", entry.id)));
+                    }
+                    Some(entry) => vec![entry],
+                    None => {
+                        return Err(PanoptesError::Config(format!("No history entry with ID '{}'", id)));
+                    }
+                }
+            } else if let Some(path) = path {
+                match history.find_by_path(&path)? {
+                    Some(entry) => vec![entry],
+                    None => {
+                        return Err(PanoptesError::Config(format!("No undoable rename recorded for {:?}", path)));
+                    }
+                }
+            } else {
+                let entries = history.get_undoable()?;
+                entries.into_iter().rev().take(count).collect()
+            };
+
+            if to_undo.is_empty() {
+                return Err(PanoptesError::NothingToDo("No renames to undo".to_string()));
+            }
+
+            for entry in to_undo {
+                undo_entry(&history, &entry, strategy, &discard, dry_run)?;
+            }
+        }
+        HistoryCommands::Clear { force } => {
+            if !force && !confirm("Clear all history", yes)? {
+                return Err(PanoptesError::NothingToDo("History kept".to_string()));
+            }
+            history.clear()?;
+            println!("History cleared");
+        }
+    }
+
+    Ok(())
+}
+
+/// Revert a single history entry
+fn undo_entry(history: &History, entry: &HistoryEntry, strategy: UndoConflict, discard: &Discard, dry_run: bool) -> Result<()> {
+    if !entry.new_path.exists() {
+        warn!("File not found (may have been moved/deleted): {:?}", entry.new_path);
+        return Ok(());
+    }
+
+    if changed_since_rename(entry)? {
+        warn!("{:?} was modified since it was renamed", entry.new_path);
+    }
+
+    if dry_run {
+        let note = if entry.original_path.exists() { " (original path occupied)" } else { "" };
+        println!("Would undo: {} -> {}{}",
+            entry.new_path.display(),
+            entry.original_path.display(),
+            note
+        );
+        return Ok(());
+    }
+
+    let mut outcome = revert_with(entry, strategy, discard)?;
+    if outcome == UndoOutcome::Conflict && strategy == UndoConflict::Prompt {
+        outcome = revert_with(entry, prompt_conflict(entry)?, discard)?;
+    }
+
+    match outcome {
+        UndoOutcome::Reverted(restored) => {
+            history.mark_undone(&entry.id, &restored)?;
+            println!("Undone: {} -> {}", entry.new_path.display(), restored.display());
+        }
+        UndoOutcome::Missing => {
+            warn!("File not found (may have been moved/deleted): {:?}", entry.new_path);
+        }
+        UndoOutcome::Conflict => {
+            warn!("Skipped {:?}: original path {:?} is occupied", entry.new_path, entry.original_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask whether to go ahead with `action`, unless `--yes` was given. Without a
+/// terminal to ask on this fails rather than hanging or going ahead unasked.
+fn confirm(action: &str, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(PanoptesError::Config(format!("Cannot ask \"{}?\" without a terminal; pass --yes to go ahead", action)));
+    }
+    print!("{}? [y/N] ", action);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// An answer to "rename this file?" in `analyze --interactive`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RenameChoice {
+    Yes,
+    No,
+    /// Rename it with this name (cleaned like a suggestion) in place of the suggestion
+    Edit(String),
+    /// Leave this file and stop the run
+    SkipAll,
+    /// Rename this file and the rest without asking
+    AcceptAll,
+}
+
+/// Ask whether to rename `file` to `proposed`. Asks on stderr, so JSON on
+/// stdout stays intact; the end of input skips the rest.
+fn prompt_rename(file: &Path, proposed: &Path, sanitizer: &SanitizerConfig) -> Result<RenameChoice> {
+    loop {
+        eprint!("Rename {} -> {}? [y]es, [n]o, [e]dit, [s]kip rest, [a]ccept rest: ",
+            file.display(), proposed.file_name().unwrap_or_default().to_string_lossy());
+        std::io::stderr().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            eprintln!();
+            return Ok(RenameChoice::SkipAll);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(RenameChoice::Yes),
+            "n" | "no" => return Ok(RenameChoice::No),
+            "s" | "skip" => return Ok(RenameChoice::SkipAll),
+            "a" | "accept" => return Ok(RenameChoice::AcceptAll),
+            "e" | "edit" => {
+                eprint!("New name: ");
+                std::io::stderr().flush()?;
+                let mut name = String::new();
+                std::io::stdin().read_line(&mut name)?;
+                match clean_filename(&name, sanitizer) {
+                    name if name.is_empty() => eprintln!("Name is empty after cleaning"),
+                    name => return Ok(RenameChoice::Edit(name)),
+                }
+            }
+            _ => continue,
+        }
+    }
+}
+
+/// Ask how to resolve an occupied original path
+fn prompt_conflict(entry: &HistoryEntry) -> Result<UndoConflict> {
+    loop {
+        print!("{} already exists. [s]kip, [o]verwrite, [r]estore with suffix? ",
+            entry.original_path.display());
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(UndoConflict::Skip);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "s" | "skip" | "" => return Ok(UndoConflict::Skip),
+            "o" | "overwrite" => return Ok(UndoConflict::Overwrite),
+            "r" | "restore" | "suffix" => return Ok(UndoConflict::Suffix),
+            _ => continue,
+        }
+    }
+}
+
+/// Revert an entire session atomically: every rename is undone, or none is.
+/// Occupied original paths are resolved by `strategy` as for a single undo,
+/// asked about up front with `prompt`; skipped ones don't count as failures.
+fn undo_session(history: &History, session: &str, strategy: UndoConflict, discard: &Discard, dry_run: bool) -> Result<()> {
+    let entries = history.get_session(session)?;
+
+    if entries.is_empty() {
+        return Err(PanoptesError::NothingToDo(format!("No undoable renames in session '{}'", session)));
+    }
+
+    // Check every entry up front so a missing file doesn't leave a half-undone
+    // batch, and settle how each occupied path is resolved before anything moves
+    let mut plan: Vec<(&HistoryEntry, UndoConflict)> = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        if !entry.new_path.exists() {
+            return Err(PanoptesError::Config(format!(
+                "Cannot undo session: {:?} no longer exists", entry.new_path
+            )));
+        }
+        if changed_since_rename(entry)? {
+            warn!("{:?} was modified since it was renamed", entry.new_path);
+        }
+        let strategy = match strategy {
+            UndoConflict::Prompt if entry.original_path.exists() && !dry_run => prompt_conflict(entry)?,
+            strategy => strategy,
+        };
+        plan.push((entry, strategy));
+    }
+
+    if dry_run {
+        for entry in &entries {
+            let note = if entry.original_path.exists() { " (original path occupied)" } else { "" };
+            println!("Would undo: {} -> {}{}", entry.new_path.display(), entry.original_path.display(), note);
+        }
+        println!("{} rename(s) would be undone", entries.len());
+        return Ok(());
+    }
+
+    // Files in the way of an overwrite are moved aside, and only discarded
+    // once every file is back, so a rollback can put them back too
+    let staging = format!(".panoptes-undo-{}", uuid::Uuid::new_v4().simple());
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut done: Vec<(&HistoryEntry, PathBuf)> = Vec::new();
+    let mut skipped = 0;
+    for (entry, strategy) in plan {
+        let result = match stage_occupant(entry, strategy, &staging) {
+            Ok(Some(aside)) => {
+                staged.push((entry.original_path.clone(), aside));
+                revert_with(entry, strategy, discard)
+            }
+            Ok(None) => revert_with(entry, strategy, discard),
+            Err(e) => Err(e),
+        };
+        let failure = match result {
+            Ok(UndoOutcome::Reverted(restored)) => {
+                done.push((entry, restored));
+                continue;
+            }
+            Ok(UndoOutcome::Conflict) => {
+                warn!("Skipped {:?}: original path {:?} is occupied", entry.new_path, entry.original_path);
+                skipped += 1;
+                continue;
+            }
+            Ok(other) => PanoptesError::Config(format!("Undo of {:?} failed: {:?}", entry.new_path, other)),
+            Err(e) => e,
+        };
+
+        error!("Undo failed for {:?}: {}. Rolling back session...", entry.new_path, failure);
+        for (undone, restored) in done.iter().rev() {
+            if let Err(e) = move_path(restored, &undone.new_path) {
+                error!("Rollback failed for {:?}: {}", restored, e);
+            }
+        }
+        for (original, aside) in staged.iter().rev() {
+            match move_path(aside, original) {
+                Ok(()) => remove_staging(aside),
+                Err(e) => error!("Failed to put {:?} back from {:?}: {}", original, aside, e),
+            }
+        }
+        return Err(failure);
+    }
+
+    let undone: Vec<(&str, &Path)> = done.iter().map(|(entry, restored)| (entry.id.as_str(), restored.as_path())).collect();
+    history.mark_undone_many(&undone)?;
+
+    for (original, aside) in &staged {
+        match discard.remove_moved(aside, original) {
+            Ok(_) => remove_staging(aside),
+            Err(e) => warn!("Failed to remove {:?}, overwritten by the undo; it is kept at {:?}: {}", original, aside, e),
+        }
+    }
+
+    for (entry, restored) in &done {
+        println!("Undone: {} -> {}", entry.new_path.display(), restored.display());
+    }
+    if skipped > 0 {
+        println!("Session undone ({} renames, {} skipped)", done.len(), skipped);
+    } else {
+        println!("Session undone ({} renames)", done.len());
+    }
+
+    Ok(())
+}
+
+/// Move the file occupying `entry`'s original path into the `staging`
+/// directory beside it when the `strategy` is to overwrite it; returns where
+/// it went. It keeps its name, which the trash shows.
+fn stage_occupant(entry: &HistoryEntry, strategy: UndoConflict, staging: &str) -> Result<Option<PathBuf>> {
+    if strategy != UndoConflict::Overwrite || !entry.original_path.exists() {
+        return Ok(None);
+    }
+    let dir = entry.original_path.parent().unwrap_or(Path::new("")).join(staging);
+    std::fs::create_dir_all(&dir)?;
+    let aside = dir.join(entry.original_path.file_name().unwrap_or_default());
+    move_path(&entry.original_path, &aside)?;
+    Ok(Some(aside))
+}
+
+/// Remove the staging directory `aside` was in once it is empty
+fn remove_staging(aside: &Path) {
+    if let Some(dir) = aside.parent() {
+        let _ = std::fs::remove_dir(dir);
+    }
+}
+
+/// The record for `target`: a file path, or a record ID or unique prefix
+fn find_tag_target(db: &Database, target: &str) -> Result<FileRecord> {
+    let path = Path::new(target);
+    if path.exists() {
+        if let Some(record) = organizer::find_record(db, path)? {
+            return Ok(record);
+        }
+    }
+    db.find_file(target)?
+        .ok_or_else(|| PanoptesError::Config(format!("No file record for '{}'", target)))
+}
+
+/// Run tag commands
+fn run_tag_command(config: AppConfig, action: TagCommands, format: &str) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+
+    match action {
+        TagCommands::Add { file, tags } => {
+            let record = find_tag_target(&db, &file)?;
+            for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+                db.add_tag_to_file(&record.id, tag)?;
+            }
+            xattrs::tag_or_warn(&db, &record.id, &config);
+            println!("{}: {}", record.new_path, db.get_file_tags(&record.id)?.join(", "));
+        }
+        TagCommands::Remove { file, tags } => {
+            let record = find_tag_target(&db, &file)?;
+            let mut missing = Vec::new();
+            for tag in &tags {
+                if !db.remove_tag_from_file(&record.id, tag.trim())? {
+                    missing.push(tag.as_str());
+                }
+            }
+            xattrs::tag_or_warn(&db, &record.id, &config);
+            println!("{}: {}", record.new_path, db.get_file_tags(&record.id)?.join(", "));
+            if !missing.is_empty() {
+                return Err(PanoptesError::Config(format!("File had no tag {}", missing.join(", "))));
+            }
+        }
+        TagCommands::List { file } => {
+            let record = find_tag_target(&db, &file)?;
+            let tags = db.get_file_tag_sources(&record.id)?;
+            if format == "json" || format == "jsonl" {
+                let tags: Vec<_> = tags.iter()
+                    .map(|(name, source)| serde_json::json!({ "name": name, "source": source }))
+                    .collect();
+                println!("{}", serde_json::to_string(&tags)?);
+                return Ok(());
+            }
+            println!("{} ({}):", record.new_path, record.id);
+            if tags.is_empty() {
+                println!("  no tags");
+            }
+            for (name, source) in tags {
+                let manual = if source == TagSource::User { " (manual)" } else { "" };
+                println!("  {}{}", name, manual);
+            }
+        }
+        TagCommands::Files { tag, limit } => {
+            let filter = FileFilter { tag: Some(tag.clone()), ..Default::default() };
+            let (files, total) = db.query_files(&filter, limit, 0)?;
+            if format == "json" || format == "jsonl" {
+                println!("{}", serde_json::to_string(&files)?);
+                return Ok(());
+            }
+            if files.is_empty() {
+                return Err(PanoptesError::Config(format!("No files tagged '{}'", tag)));
+            }
+            for file in &files {
+                println!("  {}  {}", &file.id[..8.min(file.id.len())], file.new_path);
+            }
+            if total as usize > files.len() {
+                println!("{} of {} files; use -n to see more", files.len(), total);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// How many of the latest records `plugin list` and `plugin info` look through
+const RECENT_FILES: usize = 200;
+
+/// The plugin that analyzed a file, if one did
+fn analyzing_plugin(record: &FileRecord) -> Option<&str> {
+    record.metadata.get(plugins::METADATA_KEY)?.get("name")?.as_str()
+}
+
+async fn run_plugin_command(config: AppConfig, action: PluginCommands, format: &str) -> Result<()> {
+    let plugins_dir = PathBuf::from(&config.plugins.directory);
+    let json = format == "json" || format == "jsonl";
+    // Recent files are only counted when there is a database to count them in
+    let recent = || -> Vec<FileRecord> {
+        Path::new(&config.database.path).exists()
+            .then(|| Database::open(&config.database.path).and_then(|db| db.get_recent_files(RECENT_FILES)).ok())
+            .flatten()
+            .unwrap_or_default()
+    };
+
+    match action {
+        PluginCommands::List => {
+            let installed = plugins::discover(&plugins_dir);
+            let recent = recent();
+            let handled = |name: &str| recent.iter().filter(|r| analyzing_plugin(r) == Some(name)).count();
+            if json {
+                let list: Vec<_> = installed.iter()
+                    .map(|plugin| serde_json::json!({
+                        "name": plugin.name(),
+                        "path": plugin.dir,
+                        "enabled": plugin.enabled,
+                        "manifest": plugin.manifest.as_ref().ok(),
+                        "error": plugin.manifest.as_ref().err(),
+                        "recent_files": handled(&plugin.name()),
+                    }))
+                    .collect();
+                println!("{}", serde_json::to_string(&list)?);
+                return Ok(());
+            }
+            if installed.is_empty() {
+                return Err(PanoptesError::NothingToDo(format!("No plugins installed in {}", plugins_dir.display())));
+            }
+            println!("{:<20} {:<10} {:<9} {:>6}  EXTENSIONS", "NAME", "VERSION", "STATUS", "RECENT");
+            for plugin in &installed {
+                let name = plugin.name();
+                match &plugin.manifest {
+                    Ok(manifest) => println!("{:<20} {:<10} {:<9} {:>6}  {}",
+                        name, manifest.version, if plugin.enabled { "enabled" } else { "disabled" },
+                        handled(&name), manifest.extensions.join(", ")),
+                    Err(e) => println!("{:<20} {:<10} {:<9} {:>6}  {}", name, "-", "invalid", handled(&name), e),
+                }
+            }
+            println!("RECENT: files among the latest {} analyzed by the plugin", RECENT_FILES);
+        }
+        PluginCommands::Info { name } => {
+            let plugin = plugins::find(&plugins_dir, &name)?;
+            let files: Vec<FileRecord> = recent().into_iter()
+                .filter(|r| analyzing_plugin(r) == Some(name.as_str()))
+                .collect();
+            if json {
+                println!("{}", serde_json::to_string(&serde_json::json!({
+                    "name": plugin.name(),
+                    "path": plugin.dir,
+                    "enabled": plugin.enabled,
+                    "manifest": plugin.manifest.as_ref().ok(),
+                    "error": plugin.manifest.as_ref().err(),
+                    "recent_files": files,
+                }))?);
+                return Ok(());
+            }
+            println!("{} ({})", plugin.name(), plugin.dir.display());
+            println!("  Status:      {}", if plugin.enabled { "enabled" } else { "disabled" });
+            match &plugin.manifest {
+                Ok(manifest) => {
+                    println!("  Version:     {}", manifest.version);
+                    if let Some(description) = &manifest.description {
+                        println!("  Description: {}", description);
+                    }
+                    println!("  Extensions:  {}", manifest.extensions.join(", "));
+                    println!("  Command:     {}", manifest.command.join(" "));
+                    println!("  Priority:    {}", manifest.priority);
+                }
+                Err(e) => println!("  Invalid:     {}", e),
+            }
+            if files.is_empty() {
+                println!("No recent files analyzed by it");
+            } else {
+                println!("Recent files analyzed by it:");
+                for file in files.iter().take(10) {
+                    println!("  {}  {}  {}", file.created_at.format("%Y-%m-%d %H:%M"), &file.id[..8.min(file.id.len())], file.new_path);
+                }
+                if files.len() > 10 {
+                    println!("  and {} more", files.len() - 10);
+                }
             }
         }
+        PluginCommands::Enable { name } => {
+            let changed = plugins::set_enabled(&plugins_dir, &name, true)?;
+            println!("Plugin '{}' {}", name, if changed { "enabled" } else { "was already enabled" });
+        }
+        PluginCommands::Disable { name } => {
+            let changed = plugins::set_enabled(&plugins_dir, &name, false)?;
+            println!("Plugin '{}' {}", name, if changed { "disabled" } else { "was already disabled" });
+        }
+        PluginCommands::Install { source, force } => {
+            let manifest = plugins::install(&source, &plugins_dir, force).await?;
+            println!("Installed plugin '{}' {} in {}", manifest.name, manifest.version,
+                plugins_dir.join(&manifest.name).display());
+            println!("  Analyzes: {}", manifest.extensions.join(", "));
+        }
     }
 
-    files
+    Ok(())
 }
 
-/// Run database commands
-async fn run_db_command(config: AppConfig, action: DbCommands) -> Result<()> {
-    let db = Database::open(&config.database.path)?;
-
+/// Run agent commands; `config_path` is where registering keeps the token
+async fn run_agent_command(config: AppConfig, action: AgentCommands, config_path: &Path, format: &str) -> Result<()> {
+    let json = format == "json" || format == "jsonl";
     match action {
-        DbCommands::Stats => {
-            let stats = db.get_stats()?;
-            println!("Database Statistics:");
-            println!("  Files: {}", stats.file_count);
-            println!("  Tags: {}", stats.tag_count);
-            println!("  Categories: {}", stats.category_count);
-        }
-        DbCommands::Tags { category, limit } => {
-            let tags = db.get_all_tags()?;
-            println!("Tags:");
-            for (i, tag) in tags.iter().enumerate() {
-                if i >= limit { break; }
-                if let Some(ref cat) = category {
-                    if tag.category.as_ref() == Some(cat) {
-                        println!("  {} ({})", tag.name, tag.category.as_deref().unwrap_or("-"));
-                    }
-                } else {
-                    println!("  {} ({})", tag.name, tag.category.as_deref().unwrap_or("-"));
-                }
+        AgentCommands::Register { server, token, name } => {
+            let server = server.trim_end_matches('/').to_string();
+            if !server.starts_with("http://") && !server.starts_with("https://") {
+                return Err(PanoptesError::Config("--server must be an http:// or https:// URL".to_string()));
             }
-        }
-        DbCommands::Categories => {
-            let categories = db.get_all_categories()?;
-            println!("Categories:");
-            for cat in categories {
-                println!("  {} - {} ({} files)", cat.name, cat.description.unwrap_or_default(), cat.file_count);
+            let name = name.or_else(|| config.agent.name.clone()).unwrap_or_else(agent::hostname);
+            let registration = agent::register(&server, &token, &name, &config).await?;
+            let secrets = agent::save(config_path, &server, &registration)?;
+            if json {
+                println!("{}", serde_json::to_string(&registration.agent)?);
+            } else {
+                println!("Registered with {} as '{}'", server, registration.agent.name);
+                println!("Its token is in {}; `panoptes watch` now forwards files there", secrets.display());
             }
         }
-        DbCommands::Search { query, tags_only: _, limit } => {
-            let results = db.search_files(&query, limit)?;
-            println!("Search results for '{}':", query);
-            for file in results {
-                println!("  {}: {}", file.id, file.suggested_name);
+        AgentCommands::Status => {
+            let Some(server) = agent::server(&config) else {
+                return Err(PanoptesError::NothingToDo("Not an agent; register with `panoptes agent register`".to_string()));
+            };
+            let me = agent::whoami(&config).await?;
+            if json {
+                println!("{}", serde_json::to_string(&me)?);
+            } else {
+                let last_seen = me.last_seen
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                println!("Registered with {} as '{}' ({} files forwarded, last {})", server, me.name, me.files, last_seen);
             }
         }
-        DbCommands::Export { output } => {
-            let files = db.get_all_files()?;
-            let json = serde_json::to_string_pretty(&files)?;
-            std::fs::write(&output, json)?;
-            println!("Exported {} files to {:?}", files.len(), output);
+        AgentCommands::List => {
+            let agents = Database::open(&config.database.path)?.get_agents()?;
+            if json {
+                println!("{}", serde_json::to_string(&agents)?);
+                return Ok(());
+            }
+            if agents.is_empty() {
+                println!("No agents.");
+            }
+            for agent in agents {
+                let last_seen = agent.last_seen
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                println!("  {} on {} (v{}, registered {}, {} files, last seen {})",
+                    agent.name, agent.hostname.as_deref().unwrap_or("?"), agent.version.as_deref().unwrap_or("?"),
+                    agent.registered_at.format("%Y-%m-%d"), agent.files, last_seen);
+            }
         }
-        DbCommands::Vacuum => {
-            db.vacuum()?;
-            println!("Database vacuumed successfully");
+        AgentCommands::Remove { name } => {
+            if Database::open(&config.database.path)?.remove_agent(&name)? {
+                println!("Removed agent '{}' and revoked its token", name);
+            } else {
+                return Err(PanoptesError::Config(format!("No agent named '{}'", name)));
+            }
         }
     }
-
     Ok(())
 }
 
-/// Run history commands
-async fn run_history_command(config: AppConfig, action: HistoryCommands) -> Result<()> {
-    let history = History::new(PathBuf::from("panoptes_history.jsonl"));
+/// Run token commands
+async fn run_token_command(config: AppConfig, action: TokenCommands) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
 
     match action {
-        HistoryCommands::List { count } => {
-            let entries = history.get_recent(count)?;
-            println!("Recent history ({} entries):", entries.len());
-            for entry in entries {
-                let status = if entry.undone { "[UNDONE]" } else { "" };
-                println!("  {} {} -> {} {}",
-                    entry.timestamp.format("%Y-%m-%d %H:%M"),
-                    entry.original_path.display(),
-                    entry.new_path.display(),
-                    status
-                );
+        TokenCommands::Create { name, role } => {
+            let token = auth::generate_token();
+            db.create_api_token(&name, &auth::hash_secret(&token), role)?;
+            println!("Created {} token '{}':\n\n  {}\n", role.as_str(), name, token);
+            println!("Store it now; it cannot be shown again.");
+        }
+        TokenCommands::List => {
+            let tokens = db.list_api_tokens()?;
+            if tokens.is_empty() {
+                println!("No API tokens.");
+            }
+            for token in tokens {
+                let last_used = token.last_used
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                println!("  {} [{}] (created {}, last used {})",
+                    token.name, token.role.as_str(), token.created_at.format("%Y-%m-%d"), last_used);
             }
         }
-        HistoryCommands::Undo { count, dry_run } => {
-            let entries = history.get_undoable()?;
-            let to_undo: Vec<_> = entries.into_iter().rev().take(count).collect();
-
-            if to_undo.is_empty() {
-                println!("No renames to undo");
-                return Ok(());
+        TokenCommands::Revoke { name } => {
+            if db.revoke_api_token(&name)? {
+                println!("Revoked token '{}'", name);
+            } else {
+                return Err(PanoptesError::Config(format!("No token named '{}'", name)));
             }
+        }
+    }
 
-            for entry in to_undo {
-                if entry.new_path.exists() {
-                    if dry_run {
-                        println!("Would undo: {} -> {}",
-                            entry.new_path.display(),
-                            entry.original_path.display()
-                        );
-                    } else {
-                        std::fs::rename(&entry.new_path, &entry.original_path)?;
-                        history.mark_undone(&entry.id)?;
-                        println!("Undone: {} -> {}",
-                            entry.new_path.display(),
-                            entry.original_path.display()
-                        );
-                    }
-                } else {
-                    warn!("File not found (may have been moved/deleted): {:?}", entry.new_path);
-                }
+    Ok(())
+}
+
+/// Run user commands
+async fn run_user_command(config: AppConfig, action: UserCommands) -> Result<()> {
+    let db = Database::open(&config.database.path)?;
+
+    match action {
+        UserCommands::List => {
+            let users = db.list_users()?;
+            if users.is_empty() {
+                println!("No users.");
+            }
+            for user in users {
+                let email = user.email.map(|e| format!(" <{}>", e)).unwrap_or_default();
+                println!("  {}{} [{}]", user.username, email, user.role.as_str());
             }
         }
-        HistoryCommands::Clear { force } => {
-            if !force {
-                eprintln!("Use --force to confirm clearing history");
-                return Ok(());
+        UserCommands::SetRole { username, role } => {
+            if db.set_user_role(&username, role)? {
+                println!("{} is now {}", username, role.as_str());
+            } else {
+                return Err(PanoptesError::Config(format!("No user named '{}'", username)));
             }
-            history.clear()?;
-            println!("History cleared");
+        }
+        UserCommands::Link { username, issuer, subject, role } => {
+            let username = match db.get_user_role(&username)? {
+                Some(_) => username,
+                None => db.create_user(&username, None, None, role)?,
+            };
+            db.link_identity(&issuer, &subject, &username)?;
+            println!("Linked {} identity {} to {}", issuer, subject, username);
         }
     }
 
@@ -809,18 +4324,58 @@ async fn run_history_command(config: AppConfig, action: HistoryCommands) -> Resu
 }
 
 /// Run config commands
-async fn run_config_command(config: AppConfig, action: ConfigCommands, config_path: &Path) -> Result<()> {
+async fn run_config_command(config: AppConfig, action: ConfigCommands, config_path: &Path, format: &str) -> Result<()> {
     match action {
-        ConfigCommands::Show => {
-            let json = serde_json::to_string_pretty(&config)?;
-            println!("{}", json);
+        ConfigCommands::Show { resolved: false } => {
+            let (_, settings) = layers::resolve(config_path)?;
+            let mut value = serde_json::to_value(&config)?;
+            secrets::redact(&mut value, &settings);
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        }
+        ConfigCommands::Show { resolved: true } => {
+            let (_, mut settings) = layers::resolve(config_path)?;
+            secrets::redact_settings(&mut settings);
+            if format == "json" || format == "jsonl" {
+                println!("{}", serde_json::to_string(&settings)?);
+            } else {
+                let width = settings.iter().map(|s| s.path.len()).max().unwrap_or(0);
+                for setting in &settings {
+                    println!("{:<width$} = {}  ({})", setting.path, setting.value, setting.source, width = width);
+                }
+            }
         }
         ConfigCommands::Generate { output, full: _ } => {
             let default_config = AppConfig::default();
             default_config.save(&output)?;
             println!("Generated config at {:?}", output);
         }
-        ConfigCommands::Validate => {
+        ConfigCommands::Validate { offline } => {
+            let diagnostics = diagnostics::check(&config, config_path, !offline).await;
+            let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+            if format == "json" || format == "jsonl" {
+                println!("{}", serde_json::to_string(&diagnostics)?);
+            } else {
+                if errors > 0 {
+                    println!("Configuration at {:?} has problems:", config_path);
+                }
+                for d in &diagnostics {
+                    let severity = match d.severity {
+                        Severity::Error => "error",
+                        Severity::Warning => "warning",
+                    };
+                    match (&d.file, d.line) {
+                        (Some(file), Some(line)) => println!("  {}: {} ({}:{})", severity, d.message, file.display(), line),
+                        (Some(file), None) => println!("  {}: {} ({})", severity, d.message, file.display()),
+                        _ => println!("  {}: {}", severity, d.message),
+                    }
+                }
+            }
+            if errors > 0 {
+                return Err(PanoptesError::Config(format!("{} problem(s) found", errors)));
+            }
+            if format == "json" || format == "jsonl" {
+                return Ok(());
+            }
             println!("Configuration at {:?} is valid", config_path);
             println!("  Watch paths: {:?}", config.watch_paths);
             println!("  Vision model: {}", config.ai_engine.models.vision);
@@ -832,6 +4387,75 @@ async fn run_config_command(config: AppConfig, action: ConfigCommands, config_pa
                 .arg(config_path)
                 .status()?;
         }
+        ConfigCommands::Migrate { dry_run } => {
+            let legacy = paths::legacy(&config);
+            if legacy.is_empty() {
+                return Err(PanoptesError::NothingToDo("Nothing to migrate".to_string()));
+            }
+            for item in &legacy {
+                if dry_run {
+                    println!("Would move {} to {}", item.from.display(), item.to.display());
+                    continue;
+                }
+                let updated = paths::migrate(item, config_path)?;
+                println!("Moved {} to {}", item.from.display(), item.to.display());
+                if updated {
+                    println!("  Removed {} from {:?}, so it defaults to the new place", item.setting, config_path);
+                } else if item.pinned {
+                    println!("  {} still names {}; set it to {}", item.setting, item.from.display(), item.to.display());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run service commands
+fn run_service_command(action: ServiceCommands, config_path: &Path) -> Result<()> {
+    let flag = |user: bool| if user { "--user " } else { "" };
+
+    match action {
+        ServiceCommands::Install { user, enable, force, print } => {
+            let unit = service::Unit::for_config(config_path, user)?;
+            if print {
+                print!("{}", unit.render());
+                return Ok(());
+            }
+            let path = service::install(&unit, force)?;
+            println!("Installed {}", path.display());
+            service::systemctl_checked(user, &["daemon-reload"])?;
+            if enable {
+                service::systemctl_checked(user, &["enable", "--now", service::UNIT_NAME])?;
+                println!("Enabled and started {}", service::UNIT_NAME);
+            } else {
+                println!("Start it with: systemctl {}enable --now {}", flag(user), service::UNIT_NAME);
+            }
+            if user {
+                println!("To keep it running while you're logged out: loginctl enable-linger");
+            }
+        }
+        ServiceCommands::Status { user } => {
+            let path = service::unit_path(user)?;
+            if !path.exists() {
+                println!("Not installed (no unit at {})", path.display());
+                return Ok(());
+            }
+            let origin = if service::is_generated(&path) { "" } else { ", not generated by panoptes" };
+            println!("Unit: {}{}\n", path.display(), origin);
+            // Exits non-zero for a stopped service, which is a status like any other
+            service::systemctl(user, &["status", "--no-pager", service::UNIT_NAME])?;
+        }
+        ServiceCommands::Uninstall { user, force } => {
+            let path = service::unit_path(user)?;
+            let ours = path.exists() && (force || service::is_generated(&path));
+            if ours && !service::systemctl(user, &["disable", "--now", service::UNIT_NAME])?.success() {
+                warn!("Could not stop and disable {}", service::UNIT_NAME);
+            }
+            let path = service::remove(user, force)?;
+            service::systemctl_checked(user, &["daemon-reload"])?;
+            println!("Removed {}", path.display());
+        }
     }
 
     Ok(())
@@ -867,16 +4491,41 @@ async fn run_status(config: AppConfig, model: Option<String>) -> Result<()> {
     }
 
     // Check database
+    let mut scheduled_runs = Vec::new();
     match Database::open(&config.database.path) {
         Ok(db) => {
             let stats = db.get_stats()?;
             println!("\nDatabase ({}):", config.database.path);
             println!("  Files: {}", stats.file_count);
             println!("  Tags: {}", stats.tag_count);
+            let jobs = db.count_jobs()?;
+            if !jobs.is_empty() {
+                let counts: Vec<String> = jobs.iter().map(|(state, n)| format!("{} {}", n, state.as_str())).collect();
+                println!("  Jobs: {}", counts.join(", "));
+            }
+            scheduled_runs = db.get_scheduled_runs()?;
         }
         Err(e) => println!("\nDatabase: ✗ Error - {}", e),
     }
 
+    if !config.schedule.is_empty() {
+        println!("\nSchedule (run by watch and serve):");
+        let local = |time: chrono::DateTime<chrono::Utc>| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string();
+        for entry in &config.schedule {
+            let next = entry.cron.parse::<Cron>().ok()
+                .and_then(|cron| cron.next_after(chrono::Local::now()))
+                .map_or_else(|| "never".to_string(), |time| time.format("%Y-%m-%d %H:%M").to_string());
+            let last = match scheduled_runs.iter().find(|run| run.name == entry.name()) {
+                Some(run) => match run.error {
+                    Some(ref error) => format!("; last {} failed: {}", local(run.started_at), error),
+                    None => format!("; last {}: {}", local(run.started_at), run.summary.as_deref().unwrap_or("ok")),
+                },
+                None => String::new(),
+            };
+            println!("  {:<14} {:<16} next {}{}", entry.name(), entry.cron, next, last);
+        }
+    }
+
     println!("\nConfiguration:");
     println!("  Watch paths: {:?}", config.watch_paths);
     println!("  Vision model: {}", config.ai_engine.models.vision);
@@ -886,8 +4535,18 @@ async fn run_status(config: AppConfig, model: Option<String>) -> Result<()> {
     Ok(())
 }
 
+/// Write man pages to `out_dir`: panoptes.1, plus panoptes-db.1,
+/// panoptes-db-stats.1 and so on for the subcommands
+fn run_man(out_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(out_dir)?;
+    clap_mangen::generate_to(Cli::command(), out_dir)?;
+    println!("Wrote man pages to {}", out_dir.display());
+    println!("Read them with: man -l {}", out_dir.join("panoptes.1").display());
+    Ok(())
+}
+
 /// Initialize a new Panoptes project
-async fn run_init(dir: Option<PathBuf>, force: bool) -> Result<()> {
+async fn run_init(dir: Option<PathBuf>, force: bool, template: Option<&str>) -> Result<()> {
     let target = dir.unwrap_or_else(|| PathBuf::from("."));
     let config_path = target.join("config.json");
 
@@ -897,19 +4556,39 @@ async fn run_init(dir: Option<PathBuf>, force: bool) -> Result<()> {
         ));
     }
 
-    // Create directories
-    let watch_dir = target.join("watch");
-    std::fs::create_dir_all(&watch_dir)?;
+    let config = match template {
+        Some(name) => templates::template(name)
+            .ok_or_else(|| PanoptesError::Config(format!("No template '{}'", name)))?,
+        None => {
+            let mut config = AppConfig::default();
+            config.watch_paths = vec![target.join("watch").to_string_lossy().to_string()];
+            config
+        }
+    };
 
-    // Create default config
-    let mut config = AppConfig::default();
-    config.watch_paths = vec![watch_dir.to_string_lossy().to_string()];
+    // Create directories
+    std::fs::create_dir_all(&target)?;
+    for dir in &config.watch_paths {
+        std::fs::create_dir_all(dir)?;
+    }
     config.save(&config_path)?;
 
     println!("Panoptes initialized in {:?}", target);
     println!("\nCreated:");
     println!("  - config.json");
-    println!("  - watch/");
+    if let Some(name) = template {
+        println!("\nFrom the {} template:", name);
+        for dir in &config.watch_paths {
+            println!("  Watching: {}", dir);
+        }
+        for rule in &config.rules.destinations {
+            println!("  {} -> {}", rule.category.as_deref().unwrap_or_default(), rule.directory);
+        }
+        println!("  Renamed automatically from {:.0}% confidence; less confident suggestions wait for review",
+            config.rules.auto_rename_threshold * 100.0);
+    } else {
+        println!("  - watch/");
+    }
     println!("\nNext steps:");
     println!("  1. Start Ollama: just start-engine");
     println!("  2. Start scanner: panoptes watch");
@@ -927,6 +4606,12 @@ fn test_cli_parsing() {
         assert!(!cli.verbose);
     }
 
+    #[test]
+    fn test_cli_definition() {
+        // Completions and man pages build every subcommand, so clashes there would panic
+        Cli::command().debug_assert();
+    }
+
     #[test]
     fn test_cli_watch_command() {
         let cli = Cli::try_parse_from([
@@ -942,6 +4627,20 @@ fn test_cli_watch_command() {
         }
     }
 
+    #[test]
+    fn test_cli_watch_profiles() {
+        let cli = Cli::try_parse_from([
+            "panoptes", "serve", "-P", "photos.json", "--profile", "documents.json"
+        ]).unwrap();
+
+        match cli.command {
+            Some(Commands::Serve { profile, .. }) => {
+                assert_eq!(profile, vec![PathBuf::from("photos.json"), PathBuf::from("documents.json")]);
+            }
+            _ => panic!("Expected Serve command"),
+        }
+    }
+
     #[test]
     fn test_cli_analyze_command() {
         let cli = Cli::try_parse_from([
@@ -951,9 +4650,221 @@ fn test_cli_analyze_command() {
         match cli.command {
             Some(Commands::Analyze { path, dry_run, .. }) => {
                 assert!(dry_run);
-                assert_eq!(path, PathBuf::from("/tmp/file.jpg"));
+                assert_eq!(path, Some(PathBuf::from("/tmp/file.jpg")));
+            }
+            _ => panic!("Expected Analyze command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_analyze_as_dir() {
+        let cli = Cli::try_parse_from(["panoptes", "analyze", "--as-dir", "/tmp/shoot"]).unwrap();
+        match cli.command {
+            Some(Commands::Analyze { path, as_dir, .. }) => {
+                assert!(as_dir);
+                assert_eq!(path, Some(PathBuf::from("/tmp/shoot")));
             }
             _ => panic!("Expected Analyze command"),
         }
+
+        assert!(Cli::try_parse_from(["panoptes", "analyze", "--as-dir", "--resume", "abc"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_analyze_min_confidence() {
+        let cli = Cli::try_parse_from(["panoptes", "analyze", "/tmp/photos", "--min-confidence", "0.3"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Analyze { min_confidence: Some(c), .. }) if c == 0.3));
+
+        assert!(Cli::try_parse_from(["panoptes", "analyze", "/tmp/photos", "--min-confidence", "30"]).is_err());
+        assert!(Cli::try_parse_from(["panoptes", "analyze", "/tmp/photos", "--min-confidence", "-0.1"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_summarize_command() {
+        let cli = Cli::try_parse_from(["panoptes", "summarize", "/tmp/archive", "-o", "notes.md", "--stored"]).unwrap();
+        match cli.command {
+            Some(Commands::Summarize { dir, output, stored }) => {
+                assert_eq!(dir, PathBuf::from("/tmp/archive"));
+                assert_eq!(output, Some(PathBuf::from("notes.md")));
+                assert!(stored);
+            }
+            _ => panic!("Expected Summarize command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_stats_command() {
+        let cli = Cli::try_parse_from(["panoptes", "stats", "--days", "30", "--top", "5"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Stats { days: 30, top: 5 })));
+
+        assert!(Cli::try_parse_from(["panoptes", "stats", "--days", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_similar_command() {
+        let cli = Cli::try_parse_from(["panoptes", "similar", "report.pdf", "-n", "5", "--min-score", "0.9"]).unwrap();
+        match cli.command {
+            Some(Commands::Similar { file, limit, min_score }) => {
+                assert_eq!(file, PathBuf::from("report.pdf"));
+                assert_eq!(limit, Some(5));
+                assert_eq!(min_score, Some(0.9));
+            }
+            _ => panic!("Expected Similar command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_init_template() {
+        let cli = Cli::try_parse_from(["panoptes", "init", "--template", "photos"]).unwrap();
+        match cli.command {
+            Some(Commands::Init { template, .. }) => assert_eq!(template.as_deref(), Some("photos")),
+            _ => panic!("Expected Init command"),
+        }
+
+        assert!(Cli::try_parse_from(["panoptes", "init", "--template", "music"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_config_show_resolved() {
+        let cli = Cli::try_parse_from(["panoptes", "config", "show", "--resolved"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Config { action: ConfigCommands::Show { resolved: true } })));
+    }
+
+    #[test]
+    fn test_cli_config_validate_offline() {
+        let cli = Cli::try_parse_from(["panoptes", "config", "validate", "--offline"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Config { action: ConfigCommands::Validate { offline: true } })));
+    }
+
+    #[test]
+    fn test_cli_config_migrate() {
+        let cli = Cli::try_parse_from(["panoptes", "config", "migrate", "--dry-run"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Config { action: ConfigCommands::Migrate { dry_run: true } })));
+    }
+
+    #[test]
+    fn test_cli_plugin_install() {
+        let cli = Cli::try_parse_from([
+            "panoptes", "plugin", "install", "https://example.com/heic.tar.gz", "--force"
+        ]).unwrap();
+
+        match cli.command {
+            Some(Commands::Plugin { action: PluginCommands::Install { source, force } }) => {
+                assert_eq!(source, "https://example.com/heic.tar.gz");
+                assert!(force);
+            }
+            _ => panic!("Expected Plugin install command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_analyze_interactive() {
+        let cli = Cli::try_parse_from(["panoptes", "analyze", "/tmp/photos", "-i"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Analyze { interactive: true, .. })));
+
+        assert!(Cli::try_parse_from(["panoptes", "analyze", "/tmp/photos", "-i", "--dry-run"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_organize_command() {
+        let cli = Cli::try_parse_from([
+            "panoptes", "organize", "/tmp/downloads", "--dry-run", "-r"
+        ]).unwrap();
+
+        match cli.command {
+            Some(Commands::Organize { dir, dry_run, recursive }) => {
+                assert!(dry_run);
+                assert!(recursive);
+                assert_eq!(dir, PathBuf::from("/tmp/downloads"));
+            }
+            _ => panic!("Expected Organize command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_history_undo_by_id() {
+        let cli = Cli::try_parse_from([
+            "panoptes", "history", "undo", "--id", "3f2a9c1e"
+        ]).unwrap();
+
+        match cli.command {
+            Some(Commands::History { action: HistoryCommands::Undo { id, path, .. } }) => {
+                assert_eq!(id.as_deref(), Some("3f2a9c1e"));
+                assert!(path.is_none());
+            }
+            _ => panic!("Expected History Undo command"),
+        }
+
+        assert!(Cli::try_parse_from([
+            "panoptes", "history", "undo", "--id", "abc", "--path", "/tmp/x.jpg"
+        ]).is_err());
+    }
+
+    #[test]
+    fn test_cli_token_create() {
+        let cli = Cli::try_parse_from(["panoptes", "token", "create", "laptop"]).unwrap();
+
+        match cli.command {
+            Some(Commands::Token { action: TokenCommands::Create { name, role } }) => {
+                assert_eq!(name, "laptop");
+                assert_eq!(role, Role::Viewer);
+            }
+            _ => panic!("Expected Token Create command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_agent_register() {
+        let cli = Cli::try_parse_from([
+            "panoptes", "agent", "register", "--server", "https://panoptes.lan:8080", "--token", "secret"
+        ]).unwrap();
+
+        match cli.command {
+            Some(Commands::Agent { action: AgentCommands::Register { server, token, name } }) => {
+                assert_eq!(server, "https://panoptes.lan:8080");
+                assert_eq!(token, "secret");
+                assert_eq!(name, None);
+            }
+            _ => panic!("Expected Agent Register command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_tag_add() {
+        let cli = Cli::try_parse_from(["panoptes", "tag", "add", "7a82", "receipts", "2024"]).unwrap();
+
+        match cli.command {
+            Some(Commands::Tag { action: TagCommands::Add { file, tags } }) => {
+                assert_eq!(file, "7a82");
+                assert_eq!(tags, ["receipts", "2024"]);
+            }
+            _ => panic!("Expected Tag Add command"),
+        }
+
+        assert!(Cli::try_parse_from(["panoptes", "tag", "add", "7a82"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_jobs_add() {
+        let cli = Cli::try_parse_from(["panoptes", "jobs", "add", "thumbnail", "7a82", "--priority", "-5"]).unwrap();
+
+        match cli.command {
+            Some(Commands::Jobs { action: JobCommands::Add { kind, target, priority } }) => {
+                assert_eq!(kind, JobKind::Thumbnail);
+                assert_eq!(target, "7a82");
+                assert_eq!(priority, Some(-5));
+            }
+            _ => panic!("Expected Jobs Add command"),
+        }
+
+        assert!(Cli::try_parse_from(["panoptes", "jobs", "retry"]).is_err());
+        assert!(Cli::try_parse_from(["panoptes", "jobs", "retry", "3", "--dead"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_yes_after_subcommand() {
+        let cli = Cli::try_parse_from(["panoptes", "history", "clear", "-y"]).unwrap();
+        assert!(cli.yes);
+        assert!(matches!(cli.command, Some(Commands::History { action: HistoryCommands::Clear { force: false } })));
     }
 }
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Moving files without losing or clobbering any
+//!
+//! [`move_file`] renames a file where it can, never replacing one already at
+//! the destination, whoever put it there in the meantime: on Linux with
+//! `renameat2(RENAME_NOREPLACE)`, on macOS with `renamex_np(RENAME_EXCL)`,
+//! elsewhere (and on file systems without those) by hard-linking and
+//! unlinking.
+//!
+//! Across file systems it copies the file into a temporary file next to the
+//! destination, with the original's permissions and modification time,
+//! syncs it to disk and checks its hash against what was read, then renames
+//! it into place the same way and removes the original. The original stays
+//! when the copy doesn't match or it changed while being copied.
+
+use std::fs::{self, File, FileTimes, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::xattrs;
+
+/// Bytes read at a time when copying
+const BUFFER_SIZE: usize = 1 << 16;
+
+/// Move the file `from` to `to`, failing with [`io::ErrorKind::AlreadyExists`]
+/// when `to` is taken
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    // A new case for the same name, on a case-insensitive file system
+    if same_file(from, to) {
+        return fs::rename(from, to);
+    }
+    match rename_noreplace(from, to) {
+        Err(e) if is_cross_device(&e) => copy_across(from, to),
+        result => result.map(|()| sync_parent(to)),
+    }
+}
+
+fn is_cross_device(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    return e.raw_os_error() == Some(libc::EXDEV);
+    // ERROR_NOT_SAME_DEVICE
+    #[cfg(windows)]
+    return e.raw_os_error() == Some(17);
+    #[cfg(not(any(unix, windows)))]
+    return e.kind() == io::ErrorKind::CrossesDevices;
+}
+
+/// Whether `a` and `b` are names of the same file
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        b.exists() && matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+#[cfg(unix)]
+fn c_path(path: &Path) -> io::Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} holds a NUL byte", path)))
+}
+
+/// Rename `from` to `to` unless `to` exists
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let (from_c, to_c) = (c_path(from)?, c_path(to)?);
+        // Through syscall(), as not every libc wraps it
+        let done = unsafe {
+            libc::syscall(
+                libc::SYS_renameat2, libc::AT_FDCWD, from_c.as_ptr(), libc::AT_FDCWD, to_c.as_ptr(), libc::RENAME_NOREPLACE,
+            )
+        };
+        if done == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        // Kernels and file systems without the flag
+        if !matches!(e.raw_os_error(), Some(libc::EINVAL) | Some(libc::ENOSYS)) {
+            return Err(e);
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let (from_c, to_c) = (c_path(from)?, c_path(to)?);
+        if unsafe { libc::renamex_np(from_c.as_ptr(), to_c.as_ptr(), libc::RENAME_EXCL) } == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ENOTSUP) {
+            return Err(e);
+        }
+    }
+    link_and_unlink(from, to)
+}
+
+/// Rename by hard-linking, which fails when `to` exists; where files can't
+/// be hard-linked, by checking first
+fn link_and_unlink(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::hard_link(from, to) {
+        Ok(()) => fs::remove_file(from),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists || is_cross_device(&e) => Err(e),
+        Err(_) if to.exists() || to.is_symlink() => {
+            Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists", to.display())))
+        }
+        Err(_) => fs::rename(from, to),
+    }
+}
+
+/// Copy `from` to a temporary file in `to`'s directory, then rename it to
+/// `to` and remove `from`
+fn copy_across(from: &Path, to: &Path) -> io::Result<()> {
+    let dir = to.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = to.file_name().unwrap_or_default().to_string_lossy();
+    let temp = dir.join(format!(".{}.{}.part", name, uuid::Uuid::new_v4().simple()));
+
+    let copied = copy_verified(from, &temp).and_then(|()| rename_noreplace(&temp, to));
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    sync_parent(to);
+    xattrs::copy(from, to);
+    fs::remove_file(from)?;
+    sync_parent(from);
+    Ok(())
+}
+
+/// Copy `from` to the new file `to` and sync it, keeping the permissions and
+/// times of `from`; fails unless the copy's hash is that of `from`, and
+/// `from` was left alone meanwhile
+fn copy_verified(from: &Path, to: &Path) -> io::Result<()> {
+    let before = fs::metadata(from)?;
+    let mut source = File::open(from)?;
+    let mut target = OpenOptions::new().write(true).create_new(true).open(to)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    loop {
+        let n = source.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        target.write_all(&buffer[..n])?;
+    }
+    target.set_permissions(before.permissions())?;
+    let mut times = FileTimes::new().set_modified(before.modified()?);
+    if let Ok(accessed) = before.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    target.set_times(times)?;
+    target.sync_all()?;
+    drop(target);
+
+    let after = fs::metadata(from)?;
+    if after.len() != before.len() || after.modified()? != before.modified()? {
+        return Err(io::Error::other(format!("{} changed while being copied", from.display())));
+    }
+    let mut copy = blake3::Hasher::new();
+    copy.update_reader(File::open(to)?)?;
+    if copy.finalize() != hasher.finalize() {
+        return Err(io::Error::other(format!("The copy of {} doesn't match it", from.display())));
+    }
+    Ok(())
+}
+
+/// Sync the directory holding `path`, so a rename survives a crash
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_move_refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("scan.txt"), dir.path().join("report.txt"));
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "taken").unwrap();
+
+        let e = move_file(&from, &to).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&from).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to).unwrap(), "taken");
+    }
+
+    #[test]
+    fn test_case_only_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("scan.txt"), dir.path().join("Scan.txt"));
+        fs::write(&from, "scan").unwrap();
+
+        move_file(&from, &to).unwrap();
+        assert_eq!(names(dir.path()), ["Scan.txt"]);
+        assert_eq!(fs::read_to_string(&to).unwrap(), "scan");
+    }
+
+    #[test]
+    fn test_link_and_unlink_keeps_source_when_target_exists() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("scan.txt"), dir.path().join("report.txt"));
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "taken").unwrap();
+
+        let e = link_and_unlink(&from, &to).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&from).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to).unwrap(), "taken");
+    }
+
+    #[test]
+    fn test_copy_verified_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("scan.bin"), dir.path().join("copy.bin"));
+        let contents: Vec<u8> = (0..3 * BUFFER_SIZE).map(|i| (i % 251) as u8).collect();
+        fs::write(&from, &contents).unwrap();
+
+        copy_verified(&from, &to).unwrap();
+        assert_eq!(fs::read(&to).unwrap(), contents);
+        assert_eq!(fs::metadata(&to).unwrap().modified().unwrap(), fs::metadata(&from).unwrap().modified().unwrap());
+        // Never onto an existing file
+        assert_eq!(copy_verified(&from, &to).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_copy_verified_rejects_changing_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("scan.bin"), dir.path().join("copy.bin"));
+        fs::write(&from, vec![0u8; 32 << 20]).unwrap();
+
+        // Appends to the source from when the copy is created until it is done
+        let done = AtomicBool::new(false);
+        let copied = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !to.exists() && !done.load(Ordering::Relaxed) {
+                    std::hint::spin_loop();
+                }
+                let mut source = OpenOptions::new().append(true).open(&from).unwrap();
+                while !done.load(Ordering::Relaxed) {
+                    source.write_all(b"x").unwrap();
+                }
+            });
+            let copied = copy_verified(&from, &to);
+            done.store(true, Ordering::Relaxed);
+            copied
+        });
+        let e = copied.unwrap_err();
+        assert!(e.to_string().contains("changed while being copied"), "{}", e);
+    }
+}
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! MQTT, for home automation
+//!
+//! With `mqtt.broker` set, `panoptes watch` publishes the events webhooks get
+//! to `<prefix>/event/<event>`, and keeps `<prefix>/state` (queue depth,
+//! files analyzed today, whether paused) and `<prefix>/status` (`online` or
+//! `offline`) retained. It takes commands on:
+//!
+//! - `<prefix>/command/pause`: stop the job workers; `OFF` restarts them
+//! - `<prefix>/command/resume`: restart them
+//! - `<prefix>/command/rescan`: queue the files the watcher missed, as the
+//!   `rescan` scheduled task does
+//! - `<prefix>/command/analyze`: analyze the file whose path is the payload,
+//!   if it is in a watch directory
+//!
+//! With `mqtt.discovery`, Home Assistant is told of a Panoptes device with
+//! sensors for the queue depth and files today, a pause switch and a rescan
+//! button.
+
+use chrono::{Local, Utc};
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::Serialize;
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::config::MqttConfig;
+use crate::db::Database;
+use crate::jobs::JobState;
+use crate::webhooks::{EventSink, WebhookEvent};
+use crate::{PanoptesError, Result};
+
+const DEFAULT_PORT: u16 = 1883;
+
+/// Messages waiting to be sent before publishing blocks
+const CAPACITY: usize = 64;
+
+/// Wait before connecting again after the broker went away
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Something asked for on `<prefix>/command/...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Rescan,
+    Analyze(PathBuf),
+}
+
+impl Command {
+    /// The command published to `name` with `payload`
+    fn parse(name: &str, payload: &str) -> Option<Self> {
+        let payload = payload.trim();
+        match name {
+            "pause" if payload.eq_ignore_ascii_case("off") || payload.eq_ignore_ascii_case("false") => Some(Self::Resume),
+            "pause" => Some(Self::Pause),
+            "resume" => Some(Self::Resume),
+            "rescan" => Some(Self::Rescan),
+            "analyze" if !payload.is_empty() => Some(Self::Analyze(PathBuf::from(payload))),
+            _ => None,
+        }
+    }
+}
+
+/// What `<prefix>/state` holds
+#[derive(Debug, Clone, Serialize)]
+pub struct State {
+    /// Jobs waiting or running
+    pub queue_depth: i64,
+    /// Files analyzed since midnight
+    pub files_today: i64,
+    pub paused: bool,
+}
+
+impl State {
+    pub fn collect(db: &Database, paused: bool) -> Result<Self> {
+        let queue_depth = db.count_jobs()?.iter()
+            .filter(|(state, _)| matches!(state, JobState::Queued | JobState::Running))
+            .map(|(_, n)| n)
+            .sum();
+        let midnight = Local::now().date_naive().and_hms_opt(0, 0, 0)
+            .and_then(|t| t.and_local_timezone(Local).earliest())
+            .map_or_else(Utc::now, |t| t.with_timezone(&Utc));
+        let (files_today, _) = db.count_activity_since(midnight)?;
+        Ok(Self { queue_depth, files_today, paused })
+    }
+}
+
+/// A connection to the broker, kept up in the background
+pub struct Mqtt {
+    client: AsyncClient,
+    prefix: String,
+}
+
+/// Host and port of `broker`, an `mqtt://` URL
+fn address(broker: &str) -> Result<(String, u16)> {
+    let url = reqwest::Url::parse(broker)
+        .map_err(|e| PanoptesError::Config(format!("Bad MQTT broker {}: {}", broker, e)))?;
+    let host = url.host_str()
+        .ok_or_else(|| PanoptesError::Config(format!("No host in {}", broker)))?;
+    Ok((host.to_string(), url.port().unwrap_or(DEFAULT_PORT)))
+}
+
+/// `name` as an MQTT topic level and Home Assistant ID
+fn node_id(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
+
+impl Mqtt {
+    /// Connect to `mqtt.broker`, if set; commands arrive on the receiver
+    pub fn connect(config: &MqttConfig) -> Result<Option<(Self, mpsc::UnboundedReceiver<Command>)>> {
+        let Some(ref broker) = config.broker else {
+            return Ok(None);
+        };
+        let (host, port) = address(broker)?;
+        let client_id = config.client_id.clone()
+            .unwrap_or_else(|| format!("panoptes-{}", crate::agent::hostname()));
+        let prefix = config.topic_prefix.trim_matches('/').to_string();
+
+        let mut options = MqttOptions::new(client_id.clone(), host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(format!("{}/status", prefix), "offline", QoS::AtLeastOnce, true));
+        if let Some(ref username) = config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, CAPACITY);
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let mqtt = Self { client: client.clone(), prefix: prefix.clone() };
+        let discovery = config.discovery.then(|| discovery(config, &node_id(&client_id)));
+        let broker = broker.clone();
+
+        tokio::spawn(async move {
+            let command_prefix = format!("{}/command/", prefix);
+            let mut connected = false;
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to MQTT broker {}", broker);
+                        connected = true;
+                        // Published from here, as the event loop would wait on itself
+                        let mut messages = vec![(format!("{}/status", prefix), "online".to_string())];
+                        messages.extend(discovery.iter().flatten().cloned());
+                        let sent = client.try_subscribe(format!("{}+", command_prefix), QoS::AtLeastOnce)
+                            .and_then(|_| messages.into_iter().try_for_each(|(topic, payload)| {
+                                client.try_publish(topic, QoS::AtLeastOnce, true, payload)
+                            }));
+                        if let Err(e) = sent {
+                            warn!("Failed to subscribe to MQTT commands: {}", e);
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(message))) => {
+                        let Some(name) = message.topic.strip_prefix(&command_prefix) else { continue };
+                        let payload = String::from_utf8_lossy(&message.payload);
+                        match Command::parse(name, &payload) {
+                            Some(command) => {
+                                if commands.send(command).is_err() {
+                                    return;
+                                }
+                            }
+                            None => warn!("Unknown MQTT command on {}: {:?}", message.topic, payload),
+                        }
+                    }
+                    Ok(Event::Outgoing(rumqttc::Outgoing::Disconnect)) => return,
+                    Ok(_) => {}
+                    Err(e) => {
+                        if std::mem::take(&mut connected) {
+                            warn!("Lost the MQTT broker {}: {}", broker, e);
+                        } else {
+                            debug!("Failed to reach the MQTT broker {}: {}", broker, e);
+                        }
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        });
+        Ok(Some((mqtt, receiver)))
+    }
+
+    pub fn publish_state(&self, state: &State) {
+        let payload = json!(state).to_string();
+        if let Err(e) = self.client.try_publish(format!("{}/state", self.prefix), QoS::AtLeastOnce, true, payload) {
+            debug!("Not published to MQTT: {}", e);
+        }
+    }
+
+    /// Say Panoptes is going offline, and disconnect
+    pub async fn disconnect(&self) {
+        let offline = self.client.publish(format!("{}/status", self.prefix), QoS::AtLeastOnce, true, "offline");
+        if tokio::time::timeout(Duration::from_secs(2), offline).await.is_ok() {
+            let _ = self.client.disconnect().await;
+            // Time for the event loop to send both
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+impl EventSink for Mqtt {
+    /// Publish `event` to `<prefix>/event/<event>`
+    fn publish(&self, event: WebhookEvent, data: &serde_json::Value) {
+        let topic = format!("{}/event/{}", self.prefix, event.as_str());
+        let payload = json!({ "timestamp": Utc::now(), "data": data }).to_string();
+        if let Err(e) = self.client.try_publish(topic, QoS::AtLeastOnce, false, payload) {
+            debug!("Not published to MQTT: {}", e);
+        }
+    }
+}
+
+/// Home Assistant's discovery messages, as topics and payloads
+fn discovery(config: &MqttConfig, node: &str) -> Vec<(String, String)> {
+    let prefix = config.topic_prefix.trim_matches('/');
+    let state_topic = format!("{}/state", prefix);
+    let device = json!({
+        "identifiers": [node],
+        "name": "Panoptes",
+        "model": "Panoptes",
+        "manufacturer": "hyperpolymath",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let entity = |component: &str, object: &str, mut fields: serde_json::Value| {
+        let topic = format!("{}/{}/{}/{}/config", config.discovery_prefix.trim_matches('/'), component, node, object);
+        fields["unique_id"] = json!(format!("{}_{}", node, object));
+        fields["availability_topic"] = json!(format!("{}/status", prefix));
+        fields["device"] = device.clone();
+        (topic, fields.to_string())
+    };
+    vec![
+        entity("sensor", "queue_depth", json!({
+            "name": "Queue depth",
+            "state_topic": state_topic,
+            "value_template": "{{ value_json.queue_depth }}",
+            "unit_of_measurement": "jobs",
+            "state_class": "measurement",
+            "icon": "mdi:tray-full",
+        })),
+        entity("sensor", "files_today", json!({
+            "name": "Files today",
+            "state_topic": state_topic,
+            "value_template": "{{ value_json.files_today }}",
+            "unit_of_measurement": "files",
+            "state_class": "total_increasing",
+            "icon": "mdi:file-document-multiple",
+        })),
+        entity("switch", "paused", json!({
+            "name": "Paused",
+            "state_topic": state_topic,
+            "value_template": "{{ 'ON' if value_json.paused else 'OFF' }}",
+            "command_topic": format!("{}/command/pause", prefix),
+            "payload_on": "ON",
+            "payload_off": "OFF",
+            "icon": "mdi:pause",
+        })),
+        entity("button", "rescan", json!({
+            "name": "Rescan",
+            "command_topic": format!("{}/command/rescan", prefix),
+            "icon": "mdi:folder-refresh",
+        })),
+    ]
+}
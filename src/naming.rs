@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Names in any script
+//!
+//! Names keep their scripts: `Москва_2024`, `東京タワー` and `café_menu` come
+//! through [`crate::analyzers::clean_filename`] as they are, in the Unicode
+//! normalization form `rules.sanitizer.normalization` says (NFC by default,
+//! so the same name is always written the same way). With
+//! `rules.sanitizer.transliterate` they are spelled in ASCII instead
+//! (`Moskva_2024`, `DongJingtawa`, `cafe_menu`), but for the scripts in
+//! `rules.sanitizer.keep_scripts`.
+//!
+//! Lengths, as in `rules.max_length`, count what reads as one character (a
+//! grapheme: `é` written as `e` and an accent, a flag, a family emoji), and
+//! names are only ever cut between them.
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_script::{Script, UnicodeScript};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config::{Normalization, SanitizerConfig};
+
+/// Most bytes of a name, leaving room within the 255 most file systems allow
+/// for the extension and a number telling it from another
+pub const MAX_BYTES: usize = 200;
+
+/// The script named `name`, in full (`Cyrillic`) or by its code (`Cyrl`)
+pub fn script(name: &str) -> Option<Script> {
+    Script::from_full_name(name).or_else(|| Script::from_short_name(name))
+}
+
+/// `name` in the normalization form `form`
+pub fn normalize(name: &str, form: Normalization) -> String {
+    match form {
+        Normalization::Nfc => name.nfc().collect(),
+        Normalization::Nfd => name.nfd().collect(),
+        Normalization::Nfkc => name.nfkc().collect(),
+        Normalization::Keep => name.to_string(),
+    }
+}
+
+/// `name` in ASCII, but for the graphemes of the scripts in `keep`
+pub fn transliterate(name: &str, keep: &[Script]) -> String {
+    let mut ascii = String::with_capacity(name.len());
+    for grapheme in name.graphemes(true) {
+        let kept = grapheme.is_ascii() || grapheme.chars()
+            .map(|c| c.script())
+            .find(|s| !matches!(s, Script::Common | Script::Inherited))
+            .is_some_and(|s| keep.contains(&s));
+        if kept {
+            ascii.push_str(grapheme);
+        } else {
+            // Some spellings are punctuation file systems refuse, as `⁄` is `/`
+            ascii.extend(any_ascii::any_ascii(grapheme).chars()
+                .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')));
+        }
+    }
+    ascii
+}
+
+/// `raw` normalized and transliterated as `sanitizer` says
+pub fn prepare(raw: &str, sanitizer: &SanitizerConfig) -> String {
+    if !sanitizer.transliterate {
+        return normalize(raw, sanitizer.normalization);
+    }
+    let keep: Vec<Script> = sanitizer.keep_scripts.iter().filter_map(|name| script(name)).collect();
+    // Composed first, so accents go with their letters
+    normalize(&transliterate(&raw.nfc().collect::<String>(), &keep), sanitizer.normalization)
+}
+
+/// Length of `name` in graphemes
+pub fn length(name: &str) -> usize {
+    name.graphemes(true).count()
+}
+
+/// `name` cut to at most `max` graphemes and [`MAX_BYTES`] bytes
+pub fn truncate(name: &str, max: usize) -> &str {
+    truncate_to(name, max, MAX_BYTES)
+}
+
+/// `name` cut to at most `max_bytes` bytes, between graphemes
+pub fn truncate_bytes(name: &str, max_bytes: usize) -> &str {
+    truncate_to(name, usize::MAX, max_bytes)
+}
+
+fn truncate_to(name: &str, max: usize, max_bytes: usize) -> &str {
+    let mut end = 0;
+    for (count, (start, grapheme)) in name.grapheme_indices(true).enumerate() {
+        if count == max || start + grapheme.len() > max_bytes {
+            break;
+        }
+        end = start + grapheme.len();
+    }
+    &name[..end]
+}
@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Tags in the operating system's file manager
+//!
+//! With `native_tags.enabled`, analyzed files get their tags and category as
+//! Finder tags on macOS and in the Tags and Comments properties on Windows.
+//! `native_tags.categories` sets how each category shows: a Finder color,
+//! tags in place of the category's name, and a Windows comment. Finder tags
+//! set by hand are kept; on Windows the Tags property is replaced, and only
+//! files whose format has properties (photos, Office documents, music) can
+//! carry them. Linux file managers read the extended attributes of
+//! [`crate::xattrs`] instead, so nothing is written there.
+
+use std::path::Path;
+
+use crate::config::{NativeTagConfig, TagColor};
+
+/// A tag to give a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub name: String,
+    /// Finder color, for tags standing for the category
+    pub color: Option<TagColor>,
+}
+
+/// The tags a file with `tags` in `category` gets, and its Windows comment
+pub fn labels(tags: &[String], category: Option<&str>, config: &NativeTagConfig) -> (Vec<Label>, Option<String>) {
+    let mut labels: Vec<Label> = Vec::new();
+    let mut comment = None;
+    if let Some(category) = category {
+        let settings = config.categories.get(category).cloned().unwrap_or_default();
+        let names = if settings.tags.is_empty() { vec![category.to_string()] } else { settings.tags };
+        labels.extend(names.into_iter().map(|name| Label { name, color: settings.color }));
+        comment = Some(settings.comment.unwrap_or_else(|| category.to_string()));
+    }
+    for tag in tags {
+        if !labels.iter().any(|l| l.name.eq_ignore_ascii_case(tag)) {
+            labels.push(Label { name: tag.clone(), color: None });
+        }
+    }
+    (labels, comment)
+}
+
+/// Tag `path` in the file manager as `config` says
+pub fn apply(path: &Path, tags: &[String], category: Option<&str>, config: &NativeTagConfig) -> std::io::Result<()> {
+    let (labels, comment) = labels(tags, category, config);
+    platform::write(path, &labels, comment.as_deref())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Label;
+    use std::path::Path;
+
+    /// Finder's tags, a property list of `name` or `name\ncolor` strings
+    const USER_TAGS: &str = "com.apple.metadata:_kMDItemUserTags";
+    /// Names of the Finder tags Panoptes gave the file, so they can be replaced
+    const WRITTEN: &str = "user.panoptes.finder";
+
+    fn name(tag: &str) -> &str {
+        tag.split('\n').next().unwrap_or(tag)
+    }
+
+    pub fn write(path: &Path, labels: &[Label], _comment: Option<&str>) -> std::io::Result<()> {
+        let written: Vec<String> = xattr::get(path, WRITTEN)?
+            .map(|value| String::from_utf8_lossy(&value).split(',').map(str::to_string).collect())
+            .unwrap_or_default();
+        // A list Finder can't have written is dropped rather than kept half-read
+        let mut tags: Vec<String> = xattr::get(path, USER_TAGS)?
+            .and_then(|value| plist::from_bytes::<Vec<String>>(&value).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|tag| !written.iter().any(|w| w == name(tag)))
+            .collect();
+        for label in labels {
+            if tags.iter().any(|tag| name(tag) == label.name) {
+                continue;
+            }
+            tags.push(match label.color {
+                Some(color) => format!("{}\n{}", label.name, color.index()),
+                None => label.name.clone(),
+            });
+        }
+
+        let mut value = Vec::new();
+        plist::to_writer_binary(&mut value, &tags).map_err(std::io::Error::other)?;
+        xattr::set(path, USER_TAGS, &value)?;
+        let names: Vec<&str> = labels.iter().map(|l| l.name.as_str()).collect();
+        xattr::set(path, WRITTEN, names.join(",").as_bytes())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Label;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows::core::{PCWSTR, PROPVARIANT};
+    use windows::Win32::Storage::EnhancedStorage::{PKEY_Comment, PKEY_Keywords};
+    use windows::Win32::System::Com::StructuredStorage::{InitPropVariantFromStringVector, PropVariantChangeType, PVCHF_DEFAULT};
+    use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, IBindCtx, COINIT_MULTITHREADED};
+    use windows::Win32::System::Variant::VT_LPWSTR;
+    use windows::Win32::UI::Shell::PropertiesSystem::{IPropertyStore, SHGetPropertyStoreFromParsingName, GPS_READWRITE};
+
+    /// A null-terminated UTF-16 string
+    fn wide(text: &std::ffi::OsStr) -> Vec<u16> {
+        text.encode_wide().chain(Some(0)).collect()
+    }
+
+    pub fn write(path: &Path, labels: &[Label], comment: Option<&str>) -> std::io::Result<()> {
+        unsafe {
+            let initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+            let result = set_properties(path, labels, comment);
+            if initialized {
+                CoUninitialize();
+            }
+            result.map_err(std::io::Error::from)
+        }
+    }
+
+    unsafe fn set_properties(path: &Path, labels: &[Label], comment: Option<&str>) -> windows::core::Result<()> {
+        let path = wide(path.as_os_str());
+        let store: IPropertyStore = SHGetPropertyStoreFromParsingName(PCWSTR(path.as_ptr()), None::<&IBindCtx>, GPS_READWRITE)?;
+
+        let names: Vec<Vec<u16>> = labels.iter().map(|l| wide(l.name.as_ref())).collect();
+        let names: Vec<PCWSTR> = names.iter().map(|n| PCWSTR(n.as_ptr())).collect();
+        store.SetValue(&PKEY_Keywords, &InitPropVariantFromStringVector(Some(&names))?)?;
+        if let Some(comment) = comment {
+            let mut value = PROPVARIANT::default();
+            PropVariantChangeType(&mut value, &PROPVARIANT::from(comment), PVCHF_DEFAULT, VT_LPWSTR)?;
+            store.SetValue(&PKEY_Comment, &value)?;
+        }
+        store.Commit()
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use super::Label;
+    use std::path::Path;
+
+    pub fn write(_path: &Path, _labels: &[Label], _comment: Option<&str>) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Notifications for people, rather than programs
+//!
+//! Where [`crate::webhooks`] post JSON for other services, notifications are
+//! short messages sent to a desktop, an [ntfy](https://ntfy.sh) topic, a
+//! Slack or Discord channel, or by email. Each channel of
+//! `notifications.channels` gets the events it lists, or all of them:
+//!
+//! ```json
+//! "notifications": {
+//!   "quarantine_limit": 50,
+//!   "channels": [
+//!     { "type": "desktop", "events": ["renamed"] },
+//!     { "type": "ntfy", "url": "https://ntfy.sh/my-panoptes", "events": ["quarantine_full", "engine_down"] },
+//!     { "type": "email", "host": "smtp.example.com", "username": "me", "password": "${env:SMTP_PASSWORD}",
+//!       "from": "panoptes@example.com", "to": ["me@example.com"], "events": ["daily_summary"] }
+//!   ]
+//! }
+//! ```
+//!
+//! Watch mode sends them; failures are logged and not retried.
+
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::db::{Database, ReviewStatus};
+
+/// How long a channel has to take a notification
+const SEND_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Something a notification channel can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// A file was renamed or moved
+    Renamed,
+    /// A file was queued for review
+    Queued,
+    /// Analyzing or renaming a file failed
+    Error,
+    /// A rule in `rules.actions` with a `notify` action matched a file
+    Rule,
+    /// The review queue grew past `notifications.quarantine_limit` files
+    QuarantineFull,
+    /// The AI engine stopped answering
+    EngineDown,
+    /// The AI engine is answering again
+    EngineUp,
+    /// What was done in the last day, at `notifications.summary_time`
+    DailySummary,
+}
+
+/// Notification channels, and when the events that aren't about one file are sent
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct NotificationConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<ChannelConfig>,
+    /// Send `quarantine_full` when more files than this wait for review
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine_limit: Option<i64>,
+    /// Local time of the daily summary, as `HH:MM`
+    #[serde(default = "default_summary_time")]
+    pub summary_time: String,
+}
+
+fn default_summary_time() -> String {
+    "08:00".to_string()
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            channels: Vec::new(),
+            quarantine_limit: None,
+            summary_time: default_summary_time(),
+        }
+    }
+}
+
+impl NotificationConfig {
+    /// Whether any channel gets `event`
+    pub fn wants(&self, event: NotificationEvent) -> bool {
+        self.channels.iter().any(|c| c.wants(event))
+    }
+}
+
+/// A channel and the events sent to it
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ChannelConfig {
+    #[serde(flatten)]
+    pub channel: Channel,
+    /// Events sent to this channel; all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<NotificationEvent>,
+}
+
+impl ChannelConfig {
+    fn wants(&self, event: NotificationEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Where notifications go
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Channel {
+    /// The desktop of the user running Panoptes: `notify-send` on Linux and
+    /// other Unix, Notification Center on macOS, a toast on Windows
+    Desktop,
+    /// An ntfy topic URL, with an access token for protected topics
+    Ntfy {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    /// A Slack incoming webhook URL
+    Slack { url: String },
+    /// A Discord webhook URL
+    Discord { url: String },
+    /// Mail sent through an SMTP server
+    Email(EmailConfig),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct EmailConfig {
+    /// The SMTP server
+    pub host: String,
+    /// Its port, when not the usual one for `security`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Sender, as `panoptes@example.com` or `Panoptes <panoptes@example.com>`
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// How the connection to an SMTP server is secured
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgraded with STARTTLS, on port 587
+    #[default]
+    Starttls,
+    /// TLS from the start, on port 465
+    Tls,
+    /// Unencrypted, on port 25; only for a server on the same machine
+    None,
+}
+
+/// Everything wrong with `config`, for [`crate::AppConfig::problems`]
+pub fn problems(config: &NotificationConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    if NaiveTime::parse_from_str(&config.summary_time, "%H:%M").is_err() {
+        problems.push("notifications.summary_time must be a time of day as HH:MM, like 08:00".to_string());
+    }
+    if config.quarantine_limit.is_some_and(|limit| limit < 1) {
+        problems.push("notifications.quarantine_limit must be at least 1".to_string());
+    }
+    for channel in &config.channels {
+        match &channel.channel {
+            Channel::Desktop => {}
+            Channel::Ntfy { url, .. } | Channel::Slack { url } | Channel::Discord { url } => {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    problems.push("notifications.channels[].url must be an http:// or https:// URL".to_string());
+                }
+            }
+            Channel::Email(email) => {
+                if email.host.trim().is_empty() {
+                    problems.push("notifications.channels[].host must not be empty".to_string());
+                }
+                if email.to.is_empty() {
+                    problems.push("notifications.channels[].to needs at least one address".to_string());
+                }
+                for address in std::iter::once(&email.from).chain(&email.to) {
+                    if let Err(e) = address.parse::<Mailbox>() {
+                        problems.push(format!("notifications.channels[]: '{}' is not an email address: {}", address, e));
+                    }
+                }
+                if email.username.is_some() != email.password.is_some() {
+                    problems.push("notifications.channels[] needs both username and password, or neither".to_string());
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// A notification: a short title and a line or two more
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub title: String,
+    pub body: String,
+}
+
+impl Message {
+    pub fn new(title: impl Into<String>, body: impl Into<String>) -> Self {
+        Self { title: title.into(), body: body.into() }
+    }
+}
+
+/// Sends notifications in the background
+pub struct Notifier {
+    http: reqwest::Client,
+    pending: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Send `message` to every channel of `config` subscribed to `event`; returns immediately
+    pub fn send(&self, config: &NotificationConfig, event: NotificationEvent, message: Message) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|task| !task.is_finished());
+        for channel in config.channels.iter().filter(|c| c.wants(event)) {
+            let (http, channel, message) = (self.http.clone(), channel.channel.clone(), message.clone());
+            pending.push(tokio::spawn(async move {
+                match tokio::time::timeout(SEND_TIMEOUT, deliver(&http, &channel, &message)).await {
+                    Ok(Ok(())) => debug!("Sent {:?} notification to {}", event, channel.kind()),
+                    Ok(Err(e)) => warn!("Failed to send {} notification: {}", channel.kind(), e),
+                    Err(_) => warn!("Failed to send {} notification: no answer in {}s", channel.kind(), SEND_TIMEOUT.as_secs()),
+                }
+            }));
+        }
+    }
+
+    /// Wait for notifications still being sent
+    pub async fn flush(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        for task in pending {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Channel {
+    fn kind(&self) -> &'static str {
+        match self {
+            Channel::Desktop => "desktop",
+            Channel::Ntfy { .. } => "ntfy",
+            Channel::Slack { .. } => "Slack",
+            Channel::Discord { .. } => "Discord",
+            Channel::Email(_) => "email",
+        }
+    }
+}
+
+async fn deliver(http: &reqwest::Client, channel: &Channel, message: &Message) -> Result<(), String> {
+    let request = match channel {
+        Channel::Desktop => return desktop(message).await,
+        Channel::Email(email) => return send_email(email, message).await,
+        Channel::Ntfy { url, token } => {
+            let request = http.post(url).header("Title", &message.title).body(message.body.clone());
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        }
+        Channel::Slack { url } => {
+            http.post(url).json(&serde_json::json!({ "text": format!("*{}*\n{}", message.title, message.body) }))
+        }
+        Channel::Discord { url } => {
+            http.post(url).json(&serde_json::json!({ "content": format!("**{}**\n{}", message.title, message.body) }))
+        }
+    };
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status()))
+    }
+}
+
+/// Show `message` on the desktop; the text goes through the environment so
+/// it needn't be quoted for a script
+async fn desktop(message: &Message) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = tokio::process::Command::new("osascript");
+        command.arg("-e").arg(
+            r#"display notification (system attribute "PANOPTES_BODY") with title (system attribute "PANOPTES_TITLE")"#,
+        );
+        command
+    } else if cfg!(windows) {
+        let mut command = tokio::process::Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", WINDOWS_TOAST]);
+        command
+    } else {
+        let mut command = tokio::process::Command::new("notify-send");
+        command.args(["--app-name=Panoptes", &message.title, &message.body]);
+        command
+    };
+    let program = command.as_std().get_program().to_string_lossy().into_owned();
+    let output = command
+        .env("PANOPTES_TITLE", &message.title)
+        .env("PANOPTES_BODY", &message.body)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("cannot run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Shows a toast with `$env:PANOPTES_TITLE` and `$env:PANOPTES_BODY`
+const WINDOWS_TOAST: &str = "\
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+$toast = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+$text = $toast.GetElementsByTagName('text'); \
+$text.Item(0).AppendChild($toast.CreateTextNode($env:PANOPTES_TITLE)) > $null; \
+$text.Item(1).AppendChild($toast.CreateTextNode($env:PANOPTES_BODY)) > $null; \
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('Panoptes').Show([Windows.UI.Notifications.ToastNotification]::new($toast))";
+
+async fn send_email(email: &EmailConfig, message: &Message) -> Result<(), String> {
+    let mut mail = lettre::Message::builder()
+        .from(email.from.parse::<Mailbox>().map_err(|e| e.to_string())?)
+        .subject(&message.title);
+    for to in &email.to {
+        mail = mail.to(to.parse::<Mailbox>().map_err(|e| e.to_string())?);
+    }
+    let mail = mail.body(message.body.clone()).map_err(|e| e.to_string())?;
+
+    let mut transport = match email.security {
+        SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&email.host).map_err(|e| e.to_string())?,
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&email.host).map_err(|e| e.to_string())?,
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&email.host),
+    };
+    if let Some(port) = email.port {
+        transport = transport.port(port);
+    }
+    if let (Some(username), Some(password)) = (&email.username, &email.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    transport.build().send(mail).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Message for [`NotificationEvent::Renamed`]
+pub fn renamed(from: &Path, to: &Path) -> Message {
+    let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let body = if from.parent() == to.parent() {
+        format!("to {}", name(to))
+    } else {
+        format!("to {}", to.display())
+    };
+    Message::new(format!("Renamed {}", name(from)), body)
+}
+
+/// Message for [`NotificationEvent::Queued`]
+pub fn queued(path: &Path, suggested_name: &str, confidence: f64) -> Message {
+    Message::new(
+        format!("{} needs review", path.file_name().unwrap_or_default().to_string_lossy()),
+        format!("Suggested {} with {:.0}% confidence", suggested_name, confidence * 100.0),
+    )
+}
+
+/// Message for [`NotificationEvent::Error`]
+pub fn failed(path: &Path, error: &str) -> Message {
+    Message::new(format!("Failed to process {}", path.file_name().unwrap_or_default().to_string_lossy()), error)
+}
+
+/// Message for [`NotificationEvent::Rule`]; `path` is where the file is now
+pub fn rule(rule: &str, message: Option<&str>, path: &Path) -> Message {
+    let body = match message {
+        Some(message) => format!("{}\n{}", message, path.display()),
+        None => path.display().to_string(),
+    };
+    Message::new(format!("Rule {}", rule), body)
+}
+
+/// Message for [`NotificationEvent::QuarantineFull`]
+pub fn quarantine_full(pending: i64, review_url: Option<String>) -> Message {
+    let body = match review_url {
+        Some(url) => format!("{} files are waiting for review at {}", pending, url),
+        None => format!("{} files are waiting for review", pending),
+    };
+    Message::new("Review queue is filling up", body)
+}
+
+/// Message for [`NotificationEvent::EngineDown`]
+pub fn engine_down(error: &str) -> Message {
+    Message::new("AI engine unavailable", format!("Files can't be analyzed until it is back: {}", error))
+}
+
+/// Message for [`NotificationEvent::EngineUp`]
+pub fn engine_up() -> Message {
+    Message::new("AI engine is available again", "Files are being analyzed again")
+}
+
+/// Message for [`NotificationEvent::DailySummary`], about the day before `now`
+pub fn daily_summary(db: &Database, now: DateTime<Utc>) -> crate::Result<Message> {
+    let (analyzed, renamed) = db.count_activity_since(now - chrono::Duration::days(1))?;
+    let pending = db.count_files_by_status(ReviewStatus::Pending)?;
+    Ok(Message::new(
+        "Panoptes daily summary",
+        format!("In the last day: {} files analyzed, {} renamed or moved. {} waiting for review.", analyzed, renamed, pending),
+    ))
+}
+
+/// Send the daily summary at `notifications.summary_time` each day, for as
+/// long as the task runs
+pub async fn send_daily_summaries(config: NotificationConfig, db: Database, notifier: std::sync::Arc<Notifier>) {
+    let Ok(at) = NaiveTime::parse_from_str(&config.summary_time, "%H:%M") else {
+        return;
+    };
+    loop {
+        let now = Local::now();
+        let mut next = now.date_naive().and_time(at);
+        if next <= now.naive_local() {
+            next += chrono::Duration::days(1);
+        }
+        let wait = (next - now.naive_local()).to_std().unwrap_or(Duration::from_secs(60));
+        tokio::time::sleep(wait).await;
+
+        match daily_summary(&db, Utc::now()) {
+            Ok(message) => {
+                info!("Sending the daily summary");
+                notifier.send(&config, NotificationEvent::DailySummary, message);
+            }
+            Err(e) => warn!("Failed to put together the daily summary: {}", e),
+        }
+    }
+}
@@ -2,14 +2,39 @@
 // SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
 
 //! Ollama API client for local AI inference
+//!
+//! Every client in the process shares one limit on requests in flight
+//! (`ai_engine.max_concurrent`), so parallel analyses queue here instead of
+//! overloading the engine.
 
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
-use tracing::{debug, warn};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::{debug, field, info_span, warn, Instrument, Span};
 
 use crate::{PanoptesError, Result};
 
+/// Requests in flight when [`limit_concurrency`] was never called
+const DEFAULT_MAX_CONCURRENT: usize = 2;
+
+static REQUEST_SLOTS: OnceLock<Semaphore> = OnceLock::new();
+
+/// Limit the requests this process sends to the engine at once. Only the first
+/// call takes effect, and only if it comes before the first request.
+pub fn limit_concurrency(max: usize) {
+    let _ = REQUEST_SLOTS.set(Semaphore::new(max.max(1)));
+}
+
+/// Wait for a free request slot
+async fn request_slot() -> Result<SemaphorePermit<'static>> {
+    REQUEST_SLOTS.get_or_init(|| Semaphore::new(DEFAULT_MAX_CONCURRENT))
+        .acquire()
+        .await
+        .map_err(|e| PanoptesError::OllamaUnavailable(e.to_string()))
+}
+
 /// Ollama API client
 pub struct OllamaClient {
     client: Client,
@@ -30,6 +55,17 @@ struct GenerateResponse {
     response: String,
 }
 
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
 #[derive(Deserialize)]
 struct TagsResponse {
     models: Vec<ModelInfo>,
@@ -99,32 +135,13 @@ pub async fn model_available(&self, model: &str) -> Result<bool> {
 
     /// Generate text completion
     pub async fn generate(&self, model: &str, prompt: &str) -> Result<String> {
-        let url = format!("{}/api/generate", self.base_url);
-
         let request = GenerateRequest {
             model: model.to_string(),
             prompt: prompt.to_string(),
             stream: false,
             images: None,
         };
-
-        debug!("Sending request to Ollama: model={}", model);
-
-        let response = self.client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(PanoptesError::OllamaUnavailable(format!(
-                "Ollama returned status {}",
-                response.status()
-            )));
-        }
-
-        let result: GenerateResponse = response.json().await?;
-        Ok(result.response)
+        self.send(request).await
     }
 
     /// Generate with image (for vision models)
@@ -134,32 +151,78 @@ pub async fn generate_with_image(
         prompt: &str,
         image_base64: &str,
     ) -> Result<String> {
-        let url = format!("{}/api/generate", self.base_url);
-
         let request = GenerateRequest {
             model: model.to_string(),
             prompt: prompt.to_string(),
             stream: false,
             images: Some(vec![image_base64.to_string()]),
         };
+        self.send(request).await
+    }
 
-        debug!("Sending vision request to Ollama: model={}", model);
-
-        let response = self.client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await?;
+    /// Embedding of `text`, from an embedding model
+    pub async fn embed(&self, model: &str, text: &str) -> Result<Vec<f32>> {
+        let url = format!("{}/api/embeddings", self.base_url);
+        let span = info_span!("llm", model = %model, images = 0, wait_ms = field::Empty);
+        async move {
+            let waiting = Instant::now();
+            let _slot = request_slot().await?;
+            Span::current().record("wait_ms", waiting.elapsed().as_millis() as u64);
+            debug!("Sending embedding request to Ollama: model={}", model);
+
+            let response = self.client
+                .post(&url)
+                .json(&EmbeddingRequest { model, prompt: text })
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                return Err(PanoptesError::OllamaUnavailable(format!(
+                    "Ollama returned status {}",
+                    response.status()
+                )));
+            }
 
-        if !response.status().is_success() {
-            return Err(PanoptesError::OllamaUnavailable(format!(
-                "Ollama returned status {}",
-                response.status()
-            )));
+            let result: EmbeddingResponse = response.json().await?;
+            if result.embedding.is_empty() {
+                return Err(PanoptesError::Analysis(format!("{} returned no embedding", model)));
+            }
+            Ok(result.embedding)
         }
+        .instrument(span)
+        .await
+    }
 
-        let result: GenerateResponse = response.json().await?;
-        Ok(result.response)
+    /// Send a generate request once a request slot is free, in an `llm` span
+    /// recording how long that took
+    async fn send(&self, request: GenerateRequest) -> Result<String> {
+        let url = format!("{}/api/generate", self.base_url);
+        let images = request.images.as_ref().map_or(0, Vec::len);
+        let span = info_span!("llm", model = %request.model, images, wait_ms = field::Empty);
+        async move {
+            let waiting = Instant::now();
+            let _slot = request_slot().await?;
+            Span::current().record("wait_ms", waiting.elapsed().as_millis() as u64);
+            debug!("Sending {} request to Ollama: model={}", if images > 0 { "vision" } else { "text" }, request.model);
+
+            let response = self.client
+                .post(&url)
+                .json(&request)
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                return Err(PanoptesError::OllamaUnavailable(format!(
+                    "Ollama returned status {}",
+                    response.status()
+                )));
+            }
+
+            let result: GenerateResponse = response.json().await?;
+            Ok(result.response)
+        }
+        .instrument(span)
+        .await
     }
 
     /// Generate with retry logic
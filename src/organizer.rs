@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Sorting files into per-category directories
+//!
+//! Each category goes to the directory `organize.destinations` maps it to, or
+//! to one named after the category; relative directories are inside the
+//! directory being organized. A file's category comes from its database record
+//! or, for files never analyzed, is inferred from the name. Moves are recorded
+//! in history, so `panoptes history undo` puts files back.
+//!
+//! Watch mode also routes renamed files by `rules.destinations`, which send a
+//! category or tag anywhere, ahead of organizing.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::analyzers::{calculate_file_hash, infer_category};
+use crate::config::{DestinationRule, OrganizeCollision, OrganizeConfig};
+use crate::db::{Database, FileRecord};
+use crate::history::History;
+use crate::renamer::move_recorded;
+use crate::Result;
+
+/// What organizing does with a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Placement {
+    /// Move it to this path
+    Move(PathBuf),
+    /// It is already in its category's directory
+    InPlace,
+    /// It has no category and `organize.uncategorized` is unset
+    Uncategorized,
+    /// Its name is taken at this path and collisions are skipped
+    Collision(PathBuf),
+}
+
+/// Directory under `root` for files of `category`
+pub fn category_dir(root: &Path, category: Option<&str>, config: &OrganizeConfig) -> Option<PathBuf> {
+    let dir = match category.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(category) => match config.destinations.get(category) {
+            Some(dir) => dir.clone(),
+            // Categories come from the model, so keep them to a single directory name
+            None => {
+                let name = category.replace(['/', '\\'], "-");
+                if name.chars().all(|c| c == '.') {
+                    return None;
+                }
+                name
+            }
+        },
+        None => config.uncategorized.clone()?,
+    };
+    Some(root.join(dir))
+}
+
+/// `dir` with a leading `~` replaced by the home directory
+pub fn expand_home(dir: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match (dir.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(dir),
+    }
+}
+
+/// Directory the first of `rules` matching a file in `category` with `tags`
+/// sends it to
+pub fn route(category: Option<&str>, tags: &[String], rules: &[DestinationRule]) -> Option<PathBuf> {
+    let rule = rules.iter().find(|rule| {
+        let category_matches = rule.category.as_deref()
+            .map_or(true, |wanted| category.is_some_and(|c| c.trim().eq_ignore_ascii_case(wanted.trim())));
+        let tag_matches = rule.tag.as_deref()
+            .map_or(true, |wanted| tags.iter().any(|t| t.trim().eq_ignore_ascii_case(wanted.trim())));
+        category_matches && tag_matches
+    })?;
+    Some(expand_home(rule.directory.trim()))
+}
+
+/// Plans where files under one directory go. Targets handed out are reserved,
+/// so files planned together (and dry runs) never collide with each other.
+pub struct Organizer<'a> {
+    root: PathBuf,
+    config: &'a OrganizeConfig,
+    reserved: HashSet<PathBuf>,
+}
+
+impl<'a> Organizer<'a> {
+    pub fn new(root: impl Into<PathBuf>, config: &'a OrganizeConfig) -> Self {
+        Self { root: root.into(), config, reserved: HashSet::new() }
+    }
+
+    /// Where `file` goes given its category
+    pub fn place(&mut self, file: &Path, category: Option<&str>) -> Placement {
+        let Some(dir) = category_dir(&self.root, category, self.config) else {
+            return Placement::Uncategorized;
+        };
+        if file.parent() == Some(dir.as_path()) {
+            return Placement::InPlace;
+        }
+
+        let name = file.file_name().unwrap_or_default();
+        let mut target = dir.join(name);
+        if self.is_taken(&target) {
+            if self.config.on_collision == OrganizeCollision::Skip {
+                return Placement::Collision(target);
+            }
+            let path = Path::new(name);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            let mut n = 2;
+            while self.is_taken(&target) {
+                target = dir.join(format!("{}_{}{}", stem, n, ext));
+                n += 1;
+            }
+        }
+        self.reserved.insert(target.clone());
+        Placement::Move(target)
+    }
+
+    fn is_taken(&self, path: &Path) -> bool {
+        path.exists() || self.reserved.contains(path)
+    }
+}
+
+/// The record for `file`, looked up by the path as given and as an absolute path
+pub fn find_record(db: &Database, file: &Path) -> Result<Option<FileRecord>> {
+    if let Some(record) = db.find_file_by_path(file)? {
+        return Ok(Some(record));
+    }
+    match std::env::current_dir() {
+        Ok(cwd) if file.is_relative() => db.find_file_by_path(&cwd.join(file)),
+        _ => Ok(None),
+    }
+}
+
+/// Category of `file`: its record's when it has one, inferred from the name otherwise
+pub fn category_of(file: &Path, record: Option<&FileRecord>) -> Option<String> {
+    match record {
+        Some(record) => record.category.clone(),
+        None => {
+            let stem = file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+            infer_category(stem, ext)
+        }
+    }
+}
+
+/// Move `file` to `target`, creating its directory, and record the move in history
+pub fn move_file(
+    file: &Path,
+    target: &Path,
+    record: Option<&FileRecord>,
+    history: &History,
+    session_id: Option<&str>,
+) -> Result<()> {
+    let file_hash = match record {
+        Some(record) => record.file_hash.clone(),
+        None => calculate_file_hash(file)?,
+    };
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    move_recorded(file, target, || {
+        history.record_move(file, target, file_hash, record.map(|r| r.id.clone()), session_id.map(String::from)).map(drop)
+    })?;
+    info!("Moved to: {:?}", target);
+    Ok(())
+}
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Where Panoptes keeps its files by default
+//!
+//! The config file goes in the user's config directory, and the database,
+//! thumbnail cache and plugins in their data directory:
+//!
+//! | Platform | Config | Data |
+//! |----------|--------|------|
+//! | Linux and other Unix | `$XDG_CONFIG_HOME/panoptes` (`~/.config/panoptes`) | `$XDG_DATA_HOME/panoptes` (`~/.local/share/panoptes`) |
+//! | macOS | `~/Library/Application Support/panoptes` | `~/Library/Application Support/panoptes` |
+//! | Windows | `%APPDATA%\panoptes` | `%LOCALAPPDATA%\panoptes` |
+//!
+//! A `config.json` in the current directory is still used when there is
+//! one, for per-project setups. State used to go in the current directory
+//! too; `panoptes config migrate` moves it (see [`legacy`]).
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::config::{save_json, AppConfig};
+use crate::Result;
+
+const APP_DIR: &str = "panoptes";
+
+const CONFIG_FILE: &str = "config.json";
+
+/// `var` if it names an absolute directory, as the XDG spec requires
+fn env_dir(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var).map(PathBuf::from).filter(|dir| dir.is_absolute())
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+/// The user's directory for Panoptes' configuration
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| home().map(|home| home.join(".config")))
+    };
+    base.map(|dir| dir.join(APP_DIR))
+}
+
+/// The user's directory for Panoptes' database, caches and plugins
+pub fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env_dir("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| home().map(|home| home.join(".local/share")))
+    };
+    base.map(|dir| dir.join(APP_DIR))
+}
+
+/// The user's config file
+pub fn user_config() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(CONFIG_FILE))
+}
+
+/// The config file used without `--config`: `config.json` in the current
+/// directory if there is one, or else the user's
+pub fn default_config() -> PathBuf {
+    let local = PathBuf::from(CONFIG_FILE);
+    if local.exists() {
+        return local;
+    }
+    user_config().unwrap_or(local)
+}
+
+/// `name` in the data directory, or in the current directory when there is
+/// no home directory to put it under
+pub fn data_path(name: &str) -> String {
+    match data_dir() {
+        Some(dir) => dir.join(name).to_string_lossy().into_owned(),
+        None => name.to_string(),
+    }
+}
+
+/// State at a setting's old default, in the current directory
+#[derive(Debug, Clone)]
+pub struct Legacy {
+    /// Dotted path of the setting, e.g. `database.path`
+    pub setting: &'static str,
+    pub from: PathBuf,
+    /// The setting's default now
+    pub to: PathBuf,
+    /// Whether the config still names the old place, so it's still in use
+    pub pinned: bool,
+}
+
+/// The database, thumbnail cache and plugin directory left at their old
+/// defaults in the current directory, which have nothing at the new ones yet
+pub fn legacy(config: &AppConfig) -> Vec<Legacy> {
+    let defaults = AppConfig::default();
+    [
+        ("database.path", "panoptes.db", &config.database.path, &defaults.database.path),
+        ("thumbnails.cache_dir", "thumbnails", &config.thumbnails.cache_dir, &defaults.thumbnails.cache_dir),
+        ("plugins.directory", "plugins", &config.plugins.directory, &defaults.plugins.directory),
+    ]
+    .into_iter()
+    .filter(|(_, old, current, default)| old != default && (current == old || current == default))
+    .map(|(setting, old, current, default)| Legacy {
+        setting,
+        from: PathBuf::from(old),
+        to: PathBuf::from(default),
+        pinned: current == old,
+    })
+    .filter(|legacy| legacy.from.exists() && !legacy.to.exists())
+    .collect()
+}
+
+/// Move `from` to `to`, copying when they are on different filesystems
+fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if from.is_file() {
+        return crate::mover::move_file(from, to);
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    crate::plugins::copy_dir(from, to)?;
+    std::fs::remove_dir_all(from)
+}
+
+/// Move `legacy` state to its new place, and take the setting naming its
+/// old place out of the project's config file at `project` so the new
+/// default applies; returns whether the setting was taken out
+pub fn migrate(legacy: &Legacy, project: &Path) -> Result<bool> {
+    move_path(&legacy.from, &legacy.to)?;
+    // SQLite's journal files go with the database
+    if legacy.setting == "database.path" {
+        for suffix in ["-wal", "-shm"] {
+            let mut from = legacy.from.clone().into_os_string();
+            from.push(suffix);
+            let mut to = legacy.to.clone().into_os_string();
+            to.push(suffix);
+            if Path::new(&from).exists() {
+                move_path(Path::new(&from), Path::new(&to))?;
+            }
+        }
+    }
+
+    if legacy.pinned && project.is_file() {
+        let mut file: Value = serde_json::from_str(&std::fs::read_to_string(project)?)?;
+        let (section, key) = legacy.setting.split_once('.').unwrap_or((legacy.setting, ""));
+        let old = legacy.from.to_string_lossy();
+        let removed = file.get_mut(section)
+            .and_then(Value::as_object_mut)
+            .filter(|section| section.get(key).and_then(Value::as_str) == Some(old.as_ref()))
+            .and_then(|section| section.remove(key));
+        if removed.is_some() {
+            save_json(&file, project)?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Analyzer plugins: programs that analyze file types Panoptes doesn't, or
+//! analyze them differently
+//!
+//! A plugin is a directory in `plugins.directory` with a `plugin.json`
+//! manifest:
+//!
+//! ```json
+//! {
+//!   "name": "heic",
+//!   "version": "1.0.0",
+//!   "description": "HEIC photos via heif-convert",
+//!   "extensions": ["heic", "heif"],
+//!   "command": ["./analyze.sh"],
+//!   "priority": 110
+//! }
+//! ```
+//!
+//! To analyze a file, `command` runs in the plugin's directory with the
+//! file's path as its last argument and prints a JSON object with
+//! `suggested_name` and `confidence`, and optionally `category`, `tags` and
+//! `metadata` (see [`crate::analyzers::plugin`]). Of the analyzers handling a
+//! file the one with the highest priority is used; the built-in ones range
+//! from 40 (archives) to 100 (images), and plugins default to 110, so they
+//! take over the extensions they name. A disabled plugin stays installed but
+//! isn't used.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{PanoptesError, Result};
+
+pub const MANIFEST: &str = "plugin.json";
+
+/// Marks a plugin as disabled when present in its directory
+const DISABLED_MARKER: &str = ".disabled";
+
+/// Key of the analysis metadata naming the plugin that analyzed a file
+pub const METADATA_KEY: &str = "plugin";
+
+fn default_priority() -> u8 { 110 }
+
+/// A plugin's `plugin.json`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Extensions of the files it analyzes, without the dot
+    pub extensions: Vec<String>,
+    /// Program and arguments; a program path with a `/` is relative to the plugin's directory
+    pub command: Vec<String>,
+    #[serde(default = "default_priority")]
+    pub priority: u8,
+}
+
+impl Manifest {
+    /// What is wrong with the manifest of the plugin in `dir`
+    pub fn problems(&self, dir: &Path) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, message: String| {
+            if !ok {
+                problems.push(message);
+            }
+        };
+
+        check(!self.name.is_empty() && self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            format!("name '{}' must be letters, digits, '-' and '_'", self.name));
+        let release = self.version.split(['-', '+']).next().unwrap_or_default();
+        check(release.split('.').count() == 3 && release.split('.').all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())),
+            format!("version '{}' must be MAJOR.MINOR.PATCH", self.version));
+        check(!self.extensions.is_empty(), "extensions must name at least one extension".to_string());
+        for ext in &self.extensions {
+            check(!ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()),
+                format!("extension '{}' must be letters and digits, without the dot", ext));
+        }
+        match self.command.first() {
+            Some(program) if program.contains('/') => check(dir.join(program).is_file(),
+                format!("command {} is not in the plugin's directory", program)),
+            Some(program) => check(!program.trim().is_empty(), "command must not be empty".to_string()),
+            None => check(false, "command must not be empty".to_string()),
+        }
+        problems
+    }
+}
+
+/// An installed plugin
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    pub dir: PathBuf,
+    /// Its manifest, or why it can't be used
+    pub manifest: std::result::Result<Manifest, String>,
+    pub enabled: bool,
+}
+
+impl Plugin {
+    /// Its name: the manifest's, or the directory's when the manifest is broken
+    pub fn name(&self) -> String {
+        match &self.manifest {
+            Ok(manifest) => manifest.name.clone(),
+            Err(_) => self.dir.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// Read and check the manifest of the plugin in `dir`
+pub fn load(dir: &Path) -> std::result::Result<Manifest, String> {
+    let text = std::fs::read_to_string(dir.join(MANIFEST)).map_err(|e| format!("cannot read {}: {}", MANIFEST, e))?;
+    let manifest: Manifest = serde_json::from_str(&text).map_err(|e| format!("invalid {}: {}", MANIFEST, e))?;
+    let problems = manifest.problems(dir);
+    if !problems.is_empty() {
+        return Err(problems.join("; "));
+    }
+    Ok(manifest)
+}
+
+/// The plugins in `plugins_dir`, by directory name; none when it doesn't exist
+pub fn discover(plugins_dir: &Path) -> Vec<Plugin> {
+    let Ok(entries) = std::fs::read_dir(plugins_dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        // Dot directories are installs in progress
+        .filter(|path| path.is_dir() && !path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+        .collect();
+    dirs.sort();
+    dirs.into_iter()
+        .map(|dir| Plugin {
+            manifest: load(&dir),
+            enabled: !dir.join(DISABLED_MARKER).exists(),
+            dir,
+        })
+        .collect()
+}
+
+/// The installed plugin called `name`
+pub fn find(plugins_dir: &Path, name: &str) -> Result<Plugin> {
+    discover(plugins_dir).into_iter()
+        .find(|plugin| plugin.name() == name)
+        .ok_or_else(|| PanoptesError::Config(format!("No plugin '{}' in {}", name, plugins_dir.display())))
+}
+
+/// Enable or disable the plugin called `name`; returns whether that changed anything
+pub fn set_enabled(plugins_dir: &Path, name: &str, enabled: bool) -> Result<bool> {
+    let plugin = find(plugins_dir, name)?;
+    if plugin.enabled == enabled {
+        return Ok(false);
+    }
+    let marker = plugin.dir.join(DISABLED_MARKER);
+    if enabled {
+        std::fs::remove_file(marker)?;
+    } else {
+        std::fs::write(marker, "")?;
+    }
+    Ok(true)
+}
+
+/// Install the plugin at `source` into `plugins_dir`: a plugin directory, or a
+/// `.zip`, `.tar.gz` or `.tar` archive of one, as a path or an http(s) URL.
+/// An installed plugin of the same name is only replaced when `force`.
+pub async fn install(source: &str, plugins_dir: &Path, force: bool) -> Result<Manifest> {
+    std::fs::create_dir_all(plugins_dir)?;
+    // Unpacked next to its destination, so moving it into place is a rename
+    let staging = plugins_dir.join(format!(".install-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&staging)?;
+    let installed = stage(source, &staging).await.and_then(|dir| {
+        let manifest = load(&dir).map_err(|e| PanoptesError::Config(format!("Not a valid plugin: {}", e)))?;
+        let target = plugins_dir.join(&manifest.name);
+        if target.exists() {
+            if !force {
+                return Err(PanoptesError::Config(format!(
+                    "Plugin '{}' is already installed; pass --force to replace it", manifest.name
+                )));
+            }
+            std::fs::remove_dir_all(&target)?;
+        }
+        std::fs::rename(&dir, &target)?;
+        Ok(manifest)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    installed
+}
+
+/// Put the plugin at `source` in `staging`; returns its directory there
+async fn stage(source: &str, staging: &Path) -> Result<PathBuf> {
+    let archive = if source.starts_with("http://") || source.starts_with("https://") {
+        let name = source.split(['?', '#']).next().unwrap_or(source)
+            .rsplit('/').next().unwrap_or_default().to_string();
+        let response = reqwest::get(source).await?.error_for_status()?;
+        let path = staging.join(if name.is_empty() { "plugin" } else { &name });
+        std::fs::write(&path, response.bytes().await?)?;
+        path
+    } else {
+        let path = PathBuf::from(source);
+        if path.is_dir() {
+            let dir = staging.join("plugin");
+            copy_dir(&path, &dir)?;
+            return Ok(dir);
+        }
+        if !path.is_file() {
+            return Err(PanoptesError::Config(format!("No such plugin directory or archive: {}", source)));
+        }
+        path
+    };
+
+    let unpacked = staging.join("plugin");
+    std::fs::create_dir(&unpacked)?;
+    let name = archive.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    let file = std::fs::File::open(&archive)?;
+    if name.ends_with(".zip") {
+        zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(&unpacked))
+            .map_err(|e| PanoptesError::Archive(format!("Cannot unpack {}: {}", source, e)))?;
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(&unpacked)?;
+    } else if name.ends_with(".tar") {
+        tar::Archive::new(file).unpack(&unpacked)?;
+    } else {
+        return Err(PanoptesError::Config(format!("{} is not a .zip, .tar.gz or .tar archive", source)));
+    }
+
+    // Archives often hold the plugin's directory rather than its files
+    if !unpacked.join(MANIFEST).exists() {
+        let entries: Vec<PathBuf> = std::fs::read_dir(&unpacked)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .collect();
+        if let [only] = entries.as_slice() {
+            if only.join(MANIFEST).is_file() {
+                return Ok(only.clone());
+            }
+        }
+    }
+    Ok(unpacked)
+}
+
+/// Copy the directory `from` to `to`, with its subdirectories
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
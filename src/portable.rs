@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Names every file system takes
+//!
+//! Before a file is renamed its new name is adjusted, always the same way for
+//! the same name, so the rename neither fails nor lands on another file:
+//!
+//! - a name Windows reserves for a device (`CON`, `NUL`, `COM1`...), whatever
+//!   follows its first dot, gets an underscore: `con.pdf` becomes `con_.pdf`
+//! - dots and spaces Windows drops from the end of a name go
+//! - a name longer than its directory's file system allows (most take 255
+//!   bytes, eCryptfs 143) is cut short, between graphemes
+//! - a name differing only in case from another file in the directory is
+//!   taken, as macOS and Windows see it, and is numbered like any other
+//!
+//! The first two apply on Windows, and elsewhere with `rules.portable_names`
+//! (the default) so names survive syncing to Windows; case collisions are
+//! looked for on macOS and Windows, and with `rules.portable_names`. Each
+//! adjustment is recorded in the file's metadata, under `name_adjustments`.
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::naming;
+
+/// Most bytes of a name where the file system can't say
+pub const DEFAULT_NAME_MAX: usize = 255;
+
+/// Room kept in a name for a number telling it from another
+const SUFFIX_ROOM: usize = 16;
+
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "COM¹", "COM²", "COM³",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// A change made to a name so a file system takes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Adjustment {
+    /// Underscore added to a reserved device name
+    Reserved,
+    /// Dots or spaces taken off the end
+    TrailingDots,
+    /// Cut to the file system's length limit
+    Shortened,
+    /// Numbered, another file having the name in another case
+    CaseCollision,
+}
+
+/// Whether Windows and macOS rules apply to names here
+pub fn strict(portable_names: bool) -> bool {
+    portable_names || cfg!(any(windows, target_os = "macos"))
+}
+
+/// Whether Windows reserves `stem`, a name's part before its first dot
+fn is_reserved(stem: &str) -> bool {
+    let base = stem.split('.').next().unwrap_or_default().trim_end();
+    RESERVED.iter().any(|reserved| reserved.eq_ignore_ascii_case(base))
+}
+
+/// `stem`, a name without its extension, adjusted as Windows needs when
+/// `strict`; and what was adjusted
+pub fn safe_stem(stem: &str, strict: bool) -> (String, Vec<Adjustment>) {
+    let mut adjustments = Vec::new();
+    if !strict {
+        return (stem.to_string(), adjustments);
+    }
+    let trimmed = stem.trim_end_matches(['.', ' ']);
+    if trimmed.len() != stem.len() && !trimmed.is_empty() {
+        adjustments.push(Adjustment::TrailingDots);
+    }
+    let mut stem = if trimmed.is_empty() { stem.to_string() } else { trimmed.to_string() };
+    if is_reserved(&stem) {
+        // After the part Windows looks at: `lpt1.backup` as `lpt1_.backup`
+        stem.insert(stem.find('.').unwrap_or(stem.len()), '_');
+        adjustments.push(Adjustment::Reserved);
+    }
+    (stem, adjustments)
+}
+
+/// Most bytes of a name in `dir`
+pub fn name_max(dir: &Path) -> usize {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        if let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) {
+            let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+            if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } == 0 && stats.f_namemax > 0 {
+                return (stats.f_namemax as usize).min(DEFAULT_NAME_MAX);
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    DEFAULT_NAME_MAX
+}
+
+/// `stem` cut so `stem.ext`, numbered, fits in `dir`
+pub fn fit(stem: &str, ext: &str, dir: &Path) -> Option<String> {
+    let room = name_max(dir).saturating_sub(ext.len() + 1 + SUFFIX_ROOM);
+    (stem.len() > room).then(|| naming::truncate_bytes(stem, room).trim_end_matches(['_', '-', '.', ' ']).to_string())
+}
+
+/// Whether a file other than `own` in `dir` has `name` in another case
+pub fn case_collision(dir: &Path, name: &str, own: &Path) -> bool {
+    let folded = name.to_lowercase();
+    let Ok(entries) = std::fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir }) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let other = entry.file_name();
+        let other = other.to_string_lossy();
+        other != name && other.to_lowercase() == folded && !crate::mover::same_file(&entry.path(), own)
+    })
+}
@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Removing data that is no longer needed
+//!
+//! - Records of files deleted from disk. A file on a drive that isn't mounted
+//!   looks deleted too, so a record only goes when its directory is still
+//!   there, or `panoptes verify --fix` has marked it missing.
+//! - History entries older than the age given; they can no longer be undone.
+//! - Tags no file has.
+//! - Thumbnails of files no record has, and ones left by interrupted generation.
+//! - Cached thumbnails not used for the age given; they are made again on request.
+//!
+//! Removing database rows only frees space once the database is vacuumed,
+//! which pruning does when it removed any.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::db::{Database, FileRecord};
+use crate::thumbnails::ThumbnailCache;
+use crate::Result;
+
+/// Thumbnails being generated are only this old; older partial files were abandoned
+const PARTIAL_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// What to prune
+#[derive(Debug, Clone)]
+pub struct PruneOptions {
+    pub records: bool,
+    pub history: bool,
+    pub tags: bool,
+    pub thumbnails: bool,
+    pub cache: bool,
+    /// History and cache entries older than this go
+    pub cutoff: DateTime<Utc>,
+    pub dry_run: bool,
+}
+
+/// What pruning removed, or would remove in a dry run
+#[derive(Debug, Default, Serialize)]
+pub struct PruneSummary {
+    pub dry_run: bool,
+    pub records: usize,
+    pub history: usize,
+    pub tags: usize,
+    /// Stale thumbnails
+    pub thumbnails: usize,
+    /// Cached thumbnails past the age given
+    pub cache: usize,
+    pub thumbnail_bytes: u64,
+    /// How much smaller vacuuming made the database; 0 in a dry run
+    pub database_bytes: u64,
+}
+
+impl PruneSummary {
+    /// Records, entries, tags and thumbnails removed
+    pub fn removed(&self) -> usize {
+        self.records + self.history + self.tags + self.thumbnails + self.cache
+    }
+
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.thumbnail_bytes + self.database_bytes
+    }
+}
+
+/// A record's file is gone for good: not on disk, with its directory still
+/// there or the record marked missing
+fn is_deleted(record: &FileRecord) -> bool {
+    // Not knowable for files on another machine
+    if record.is_remote() {
+        return false;
+    }
+    let path = Path::new(&record.new_path);
+    if path.symlink_metadata().is_ok() {
+        return false;
+    }
+    record.missing_since.is_some() || path.parent().is_some_and(|dir| dir.as_os_str().is_empty() || dir.is_dir())
+}
+
+/// Prune `db` (stored at `database_path`) and `thumbnails` as `options` say
+pub fn prune(db: &Database, database_path: &Path, thumbnails: &ThumbnailCache, options: &PruneOptions) -> Result<PruneSummary> {
+    let mut summary = PruneSummary { dry_run: options.dry_run, ..Default::default() };
+
+    let live = db.get_live_files()?;
+    let mut gone = HashSet::new();
+    if options.records {
+        for record in live.iter().chain(db.get_deleted_files()?.iter()) {
+            if is_deleted(record) {
+                gone.insert(record.id.clone());
+            }
+        }
+    }
+
+    let size_before = file_size(database_path);
+    let file_ids: Vec<String> = gone.iter().cloned().collect();
+    let history_before = options.history.then_some(options.cutoff);
+    let counts = db.prune(&file_ids, history_before, options.tags, options.dry_run)?;
+    summary.records = counts.records;
+    summary.history = counts.history;
+    summary.tags = counts.tags;
+
+    if options.thumbnails || options.cache {
+        // Thumbnails are only shown for live records
+        let wanted: HashSet<&str> = live.iter()
+            .filter(|r| !gone.contains(&r.id))
+            .map(|r| r.file_hash.as_str())
+            .collect();
+        let now = SystemTime::now();
+        let cutoff = SystemTime::from(options.cutoff);
+        for entry in thumbnails.entries() {
+            let stale = match &entry.hash {
+                // Or made at another size, so never shown again
+                Some(hash) => !wanted.contains(hash.as_str()) || entry.size != Some(thumbnails.size()),
+                None => now.duration_since(entry.used).is_ok_and(|age| age > PARTIAL_MAX_AGE),
+            };
+            let expired = entry.hash.is_some() && entry.used < cutoff;
+            let counted = if options.thumbnails && stale {
+                &mut summary.thumbnails
+            } else if options.cache && expired {
+                &mut summary.cache
+            } else {
+                continue;
+            };
+            if !options.dry_run {
+                if let Err(e) = std::fs::remove_file(&entry.path) {
+                    tracing::warn!("Cannot remove {}: {}", entry.path.display(), e);
+                    continue;
+                }
+            }
+            *counted += 1;
+            summary.thumbnail_bytes += entry.len;
+        }
+    }
+
+    if !options.dry_run && summary.records + summary.history + summary.tags > 0 {
+        db.vacuum()?;
+        summary.database_bytes = size_before.saturating_sub(file_size(database_path));
+    }
+    Ok(summary)
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
@@ -0,0 +1,621 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Applying suggested names to files on disk
+//!
+//! Shared by the CLI (watch/analyze) and the web review queue so both apply
+//! the same naming rules and record the same history. A category with a rule
+//! in `rules.categories` is named by its template, date source, length and
+//! casing; others get the date prefix and length limit of `rules`.
+//!
+//! A name already taken is numbered as `rules.collision` says: `_01`, `_02`
+//! and so on by default (where the template has `{counter}`, or at the end),
+//! the lowest number free, so the same files are always numbered the same.
+//! Files renamed at once, by several watchers or processes, never take the
+//! same name: a rename losing the race for a name is numbered again.
+//!
+//! With `vault.directory` set, files are moved into the vault under their
+//! hash instead (see [`crate::vault`]).
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::analyzers::AnalysisResult;
+use crate::config::{AppConfig, CollisionPolicy, DateSource, NamingRule};
+use crate::history::{create_entry, History};
+use crate::mover;
+use crate::naming;
+use crate::portable::{self, Adjustment};
+use crate::sidecar;
+use crate::skiplist;
+use crate::vault;
+use crate::{PanoptesError, Result};
+
+/// What to do with a suggestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Disposition {
+    /// Rename immediately
+    Apply,
+    /// Queue for review in the web UI
+    Review,
+    /// Leave the file alone
+    Skip,
+}
+
+/// Confidence at or above which suggestions are renamed without review
+pub fn auto_rename_threshold(config: &AppConfig) -> f64 {
+    match config.review.auto_apply_threshold {
+        Some(threshold) if config.review.is_active() => threshold,
+        _ => config.rules.auto_rename_threshold,
+    }
+}
+
+/// Decide whether a suggestion is applied, queued for review, or skipped.
+/// Watch, analyze, reprocess and web uploads all go by this.
+pub fn disposition(confidence: f64, config: &AppConfig) -> Disposition {
+    if confidence >= auto_rename_threshold(config) {
+        Disposition::Apply
+    } else if config.review.is_active() && confidence >= config.rules.suggest_threshold {
+        Disposition::Review
+    } else {
+        Disposition::Skip
+    }
+}
+
+/// The rule `rules.categories` has for `category`, if any
+fn naming_rule<'a>(category: Option<&str>, config: &'a AppConfig) -> Option<&'a NamingRule> {
+    let category = category?.trim();
+    config.rules.categories.iter()
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case(category))
+        .map(|(_, rule)| rule)
+}
+
+/// The date `source` gives for `file`; now when the file system doesn't say
+fn name_date(file: &Path, source: DateSource) -> DateTime<Local> {
+    let meta = std::fs::metadata(file).ok();
+    let time = match source {
+        DateSource::Now => None,
+        DateSource::Modified => meta.and_then(|m| m.modified().ok()),
+        DateSource::Created => meta.and_then(|m| m.created().or_else(|_| m.modified()).ok()),
+    };
+    time.map(DateTime::<Local>::from).unwrap_or_else(Local::now)
+}
+
+/// Stands for `{counter}` in a name until the name is placed
+const COUNTER: &str = "{counter}";
+
+/// Most numbers tried for a taken name
+const MAX_COUNTER: u32 = 9999;
+
+/// Times a rename losing the race for a name to another is tried again
+const RACE_RETRIES: usize = 100;
+
+/// Characters separating the words of a name
+const SEPARATORS: [char; 3] = ['_', '-', ' '];
+
+/// A name, and where it is numbered if taken
+#[derive(Debug, Clone)]
+struct Stem {
+    head: String,
+    tail: String,
+    /// Numbered between `head` and `tail` as the template says, rather than
+    /// after an underscore at the end
+    marked: bool,
+}
+
+impl Stem {
+    fn plain(name: String) -> Self {
+        Self { head: name, tail: String::new(), marked: false }
+    }
+
+    /// `name` with its `{counter}` (in any case) taken out
+    fn parse(name: String) -> Self {
+        let at = name.char_indices()
+            .map(|(i, _)| i)
+            .find(|&i| name.get(i..i + COUNTER.len()).is_some_and(|s| s.eq_ignore_ascii_case(COUNTER)));
+        match at {
+            Some(at) => Self {
+                head: name[..at].to_string(),
+                tail: name[at + COUNTER.len()..].replace(COUNTER, ""),
+                marked: true,
+            },
+            None => Self::plain(name),
+        }
+    }
+
+    /// The name with `counter`, or without a number
+    fn numbered(&self, counter: Option<&str>) -> String {
+        match counter {
+            Some(counter) if self.marked => format!("{}{}{}", self.head, counter, self.tail),
+            Some(counter) => format!("{}_{}", self.head, counter),
+            None => {
+                let head = self.head.trim_end_matches(SEPARATORS);
+                let tail = self.tail.trim_start_matches(SEPARATORS);
+                if head.is_empty() || tail.is_empty() {
+                    return format!("{}{}", head, tail);
+                }
+                // One of the separators around the counter
+                let separator = &self.head[head.len()..].chars().next()
+                    .or_else(|| self.tail.chars().next().filter(|c| SEPARATORS.contains(c)))
+                    .map(String::from)
+                    .unwrap_or_default();
+                format!("{}{}{}", head, separator, tail)
+            }
+        }
+    }
+
+    /// Cut to `max` graphemes, not counting the number
+    fn truncate(&mut self, max: usize) {
+        let head = naming::length(&self.head);
+        if head + naming::length(&self.tail) <= max {
+            return;
+        }
+        if head >= max {
+            self.head = naming::truncate(&self.head, max).trim_end_matches(['_', '-']).to_string();
+            self.tail.clear();
+        } else {
+            let tail = naming::truncate(&self.tail, max - head);
+            self.tail = naming::truncate_bytes(tail, naming::MAX_BYTES.saturating_sub(self.head.len()))
+                .trim_end_matches(['_', '-'])
+                .to_string();
+        }
+    }
+}
+
+/// What `{counter}` holds the `n`th time a name is numbered
+fn counter(policy: CollisionPolicy, file_hash: &str, n: u32) -> String {
+    let hash = file_hash.get(..8).unwrap_or(file_hash);
+    match policy {
+        CollisionPolicy::Hash if !hash.is_empty() && n == 1 => hash.to_string(),
+        CollisionPolicy::Hash if !hash.is_empty() => format!("{}_{:02}", hash, n - 1),
+        _ => format!("{:02}", n),
+    }
+}
+
+/// A metadata value or category as part of a name
+fn name_part(value: &str) -> String {
+    value.trim().to_lowercase().replace(char::is_whitespace, "_")
+}
+
+/// `template` with its `{placeholders}` filled in from `result`
+fn fill(template: &str, result: &AnalysisResult, date: DateTime<Local>) -> String {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        name.push_str(&rest[..start]);
+        match &rest[start + 1..start + len] {
+            "name" => name.push_str(&result.suggested_name),
+            "date" => name.push_str(&date.format("%Y-%m-%d").to_string()),
+            "time" => name.push_str(&date.format("%H%M%S").to_string()),
+            "category" => name.push_str(&name_part(result.category.as_deref().unwrap_or_default())),
+            "counter" => name.push_str(COUNTER),
+            key => match result.metadata.get(key) {
+                Some(serde_json::Value::String(value)) => name.push_str(&name_part(value)),
+                Some(serde_json::Value::Number(value)) => name.push_str(&value.to_string()),
+                _ => {}
+            },
+        }
+        rest = &rest[start + len + 1..];
+    }
+    name.push_str(rest);
+
+    // No path separators or characters file systems refuse, and no
+    // separators left over from empty placeholders
+    let name: String = name.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    let mut tidy = String::with_capacity(name.len());
+    for c in name.chars() {
+        if !(c == '_' && tidy.ends_with('_')) {
+            tidy.push(c);
+        }
+    }
+    tidy.trim_matches(['_', '-', ' ']).to_string()
+}
+
+/// The name (without extension) `file` gets for `result`: by its category's
+/// rule in `rules.categories`, or with the date prefix, and within the length limit
+pub fn final_name(result: &AnalysisResult, file: &Path, config: &AppConfig) -> String {
+    portable::safe_stem(&stem(result, file, config).numbered(None), portable::strict(config.rules.portable_names)).0
+}
+
+/// Whether `name` starts with a date, as `2025-03-02_invoice`
+fn dated(name: &str) -> bool {
+    name.get(..10).is_some_and(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+}
+
+/// [`final_name`], with its place for a number
+fn stem(result: &AnalysisResult, file: &Path, config: &AppConfig) -> Stem {
+    let rule = naming_rule(result.category.as_deref(), config).cloned().unwrap_or_default();
+    let template = match rule.template {
+        Some(template) => template,
+        // Names carrying their own date, as emails', aren't dated again
+        None if config.rules.date_prefix && !dated(&result.suggested_name) => "{date}_{name}".to_string(),
+        None => "{name}".to_string(),
+    };
+    let filled = rule.casing.apply(&fill(&template, result, name_date(file, rule.date_source)));
+
+    // Metadata filled in comes as its tags have it
+    let mut stem = Stem::parse(naming::prepare(&filled, &config.rules.sanitizer));
+    if stem.numbered(None).is_empty() {
+        stem = Stem::plain(naming::prepare(&result.suggested_name, &config.rules.sanitizer));
+    }
+
+    // Truncate to max length, in graphemes
+    stem.truncate(rule.max_length.unwrap_or(config.rules.max_length));
+    stem
+}
+
+/// Where a file named `name` (see [`final_name`]) goes when planned for
+/// `planned`, numbered as `rules.collision` says if that is taken; None when
+/// the file is to be left alone
+pub fn target_path(planned: &Path, name: &str, file_hash: &str, config: &AppConfig) -> Result<Option<PathBuf>> {
+    place(planned, &Stem::plain(name.to_string()), file_hash, config).map(|placed| placed.map(|(path, _)| path))
+}
+
+/// Where `file` goes for `result` when planned for `planned`, as
+/// [`rename_file`] would take it; None when it is to be left alone
+pub fn suggested_path(result: &AnalysisResult, file: &Path, planned: &Path, config: &AppConfig) -> Result<Option<PathBuf>> {
+    place(planned, &stem(result, file, config), &result.file_hash, config).map(|placed| placed.map(|(path, _)| path))
+}
+
+/// [`target_path`] for `stem`, with what was adjusted for its file system to take it
+fn place(planned: &Path, stem: &Stem, file_hash: &str, config: &AppConfig) -> Result<Option<(PathBuf, Vec<Adjustment>)>> {
+    let parent = planned.parent()
+        .ok_or_else(|| PanoptesError::Config("Cannot determine parent directory".to_string()))?;
+
+    // Directories are named whole, dots and all
+    let ext = if planned.is_dir() {
+        ""
+    } else {
+        planned.extension().and_then(|e| e.to_str()).unwrap_or("")
+    };
+    let file_name = |stem: &str| if ext.is_empty() { stem.to_string() } else { format!("{}.{}", stem, ext) };
+
+    let strict = portable::strict(config.rules.portable_names);
+    let mut adjustments = Vec::new();
+    let mut stem = stem.clone();
+    if let Some(short) = portable::fit(&stem.numbered(None), ext, parent) {
+        stem = Stem::plain(short);
+        adjustments.push(Adjustment::Shortened);
+    }
+    let candidate = |counter: Option<&str>| {
+        let (name, adjusted) = portable::safe_stem(&stem.numbered(counter), strict);
+        (file_name(&name), adjusted)
+    };
+
+    // Handle filename collision
+    let taken = |path: &Path| (path.exists() || path.is_symlink()) && !mover::same_file(path, planned);
+    let (name, adjusted) = candidate(None);
+    adjustments.extend(adjusted);
+    let path = parent.join(&name);
+    let case_collision = strict && portable::case_collision(parent, &name, planned);
+    if !case_collision && !taken(&path) {
+        return Ok(Some((path, adjustments)));
+    }
+    match config.rules.collision {
+        CollisionPolicy::Skip => {
+            info!("{:?} is taken, leaving {:?} as it is", path, planned.file_name().unwrap_or_default());
+            return Ok(None);
+        }
+        CollisionPolicy::Fail => {
+            return Err(PanoptesError::FileSystem(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} is taken", path.display()),
+            )));
+        }
+        CollisionPolicy::Counter | CollisionPolicy::Hash => {}
+    }
+    if case_collision {
+        adjustments.push(Adjustment::CaseCollision);
+    }
+    for n in 1..=MAX_COUNTER {
+        let (name, _) = candidate(Some(&counter(config.rules.collision, file_hash, n)));
+        let path = parent.join(&name);
+        let case_collision = strict && portable::case_collision(parent, &name, planned);
+        if !case_collision && !taken(&path) {
+            return Ok(Some((path, adjustments)));
+        }
+    }
+    Err(PanoptesError::FileSystem(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!("No free name for {} in {}", file_name(&stem.numbered(None)), parent.display()),
+    )))
+}
+
+/// Rename a file with the analysis result, recording it in history; returns the new path.
+/// With a `destination` the file is moved there instead of being renamed in place,
+/// and with a vault it is moved into the vault, whatever the destination. When
+/// the name is taken and `rules.collision` is `skip`, the file stays where it is.
+pub fn rename_file(
+    original: &Path,
+    destination: Option<&Path>,
+    result: &AnalysisResult,
+    config: &AppConfig,
+    history: &History,
+    session_id: Option<&str>,
+    file_id: Option<&str>,
+) -> Result<PathBuf> {
+    let vault_address = match vault::root(config) {
+        Some(root) => {
+            let ext = original.extension().and_then(|e| e.to_str());
+            let address = vault::address(&root, &result.file_hash, ext);
+            if address.exists() {
+                // Already in the vault: the file itself when reprocessed, or a copy
+                if std::fs::canonicalize(&address)? == std::fs::canonicalize(original)? {
+                    return Ok(original.to_path_buf());
+                }
+                return Err(PanoptesError::FileSystem(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} is already in the vault at {}", original.display(), address.display()),
+                )));
+            }
+            std::fs::create_dir_all(address.parent().unwrap_or(&root))?;
+            Some(address)
+        }
+        None => None,
+    };
+    let planned = match destination {
+        Some(dir) if vault_address.is_none() => {
+            std::fs::create_dir_all(dir)?;
+            dir.join(original.file_name().unwrap_or_default())
+        }
+        _ => original.to_path_buf(),
+    };
+    let stem = stem(result, original, config);
+
+    // History entry, written once the file is where it says
+    let mut entry = create_entry(
+        uuid::Uuid::new_v4().to_string(),
+        original.to_path_buf(),
+        planned.clone(),
+        result.suggested_name.clone(),
+        result.category.clone(),
+        result.tags.clone(),
+        result.file_hash.clone(),
+        session_id.map(String::from),
+    );
+    entry.file_id = file_id.map(String::from);
+    let mut tries = 0;
+    let (new_path, adjustments) = loop {
+        let (new_path, adjustments) = match vault_address {
+            Some(ref address) => (address.clone(), Vec::new()),
+            None => match place(&planned, &stem, &result.file_hash, config)? {
+                Some(placed) => placed,
+                None => return Ok(original.to_path_buf()),
+            },
+        };
+        // Perform rename
+        let recorded = move_recorded(original, &new_path, || {
+            entry.new_path = new_path.clone();
+            history.append(&entry)
+        });
+        match recorded {
+            // Taken by another rename since it was found free
+            Err(PanoptesError::FileSystem(e))
+                if e.kind() == std::io::ErrorKind::AlreadyExists && vault_address.is_none() && tries < RACE_RETRIES =>
+            {
+                tracing::debug!("{:?} was taken meanwhile, numbering again", new_path);
+                tries += 1;
+            }
+            moved => break moved.map(|()| (new_path, adjustments))?,
+        }
+    };
+    if !adjustments.is_empty() {
+        info!("Adjusted the name of {:?} for the file system: {:?}", original, adjustments);
+        if let Some(id) = file_id {
+            if let Err(e) = history.db().set_metadata_field(id, "name_adjustments", &serde_json::json!(adjustments)) {
+                tracing::debug!("Failed to record name adjustments: {}", e);
+            }
+        }
+    }
+    if vault::contains(&new_path, config) {
+        vault::seal(&new_path, config);
+    }
+    info!("Renamed to: {:?}", new_path);
+
+    Ok(new_path)
+}
+
+/// Whether `dir` is a quarantine directory (`review.quarantine_dir`)
+pub fn is_quarantine_dir(dir: &Path, config: &AppConfig) -> bool {
+    match config.review.quarantine_dir.as_deref().map(Path::new) {
+        Some(quarantine) if quarantine.is_absolute() => dir == quarantine,
+        Some(quarantine) => dir.ends_with(quarantine),
+        None => false,
+    }
+}
+
+/// Whether `path` is waiting in a quarantine directory
+pub fn is_quarantined(path: &Path, config: &AppConfig) -> bool {
+    path.parent().is_some_and(|dir| is_quarantine_dir(dir, config))
+}
+
+/// Move a file queued for review into its quarantine directory, recording the
+/// move in history. Returns where it went; `None` without `review.quarantine_dir`
+/// or when it is already there.
+pub fn quarantine_file(
+    path: &Path,
+    file_hash: &str,
+    config: &AppConfig,
+    history: &History,
+    session_id: Option<&str>,
+    file_id: Option<&str>,
+) -> Result<Option<PathBuf>> {
+    let Some(ref quarantine) = config.review.quarantine_dir else {
+        return Ok(None);
+    };
+    if is_quarantined(path, config) {
+        return Ok(None);
+    }
+    // An absolute quarantine directory replaces the file's directory
+    let dir = path.parent().unwrap_or(Path::new("")).join(quarantine);
+    move_into(path, &dir, file_hash, history, session_id, file_id).map(Some)
+}
+
+/// Where an approved file leaves quarantine for: the destination of the watch
+/// directory it came from, or that directory itself. `original` is where it
+/// was analyzed.
+pub fn release_dir(original: &Path, config: &AppConfig) -> PathBuf {
+    config.watch_destination(original)
+        .unwrap_or_else(|| original.parent().unwrap_or(Path::new("")).to_path_buf())
+}
+
+/// Move a file out of quarantine into `dir`, keeping its name; for suggestions
+/// that were rejected
+pub fn release_file(path: &Path, dir: &Path, file_hash: &str, history: &History, file_id: Option<&str>) -> Result<PathBuf> {
+    move_into(path, dir, file_hash, history, None, file_id)
+}
+
+/// Move `path` into `dir` under its own name, numbered if the name is taken,
+/// and record the move
+fn move_into(
+    path: &Path,
+    dir: &Path,
+    file_hash: &str,
+    history: &History,
+    session_id: Option<&str>,
+    file_id: Option<&str>,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let name = Path::new(path.file_name().unwrap_or_default());
+    let mut target = dir.join(name);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = name.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut n = 2;
+    while target.exists() {
+        target = dir.join(format!("{}_{}{}", stem, n, ext));
+        n += 1;
+    }
+
+    move_recorded(path, &target, || {
+        history.record_move(path, &target, file_hash.to_string(), file_id.map(String::from), session_id.map(String::from))
+            .map(drop)
+    })?;
+    info!("Moved to: {:?}", target);
+    Ok(target)
+}
+
+/// Move `from` to `to` with [`move_path`] and `record` the move; if it can't
+/// be recorded the file is moved back, as a move left unrecorded could never
+/// be undone
+pub fn move_recorded(from: &Path, to: &Path, record: impl FnOnce() -> Result<()>) -> Result<()> {
+    move_path(from, to)?;
+    if let Err(e) = record() {
+        if let Err(back) = move_path(to, from) {
+            tracing::warn!("Failed to move {:?} back to {:?}: {}", to, from, back);
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Move `from` to `to` with [`mover::move_file`], never replacing a file at
+/// `to`; its sidecars go along, and watch mode ignores it there for a while
+pub fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    skiplist::remember(to);
+    mover::move_file(from, to)?;
+    sidecar::follow(from, to);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(name: &str, file_hash: &str) -> AnalysisResult {
+        AnalysisResult {
+            suggested_name: name.to_string(),
+            confidence: 0.9,
+            category: None,
+            tags: Vec::new(),
+            file_hash: file_hash.to_string(),
+            metadata: serde_json::json!({}),
+            analyzer: None,
+            model: None,
+        }
+    }
+
+    fn config(collision: CollisionPolicy) -> AppConfig {
+        let mut config = AppConfig::default();
+        config.rules.date_prefix = false;
+        config.rules.collision = collision;
+        config
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_concurrent_renames_are_numbered() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::new(crate::db::Database::in_memory().unwrap());
+        let config = config(CollisionPolicy::Counter);
+        let barrier = std::sync::Barrier::new(8);
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (dir, history, config, barrier) = (dir.path(), &history, &config, &barrier);
+                scope.spawn(move || {
+                    let file = dir.join(format!("scan{}.txt", i));
+                    std::fs::write(&file, i.to_string()).unwrap();
+                    barrier.wait();
+                    rename_file(&file, None, &suggestion("report", &i.to_string()), config, history, None, None).unwrap();
+                });
+            }
+        });
+
+        let mut expected = vec!["report.txt".to_string()];
+        expected.extend((1..8).map(|n| format!("report_{:02}.txt", n)));
+        assert_eq!(names(dir.path()), expected);
+        assert_eq!(history.read_all().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_collision_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::new(crate::db::Database::in_memory().unwrap());
+        std::fs::write(dir.path().join("report.txt"), "taken").unwrap();
+        let file = dir.path().join("scan.txt");
+        std::fs::write(&file, "new").unwrap();
+        let result = suggestion("report", "3fa2c19b0d4e");
+
+        let skipped = rename_file(&file, None, &result, &config(CollisionPolicy::Skip), &history, None, None).unwrap();
+        assert_eq!(skipped, file);
+        assert!(rename_file(&file, None, &result, &config(CollisionPolicy::Fail), &history, None, None).is_err());
+        assert!(history.read_all().unwrap().is_empty());
+
+        let hashed = rename_file(&file, None, &result, &config(CollisionPolicy::Hash), &history, None, None).unwrap();
+        assert_eq!(hashed, dir.path().join("report_3fa2c19b.txt"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("report.txt")).unwrap(), "taken");
+    }
+
+    #[test]
+    fn test_counter_placeholder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config(CollisionPolicy::Counter);
+        config.rules.categories.insert("Scans".to_string(), NamingRule {
+            template: Some("{name}_{counter}_final".to_string()),
+            ..Default::default()
+        });
+        let mut result = suggestion("report", "");
+        result.category = Some("Scans".to_string());
+        let file = dir.path().join("scan.txt");
+        std::fs::write(&file, "new").unwrap();
+
+        assert_eq!(final_name(&result, &file, &config), "report_final");
+        std::fs::write(dir.path().join("report_final.txt"), "taken").unwrap();
+        let target = suggested_path(&result, &file, &file, &config).unwrap();
+        assert_eq!(target, Some(dir.path().join("report_01_final.txt")));
+    }
+}
@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Batch analysis reports
+//!
+//! A report shows what a run of `panoptes analyze` did or, for a dry run,
+//! would do: the renames, how confident the suggestions were, the categories
+//! files fall into, failures and how long analysis took. It renders as a
+//! standalone HTML page, for reviewing a large reorganization before running
+//! it for real, or as Markdown.
+
+use indicatif::HumanDuration;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::db::{ScanOutcome, ScanResult, ScanRun};
+use crate::web::escape_html;
+
+/// Width of the confidence distribution's ranges, in percent
+const CONFIDENCE_STEP: u32 = 10;
+
+/// Width of the longest bar in Markdown charts, in characters
+const MARKDOWN_BAR_WIDTH: usize = 30;
+
+const STYLE: &str = "body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 72rem; padding: 0 1rem; color: #222; }
+h1 { font-size: 1.5rem; } h2 { font-size: 1.2rem; margin-top: 2rem; }
+table { border-collapse: collapse; width: 100%; font-size: 0.9rem; }
+th, td { text-align: left; padding: 0.3rem 0.6rem; border-bottom: 1px solid #ddd; vertical-align: top; }
+th { background: #f4f4f4; } td.num { text-align: right; white-space: nowrap; }
+table.summary { width: auto; } table.summary th { background: none; font-weight: normal; color: #555; }
+.bar { background: #4a7bd0; height: 0.9rem; min-width: 1px; }
+.muted { color: #777; } .error { color: #a00; }";
+
+/// How to render a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Html,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Markdown for `.md` and `.markdown` files, HTML otherwise
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("md" | "markdown") => Self::Markdown,
+            _ => Self::Html,
+        }
+    }
+}
+
+/// A run and the outcomes recorded for its files
+pub struct Report<'a> {
+    run: &'a ScanRun,
+    results: &'a [ScanResult],
+}
+
+impl<'a> Report<'a> {
+    pub fn new(run: &'a ScanRun, results: &'a [ScanResult]) -> Self {
+        Self { run, results }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Html => self.html(),
+            ReportFormat::Markdown => self.markdown(),
+        }
+    }
+
+    fn title(&self) -> String {
+        let kind = if self.run.dry_run { "Dry run" } else { "Analysis" };
+        format!("{} of {}", kind, self.run.path)
+    }
+
+    fn count(&self, outcome: ScanOutcome) -> usize {
+        self.results.iter().filter(|r| r.outcome == outcome).count()
+    }
+
+    /// What the report says a file's outcome was
+    fn describe(&self, outcome: ScanOutcome) -> &'static str {
+        match (outcome, self.run.dry_run) {
+            (ScanOutcome::Rename, true) => "would rename",
+            (ScanOutcome::Rename, false) => "renamed",
+            (ScanOutcome::Review, true) => "would queue for review",
+            (ScanOutcome::Review, false) => "queued for review",
+            (ScanOutcome::Skip, true) => "would skip",
+            (ScanOutcome::Skip, false) => "skipped",
+            (ScanOutcome::BelowMinimum, _) => "below --min-confidence",
+            (ScanOutcome::Failed, _) => "failed",
+        }
+    }
+
+    /// Label and value rows of the summary table
+    fn summary(&self) -> Vec<(String, String)> {
+        let analysis_time = Duration::from_millis(self.results.iter().map(|r| r.elapsed_ms).sum());
+        let mut rows = vec![
+            ("Run".to_string(), self.run.id.clone()),
+            ("Started".to_string(), self.run.started_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+            ("Finished".to_string(), match self.run.finished_at {
+                Some(finished) => finished.format("%Y-%m-%d %H:%M UTC").to_string(),
+                None => "not yet; resume to retry failures".to_string(),
+            }),
+            ("Files".to_string(), self.results.len().to_string()),
+        ];
+        for outcome in [ScanOutcome::Rename, ScanOutcome::Review, ScanOutcome::Skip, ScanOutcome::BelowMinimum, ScanOutcome::Failed] {
+            let mut label = self.describe(outcome).to_string();
+            label[..1].make_ascii_uppercase();
+            rows.push((label, self.count(outcome).to_string()));
+        }
+        rows.push(("Analysis time".to_string(), HumanDuration(analysis_time).to_string()));
+        let analyzed = self.results.iter().filter(|r| r.elapsed_ms > 0).count();
+        if analyzed > 0 {
+            let average = analysis_time / analyzed as u32;
+            rows.push(("Per file".to_string(), format!("{:.1}s", average.as_secs_f64())));
+        }
+        if self.run.dry_run {
+            // A real run analyzes every file again
+            rows.push(("Estimated real run".to_string(), format!("{} with one job", HumanDuration(analysis_time))));
+        }
+        rows
+    }
+
+    /// Renames and review suggestions, the changes to look over
+    fn proposals(&self) -> impl Iterator<Item = &ScanResult> {
+        self.results.iter().filter(|r| matches!(r.outcome, ScanOutcome::Rename | ScanOutcome::Review))
+    }
+
+    fn failures(&self) -> impl Iterator<Item = &ScanResult> {
+        self.results.iter().filter(|r| r.outcome == ScanOutcome::Failed)
+    }
+
+    /// Files per confidence range, lowest first
+    fn confidence_distribution(&self) -> Vec<(String, usize)> {
+        let buckets = (100 / CONFIDENCE_STEP) as usize;
+        let mut counts = vec![0; buckets];
+        for confidence in self.results.iter().filter_map(|r| r.confidence) {
+            let percent = (confidence.clamp(0.0, 1.0) * 100.0) as usize;
+            counts[(percent / CONFIDENCE_STEP as usize).min(buckets - 1)] += 1;
+        }
+        counts.into_iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let low = i as u32 * CONFIDENCE_STEP;
+                (format!("{}-{}%", low, low + CONFIDENCE_STEP), count)
+            })
+            .collect()
+    }
+
+    /// Files per category, most common first
+    fn categories(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for result in self.results.iter().filter(|r| r.outcome != ScanOutcome::Failed) {
+            let category = result.category.clone().unwrap_or_else(|| "Uncategorized".to_string());
+            *counts.entry(category).or_default() += 1;
+        }
+        let mut categories: Vec<_> = counts.into_iter().collect();
+        categories.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        categories
+    }
+
+    /// `path` relative to the analyzed directory, when it is inside it
+    fn display_path(&self, path: &str) -> String {
+        Path::new(path)
+            .strip_prefix(&self.run.path)
+            .ok()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string())
+    }
+
+    /// The name a proposal gives its file: where it went, or the suggestion
+    fn new_name(result: &ScanResult) -> String {
+        match &result.new_path {
+            Some(path) => Path::new(path).file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            None => result.suggested_name.clone().unwrap_or_default(),
+        }
+    }
+
+    fn html(&self) -> String {
+        let mut html = String::new();
+        let title = escape_html(&self.title());
+        let _ = writeln!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">");
+        let _ = writeln!(html, "<title>Panoptes: {}</title>\n<style>\n{}\n</style>\n</head>\n<body>", title, STYLE);
+        let _ = writeln!(html, "<h1>{}</h1>", title);
+
+        html.push_str("<table class=\"summary\">\n");
+        for (label, value) in self.summary() {
+            let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", escape_html(&label), escape_html(&value));
+        }
+        html.push_str("</table>\n");
+
+        let heading = if self.run.dry_run { "Proposed renames" } else { "Renames" };
+        let _ = writeln!(html, "<h2>{}</h2>", heading);
+        let proposals: Vec<_> = self.proposals().collect();
+        if proposals.is_empty() {
+            html.push_str("<p class=\"muted\">None.</p>\n");
+        } else {
+            html.push_str("<table>\n<tr><th>File</th><th>New name</th><th>Confidence</th><th>Category</th><th>Action</th></tr>\n");
+            for result in proposals {
+                let _ = writeln!(
+                    html,
+                    "<tr><td title=\"{}\">{}</td><td>{}</td><td class=\"num\">{:.0}%</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(&result.path),
+                    escape_html(&self.display_path(&result.path)),
+                    escape_html(&Self::new_name(result)),
+                    result.confidence.unwrap_or(0.0) * 100.0,
+                    escape_html(result.category.as_deref().unwrap_or("")),
+                    self.describe(result.outcome),
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("<h2>Confidence</h2>\n");
+        html_chart(&mut html, &self.confidence_distribution());
+        html.push_str("<h2>Categories</h2>\n");
+        html_chart(&mut html, &self.categories());
+
+        html.push_str("<h2>Failures</h2>\n");
+        let failures: Vec<_> = self.failures().collect();
+        if failures.is_empty() {
+            html.push_str("<p class=\"muted\">None.</p>\n");
+        } else {
+            html.push_str("<table>\n<tr><th>File</th><th>Error</th></tr>\n");
+            for result in failures {
+                let _ = writeln!(
+                    html,
+                    "<tr><td title=\"{}\">{}</td><td class=\"error\">{}</td></tr>",
+                    escape_html(&result.path),
+                    escape_html(&self.display_path(&result.path)),
+                    escape_html(result.error.as_deref().unwrap_or("")),
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "# {}\n", self.title());
+
+        md.push_str("| | |\n|---|---|\n");
+        for (label, value) in self.summary() {
+            let _ = writeln!(md, "| {} | {} |", md_cell(&label), md_cell(&value));
+        }
+
+        let heading = if self.run.dry_run { "Proposed renames" } else { "Renames" };
+        let _ = writeln!(md, "\n## {}\n", heading);
+        let proposals: Vec<_> = self.proposals().collect();
+        if proposals.is_empty() {
+            md.push_str("None.\n");
+        } else {
+            md.push_str("| File | New name | Confidence | Category | Action |\n|---|---|--:|---|---|\n");
+            for result in proposals {
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {:.0}% | {} | {} |",
+                    md_cell(&self.display_path(&result.path)),
+                    md_cell(&Self::new_name(result)),
+                    result.confidence.unwrap_or(0.0) * 100.0,
+                    md_cell(result.category.as_deref().unwrap_or("")),
+                    self.describe(result.outcome),
+                );
+            }
+        }
+
+        md.push_str("\n## Confidence\n\n");
+        markdown_chart(&mut md, "Confidence", &self.confidence_distribution());
+        md.push_str("\n## Categories\n\n");
+        markdown_chart(&mut md, "Category", &self.categories());
+
+        md.push_str("\n## Failures\n\n");
+        let failures: Vec<_> = self.failures().collect();
+        if failures.is_empty() {
+            md.push_str("None.\n");
+        } else {
+            md.push_str("| File | Error |\n|---|---|\n");
+            for result in failures {
+                let _ = writeln!(
+                    md,
+                    "| {} | {} |",
+                    md_cell(&self.display_path(&result.path)),
+                    md_cell(result.error.as_deref().unwrap_or("")),
+                );
+            }
+        }
+        md
+    }
+}
+
+/// A bar chart of `rows` as a table
+fn html_chart(html: &mut String, rows: &[(String, usize)]) {
+    let max = rows.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    if max == 0 {
+        html.push_str("<p class=\"muted\">No files.</p>\n");
+        return;
+    }
+    html.push_str("<table>\n");
+    for (label, count) in rows {
+        let _ = writeln!(
+            html,
+            "<tr><td style=\"width: 12rem\">{}</td><td class=\"num\" style=\"width: 4rem\">{}</td>\
+             <td><div class=\"bar\" style=\"width: {}%\"></div></td></tr>",
+            escape_html(label), count, count * 100 / max,
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+/// A bar chart of `rows` as a table, with bars drawn in block characters
+fn markdown_chart(md: &mut String, heading: &str, rows: &[(String, usize)]) {
+    let max = rows.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    if max == 0 {
+        md.push_str("No files.\n");
+        return;
+    }
+    let _ = writeln!(md, "| {} | Files | |\n|---|--:|---|", heading);
+    for (label, count) in rows {
+        let bar = "█".repeat(count * MARKDOWN_BAR_WIDTH / max);
+        let _ = writeln!(md, "| {} | {} | {} |", md_cell(label), count, bar);
+    }
+}
+
+/// Text safe inside a Markdown table cell
+fn md_cell(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|").replace(['\n', '\r'], " ")
+}
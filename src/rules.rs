@@ -0,0 +1,442 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Conditional actions on analyzed files
+//!
+//! `rules.actions` lists what to do with files matching conditions, checked
+//! once a file has been analyzed, before it is renamed:
+//!
+//! ```json
+//! "actions": [
+//!   { "name": "invoices", "when": { "categories": ["Invoice"], "min_confidence": 0.6 },
+//!     "then": [{ "action": "move", "to": "~/Finance" }, { "action": "notify" }] },
+//!   { "when": { "extensions": ["tmp"] }, "then": [{ "action": "skip" }] }
+//! ]
+//! ```
+//!
+//! A rule matches when all of its conditions hold; one without any matches
+//! every file. Every matching rule applies, in order, so a later rule sees the
+//! category and tags set by an earlier one and overrides where it sends the
+//! file. `skip` ends it: the file is left alone and no later rule is checked.
+//!
+//! Without rules, or when none decides, a file is renamed, queued for review
+//! or skipped by its confidence (see [`crate::renamer::disposition`]) and goes
+//! where `rules.destinations`, organizing and the watch directory send it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::analyzers::AnalysisResult;
+use crate::config::AppConfig;
+use crate::organizer::expand_home;
+use crate::renamer::{disposition, Disposition};
+use crate::selection::parse_size;
+
+/// How long a `run` command may take
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Actions for the files matching conditions
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ActionRule {
+    /// Shown in the log and sent with notifications
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub when: Conditions,
+    pub then: Vec<Action>,
+}
+
+/// What a file must be like for a rule to apply; all given conditions must hold
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct Conditions {
+    /// One of these extensions (without the dot, any case)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// At least this big, e.g. `10MB`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+    /// At most this big
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+    /// In one of these categories (any case)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    /// With at least one of these tags (any case)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f64>,
+    /// Below this confidence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_confidence: Option<f64>,
+    /// Metadata fields matching globs (any case), e.g. `"artist": "*Beatles*"`;
+    /// nested fields are dotted, as in `exif.model`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Something done with a matching file
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Rename it whatever its confidence
+    Rename,
+    /// Move it to a directory when it is renamed; a leading `~` is the home directory
+    Move { to: String },
+    /// Add tags
+    Tag { tags: Vec<String> },
+    /// Put it in a category, which then names and organizes it
+    SetCategory { category: String },
+    /// Leave it alone
+    Skip,
+    /// Queue it for review, moving it to `review.quarantine_dir` if set
+    Quarantine,
+    /// Send the `rule` webhook event
+    Notify {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    /// Run a program once the file is dealt with, with its path as the last
+    /// argument and `PANOPTES_*` variables describing it
+    Run { command: Vec<String> },
+}
+
+impl ActionRule {
+    /// Name for the log: its `name`, or its place in `rules.actions`
+    pub fn label(&self, index: usize) -> String {
+        self.name.clone().unwrap_or_else(|| format!("rules.actions[{}]", index))
+    }
+
+    /// Whether it applies to `file` with `result`
+    pub fn matches(&self, file: &Path, result: &AnalysisResult) -> bool {
+        let when = &self.when;
+        let same = |a: &str, b: &str| a.trim().eq_ignore_ascii_case(b.trim());
+        if !when.extensions.is_empty() {
+            let ext = file.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            if !when.extensions.iter().any(|e| same(e.trim_start_matches('.'), &ext)) {
+                return false;
+            }
+        }
+        if !when.categories.is_empty()
+            && !result.category.as_deref().is_some_and(|c| when.categories.iter().any(|wanted| same(wanted, c)))
+        {
+            return false;
+        }
+        if !when.tags.is_empty() && !result.tags.iter().any(|t| when.tags.iter().any(|wanted| same(wanted, t))) {
+            return false;
+        }
+        if when.min_confidence.is_some_and(|min| result.confidence < min)
+            || when.max_confidence.is_some_and(|max| result.confidence >= max)
+        {
+            return false;
+        }
+        let options = glob::MatchOptions { case_sensitive: false, ..Default::default() };
+        for (field, pattern) in &when.metadata {
+            let value = field.split('.').try_fold(&result.metadata, |value, key| value.get(key));
+            let text = match value {
+                Some(serde_json::Value::String(text)) => text.clone(),
+                Some(serde_json::Value::Null) | None => return false,
+                Some(value) => value.to_string(),
+            };
+            if !glob::Pattern::new(pattern).is_ok_and(|p| p.matches_with(&text, options)) {
+                return false;
+            }
+        }
+        if when.min_size.is_some() || when.max_size.is_some() {
+            let Ok(len) = std::fs::metadata(file).map(|m| m.len()) else {
+                return false;
+            };
+            let size = |value: &Option<String>| value.as_deref().and_then(parse_size);
+            if size(&when.min_size).is_some_and(|min| len < min) || size(&when.max_size).is_some_and(|max| len > max) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Everything wrong with `rules`, for [`AppConfig::problems`]
+pub fn problems(rules: &[ActionRule]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (index, rule) in rules.iter().enumerate() {
+        let label = format!("rules.actions[{}]", rule.name.as_deref().map_or_else(|| index.to_string(), String::from));
+        let when = &rule.when;
+        if rule.then.is_empty() {
+            problems.push(format!("{} needs at least one action in then", label));
+        }
+        for (name, size) in [("min_size", &when.min_size), ("max_size", &when.max_size)] {
+            if size.as_deref().is_some_and(|s| parse_size(s).is_none()) {
+                problems.push(format!("{}.when.{} must be bytes or a size like 500K, 10MB, 2GiB", label, name));
+            }
+        }
+        for (name, confidence) in [("min_confidence", when.min_confidence), ("max_confidence", when.max_confidence)] {
+            if confidence.is_some_and(|c| !(0.0..=1.0).contains(&c)) {
+                problems.push(format!("{}.when.{} must be between 0 and 1", label, name));
+            }
+        }
+        for (field, pattern) in &when.metadata {
+            if let Err(e) = glob::Pattern::new(pattern) {
+                problems.push(format!("{}.when.metadata[{}] '{}' is not a valid pattern: {}", label, field, pattern, e));
+            }
+        }
+        for action in &rule.then {
+            let problem = match action {
+                Action::Move { to } if to.trim().is_empty() => Some("move needs a directory in to"),
+                Action::Tag { tags } if tags.iter().all(|t| t.trim().is_empty()) => Some("tag needs tags"),
+                Action::SetCategory { category } if category.trim().is_empty() => Some("set_category needs a category"),
+                Action::Run { command } if command.first().map_or(true, |p| p.trim().is_empty()) => {
+                    Some("run needs a command, as a list of the program and its arguments")
+                }
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                problems.push(format!("{}.then: {}", label, problem));
+            }
+        }
+    }
+    problems
+}
+
+/// A `notify` action to carry out
+#[derive(Debug, Clone)]
+pub struct Notice {
+    pub rule: String,
+    pub message: Option<String>,
+}
+
+/// A `run` action to carry out
+#[derive(Debug, Clone)]
+pub struct RunCommand {
+    pub rule: String,
+    pub command: Vec<String>,
+}
+
+/// What to do with an analyzed file
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub disposition: Disposition,
+    /// Where it goes when renamed; renamed in place without one
+    pub destination: Option<PathBuf>,
+    /// The rule that decided its disposition, if one did
+    pub decided_by: Option<String>,
+    pub notices: Vec<Notice>,
+    pub commands: Vec<RunCommand>,
+}
+
+/// The plan for `file`, analyzed as `result`: by its confidence and to where
+/// `place` sends it, unless the rules of `config` say otherwise. Their `tag`
+/// and `set_category` actions change `result` first, so `place` sees them.
+pub fn plan(
+    file: &Path,
+    result: &mut AnalysisResult,
+    config: &AppConfig,
+    place: impl FnOnce(&AnalysisResult) -> Option<PathBuf>,
+) -> Plan {
+    let mut plan = Plan {
+        disposition: disposition(result.confidence, config),
+        destination: None,
+        decided_by: None,
+        notices: Vec::new(),
+        commands: Vec::new(),
+    };
+    for (index, rule) in config.rules.actions.iter().enumerate() {
+        if !rule.matches(file, result) {
+            continue;
+        }
+        let label = rule.label(index);
+        info!("Rule {} applies to {:?}", label, file);
+        for action in &rule.then {
+            match action {
+                Action::Rename | Action::Quarantine | Action::Skip => {
+                    plan.disposition = match action {
+                        Action::Rename => Disposition::Apply,
+                        Action::Quarantine => Disposition::Review,
+                        _ => Disposition::Skip,
+                    };
+                    plan.decided_by = Some(label.clone());
+                }
+                Action::Move { to } => plan.destination = Some(expand_home(to.trim())),
+                Action::Tag { tags } => {
+                    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+                        if !result.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                            result.tags.push(tag.to_string());
+                        }
+                    }
+                }
+                Action::SetCategory { category } => result.category = Some(category.trim().to_string()),
+                Action::Notify { message } => plan.notices.push(Notice { rule: label.clone(), message: message.clone() }),
+                Action::Run { command } => plan.commands.push(RunCommand { rule: label.clone(), command: command.clone() }),
+            }
+        }
+        if plan.disposition == Disposition::Skip && plan.decided_by.as_ref() == Some(&label) {
+            break;
+        }
+    }
+    if plan.destination.is_none() {
+        plan.destination = place(result);
+    }
+    plan
+}
+
+/// Run `command` for a file now at `path`, analyzed as `result`, warning
+/// rather than failing when it does
+pub async fn run(command: &RunCommand, path: &Path, original: &Path, result: &AnalysisResult) {
+    let Some((program, args)) = command.command.split_first() else {
+        return;
+    };
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let child = tokio::process::Command::new(program)
+        .args(args)
+        .arg(&path)
+        .env("PANOPTES_FILE", &path)
+        .env("PANOPTES_ORIGINAL", original)
+        .env("PANOPTES_NAME", &result.suggested_name)
+        .env("PANOPTES_CATEGORY", result.category.as_deref().unwrap_or_default())
+        .env("PANOPTES_TAGS", result.tags.join(","))
+        .env("PANOPTES_CONFIDENCE", result.confidence.to_string())
+        .env("PANOPTES_RULE", &command.rule)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Rule {}: cannot run {}: {}", command.rule, program, e);
+            return;
+        }
+    };
+    match tokio::time::timeout(COMMAND_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) if output.status.success() => {}
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!("Rule {}: {} failed ({}): {}", command.rule, program, output.status, stderr.trim());
+        }
+        Ok(Err(e)) => warn!("Rule {}: {} failed: {}", command.rule, program, e),
+        Err(_) => warn!("Rule {}: {} took longer than {}s", command.rule, program, COMMAND_TIMEOUT.as_secs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(value: serde_json::Value) -> Vec<ActionRule> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn result(category: Option<&str>, tags: &[&str], confidence: f64) -> AnalysisResult {
+        AnalysisResult {
+            suggested_name: "acme_invoice".to_string(),
+            confidence,
+            category: category.map(String::from),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            file_hash: String::new(),
+            metadata: json!({ "exif": { "model": "Canon EOS R5" }, "pages": 3 }),
+            analyzer: None,
+            model: None,
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let file = Path::new("/scans/Invoice.PDF");
+        let invoice = result(Some("Invoice"), &["Finance"], 0.7);
+        let rule = |when: serde_json::Value| rules(json!([{ "when": when, "then": [{ "action": "skip" }] }])).remove(0);
+
+        assert!(rule(json!({})).matches(file, &invoice));
+        assert!(rule(json!({ "extensions": [".pdf", "png"], "categories": [" invoice "] })).matches(file, &invoice));
+        assert!(!rule(json!({ "extensions": ["png"] })).matches(file, &invoice));
+        assert!(!rule(json!({ "categories": ["Receipt"] })).matches(file, &invoice));
+        assert!(rule(json!({ "tags": ["photo", "finance"] })).matches(file, &invoice));
+        assert!(!rule(json!({ "tags": ["photo"] })).matches(file, &invoice));
+        // From the minimum up to, not including, the maximum
+        assert!(rule(json!({ "min_confidence": 0.7, "max_confidence": 0.8 })).matches(file, &invoice));
+        assert!(!rule(json!({ "max_confidence": 0.7 })).matches(file, &invoice));
+        assert!(rule(json!({ "metadata": { "exif.model": "canon*", "pages": "3" } })).matches(file, &invoice));
+        assert!(!rule(json!({ "metadata": { "exif.model": "Nikon*" } })).matches(file, &invoice));
+        assert!(!rule(json!({ "metadata": { "artist": "*" } })).matches(file, &invoice));
+        assert!(!rule(json!({ "categories": ["Invoice"] })).matches(file, &result(None, &[], 0.9)));
+    }
+
+    #[test]
+    fn test_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("scan.pdf");
+        std::fs::write(&file, vec![0u8; 2048]).unwrap();
+        let any = result(None, &[], 0.5);
+        let rule = |when: serde_json::Value| rules(json!([{ "when": when, "then": [{ "action": "skip" }] }])).remove(0);
+
+        assert!(rule(json!({ "min_size": "1K", "max_size": "2K" })).matches(&file, &any));
+        assert!(!rule(json!({ "min_size": "3K" })).matches(&file, &any));
+        assert!(!rule(json!({ "max_size": "1K" })).matches(&file, &any));
+        // A file that isn't there has no size to match
+        assert!(!rule(json!({ "max_size": "1K" })).matches(&dir.path().join("gone.pdf"), &any));
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut config = AppConfig::default();
+        config.rules.actions = rules(json!([
+            { "name": "scans", "when": { "extensions": ["pdf"] },
+              "then": [{ "action": "set_category", "category": "Invoice" }, { "action": "tag", "tags": ["scan", " "] }] },
+            { "name": "invoices", "when": { "categories": ["invoice"] },
+              "then": [{ "action": "move", "to": "/finance" }, { "action": "rename" }, { "action": "notify" }] },
+            { "name": "tagged", "when": { "tags": ["scan"] }, "then": [{ "action": "move", "to": "/scans" }] },
+        ]));
+        let mut scanned = result(None, &["Scan"], 0.1);
+        let plan = plan(Path::new("/in/a.pdf"), &mut scanned, &config, |_| panic!("moved by a rule"));
+
+        // A later rule sees what an earlier one set, and overrides where the file goes
+        assert_eq!(scanned.category.as_deref(), Some("Invoice"));
+        assert_eq!(scanned.tags, ["Scan"]);
+        assert_eq!(plan.disposition, Disposition::Apply);
+        assert_eq!(plan.decided_by.as_deref(), Some("invoices"));
+        assert_eq!(plan.destination, Some(PathBuf::from("/scans")));
+        assert_eq!(plan.notices.len(), 1);
+
+        // Without a matching rule, by confidence and to where `place` says
+        let mut other = result(None, &[], 0.99);
+        let plan = super::plan(Path::new("/in/a.png"), &mut other, &config, |_| Some(PathBuf::from("/photos")));
+        assert_eq!((plan.disposition, plan.decided_by), (Disposition::Apply, None));
+        assert_eq!(plan.destination, Some(PathBuf::from("/photos")));
+    }
+
+    #[test]
+    fn test_skip_ends_the_rules() {
+        let mut config = AppConfig::default();
+        config.rules.actions = rules(json!([
+            { "when": { "extensions": ["tmp"] }, "then": [{ "action": "skip" }] },
+            { "then": [{ "action": "rename" }, { "action": "run", "command": ["true"] }] },
+        ]));
+        let plan = plan(Path::new("/in/a.tmp"), &mut result(None, &[], 0.9), &config, |_| None);
+        assert_eq!(plan.disposition, Disposition::Skip);
+        assert_eq!(plan.decided_by.as_deref(), Some("rules.actions[0]"));
+        assert!(plan.commands.is_empty());
+    }
+
+    #[test]
+    fn test_problems() {
+        assert!(problems(&rules(json!([{ "when": { "min_size": "10MB" }, "then": [{ "action": "skip" }] }]))).is_empty());
+
+        let found = problems(&rules(json!([
+            { "name": "bad", "when": { "min_size": "lots", "max_confidence": 2.0, "metadata": { "artist": "[" } },
+              "then": [{ "action": "move", "to": " " }, { "action": "run", "command": [] }] },
+            { "then": [] },
+        ])));
+        assert_eq!(found, [
+            "rules.actions[bad].when.min_size must be bytes or a size like 500K, 10MB, 2GiB",
+            "rules.actions[bad].when.max_confidence must be between 0 and 1",
+            &format!("rules.actions[bad].when.metadata[artist] '[' is not a valid pattern: {}", glob::Pattern::new("[").unwrap_err()),
+            "rules.actions[bad].then: move needs a directory in to",
+            "rules.actions[bad].then: run needs a command, as a list of the program and its arguments",
+            "rules.actions[1] needs at least one action in then",
+        ]);
+    }
+}
@@ -0,0 +1,456 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Recurring tasks
+//!
+//! `panoptes watch` and `panoptes serve` run the tasks of `schedule` at the
+//! times of their cron expressions, in local time:
+//!
+//! ```json
+//! "schedule": [
+//!   { "task": "rescan", "cron": "*/30 * * * *" },
+//!   { "task": "backup", "cron": "30 2 * * *", "dir": "/var/backups/panoptes", "keep": 14 },
+//!   { "name": "weekly-report", "task": "report", "cron": "0 7 * * mon", "output": "reports/%Y-%m-%d.html" },
+//!   { "task": "reprocess", "cron": "@weekly", "max_confidence": 0.5, "model": "llava:13b" }
+//! ]
+//! ```
+//!
+//! Expressions have the usual five fields (minute, hour, day of month, month,
+//! day of week) with `*`, lists, ranges, steps and English names, or are one
+//! of `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly`. Runs missed
+//! while Panoptes wasn't running are skipped rather than caught up. The last
+//! run of each task is recorded in the database; `panoptes status` shows it
+//! and the next.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use crate::db::{Database, ScheduledRun};
+use crate::{PanoptesError, Result};
+
+/// Longest a scheduler sleeps before looking at the clock again, so a clock
+/// that changed or a machine that was suspended doesn't throw it off
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How far ahead the next time of an expression is looked for
+const LOOKAHEAD_DAYS: i64 = 5 * 366;
+
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A task and when to run it
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ScheduleEntry {
+    /// Name in logs and `panoptes status`; the task's when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// When to run, as a cron expression
+    pub cron: String,
+    #[serde(flatten)]
+    pub task: ScheduledTask,
+}
+
+impl ScheduleEntry {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.task.as_str())
+    }
+}
+
+/// What a schedule entry does
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "task", rename_all = "snake_case")]
+pub enum ScheduledTask {
+    /// Check the database against the watch directories and fix it, as
+    /// `panoptes verify --fix` does, then queue files the watcher missed
+    Rescan,
+    /// Check the database's integrity, update its statistics and vacuum it
+    Maintenance,
+    /// Copy the database into `dir`, keeping the latest `keep` copies
+    Backup {
+        dir: String,
+        #[serde(default = "default_backup_keep")]
+        keep: usize,
+    },
+    /// Write the report of the latest batch analysis to `output`, where `%Y`,
+    /// `%m`, `%d` and the like stand for the date
+    Report { output: String },
+    /// Remove what `panoptes prune` does, history and cached thumbnails
+    /// after `older_than_days`
+    Prune {
+        #[serde(default = "default_prune_days")]
+        older_than_days: u32,
+    },
+    /// Queue the least confident files for reprocessing, as
+    /// `panoptes reprocess --queue` does
+    Reprocess {
+        max_confidence: f64,
+        #[serde(default = "default_reprocess_limit")]
+        limit: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model: Option<String>,
+        #[serde(default)]
+        force: bool,
+    },
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+fn default_prune_days() -> u32 {
+    90
+}
+
+fn default_reprocess_limit() -> usize {
+    100
+}
+
+impl ScheduledTask {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rescan => "rescan",
+            Self::Maintenance => "maintenance",
+            Self::Backup { .. } => "backup",
+            Self::Report { .. } => "report",
+            Self::Prune { .. } => "prune",
+            Self::Reprocess { .. } => "reprocess",
+        }
+    }
+}
+
+/// Everything wrong with `schedule`, for [`crate::AppConfig::problems`]
+pub fn problems(schedule: &[ScheduleEntry]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut names = HashSet::new();
+    for entry in schedule {
+        let name = entry.name();
+        if !names.insert(name) {
+            problems.push(format!("schedule: more than one entry is named {}; give them a name each", name));
+        }
+        match entry.cron.parse::<Cron>() {
+            Ok(cron) if cron.next_after(Local::now()).is_none() => {
+                problems.push(format!("schedule[{}].cron never matches: {}", name, entry.cron));
+            }
+            Ok(_) => {}
+            Err(PanoptesError::Config(message)) => problems.push(format!("schedule[{}].cron: {}", name, message)),
+            Err(e) => problems.push(format!("schedule[{}].cron: {}", name, e)),
+        }
+        match &entry.task {
+            ScheduledTask::Rescan | ScheduledTask::Maintenance => {}
+            ScheduledTask::Backup { dir, keep } => {
+                if dir.trim().is_empty() {
+                    problems.push(format!("schedule[{}].dir must not be empty", name));
+                }
+                if *keep == 0 {
+                    problems.push(format!("schedule[{}].keep must be greater than 0", name));
+                }
+            }
+            ScheduledTask::Report { output } => {
+                if output.trim().is_empty() {
+                    problems.push(format!("schedule[{}].output must not be empty", name));
+                } else if StrftimeItems::new(output).any(|item| item == Item::Error) {
+                    problems.push(format!("schedule[{}].output has a % that isn't a date field, like %Y or %d; write %% for %", name));
+                }
+            }
+            ScheduledTask::Prune { older_than_days } => {
+                if *older_than_days == 0 {
+                    problems.push(format!("schedule[{}].older_than_days must be greater than 0", name));
+                }
+            }
+            ScheduledTask::Reprocess { max_confidence, limit, .. } => {
+                if !(0.0..=1.0).contains(max_confidence) {
+                    problems.push(format!("schedule[{}].max_confidence must be between 0 and 1", name));
+                }
+                if *limit == 0 {
+                    problems.push(format!("schedule[{}].limit must be greater than 0", name));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    /// Bit n set when n is one of the field's values
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Both day of month and day of week were given, so either will do
+    either_day: bool,
+}
+
+impl std::str::FromStr for Cron {
+    type Err = PanoptesError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(PanoptesError::Config(format!(
+                "'{}' needs five fields (minute hour day-of-month month day-of-week), or to be @hourly, @daily, @weekly, @monthly or @yearly",
+                s
+            )));
+        };
+        let mut weekdays = field(weekday, 0, 7, WEEKDAYS, "day of week")?;
+        // 7 is Sunday too
+        if weekdays & 1 << 7 != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: field(minute, 0, 59, &[], "minute")?,
+            hours: field(hour, 0, 23, &[], "hour")?,
+            days: field(day, 1, 31, &[], "day of month")?,
+            months: field(month, 1, 12, MONTHS, "month")?,
+            weekdays,
+            either_day: !day.starts_with('*') && !weekday.starts_with('*'),
+        })
+    }
+}
+
+/// The values of one field of an expression, as bits; `names` are those of
+/// `min`, `min + 1` and so on
+fn field(spec: &str, min: u32, max: u32, names: &[&str], what: &str) -> Result<u64> {
+    let invalid = |detail: String| PanoptesError::Config(format!("invalid {} '{}': {}", what, spec, detail));
+    let value = |text: &str| -> Result<u32> {
+        let value = match names.iter().position(|name| name.eq_ignore_ascii_case(text)) {
+            Some(i) => min + i as u32,
+            None => text.parse().map_err(|_| invalid(format!("'{}' is not a number", text)))?,
+        };
+        if !(min..=max).contains(&value) {
+            return Err(invalid(format!("{} is not between {} and {}", value, min, max)));
+        }
+        Ok(value)
+    };
+
+    let mut bits = 0;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().ok().filter(|step| *step > 0)
+                    .ok_or_else(|| invalid(format!("'{}' is not a step", step)))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // `5/15` is every 15 from 5
+            None if step.is_some() => (value(range)?, max),
+            None => {
+                let value = value(range)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(invalid(format!("{}-{} runs backwards", start, end)));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl Cron {
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days & 1 << date.day() != 0;
+        let weekday = self.weekdays & 1 << date.weekday().num_days_from_sunday() != 0;
+        if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first time after `after` the expression matches, if it ever does
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let end = start + Duration::days(LOOKAHEAD_DAYS);
+        let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0);
+        let mut t: NaiveDateTime = start;
+        while t < end {
+            if self.months & 1 << t.month() == 0 {
+                let (year, month) = if t.month() == 12 { (t.year() + 1, 1) } else { (t.year(), t.month() + 1) };
+                t = midnight(NaiveDate::from_ymd_opt(year, month, 1)?)?;
+            } else if !self.day_matches(t.date()) {
+                t = midnight(t.date().succ_opt()?)?;
+            } else if self.hours & 1 << t.hour() == 0 {
+                t = t.date().and_hms_opt(t.hour(), 0, 0)? + Duration::hours(1);
+            } else if self.minutes & 1 << t.minute() == 0 {
+                t += Duration::minutes(1);
+            } else {
+                // Skipped when the clocks go forward, and the first of the
+                // two not yet past when they go back
+                let found = match Local.from_local_datetime(&t) {
+                    LocalResult::Single(time) => Some(time),
+                    LocalResult::Ambiguous(first, second) => [first, second].into_iter().find(|time| *time > after),
+                    LocalResult::None => None,
+                };
+                match found {
+                    Some(time) if time > after => return Some(time),
+                    _ => t += Duration::minutes(1),
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Run each entry's task with `run` at its times, for as long as the process
+/// does. `run` returns a line saying what it did; it may block, so is called
+/// on a blocking thread.
+pub fn start<F>(schedule: &[ScheduleEntry], db: &Database, run: F)
+where
+    F: Fn(&ScheduledTask) -> Result<String> + Send + Sync + 'static,
+{
+    let run = Arc::new(run);
+    for entry in schedule {
+        match entry.cron.parse::<Cron>() {
+            Ok(cron) => {
+                tokio::spawn(run_entry(entry.clone(), cron, db.clone(), run.clone()));
+            }
+            Err(e) => warn!("Not scheduling {}: {}", entry.name(), e),
+        }
+    }
+}
+
+async fn run_entry<F>(entry: ScheduleEntry, cron: Cron, db: Database, run: Arc<F>)
+where
+    F: Fn(&ScheduledTask) -> Result<String> + Send + Sync + 'static,
+{
+    let name = entry.name().to_string();
+    let mut first = true;
+    loop {
+        let Some(next) = cron.next_after(Local::now()) else {
+            warn!("Scheduled {} never runs again", name);
+            return;
+        };
+        if std::mem::take(&mut first) {
+            info!("Scheduled {} ({}), first at {}", name, entry.cron, next.format("%Y-%m-%d %H:%M"));
+        } else {
+            debug!("Next {} at {}", name, next.format("%Y-%m-%d %H:%M"));
+        }
+        while Local::now() < next {
+            let wait = (next - Local::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait.min(MAX_SLEEP)).await;
+        }
+
+        info!("Running scheduled {}", name);
+        let started_at = Utc::now();
+        let (task, run) = (entry.task.clone(), run.clone());
+        let outcome = tokio::task::spawn_blocking(move || run(&task)).await
+            .unwrap_or_else(|e| Err(PanoptesError::Config(format!("the task stopped: {}", e))));
+        let (summary, error) = match outcome {
+            Ok(summary) => {
+                info!("Scheduled {}: {}", name, summary);
+                (Some(summary), None)
+            }
+            Err(PanoptesError::NothingToDo(summary)) => {
+                info!("Scheduled {}: {}", name, summary);
+                (Some(summary), None)
+            }
+            Err(e) => {
+                warn!("Scheduled {} failed: {}", name, e);
+                (None, Some(e.to_string()))
+            }
+        };
+        let record = ScheduledRun { name: name.clone(), started_at, finished_at: Utc::now(), summary, error };
+        if let Err(e) = db.record_scheduled_run(&record) {
+            warn!("Failed to record the run of {}: {}", name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cron(expression: &str) -> Cron {
+        expression.parse().unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Local>) -> Option<NaiveDateTime> {
+        cron(expression).next_after(after).map(|t| t.naive_local())
+    }
+
+    fn bits(values: impl IntoIterator<Item = u32>) -> u64 {
+        values.into_iter().fold(0, |bits, v| bits | 1 << v)
+    }
+
+    #[test]
+    fn test_parse() {
+        let workdays = cron("*/15 9-17 * * mon-fri");
+        assert_eq!(workdays.minutes, bits([0, 15, 30, 45]));
+        assert_eq!(workdays.hours, bits(9..=17));
+        assert_eq!(workdays.days, bits(1..=31));
+        assert_eq!(workdays.weekdays, bits(1..=5));
+        assert!(!workdays.either_day);
+
+        assert_eq!(cron("5/20,58 * * JAN,dec *").minutes, bits([5, 25, 45, 58]));
+        assert_eq!(cron("* * * JAN,dec *").months, bits([1, 12]));
+        assert_eq!(cron("0 0 * * 7"), cron("0 0 * * sun"));
+        assert_eq!(cron("@weekly"), cron("0 0 * * 0"));
+        assert_eq!(cron("@daily"), cron("0 0 * * *"));
+        assert!(cron("0 0 13 * fri").either_day);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for expression in ["* * * *", "* * * * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "5-1 * * * *",
+            "*/0 * * * *", "* * * foo *", "@often", ""]
+        {
+            assert!(expression.parse::<Cron>().is_err(), "{:?} parsed", expression);
+        }
+    }
+
+    #[test]
+    fn test_next_after() {
+        // Wednesday
+        let now = at(2025, 1, 15, 10, 0);
+        let time = |d, h, min| NaiveDate::from_ymd_opt(2025, 1, d).unwrap().and_hms_opt(h, min, 0);
+        assert_eq!(next("30 2 * * *", now), time(16, 2, 30));
+        assert_eq!(next("*/30 * * * *", now), time(15, 10, 30));
+        assert_eq!(next("0 7 * * mon", now), time(20, 7, 0));
+        // Day of month or day of week
+        assert_eq!(next("0 0 13 * fri", now), time(17, 0, 0));
+        assert_eq!(next("0 0 1 * *", now), NaiveDate::from_ymd_opt(2025, 2, 1).unwrap().and_hms_opt(0, 0, 0));
+        assert_eq!(next("0 0 29 2 *", now), NaiveDate::from_ymd_opt(2028, 2, 29).unwrap().and_hms_opt(0, 0, 0));
+        assert_eq!(next("0 0 31 2 *", now), None);
+    }
+
+    #[test]
+    fn test_problems() {
+        let entry = |name: Option<&str>, cron: &str| ScheduleEntry {
+            name: name.map(String::from),
+            cron: cron.to_string(),
+            task: ScheduledTask::Rescan,
+        };
+        assert!(problems(&[entry(None, "@hourly"), entry(Some("nightly"), "0 3 * * *")]).is_empty());
+
+        let found = problems(&[entry(None, "@hourly"), entry(None, "0 0 31 2 *"), entry(Some("bad"), "61 * * * *")]);
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found[0].contains("more than one entry is named rescan"));
+        assert!(found[1].contains("never matches"));
+        assert!(found[2].starts_with("schedule[bad].cron: invalid minute"));
+    }
+}
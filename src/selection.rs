@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Picking files by extension, name, size, age and depth
+//!
+//! `analyze` takes these as flags and watch directories as `watch_options`;
+//! either way files are checked before any analyzer sees them. Sizes are bytes
+//! or take a binary suffix (`500K`, `1.5MB`, `2GiB`); ages take `s`, `m`, `h`,
+//! `d` or `w` (`12h`, `7d`).
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::{PanoptesError, Result};
+
+/// Which files to process; everything when empty
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSelection {
+    /// Only files with these extensions (without the dot, any case)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Leave out files, and directories, whose name or path matches one of these globs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Only files at least this big
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+    /// Only files at most this big
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+    /// Only files modified within this long
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_than: Option<String>,
+    /// Only files at most this many directories below the one searched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+}
+
+impl FileSelection {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A [`FileSelection`] ready for matching
+#[derive(Debug, Clone, Default)]
+pub struct Selector {
+    extensions: Vec<String>,
+    exclude: Vec<glob::Pattern>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<SystemTime>,
+    max_depth: Option<usize>,
+}
+
+impl Selector {
+    pub fn new(selection: &FileSelection) -> Result<Self> {
+        let exclude = selection.exclude.iter()
+            .map(|pattern| glob::Pattern::new(pattern)
+                .map_err(|e| PanoptesError::Config(format!("Invalid exclude pattern '{}': {}", pattern, e))))
+            .collect::<Result<Vec<_>>>()?;
+        let size = |value: &Option<String>| value.as_deref()
+            .map(|s| parse_size(s).ok_or_else(|| PanoptesError::Config(format!(
+                "Invalid size '{}'; use bytes or a suffix like 500K, 10MB, 2GiB", s
+            ))))
+            .transpose();
+        let newer_than = selection.newer_than.as_deref()
+            .map(|s| parse_age(s).ok_or_else(|| PanoptesError::Config(format!(
+                "Invalid age '{}'; use a number with s, m, h, d or w, like 7d", s
+            ))))
+            .transpose()?;
+
+        Ok(Self {
+            extensions: selection.extensions.iter()
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+            exclude,
+            min_size: size(&selection.min_size)?,
+            max_size: size(&selection.max_size)?,
+            modified_after: newer_than.and_then(|age| SystemTime::now().checked_sub(age)),
+            max_depth: selection.max_depth,
+        })
+    }
+
+    /// Whether the directory `dir`, `depth` levels below the one searched, is
+    /// worth looking in
+    pub fn descends(&self, dir: &Path, depth: usize) -> bool {
+        !self.max_depth.is_some_and(|max| depth > max) && !self.excluded(dir)
+    }
+
+    /// Whether to process `file`, `depth` directories below the one searched.
+    /// Reads the file's metadata only when a size or age limit needs it.
+    pub fn matches(&self, file: &Path, depth: usize) -> bool {
+        if self.max_depth.is_some_and(|max| depth > max) || self.excluded(file) {
+            return false;
+        }
+        if !self.extensions.is_empty() {
+            let ext = file.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            if !self.extensions.contains(&ext) {
+                return false;
+            }
+        }
+        if self.min_size.is_none() && self.max_size.is_none() && self.modified_after.is_none() {
+            return true;
+        }
+
+        let Ok(meta) = std::fs::metadata(file) else {
+            return false;
+        };
+        let len = meta.len();
+        if self.min_size.is_some_and(|min| len < min) || self.max_size.is_some_and(|max| len > max) {
+            return false;
+        }
+        match self.modified_after {
+            Some(after) => meta.modified().is_ok_and(|modified| modified >= after),
+            None => true,
+        }
+    }
+
+    /// Whether to process `file`, which is at `relative` in the directory
+    /// searched, checking the directories in between as well
+    pub fn matches_at(&self, file: &Path, relative: &Path) -> bool {
+        let depth = relative.components().count().saturating_sub(1);
+        let mut dirs = file.ancestors().skip(1).take(depth);
+        (1..=depth).rev().all(|level| dirs.next().is_some_and(|dir| self.descends(dir, level)))
+            && self.matches(file, depth)
+    }
+
+    fn excluded(&self, path: &Path) -> bool {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        self.exclude.iter().any(|pattern| pattern.matches(&name) || pattern.matches_path(path))
+    }
+}
+
+/// Bytes in a size like `1024`, `500K`, `1.5MB` or `2GiB` (units are powers of 1024)
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let shift = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        "t" | "tb" | "tib" => 40,
+        _ => return None,
+    };
+    Some((number * (1u64 << shift) as f64) as u64)
+}
+
+/// Length of an age like `45s`, `30m`, `12h`, `7d` or `2w`
+pub fn parse_age(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit = value.chars().last()?;
+    let number: u64 = value[..value.len() - unit.len_utf8()].trim().parse().ok()?;
+    let seconds = match unit.to_ascii_lowercase() {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Installing the watcher as a systemd service
+//!
+//! `panoptes service install` writes a unit running `panoptes watch` with the
+//! current binary and config file, either system-wide or, with `--user`, for
+//! the user's own service manager. The unit uses `Type=notify`, so systemd
+//! knows when the watcher is ready and restarts it if the watchdog stops
+//! hearing from it (see [`crate::daemon::notify`]).
+//!
+//! Units carry a header marking them as generated; install and uninstall leave
+//! any other unit of the same name alone unless forced.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use crate::{PanoptesError, Result};
+
+/// Name of the installed unit
+pub const UNIT_NAME: &str = "panoptes.service";
+
+/// First line of generated units
+const GENERATED_HEADER: &str = "# Generated by `panoptes service install`; rerun it rather than editing this file";
+
+/// A unit running the watcher
+#[derive(Debug, Clone)]
+pub struct Unit {
+    /// For the user's service manager rather than the system's
+    pub user: bool,
+    /// The panoptes binary
+    pub exe: PathBuf,
+    /// The config file; its directory is the working directory
+    pub config: PathBuf,
+    /// Account a system unit runs as; root when unset
+    pub run_as: Option<String>,
+}
+
+impl Unit {
+    /// A unit running this binary with `config_path`. System units run as the
+    /// user who invoked sudo, or the current user.
+    pub fn for_config(config_path: &Path, user: bool) -> Result<Self> {
+        let config = config_path.canonicalize().map_err(|e| PanoptesError::Config(format!(
+            "Config file {} not found ({}); create one with `panoptes init`", config_path.display(), e
+        )))?;
+        let exe = std::env::current_exe()?;
+        let exe = exe.canonicalize().unwrap_or(exe);
+        let run_as = if user { None } else { invoking_user() };
+        Ok(Self { user, exe, config, run_as })
+    }
+
+    fn working_dir(&self) -> &Path {
+        self.config.parent().unwrap_or(Path::new("/"))
+    }
+
+    /// The unit file's contents
+    pub fn render(&self) -> String {
+        let exec_start = [self.exe.as_path(), Path::new("--config"), self.config.as_path(), Path::new("watch")]
+            .iter()
+            .map(|arg| quote_arg(&arg.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut unit = vec![
+            GENERATED_HEADER.to_string(),
+            "[Unit]".to_string(),
+            "Description=Panoptes file scanner".to_string(),
+            // Ollama is usually a system service; ordering on a unit that doesn't exist is a no-op
+            "After=network-online.target ollama.service".to_string(),
+        ];
+        if !self.user {
+            // The user manager has no network-online.target
+            unit.push("Wants=network-online.target".to_string());
+        }
+
+        unit.extend([
+            String::new(),
+            "[Service]".to_string(),
+            "Type=notify".to_string(),
+            format!("ExecStart={}", exec_start),
+            format!("WorkingDirectory={}", escape_specifiers(&self.working_dir().to_string_lossy())),
+        ]);
+        if let Some(account) = &self.run_as {
+            unit.push(format!("User={}", account));
+        }
+        unit.extend([
+            "Restart=on-failure",
+            "RestartSec=5",
+            "WatchdogSec=60",
+            "",
+            // Watch directories can be anywhere and change at runtime, so the
+            // file system stays writable outside the OS directories
+            "NoNewPrivileges=yes",
+            "RestrictSUIDSGID=yes",
+            "LockPersonality=yes",
+            "RestrictRealtime=yes",
+            "MemoryDenyWriteExecute=yes",
+            "SystemCallArchitectures=native",
+        ].map(String::from));
+        if !self.user {
+            // These need namespaces the user manager can't always set up
+            unit.extend([
+                "ProtectSystem=full",
+                "ProtectKernelTunables=yes",
+                "ProtectKernelModules=yes",
+                "ProtectKernelLogs=yes",
+                "ProtectControlGroups=yes",
+                "ProtectClock=yes",
+                "ProtectHostname=yes",
+                "PrivateDevices=yes",
+                "RestrictNamespaces=yes",
+            ].map(String::from));
+        }
+
+        unit.extend([
+            String::new(),
+            "[Install]".to_string(),
+            format!("WantedBy={}", if self.user { "default.target" } else { "multi-user.target" }),
+        ]);
+        let mut unit = unit.join("\n");
+        unit.push('\n');
+        unit
+    }
+}
+
+/// The user behind this process: whoever ran sudo, else the current user
+/// unless that is root
+fn invoking_user() -> Option<String> {
+    let name = std::env::var("SUDO_USER").ok()
+        .or_else(|| std::env::var("USER").ok())
+        .filter(|name| !name.is_empty())?;
+    (name != "root").then_some(name)
+}
+
+/// Quote an `ExecStart=` argument so systemd passes it through unchanged
+fn quote_arg(arg: &str) -> String {
+    let arg = escape_specifiers(arg).replace('$', "$$");
+    if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';')) {
+        return arg;
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escape `%`, which starts a specifier in unit files
+fn escape_specifiers(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+/// Where the unit goes: `/etc/systemd/system`, or the user's
+/// `~/.config/systemd/user` with `user`
+pub fn unit_path(user: bool) -> Result<PathBuf> {
+    if !user {
+        return Ok(PathBuf::from("/etc/systemd/system").join(UNIT_NAME));
+    }
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .ok_or_else(|| PanoptesError::Config("HOME is not set; cannot find the user unit directory".to_string()))?;
+    Ok(config_home.join("systemd/user").join(UNIT_NAME))
+}
+
+/// Whether the unit at `path` was written by `panoptes service install`
+pub fn is_generated(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .is_ok_and(|content| content.lines().next() == Some(GENERATED_HEADER))
+}
+
+/// Write `unit` to its unit path, replacing a unit installed earlier; a unit
+/// of the same name from elsewhere is only replaced with `force`
+pub fn install(unit: &Unit, force: bool) -> Result<PathBuf> {
+    let path = unit_path(unit.user)?;
+    if path.exists() && !force && !is_generated(&path) {
+        return Err(PanoptesError::Config(format!(
+            "{} exists and was not generated by panoptes; use --force to replace it", path.display()
+        )));
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| permission_hint(e, unit.user))?;
+    }
+    std::fs::write(&path, unit.render()).map_err(|e| permission_hint(e, unit.user))?;
+    Ok(path)
+}
+
+/// Remove the installed unit, returning its path; one not generated by
+/// panoptes is only removed with `force`
+pub fn remove(user: bool, force: bool) -> Result<PathBuf> {
+    let path = unit_path(user)?;
+    if !path.exists() {
+        return Err(PanoptesError::Config(format!("No service installed at {}", path.display())));
+    }
+    if !force && !is_generated(&path) {
+        return Err(PanoptesError::Config(format!(
+            "{} was not generated by panoptes; use --force to remove it", path.display()
+        )));
+    }
+    std::fs::remove_file(&path).map_err(|e| permission_hint(e, user))?;
+    Ok(path)
+}
+
+/// System units need root; say so instead of a bare "permission denied"
+fn permission_hint(error: std::io::Error, user: bool) -> PanoptesError {
+    if error.kind() == std::io::ErrorKind::PermissionDenied && !user {
+        return PanoptesError::Config(format!(
+            "{}; system services need root, so run with sudo or use --user", error
+        ));
+    }
+    error.into()
+}
+
+/// Run `systemctl` (with `--user` for the user manager), its output going to ours
+pub fn systemctl(user: bool, args: &[&str]) -> Result<ExitStatus> {
+    let mut command = Command::new("systemctl");
+    if user {
+        command.arg("--user");
+    }
+    command.args(args).status().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => PanoptesError::Config("systemctl not found; is this a systemd system?".to_string()),
+        _ => e.into(),
+    })
+}
+
+/// Run `systemctl`, failing unless it succeeds
+pub fn systemctl_checked(user: bool, args: &[&str]) -> Result<()> {
+    let status = systemctl(user, args)?;
+    if !status.success() {
+        return Err(PanoptesError::Config(format!("`systemctl {}` failed ({})", args.join(" "), status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Sidecar files: a file's suggestion, tags, category and provenance written
+//! next to it, so they travel with copies of the file and survive losing the
+//! database
+//!
+//! With `sidecars.enabled`, analyzing `photo.jpg` writes
+//! `photo.jpg.panoptes.json`, or with `sidecars.format` `xmp` an XMP sidecar
+//! `photo.jpg.xmp` that photo managers read (other files still get JSON). An
+//! XMP sidecar some other program wrote is left alone. Sidecars move with
+//! their file whenever Panoptes renames, moves or restores it, and are never
+//! analyzed themselves.
+
+use chrono::Utc;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::analyzers::AnalysisResult;
+use crate::config::{AppConfig, SidecarFormat};
+use crate::Result;
+
+const JSON_SUFFIX: &str = ".panoptes.json";
+const XMP_SUFFIX: &str = ".xmp";
+
+/// Namespace of the Panoptes properties in XMP sidecars, which also marks
+/// sidecars Panoptes may overwrite
+const XMP_NAMESPACE: &str = "https://github.com/hyperpolymath/panoptes/ns/1.0/";
+
+const GENERATOR: &str = concat!("Panoptes ", env!("CARGO_PKG_VERSION"));
+
+/// Contents of a JSON sidecar
+#[derive(Debug, Serialize)]
+struct Sidecar<'a> {
+    generator: &'static str,
+    /// The file's name when it was analyzed
+    original_name: String,
+    suggested_name: &'a str,
+    confidence: f64,
+    category: Option<&'a str>,
+    tags: &'a [String],
+    file_hash: &'a str,
+    /// ID of the file's record in the database that wrote this
+    record_id: Option<&'a str>,
+    analyzed_at: String,
+    metadata: &'a serde_json::Value,
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// The sidecars `path` may have
+fn sidecars_of(path: &Path) -> [PathBuf; 2] {
+    [with_suffix(path, JSON_SUFFIX), with_suffix(path, XMP_SUFFIX)]
+}
+
+/// Whether `path` is a sidecar of another file rather than a file to analyze
+pub fn is_sidecar(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if name.ends_with(JSON_SUFFIX) {
+        return true;
+    }
+    // Only `photo.jpg.xmp` next to `photo.jpg`; a lone .xmp file is just a file
+    name.ends_with(XMP_SUFFIX) && path.to_str()
+        .and_then(|p| p.get(..p.len() - XMP_SUFFIX.len()))
+        .is_some_and(|file| Path::new(file).is_file())
+}
+
+/// Whether a sidecar may be (over)written: it doesn't exist yet or Panoptes wrote it
+fn is_ours(sidecar: &Path) -> bool {
+    match std::fs::read_to_string(sidecar) {
+        Ok(contents) => contents.contains(XMP_NAMESPACE) || contents.contains(r#""generator": "Panoptes "#),
+        Err(e) => e.kind() == std::io::ErrorKind::NotFound,
+    }
+}
+
+/// Write the sidecar for `path` describing `result`, if `sidecars.enabled`.
+/// `record_id` is the file's record, when it has one.
+pub fn write(path: &Path, result: &AnalysisResult, record_id: Option<&str>, config: &AppConfig) -> Result<()> {
+    if !config.sidecars.enabled {
+        return Ok(());
+    }
+    let [json_path, xmp_path] = sidecars_of(path);
+    let is_image = path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| config.analyzers.image.formats.iter().any(|f| f.eq_ignore_ascii_case(ext)));
+
+    let sidecar = Sidecar {
+        generator: GENERATOR,
+        original_name: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        suggested_name: &result.suggested_name,
+        confidence: result.confidence,
+        category: result.category.as_deref(),
+        tags: &result.tags,
+        file_hash: &result.file_hash,
+        record_id,
+        analyzed_at: Utc::now().to_rfc3339(),
+        metadata: &result.metadata,
+    };
+
+    if config.sidecars.format == SidecarFormat::Xmp && is_image {
+        if is_ours(&xmp_path) {
+            std::fs::write(&xmp_path, to_xmp(&sidecar))?;
+            debug!("Wrote sidecar {:?}", xmp_path);
+            return Ok(());
+        }
+        debug!("Leaving {:?}, written by another program; writing JSON instead", xmp_path);
+    }
+    std::fs::write(&json_path, serde_json::to_string_pretty(&sidecar)?)?;
+    debug!("Wrote sidecar {:?}", json_path);
+    Ok(())
+}
+
+/// Write the sidecar for `path`, logging rather than failing
+pub fn write_or_warn(path: &Path, result: &AnalysisResult, record_id: Option<&str>, config: &AppConfig) {
+    if let Err(e) = write(path, result, record_id, config) {
+        warn!("Failed to write sidecar for {:?}: {}", path, e);
+    }
+}
+
+/// Move the sidecars of a file that moved from `from` to `to` along with it
+pub fn follow(from: &Path, to: &Path) {
+    for (old, new) in sidecars_of(from).into_iter().zip(sidecars_of(to)) {
+        if !old.is_file() || (new.exists() && !is_ours(&new)) {
+            continue;
+        }
+        let moved = std::fs::rename(&old, &new)
+            .or_else(|_| std::fs::copy(&old, &new).and_then(|_| std::fs::remove_file(&old)));
+        if let Err(e) = moved {
+            warn!("Failed to move sidecar {:?} to {:?}: {}", old, new, e);
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// An XMP packet: the suggestion as the title, tags as keywords, and the rest
+/// as Panoptes properties
+fn to_xmp(sidecar: &Sidecar) -> String {
+    let mut properties = vec![
+        ("xmp:CreatorTool", sidecar.generator.to_string()),
+        ("xmp:MetadataDate", sidecar.analyzed_at.clone()),
+        ("panoptes:OriginalName", sidecar.original_name.clone()),
+        ("panoptes:SuggestedName", sidecar.suggested_name.to_string()),
+        ("panoptes:Confidence", format!("{:.2}", sidecar.confidence)),
+        ("panoptes:FileHash", sidecar.file_hash.to_string()),
+    ];
+    if let Some(category) = sidecar.category {
+        properties.push(("panoptes:Category", category.to_string()));
+    }
+    if let Some(id) = sidecar.record_id {
+        properties.push(("panoptes:RecordId", id.to_string()));
+    }
+    let properties: String = properties.iter()
+        .map(|(name, value)| format!("\n    {}=\"{}\"", name, escape(value)))
+        .collect();
+    let keywords: String = sidecar.tags.iter()
+        .map(|tag| format!("\n     <rdf:li>{}</rdf:li>", escape(tag)))
+        .collect();
+
+    format!(r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:panoptes="{namespace}"{properties}>
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">{title}</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <dc:subject>
+    <rdf:Bag>{keywords}
+    </rdf:Bag>
+   </dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>
+"#, namespace = XMP_NAMESPACE, properties = properties, title = escape(sidecar.suggested_name), keywords = keywords)
+}
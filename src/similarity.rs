@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Finding files alike
+//!
+//! Three fingerprints say how alike two files are:
+//!
+//! - the blake3 hash every record has, for files with the same contents;
+//! - a 64-bit difference hash of images, which stays close when a picture is
+//!   resized, recompressed or lightly edited;
+//! - an embedding of a document's text, or a recording's transcript (see
+//!   [`crate::transcribe`]), from `ai_engine.models.embedding` (with
+//!   `similarity.embeddings`), close for the same report exported twice, to
+//!   PDF and DOCX, or with a paragraph changed.
+//!
+//! Two files score 1 when their contents are the same, and otherwise by the
+//! closest of their image hashes or embeddings. Fingerprints are kept by file
+//! hash in the `fingerprints` table, and made for records lacking one
+//! whenever similar files are looked for, by `panoptes similar` and the web
+//! UI's "More like this".
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{debug, warn};
+
+use crate::analyzers::{calculate_file_hash, extract_text};
+use crate::db::{Database, FileRecord};
+use crate::ollama::OllamaClient;
+use crate::{AppConfig, Result};
+
+/// Characters of a document's text that go into its embedding
+const EMBEDDING_TEXT: usize = 4000;
+
+/// Documents with less text than this are left to their hash
+const MIN_TEXT: usize = 50;
+
+/// What can be known about how a file looks and reads
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub file_hash: String,
+    /// Difference hash, for images
+    pub image_hash: Option<u64>,
+    /// Embedding of the text, for documents
+    pub embedding: Option<Vec<f32>>,
+    /// Model `embedding` came from
+    pub embedding_model: Option<String>,
+}
+
+/// Why two files are alike
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Likeness {
+    /// Same contents
+    Identical,
+    /// Close image hashes
+    LooksAlike,
+    /// Close text embeddings
+    ReadsAlike,
+}
+
+impl Likeness {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Identical => "identical",
+            Self::LooksAlike => "looks alike",
+            Self::ReadsAlike => "reads alike",
+        }
+    }
+}
+
+/// A recorded file like the one looked for
+#[derive(Debug, Clone, Serialize)]
+pub struct Match {
+    pub file: FileRecord,
+    /// From 0 to 1
+    pub score: f64,
+    pub likeness: Likeness,
+}
+
+/// Difference hash of the image at `path`: each bit says whether a pixel of
+/// the 9x8 grayscale thumbnail is brighter than the one right of it
+pub fn image_hash(path: &Path) -> Option<u64> {
+    if image::ImageFormat::from_path(path).is_err() && !crate::analyzers::image::needs_conversion(path) {
+        return None;
+    }
+    let image = match crate::analyzers::image::open(path) {
+        Ok(image) => image,
+        Err(e) => {
+            debug!("Cannot decode {:?} for hashing: {}", path, e);
+            return None;
+        }
+    };
+    let small = image.resize_exact(9, 8, image::imageops::FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash)
+}
+
+/// The text of a document, if it is one with enough text to compare
+pub fn document_text(path: &Path) -> Option<String> {
+    let text = extract_text(path).ok()?;
+    let text: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (text.chars().count() >= MIN_TEXT).then(|| text.chars().take(EMBEDDING_TEXT).collect())
+}
+
+/// The transcript of a recording, if one was made with enough text to compare
+fn transcript_text(db: &Database, file_hash: &str) -> Option<String> {
+    let transcript = db.get_transcript(file_hash).ok()??;
+    let text: String = transcript.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (text.chars().count() >= MIN_TEXT).then(|| text.chars().take(EMBEDDING_TEXT).collect())
+}
+
+/// Fingerprint of the file at `path`, whose hash is `file_hash`. None when it
+/// has text but no embedding could be made, to be tried again later.
+pub async fn fingerprint(path: &Path, file_hash: &str, db: &Database, config: &AppConfig) -> Option<Fingerprint> {
+    let mut fingerprint = Fingerprint { file_hash: file_hash.to_string(), ..Default::default() };
+    fingerprint.image_hash = image_hash(path);
+    if fingerprint.image_hash.is_none() && config.similarity.embeddings {
+        if let Some(text) = document_text(path).or_else(|| transcript_text(db, file_hash)) {
+            let model = &config.ai_engine.models.embedding;
+            match OllamaClient::new(&config.ai_engine.url).embed(model, &text).await {
+                Ok(embedding) => {
+                    fingerprint.embedding = Some(embedding);
+                    fingerprint.embedding_model = Some(model.clone());
+                }
+                Err(e) => {
+                    warn!("No embedding of {:?}: {}", path, e);
+                    return None;
+                }
+            }
+        }
+    }
+    Some(fingerprint)
+}
+
+/// Cosine similarity of two embeddings of the same length
+fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (x, y) in a.iter().zip(b) {
+        dot += *x as f64 * *y as f64;
+        norm_a += *x as f64 * *x as f64;
+        norm_b += *y as f64 * *y as f64;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 { 0.0 } else { dot / (norm_a.sqrt() * norm_b.sqrt()) }
+}
+
+/// How alike the files of two fingerprints are, if they can be compared
+pub fn compare(a: &Fingerprint, b: &Fingerprint) -> Option<(f64, Likeness)> {
+    if a.file_hash == b.file_hash {
+        return Some((1.0, Likeness::Identical));
+    }
+    if let (Some(x), Some(y)) = (a.image_hash, b.image_hash) {
+        return Some((1.0 - (x ^ y).count_ones() as f64 / 64.0, Likeness::LooksAlike));
+    }
+    match (&a.embedding, &b.embedding) {
+        (Some(x), Some(y)) if a.embedding_model == b.embedding_model => Some((cosine(x, y), Likeness::ReadsAlike)),
+        _ => None,
+    }
+}
+
+/// Make fingerprints for the recorded files lacking one, or whose embedding
+/// came from another model than the one configured; returns how many were made
+pub async fn index(db: &Database, config: &AppConfig) -> Result<usize> {
+    let mut known: HashMap<String, Fingerprint> = db.get_fingerprints()?.into_iter()
+        .map(|f| (f.file_hash.clone(), f))
+        .collect();
+    let model = &config.ai_engine.models.embedding;
+    let mut made = 0;
+    for file in db.get_live_files()?.into_iter().filter(|file| !file.is_remote()) {
+        let stale = match known.get(&file.file_hash) {
+            None => true,
+            Some(f) => config.similarity.embeddings && f.embedding_model.as_ref().is_some_and(|m| m != model),
+        };
+        let path = Path::new(&file.new_path);
+        if !stale || !path.is_file() {
+            continue;
+        }
+        // The file may have changed since it was recorded
+        if !calculate_file_hash(path).is_ok_and(|hash| hash == file.file_hash) {
+            continue;
+        }
+        if let Some(fingerprint) = fingerprint(path, &file.file_hash, db, config).await {
+            db.set_fingerprint(&fingerprint)?;
+            known.insert(fingerprint.file_hash.clone(), fingerprint);
+            made += 1;
+        }
+    }
+    Ok(made)
+}
+
+/// Recorded files like the one `of` fingerprints, most alike first, leaving
+/// out the records `skip` says to
+pub fn matches(db: &Database, of: &Fingerprint, skip: impl Fn(&FileRecord) -> bool, config: &AppConfig) -> Result<Vec<Match>> {
+    let fingerprints: HashMap<String, Fingerprint> = db.get_fingerprints()?.into_iter()
+        .map(|f| (f.file_hash.clone(), f))
+        .collect();
+    let mut matches: Vec<Match> = db.get_live_files()?.into_iter()
+        .filter(|file| !skip(file))
+        .filter_map(|file| {
+            let (score, likeness) = match fingerprints.get(&file.file_hash) {
+                Some(other) => compare(of, other)?,
+                None if file.file_hash == of.file_hash => (1.0, Likeness::Identical),
+                None => return None,
+            };
+            (score >= config.similarity.min_score).then_some(Match { file, score, likeness })
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.file.new_path.cmp(&b.file.new_path)));
+    matches.truncate(config.similarity.limit);
+    Ok(matches)
+}
+
+/// Recorded files like the file at `path`, which needn't be recorded itself
+pub async fn similar_to_path(db: &Database, path: &Path, config: &AppConfig) -> Result<Vec<Match>> {
+    index(db, config).await?;
+    let hash = calculate_file_hash(path)?;
+    let of = match db.get_fingerprint(&hash)? {
+        Some(known) => known,
+        None => fingerprint(path, &hash, db, config).await
+            .unwrap_or(Fingerprint { file_hash: hash, ..Default::default() }),
+    };
+    // Not the file itself, if it is recorded
+    let itself = std::fs::canonicalize(path)?;
+    matches(db, &of, |file| std::fs::canonicalize(&file.new_path).is_ok_and(|p| p == itself), config)
+}
+
+/// Recorded files like the recorded file `file`
+pub async fn similar_to_record(db: &Database, file: &FileRecord, config: &AppConfig) -> Result<Vec<Match>> {
+    index(db, config).await?;
+    let of = db.get_fingerprint(&file.file_hash)?
+        .unwrap_or(Fingerprint { file_hash: file.file_hash.clone(), ..Default::default() });
+    matches(db, &of, |other| other.id == file.id, config)
+}
@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Files watch mode leaves alone, being Panoptes' own output
+//!
+//! Renaming or moving a file makes events for its new path, which would
+//! bring it back to be analyzed and renamed again. For a minute after
+//! Panoptes puts a file somewhere ([`remember`]), events for that path are
+//! ignored ([`is_recent`]).
+//!
+//! With `rules.skip_conformant` (the default), files whose names already
+//! have the shape Panoptes gives them are taken as named, by Panoptes or by
+//! hand ([`is_conformant`]): a name matching the template of `rules` or of a
+//! category in `rules.categories`, numbered or not, whose `{name}` is as the
+//! sanitizer leaves names. Only templates carrying `{date}` or `{time}`
+//! count, as any tidy name would match `{name}` alone.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::analyzers::clean_filename;
+use crate::config::{AppConfig, Casing};
+
+/// How long events for a file Panoptes moved are ignored
+const RECENT: Duration = Duration::from_secs(60);
+
+/// Paths Panoptes moved files to, and when
+static MOVED_TO: Mutex<BTreeMap<PathBuf, Instant>> = Mutex::new(BTreeMap::new());
+
+/// `path` as events give it: absolute, without `.` components
+fn key(path: &Path) -> PathBuf {
+    let absolute = if path.is_relative() {
+        std::env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_path_buf())
+    } else {
+        path.to_path_buf()
+    };
+    absolute.components().collect()
+}
+
+/// Note that Panoptes just put a file at `path`
+pub fn remember(path: &Path) {
+    let mut moved = MOVED_TO.lock().unwrap_or_else(|e| e.into_inner());
+    moved.retain(|_, at| at.elapsed() < RECENT);
+    moved.insert(key(path), Instant::now());
+}
+
+/// Whether Panoptes put a file at `path` in the last minute
+pub fn is_recent(path: &Path) -> bool {
+    let mut moved = MOVED_TO.lock().unwrap_or_else(|e| e.into_inner());
+    moved.retain(|_, at| at.elapsed() < RECENT);
+    moved.contains_key(&key(path))
+}
+
+/// A piece of a naming template
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Part<'a> {
+    Literal(&'a str),
+    /// `2024-05-31`
+    Date,
+    /// `142500`
+    Time,
+    /// Digits, or nothing
+    Counter,
+    /// `{name}`, `{category}` or metadata
+    Free,
+}
+
+fn parts(template: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else { break };
+        if start > 0 {
+            parts.push(Part::Literal(&rest[..start]));
+        }
+        parts.push(match &rest[start + 1..start + len] {
+            "date" => Part::Date,
+            "time" => Part::Time,
+            "counter" => Part::Counter,
+            _ => Part::Free,
+        });
+        rest = &rest[start + len + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Literal(rest));
+    }
+    parts
+}
+
+/// Whether `name` is made of `parts`, `free` saying what a free part may be
+fn matches(parts: &[Part], name: &str, free: &dyn Fn(&str) -> bool) -> bool {
+    let digits = |n: usize| name.get(..n).filter(|s| s.chars().all(|c| c.is_ascii_digit()));
+    match parts.split_first() {
+        None => name.is_empty(),
+        Some((Part::Literal(literal), rest)) => {
+            name.get(..literal.len()).is_some_and(|start| start.eq_ignore_ascii_case(literal))
+                && matches(rest, &name[literal.len()..], free)
+        }
+        Some((Part::Date, rest)) => {
+            let date = name.get(..10).is_some_and(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok());
+            date && matches(rest, &name[10..], free)
+        }
+        Some((Part::Time, rest)) => digits(6).is_some() && matches(rest, &name[6..], free),
+        Some((Part::Counter, rest)) => {
+            let run = name.chars().take_while(char::is_ascii_digit).count();
+            matches(rest, &name[run..], free) || (run > 0 && matches(rest, name, free))
+        }
+        Some((Part::Free, rest)) => name.char_indices()
+            .map(|(i, c)| i + c.len_utf8())
+            .any(|end| free(&name[..end]) && matches(rest, &name[end..], free)),
+    }
+}
+
+/// Whether the name of `path` has the shape Panoptes gives names
+pub fn is_conformant(path: &Path, config: &AppConfig) -> bool {
+    let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+        return false;
+    };
+    let sanitizer = &config.rules.sanitizer;
+    let default = config.rules.date_prefix.then_some(("{date}_{name}", Casing::Keep));
+    let rules = config.rules.categories.values()
+        .filter_map(|rule| rule.template.as_deref().map(|template| (template, rule.casing)));
+    default.into_iter().chain(rules).any(|(template, casing)| {
+        let mut parts = parts(template);
+        if !parts.iter().any(|part| matches!(part, Part::Date | Part::Time)) {
+            return false;
+        }
+        let free = |part: &str| casing.apply(&clean_filename(part, sanitizer)) == part;
+        if matches(&parts, stem, &free) {
+            return true;
+        }
+        // Numbered at the end, the template not saying where
+        !parts.contains(&Part::Counter) && {
+            parts.extend([Part::Literal("_"), Part::Counter]);
+            matches(&parts, stem, &free)
+        }
+    })
+}
@@ -0,0 +1,476 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Remote sources: S3 buckets, WebDAV shares and SFTP directories
+//!
+//! Each of `sources` is a remote directory whose new files are downloaded to
+//! a staging directory, analyzed and recorded like local ones, then removed
+//! again. What is to be done with them goes by the usual thresholds: with
+//! `rename` set, confident suggestions are renamed on the source; others, and
+//! all of them without `rename`, make up a rename plan, listed by
+//! `panoptes sources plan` and done by `panoptes sources apply`.
+//!
+//! ```json
+//! "sources": [
+//!   { "name": "scans", "url": "s3://office-scans/inbox", "endpoint": "https://minio.lan:9000" },
+//!   { "name": "nas", "url": "https://nas.lan/remote.php/dav/files/me/Inbox/", "username": "me" },
+//!   { "name": "server", "url": "sftp://me@files.lan/srv/incoming", "recursive": true }
+//! ]
+//! ```
+//!
+//! `panoptes sources sync` looks at them once; `panoptes watch` looks every
+//! `interval_mins`. The `remote_objects` table keeps what became of each file,
+//! by its key (its path under the source's URL) and version (ETag, or size
+//! and time), so only new and changed files are downloaded. S3 credentials
+//! are `access_key` and `secret_key` or the usual `AWS_*` variables, SFTP
+//! goes through the `sftp` program and its SSH keys.
+
+pub mod s3;
+pub mod sftp;
+pub mod webdav;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::analyzers::AnalyzerRegistry;
+use crate::config::SourceConfig;
+use crate::db::Database;
+use crate::organizer::expand_home;
+use crate::renamer::{disposition, final_name, Disposition};
+use crate::{AppConfig, PanoptesError, Result};
+
+/// A file on a remote source
+#[derive(Debug, Clone)]
+pub struct RemoteObject {
+    /// Path under the source's URL, with `/` separators
+    pub key: String,
+    pub size: u64,
+    /// Changes when the file does: its ETag, or its size and time
+    pub version: Option<String>,
+}
+
+/// Listing, fetching and renaming the files of a remote directory
+#[async_trait]
+pub trait Source: Send + Sync {
+    /// The files there, in subdirectories too when recursive
+    async fn list(&self) -> Result<Vec<RemoteObject>>;
+
+    /// Download the file `key` to `to`
+    async fn download(&self, key: &str, to: &Path) -> Result<()>;
+
+    /// Rename the file `key` to `new_key`, which must not exist
+    async fn rename(&self, key: &str, new_key: &str) -> Result<()>;
+}
+
+/// The connector for `config`'s URL
+pub fn open(config: &SourceConfig) -> Result<Box<dyn Source>> {
+    let url = config.url.as_str();
+    if url.starts_with("s3://") {
+        Ok(Box::new(s3::S3::new(config)?))
+    } else if url.starts_with("sftp://") {
+        Ok(Box::new(sftp::Sftp::new(config)?))
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(Box::new(webdav::WebDav::new(config)?))
+    } else {
+        Err(PanoptesError::Config(format!("Unsupported source URL: {}", url)))
+    }
+}
+
+/// The source named `name` in `config`
+pub fn find<'a>(config: &'a AppConfig, name: &str) -> Result<&'a SourceConfig> {
+    config.sources.iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| PanoptesError::Config(format!("No source named '{}'", name)))
+}
+
+/// What became of a file of a remote source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteState {
+    /// Renamed on the source
+    Renamed,
+    /// To be renamed with `panoptes sources apply`
+    Planned,
+    /// Already named as suggested
+    Kept,
+    /// Not analyzed, or not confident enough to rename
+    Skipped,
+    /// Downloading, analyzing or renaming it failed; tried again next time
+    Failed,
+}
+
+impl RemoteState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Renamed => "renamed",
+            Self::Planned => "planned",
+            Self::Kept => "kept",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for RemoteState {
+    type Err = PanoptesError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "renamed" => Ok(Self::Renamed),
+            "planned" => Ok(Self::Planned),
+            "kept" => Ok(Self::Kept),
+            "skipped" => Ok(Self::Skipped),
+            "failed" => Ok(Self::Failed),
+            other => Err(PanoptesError::Config(format!("Unknown remote file state: {}", other))),
+        }
+    }
+}
+
+/// A file of a remote source as recorded in `remote_objects`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteRecord {
+    pub source: String,
+    pub key: String,
+    pub version: Option<String>,
+    pub state: RemoteState,
+    /// Its analysis
+    pub file_id: Option<String>,
+    /// What it was or is to be renamed to
+    pub new_key: Option<String>,
+    pub confidence: Option<f64>,
+    pub error: Option<String>,
+    pub seen_at: DateTime<Utc>,
+}
+
+impl RemoteRecord {
+    fn new(source: &str, object: &RemoteObject, state: RemoteState) -> Self {
+        Self {
+            source: source.to_string(),
+            key: object.key.clone(),
+            version: object.version.clone(),
+            state,
+            file_id: None,
+            new_key: None,
+            confidence: None,
+            error: None,
+            seen_at: Utc::now(),
+        }
+    }
+}
+
+/// What looking at a source did
+#[derive(Debug, Default, Serialize)]
+pub struct Pass {
+    /// Files there
+    pub listed: usize,
+    /// New or changed files analyzed
+    pub analyzed: usize,
+    pub renamed: usize,
+    /// Renames left for `panoptes sources apply`
+    pub planned: usize,
+    /// Files too large, of no known type, or named well enough
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// The address of the file `key` of the source `config`, as it is recorded
+pub fn address(config: &SourceConfig, key: &str) -> String {
+    format!("{}/{}", config.url.trim_end_matches('/'), key)
+}
+
+/// Where files of `config` are downloaded to
+fn staging_dir(config: &SourceConfig) -> PathBuf {
+    match config.staging_dir.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(dir) => expand_home(dir),
+        None => std::env::temp_dir().join("panoptes-sources").join(&config.name),
+    }
+}
+
+/// `dir/file` of `dir` and `file`, or `file` at the top
+fn join_key(dir: &str, file: &str) -> String {
+    if dir.is_empty() { file.to_string() } else { format!("{}/{}", dir, file) }
+}
+
+/// The directory, stem and extension (with its dot) of `key`
+fn split_key(key: &str) -> (&str, &str, &str) {
+    let (dir, file) = key.rsplit_once('/').unwrap_or(("", key));
+    match file.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => (dir, stem, &file[stem.len()..]),
+        _ => (dir, file, ""),
+    }
+}
+
+/// `key` with its file name, extension aside, replaced by `name`
+fn renamed_key(key: &str, name: &str) -> String {
+    let (dir, _, ext) = split_key(key);
+    join_key(dir, &format!("{}{}", name, ext))
+}
+
+/// `key`, numbered until it is not in `taken`
+fn free_key(key: String, taken: &HashSet<String>) -> String {
+    if !taken.contains(&key) {
+        return key;
+    }
+    let (dir, stem, ext) = split_key(&key);
+    (2..).map(|n| join_key(dir, &format!("{}_{}{}", stem, n, ext)))
+        .find(|k| !taken.contains(k))
+        .unwrap_or_default()
+}
+
+/// Look at the source `source` for new and changed files, analyze them, and
+/// rename or plan renaming them. Nothing is downloaded or changed with `dry_run`.
+pub async fn sync(
+    source: &SourceConfig,
+    config: &AppConfig,
+    registry: &AnalyzerRegistry,
+    db: &Database,
+    dry_run: bool,
+) -> Result<Pass> {
+    let remote = open(source)?;
+    let objects = remote.list().await?;
+    let seen = db.remote_versions(&source.name)?;
+    let mut taken: HashSet<String> = objects.iter().map(|o| o.key.clone()).collect();
+    let mut pass = Pass { listed: objects.len(), ..Default::default() };
+    let staging = staging_dir(source);
+
+    for object in &objects {
+        match seen.get(&object.key) {
+            Some(Some(version)) if object.version.as_ref() == Some(version) => continue,
+            // Renamed here: this is its first listing under its new name
+            Some(None) => {
+                if !dry_run {
+                    db.set_remote_version(&source.name, &object.key, object.version.as_deref())?;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if object.size > source.max_size_mb * 1024 * 1024 || registry.find_analyzer(Path::new(&object.key)).is_none() {
+            if !dry_run {
+                db.record_remote_object(&RemoteRecord::new(&source.name, object, RemoteState::Skipped))?;
+            }
+            pass.skipped += 1;
+            continue;
+        }
+        if dry_run {
+            info!("Would analyze {}", address(source, &object.key));
+            pass.analyzed += 1;
+            continue;
+        }
+
+        let record = match analyze(remote.as_ref(), source, object, config, registry, db, &staging, &mut taken).await {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Failed to analyze {}: {}", address(source, &object.key), e);
+                let mut record = RemoteRecord::new(&source.name, object, RemoteState::Failed);
+                record.error = Some(e.to_string());
+                record
+            }
+        };
+        match record.state {
+            RemoteState::Renamed => pass.renamed += 1,
+            RemoteState::Planned => pass.planned += 1,
+            RemoteState::Kept | RemoteState::Skipped => pass.skipped += 1,
+            RemoteState::Failed => pass.failed += 1,
+        }
+        if record.state != RemoteState::Failed {
+            pass.analyzed += 1;
+        }
+        db.record_remote_object(&record)?;
+    }
+    Ok(pass)
+}
+
+/// Download `object`, analyze and record it, and rename it or plan to
+#[allow(clippy::too_many_arguments)]
+async fn analyze(
+    remote: &dyn Source,
+    source: &SourceConfig,
+    object: &RemoteObject,
+    config: &AppConfig,
+    registry: &AnalyzerRegistry,
+    db: &Database,
+    staging: &Path,
+    taken: &mut HashSet<String>,
+) -> Result<RemoteRecord> {
+    // A directory of its own, so the file keeps its name for the analyzers
+    let dir = staging.join(uuid::Uuid::new_v4().to_string());
+    tokio::fs::create_dir_all(&dir).await?;
+    let file = dir.join(object.key.rsplit('/').next().unwrap_or(&object.key));
+    let analysis = async {
+        remote.download(&object.key, &file).await?;
+        let analyzer = registry.find_analyzer(&file)
+            .ok_or_else(|| PanoptesError::UnsupportedFileType(object.key.clone()))?;
+        let mut result = analyzer.analyze(&file, config).await?;
+        if let Some(extra) = result.metadata.as_object_mut() {
+            extra.insert("source".to_string(), source.name.clone().into());
+        }
+        let name = final_name(&result, &file, config);
+        Ok::<_, PanoptesError>((result, name))
+    }.await;
+    if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+        warn!("Failed to remove {}: {}", dir.display(), e);
+    }
+    let (result, name) = analysis?;
+
+    let file_id = db.record_analysis(Path::new(&address(source, &object.key)), &result)?;
+    let mut record = RemoteRecord::new(&source.name, object, RemoteState::Skipped);
+    record.file_id = Some(file_id);
+    record.confidence = Some(result.confidence);
+
+    let new_key = renamed_key(&object.key, &name);
+    if new_key == object.key {
+        record.state = RemoteState::Kept;
+        return Ok(record);
+    }
+    let new_key = free_key(new_key, taken);
+    record.state = match disposition(result.confidence, config) {
+        Disposition::Apply if source.rename => {
+            remote.rename(&object.key, &new_key).await?;
+            info!("Renamed {} to {}", address(source, &object.key), new_key);
+            db.record_remote_object(&RemoteRecord {
+                key: new_key.clone(),
+                version: None,
+                state: RemoteState::Kept,
+                ..record.clone()
+            })?;
+            RemoteState::Renamed
+        }
+        Disposition::Apply | Disposition::Review => RemoteState::Planned,
+        Disposition::Skip => return Ok(record),
+    };
+    taken.insert(new_key.clone());
+    record.new_key = Some(new_key);
+    Ok(record)
+}
+
+/// Do the planned renames of the source `source`; returns those done.
+/// Nothing is changed with `dry_run`.
+pub async fn apply(source: &SourceConfig, db: &Database, dry_run: bool) -> Result<Vec<RemoteRecord>> {
+    let remote = open(source)?;
+    let mut done = Vec::new();
+    for mut record in db.get_remote_objects(Some(&source.name), RemoteState::Planned)? {
+        let Some(new_key) = record.new_key.clone() else {
+            continue;
+        };
+        if dry_run {
+            done.push(record);
+            continue;
+        }
+        if let Err(e) = remote.rename(&record.key, &new_key).await {
+            warn!("Failed to rename {}: {}", address(source, &record.key), e);
+            continue;
+        }
+        db.record_remote_object(&RemoteRecord {
+            key: new_key,
+            version: None,
+            state: RemoteState::Kept,
+            seen_at: Utc::now(),
+            ..record.clone()
+        })?;
+        record.state = RemoteState::Renamed;
+        db.record_remote_object(&record)?;
+        done.push(record);
+    }
+    Ok(done)
+}
+
+/// Percent-encode `value` as a URL path, leaving `/` as it is
+pub(crate) fn encode_path(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `value` with its `%XX` escapes decoded
+pub(crate) fn decode_path(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Walk the elements of `xml`, calling `visit` with the names (without
+/// namespace prefix) of the elements open, and with each element's text, or
+/// None as the element starts
+pub(crate) fn walk_xml(xml: &str, mut visit: impl FnMut(&[String], Option<&str>)) -> Result<()> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut open = Vec::new();
+    loop {
+        let event = reader.read_event()
+            .map_err(|e| PanoptesError::Source(format!("Unreadable XML: {}", e)))?;
+        match event {
+            Event::Start(element) => {
+                open.push(String::from_utf8_lossy(element.local_name().as_ref()).into_owned());
+                visit(&open, None);
+            }
+            Event::Empty(element) => {
+                open.push(String::from_utf8_lossy(element.local_name().as_ref()).into_owned());
+                visit(&open, None);
+                open.pop();
+            }
+            Event::Text(text) => {
+                let text = text.unescape()
+                    .map_err(|e| PanoptesError::Source(format!("Unreadable XML: {}", e)))?;
+                visit(&open, Some(&text));
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Save the body of `response` to `to`
+pub(crate) async fn save_body(mut response: reqwest::Response, to: &Path) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut file = tokio::fs::File::create(to).await?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+/// `response`, or the error of a request refused with it
+pub(crate) async fn check(what: &str, response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let body: String = body.chars().take(200).collect();
+    Err(PanoptesError::Source(format!("{} failed with {}: {}", what, status, body.trim())))
+}
@@ -0,0 +1,232 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! S3 buckets, and stores speaking its API (MinIO, Garage, R2...)
+//!
+//! Requests are signed with AWS Signature Version 4 and address the bucket in
+//! the path (`https://endpoint/bucket/key`), which every such store takes.
+//! Renaming is copying and deleting.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{Method, RequestBuilder};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use super::{check, encode_path, save_body, walk_xml, RemoteObject, Source};
+use crate::config::SourceConfig;
+use crate::{PanoptesError, Result};
+
+/// Keys listed per request
+const PAGE_SIZE: &str = "1000";
+
+pub struct S3 {
+    client: reqwest::Client,
+    /// Scheme, host and port, without a trailing slash
+    endpoint: String,
+    /// Host (and port) as sent in the `Host` header
+    host: String,
+    bucket: String,
+    /// Keys of the source start with this, e.g. `inbox/`
+    prefix: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    recursive: bool,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// A query string as signed: sorted, with keys and values percent-encoded
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut pairs: Vec<(String, String)> = query.iter()
+        .map(|(k, v)| (encode_path(k).replace('/', "%2F"), encode_path(v).replace('/', "%2F")))
+        .collect();
+    pairs.sort();
+    pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&")
+}
+
+impl S3 {
+    pub fn new(config: &SourceConfig) -> Result<Self> {
+        let location = config.url.trim_start_matches("s3://");
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(PanoptesError::Config(format!("No bucket in {}", config.url)));
+        }
+        let prefix = prefix.trim_matches('/');
+        let endpoint = config.endpoint.clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", config.region));
+        let endpoint = endpoint.trim_end_matches('/').to_string();
+        let url = reqwest::Url::parse(&endpoint)
+            .map_err(|e| PanoptesError::Config(format!("Bad S3 endpoint {}: {}", endpoint, e)))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(PanoptesError::Config(format!("No host in S3 endpoint {}", endpoint))),
+        };
+        let credential = |value: &Option<String>, variable: &str| {
+            value.clone().filter(|v| !v.is_empty())
+                .or_else(|| std::env::var(variable).ok())
+                .ok_or_else(|| PanoptesError::Config(format!("Source {} needs {} (or the setting)", config.name, variable)))
+        };
+        Ok(Self {
+            client: reqwest::Client::new(),
+            endpoint,
+            host,
+            bucket: bucket.to_string(),
+            prefix: if prefix.is_empty() { String::new() } else { format!("{}/", prefix) },
+            region: config.region.clone(),
+            access_key: credential(&config.access_key, "AWS_ACCESS_KEY_ID")?,
+            secret_key: credential(&config.secret_key, "AWS_SECRET_ACCESS_KEY")?,
+            recursive: config.recursive,
+        })
+    }
+
+    /// Path of the object `key` of the source
+    fn object_path(&self, key: &str) -> String {
+        format!("/{}/{}", encode_path(&self.bucket), encode_path(&format!("{}{}", self.prefix, key)))
+    }
+
+    /// A request signed with Signature Version 4, its payload unsigned
+    fn request(&self, method: Method, path: &str, query: &[(&str, &str)], headers: &[(&str, String)]) -> RequestBuilder {
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let query = canonical_query(query);
+
+        let mut signed: Vec<(String, String)> = vec![
+            ("host".to_string(), self.host.clone()),
+            ("x-amz-content-sha256".to_string(), "UNSIGNED-PAYLOAD".to_string()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        signed.extend(headers.iter().map(|(k, v)| (k.to_lowercase(), v.trim().to_string())));
+        signed.sort();
+        let canonical_headers: String = signed.iter().map(|(k, v)| format!("{}:{}\n", k, v)).collect();
+        let signed_headers = signed.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>().join(";");
+
+        let canonical = format!("{}\n{}\n{}\n{}\n{}\nUNSIGNED-PAYLOAD", method, path, query, canonical_headers, signed_headers);
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&Sha256::digest(canonical.as_bytes())));
+        let key = ["s3", "aws4_request"].iter().fold(
+            hmac(&hmac(format!("AWS4{}", self.secret_key).as_bytes(), &date), &self.region),
+            |key, part| hmac(&key, part),
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, hex(&hmac(&key, &string_to_sign))
+        );
+
+        let url = if query.is_empty() {
+            format!("{}{}", self.endpoint, path)
+        } else {
+            format!("{}{}?{}", self.endpoint, path, query)
+        };
+        let mut request = self.client.request(method, url)
+            .header("x-amz-content-sha256", "UNSIGNED-PAYLOAD")
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization);
+        for (name, value) in headers {
+            request = request.header(*name, value.as_str());
+        }
+        request
+    }
+}
+
+/// One page of a ListObjectsV2 reply
+#[derive(Default)]
+struct Page {
+    objects: Vec<RemoteObject>,
+    next: Option<String>,
+}
+
+fn parse_page(xml: &str) -> Result<Page> {
+    let mut page = Page::default();
+    walk_xml(xml, |open, text| {
+        let field = open.last().map(String::as_str);
+        let parent = open.len().checked_sub(2).map(|i| open[i].as_str());
+        match (parent, field, text) {
+            (_, Some("Contents"), None) => page.objects.push(RemoteObject { key: String::new(), size: 0, version: None }),
+            (Some("Contents"), Some("Key"), Some(text)) => {
+                if let Some(object) = page.objects.last_mut() {
+                    object.key = text.to_string();
+                }
+            }
+            (Some("Contents"), Some("Size"), Some(text)) => {
+                if let Some(object) = page.objects.last_mut() {
+                    object.size = text.parse().unwrap_or_default();
+                }
+            }
+            (Some("Contents"), Some("ETag"), Some(text)) => {
+                if let Some(object) = page.objects.last_mut() {
+                    object.version = Some(text.trim_matches('"').to_string());
+                }
+            }
+            (Some("ListBucketResult"), Some("NextContinuationToken"), Some(text)) => page.next = Some(text.to_string()),
+            _ => {}
+        }
+    })?;
+    Ok(page)
+}
+
+#[async_trait]
+impl Source for S3 {
+    async fn list(&self) -> Result<Vec<RemoteObject>> {
+        let path = format!("/{}", encode_path(&self.bucket));
+        let mut objects = Vec::new();
+        let mut next: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("max-keys", PAGE_SIZE), ("prefix", self.prefix.as_str())];
+            if !self.recursive {
+                query.push(("delimiter", "/"));
+            }
+            if let Some(ref token) = next {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let response = self.request(Method::GET, &path, &query, &[]).send().await?;
+            let xml = check("Listing the bucket", response).await?.text().await?;
+            let page = parse_page(&xml)?;
+            objects.extend(page.objects.into_iter().filter_map(|mut object| {
+                // Directory markers aren't files
+                object.key = object.key.strip_prefix(&self.prefix)?.to_string();
+                (!object.key.is_empty() && !object.key.ends_with('/')).then_some(object)
+            }));
+            match page.next {
+                Some(token) => next = Some(token),
+                None => break,
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn download(&self, key: &str, to: &Path) -> Result<()> {
+        let response = self.request(Method::GET, &self.object_path(key), &[], &[]).send().await?;
+        save_body(check(&format!("Downloading {}", key), response).await?, to).await
+    }
+
+    async fn rename(&self, key: &str, new_key: &str) -> Result<()> {
+        let new_path = self.object_path(new_key);
+        let head = self.request(Method::HEAD, &new_path, &[], &[]).send().await?;
+        if head.status().is_success() {
+            return Err(PanoptesError::Source(format!("{} already exists", new_key)));
+        }
+        let copy_source = self.object_path(key);
+        let response = self.request(Method::PUT, &new_path, &[], &[("x-amz-copy-source", copy_source)]).send().await?;
+        // A copy can fail after it started, with 200 and an error for a body
+        let body = check(&format!("Copying {} to {}", key, new_key), response).await?.text().await?;
+        if body.contains("<Error>") {
+            return Err(PanoptesError::Source(format!("Copying {} to {} failed: {}", key, new_key, body.trim())));
+        }
+        let response = self.request(Method::DELETE, &self.object_path(key), &[], &[]).send().await?;
+        check(&format!("Deleting {}", key), response).await?;
+        Ok(())
+    }
+}
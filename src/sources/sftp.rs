@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! SFTP directories, through the `sftp` program in batch mode
+//!
+//! So the host keys, SSH keys, agent and `~/.ssh/config` of the user running
+//! Panoptes apply as they would on the command line. There is no password
+//! prompt: the server must take a key (`identity_file`, or ssh's own).
+
+use async_trait::async_trait;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::{decode_path, RemoteObject, Source};
+use crate::config::SourceConfig;
+use crate::organizer::expand_home;
+use crate::{PanoptesError, Result};
+
+pub struct Sftp {
+    /// `user@host` or `host`
+    destination: String,
+    port: Option<u16>,
+    /// The source's directory on the server, relative to the login
+    /// directory when it doesn't start with `/`
+    root: String,
+    identity_file: Option<String>,
+    recursive: bool,
+}
+
+/// `value` as one argument of an sftp command
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// An entry of `ls -ln`: whether it is a directory, its size, its version and name
+fn parse_entry(line: &str) -> Option<(bool, u64, String, String)> {
+    let mut rest = line.trim_start();
+    let mut fields = Vec::with_capacity(8);
+    for _ in 0..8 {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    // Names may be printed with the directory listed
+    let name = rest.rsplit('/').next()?.to_string();
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    let kind = fields[0].chars().next()?;
+    if kind != '-' && kind != 'd' {
+        return None;
+    }
+    let size = fields[4].parse().ok()?;
+    Some((kind == 'd', size, format!("{} {}", size, fields[5..8].join(" ")), name))
+}
+
+impl Sftp {
+    pub fn new(config: &SourceConfig) -> Result<Self> {
+        let url = reqwest::Url::parse(&config.url)
+            .map_err(|e| PanoptesError::Config(format!("Bad SFTP URL {}: {}", config.url, e)))?;
+        let host = url.host_str()
+            .ok_or_else(|| PanoptesError::Config(format!("No host in {}", config.url)))?;
+        let destination = match url.username() {
+            "" => host.to_string(),
+            user => format!("{}@{}", decode_path(user), host),
+        };
+        // sftp://host/~/inbox is inbox in the login directory
+        let path = decode_path(url.path());
+        let root = match path.strip_prefix("/~") {
+            Some(rest) => rest.trim_start_matches('/').to_string(),
+            None => path,
+        };
+        Ok(Self {
+            destination,
+            port: url.port(),
+            root: root.trim_end_matches('/').to_string(),
+            identity_file: config.identity_file.clone(),
+            recursive: config.recursive,
+        })
+    }
+
+    /// Path on the server of `key`, a file or directory of the source
+    fn remote_path(&self, key: &str) -> String {
+        match (self.root.as_str(), key) {
+            (root, "") => root.to_string(),
+            ("", key) => key.to_string(),
+            (root, key) => format!("{}/{}", root, key),
+        }
+    }
+
+    /// Run sftp `commands`, returning what they printed
+    async fn run(&self, commands: &str) -> Result<String> {
+        let mut command = Command::new("sftp");
+        command.args(["-q", "-b", "-", "-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            command.arg("-P").arg(port.to_string());
+        }
+        if let Some(ref identity) = self.identity_file {
+            command.arg("-i").arg(expand_home(identity));
+        }
+        let mut child = command.arg(&self.destination)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| PanoptesError::Source(format!("Failed to run sftp: {}", e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(commands.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(PanoptesError::Source(format!("sftp {}: {}", self.destination, error.trim())));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl Source for Sftp {
+    async fn list(&self) -> Result<Vec<RemoteObject>> {
+        let mut objects = Vec::new();
+        let mut dirs = vec![String::new()];
+        while let Some(dir) = dirs.pop() {
+            let path = self.remote_path(&dir);
+            let listing = self.run(&format!("ls -ln {}\n", quote(if path.is_empty() { "." } else { &path }))).await?;
+            // Commands are echoed after `sftp>`
+            for line in listing.lines().filter(|l| !l.starts_with("sftp>")) {
+                let Some((is_dir, size, version, name)) = parse_entry(line) else {
+                    continue;
+                };
+                let key = if dir.is_empty() { name } else { format!("{}/{}", dir, name) };
+                if is_dir {
+                    if self.recursive {
+                        dirs.push(key);
+                    }
+                } else {
+                    objects.push(RemoteObject { key, size, version: Some(version) });
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn download(&self, key: &str, to: &Path) -> Result<()> {
+        let commands = format!("get {} {}\n", quote(&self.remote_path(key)), quote(&to.to_string_lossy()));
+        self.run(&commands).await.map(|_| ())
+    }
+
+    async fn rename(&self, key: &str, new_key: &str) -> Result<()> {
+        // Fails when the new name is taken
+        let commands = format!("rename {} {}\n", quote(&self.remote_path(key)), quote(&self.remote_path(new_key)));
+        self.run(&commands).await.map(|_| ())
+    }
+}
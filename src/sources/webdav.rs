@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! WebDAV shares (Nextcloud, ownCloud, Apache mod_dav, rclone serve...)
+//!
+//! Directories are listed with `PROPFIND` one level at a time, and files
+//! renamed with `MOVE`, never overwriting.
+
+use async_trait::async_trait;
+use reqwest::{Method, RequestBuilder, StatusCode};
+use std::path::Path;
+
+use super::{check, decode_path, encode_path, save_body, walk_xml, RemoteObject, Source};
+use crate::config::SourceConfig;
+use crate::{PanoptesError, Result};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop>
+<d:resourcetype/><d:getcontentlength/><d:getetag/><d:getlastmodified/>
+</d:prop></d:propfind>"#;
+
+pub struct WebDav {
+    client: reqwest::Client,
+    /// The share's directory, ending with `/`
+    base: reqwest::Url,
+    username: Option<String>,
+    password: Option<String>,
+    recursive: bool,
+}
+
+/// An entry of a `PROPFIND` reply
+#[derive(Default)]
+struct Entry {
+    /// Its decoded path on the server
+    path: String,
+    collection: bool,
+    size: u64,
+    etag: Option<String>,
+    modified: Option<String>,
+}
+
+fn parse_multistatus(xml: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    walk_xml(xml, |open, text| {
+        let field = open.last().map(String::as_str);
+        let parent = open.len().checked_sub(2).map(|i| open[i].as_str());
+        let entry = match (field, text) {
+            (Some("response"), None) => {
+                entries.push(Entry::default());
+                return;
+            }
+            _ => match entries.last_mut() {
+                Some(entry) => entry,
+                None => return,
+            },
+        };
+        match (parent, field, text) {
+            (Some("response"), Some("href"), Some(href)) => {
+                // Absolute URLs or paths, percent-encoded
+                let path = reqwest::Url::parse(href).map(|url| url.path().to_string()).unwrap_or_else(|_| href.to_string());
+                entry.path = decode_path(&path);
+            }
+            (Some("resourcetype"), Some("collection"), None) => entry.collection = true,
+            (_, Some("getcontentlength"), Some(size)) => entry.size = size.parse().unwrap_or_default(),
+            (_, Some("getetag"), Some(etag)) => entry.etag = Some(etag.trim_matches('"').to_string()),
+            (_, Some("getlastmodified"), Some(modified)) => entry.modified = Some(modified.to_string()),
+            _ => {}
+        }
+    })?;
+    Ok(entries)
+}
+
+impl WebDav {
+    pub fn new(config: &SourceConfig) -> Result<Self> {
+        let url = format!("{}/", config.url.trim_end_matches('/'));
+        let base = reqwest::Url::parse(&url)
+            .map_err(|e| PanoptesError::Config(format!("Bad WebDAV URL {}: {}", config.url, e)))?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            base,
+            username: config.username.clone(),
+            password: config.password.clone(),
+            recursive: config.recursive,
+        })
+    }
+
+    /// URL of `key`, a file or directory of the share
+    fn url(&self, key: &str) -> Result<reqwest::Url> {
+        self.base.join(&encode_path(key))
+            .map_err(|e| PanoptesError::Source(format!("Bad path {}: {}", key, e)))
+    }
+
+    fn request(&self, method: Method, url: reqwest::Url) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match self.username {
+            Some(ref username) => request.basic_auth(username, self.password.as_ref()),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl Source for WebDav {
+    async fn list(&self) -> Result<Vec<RemoteObject>> {
+        let propfind = Method::from_bytes(b"PROPFIND").expect("PROPFIND is a valid method");
+        let root = decode_path(self.base.path());
+        let mut objects = Vec::new();
+        let mut dirs = vec![String::new()];
+        while let Some(dir) = dirs.pop() {
+            let response = self.request(propfind.clone(), self.url(&dir)?)
+                .header("Depth", "1")
+                .header("Content-Type", "application/xml")
+                .body(PROPFIND_BODY)
+                .send().await?;
+            let xml = check(&format!("Listing {}", self.url(&dir)?), response).await?.text().await?;
+            for entry in parse_multistatus(&xml)? {
+                let Some(key) = entry.path.strip_prefix(&root) else {
+                    continue;
+                };
+                let key = key.trim_end_matches('/');
+                // The directory itself is listed too
+                if key.is_empty() || key == dir {
+                    continue;
+                }
+                if entry.collection {
+                    if self.recursive {
+                        dirs.push(key.to_string());
+                    }
+                    continue;
+                }
+                let version = entry.etag.or_else(|| entry.modified.map(|m| format!("{} {}", entry.size, m)));
+                objects.push(RemoteObject { key: key.to_string(), size: entry.size, version });
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn download(&self, key: &str, to: &Path) -> Result<()> {
+        let response = self.request(Method::GET, self.url(key)?).send().await?;
+        save_body(check(&format!("Downloading {}", key), response).await?, to).await
+    }
+
+    async fn rename(&self, key: &str, new_key: &str) -> Result<()> {
+        let moving = Method::from_bytes(b"MOVE").expect("MOVE is a valid method");
+        let response = self.request(moving, self.url(key)?)
+            .header("Destination", self.url(new_key)?.as_str())
+            .header("Overwrite", "F")
+            .send().await?;
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(PanoptesError::Source(format!("{} already exists", new_key)));
+        }
+        check(&format!("Moving {} to {}", key, new_key), response).await?;
+        Ok(())
+    }
+}
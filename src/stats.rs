@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Library statistics for the terminal
+//!
+//! `panoptes stats` shows how the recorded files split into categories, how
+//! confident the suggestions of recent days were, how many files were
+//! processed each day, the most used tags and how often each analyzer and
+//! model had its suggestions corrected, as text bar charts. The numbers are
+//! the ones the web dashboard charts.
+
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+use std::fmt::Write;
+
+use crate::db::Database;
+use crate::feedback::{self, CorrectionRate};
+use crate::Result;
+
+/// Number of equal confidence ranges from 0 to 1
+const CONFIDENCE_BUCKETS: usize = 10;
+
+/// Width of the longest bar, in characters
+const BAR_WIDTH: usize = 40;
+
+/// Labels longer than this are cut short
+const LABEL_WIDTH: usize = 24;
+
+/// A name with its number of files
+#[derive(Debug, Clone, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub files: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfidenceRange {
+    pub min: f64,
+    pub max: f64,
+    pub files: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayCount {
+    pub day: NaiveDate,
+    pub files: i64,
+}
+
+/// What `panoptes stats` shows
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    /// Recorded files
+    pub files: i64,
+    /// Recorded files per category, most first
+    pub categories: Vec<Count>,
+    /// How many days back the confidence and throughput go, including today
+    pub days: u32,
+    /// Confidence of the files processed in those days
+    pub confidence: Vec<ConfidenceRange>,
+    /// Files processed on each of those days, oldest first
+    pub throughput: Vec<DayCount>,
+    /// The most used tags, most first
+    pub tags: Vec<Count>,
+    /// Corrections per analyzer and model, most often corrected first
+    pub corrections: Vec<CorrectionRate>,
+}
+
+/// Statistics of `db` over the last `days` days, with the `top` most used tags
+pub fn collect(db: &Database, days: u32, top: usize) -> Result<Stats> {
+    let days = days.max(1);
+    // Timestamps are stored in UTC
+    let since = Utc::now().date_naive() - chrono::Duration::days(days as i64 - 1);
+
+    let mut categories: Vec<Count> = db.get_category_stats()?.into_iter()
+        .map(|(name, files)| Count { name, files })
+        .collect();
+    categories.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.name.cmp(&b.name)));
+
+    let bound = |i: usize| i as f64 / CONFIDENCE_BUCKETS as f64;
+    let confidence = db.get_confidence_histogram(since, CONFIDENCE_BUCKETS)?.into_iter()
+        .enumerate()
+        .map(|(i, files)| ConfidenceRange { min: bound(i), max: bound(i + 1), files })
+        .collect();
+
+    let mut throughput: Vec<DayCount> = since.iter_days()
+        .take(days as usize)
+        .map(|day| DayCount { day, files: 0 })
+        .collect();
+    for (day, _, files) in db.get_daily_category_counts(since)? {
+        if let Some(entry) = throughput.iter_mut().find(|d| d.day == day) {
+            entry.files += files;
+        }
+    }
+
+    let mut tags: Vec<Count> = db.get_tag_counts()?.into_iter()
+        .filter(|(_, files)| *files > 0)
+        .map(|(name, files)| Count { name, files })
+        .collect();
+    tags.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.name.cmp(&b.name)));
+    tags.truncate(top);
+
+    let corrections = feedback::rates(db)?;
+
+    Ok(Stats { files: db.get_file_count()?, categories, days, confidence, throughput, tags, corrections })
+}
+
+/// `label` cut to `LABEL_WIDTH` characters
+fn label(text: &str) -> String {
+    if text.chars().count() <= LABEL_WIDTH {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(LABEL_WIDTH - 1).collect();
+    short.push('~');
+    short
+}
+
+/// A bar chart of `rows`, with the share of `total` after each count when given
+fn chart(out: &mut String, rows: &[(String, i64)], total: Option<i64>) {
+    let max = rows.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    if max == 0 {
+        out.push_str("  No files.\n");
+        return;
+    }
+    let labels: Vec<String> = rows.iter().map(|(l, _)| label(l)).collect();
+    let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let digits = max.to_string().len();
+    for (label, (_, count)) in labels.iter().zip(rows) {
+        // Anything above zero gets at least a sliver
+        let len = match (*count as usize * BAR_WIDTH) / max as usize {
+            0 if *count > 0 => 1,
+            len => len,
+        };
+        let _ = write!(out, "  {:<width$}  {:>digits$}", label, count, width = width, digits = digits);
+        let _ = match total.filter(|t| *t > 0) {
+            Some(total) => writeln!(out, " {:<bar$} {:>3}%", "#".repeat(len), count * 100 / total, bar = BAR_WIDTH),
+            None if len > 0 => writeln!(out, " {}", "#".repeat(len)),
+            None => writeln!(out),
+        };
+    }
+}
+
+/// `stats` as text bar charts
+pub fn render(stats: &Stats) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{} file(s) recorded", stats.files);
+
+    out.push_str("\nCategories\n");
+    let rows: Vec<(String, i64)> = stats.categories.iter().map(|c| (c.name.clone(), c.files)).collect();
+    chart(&mut out, &rows, Some(stats.files));
+
+    let span = if stats.days == 1 { "today".to_string() } else { format!("last {} days", stats.days) };
+    let _ = writeln!(out, "\nConfidence, {}", span);
+    let rows: Vec<(String, i64)> = stats.confidence.iter()
+        .map(|r| (format!("{:.0}-{:.0}%", r.min * 100.0, r.max * 100.0), r.files))
+        .collect();
+    chart(&mut out, &rows, None);
+
+    let _ = writeln!(out, "\nFiles processed per day, {}", span);
+    let rows: Vec<(String, i64)> = stats.throughput.iter().map(|d| (d.day.to_string(), d.files)).collect();
+    chart(&mut out, &rows, None);
+    let processed: i64 = stats.throughput.iter().map(|d| d.files).sum();
+    if processed > 0 {
+        let _ = writeln!(out, "  {} in all, {:.1} a day", processed, processed as f64 / stats.days as f64);
+    }
+
+    out.push_str("\nTop tags\n");
+    if stats.tags.is_empty() {
+        out.push_str("  No tags.\n");
+    } else {
+        let rows: Vec<(String, i64)> = stats.tags.iter().map(|t| (t.name.clone(), t.files)).collect();
+        chart(&mut out, &rows, None);
+    }
+
+    out.push_str("\nCorrected names by analyzer and model\n");
+    let corrected: Vec<&CorrectionRate> = stats.corrections.iter().filter(|r| r.corrected > 0).collect();
+    if corrected.is_empty() {
+        out.push_str("  No corrections.\n");
+    } else {
+        let labels: Vec<String> = corrected.iter().map(|r| label(&r.label())).collect();
+        let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        for (label, rate) in labels.iter().zip(&corrected) {
+            let len = ((rate.rate() * BAR_WIDTH as f64).round() as usize).max(1);
+            let _ = writeln!(out, "  {:<width$}  {:<bar$} {:>3.0}% of {}", label, "#".repeat(len), rate.rate() * 100.0, rate.files,
+                width = width, bar = BAR_WIDTH);
+        }
+    }
+    out
+}
@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Summaries of what a directory holds
+//!
+//! `panoptes summarize <dir>` counts the files in a directory by category,
+//! from their records where Panoptes analyzed them and by their extension
+//! where not, finds the dates they span and the largest documents among
+//! them, and has the text model write an overview of it all
+//! (`prompts.summary`): handover notes for an archive. Summaries are kept in
+//! the `summaries` table, to be exported again, and render as Markdown.
+
+use chrono::{DateTime, Utc};
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::analyzers::infer_category;
+use crate::db::Database;
+use crate::ollama::OllamaClient;
+use crate::watcher::should_process;
+use crate::{AppConfig, Result};
+
+/// Categories of files read rather than looked at or listened to
+const DOCUMENT_CATEGORIES: &[&str] = &[
+    "Documents", "Finance", "Career", "Manuals", "Email", "Books", "Spreadsheets", "Presentations",
+];
+
+/// Most documents a summary lists
+const NOTABLE: usize = 10;
+
+/// Most other file names shown to the text model
+const NAMES_SHOWN: usize = 40;
+
+/// What a directory held when it was summarized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub id: String,
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    /// Files with an analysis on record
+    pub analyzed: usize,
+    /// Files by category, `Other` for those without one
+    pub categories: BTreeMap<String, usize>,
+    /// When the oldest and newest files were last modified
+    pub earliest: Option<DateTime<Utc>>,
+    pub latest: Option<DateTime<Utc>>,
+    /// The largest documents
+    pub notable: Vec<Notable>,
+    /// The text model's overview, if it answered
+    pub overview: Option<String>,
+    pub model: Option<String>,
+}
+
+/// A document worth pointing out in a summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notable {
+    /// Where it is, under the summarized directory
+    pub path: String,
+    pub category: String,
+    pub bytes: u64,
+    /// What Panoptes suggested calling it, if it was analyzed
+    pub suggested_name: Option<String>,
+}
+
+/// A file found in the directory
+struct Entry {
+    path: PathBuf,
+    bytes: u64,
+    modified: Option<DateTime<Utc>>,
+    category: String,
+    suggested_name: Option<String>,
+}
+
+/// Summarize the directory `dir`, recursively
+pub async fn summarize(dir: &Path, config: &AppConfig, db: &Database) -> Result<Summary> {
+    info!("Summarizing {:?}", dir);
+    let mut entries = Vec::new();
+    let mut analyzed = 0;
+    for path in files(dir) {
+        let meta = std::fs::metadata(&path)?;
+        let record = db.find_file_by_path(&path).unwrap_or_else(|e| {
+            warn!("Failed to look up {:?}: {}", path, e);
+            None
+        });
+        analyzed += usize::from(record.is_some());
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let category = record.as_ref().and_then(|r| r.category.clone())
+            .or_else(|| infer_category(&name, ext))
+            .unwrap_or_else(|| "Other".to_string());
+        entries.push(Entry {
+            bytes: meta.len(),
+            modified: meta.modified().ok().map(DateTime::<Utc>::from),
+            category,
+            suggested_name: record.map(|r| r.suggested_name),
+            path,
+        });
+    }
+
+    let mut categories = BTreeMap::new();
+    for entry in &entries {
+        *categories.entry(entry.category.clone()).or_insert(0) += 1;
+    }
+    let mut documents: Vec<&Entry> = entries.iter()
+        .filter(|entry| DOCUMENT_CATEGORIES.contains(&entry.category.as_str()))
+        .collect();
+    documents.sort_by_key(|d| std::cmp::Reverse(d.bytes));
+    let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).to_string_lossy().into_owned();
+    let notable = documents.into_iter().take(NOTABLE)
+        .map(|entry| Notable {
+            path: relative(&entry.path),
+            category: entry.category.clone(),
+            bytes: entry.bytes,
+            suggested_name: entry.suggested_name.clone(),
+        })
+        .collect();
+
+    let mut summary = Summary {
+        id: uuid::Uuid::new_v4().to_string(),
+        path: dir.to_string_lossy().into_owned(),
+        created_at: Utc::now(),
+        file_count: entries.len(),
+        total_bytes: entries.iter().map(|entry| entry.bytes).sum(),
+        analyzed,
+        categories,
+        earliest: entries.iter().filter_map(|entry| entry.modified).min(),
+        latest: entries.iter().filter_map(|entry| entry.modified).max(),
+        notable,
+        overview: None,
+        model: None,
+    };
+
+    if !entries.is_empty() {
+        let names: Vec<String> = entries.iter().take(NAMES_SHOWN)
+            .map(|entry| entry.suggested_name.clone().unwrap_or_else(|| relative(&entry.path)))
+            .collect();
+        let prompt = format!("{}\n\n{}\nSome of the files:\n{}", config.prompts.summary, summary.facts(), names.join("\n"));
+        let client = OllamaClient::new(&config.ai_engine.url);
+        match client.generate(&config.ai_engine.models.text, &prompt).await {
+            Ok(overview) if !overview.trim().is_empty() => {
+                summary.overview = Some(overview.trim().to_string());
+                summary.model = Some(config.ai_engine.models.text.clone());
+            }
+            Ok(_) => warn!("The text model had no overview of {:?}", dir),
+            Err(e) => warn!("Text model failed on {:?}: {}", dir, e),
+        }
+    }
+    Ok(summary)
+}
+
+impl Summary {
+    /// The counts, dates and documents, as told to the text model
+    fn facts(&self) -> String {
+        let mut facts = format!("Directory: {}\nFiles: {} ({})\n", self.path, self.file_count, HumanBytes(self.total_bytes));
+        for (category, count) in self.by_count() {
+            let _ = writeln!(facts, "- {}: {}", category, count);
+        }
+        if let (Some(earliest), Some(latest)) = (self.earliest, self.latest) {
+            let _ = writeln!(facts, "Modified from {} to {}", earliest.format("%Y-%m-%d"), latest.format("%Y-%m-%d"));
+        }
+        if !self.notable.is_empty() {
+            facts.push_str("Largest documents:\n");
+            for doc in &self.notable {
+                let _ = writeln!(facts, "- {}", doc.suggested_name.as_deref().unwrap_or(&doc.path));
+            }
+        }
+        facts
+    }
+
+    /// Categories, the most common first
+    fn by_count(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = self.categories.iter().map(|(c, &n)| (c.as_str(), n)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# Contents of {}\n\n", self.path);
+        let _ = writeln!(md, "_Summarized {}_\n", self.created_at.format("%Y-%m-%d %H:%M UTC"));
+        if let Some(overview) = &self.overview {
+            let _ = writeln!(md, "{}\n", overview);
+        }
+
+        md.push_str("## At a glance\n\n");
+        let _ = writeln!(md, "- **Files:** {} ({})", self.file_count, HumanBytes(self.total_bytes));
+        let _ = writeln!(md, "- **Analyzed by Panoptes:** {}", self.analyzed);
+        if let (Some(earliest), Some(latest)) = (self.earliest, self.latest) {
+            let _ = writeln!(md, "- **Modified:** {} to {}", earliest.format("%Y-%m-%d"), latest.format("%Y-%m-%d"));
+        }
+
+        if !self.categories.is_empty() {
+            md.push_str("\n## By category\n\n| Category | Files |\n|---|---:|\n");
+            for (category, count) in self.by_count() {
+                let _ = writeln!(md, "| {} | {} |", escape(category), count);
+            }
+        }
+
+        if !self.notable.is_empty() {
+            md.push_str("\n## Notable documents\n\n| Document | Category | Size |\n|---|---|---:|\n");
+            for doc in &self.notable {
+                let name = match &doc.suggested_name {
+                    Some(suggested) => format!("{} ({})", escape(&doc.path), escape(suggested)),
+                    None => escape(&doc.path),
+                };
+                let _ = writeln!(md, "| {} | {} | {} |", name, escape(&doc.category), HumanBytes(doc.bytes));
+            }
+        }
+        md
+    }
+}
+
+/// Pipes would end a table cell
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// The files under `dir`, in order, leaving out hidden and temporary ones
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if !should_process(&path) {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
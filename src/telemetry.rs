@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Tracing spans and their export
+//!
+//! Each file gets a correlation ID when it turns up, and the spans of what
+//! happens to it carry that ID: `file` (the watch event) with `settle` (the
+//! wait for the file to stop changing), then `job` (taken from the queue)
+//! with `analyze`, `llm` (one request to the AI engine, with how long it
+//! waited for a free slot), `record` (the database write), `rename` and
+//! `follow_up` (rule actions). So `grep` on the ID in the log, or a search
+//! on the `cid` attribute in a trace viewer, shows where the time went.
+//!
+//! `--log-format json` writes each log line as JSON with its spans, and
+//! `--verbose` logs each span's duration as it closes. With `--otlp-endpoint`
+//! or the standard `OTEL_EXPORTER_OTLP_ENDPOINT` environment variable, spans
+//! are also exported to an OpenTelemetry collector over OTLP/HTTP.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::sync::OnceLock;
+
+use crate::{PanoptesError, Result};
+
+/// Environment variables that configure the OTLP exporter, per the
+/// OpenTelemetry specification
+const OTLP_ENV_VARS: &[&str] = &["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"];
+
+static PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+/// A new correlation ID, short enough to read and grep for
+pub fn correlation_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..12].to_string()
+}
+
+/// Whether spans are to be exported, to `endpoint` or the collector the
+/// environment names
+pub fn otlp_enabled(endpoint: Option<&str>) -> bool {
+    endpoint.is_some() || OTLP_ENV_VARS.iter().any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()))
+}
+
+/// A tracer exporting spans to the collector at `endpoint` (its base URL,
+/// such as `http://localhost:4318`), or to the one the environment names.
+/// Spans are sent in batches until [`shutdown`].
+pub fn otlp_tracer(endpoint: Option<&str>) -> Result<Tracer> {
+    let mut exporter = SpanExporter::builder().with_http();
+    if let Some(endpoint) = endpoint {
+        if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+            return Err(PanoptesError::Config(format!("--otlp-endpoint must be an http:// or https:// URL, not {}", endpoint)));
+        }
+        exporter = exporter.with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')));
+    }
+    let exporter = exporter.build()
+        .map_err(|e| PanoptesError::Config(format!("Cannot export traces: {}", e)))?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([
+            KeyValue::new("service.name", "panoptes"),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]))
+        .build();
+    let tracer = provider.tracer("panoptes");
+    let _ = PROVIDER.set(provider);
+    Ok(tracer)
+}
+
+/// Send the spans not yet exported; call before exiting
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            eprintln!("Failed to export the last traces: {}", e);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Thumbnail generation and caching
+//!
+//! Thumbnails are JPEGs named after the file's content hash and their size, so
+//! a renamed or moved file keeps its thumbnail and a new `size` makes new ones. They are generated on first request: images
+//! with the `image` crate, PDFs from their first page with `pdftoppm`, and
+//! videos from a frame near the start with `ffmpeg`. When the cache grows past
+//! `max_cache_mb` the least recently used thumbnails are removed.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+use crate::analyzers::{image::ImageAnalyzer, pdf::PdfAnalyzer, video::VideoAnalyzer, FileAnalyzer};
+use crate::config::ThumbnailConfig;
+use crate::{PanoptesError, Result};
+
+/// What a thumbnail is made from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Image,
+    Pdf,
+    Video,
+}
+
+impl Source {
+    fn of(path: &Path) -> Option<Self> {
+        if ImageAnalyzer::new().can_handle(path) {
+            Some(Self::Image)
+        } else if PdfAnalyzer::new().can_handle(path) {
+            Some(Self::Pdf)
+        } else if VideoAnalyzer::new().can_handle(path) {
+            Some(Self::Video)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file in the thumbnail cache
+#[derive(Debug, Clone)]
+pub struct CachedThumbnail {
+    pub path: PathBuf,
+    /// Hash of the file it shows; `None` for one still being generated, or
+    /// left behind by generation that was interrupted
+    pub hash: Option<String>,
+    /// Its longest edge as configured when it was made; `None` for those
+    /// named before sizes were part of the name
+    pub size: Option<u32>,
+    pub len: u64,
+    /// Last generated or served
+    pub used: SystemTime,
+}
+
+/// On-disk thumbnail cache
+#[derive(Debug, Clone)]
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    size: u32,
+    max_bytes: u64,
+}
+
+impl ThumbnailCache {
+    pub fn new(config: &ThumbnailConfig) -> Self {
+        Self {
+            dir: PathBuf::from(&config.cache_dir),
+            size: config.size,
+            max_bytes: config.max_cache_mb * 1024 * 1024,
+        }
+    }
+
+    /// Everything in the cache; empty when there is no cache yet
+    pub fn entries(&self) -> Vec<CachedThumbnail> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let meta = e.metadata().ok().filter(|m| m.is_file())?;
+                let name = e.file_name().to_string_lossy().into_owned();
+                let (hash, size) = match name.strip_suffix(".jpg") {
+                    Some(stem) if stem.ends_with(".partial") => (None, None),
+                    Some(stem) => match stem.rsplit_once('_').and_then(|(hash, size)| Some((hash, size.parse().ok()?))) {
+                        Some((hash, size)) => (Some(hash.to_string()), Some(size)),
+                        None => (Some(stem.to_string()), None),
+                    },
+                    // Not ours
+                    None => return None,
+                };
+                Some(CachedThumbnail {
+                    path: e.path(),
+                    hash,
+                    size,
+                    len: meta.len(),
+                    used: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                })
+            })
+            .collect()
+    }
+
+    /// Longest edge of the thumbnails made now
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Whether a thumbnail can be made for this kind of file
+    pub fn supports(path: &Path) -> bool {
+        Source::of(path).is_some()
+    }
+
+    /// Path of the thumbnail for `path`, generating it if it isn't cached yet.
+    /// Returns `None` for file types without thumbnails, or when the external
+    /// tool they need isn't installed. This blocks; call it off the async runtime.
+    pub fn get_or_create(&self, path: &Path, hash: &str) -> Result<Option<PathBuf>> {
+        let Some(source) = Source::of(path) else {
+            return Ok(None);
+        };
+        if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(PanoptesError::Analysis(format!("Invalid file hash: {}", hash)));
+        }
+
+        let thumbnail = self.dir.join(format!("{}_{}.jpg", hash, self.size));
+        if thumbnail.exists() {
+            touch(&thumbnail);
+            return Ok(Some(thumbnail));
+        }
+
+        std::fs::create_dir_all(&self.dir)?;
+        // Generate under a unique name so concurrent requests never see a partial file
+        let partial = self.dir.join(format!("{}.{}.partial.jpg", hash, uuid::Uuid::new_v4().simple()));
+        let generated = match source {
+            Source::Image => self.render_image(path, &partial).map(|_| true),
+            Source::Pdf => self.render_pdf(path, &partial),
+            Source::Video => self.render_video(path, &partial),
+        };
+
+        match generated {
+            Ok(true) => {
+                std::fs::rename(&partial, &thumbnail)?;
+                debug!("Generated thumbnail for {:?}", path);
+                self.enforce_limit(&thumbnail);
+                Ok(Some(thumbnail))
+            }
+            Ok(false) => {
+                let _ = std::fs::remove_file(&partial);
+                Ok(None)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                Err(e)
+            }
+        }
+    }
+
+    fn render_image(&self, path: &Path, output: &Path) -> Result<()> {
+        let img = crate::analyzers::image::open(path)?;
+        img.thumbnail(self.size, self.size)
+            .to_rgb8()
+            .save_with_format(output, image::ImageFormat::Jpeg)?;
+        Ok(())
+    }
+
+    fn render_pdf(&self, path: &Path, output: &Path) -> Result<bool> {
+        // pdftoppm appends the extension itself
+        let prefix = output.with_extension("");
+        let status = Command::new("pdftoppm")
+            .args(["-f", "1", "-l", "1", "-singlefile", "-jpeg", "-scale-to"])
+            .arg(self.size.to_string())
+            .arg(path)
+            .arg(&prefix)
+            .output();
+
+        match status {
+            Ok(out) if out.status.success() => Ok(output.exists()),
+            Ok(out) => Err(PanoptesError::Pdf(format!(
+                "pdftoppm failed: {}", String::from_utf8_lossy(&out.stderr).trim()
+            ))),
+            Err(_) => {
+                warn!("pdftoppm not found; PDF thumbnails are unavailable");
+                Ok(false)
+            }
+        }
+    }
+
+    fn render_video(&self, path: &Path, output: &Path) -> Result<bool> {
+        let scale = format!("scale='min({0},iw)':'min({0},ih)':force_original_aspect_ratio=decrease", self.size);
+        // A second in skips black lead-in frames; very short clips fall back to the first frame
+        for seek in ["1", "0"] {
+            let status = Command::new("ffmpeg")
+                .args(["-v", "error", "-ss", seek, "-i"])
+                .arg(path)
+                .args(["-vframes", "1", "-vf", &scale, "-q:v", "4", "-y"])
+                .arg(output)
+                .output();
+
+            match status {
+                Ok(out) if out.status.success() && output.exists() => return Ok(true),
+                Ok(_) => continue,
+                Err(_) => {
+                    warn!("ffmpeg not found; video thumbnails are unavailable");
+                    return Ok(false);
+                }
+            }
+        }
+        Err(PanoptesError::Analysis(format!("Could not extract a frame from {:?}", path)))
+    }
+
+    /// Remove least recently used thumbnails (other than `keep`) until the
+    /// cache fits its limit; those still being generated are left alone
+    fn enforce_limit(&self, keep: &Path) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                let partial = e.file_name().to_string_lossy().ends_with(".partial.jpg");
+                (meta.is_file() && !partial).then(|| (e.path(), meta.len(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+            })
+            .collect();
+
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        files.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= len;
+                debug!("Evicted thumbnail {:?}", path);
+            }
+        }
+    }
+}
+
+/// Mark a cached thumbnail as recently used
+fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Transcribing recordings
+//!
+//! With `analyzers.audio.transcribe`, recordings are transcribed by
+//! whisper.cpp (`analyzers.audio.whisper_command`, `whisper-cli` by default)
+//! with the model `analyzers.audio.whisper_model`. FFmpeg cuts them into
+//! chunks of `analyzers.audio.chunk_secs` (ten minutes by default), each
+//! stored in the `transcript_chunks` table once transcribed, so a long
+//! recording reports its progress chunk by chunk and an interrupted
+//! transcription picks up at the chunk it stopped at.
+//!
+//! Finished transcripts are kept in the `transcripts` table by file hash:
+//! analyzing a recording again, or a copy of it, and finding files alike
+//! (`panoptes similar`) use them rather than transcribing again.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use crate::config::AudioAnalyzerConfig;
+use crate::db::Database;
+use crate::organizer::expand_home;
+use crate::{PanoptesError, Result};
+
+/// Sample rate whisper.cpp takes
+const SAMPLE_RATE: &str = "16000";
+
+/// What was said in a recording
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Transcript {
+    pub file_hash: String,
+    /// The whisper.cpp model, as `analyzers.audio.whisper_model` named it
+    pub model: String,
+    /// Language asked for, or None when detected
+    pub language: Option<String>,
+    pub text: String,
+    pub duration_secs: Option<f64>,
+}
+
+/// The transcript of the recording at `path`, whose hash is `file_hash` and
+/// which lasts `duration_secs` if known; the one made before, if any
+pub async fn transcript(
+    path: &Path,
+    file_hash: &str,
+    duration_secs: Option<f64>,
+    db: &Database,
+    config: &AudioAnalyzerConfig,
+) -> Result<Transcript> {
+    if let Some(known) = db.get_transcript(file_hash)? {
+        debug!("Transcript of {:?} from the cache", path);
+        return Ok(known);
+    }
+    let model = config.whisper_model.clone()
+        .ok_or_else(|| PanoptesError::Config("analyzers.audio.whisper_model is not set".to_string()))?;
+
+    let duration_secs = match duration_secs {
+        Some(duration) => Some(duration),
+        None => probe_duration(path).await,
+    };
+    let chunk_secs = config.chunk_secs.max(1);
+    let chunks = duration_secs.map_or(1, |d| ((d / chunk_secs as f64).ceil() as usize).max(1));
+    let mut done = db.get_transcript_chunks(file_hash, &model, chunk_secs)?;
+    if !done.is_empty() {
+        info!("Resuming the transcription of {:?} after {} of {} chunks", path, done.len(), chunks);
+    }
+
+    let dir = std::env::temp_dir().join(format!("panoptes-transcribe-{}", uuid::Uuid::new_v4().simple()));
+    tokio::fs::create_dir_all(&dir).await?;
+    let transcribed = transcribe_missing(path, &dir, file_hash, &model, chunks, duration_secs.is_some(), &mut done, db, config).await;
+    if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+        debug!("Failed to remove {:?}: {}", dir, e);
+    }
+    transcribed?;
+
+    let text = (0..chunks)
+        .filter_map(|chunk| done.get(&chunk))
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let transcript = Transcript {
+        file_hash: file_hash.to_string(),
+        model,
+        language: config.language.clone(),
+        text,
+        duration_secs,
+    };
+    db.set_transcript(&transcript)?;
+    Ok(transcript)
+}
+
+/// Transcribe the chunks not `done`, storing each as it is
+#[allow(clippy::too_many_arguments)]
+async fn transcribe_missing(
+    path: &Path,
+    dir: &Path,
+    file_hash: &str,
+    model: &str,
+    chunks: usize,
+    timed: bool,
+    done: &mut HashMap<usize, String>,
+    db: &Database,
+    config: &AudioAnalyzerConfig,
+) -> Result<()> {
+    for chunk in 0..chunks {
+        if done.contains_key(&chunk) {
+            continue;
+        }
+        let audio = dir.join(format!("{}.wav", chunk));
+        let span = timed.then(|| (chunk as u64 * config.chunk_secs as u64, config.chunk_secs));
+        cut(path, &audio, span).await?;
+        let text = whisper(&audio, model, config).await?;
+        db.set_transcript_chunk(file_hash, model, config.chunk_secs.max(1), chunk, &text)?;
+        done.insert(chunk, text);
+        info!("Transcribed {:?}: chunk {} of {}", path, chunk + 1, chunks);
+        let _ = tokio::fs::remove_file(&audio).await;
+    }
+    Ok(())
+}
+
+/// Write the `span` (start and length in seconds) of the recording at
+/// `path`, or all of it, to `to` as whisper.cpp takes it
+async fn cut(path: &Path, to: &Path, span: Option<(u64, u32)>) -> Result<()> {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", "error", "-y"]);
+    if let Some((start, length)) = span {
+        command.args(["-ss", &start.to_string(), "-t", &length.to_string()]);
+    }
+    let output = command.arg("-i").arg(path)
+        .args(["-ar", SAMPLE_RATE, "-ac", "1", "-c:a", "pcm_s16le"])
+        .arg(to)
+        .output()
+        .await
+        .map_err(|e| PanoptesError::Audio(format!("Failed to run ffmpeg: {}", e)))?;
+    if !output.status.success() {
+        return Err(PanoptesError::Audio(format!(
+            "ffmpeg failed on {}: {}", path.display(), String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// What whisper.cpp hears in the WAV file `audio`
+async fn whisper(audio: &Path, model: &str, config: &AudioAnalyzerConfig) -> Result<String> {
+    let output = Command::new(&config.whisper_command)
+        .arg("-m").arg(expand_home(model))
+        .arg("-f").arg(audio)
+        .args(["-l", config.language.as_deref().unwrap_or("auto"), "-nt", "-np"])
+        .output()
+        .await
+        .map_err(|e| PanoptesError::Audio(format!("Failed to run {}: {}", config.whisper_command, e)))?;
+    if !output.status.success() {
+        return Err(PanoptesError::Audio(format!(
+            "{} failed: {}", config.whisper_command, String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Length of the recording at `path` in seconds, as FFprobe tells it
+async fn probe_duration(path: &Path) -> Option<f64> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "quiet", "-show_entries", "format=duration", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .await
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
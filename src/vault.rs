@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Content-addressed vault
+//!
+//! With `vault.directory` set, files are not renamed where they are but moved
+//! into the vault under their blake3 hash, in directories named after its
+//! first two pairs of digits: `vault/3f/a2/3fa2….pdf`. A file's address never
+//! changes and its contents can always be checked against it (`panoptes
+//! verify` reports vault files that changed); with `vault.read_only` they are
+//! made read-only as well.
+//!
+//! The database is the vault's index. `panoptes vault export` lays it out as a
+//! tree of symlinks by category, named as the files would have been renamed,
+//! and brings an existing tree up to date; views (see [`crate::views`]) name
+//! vault files the same way.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::analyzers::AnalysisResult;
+use crate::db::{Database, FileRecord};
+use crate::organizer::{category_dir, expand_home};
+use crate::renamer::final_name;
+use crate::views::{free, sync, Build};
+use crate::{AppConfig, Result};
+
+/// The vault directory, if files go to one
+pub fn root(config: &AppConfig) -> Option<PathBuf> {
+    let dir = config.vault.directory.as_deref()?.trim();
+    (!dir.is_empty()).then(|| expand_home(dir))
+}
+
+/// Where the file with `hash` and extension `ext` is kept in the vault at `root`
+pub fn address(root: &Path, hash: &str, ext: Option<&str>) -> PathBuf {
+    let dir = match (hash.get(..2), hash.get(2..4)) {
+        (Some(a), Some(b)) => root.join(a).join(b),
+        _ => root.to_path_buf(),
+    };
+    match ext.filter(|e| !e.is_empty()) {
+        Some(ext) => dir.join(format!("{}.{}", hash, ext.to_lowercase())),
+        None => dir.join(hash),
+    }
+}
+
+/// Whether `path` is named as the vault address of contents hashing to `hash`
+pub fn is_address(path: &Path, hash: &str) -> bool {
+    !hash.is_empty() && path.file_stem().and_then(|s| s.to_str()) == Some(hash)
+}
+
+/// Whether `path` is inside the vault
+pub fn contains(path: &Path, config: &AppConfig) -> bool {
+    let Some(root) = root(config) else {
+        return false;
+    };
+    let root = root.canonicalize().unwrap_or(root);
+    let path = path.parent()
+        .and_then(|dir| dir.canonicalize().ok())
+        .map(|dir| dir.join(path.file_name().unwrap_or_default()))
+        .unwrap_or_else(|| path.to_path_buf());
+    path.starts_with(root)
+}
+
+/// Make a file just moved into the vault read-only, with `vault.read_only`
+pub fn seal(path: &Path, config: &AppConfig) {
+    if !config.vault.read_only {
+        return;
+    }
+    let result = std::fs::metadata(path).and_then(|meta| {
+        let mut permissions = meta.permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(path, permissions)
+    });
+    if let Err(e) = result {
+        warn!("Failed to make {} read-only: {}", path.display(), e);
+    }
+}
+
+/// Let the owner write to a file taken back out of the vault
+pub fn unseal(path: &Path) {
+    let result = std::fs::metadata(path).and_then(|meta| {
+        let mut permissions = meta.permissions();
+        if !permissions.readonly() {
+            return Ok(());
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(permissions.mode() | 0o200);
+        }
+        #[cfg(not(unix))]
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(path, permissions)
+    });
+    if let Err(e) = result {
+        warn!("Failed to make {} writable: {}", path.display(), e);
+    }
+}
+
+/// The name, without extension, of the file of `file` at `path`: what it
+/// would have been renamed to if it is in the vault, its own otherwise
+pub fn name(file: &FileRecord, path: &Path, config: &AppConfig) -> String {
+    if !is_address(path, &file.file_hash) {
+        return path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    }
+    let result = AnalysisResult {
+        suggested_name: file.corrected_name.clone().unwrap_or_else(|| file.suggested_name.clone()),
+        confidence: file.confidence,
+        category: file.category.clone(),
+        tags: Vec::new(),
+        file_hash: file.file_hash.clone(),
+        metadata: file.metadata.clone(),
+        analyzer: file.analyzer.clone(),
+        model: file.model.clone(),
+    };
+    final_name(&result, path, config)
+}
+
+/// Lay out the files in the vault under `dir` as symlinks, in a directory per
+/// category (see `organize`), named as they would have been renamed; other
+/// symlinks under `dir` are removed. Nothing is changed with `dry_run`.
+pub fn export(db: &Database, dir: &Path, config: &AppConfig, dry_run: bool) -> Result<Build> {
+    let Some(root) = root(config) else {
+        return Err(crate::PanoptesError::Config("vault.directory is not set".to_string()));
+    };
+    let root = root.canonicalize().unwrap_or(root);
+
+    let mut links = BTreeMap::new();
+    let mut taken = HashSet::new();
+    for file in db.get_live_files()?.into_iter().filter(|file| !file.is_remote()) {
+        let Ok(target) = Path::new(&file.new_path).canonicalize() else {
+            continue;
+        };
+        if !target.starts_with(&root) || !target.is_file() {
+            continue;
+        }
+        let mut name = name(&file, &target, config);
+        if let Some(ext) = target.extension() {
+            name = format!("{}.{}", name, ext.to_string_lossy());
+        }
+        let category_dir = category_dir(dir, file.category.as_deref(), &config.organize)
+            .unwrap_or_else(|| dir.to_path_buf());
+        let link = free(category_dir.join(name), &taken);
+        taken.insert(link.clone());
+        links.insert(link, target);
+    }
+    sync(dir, &links, dry_run)
+}
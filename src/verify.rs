@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Checking the database against the file system
+//!
+//! Every file record is checked for its file still being where the record
+//! says, with the contents it had when analyzed, and the watch directories
+//! are searched for files nothing records. A record whose file is gone is
+//! matched by hash against those unrecorded files, which finds files moved
+//! outside Panoptes. Fixing re-links moved files and marks missing ones.
+//!
+//! With `xattrs.enabled`, files' extended attributes are read back as well:
+//! fixing writes them again to files that lost them, and takes tags changed
+//! in a file manager into the database.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::analyzers::calculate_file_hash;
+use crate::config::WatchOptions;
+use crate::db::{Database, FileRecord};
+use crate::selection::Selector;
+use crate::watcher::should_process;
+use crate::xattrs;
+use crate::Result;
+
+/// A difference between the database and the file system
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// A file in a watch directory no record knows
+    Unknown { path: PathBuf },
+    /// A record's file is gone
+    Missing { file_id: String, path: String },
+    /// A record's file is gone, but a file with the same contents is at `to`
+    Moved { file_id: String, from: String, to: PathBuf },
+    /// A record's file has changed since it was analyzed
+    Changed { file_id: String, path: String },
+    /// A record marked missing has its file back
+    Reappeared { file_id: String, path: String },
+    /// A record's file has lost the tags and category in its extended attributes
+    Untagged { file_id: String, path: String },
+    /// A record's file had its tags changed by another program
+    Retagged { file_id: String, path: String, added: Vec<String>, removed: Vec<String> },
+}
+
+impl Finding {
+    /// Whether `--fix` can do something about it
+    pub fn fixable(&self) -> bool {
+        !matches!(self, Self::Unknown { .. } | Self::Changed { .. })
+    }
+}
+
+/// What checking found
+#[derive(Debug, Default, Serialize)]
+pub struct Verification {
+    /// Records checked
+    pub records: usize,
+    /// Files found in the watch directories
+    pub files: usize,
+    /// Records still missing that were marked missing before
+    pub already_missing: usize,
+    pub findings: Vec<Finding>,
+}
+
+/// A path as it compares against others: canonical when the file exists,
+/// absolute otherwise
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| match std::env::current_dir() {
+        Ok(cwd) if path.is_relative() => cwd.join(path),
+        _ => path.to_path_buf(),
+    })
+}
+
+/// Files Panoptes would process under `dir`, `depth` levels below the watch directory
+fn list_files(dir: &Path, recursive: bool, selector: &Selector, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive && selector.descends(&path, depth + 1) {
+                list_files(&path, recursive, selector, depth + 1, files);
+            }
+        } else if path.is_file() && should_process(&path) && selector.matches(&path, depth) {
+            files.push(path);
+        }
+    }
+}
+
+/// How a record's file's extended attributes differ from the record, if they do
+fn check_tags(db: &Database, record: &FileRecord, path: &Path) -> Result<Option<Finding>> {
+    let file_id = record.id.clone();
+    let recorded = record.new_path.clone();
+    match xattrs::read(path) {
+        Ok(Some(attributes)) => {
+            let (added, removed) = attributes.edits();
+            Ok((!added.is_empty() || !removed.is_empty())
+                .then_some(Finding::Retagged { file_id, path: recorded, added, removed }))
+        }
+        Ok(None) => {
+            let tagged = record.category.is_some() || !db.get_file_tags(&record.id)?.is_empty();
+            Ok(tagged.then_some(Finding::Untagged { file_id, path: recorded }))
+        }
+        // The file system has none
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => Ok(None),
+        Err(e) => {
+            tracing::warn!("Cannot read extended attributes of {}: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+/// Compare the records in `db` with the files in `watch_dirs`, searched as
+/// their options say; with `tags`, check extended attributes too
+pub fn verify(db: &Database, watch_dirs: &[(PathBuf, WatchOptions)], tags: bool) -> Result<Verification> {
+    // Files on other machines can't be checked from here
+    let records: Vec<FileRecord> = db.get_live_files()?.into_iter().filter(|r| !r.is_remote()).collect();
+    let mut verification = Verification { records: records.len(), ..Default::default() };
+
+    let mut on_disk = Vec::new();
+    for (dir, options) in watch_dirs {
+        let selector = Selector::new(&options.select)?;
+        list_files(dir, options.recursive, &selector, 0, &mut on_disk);
+    }
+    let mut on_disk: Vec<PathBuf> = on_disk.iter().map(|p| normalize(p)).collect();
+    on_disk.sort();
+    on_disk.dedup();
+    verification.files = on_disk.len();
+
+    let mut gone = Vec::new();
+    let mut known = HashSet::new();
+    for record in &records {
+        let path = normalize(Path::new(&record.new_path));
+        if !path.is_file() {
+            gone.push(record);
+            continue;
+        }
+        known.insert(path.clone());
+        if record.missing_since.is_some() {
+            verification.findings.push(Finding::Reappeared { file_id: record.id.clone(), path: record.new_path.clone() });
+        }
+        match calculate_file_hash(&path) {
+            Ok(hash) if hash == record.file_hash => {}
+            Ok(_) => verification.findings.push(Finding::Changed { file_id: record.id.clone(), path: record.new_path.clone() }),
+            Err(e) => tracing::warn!("Cannot hash {}: {}", path.display(), e),
+        }
+        if tags {
+            verification.findings.extend(check_tags(db, record, &path)?);
+        }
+    }
+
+    // Unrecorded files by contents, for finding where missing ones went
+    let unknown: Vec<PathBuf> = on_disk.into_iter().filter(|p| !known.contains(p)).collect();
+    let mut by_hash: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+    if !gone.is_empty() {
+        for path in &unknown {
+            if let Ok(hash) = calculate_file_hash(path) {
+                by_hash.entry(hash).or_default().push(path);
+            }
+        }
+    }
+
+    let mut claimed = HashSet::new();
+    for record in gone {
+        let found = by_hash.get_mut(&record.file_hash).and_then(|paths| paths.pop());
+        match found {
+            Some(to) => {
+                claimed.insert(to.clone());
+                verification.findings.push(Finding::Moved {
+                    file_id: record.id.clone(),
+                    from: record.new_path.clone(),
+                    to: to.clone(),
+                });
+            }
+            // Already marked, so not news
+            None if record.missing_since.is_some() => verification.already_missing += 1,
+            None => verification.findings.push(Finding::Missing { file_id: record.id.clone(), path: record.new_path.clone() }),
+        }
+    }
+    verification.findings.extend(
+        unknown.into_iter().filter(|p| !claimed.contains(p)).map(|path| Finding::Unknown { path })
+    );
+    Ok(verification)
+}
+
+/// Re-link moved files, mark missing ones (or ones back) and settle tags in
+/// `db`; returns how many records changed
+pub fn fix(db: &Database, findings: &[Finding]) -> Result<usize> {
+    let mut fixed = 0;
+    for finding in findings {
+        let changed = match finding {
+            Finding::Moved { file_id, to, .. } => db.relink_file(file_id, to)?,
+            Finding::Missing { file_id, .. } => db.set_missing(file_id, true)?,
+            Finding::Reappeared { file_id, .. } => db.set_missing(file_id, false)?,
+            Finding::Untagged { file_id, .. } => xattrs::tag_record(db, file_id)?,
+            Finding::Retagged { file_id, added, removed, .. } => {
+                for tag in added {
+                    db.add_tag_to_file(file_id, tag)?;
+                }
+                for tag in removed {
+                    db.remove_tag_from_file(file_id, tag)?;
+                }
+                xattrs::tag_record(db, file_id)?
+            }
+            Finding::Unknown { .. } | Finding::Changed { .. } => false,
+        };
+        if changed {
+            fixed += 1;
+        }
+    }
+    Ok(fixed)
+}
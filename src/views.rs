@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Virtual views
+//!
+//! `panoptes views build` lays the recorded files out in `views.directory` as
+//! trees of symlinks, one for each of `views.trees`, leaving the files where
+//! they are. A tree's template is a path whose `{placeholders}` are filled in
+//! from each file's record, and which gets the file's extension:
+//!
+//! ```json
+//! "views": {
+//!   "directory": "~/Views",
+//!   "trees": [
+//!     { "name": "ByCategory", "template": "{category}/{year}-{month}_{name}" },
+//!     { "name": "ByTag", "template": "{tag}/{name}" }
+//!   ]
+//! }
+//! ```
+//!
+//! links `~/Views/ByCategory/Finance/2024-11_acme_invoice.pdf` to the invoice.
+//! `{tag}` makes a link for each of a file's tags. The others are `{name}`,
+//! the file's name without extension; `{category}`; `{year}`, `{month}`,
+//! `{day}` and `{date}` of its last modification; `{ext}`, `{analyzer}`,
+//! `{model}`, and the keys of its metadata.
+//!
+//! Building again only changes the links that are wrong or missing, and
+//! removes links to files gone or renamed. While watching, the views are
+//! brought up to date every `views.refresh_secs` when the database changed.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::ViewTree;
+use crate::db::{Database, FileRecord};
+use crate::organizer::expand_home;
+use crate::vault;
+use crate::{AppConfig, Result};
+
+/// The views directory, if views are built
+pub fn root(config: &AppConfig) -> Option<PathBuf> {
+    let dir = config.views.directory.as_deref()?.trim();
+    (!dir.is_empty()).then(|| expand_home(dir))
+}
+
+/// The trees of `views.trees`, or by category, tag and year without any
+pub fn trees(config: &AppConfig) -> Vec<ViewTree> {
+    if !config.views.trees.is_empty() {
+        return config.views.trees.clone();
+    }
+    [("ByCategory", "{category}/{name}"), ("ByTag", "{tag}/{name}"), ("ByYear", "{year}/{month}/{name}")]
+        .into_iter()
+        .map(|(name, template)| ViewTree { name: name.to_string(), template: template.to_string() })
+        .collect()
+}
+
+/// Whether `path` is inside the views directory
+pub fn contains(path: &Path, config: &AppConfig) -> bool {
+    let Some(root) = root(config) else {
+        return false;
+    };
+    let root = root.canonicalize().unwrap_or(root);
+    let dir = path.parent().map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()));
+    dir.is_some_and(|dir| dir.starts_with(root))
+}
+
+/// What building views did
+#[derive(Debug, Default, Serialize)]
+pub struct Build {
+    /// Symlinks made or corrected
+    pub linked: usize,
+    /// Symlinks already right
+    pub kept: usize,
+    /// Symlinks removed, their file gone or renamed
+    pub removed: usize,
+}
+
+/// A value as one directory or file name: no separators, and never `.` or `..`
+fn segment(value: &str) -> String {
+    let value: String = value.trim().chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    if value.chars().all(|c| c == '.') { String::new() } else { value }
+}
+
+/// What a file's template is filled in from
+struct Fields<'a> {
+    file: &'a FileRecord,
+    name: String,
+    ext: String,
+    modified: DateTime<Local>,
+}
+
+impl Fields<'_> {
+    /// The value of `{key}`, other than `{tag}`
+    fn get(&self, key: &str) -> String {
+        match key {
+            "name" => self.name.clone(),
+            "ext" => self.ext.clone(),
+            "category" => self.file.category.clone().unwrap_or_else(|| "Uncategorized".to_string()),
+            "year" => self.modified.format("%Y").to_string(),
+            "month" => self.modified.format("%m").to_string(),
+            "day" => self.modified.format("%d").to_string(),
+            "date" => self.modified.format("%Y-%m-%d").to_string(),
+            "analyzer" => self.file.analyzer.clone().unwrap_or_default(),
+            "model" => self.file.model.clone().unwrap_or_default(),
+            key => match self.file.metadata.get(key) {
+                Some(serde_json::Value::String(value)) => value.clone(),
+                Some(serde_json::Value::Number(value)) => value.to_string(),
+                _ => String::new(),
+            },
+        }
+    }
+}
+
+/// One path segment of a template filled in, with `tag` for `{tag}`
+fn fill(template: &str, fields: &Fields, tag: Option<&str>) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        filled.push_str(&rest[..start]);
+        match &rest[start + 1..start + len] {
+            "tag" => filled.push_str(tag.unwrap_or_default()),
+            key => filled.push_str(&fields.get(key)),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    filled.push_str(rest);
+    segment(&filled)
+}
+
+/// Where `template` puts the file of `fields`, relative to its tree: one path
+/// per tag with `{tag}`, and none when it has no tags
+fn place(template: &str, fields: &Fields, tags: &[String]) -> Vec<PathBuf> {
+    let tags: Vec<Option<&str>> = if template.contains("{tag}") {
+        tags.iter().map(|t| Some(t.as_str())).collect()
+    } else {
+        vec![None]
+    };
+    tags.into_iter()
+        .filter_map(|tag| {
+            let segments: Vec<String> = template.split('/')
+                .map(|part| fill(part, fields, tag))
+                .filter(|part| !part.is_empty())
+                .collect();
+            let (last, dirs) = segments.split_last()?;
+            let mut path: PathBuf = dirs.iter().collect();
+            if template.contains("{ext}") || fields.ext.is_empty() {
+                path.push(last);
+            } else {
+                path.push(format!("{}.{}", last, fields.ext));
+            }
+            Some(path)
+        })
+        .collect()
+}
+
+/// `link` numbered until it is not in `taken`
+pub(crate) fn free(link: PathBuf, taken: &HashSet<PathBuf>) -> PathBuf {
+    if !taken.contains(&link) {
+        return link;
+    }
+    let stem = link.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = link.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut n = 2;
+    loop {
+        let candidate = link.with_file_name(format!("{}_{}{}", stem, n, ext));
+        if !taken.contains(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// The links views should have, under `root`, and the files they point to
+pub fn plan(db: &Database, root: &Path, config: &AppConfig) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    let trees = trees(config);
+    let tags = db.get_all_file_tags()?;
+    let mut links = BTreeMap::new();
+    let mut taken = HashSet::new();
+    // Oldest records first, so a name that two files want goes to the same one every time
+    for file in db.get_live_files()?.into_iter().filter(|file| !file.is_remote()) {
+        let Ok(target) = Path::new(&file.new_path).canonicalize() else {
+            continue;
+        };
+        let Ok(meta) = std::fs::metadata(&target) else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let fields = Fields {
+            file: &file,
+            name: vault::name(&file, &target, config),
+            ext: target.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default(),
+            modified: meta.modified().map(DateTime::<Local>::from).unwrap_or_else(|_| Local::now()),
+        };
+        let file_tags = tags.get(&file.id).map(Vec::as_slice).unwrap_or_default();
+        for tree in &trees {
+            for path in place(&tree.template, &fields, file_tags) {
+                let link = free(root.join(segment(&tree.name)).join(path), &taken);
+                taken.insert(link.clone());
+                links.insert(link, target.clone());
+            }
+        }
+    }
+    Ok(links)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Symlinks under `dir`, recursively
+fn list_links(dir: &Path, links: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_symlink() => links.push(path),
+            Ok(kind) if kind.is_dir() => list_links(&path, links),
+            _ => {}
+        }
+    }
+}
+
+/// Make the symlinks under `dir` those of `links`, link to target: missing
+/// and wrong ones are made, others removed along with the directories they
+/// leave empty. Files that aren't symlinks are left alone, and links whose
+/// place they take are skipped. Nothing is changed with `dry_run`.
+pub fn sync(dir: &Path, links: &BTreeMap<PathBuf, PathBuf>, dry_run: bool) -> Result<Build> {
+    let mut build = Build::default();
+    let mut existing = Vec::new();
+    list_links(dir, &mut existing);
+
+    for link in &existing {
+        if links.contains_key(link) {
+            continue;
+        }
+        if !dry_run {
+            std::fs::remove_file(link)?;
+            // Up to, not including, the views directory
+            let mut parent = link.parent();
+            while let Some(emptied) = parent.filter(|p| *p != dir && p.starts_with(dir)) {
+                if std::fs::remove_dir(emptied).is_err() {
+                    break;
+                }
+                parent = emptied.parent();
+            }
+        }
+        build.removed += 1;
+    }
+
+    for (link, target) in links {
+        match std::fs::read_link(link) {
+            Ok(to) if &to == target => {
+                build.kept += 1;
+                continue;
+            }
+            Ok(_) => {
+                if !dry_run {
+                    std::fs::remove_file(link)?;
+                }
+            }
+            Err(_) if link.symlink_metadata().is_ok() => {
+                tracing::warn!("Not linking {}: something else is there", link.display());
+                continue;
+            }
+            Err(_) => {}
+        }
+        if !dry_run {
+            if let Some(parent) = link.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            symlink(target, link)?;
+        }
+        build.linked += 1;
+    }
+    Ok(build)
+}
+
+/// Bring the views in `views.directory` up to date with the database
+pub fn build(db: &Database, config: &AppConfig, dry_run: bool) -> Result<Build> {
+    let Some(root) = root(config) else {
+        return Err(crate::PanoptesError::Config("views.directory is not set".to_string()));
+    };
+    if !dry_run {
+        std::fs::create_dir_all(&root)?;
+    }
+    let links = plan(db, &root, config)?;
+    sync(&root, &links, dry_run)
+}
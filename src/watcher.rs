@@ -3,6 +3,7 @@
 
 //! File system watcher for monitoring directories
 
+use notify::event::{ModifyKind, RenameMode};
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
@@ -27,10 +28,20 @@ pub enum WatchEvent {
     Error(String),
 }
 
+/// A directory being watched
+struct Watched {
+    /// As given to [`FileWatcher::watch`]
+    path: PathBuf,
+    /// Relative paths resolved, as they appear in events
+    absolute: PathBuf,
+    /// Whether subdirectories are watched too
+    recursive: bool,
+}
+
 /// File system watcher
 pub struct FileWatcher {
     watcher: RecommendedWatcher,
-    watched_paths: Vec<PathBuf>,
+    watched: Vec<Watched>,
     event_rx: Receiver<notify::Result<Event>>,
 }
 
@@ -46,34 +57,80 @@ pub fn new() -> Result<Self> {
 
         Ok(Self {
             watcher,
-            watched_paths: Vec::new(),
+            watched: Vec::new(),
             event_rx: rx,
         })
     }
 
     /// Add a directory to watch
-    pub fn watch(&mut self, path: &Path) -> Result<()> {
+    pub fn watch(&mut self, path: &Path, recursive: bool) -> Result<()> {
         // Create directory if it doesn't exist
         if !path.exists() {
             std::fs::create_dir_all(path)?;
             info!("Created watch directory: {:?}", path);
         }
 
-        self.watcher.watch(path, RecursiveMode::NonRecursive)?;
-        self.watched_paths.push(path.to_path_buf());
-        info!("Watching: {:?}", path);
+        let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        self.watcher.watch(path, mode)?;
+        self.watched.push(Watched {
+            path: path.to_path_buf(),
+            absolute: std::env::current_dir()?.join(path),
+            recursive,
+        });
+        info!("Watching: {:?}{}", path, if recursive { " (recursive)" } else { "" });
 
         Ok(())
     }
 
     /// Stop watching a directory
     pub fn unwatch(&mut self, path: &Path) -> Result<()> {
+        self.watched.retain(|w| w.path != path);
         self.watcher.unwatch(path)?;
-        self.watched_paths.retain(|p| p != path);
         info!("Stopped watching: {:?}", path);
         Ok(())
     }
 
+    /// Watch exactly `dirs` (path and recursion), starting and stopping as needed.
+    /// Returns the directories that couldn't be watched, with the reason.
+    pub fn sync(&mut self, dirs: &[(PathBuf, bool)]) -> Vec<(PathBuf, String)> {
+        let stale: Vec<PathBuf> = self.watched.iter()
+            .filter(|w| !dirs.iter().any(|(path, recursive)| *path == w.path && *recursive == w.recursive))
+            .map(|w| w.path.clone())
+            .collect();
+        for path in stale {
+            if let Err(e) = self.unwatch(&path) {
+                warn!("Failed to stop watching {:?}: {}", path, e);
+            }
+        }
+
+        let mut failed = Vec::new();
+        for (path, recursive) in dirs {
+            if self.watched.iter().any(|w| w.path == *path) {
+                continue;
+            }
+            if let Err(e) = self.watch(path, *recursive) {
+                warn!("Failed to watch {:?}: {}", path, e);
+                failed.push((path.clone(), e.to_string()));
+            }
+        }
+        failed
+    }
+
+    /// The watched directory an event `path` is in, preferring the innermost
+    pub fn watch_dir_of(&self, path: &Path) -> Option<&Path> {
+        self.watched.iter()
+            .filter(|w| if w.recursive { path.starts_with(&w.absolute) } else { path.parent() == Some(w.absolute.as_path()) })
+            .max_by_key(|w| w.absolute.components().count())
+            .map(|w| w.path.as_path())
+    }
+
+    /// Where an event `path` is within its watched directory
+    pub fn relative_path<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        self.watched.iter()
+            .filter_map(|w| path.strip_prefix(&w.absolute).ok())
+            .min_by_key(|relative| relative.components().count())
+    }
+
     /// Get the next event (blocking with timeout)
     pub fn next_event(&self, timeout: Duration) -> Option<WatchEvent> {
         match self.event_rx.recv_timeout(timeout) {
@@ -92,6 +149,9 @@ fn convert_event(event: Event) -> Option<WatchEvent> {
             EventKind::Create(_) => {
                 event.paths.first().map(|p| WatchEvent::FileCreated(p.clone()))
             }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                Some(WatchEvent::FileRenamed { from: event.paths[0].clone(), to: event.paths[1].clone() })
+            }
             EventKind::Modify(_) => {
                 event.paths.first().map(|p| WatchEvent::FileModified(p.clone()))
             }
@@ -102,9 +162,26 @@ fn convert_event(event: Event) -> Option<WatchEvent> {
         }
     }
 
+    /// The events as a stream, read on a thread of their own until the
+    /// stream is dropped
+    pub fn into_stream(self) -> impl futures_util::Stream<Item = WatchEvent> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            while !tx.is_closed() {
+                if let Some(event) = self.next_event(Duration::from_millis(100)) {
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+            debug!("Stopped reading watch events");
+        });
+        futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|event| (event, rx)) })
+    }
+
     /// Get currently watched paths
-    pub fn watched_paths(&self) -> &[PathBuf] {
-        &self.watched_paths
+    pub fn watched_paths(&self) -> Vec<&Path> {
+        self.watched.iter().map(|w| w.path.as_path()).collect()
     }
 }
 
@@ -134,6 +211,11 @@ pub fn should_process(path: &Path) -> bool {
         return false;
     }
 
+    // Sidecars describe other files
+    if crate::sidecar::is_sidecar(path) {
+        return false;
+    }
+
     true
 }
 
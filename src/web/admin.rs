@@ -0,0 +1,289 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Admin-only API: users and their roles, watch directories, settings, and the audit log
+
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::auth::Actor;
+use super::AppState;
+use crate::config::{layers, secrets, AppConfig, ConfigChange, WatchOptions};
+use crate::db::{AuditEntry, Role, User, WebhookDelivery};
+use crate::live::LiveSnapshot;
+
+type AdminReply = (StatusCode, Json<Value>);
+
+fn admin_error(code: StatusCode, message: impl ToString) -> AdminReply {
+    (code, Json(json!({ "error": message.to_string() })))
+}
+
+pub async fn api_get_users(State(state): State<Arc<AppState>>) -> Result<Json<Vec<User>>, AdminReply> {
+    state.db.list_users()
+        .map(Json)
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+#[derive(Deserialize)]
+pub struct RoleRequest {
+    role: Role,
+}
+
+pub async fn api_set_user_role(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(username): Path<String>,
+    Json(request): Json<RoleRequest>,
+) -> AdminReply {
+    match state.db.set_user_role(&username, request.role) {
+        Ok(true) => {
+            state.audit(&actor, "user.role", Some(&username), json!({ "role": request.role }));
+            (StatusCode::OK, Json(json!({ "username": username, "role": request.role })))
+        }
+        Ok(false) => admin_error(StatusCode::NOT_FOUND, format!("No user named '{}'", username)),
+        Err(e) => admin_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// Status reports older than this mean the watch daemon isn't running
+const WATCH_STATUS_STALE_SECS: i64 = 30;
+
+/// Whether the watch daemon is handling a directory
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchState {
+    Watching,
+    /// The daemon couldn't watch it
+    Error,
+    /// The daemon is running but hasn't picked up the directory yet
+    Pending,
+    /// The daemon isn't running
+    Stopped,
+}
+
+/// A configured watch directory and what the daemon last reported about it
+#[derive(Debug, Serialize)]
+pub struct WatchDir {
+    pub path: String,
+    #[serde(flatten)]
+    pub options: WatchOptions,
+    pub state: WatchState,
+    pub error: Option<String>,
+    pub last_event_at: Option<DateTime<Utc>>,
+}
+
+/// The configured watch directories with their status
+pub(crate) fn watch_dirs(state: &AppState) -> crate::Result<Vec<WatchDir>> {
+    let config = state.config();
+    let statuses = state.db.get_watch_statuses()?;
+    let fresh = |updated_at: DateTime<Utc>| Utc::now() - updated_at < Duration::seconds(WATCH_STATUS_STALE_SECS);
+    let daemon_running = statuses.iter().any(|s| fresh(s.updated_at));
+
+    Ok(config.watch_paths.iter()
+        .map(|path| {
+            let status = statuses.iter().find(|s| s.path == *path);
+            let state = match status {
+                Some(s) if fresh(s.updated_at) && s.error.is_some() => WatchState::Error,
+                Some(s) if fresh(s.updated_at) => WatchState::Watching,
+                _ if daemon_running => WatchState::Pending,
+                _ => WatchState::Stopped,
+            };
+            WatchDir {
+                path: path.clone(),
+                options: config.watch_options(path),
+                state,
+                error: status.and_then(|s| s.error.clone()),
+                last_event_at: status.and_then(|s| s.last_event_at),
+            }
+        })
+        .collect())
+}
+
+fn watch_dirs_reply(state: &AppState) -> Result<Json<Vec<WatchDir>>, AdminReply> {
+    watch_dirs(state)
+        .map(Json)
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Status of the watcher running in this process
+pub async fn api_get_live_status(State(state): State<Arc<AppState>>) -> Result<Json<LiveSnapshot>, AdminReply> {
+    state.live.as_ref()
+        .map(|live| Json(live.snapshot()))
+        .ok_or_else(|| admin_error(StatusCode::NOT_FOUND, "No watcher runs in this process; start it with `panoptes serve`"))
+}
+
+pub async fn api_get_watch_dirs(State(state): State<Arc<AppState>>) -> Result<Json<Vec<WatchDir>>, AdminReply> {
+    watch_dirs_reply(&state)
+}
+
+#[derive(Deserialize)]
+pub struct WatchDirRequest {
+    path: String,
+    #[serde(flatten)]
+    options: WatchOptions,
+}
+
+#[derive(Deserialize)]
+pub struct WatchDirQuery {
+    path: String,
+}
+
+/// Store `options` for `path`, leaving defaults out of the config file
+fn set_watch_options(config: &mut AppConfig, path: &str, mut options: WatchOptions) {
+    options.destination = options.destination
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty());
+    if options == WatchOptions::default() {
+        config.watch_options.remove(path);
+    } else {
+        config.watch_options.insert(path.to_string(), options);
+    }
+}
+
+pub async fn api_add_watch_dir(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Json(request): Json<WatchDirRequest>,
+) -> Result<Json<Vec<WatchDir>>, AdminReply> {
+    let path = request.path.trim().to_string();
+    if path.is_empty() {
+        return Err(admin_error(StatusCode::BAD_REQUEST, "Path is required"));
+    }
+    if state.config().watch_paths.contains(&path) {
+        return Err(admin_error(StatusCode::CONFLICT, format!("Already watching {}", path)));
+    }
+
+    let config = state.update_config(|config| {
+        config.watch_paths.push(path.clone());
+        set_watch_options(config, &path, request.options);
+    }).map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.audit(&actor, "watch_dir.add", Some(&path), json!(config.watch_options(&path)));
+    watch_dirs_reply(&state)
+}
+
+/// Replace a watch directory's options
+pub async fn api_update_watch_dir(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Json(request): Json<WatchDirRequest>,
+) -> Result<Json<Vec<WatchDir>>, AdminReply> {
+    if !state.config().watch_paths.contains(&request.path) {
+        return Err(admin_error(StatusCode::NOT_FOUND, format!("Not watching {}", request.path)));
+    }
+
+    let config = state.update_config(|config| set_watch_options(config, &request.path, request.options))
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.audit(&actor, "watch_dir.update", Some(&request.path), json!(config.watch_options(&request.path)));
+    watch_dirs_reply(&state)
+}
+
+pub async fn api_remove_watch_dir(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Query(request): Query<WatchDirQuery>,
+) -> Result<Json<Vec<WatchDir>>, AdminReply> {
+    if !state.config().watch_paths.contains(&request.path) {
+        return Err(admin_error(StatusCode::NOT_FOUND, format!("Not watching {}", request.path)));
+    }
+
+    state.update_config(|config| {
+        config.watch_paths.retain(|p| *p != request.path);
+        config.watch_options.remove(&request.path);
+    }).map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.audit(&actor, "watch_dir.remove", Some(&request.path), json!({}));
+    watch_dirs_reply(&state)
+}
+
+#[derive(Deserialize)]
+pub struct PageQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+pub async fn api_get_audit(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Vec<AuditEntry>>, AdminReply> {
+    state.db.get_audit_log(query.limit.unwrap_or(100), query.offset.unwrap_or(0))
+        .map(Json)
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Webhook delivery attempts, newest first
+pub async fn api_get_webhook_deliveries(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, AdminReply> {
+    state.db.get_webhook_deliveries(query.limit.unwrap_or(100), query.offset.unwrap_or(0))
+        .map(Json)
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// The current configuration, with secrets redacted
+pub async fn api_get_settings(State(state): State<Arc<AppState>>) -> Result<Json<Value>, AdminReply> {
+    let mut config = serde_json::to_value(&*state.config())
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let settings = layers::resolve(&state.config_path).map(|(_, settings)| settings).unwrap_or_default();
+    secrets::redact(&mut config, &settings);
+    Ok(Json(config))
+}
+
+#[derive(Serialize)]
+pub struct SettingsPreview {
+    problems: Vec<String>,
+    changes: Vec<ConfigChange>,
+}
+
+/// Parse a submitted configuration and compare it with the current one
+fn preview(state: &AppState, mut submitted: Value) -> Result<(AppConfig, SettingsPreview), AdminReply> {
+    // Secrets are sent redacted (see `api_get_settings`) and come back so when unchanged
+    let current = serde_json::to_value(&*state.config())
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    secrets::restore(&mut submitted, &current);
+    let config: AppConfig = serde_json::from_value(submitted)
+        .map_err(|e| admin_error(StatusCode::BAD_REQUEST, format!("Invalid configuration: {}", e)))?;
+    let changes = state.config().diff(&config)
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let problems = config.problems();
+    Ok((config, SettingsPreview { problems, changes }))
+}
+
+/// Validate a configuration and list what would change, without saving
+pub async fn api_preview_settings(
+    State(state): State<Arc<AppState>>,
+    Json(submitted): Json<Value>,
+) -> Result<Json<SettingsPreview>, AdminReply> {
+    preview(&state, submitted).map(|(_, preview)| Json(preview))
+}
+
+/// Validate, save and apply a configuration
+pub async fn api_save_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Json(submitted): Json<Value>,
+) -> Result<Json<SettingsPreview>, AdminReply> {
+    let (config, preview) = preview(&state, submitted)?;
+    if !preview.problems.is_empty() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(json!({
+            "error": "Configuration is invalid",
+            "problems": preview.problems,
+        }))));
+    }
+    if preview.changes.is_empty() {
+        return Ok(Json(preview));
+    }
+
+    state.update_config(|current| *current = config)
+        .map_err(|e| admin_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // Paths only: values may include secrets
+    let changed: Vec<&str> = preview.changes.iter().map(|c| c.path.as_str()).collect();
+    state.audit(&actor, "settings.update", None, json!({ "changed": changed }));
+    Ok(Json(preview))
+}
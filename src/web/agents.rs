@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Agents: machines forwarding their files here (see [`crate::agent`])
+
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::auth::{self, Actor, Identity};
+use super::AppState;
+use crate::agent::{agent_of_token, Agent, Registration};
+
+type AgentReply = (StatusCode, Json<Value>);
+
+fn agent_error(code: StatusCode, message: impl ToString) -> AgentReply {
+    (code, Json(json!({ "error": message.to_string() })))
+}
+
+/// The agent making a request with its token, if it is one
+pub fn requesting_agent(actor: &Actor) -> Option<&str> {
+    match actor.identity {
+        Identity::Token(ref name) => agent_of_token(name),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RegisterRequest {
+    name: String,
+    #[serde(default)]
+    hostname: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+/// Register an agent, giving it a token of its own; registering a name again
+/// replaces its token
+pub async fn api_register_agent(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Json(request): Json<RegisterRequest>,
+) -> Result<Json<Registration>, AgentReply> {
+    let name = request.name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return Err(agent_error(StatusCode::BAD_REQUEST, "Agent names are letters, digits, '-', '_' and '.'"));
+    }
+    let token = auth::generate_token();
+    let agent = state.db.register_agent(name, request.hostname.as_deref(), request.version.as_deref(), &auth::hash_secret(&token))
+        .map_err(|e| agent_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.audit(&actor, "agent.register", Some(name), json!({ "hostname": agent.hostname, "version": agent.version }));
+    Ok(Json(Registration { agent, token }))
+}
+
+pub async fn api_get_agents(State(state): State<Arc<AppState>>) -> Result<Json<Vec<Agent>>, AgentReply> {
+    state.db.get_agents()
+        .map(Json)
+        .map_err(|e| agent_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// The agent whose token made the request
+pub async fn api_agent_me(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+) -> Result<Json<Agent>, AgentReply> {
+    let Some(name) = requesting_agent(&actor) else {
+        return Err(agent_error(StatusCode::NOT_FOUND, "Not an agent's token"));
+    };
+    state.db.get_agent(name)
+        .map_err(|e| agent_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .map(Json)
+        .ok_or_else(|| agent_error(StatusCode::NOT_FOUND, format!("No agent named '{}'", name)))
+}
+
+/// Remove an agent, revoking its token
+pub async fn api_remove_agent(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, AgentReply> {
+    let removed = state.db.remove_agent(&name)
+        .map_err(|e| agent_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if !removed {
+        return Err(agent_error(StatusCode::NOT_FOUND, format!("No agent named '{}'", name)));
+    }
+    state.audit(&actor, "agent.remove", Some(&name), json!({}));
+    Ok(Json(json!({ "removed": name })))
+}
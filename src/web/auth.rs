@@ -0,0 +1,396 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Authentication for the web UI and API
+//!
+//! API clients send a token as `Authorization: Bearer <token>` (or `X-API-Key`);
+//! browsers log in with a token once and then carry a session cookie. Tokens
+//! come from `web.auth.tokens` or are created with `panoptes token create`, in
+//! which case only their hash is stored.
+//!
+//! Every authenticated request carries an [`Actor`] whose [`Role`] gates which
+//! routes it may use (see [`require_role`]).
+
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use chrono::{Duration, Utc};
+use minijinja::context;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::warn;
+
+use super::security::{csrf_cookie, csrf_valid};
+use super::AppState;
+use crate::db::Role;
+
+/// Name of the login session cookie
+pub const SESSION_COOKIE: &str = "panoptes_session";
+
+/// Session subjects with this prefix belong to a user account
+pub const USER_SUBJECT_PREFIX: &str = "user:";
+
+/// Session subjects with this prefix were started with a named API token
+pub const TOKEN_SUBJECT_PREFIX: &str = "token:";
+
+/// Session subjects with this prefix were started with a token from the
+/// config file, followed by the token's hash
+const CONFIG_SUBJECT_PREFIX: &str = "config:";
+
+/// Who made a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Identity {
+    /// Auth is disabled, or the request came from localhost with `allow_localhost`
+    Anonymous,
+    /// An API token from the config file
+    ConfigToken,
+    /// A named API token from the database
+    Token(String),
+    /// A browser login session started with a token
+    Session(String),
+    /// A user account (e.g. logged in through OIDC)
+    User(String),
+}
+
+impl Identity {
+    /// How this identity is named in the audit log
+    pub fn audit_name(&self) -> String {
+        match self {
+            Self::Anonymous => "local".to_string(),
+            Self::ConfigToken => "token:config".to_string(),
+            Self::Token(name) | Self::Session(name) => format!("{}{}", TOKEN_SUBJECT_PREFIX, name),
+            Self::User(name) => format!("{}{}", USER_SUBJECT_PREFIX, name),
+        }
+    }
+}
+
+/// An authenticated requester and what they may do
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub identity: Identity,
+    pub role: Role,
+}
+
+impl Actor {
+    fn new(identity: Identity, role: Role) -> Self {
+        Self { identity, role }
+    }
+}
+
+/// Hash a token or session ID for storage and lookup
+pub fn hash_secret(secret: &str) -> String {
+    blake3::hash(secret.as_bytes()).to_hex().to_string()
+}
+
+/// Generate a new random API token
+pub fn generate_token() -> String {
+    format!("pt_{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// Resolve a presented token to an actor; config-file tokens have full access
+pub fn check_token(state: &AppState, token: &str) -> Option<Actor> {
+    let presented = blake3::hash(token.as_bytes());
+    // blake3::Hash compares in constant time
+    if state.config().web.auth.tokens.iter().any(|t| blake3::hash(t.as_bytes()) == presented) {
+        return Some(Actor::new(Identity::ConfigToken, Role::Admin));
+    }
+    match state.db.use_api_token(&presented.to_hex()) {
+        Ok(token) => token.map(|(name, role)| Actor::new(Identity::Token(name), role)),
+        Err(e) => {
+            warn!("Token lookup failed: {}", e);
+            None
+        }
+    }
+}
+
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+}
+
+/// Value of a request cookie
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(n, _)| *n == name)
+        .map(|(_, value)| value)
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    cookie(headers, SESSION_COOKIE)
+}
+
+/// Resolve a session subject to an actor, with the current role of its user or token
+fn session_actor(state: &AppState, subject: &str) -> Option<Actor> {
+    if let Some(hash) = subject.strip_prefix(CONFIG_SUBJECT_PREFIX) {
+        // Only while the token is still in the config
+        let hash = blake3::Hash::from_hex(hash).ok()?;
+        let config = state.config();
+        return config.web.auth.tokens.iter()
+            .any(|t| blake3::hash(t.as_bytes()) == hash)
+            .then(|| Actor::new(Identity::ConfigToken, Role::Admin));
+    }
+    if let Some(username) = subject.strip_prefix(USER_SUBJECT_PREFIX) {
+        let role = state.db.get_user_role(username).ok()??;
+        return Some(Actor::new(Identity::User(username.to_string()), role));
+    }
+    let name = subject.strip_prefix(TOKEN_SUBJECT_PREFIX)?;
+    let role = state.db.get_api_token_role(name).ok()??;
+    Some(Actor::new(Identity::Session(name.to_string()), role))
+}
+
+/// Work out who is making a request, if anyone. Without auth, and for localhost
+/// when `allow_localhost` is set, the requester is treated as the local admin.
+pub fn authenticate(state: &AppState, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<Actor> {
+    let config = state.config();
+    let auth = &config.web.auth;
+    if !auth.enabled {
+        return Some(Actor::new(Identity::Anonymous, Role::Admin));
+    }
+
+    if let Some(token) = bearer_token(headers) {
+        return check_token(state, token);
+    }
+
+    if let Some(session) = session_cookie(headers) {
+        if let Ok(Some(subject)) = state.db.get_web_session(&hash_secret(session)) {
+            if let Some(actor) = session_actor(state, &subject) {
+                return Some(actor);
+            }
+        }
+    }
+
+    if auth.allow_localhost && peer.is_some_and(|addr| addr.ip().is_loopback()) {
+        return Some(Actor::new(Identity::Anonymous, Role::Admin));
+    }
+
+    None
+}
+
+/// Middleware rejecting unauthenticated requests: 401 for the API, a redirect to
+/// the login page for everything else
+pub async fn require_auth(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0);
+    match authenticate(&state, request.headers(), peer) {
+        Some(actor) => {
+            request.extensions_mut().insert(actor);
+            next.run(request).await
+        }
+        None if request.uri().path().starts_with("/api/") => (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "Authentication required" })),
+        ).into_response(),
+        None => {
+            let next = urlencode(request.uri().path());
+            Redirect::to(&format!("{}/login?next={}", state.base_path, next)).into_response()
+        }
+    }
+}
+
+/// Middleware rejecting actors below `role`; runs inside [`require_auth`]
+pub async fn require_role(role: Role, request: Request, next: Next) -> Response {
+    match request.extensions().get::<Actor>() {
+        Some(actor) if actor.role >= role => next.run(request).await,
+        _ => (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": format!("Requires the {} role", role.as_str()) })),
+        ).into_response(),
+    }
+}
+
+/// `Secure` cookie attribute when serving HTTPS
+pub(crate) fn secure_attr(state: &AppState) -> &'static str {
+    if state.config().web.tls.is_some() { "; Secure" } else { "" }
+}
+
+/// `Path` for cookies covering the whole UI
+pub(crate) fn cookie_path(state: &AppState) -> &str {
+    if state.base_path.is_empty() { "/" } else { &state.base_path }
+}
+
+/// Minimal percent-encoding for a path used as a query value
+fn urlencode(value: &str) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Percent-encoding for a single path segment
+pub(crate) fn encode_segment(value: &str) -> String {
+    urlencode(value).replace('/', "%2F")
+}
+
+/// Only same-site paths are valid redirect targets after login
+pub(crate) fn safe_next(next: Option<&str>) -> &str {
+    match next {
+        Some(path) if path.starts_with('/') && !path.starts_with("//") && !path.starts_with("/\\") => path,
+        _ => "/",
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    token: String,
+    next: Option<String>,
+}
+
+pub async fn login_page(State(state): State<Arc<AppState>>, Query(query): Query<LoginQuery>) -> Response {
+    render_login(&state, safe_next(query.next.as_deref()), None)
+}
+
+pub async fn login(State(state): State<Arc<AppState>>, Form(form): Form<LoginForm>) -> Response {
+    let next = safe_next(form.next.as_deref());
+    let Some(actor) = check_token(&state, form.token.trim()) else {
+        return (StatusCode::UNAUTHORIZED, render_login(&state, next, Some("Invalid token"))).into_response();
+    };
+
+    let subject = match actor.identity {
+        Identity::Token(name) => format!("{}{}", TOKEN_SUBJECT_PREFIX, name),
+        _ => format!("{}{}", CONFIG_SUBJECT_PREFIX, hash_secret(form.token.trim())),
+    };
+    let session = generate_token();
+    let hours = state.config().web.auth.session_hours;
+    let expires = Utc::now() + Duration::hours(hours as i64);
+    if let Err(e) = state.db.create_web_session(&hash_secret(&session), &subject, expires) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, render_login(&state, next, Some(&e.to_string()))).into_response();
+    }
+
+    let cookie = format!(
+        "{}={}; Path={}; HttpOnly; SameSite=Strict; Max-Age={}{}",
+        SESSION_COOKIE, session, cookie_path(&state), hours * 3600, secure_attr(&state)
+    );
+    let csrf = csrf_cookie(&state, Some(&session), hours * 3600);
+    (
+        AppendHeaders([(header::SET_COOKIE, cookie), (header::SET_COOKIE, csrf)]),
+        Redirect::to(&format!("{}{}", state.base_path, next)),
+    ).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct LogoutForm {
+    csrf_token: Option<String>,
+}
+
+pub async fn logout(State(state): State<Arc<AppState>>, headers: HeaderMap, Form(form): Form<LogoutForm>) -> Response {
+    if !csrf_valid(&headers, form.csrf_token.as_deref()) {
+        return (StatusCode::FORBIDDEN, "Missing or invalid CSRF token").into_response();
+    }
+    if let Some(session) = session_cookie(&headers) {
+        let _ = state.db.delete_web_session(&hash_secret(session));
+    }
+    let cookie = format!(
+        "{}=; Path={}; HttpOnly; SameSite=Strict; Max-Age=0{}",
+        SESSION_COOKIE, cookie_path(&state), secure_attr(&state)
+    );
+    let csrf = csrf_cookie(&state, None, 0);
+    (
+        AppendHeaders([(header::SET_COOKIE, cookie), (header::SET_COOKIE, csrf)]),
+        Redirect::to(&format!("{}/login", state.base_path)),
+    ).into_response()
+}
+
+fn render_login(state: &AppState, next: &str, error: Option<&str>) -> Response {
+    state.templates.render("login.html", context! {
+        next,
+        error,
+        oidc_provider => state.oidc.as_ref().map(|oidc| oidc.provider_name()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::AppConfig;
+
+    const CONFIG_TOKEN: &str = "pt_from_the_config";
+
+    fn state(dir: &std::path::Path) -> AppState {
+        let mut config = AppConfig::default();
+        config.web.auth.enabled = true;
+        config.web.auth.allow_localhost = false;
+        config.web.auth.tokens = vec![CONFIG_TOKEN.to_string()];
+        AppState::new(config, dir.join("config.json"), Database::in_memory().unwrap())
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(dir.path());
+        state.db.create_api_token("ci", &hash_secret("pt_ci"), Role::Editor).unwrap();
+
+        let actor = authenticate(&state, &bearer(CONFIG_TOKEN), None).unwrap();
+        assert_eq!((actor.identity, actor.role), (Identity::ConfigToken, Role::Admin));
+        let actor = authenticate(&state, &bearer("pt_ci"), None).unwrap();
+        assert_eq!((actor.identity, actor.role), (Identity::Token("ci".to_string()), Role::Editor));
+        assert!(authenticate(&state, &bearer("pt_wrong"), None).is_none());
+        assert!(authenticate(&state, &HeaderMap::new(), None).is_none());
+
+        state.db.revoke_api_token("ci").unwrap();
+        assert!(authenticate(&state, &bearer("pt_ci"), None).is_none());
+    }
+
+    #[test]
+    fn test_localhost() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(dir.path());
+        let local: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        assert!(authenticate(&state, &HeaderMap::new(), Some(local)).is_none());
+
+        state.update_config(|config| config.web.auth.allow_localhost = true).unwrap();
+        let remote: SocketAddr = "192.0.2.7:5000".parse().unwrap();
+        assert!(authenticate(&state, &HeaderMap::new(), Some(local)).is_some_and(|a| a.role == Role::Admin));
+        assert!(authenticate(&state, &HeaderMap::new(), Some(remote)).is_none());
+    }
+
+    #[test]
+    fn test_config_token_session_ends_with_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(dir.path());
+        let subject = format!("{}{}", CONFIG_SUBJECT_PREFIX, hash_secret(CONFIG_TOKEN));
+        assert!(session_actor(&state, &subject).is_some_and(|a| a.identity == Identity::ConfigToken));
+
+        state.update_config(|config| config.web.auth.tokens.clear()).unwrap();
+        assert!(session_actor(&state, &subject).is_none());
+    }
+
+    #[test]
+    fn test_session_subjects() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(dir.path());
+        state.db.create_api_token("ci", &hash_secret("pt_ci"), Role::Viewer).unwrap();
+
+        let actor = session_actor(&state, "token:ci").unwrap();
+        assert_eq!((actor.identity, actor.role), (Identity::Session("ci".to_string()), Role::Viewer));
+        // Unprefixed subjects name nothing
+        assert!(session_actor(&state, "ci").is_none());
+        assert!(session_actor(&state, "config").is_none());
+        assert!(session_actor(&state, "config:not-a-hash").is_none());
+        assert!(session_actor(&state, "user:nobody").is_none());
+    }
+}
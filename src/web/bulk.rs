@@ -0,0 +1,429 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Bulk operations on file records
+//!
+//! `POST /api/files/bulk` applies one action to a list of file IDs or to every
+//! file matching a filter. The work runs in the background; the response holds
+//! a job ID whose progress can be polled at `/api/jobs/:id`. A few jobs run at
+//! a time and the rest wait in a queue; jobs can be cancelled, and a finished
+//! job retried for the files it failed on or never reached.
+
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use super::auth::Actor;
+use super::{analyze_error, reanalyze, AppState, FilesQuery};
+use crate::xattrs;
+
+/// Most files a single bulk request may touch
+const MAX_BULK_FILES: usize = 10_000;
+
+/// How long finished jobs stay queryable
+const JOB_RETENTION_HOURS: i64 = 24;
+
+/// Jobs running at once; later ones wait in the queue
+const MAX_RUNNING_JOBS: usize = 2;
+
+/// Errors kept per job; later ones are only counted
+const MAX_JOB_ERRORS: usize = 100;
+
+type BulkReply<T> = Result<T, (StatusCode, Json<serde_json::Value>)>;
+
+/// What to do with each selected file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// Set the category; an empty or missing one clears it
+    SetCategory { category: Option<String> },
+    AddTag { tag: String },
+    RemoveTag { tag: String },
+    /// Run the analyzer again with the current configuration
+    Reanalyze,
+    /// Hide the records (the files on disk are left alone)
+    Delete,
+}
+
+impl BulkAction {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::SetCategory { .. } => "set_category",
+            Self::AddTag { .. } => "add_tag",
+            Self::RemoveTag { .. } => "remove_tag",
+            Self::Reanalyze => "reanalyze",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BulkRequest {
+    /// Files to act on
+    ids: Option<Vec<String>>,
+    /// Act on every file matching this filter instead (same fields as `/api/files`)
+    filter: Option<FilesQuery>,
+    #[serde(flatten)]
+    action: BulkAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    /// Waiting for a free slot
+    Queued,
+    Running,
+    /// Finished without failures
+    Completed,
+    /// Finished, but some files failed
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobError {
+    pub id: String,
+    pub error: String,
+}
+
+/// Progress of a bulk job
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub action: &'static str,
+    pub state: JobState,
+    pub total: usize,
+    pub processed: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// The first failures, by file ID
+    pub errors: Vec<JobError>,
+    /// File being worked on
+    pub current: Option<String>,
+    /// The job this one retries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_of: Option<String>,
+    pub queued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Seconds spent running so far
+    pub elapsed_secs: i64,
+}
+
+/// A job and what it needs to run, cancel and retry
+struct JobEntry {
+    job: Job,
+    action: BulkAction,
+    ids: Vec<String>,
+    /// Every failed file, not just the reported ones
+    failed_ids: Vec<String>,
+    cancel: bool,
+}
+
+/// Bulk jobs of this server process, run a few at a time
+pub struct Jobs {
+    jobs: Mutex<HashMap<String, JobEntry>>,
+    slots: Arc<Semaphore>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self {
+            jobs: Mutex::default(),
+            slots: Arc::new(Semaphore::new(MAX_RUNNING_JOBS)),
+        }
+    }
+}
+
+impl Jobs {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, JobEntry>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn start(&self, action: &BulkAction, ids: Vec<String>, retry_of: Option<String>) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let cutoff = Utc::now() - Duration::hours(JOB_RETENTION_HOURS);
+        let mut jobs = self.lock();
+        jobs.retain(|_, entry| entry.job.finished_at.map_or(true, |at| at > cutoff));
+        jobs.insert(id.clone(), JobEntry {
+            job: Job {
+                id: id.clone(),
+                action: action.name(),
+                state: JobState::Queued,
+                total: ids.len(),
+                processed: 0,
+                succeeded: 0,
+                failed: 0,
+                errors: Vec::new(),
+                current: None,
+                retry_of,
+                queued_at: Utc::now(),
+                started_at: None,
+                finished_at: None,
+                elapsed_secs: 0,
+            },
+            action: action.clone(),
+            ids,
+            failed_ids: Vec::new(),
+            cancel: false,
+        });
+        id
+    }
+
+    fn update<T>(&self, id: &str, change: impl FnOnce(&mut JobEntry) -> T) -> Option<T> {
+        self.lock().get_mut(id).map(change)
+    }
+
+    /// Current progress of a job
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.lock().get(id).map(snapshot)
+    }
+
+    /// Every job, newest first
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.lock().values().map(snapshot).collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.queued_at));
+        jobs
+    }
+
+    /// Stop a job before its next file; `None` when there is no such job
+    fn cancel(&self, id: &str) -> Option<Result<Job, &'static str>> {
+        self.update(id, |entry| {
+            if entry.job.state.is_finished() {
+                return Err("The job has already finished");
+            }
+            entry.cancel = true;
+            if entry.job.state == JobState::Queued {
+                entry.job.state = JobState::Cancelled;
+                entry.job.finished_at = Some(Utc::now());
+            }
+            Ok(snapshot(entry))
+        })
+    }
+}
+
+fn snapshot(entry: &JobEntry) -> Job {
+    let mut job = entry.job.clone();
+    if let Some(started) = job.started_at {
+        job.elapsed_secs = (job.finished_at.unwrap_or_else(Utc::now) - started).num_seconds();
+    }
+    job
+}
+
+#[derive(Serialize)]
+pub struct BulkResponse {
+    job_id: String,
+    total: usize,
+}
+
+/// Start a bulk job
+pub async fn api_bulk_files(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Json(request): Json<BulkRequest>,
+) -> BulkReply<(StatusCode, Json<BulkResponse>)> {
+    let ids = match (request.ids, request.filter) {
+        (Some(ids), None) => ids,
+        (None, Some(filter)) => {
+            let (files, total) = state.db.query_files(&filter.filter(), MAX_BULK_FILES, 0)
+                .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            if total as usize > MAX_BULK_FILES {
+                return Err(analyze_error(StatusCode::UNPROCESSABLE_ENTITY, format!(
+                    "The filter matches {} files; at most {} can be changed at once", total, MAX_BULK_FILES
+                )));
+            }
+            files.into_iter().map(|f| f.id).collect()
+        }
+        _ => return Err(analyze_error(StatusCode::BAD_REQUEST, "Give either ids or filter")),
+    };
+    if ids.len() > MAX_BULK_FILES {
+        return Err(analyze_error(StatusCode::UNPROCESSABLE_ENTITY, format!(
+            "At most {} files can be changed at once", MAX_BULK_FILES
+        )));
+    }
+
+    let action = match request.action {
+        BulkAction::SetCategory { category } => BulkAction::SetCategory {
+            category: category.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()),
+        },
+        BulkAction::AddTag { tag } | BulkAction::RemoveTag { tag } if tag.trim().is_empty() => {
+            return Err(analyze_error(StatusCode::BAD_REQUEST, "Tag name is empty"));
+        }
+        BulkAction::AddTag { tag } => BulkAction::AddTag { tag: tag.trim().to_string() },
+        BulkAction::RemoveTag { tag } => BulkAction::RemoveTag { tag: tag.trim().to_string() },
+        other => other,
+    };
+
+    Ok((StatusCode::ACCEPTED, Json(submit(&state, &actor, action, ids, None))))
+}
+
+/// Queue a job and record it in the audit log
+fn submit(state: &Arc<AppState>, actor: &Actor, action: BulkAction, ids: Vec<String>, retry_of: Option<String>) -> BulkResponse {
+    let total = ids.len();
+    let job_id = state.jobs.start(&action, ids, retry_of.clone());
+    let mut details = serde_json::to_value(&action).unwrap_or_default();
+    details["files"] = total.into();
+    if let Some(retry_of) = retry_of {
+        details["retry_of"] = retry_of.into();
+    }
+    state.audit(actor, "files.bulk", Some(&job_id), details);
+    info!("Queued bulk {} on {} files (job {})", action.name(), total, job_id);
+
+    tokio::spawn(run_job(state.clone(), job_id.clone()));
+    BulkResponse { job_id, total }
+}
+
+/// All jobs kept in memory, newest first
+pub async fn api_list_jobs(State(state): State<Arc<AppState>>) -> Json<Vec<Job>> {
+    Json(state.jobs.list())
+}
+
+/// Progress of a bulk job
+pub async fn api_get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> BulkReply<Json<Job>> {
+    state.jobs.get(&id)
+        .map(Json)
+        .ok_or_else(|| analyze_error(StatusCode::NOT_FOUND, "No such job"))
+}
+
+/// Stop a queued or running job; files already done stay done
+pub async fn api_cancel_job(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+) -> BulkReply<Json<Job>> {
+    let job = state.jobs.cancel(&id)
+        .ok_or_else(|| analyze_error(StatusCode::NOT_FOUND, "No such job"))?
+        .map_err(|e| analyze_error(StatusCode::CONFLICT, e))?;
+    state.audit(&actor, "job.cancel", Some(&id), serde_json::json!({ "action": job.action }));
+    Ok(Json(job))
+}
+
+/// Start a new job for the files a finished job failed on or never reached
+pub async fn api_retry_job(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+) -> BulkReply<(StatusCode, Json<BulkResponse>)> {
+    let (action, ids) = state.jobs.update(&id, |entry| {
+        if !entry.job.state.is_finished() {
+            return Err("The job is still running");
+        }
+        let mut ids = entry.failed_ids.clone();
+        ids.extend_from_slice(&entry.ids[entry.job.processed.min(entry.ids.len())..]);
+        if ids.is_empty() {
+            return Err("Nothing to retry");
+        }
+        Ok((entry.action.clone(), ids))
+    })
+    .ok_or_else(|| analyze_error(StatusCode::NOT_FOUND, "No such job"))?
+    .map_err(|e| analyze_error(StatusCode::CONFLICT, e))?;
+
+    Ok((StatusCode::ACCEPTED, Json(submit(&state, &actor, action, ids, Some(id)))))
+}
+
+async fn run_job(state: Arc<AppState>, job_id: String) {
+    let Ok(_slot) = state.jobs.slots.clone().acquire_owned().await else {
+        return;
+    };
+    // Jobs cancelled while queued are already finished
+    let Some(Some((action, ids))) = state.jobs.update(&job_id, |entry| {
+        if entry.cancel {
+            return None;
+        }
+        entry.job.started_at = Some(Utc::now());
+        entry.job.state = JobState::Running;
+        Some((entry.action.clone(), entry.ids.clone()))
+    }) else {
+        return;
+    };
+
+    // Re-analysis uses the configuration as it was when the job started
+    let config = state.config();
+    for id in ids {
+        let proceed = state.jobs.update(&job_id, |entry| {
+            entry.job.current = Some(id.clone());
+            !entry.cancel
+        });
+        if proceed != Some(true) {
+            break;
+        }
+        let outcome = apply(&state, &config, &action, &id).await;
+        state.jobs.update(&job_id, |entry| {
+            let job = &mut entry.job;
+            job.processed += 1;
+            match outcome {
+                Ok(()) => job.succeeded += 1,
+                Err(error) => {
+                    job.failed += 1;
+                    entry.failed_ids.push(id.clone());
+                    if job.errors.len() < MAX_JOB_ERRORS {
+                        job.errors.push(JobError { id, error });
+                    }
+                }
+            }
+        });
+    }
+
+    state.jobs.update(&job_id, |entry| {
+        let job = &mut entry.job;
+        job.current = None;
+        job.finished_at = Some(Utc::now());
+        job.state = if entry.cancel {
+            JobState::Cancelled
+        } else if job.failed > 0 {
+            JobState::Failed
+        } else {
+            JobState::Completed
+        };
+        match job.state {
+            JobState::Cancelled => info!("Bulk {} job {} cancelled after {} files", job.action, job.id, job.processed),
+            JobState::Failed => warn!("Bulk {} job {} finished with {} failures", job.action, job.id, job.failed),
+            _ => info!("Bulk {} job {} finished", job.action, job.id),
+        }
+    });
+}
+
+async fn apply(state: &Arc<AppState>, config: &crate::AppConfig, action: &BulkAction, id: &str) -> Result<(), String> {
+    let file = state.db.get_file(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "No such file".to_string())?;
+    match action {
+        BulkAction::SetCategory { category } => {
+            state.db.set_category(&file.id, category.as_deref()).map_err(|e| e.to_string())?;
+            xattrs::tag_or_warn(&state.db, &file.id, config);
+        }
+        BulkAction::AddTag { tag } => {
+            state.db.add_tag_to_file(&file.id, tag).map_err(|e| e.to_string())?;
+            xattrs::tag_or_warn(&state.db, &file.id, config);
+        }
+        BulkAction::RemoveTag { tag } => {
+            state.db.remove_tag_from_file(&file.id, tag).map_err(|e| e.to_string())?;
+            xattrs::tag_or_warn(&state.db, &file.id, config);
+        }
+        BulkAction::Reanalyze => {
+            reanalyze(state, &file, config).await
+                .map_err(|(_, Json(body))| body["error"].as_str().unwrap_or("Analysis failed").to_string())?;
+        }
+        BulkAction::Delete => {
+            state.db.soft_delete_file(&file.id).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Read-only GraphQL API
+//!
+//! Exposes files, tags, categories, history and stats at `/api/graphql`, with
+//! nested lookups (a file's tags, the other files carrying a tag, the file a
+//! history event belongs to) so a client can fetch what a view needs in one
+//! request. `/graphql` serves GraphiQL for exploring the schema.
+
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Enum, Object, Schema, SimpleObject,
+};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse},
+    Json,
+};
+use std::sync::Arc;
+
+use super::auth::{cookie, SESSION_COOKIE};
+use super::security::{csrf_token, CSRF_HEADER, GRAPHIQL_CONTENT_SECURITY_POLICY};
+use super::AppState;
+use crate::db::{Database, FileFilter, FileRecord, FileSort};
+use crate::history::{History, HistoryEntry};
+
+/// Schema served at `/api/graphql`
+pub type PanoptesSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Items per page when a query doesn't say
+const DEFAULT_PAGE: usize = 50;
+
+/// Largest page a single field may return
+const MAX_PAGE: usize = 500;
+
+/// Deepest nesting allowed, so file → tags → files chains can't recurse forever
+const MAX_DEPTH: usize = 12;
+
+/// Build the schema over `db`
+pub fn schema(db: Database) -> PanoptesSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(db)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+/// Execute a GraphQL request
+pub async fn api_graphql(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(state.graphql.execute(request).await)
+}
+
+/// GraphiQL explorer
+pub async fn graphiql_page(State(state): State<Arc<AppState>>, headers: HeaderMap) -> impl IntoResponse {
+    let endpoint = format!("{}/api/graphql", state.base_path);
+    let csrf = cookie(&headers, SESSION_COOKIE).map(csrf_token);
+    let mut source = async_graphql::http::GraphiQLSource::build()
+        .endpoint(&endpoint)
+        .title("Panoptes GraphQL");
+    if let Some(token) = &csrf {
+        source = source.header(CSRF_HEADER, token);
+    }
+    (
+        [(header::CONTENT_SECURITY_POLICY, GRAPHIQL_CONTENT_SECURITY_POLICY)],
+        Html(source.finish()),
+    )
+}
+
+fn page(first: Option<i32>, offset: Option<i32>) -> (usize, usize) {
+    let first = first.map_or(DEFAULT_PAGE, |n| n.max(0) as usize).min(MAX_PAGE);
+    let offset = offset.map_or(0, |n| n.max(0) as usize);
+    (first, offset)
+}
+
+fn files_page(ctx: &Context<'_>, filter: &FileFilter, first: Option<i32>, offset: Option<i32>) -> async_graphql::Result<FilePage> {
+    let (limit, offset) = page(first, offset);
+    let (files, total) = ctx.data::<Database>()?.query_files(filter, limit, offset)?;
+    Ok(FilePage {
+        total,
+        items: files.into_iter().map(FileNode).collect(),
+    })
+}
+
+/// How files are ordered
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileOrder {
+    /// When the file was processed
+    #[default]
+    Date,
+    Confidence,
+    Name,
+}
+
+impl From<FileOrder> for FileSort {
+    fn from(order: FileOrder) -> Self {
+        match order {
+            FileOrder::Date => Self::Date,
+            FileOrder::Confidence => Self::Confidence,
+            FileOrder::Name => Self::Name,
+        }
+    }
+}
+
+/// A page of files and the total number of matches
+#[derive(SimpleObject)]
+pub struct FilePage {
+    pub total: i64,
+    pub items: Vec<FileNode>,
+}
+
+/// A page of history events and the total number of events
+#[derive(SimpleObject)]
+pub struct HistoryPage {
+    pub total: i64,
+    pub items: Vec<HistoryNode>,
+}
+
+/// Totals across the database
+#[derive(SimpleObject)]
+pub struct Stats {
+    pub file_count: i64,
+    pub tag_count: i64,
+    pub category_count: i64,
+}
+
+/// A tag and the files carrying it
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct TagNode {
+    pub name: String,
+}
+
+#[ComplexObject]
+impl TagNode {
+    /// Number of files with this tag
+    async fn count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        let filter = FileFilter { tag: Some(self.name.clone()), ..Default::default() };
+        Ok(ctx.data::<Database>()?.query_files(&filter, 0, 0)?.1)
+    }
+
+    async fn files(&self, ctx: &Context<'_>, first: Option<i32>, offset: Option<i32>) -> async_graphql::Result<FilePage> {
+        let filter = FileFilter { tag: Some(self.name.clone()), ..Default::default() };
+        files_page(ctx, &filter, first, offset)
+    }
+}
+
+/// A category and the files in it
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct CategoryNode {
+    /// `Uncategorized` for files without a category
+    pub name: String,
+    pub file_count: i64,
+}
+
+#[ComplexObject]
+impl CategoryNode {
+    /// Files in this category (none for `Uncategorized`, which isn't a stored name)
+    async fn files(&self, ctx: &Context<'_>, first: Option<i32>, offset: Option<i32>) -> async_graphql::Result<FilePage> {
+        let filter = FileFilter { category: Some(self.name.clone()), ..Default::default() };
+        files_page(ctx, &filter, first, offset)
+    }
+}
+
+/// A processed file
+pub struct FileNode(FileRecord);
+
+#[Object]
+impl FileNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn original_path(&self) -> &str {
+        &self.0.original_path
+    }
+
+    /// Where the file currently lives
+    async fn path(&self) -> &str {
+        &self.0.new_path
+    }
+
+    async fn suggested_name(&self) -> &str {
+        &self.0.suggested_name
+    }
+
+    /// Name chosen in review when it differs from the suggestion
+    async fn corrected_name(&self) -> Option<&str> {
+        self.0.corrected_name.as_deref()
+    }
+
+    async fn hash(&self) -> &str {
+        &self.0.file_hash
+    }
+
+    async fn category(&self) -> Option<&str> {
+        self.0.category.as_deref()
+    }
+
+    async fn confidence(&self) -> f64 {
+        self.0.confidence
+    }
+
+    /// Review queue state: pending, approved or rejected
+    async fn status(&self) -> Option<&str> {
+        self.0.status.map(|s| s.as_str())
+    }
+
+    /// When the file was processed (RFC 3339)
+    async fn created_at(&self) -> String {
+        self.0.created_at.to_rfc3339()
+    }
+
+    /// Whether the most recent rename has been undone
+    async fn undone(&self) -> bool {
+        self.0.undone
+    }
+
+    /// Analyzer metadata
+    async fn metadata(&self) -> async_graphql::Json<&serde_json::Value> {
+        async_graphql::Json(&self.0.metadata)
+    }
+
+    async fn tags(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<TagNode>> {
+        let tags = ctx.data::<Database>()?.get_file_tags(&self.0.id)?;
+        Ok(tags.into_iter().map(|name| TagNode { name }).collect())
+    }
+
+    /// Renames and moves of this file, newest first
+    async fn history(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<HistoryNode>> {
+        let history = History::new(ctx.data::<Database>()?.clone());
+        let mut entries: Vec<HistoryNode> = history.read_all()?
+            .into_iter()
+            .filter(|e| e.file_id.as_deref() == Some(self.0.id.as_str()))
+            .map(HistoryNode)
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.0.timestamp));
+        Ok(entries)
+    }
+}
+
+/// A rename or move
+pub struct HistoryNode(HistoryEntry);
+
+#[Object]
+impl HistoryNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    /// When it happened (RFC 3339)
+    async fn timestamp(&self) -> String {
+        self.0.timestamp.to_rfc3339()
+    }
+
+    /// `rename`, `move`, `trash` or `delete`
+    async fn action(&self) -> &str {
+        self.0.action.as_str()
+    }
+
+    async fn original_path(&self) -> String {
+        self.0.original_path.display().to_string()
+    }
+
+    async fn new_path(&self) -> String {
+        self.0.new_path.display().to_string()
+    }
+
+    async fn suggestion(&self) -> &str {
+        &self.0.ai_suggestion
+    }
+
+    async fn category(&self) -> Option<&str> {
+        self.0.category.as_deref()
+    }
+
+    async fn tags(&self) -> &[String] {
+        &self.0.tags
+    }
+
+    async fn undone(&self) -> bool {
+        self.0.undone
+    }
+
+    async fn session_id(&self) -> Option<&str> {
+        self.0.session_id.as_deref()
+    }
+
+    /// The file record this event belongs to, if it was analyzed
+    async fn file(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<FileNode>> {
+        let Some(ref id) = self.0.file_id else {
+            return Ok(None);
+        };
+        Ok(ctx.data::<Database>()?.get_file(id)?.map(FileNode))
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Files matching the given filters
+    #[allow(clippy::too_many_arguments)]
+    async fn files(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        offset: Option<i32>,
+        category: Option<String>,
+        tag: Option<String>,
+        min_confidence: Option<f64>,
+        max_confidence: Option<f64>,
+        #[graphql(default)] sort: FileOrder,
+        #[graphql(default)] ascending: bool,
+    ) -> async_graphql::Result<FilePage> {
+        let filter = FileFilter {
+            category,
+            tag,
+            min_confidence,
+            max_confidence,
+            sort: sort.into(),
+            ascending,
+            ..Default::default()
+        };
+        files_page(ctx, &filter, first, offset)
+    }
+
+    async fn file(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<FileNode>> {
+        Ok(ctx.data::<Database>()?.get_file(&id)?.map(FileNode))
+    }
+
+    /// Files whose suggested name or path contains `query`
+    async fn search(&self, ctx: &Context<'_>, query: String, first: Option<i32>) -> async_graphql::Result<Vec<FileNode>> {
+        let (limit, _) = page(first, None);
+        let files = ctx.data::<Database>()?.search_files(&query, limit)?;
+        Ok(files.into_iter().map(FileNode).collect())
+    }
+
+    /// Tags in use, most used first
+    async fn tags(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<TagNode>> {
+        let tags = ctx.data::<Database>()?.get_tag_counts()?;
+        Ok(tags.into_iter().map(|(name, _)| TagNode { name }).collect())
+    }
+
+    async fn tag(&self, ctx: &Context<'_>, name: String) -> async_graphql::Result<Option<TagNode>> {
+        let exists = ctx.data::<Database>()?.tag_exists(&name)?;
+        Ok(exists.then_some(TagNode { name }))
+    }
+
+    async fn categories(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<CategoryNode>> {
+        let stats = ctx.data::<Database>()?.get_category_stats()?;
+        Ok(stats.into_iter().map(|(name, file_count)| CategoryNode { name, file_count }).collect())
+    }
+
+    /// Renames and moves, newest first
+    async fn history(&self, ctx: &Context<'_>, first: Option<i32>, offset: Option<i32>) -> async_graphql::Result<HistoryPage> {
+        let (limit, offset) = page(first, offset);
+        let history = History::new(ctx.data::<Database>()?.clone());
+        let (entries, total) = history.get_page(limit, offset)?;
+        Ok(HistoryPage {
+            total,
+            items: entries.into_iter().map(HistoryNode).collect(),
+        })
+    }
+
+    async fn stats(&self, ctx: &Context<'_>) -> async_graphql::Result<Stats> {
+        let stats = ctx.data::<Database>()?.get_stats()?;
+        Ok(Stats {
+            file_count: stats.file_count,
+            tag_count: stats.tag_count,
+            category_count: stats.category_count,
+        })
+    }
+}
@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Remote ingest: other machines submit files for analysis
+//!
+//! `POST /api/ingest` takes either the file itself (multipart field `file`) or,
+//! so the contents never leave the client, a JSON body with the file name and
+//! text extracted from it. The analysis is recorded under the bare file name,
+//! marked remote with the client's `path` in its metadata as `client_path`,
+//! so nothing here treats it as a local file. The reply carries the file name
+//! to use, so the client renames its own copy; nothing is renamed or queued
+//! for review here. Agents (see [`crate::agent`]) forward their files this way.
+//!
+//! ```json
+//! { "filename": "scan.pdf", "text": "Invoice #1042 ...", "path": "laptop:~/Downloads/scan.pdf" }
+//! ```
+
+use axum::{
+    extract::{FromRequest, Multipart, Request, State},
+    http::{header, StatusCode},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::agents::requesting_agent;
+use super::auth::Actor;
+use super::{analyze_error, analyze_upload, AppState};
+use crate::analyzers::{document, AnalysisResult};
+use crate::renamer::{disposition, final_name, Disposition};
+
+type IngestReply = (StatusCode, Json<serde_json::Value>);
+
+/// Metadata-only submission
+#[derive(Deserialize)]
+struct TextIngest {
+    /// Original file name; its extension is kept
+    filename: String,
+    /// Text extracted from the file
+    text: String,
+    /// Where the file lives on the client, kept in the record's metadata as `client_path`
+    path: Option<String>,
+    /// Hash of the file's contents, for matching it later (blake3 of `text` otherwise)
+    file_hash: Option<String>,
+    /// Anything else the client knows about the file
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct IngestResponse {
+    /// The submitted file name
+    filename: String,
+    /// What to rename it to: the suggestion with naming rules and extension applied
+    suggested_filename: String,
+    /// Whether the suggestion is confident enough to apply without asking
+    disposition: Disposition,
+    analyzer: &'static str,
+    file_id: String,
+    #[serde(flatten)]
+    result: AnalysisResult,
+}
+
+/// Only the final component, so a crafted name can't escape a directory
+fn base_name(name: &str) -> Option<String> {
+    Path::new(name).file_name().and_then(|n| n.to_str()).map(str::to_string)
+}
+
+/// Analyze a file from another machine and suggest its new name
+pub async fn api_ingest(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    request: Request,
+) -> Result<Json<IngestResponse>, IngestReply> {
+    let is_multipart = request.headers().get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+
+    let (filename, client_path, analyzer, mut result) = if is_multipart {
+        let multipart = Multipart::from_request(request, &state).await
+            .map_err(|e| analyze_error(e.status(), e.body_text()))?;
+        ingest_file(&state, multipart).await?
+    } else {
+        let Json(submission) = Json::<TextIngest>::from_request(request, &state).await
+            .map_err(|e| analyze_error(e.status(), e.body_text()))?;
+        ingest_text(&state, submission).await?
+    };
+
+    // The file isn't here, so its path is only kept for reference: recording
+    // it as the path would have previews and the like read whatever is there
+    if !result.metadata.is_object() {
+        result.metadata = serde_json::json!({});
+    }
+    if let Some(extra) = result.metadata.as_object_mut() {
+        extra.insert("source".to_string(), "ingest".into());
+        extra.insert("remote".to_string(), true.into());
+        if let Some(client_path) = client_path {
+            extra.insert("client_path".to_string(), client_path.into());
+        }
+    }
+    let file_id = state.db.record_analysis(Path::new(&filename), &result)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let config = state.config();
+    let name = final_name(&result, Path::new(&filename), &config);
+    let suggested_filename = match Path::new(&filename).extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}", name, ext),
+        None => name,
+    };
+    if let Some(agent) = requesting_agent(&actor) {
+        if let Err(e) = state.db.touch_agent(agent) {
+            tracing::warn!("Failed to note agent {} forwarding a file: {}", agent, e);
+        }
+    }
+    state.audit(&actor, "ingest", Some(&file_id), serde_json::json!({
+        "filename": filename,
+        "suggested_filename": suggested_filename,
+    }));
+
+    Ok(Json(IngestResponse {
+        filename,
+        suggested_filename,
+        disposition: disposition(result.confidence, &config),
+        analyzer,
+        file_id,
+        result,
+    }))
+}
+
+/// Run the analyzers on an uploaded file (field `file`, with an optional `path` field)
+async fn ingest_file(
+    state: &Arc<AppState>,
+    mut multipart: Multipart,
+) -> Result<(String, Option<String>, &'static str, AnalysisResult), IngestReply> {
+    let mut upload = None;
+    let mut client_path = None;
+    while let Some(field) = multipart.next_field().await
+        .map_err(|e| analyze_error(StatusCode::BAD_REQUEST, e))?
+    {
+        match field.name() {
+            Some("file") => {
+                let filename = field.file_name().and_then(base_name).unwrap_or_else(|| "upload".to_string());
+                let bytes = field.bytes().await.map_err(|e| analyze_error(StatusCode::BAD_REQUEST, e))?;
+                upload = Some((filename, bytes));
+            }
+            Some("path") => {
+                client_path = Some(field.text().await.map_err(|e| analyze_error(StatusCode::BAD_REQUEST, e))?);
+            }
+            _ => {}
+        }
+    }
+    let (filename, bytes) = upload.ok_or_else(|| analyze_error(StatusCode::BAD_REQUEST, "Missing `file` field"))?;
+
+    // Analyzers dispatch on the extension, so keep the original name in a private directory
+    let dir = std::env::temp_dir().join(format!("panoptes-ingest-{}", uuid::Uuid::new_v4()));
+    let path = dir.join(&filename);
+    let outcome = async {
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(&path, &bytes).await?;
+        analyze_upload(state, &path, None).await
+    }.await;
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+
+    let (analyzer, result, _) = outcome.map_err(|e| match e {
+        crate::PanoptesError::UnsupportedFileType(_) => analyze_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, e),
+        e => analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    })?;
+    Ok((filename, client_path, analyzer, result))
+}
+
+/// Suggest a name from text the client extracted, as the document analyzer would
+async fn ingest_text(
+    state: &AppState,
+    submission: TextIngest,
+) -> Result<(String, Option<String>, &'static str, AnalysisResult), IngestReply> {
+    let filename = base_name(&submission.filename)
+        .ok_or_else(|| analyze_error(StatusCode::BAD_REQUEST, "`filename` must name a file"))?;
+    if submission.text.trim().is_empty() {
+        return Err(analyze_error(StatusCode::BAD_REQUEST, "`text` must not be empty"));
+    }
+
+    let file = Path::new(&filename);
+    let stem = file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let extension = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let file_hash = submission.file_hash
+        .unwrap_or_else(|| blake3::hash(submission.text.as_bytes()).to_hex().to_string());
+    let mut result = document::analyze_text(&submission.text, stem, extension, file_hash, &state.config()).await;
+
+    if let (Some(ours), serde_json::Value::Object(theirs)) = (result.metadata.as_object_mut(), submission.metadata) {
+        for (key, value) in theirs {
+            ours.entry(key).or_insert(value);
+        }
+    }
+    Ok((filename, submission.path, "text", result))
+}
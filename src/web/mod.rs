@@ -3,88 +3,339 @@
 
 //! Web UI for Panoptes dashboard
 
+pub mod admin;
+pub mod agents;
+pub mod auth;
+pub mod bulk;
+pub mod graphql;
+pub mod ingest;
+pub mod oidc;
+pub mod pages;
+pub mod queue;
+pub mod search;
+pub mod security;
+pub mod tags;
+pub mod templates;
+pub mod tls;
+
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Json},
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Extension, Multipart, Path, Query, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post, put},
     Router,
 };
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tower_http::cors::CorsLayer;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tower::ServiceExt;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tracing::info;
 
-use crate::db::{Database, FileRecord, Tag};
-use crate::config::AppConfig;
+use crate::analyzers::{clean_filename, AnalysisResult, AnalyzerRegistry};
+use crate::db::{Database, FileFilter, FileRecord, FileSort, ReviewStatus, Role, Tag};
+use crate::config::{layers, save_json, AppConfig};
+use crate::feedback::{self, Correction, CorrectionRate, CorrectionSource};
+use crate::discard::{Deletion, Discard};
+use crate::history::{changed_since_rename, revert_with, History, HistoryEntry, UndoConflict, UndoOutcome};
+use crate::live::LiveStatus;
+use crate::renamer::{is_quarantined, release_dir, release_file, rename_file, suggested_path};
+use crate::sidecar;
+use crate::similarity::{self, Match};
+use crate::thumbnails::ThumbnailCache;
+use crate::webhooks::{self, WebhookEvent, Webhooks};
+use crate::xattrs;
+use crate::PanoptesError;
+use auth::Actor;
 
 /// Shared application state
 pub struct AppState {
     pub db: Database,
-    pub config: AppConfig,
+    config: RwLock<Arc<AppConfig>>,
+    /// Where configuration changes are saved
+    pub config_path: PathBuf,
+    /// Prefix for links and redirects (`web.base_path`, fixed at startup)
+    pub base_path: String,
+    pub registry: AnalyzerRegistry,
+    pub thumbnails: ThumbnailCache,
+    pub graphql: graphql::PanoptesSchema,
+    pub templates: templates::Templates,
+    /// Background bulk operations
+    pub jobs: bulk::Jobs,
+    pub webhooks: Webhooks,
+    /// OIDC login, when configured
+    pub oidc: Option<oidc::OidcClient>,
+    /// The watcher's status, when it runs in this process (`panoptes serve`)
+    pub live: Option<Arc<LiveStatus>>,
+}
+
+impl AppState {
+    pub fn new(config: AppConfig, config_path: PathBuf, db: Database) -> Self {
+        let registry = AnalyzerRegistry::new(&config);
+        registry.load_corrections(&db);
+        Self {
+            graphql: graphql::schema(db.clone()),
+            jobs: bulk::Jobs::default(),
+            webhooks: Webhooks::new(db.clone()),
+            templates: templates::Templates::new(&config.web.templates_dir, config.web.base_path()),
+            base_path: config.web.base_path().to_string(),
+            db,
+            registry,
+            thumbnails: ThumbnailCache::new(&config.thumbnails),
+            oidc: config.web.auth.oidc.clone().map(oidc::OidcClient::new),
+            config: RwLock::new(Arc::new(config)),
+            config_path,
+            live: None,
+        }
+    }
+
+    /// Show the status of a watcher running in the same process
+    pub fn with_live_status(mut self, live: Arc<LiveStatus>) -> Self {
+        self.live = Some(live);
+        self
+    }
+
+    /// Snapshot of the current configuration
+    pub fn config(&self) -> Arc<AppConfig> {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Modify the configuration, validate it, save it to `config_path` and make it current
+    pub fn update_config(&self, change: impl FnOnce(&mut AppConfig)) -> crate::Result<Arc<AppConfig>> {
+        let mut current = self.config.write().unwrap_or_else(|e| e.into_inner());
+        let mut config = AppConfig::clone(&current);
+        change(&mut config);
+        config.validate()?;
+
+        // A listen address given on the command line stays out of the file unless it was edited
+        let saved = layers::project_file(&config, &current, &self.config_path, &["web.host", "web.port"])?;
+        save_json(&saved, &self.config_path)?;
+        *current = Arc::new(config);
+        Ok(current.clone())
+    }
+
+    /// Record an action in the audit log
+    pub fn audit(&self, actor: &Actor, action: &str, target: Option<&str>, details: serde_json::Value) {
+        if let Err(e) = self.db.record_audit(&actor.identity.audit_name(), action, target, &details) {
+            tracing::warn!("Failed to write audit log: {}", e);
+        }
+    }
 }
 
 /// Create the web application router
 pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
+    // Browsing and searching
+    let viewer = Router::new()
         // Pages
-        .route("/", get(index_page))
-        .route("/files", get(files_page))
-        .route("/tags", get(tags_page))
-        .route("/settings", get(settings_page))
+        .route("/", get(pages::dashboard::page))
+        .route("/files", get(pages::files::page))
+        .route("/search", get(pages::search::page))
+        .route("/tags", get(pages::tags::page))
+        .route("/tags/:name", get(pages::tags::tag_page))
+        .route("/files/:id/similar", get(pages::similar::page))
+        .route("/history", get(pages::history::page))
+        .route("/jobs", get(pages::jobs::page))
+        .route("/review", get(pages::review::page))
+        .route("/settings", get(pages::settings::page))
+        .route("/graphql", get(graphql::graphiql_page))
         // API endpoints
         .route("/api/files", get(api_get_files))
         .route("/api/files/search", get(api_search_files))
+        .route("/api/search", get(search::api_search))
         .route("/api/tags", get(api_get_tags))
         .route("/api/stats", get(api_get_stats))
+        .route("/api/stats/timeseries", get(api_get_timeseries))
+        .route("/api/feedback", get(api_get_feedback))
         .route("/api/categories", get(api_get_categories))
-        .layer(CorsLayer::permissive())
-        .with_state(state)
-}
+        .route("/api/history", get(api_get_history))
+        .route("/api/jobs", get(bulk::api_list_jobs))
+        .route("/api/jobs/:id", get(bulk::api_get_job))
+        .route("/api/queue", get(queue::api_list_queue))
+        .route("/api/queue/:id", get(queue::api_get_queued_job))
+        .route("/api/files/:id/preview", get(api_file_preview))
+        .route("/api/files/:id/thumbnail", get(api_file_thumbnail))
+        .route("/api/files/:id/tags", get(tags::api_get_file_tags))
+        .route("/api/files/:id/similar", get(api_get_similar))
+        .route("/api/review", get(api_get_review))
+        .route("/api/agents/me", get(agents::api_agent_me))
+        .route("/api/graphql", post(graphql::api_graphql));
 
-// === Page Handlers ===
+    // Changing files and their records
+    let editor = Router::new()
+        .route("/api/analyze", post(api_analyze_upload)
+            .layer(DefaultBodyLimit::max(state.config().web.max_upload_mb * 1024 * 1024)))
+        .route("/api/ingest", post(ingest::api_ingest)
+            .layer(DefaultBodyLimit::max(state.config().web.max_upload_mb * 1024 * 1024)))
+        .route("/api/history/undo", post(api_undo_batch))
+        .route("/api/history/:id/undo", post(api_undo_history))
+        .route("/api/files/:id/reanalyze", post(api_reanalyze_file))
+        .route("/api/files/bulk", post(bulk::api_bulk_files))
+        .route("/api/jobs/:id/cancel", post(bulk::api_cancel_job))
+        .route("/api/jobs/:id/retry", post(bulk::api_retry_job))
+        .route("/api/queue/:id/cancel", post(queue::api_cancel_queued_job))
+        .route("/api/queue/:id/retry", post(queue::api_retry_queued_job))
+        .route("/api/review/:id/approve", post(api_approve_review))
+        .route("/api/review/:id/reject", post(api_reject_review))
+        .route("/api/review/:id/edit", post(api_edit_review))
+        .route("/api/files/:id/tags", post(tags::api_add_file_tag))
+        .route("/api/files/:id/tags/:tag", delete(tags::api_remove_file_tag))
+        .route("/api/tags/merge", post(tags::api_merge_tags))
+        .route("/api/tags/:name", put(tags::api_rename_tag))
+        .route("/api/agents/register", post(agents::api_register_agent))
+        .route_layer(middleware::from_fn(|req, next| auth::require_role(Role::Editor, req, next)));
 
-async fn index_page(State(state): State<Arc<AppState>>) -> Html<String> {
-    let recent_files = state.db.get_recent_files(10).unwrap_or_default();
-    let stats = state.db.get_category_stats().unwrap_or_default();
-    let file_count = state.db.get_file_count().unwrap_or(0);
+    // Configuration, watch directories and users
+    let admin = Router::new()
+        .route("/watch", get(pages::watch::page))
+        .route("/api/users", get(admin::api_get_users))
+        .route("/api/users/:username/role", put(admin::api_set_user_role))
+        .route("/api/watch/live", get(admin::api_get_live_status))
+        .route("/api/watch-dirs", get(admin::api_get_watch_dirs)
+            .post(admin::api_add_watch_dir)
+            .put(admin::api_update_watch_dir)
+            .delete(admin::api_remove_watch_dir))
+        .route("/api/audit", get(admin::api_get_audit))
+        .route("/api/agents", get(agents::api_get_agents))
+        .route("/api/agents/:name", delete(agents::api_remove_agent))
+        .route("/api/webhooks/deliveries", get(admin::api_get_webhook_deliveries))
+        .route("/api/settings", get(admin::api_get_settings).put(admin::api_save_settings))
+        .route("/api/settings/preview", post(admin::api_preview_settings))
+        .route_layer(middleware::from_fn(|req, next| auth::require_role(Role::Admin, req, next)));
 
-    Html(render_index(&recent_files, &stats, file_count))
-}
+    let router = viewer
+        .merge(editor)
+        .merge(admin)
+        // Everything above requires authentication, and the CSRF token with a session
+        .route_layer(middleware::from_fn(security::require_csrf))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_auth))
+        .route("/login", get(auth::login_page).post(auth::login))
+        .route("/static/*path", get(templates::static_asset))
+        .route("/logout", post(auth::logout))
+        .route("/auth/oidc/login", get(oidc::oidc_login))
+        .route("/auth/oidc/callback", get(oidc::oidc_callback));
 
-async fn files_page(State(state): State<Arc<AppState>>) -> Html<String> {
-    let files = state.db.get_recent_files(100).unwrap_or_default();
-    Html(render_files_page(&files))
-}
+    let router = router.layer(middleware::from_fn_with_state(state.clone(), security::protect));
+    let router = match cors_layer(&state.config().web.cors_origins) {
+        Some(cors) => router.layer(cors),
+        None => router,
+    };
+    let router = router.with_state(state.clone());
 
-async fn tags_page(State(state): State<Arc<AppState>>) -> Html<String> {
-    let tags = state.db.get_all_tags().unwrap_or_default();
-    Html(render_tags_page(&tags))
+    // Behind a reverse proxy the prefix may or may not have been stripped
+    match state.base_path.as_str() {
+        "" => router,
+        base => {
+            // `nest` only maps `/` to the bare prefix; serve the dashboard with a slash too
+            let dashboard = router.clone().map_request(|mut request: axum::extract::Request| {
+                *request.uri_mut() = axum::http::Uri::from_static("/");
+                request
+            });
+            Router::new()
+                .route_service(&format!("{}/", base), dashboard)
+                .nest(base, router.clone())
+                .merge(router)
+        }
+    }
 }
 
-async fn settings_page(State(state): State<Arc<AppState>>) -> Html<String> {
-    Html(render_settings_page(&state.config))
+/// CORS for the configured origins; without any, browsers only allow same-origin calls
+fn cors_layer(origins: &[String]) -> Option<CorsLayer> {
+    let origins: Vec<HeaderValue> = origins.iter()
+        .filter_map(|o| match HeaderValue::from_str(o) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!("Ignoring invalid CORS origin: {}", o);
+                None
+            }
+        })
+        .collect();
+    if origins.is_empty() {
+        return None;
+    }
+    Some(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]))
 }
 
 // === API Handlers ===
 
+/// Rows per page on the files page
+const FILES_PAGE_SIZE: usize = 50;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
 #[derive(Deserialize)]
 struct FilesQuery {
     limit: Option<usize>,
+    /// Given, the files come in a page with the total count
+    offset: Option<usize>,
+    /// `Uncategorized` for files without one
     category: Option<String>,
+    tag: Option<String>,
+    min_confidence: Option<f64>,
+    max_confidence: Option<f64>,
+    /// First day to include (YYYY-MM-DD)
+    since: Option<NaiveDate>,
+    /// Last day to include (YYYY-MM-DD)
+    until: Option<NaiveDate>,
+    #[serde(default)]
+    sort: FileSort,
+    #[serde(default)]
+    order: SortOrder,
 }
 
+impl FilesQuery {
+    fn filter(&self) -> FileFilter {
+        let non_empty = |s: &Option<String>| s.as_ref().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let category = non_empty(&self.category);
+        let uncategorized = category.as_deref() == Some("Uncategorized");
+        FileFilter {
+            category: category.filter(|_| !uncategorized),
+            uncategorized,
+            tag: non_empty(&self.tag),
+            min_confidence: self.min_confidence,
+            max_confidence: self.max_confidence,
+            since: self.since,
+            until: self.until,
+            sort: self.sort,
+            ascending: self.order == SortOrder::Asc,
+        }
+    }
+}
+
+/// A page of files, answering requests with an `offset`
+#[derive(Serialize)]
+struct FilesResponse {
+    total: i64,
+    limit: usize,
+    offset: usize,
+    files: Vec<FileRecord>,
+}
+
+/// The files matching the query: a bare list, as clients before paging
+/// expect, unless an `offset` asks for a page
 async fn api_get_files(
     State(state): State<Arc<AppState>>,
     Query(query): Query<FilesQuery>,
-) -> Json<Vec<FileRecord>> {
-    let limit = query.limit.unwrap_or(50);
-    let files = if let Some(category) = query.category {
-        state.db.get_files_by_category(&category, limit).unwrap_or_default()
-    } else {
-        state.db.get_recent_files(limit).unwrap_or_default()
-    };
-    Json(files)
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let limit = query.limit.unwrap_or(FILES_PAGE_SIZE);
+    let (files, total) = state.db.query_files(&query.filter(), limit, query.offset.unwrap_or(0))
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(match query.offset {
+        Some(offset) => Json(FilesResponse { total, limit, offset, files }).into_response(),
+        None => Json(files).into_response(),
+    })
 }
 
 #[derive(Deserialize)]
@@ -119,315 +370,801 @@ async fn api_get_stats(State(state): State<Arc<AppState>>) -> Json<StatsResponse
     Json(StatsResponse { total_files, categories })
 }
 
+/// Days covered by the dashboard charts unless asked otherwise
+const TIMESERIES_DAYS: u32 = 30;
+
+/// Number of confidence ranges in the distribution
+const CONFIDENCE_BUCKETS: usize = 10;
+
+#[derive(Deserialize)]
+struct TimeseriesQuery {
+    /// How many days back to go, including today
+    days: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct ConfidenceBucket {
+    min: f64,
+    max: f64,
+    count: i64,
+}
+
+#[derive(Serialize)]
+struct TimeseriesResponse {
+    /// Every day in the range, oldest first (YYYY-MM-DD)
+    days: Vec<NaiveDate>,
+    /// Files processed on each day
+    files: Vec<i64>,
+    /// Files processed on each day, per category
+    categories: BTreeMap<String, Vec<i64>>,
+    /// Confidence of the files processed in the range
+    confidence: Vec<ConfidenceBucket>,
+}
+
+async fn api_get_timeseries(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TimeseriesQuery>,
+) -> Result<Json<TimeseriesResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let span = query.days.unwrap_or(TIMESERIES_DAYS).clamp(1, 366);
+    // Timestamps are stored in UTC
+    let today = chrono::Utc::now().date_naive();
+    let since = today - chrono::Duration::days(span as i64 - 1);
+    let days: Vec<NaiveDate> = since.iter_days().take(span as usize).collect();
+
+    let rows = state.db.get_daily_category_counts(since)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let mut files = vec![0; days.len()];
+    let mut categories: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    for (day, category, count) in rows {
+        let index = day.signed_duration_since(since).num_days() as usize;
+        if index >= days.len() {
+            continue;
+        }
+        files[index] += count;
+        categories.entry(category).or_insert_with(|| vec![0; days.len()])[index] += count;
+    }
+
+    let counts = state.db.get_confidence_histogram(since, CONFIDENCE_BUCKETS)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let bound = |i: usize| i as f64 / CONFIDENCE_BUCKETS as f64;
+    let confidence = counts.into_iter()
+        .enumerate()
+        .map(|(i, count)| ConfidenceBucket { min: bound(i), max: bound(i + 1), count })
+        .collect();
+
+    Ok(Json(TimeseriesResponse { days, files, categories, confidence }))
+}
+
+async fn api_get_similar(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<Match>>, (StatusCode, Json<serde_json::Value>)> {
+    let file = match state.db.get_file(&id) {
+        Ok(Some(file)) => file,
+        Ok(None) => return Err(analyze_error(StatusCode::NOT_FOUND, "No such file")),
+        Err(e) => return Err(analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+    // Fingerprinting the unindexed files and comparing them all is blocking work
+    let (db, config) = (state.db.clone(), state.config());
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || runtime.block_on(similarity::similar_to_record(&db, &file, &config)))
+        .await
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .map(Json)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+#[derive(Deserialize)]
+struct FeedbackQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct FeedbackResponse {
+    /// How often each analyzer and model was corrected, most often first
+    rates: Vec<CorrectionRate>,
+    /// Corrections, newest first
+    corrections: Vec<Correction>,
+}
+
+async fn api_get_feedback(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FeedbackQuery>,
+) -> Result<Json<FeedbackResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let rates = feedback::rates(&state.db)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let corrections = state.db.get_corrections(query.limit.unwrap_or(50).min(500), query.offset.unwrap_or(0))
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(FeedbackResponse { rates, corrections }))
+}
+
 async fn api_get_categories(State(state): State<Arc<AppState>>) -> Json<Vec<(String, i64)>> {
     let stats = state.db.get_category_stats().unwrap_or_default();
     Json(stats)
 }
 
-// === Template Rendering ===
-
-fn base_template(title: &str, content: &str) -> String {
-    format!(r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{} - Panoptes</title>
-    <style>
-        :root {{
-            --bg-primary: #1a1a2e;
-            --bg-secondary: #16213e;
-            --bg-card: #0f3460;
-            --text-primary: #e8e8e8;
-            --text-secondary: #a0a0a0;
-            --accent: #e94560;
-            --accent-hover: #ff6b6b;
-            --success: #00d9a5;
-            --border: #2a2a4a;
-        }}
-        * {{ box-sizing: border-box; margin: 0; padding: 0; }}
-        body {{
-            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
-            background: var(--bg-primary);
-            color: var(--text-primary);
-            line-height: 1.6;
-        }}
-        .container {{ max-width: 1400px; margin: 0 auto; padding: 20px; }}
-        nav {{
-            background: var(--bg-secondary);
-            padding: 15px 20px;
-            display: flex;
-            align-items: center;
-            gap: 30px;
-            border-bottom: 1px solid var(--border);
-        }}
-        nav .logo {{
-            font-size: 1.5em;
-            font-weight: bold;
-            color: var(--accent);
-            text-decoration: none;
-        }}
-        nav a {{
-            color: var(--text-secondary);
-            text-decoration: none;
-            transition: color 0.2s;
-        }}
-        nav a:hover {{ color: var(--text-primary); }}
-        .card {{
-            background: var(--bg-card);
-            border-radius: 12px;
-            padding: 20px;
-            margin-bottom: 20px;
-        }}
-        .card h2 {{
-            margin-bottom: 15px;
-            color: var(--accent);
-        }}
-        .stats-grid {{
-            display: grid;
-            grid-template-columns: repeat(auto-fit, minmax(200px, 1fr));
-            gap: 20px;
-            margin-bottom: 30px;
-        }}
-        .stat-card {{
-            background: var(--bg-card);
-            border-radius: 12px;
-            padding: 20px;
-            text-align: center;
-        }}
-        .stat-card .number {{
-            font-size: 2.5em;
-            font-weight: bold;
-            color: var(--accent);
-        }}
-        .stat-card .label {{
-            color: var(--text-secondary);
-            font-size: 0.9em;
-        }}
-        table {{
-            width: 100%;
-            border-collapse: collapse;
-        }}
-        th, td {{
-            padding: 12px;
-            text-align: left;
-            border-bottom: 1px solid var(--border);
-        }}
-        th {{ color: var(--text-secondary); font-weight: 500; }}
-        tr:hover {{ background: rgba(255,255,255,0.05); }}
-        .tag {{
-            display: inline-block;
-            background: var(--accent);
-            color: white;
-            padding: 2px 8px;
-            border-radius: 12px;
-            font-size: 0.8em;
-            margin: 2px;
-        }}
-        .category-badge {{
-            display: inline-block;
-            background: var(--bg-secondary);
-            border: 1px solid var(--border);
-            padding: 4px 10px;
-            border-radius: 6px;
-            font-size: 0.85em;
-        }}
-        .confidence {{
-            display: inline-block;
-            width: 60px;
-            height: 8px;
-            background: var(--bg-secondary);
-            border-radius: 4px;
-            overflow: hidden;
-        }}
-        .confidence-fill {{
-            height: 100%;
-            background: var(--success);
-            border-radius: 4px;
-        }}
-    </style>
-</head>
-<body>
-    <nav>
-        <a href="/" class="logo">Panoptes</a>
-        <a href="/">Dashboard</a>
-        <a href="/files">Files</a>
-        <a href="/tags">Tags</a>
-        <a href="/settings">Settings</a>
-    </nav>
-    <main class="container">
-        {}
-    </main>
-</body>
-</html>"#, title, content)
-}
-
-fn render_index(files: &[FileRecord], stats: &[(String, i64)], file_count: i64) -> String {
-    let category_count = stats.len();
-
-    let stats_html = format!(r#"
-        <div class="stats-grid">
-            <div class="stat-card">
-                <div class="number">{}</div>
-                <div class="label">Total Files</div>
-            </div>
-            <div class="stat-card">
-                <div class="number">{}</div>
-                <div class="label">Categories</div>
-            </div>
-        </div>
-    "#, file_count, category_count);
-
-    let files_html = render_files_table(files);
-
-    let categories_html: String = stats.iter()
-        .map(|(cat, count)| format!(r#"<tr><td>{}</td><td>{}</td></tr>"#, cat, count))
-        .collect();
+#[derive(Deserialize)]
+struct AnalyzeQuery {
+    /// Save the upload into the configured inbox under its suggested name
+    #[serde(default)]
+    save: bool,
+}
 
-    let content = format!(r#"
-        <h1>Dashboard</h1>
-        {}
-        <div style="display: grid; grid-template-columns: 2fr 1fr; gap: 20px;">
-            <div class="card">
-                <h2>Recent Files</h2>
-                {}
-            </div>
-            <div class="card">
-                <h2>Categories</h2>
-                <table>
-                    <tr><th>Category</th><th>Count</th></tr>
-                    {}
-                </table>
-            </div>
-        </div>
-    "#, stats_html, files_html, categories_html);
-
-    base_template("Dashboard", &content)
-}
-
-fn render_files_table(files: &[FileRecord]) -> String {
-    let rows: String = files.iter()
-        .map(|f| {
-            let confidence_pct = (f.confidence * 100.0) as u32;
-            format!(r#"
-                <tr>
-                    <td>{}</td>
-                    <td><span class="category-badge">{}</span></td>
-                    <td>
-                        <div class="confidence">
-                            <div class="confidence-fill" style="width: {}%"></div>
-                        </div>
-                    </td>
-                    <td>{}</td>
-                </tr>
-            "#,
-            f.suggested_name,
-            f.category.as_deref().unwrap_or("Uncategorized"),
-            confidence_pct,
-            f.created_at.format("%Y-%m-%d %H:%M")
-            )
-        })
-        .collect();
+#[derive(Serialize)]
+struct AnalyzeResponse {
+    filename: String,
+    analyzer: &'static str,
+    #[serde(flatten)]
+    result: AnalysisResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    saved_to: Option<String>,
+}
 
-    format!(r#"
-        <table>
-            <tr>
-                <th>Name</th>
-                <th>Category</th>
-                <th>Confidence</th>
-                <th>Date</th>
-            </tr>
-            {}
-        </table>
-    "#, rows)
-}
-
-fn render_files_page(files: &[FileRecord]) -> String {
-    let content = format!(r#"
-        <h1>Files</h1>
-        <div class="card">
-            {}
-        </div>
-    "#, render_files_table(files));
-
-    base_template("Files", &content)
-}
-
-fn render_tags_page(tags: &[Tag]) -> String {
-    let tags_html: String = tags.iter()
-        .map(|t| format!(r#"<span class="tag">{}</span>"#, t.name))
-        .collect();
+fn analyze_error(code: StatusCode, message: impl std::fmt::Display) -> (StatusCode, Json<serde_json::Value>) {
+    (code, Json(serde_json::json!({ "error": message.to_string() })))
+}
 
-    let content = format!(r#"
-        <h1>Tags</h1>
-        <div class="card">
-            <p>All tags in the database:</p>
-            <div style="margin-top: 20px;">
-                {}
-            </div>
-        </div>
-    "#, if tags_html.is_empty() { "No tags yet".to_string() } else { tags_html });
+/// Analyze an uploaded file (multipart field `file`) and return the suggestion
+async fn api_analyze_upload(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Query(query): Query<AnalyzeQuery>,
+    mut multipart: Multipart,
+) -> std::result::Result<Json<AnalyzeResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let inbox = match (&state.config().web.inbox, query.save) {
+        (Some(inbox), true) => Some(std::path::PathBuf::from(inbox)),
+        (None, true) => return Err(analyze_error(StatusCode::BAD_REQUEST, "No inbox directory configured")),
+        (_, false) => None,
+    };
 
-    base_template("Tags", &content)
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await
+        .map_err(|e| analyze_error(StatusCode::BAD_REQUEST, e))?
+    {
+        if field.name() == Some("file") {
+            // Only the final component, so a crafted name can't escape the upload directory
+            let filename = field.file_name()
+                .and_then(|n| std::path::Path::new(n).file_name())
+                .and_then(|n| n.to_str())
+                .unwrap_or("upload")
+                .to_string();
+            let bytes = field.bytes().await.map_err(|e| analyze_error(StatusCode::BAD_REQUEST, e))?;
+            upload = Some((filename, bytes));
+            break;
+        }
+    }
+    let (filename, bytes) = upload.ok_or_else(|| analyze_error(StatusCode::BAD_REQUEST, "Missing `file` field"))?;
+
+    // Analyzers dispatch on the extension, so keep the original name in a private directory
+    let dir = std::env::temp_dir().join(format!("panoptes-upload-{}", uuid::Uuid::new_v4()));
+    let path = dir.join(&filename);
+    let outcome = async {
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(&path, &bytes).await?;
+        analyze_upload(&state, &path, inbox.as_deref()).await
+    }.await;
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+
+    let (analyzer, result, saved_to) = outcome.map_err(|e| match e {
+        crate::PanoptesError::UnsupportedFileType(_) => analyze_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, e),
+        e => analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    })?;
+    if let Some(ref saved) = saved_to {
+        state.audit(&actor, "upload.save", Some(&saved.display().to_string()),
+            serde_json::json!({ "filename": filename, "suggested_name": result.suggested_name }));
+    }
+
+    Ok(Json(AnalyzeResponse {
+        filename,
+        analyzer,
+        result,
+        saved_to: saved_to.map(|p| p.display().to_string()),
+    }))
 }
 
-fn render_settings_page(config: &AppConfig) -> String {
-    let watch_paths: String = config.watch_paths.iter()
-        .map(|p| format!("<li>{}</li>", p))
-        .collect();
+/// Analyze `path` with `config` on a blocking thread: analyzers read and hash
+/// whole files, which on the runtime's own threads would hold up every other
+/// request. Returns the analyzer's name with its result.
+async fn analyze_blocking(
+    state: &Arc<AppState>,
+    path: &std::path::Path,
+    config: Arc<AppConfig>,
+) -> crate::Result<(&'static str, AnalysisResult)> {
+    let (state, owned) = (state.clone(), path.to_path_buf());
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let analyzer = state.registry.find_analyzer(&owned)
+            .ok_or_else(|| PanoptesError::UnsupportedFileType(
+                owned.file_name().unwrap_or_default().to_string_lossy().to_string()
+            ))?;
+        let result = runtime.block_on(analyzer.analyze(&owned, &config))?;
+        Ok((analyzer.name(), result))
+    })
+    .await
+    .map_err(|e| PanoptesError::Analysis(format!("Analysis of {} stopped: {}", path.display(), e)))?
+}
+
+/// Run the analyzer pipeline on an upload, optionally moving it into `inbox`
+async fn analyze_upload(
+    state: &Arc<AppState>,
+    path: &std::path::Path,
+    inbox: Option<&std::path::Path>,
+) -> crate::Result<(&'static str, AnalysisResult, Option<std::path::PathBuf>)> {
+    let (analyzer, result) = analyze_blocking(state, path, state.config()).await?;
+
+    let Some(inbox) = inbox else {
+        return Ok((analyzer, result, None));
+    };
+
+    tokio::fs::create_dir_all(inbox).await?;
+    let name = path.file_name().unwrap_or_default();
+    let config = state.config();
+    let destination = suggested_path(&result, path, &inbox.join(name), &config)?
+        .ok_or_else(|| crate::PanoptesError::FileSystem(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} is taken in {}", name.to_string_lossy(), inbox.display()),
+        )))?;
+    // The temp directory may be on another filesystem, so copy rather than rename
+    crate::skiplist::remember(&destination);
+    tokio::fs::copy(path, &destination).await?;
+    let file_id = state.db.record_analysis(&destination, &result)?;
+    sidecar::write_or_warn(&destination, &result, Some(&file_id), &config);
+    xattrs::tag_or_warn(&state.db, &file_id, &config);
+    info!("Saved upload to {:?}", destination);
+
+    Ok((analyzer, result, Some(destination)))
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    total: i64,
+    limit: usize,
+    offset: usize,
+    entries: Vec<HistoryEntry>,
+}
+
+async fn api_get_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Json<HistoryResponse> {
+    let limit = query.limit.unwrap_or(50);
+    let offset = query.offset.unwrap_or(0);
+    let history = History::new(state.db.clone());
+    let (entries, total) = history.get_page(limit, offset).unwrap_or_default();
+    Json(HistoryResponse { total, limit, offset, entries })
+}
+
+#[derive(Deserialize)]
+struct UndoQuery {
+    on_conflict: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UndoResponse {
+    id: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restored_to: Option<String>,
+    /// The file's content changed after it was renamed
+    modified: bool,
+    message: String,
+}
+
+impl UndoResponse {
+    fn new(id: &str, status: &'static str, message: impl Into<String>) -> Self {
+        Self { id: id.to_string(), status, restored_to: None, modified: false, message: message.into() }
+    }
+}
+
+impl UndoQuery {
+    fn strategy(&self, config: &AppConfig) -> crate::Result<UndoConflict> {
+        let strategy = match self.on_conflict.as_deref() {
+            None => config.history.undo_conflict,
+            Some(value) => value.parse::<UndoConflict>()?,
+        };
+        // There is nobody to ask over HTTP, so a prompt means skip
+        Ok(if strategy == UndoConflict::Prompt { UndoConflict::Skip } else { strategy })
+    }
+}
+
+async fn api_undo_history(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+    Query(query): Query<UndoQuery>,
+) -> (StatusCode, Json<UndoResponse>) {
+    let strategy = match query.strategy(&state.config()) {
+        Ok(strategy) => strategy,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(UndoResponse::new(&id, "error", e.to_string()))),
+    };
 
-    let content = format!(r#"
-        <h1>Settings</h1>
-        <div class="card">
-            <h2>Watch Directories</h2>
-            <ul>{}</ul>
-        </div>
-        <div class="card">
-            <h2>AI Configuration</h2>
-            <table>
-                <tr><td>Vision Model</td><td>{}</td></tr>
-                <tr><td>Text Model</td><td>{}</td></tr>
-                <tr><td>Code Model</td><td>{}</td></tr>
-                <tr><td>API URL</td><td>{}</td></tr>
-            </table>
-        </div>
-        <div class="card">
-            <h2>Rules</h2>
-            <table>
-                <tr><td>Date Prefix</td><td>{}</td></tr>
-                <tr><td>Max Length</td><td>{}</td></tr>
-                <tr><td>Auto Categorize</td><td>{}</td></tr>
-            </table>
-        </div>
-    "#,
-        watch_paths,
-        config.ai_engine.models.vision,
-        config.ai_engine.models.text,
-        config.ai_engine.models.code,
-        config.ai_engine.url,
-        config.rules.date_prefix,
-        config.rules.max_length,
-        config.rules.auto_categorize,
-    );
-
-    base_template("Settings", &content)
-}
-
-/// Start the web server with config and database
-pub async fn start_server(config: AppConfig, db: Database) -> crate::Result<()> {
-    let state = Arc::new(AppState {
-        db,
-        config: config.clone(),
+    let history = History::new(state.db.clone());
+    let entry = match history.find_by_id(&id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(UndoResponse::new(&id, "not_found", "No such history entry"))),
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(UndoResponse::new(&id, "error", e.to_string()))),
+    };
+
+    // Moving files and hashing them to see if they changed is blocking work
+    let undone = tokio::task::spawn_blocking(move || undo_entry(&state, &actor, &history, &entry, strategy)).await;
+    let (code, response) = undone.unwrap_or_else(|e| {
+        (StatusCode::INTERNAL_SERVER_ERROR, UndoResponse::new(&id, "error", e.to_string()))
     });
+    (code, Json(response))
+}
 
-    let addr = format!("{}:{}", config.web.host, config.web.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+/// Revert one history entry, recording it as undone
+fn undo_entry(
+    state: &AppState,
+    actor: &Actor,
+    history: &History,
+    entry: &HistoryEntry,
+    strategy: UndoConflict,
+) -> (StatusCode, UndoResponse) {
+    if entry.undone {
+        return (StatusCode::CONFLICT, UndoResponse::new(&entry.id, "already_undone", "Entry was already undone"));
+    }
+    if entry.action.is_removal() {
+        return (StatusCode::BAD_REQUEST, UndoResponse::new(&entry.id, "not_undoable", "Entry records a deletion"));
+    }
+
+    let modified = changed_since_rename(entry).unwrap_or(false);
+    // Files overwritten go to the trash
+    let discard = Discard::new(history.clone(), Deletion::Trash);
+    let (code, mut response) = match revert_with(entry, strategy, &discard) {
+        Ok(UndoOutcome::Reverted(restored)) => {
+            if let Err(e) = history.mark_undone(&entry.id, &restored) {
+                return (StatusCode::INTERNAL_SERVER_ERROR, UndoResponse::new(&entry.id, "error", e.to_string()));
+            }
+            state.audit(actor, "history.undo", Some(&entry.id), serde_json::json!({
+                "from": entry.new_path, "to": restored,
+            }));
+            let mut response = UndoResponse::new(&entry.id, "reverted",
+                format!("{} -> {}", entry.new_path.display(), restored.display()));
+            response.restored_to = Some(restored.display().to_string());
+            (StatusCode::OK, response)
+        }
+        Ok(UndoOutcome::Missing) => (StatusCode::GONE, UndoResponse::new(&entry.id, "missing",
+            format!("{} no longer exists", entry.new_path.display()))),
+        Ok(UndoOutcome::Conflict) => (StatusCode::CONFLICT, UndoResponse::new(&entry.id, "conflict",
+            format!("{} is occupied", entry.original_path.display()))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, UndoResponse::new(&entry.id, "error", e.to_string())),
+    };
+    response.modified = modified;
+    (code, response)
+}
+
+#[derive(Deserialize)]
+struct BatchUndoRequest {
+    /// Entries to undo
+    #[serde(default)]
+    ids: Vec<String>,
+    /// Undo every remaining rename from this batch/session
+    session_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchUndoResponse {
+    reverted: usize,
+    results: Vec<UndoResponse>,
+}
+
+/// Undo several entries, newest first so chained renames unwind in order
+async fn api_undo_batch(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Query(query): Query<UndoQuery>,
+    Json(request): Json<BatchUndoRequest>,
+) -> Result<Json<BatchUndoResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let strategy = query.strategy(&state.config())
+        .map_err(|e| analyze_error(StatusCode::BAD_REQUEST, e))?;
+    let history = History::new(state.db.clone());
+
+    let mut entries = match request.session_id {
+        Some(ref session) => history.get_session(session).map_err(|e| match e {
+            // An ambiguous prefix
+            PanoptesError::Config(_) => analyze_error(StatusCode::BAD_REQUEST, e),
+            e => analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+        })?,
+        None => Vec::new(),
+    };
+    let mut results = Vec::new();
+    for id in &request.ids {
+        match history.find_by_id(id) {
+            Ok(Some(entry)) if !entries.iter().any(|e| e.id == entry.id) => entries.push(entry),
+            Ok(Some(_)) => {}
+            Ok(None) => results.push(UndoResponse::new(id, "not_found", "No such history entry")),
+            Err(e) => results.push(UndoResponse::new(id, "error", e.to_string())),
+        }
+    }
+    if entries.is_empty() && results.is_empty() {
+        return Err(analyze_error(StatusCode::BAD_REQUEST, "Nothing to undo"));
+    }
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+    let undone = tokio::task::spawn_blocking(move || {
+        entries.iter().map(|entry| undo_entry(&state, &actor, &history, entry, strategy).1).collect::<Vec<_>>()
+    }).await.map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    results.extend(undone);
+    let reverted = results.iter().filter(|r| r.status == "reverted").count();
+    Ok(Json(BatchUndoResponse { reverted, results }))
+}
+
+/// Image types the review page can preview inline
+const PREVIEW_TYPES: &[(&str, &str)] = &[
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("bmp", "image/bmp"),
+    ("svg", "image/svg+xml"),
+    ("avif", "image/avif"),
+];
+
+fn preview_type(path: &str) -> Option<&'static str> {
+    let ext = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+    PREVIEW_TYPES.iter().find(|(e, _)| *e == ext).map(|(_, mime)| *mime)
+}
 
-    info!("Web UI available at https://{}", addr);
+/// Serve a recorded file's content for preview (images only)
+async fn api_file_preview(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<([(header::HeaderName, &'static str); 1], Vec<u8>), StatusCode> {
+    // Off the runtime's threads, as images can be large
+    tokio::task::spawn_blocking(move || {
+        let file = state.db.get_file(&id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter(|file| !file.is_remote())
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let mime = preview_type(&file.new_path).ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?;
+        let bytes = std::fs::read(&file.new_path).map_err(|_| StatusCode::NOT_FOUND)?;
+        Ok(([(header::CONTENT_TYPE, mime)], bytes))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// Serve a cached thumbnail for a recorded file, generating it on first request
+async fn api_file_thumbnail(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<([(header::HeaderName, &'static str); 2], Vec<u8>), StatusCode> {
+    let file = state.db.get_file(&id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter(|file| !file.is_remote())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let path = std::path::PathBuf::from(&file.new_path);
+    if !ThumbnailCache::supports(&path) {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+    if !path.exists() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let cache = state.thumbnails.clone();
+    let thumbnail = tokio::task::spawn_blocking(move || cache.get_or_create(&path, &file.file_hash))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            tracing::warn!("Thumbnail generation failed for {}: {}", id, e);
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let bytes = tokio::fs::read(&thumbnail).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok(([(header::CONTENT_TYPE, "image/jpeg"), (header::CACHE_CONTROL, "private, max-age=86400")], bytes))
+}
+
+#[derive(Deserialize, Default)]
+struct ReanalyzeRequest {
+    /// Model to use instead of the configured one
+    model: Option<String>,
+    /// Prompt to use instead of the configured one
+    prompt: Option<String>,
+}
+
+/// Run analysis again on a file's current path and store the new suggestion
+async fn reanalyze(
+    state: &Arc<AppState>,
+    file: &FileRecord,
+    config: &AppConfig,
+) -> std::result::Result<AnalysisResult, (StatusCode, Json<serde_json::Value>)> {
+    if file.is_remote() {
+        return Err(analyze_error(StatusCode::CONFLICT, "File is on another machine"));
+    }
+    let path = std::path::Path::new(&file.new_path);
+    if !path.exists() {
+        return Err(analyze_error(StatusCode::GONE, format!("{} no longer exists", file.new_path)));
+    }
+    let (_, result) = analyze_blocking(state, path, Arc::new(config.clone())).await.map_err(|e| match e {
+        PanoptesError::UnsupportedFileType(_) => analyze_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, "No analyzer for this file type"),
+        e => analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    })?;
+    info!("Re-analyzed {:?}: {} ({:.0}%)", path, result.suggested_name, result.confidence * 100.0);
+
+    state.db.update_analysis(&file.id, &result)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    sidecar::write_or_warn(path, &result, Some(&file.id), config);
+    xattrs::tag_or_warn(&state.db, &file.id, config);
+    Ok(result)
+}
+
+/// Re-run analysis on a file's current path and update its record
+async fn api_reanalyze_file(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+    body: Option<Json<ReanalyzeRequest>>,
+) -> std::result::Result<Json<FileRecord>, (StatusCode, Json<serde_json::Value>)> {
+    let request = body.map(|Json(r)| r).unwrap_or_default();
+    let file = state.db.get_file(&id)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or_else(|| analyze_error(StatusCode::NOT_FOUND, "No such file"))?;
+
+    let config = state.config().with_overrides(request.model.as_deref(), request.prompt.as_deref());
+    let result = reanalyze(&state, &file, &config).await?;
+    state.audit(&actor, "file.reanalyze", Some(&id), serde_json::json!({
+        "model": request.model, "suggested_name": result.suggested_name,
+    }));
+    let updated = state.db.get_file(&id)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or_else(|| analyze_error(StatusCode::NOT_FOUND, "No such file"))?;
+    Ok(Json(updated))
+}
+
+#[derive(Deserialize)]
+struct ReviewQuery {
+    limit: Option<usize>,
+}
+
+async fn api_get_review(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReviewQuery>,
+) -> Json<Vec<FileRecord>> {
+    let limit = query.limit.unwrap_or(50);
+    let files = state.db.get_files_by_status(ReviewStatus::Pending, limit).unwrap_or_default();
+    Json(files)
+}
 
+#[derive(Deserialize, Default)]
+struct ReviewRequest {
+    /// Name to use instead of the suggestion
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ReviewResponse {
+    id: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_path: Option<String>,
+    message: String,
+}
+
+impl ReviewResponse {
+    fn new(id: &str, status: &'static str, message: impl Into<String>) -> Self {
+        Self { id: id.to_string(), status, new_path: None, message: message.into() }
+    }
+}
+
+type ReviewReply = (StatusCode, Json<ReviewResponse>);
+
+fn review_error(id: &str, code: StatusCode, message: impl Into<String>) -> ReviewReply {
+    (code, Json(ReviewResponse::new(id, "error", message)))
+}
+
+/// Look up a file that is still waiting in the review queue
+fn pending_file(state: &AppState, id: &str) -> std::result::Result<FileRecord, ReviewReply> {
+    match state.db.get_file(id) {
+        Ok(Some(file)) if file.status == Some(ReviewStatus::Pending) => Ok(file),
+        Ok(Some(_)) => Err(review_error(id, StatusCode::CONFLICT, "File is not awaiting review")),
+        Ok(None) => Err(review_error(id, StatusCode::NOT_FOUND, "No such file")),
+        Err(e) => Err(review_error(id, StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Record a user-chosen name if it differs from the suggestion; returns the name to apply
+fn apply_correction(state: &AppState, file: &FileRecord, name: Option<&str>) -> std::result::Result<String, ReviewReply> {
+    let config = state.config();
+    let name = match name.map(|name| clean_filename(name, &config.rules.sanitizer)) {
+        Some(name) if name.is_empty() => {
+            return Err(review_error(&file.id, StatusCode::BAD_REQUEST, "Name is empty after cleaning"));
+        }
+        Some(name) => name,
+        None => return Ok(file.corrected_name.clone().unwrap_or_else(|| file.suggested_name.clone())),
+    };
+
+    feedback::record(&state.db, &file.id, &name, CorrectionSource::Review)
+        .map_err(|e| review_error(&file.id, StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(name)
+}
+
+async fn api_approve_review(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+    body: Option<Json<ReviewRequest>>,
+) -> ReviewReply {
+    let request = body.map(|Json(r)| r).unwrap_or_default();
+    let file = match pending_file(&state, &id) {
+        Ok(file) => file,
+        Err(reply) => return reply,
+    };
+    let name = match apply_correction(&state, &file, request.name.as_deref()) {
+        Ok(name) => name,
+        Err(reply) => return reply,
+    };
+
+    let path = std::path::Path::new(&file.new_path);
+    if !path.exists() {
+        return review_error(&id, StatusCode::GONE, format!("{} no longer exists", file.new_path));
+    }
+
+    let result = AnalysisResult {
+        suggested_name: name,
+        confidence: file.confidence,
+        category: file.category.clone(),
+        tags: state.db.get_file_tags(&id).unwrap_or_default(),
+        file_hash: file.file_hash.clone(),
+        metadata: file.metadata.clone(),
+        analyzer: file.analyzer.clone(),
+        model: file.model.clone(),
+    };
+    let history = History::new(state.db.clone());
+    let config = state.config();
+    // Approved files leave quarantine for where they came from
+    let destination = if is_quarantined(path, &config) {
+        Some(release_dir(std::path::Path::new(&file.original_path), &config))
+    } else {
+        config.watch_destination(path)
+    };
+    // Moving the file, across devices a copy that is hashed, is blocking work
+    let renamed = {
+        let (path, result, id) = (path.to_path_buf(), result.clone(), id.clone());
+        tokio::task::spawn_blocking(move || {
+            rename_file(&path, destination.as_deref(), &result, &config, &history, None, Some(&id))
+        })
+        .await
+    };
+    let new_path = match renamed {
+        Ok(Ok(new_path)) => new_path,
+        Ok(Err(e)) => return review_error(&id, StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        Err(e) => return review_error(&id, StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if let Err(e) = state.db.set_review_status(&id, ReviewStatus::Approved) {
+        return review_error(&id, StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    state.webhooks.emit(&state.config().webhooks, WebhookEvent::Renamed,
+        webhooks::renamed(Some(&id), path, &new_path, &result));
+    state.audit(&actor, "review.approve", Some(&id), serde_json::json!({
+        "suggested_name": file.suggested_name, "new_path": new_path,
+    }));
+
+    let mut response = ReviewResponse::new(&id, "approved",
+        format!("{} -> {}", file.new_path, new_path.display()));
+    response.new_path = Some(new_path.display().to_string());
+    (StatusCode::OK, Json(response))
+}
+
+async fn api_reject_review(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+) -> ReviewReply {
+    let file = match pending_file(&state, &id) {
+        Ok(file) => file,
+        Err(reply) => return reply,
+    };
+    // Rejected files go back under their own name
+    let path = std::path::Path::new(&file.new_path);
+    if is_quarantined(path, &state.config()) && path.exists() {
+        let original_dir = std::path::Path::new(&file.original_path).parent().unwrap_or(std::path::Path::new(""));
+        let history = History::new(state.db.clone());
+        if let Err(e) = release_file(path, original_dir, &file.file_hash, &history, Some(&id)) {
+            return review_error(&id, StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        }
+    }
+    if let Err(e) = state.db.set_review_status(&id, ReviewStatus::Rejected) {
+        return review_error(&id, StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    state.audit(&actor, "review.reject", Some(&id), serde_json::json!({ "suggested_name": file.suggested_name }));
+    (StatusCode::OK, Json(ReviewResponse::new(&id, "rejected", "Suggestion rejected")))
+}
+
+async fn api_edit_review(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+    Json(request): Json<ReviewRequest>,
+) -> ReviewReply {
+    let file = match pending_file(&state, &id) {
+        Ok(file) => file,
+        Err(reply) => return reply,
+    };
+    if request.name.is_none() {
+        return review_error(&id, StatusCode::BAD_REQUEST, "Missing name");
+    }
+    match apply_correction(&state, &file, request.name.as_deref()) {
+        Ok(name) => {
+            state.audit(&actor, "review.edit", Some(&id), serde_json::json!({
+                "suggested_name": file.suggested_name, "name": name,
+            }));
+            (StatusCode::OK, Json(ReviewResponse::new(&id, "pending", name)))
+        }
+        Err(reply) => reply,
+    }
+}
+
+// === Helpers ===
+
+/// Escape text for inclusion in HTML content or attribute values
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Start the web server with config (saved back to `config_path` on changes) and database
+pub async fn start_server(config: AppConfig, config_path: PathBuf, db: Database) -> crate::Result<()> {
+    serve(Arc::new(AppState::new(config, config_path, db))).await
+}
+
+/// Serve the web UI for `state` until the server fails
+pub async fn serve(state: Arc<AppState>) -> crate::Result<()> {
+    let config = state.config();
+    crate::ollama::limit_concurrency(config.ai_engine.max_concurrent);
+
+    let addr = format!("{}:{}", config.web.host, config.web.port);
     let router = create_router(state);
-    axum::serve(listener, router).await
-        .map_err(|e| crate::PanoptesError::Config(format!("Server error: {}", e)))?;
+
+    if !config.web.auth.enabled {
+        tracing::warn!("Web authentication is disabled");
+    }
+
+    let Some(ref tls_config) = config.web.tls else {
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        info!("Web UI available at {}", config.web.url());
+        axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await
+            .map_err(|e| crate::PanoptesError::Config(format!("Server error: {}", e)))?;
+        return Ok(());
+    };
+
+    tls::ensure_certificate(tls_config)?;
+    let rustls = tls::rustls_config(tls_config).await?;
+
+    if let Some(redirect_port) = tls_config.redirect_port {
+        let host = config.web.host.clone();
+        let https_port = config.web.port;
+        tokio::spawn(async move {
+            if let Err(e) = tls::serve_redirect(host, redirect_port, https_port).await {
+                tracing::error!("{}", e);
+            }
+        });
+    }
+
+    let listener = std::net::TcpListener::bind(&addr)?;
+    listener.set_nonblocking(true)?;
+    info!("Web UI available at {}", config.web.url());
+    axum_server::from_tcp_rustls(listener, rustls)
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
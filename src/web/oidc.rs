@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! OpenID Connect login (authorization code flow with PKCE)
+//!
+//! The provider is discovered from `{issuer}/.well-known/openid-configuration`.
+//! After the code exchange the user's claims are read from the userinfo
+//! endpoint and the `(issuer, sub)` pair is mapped to a Panoptes user, creating
+//! one on first login when `auto_create` is set. Only an email the provider
+//! has verified counts for `allowed_domains` or creates a user, as anyone can
+//! claim an address on a shared provider.
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{Duration, Utc};
+use minijinja::context;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+use super::security::csrf_cookie;
+use super::auth::{cookie_path, generate_token, hash_secret, safe_next, secure_attr, SESSION_COOKIE, USER_SUBJECT_PREFIX};
+use super::{escape_html, AppState};
+use crate::config::OidcConfig;
+use crate::{PanoptesError, Result};
+
+/// Cookie binding an in-progress login to the browser that started it
+const STATE_COOKIE: &str = "panoptes_oidc_state";
+
+/// How long a login may take between redirect and callback
+const LOGIN_TIMEOUT_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Claims read from the userinfo endpoint
+#[derive(Debug, Deserialize)]
+struct UserInfo {
+    sub: String,
+    email: Option<String>,
+    /// Some providers send it as a string
+    #[serde(default, deserialize_with = "bool_or_string")]
+    email_verified: Option<bool>,
+    preferred_username: Option<String>,
+    name: Option<String>,
+}
+
+impl UserInfo {
+    /// The email, if the provider has verified it
+    fn verified_email(&self) -> Option<&str> {
+        self.email.as_deref().filter(|_| self.email_verified == Some(true))
+    }
+}
+
+fn bool_or_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<bool>, D::Error> {
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::Bool(b)) => Some(b),
+        Some(serde_json::Value::String(s)) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+struct PendingLogin {
+    verifier: String,
+    next: String,
+    started: Instant,
+}
+
+/// OIDC client state shared by the login and callback handlers
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    discovery: OnceCell<Discovery>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            discovery: OnceCell::new(),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Label for the login button
+    pub fn provider_name(&self) -> &str {
+        &self.config.provider_name
+    }
+
+    async fn discovery(&self) -> Result<&Discovery> {
+        self.discovery.get_or_try_init(|| async {
+            let url = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+            let discovery = self.http.get(&url).send().await?
+                .error_for_status()?
+                .json::<Discovery>().await?;
+            Ok::<_, PanoptesError>(discovery)
+        }).await
+    }
+
+    fn start(&self, next: &str) -> (String, String) {
+        let state = generate_token();
+        let verifier = generate_token();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, p| p.started.elapsed().as_secs() < LOGIN_TIMEOUT_SECS);
+        pending.insert(state.clone(), PendingLogin {
+            verifier,
+            next: next.to_string(),
+            started: Instant::now(),
+        });
+        (state, challenge)
+    }
+
+    fn finish(&self, state: &str) -> Option<PendingLogin> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.remove(state)
+            .filter(|p| p.started.elapsed().as_secs() < LOGIN_TIMEOUT_SECS)
+    }
+
+    async fn exchange(&self, code: &str, verifier: &str) -> Result<UserInfo> {
+        let discovery = self.discovery().await?;
+
+        let token: TokenResponse = self.http.post(&discovery.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("code_verifier", verifier),
+            ])
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        let info = self.http.get(&discovery.userinfo_endpoint)
+            .bearer_auth(&token.access_token)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+        Ok(info)
+    }
+
+    /// Whether this identity may log in at all
+    fn allowed(&self, info: &UserInfo) -> bool {
+        if self.config.allowed_domains.is_empty() {
+            return true;
+        }
+        info.verified_email()
+            .and_then(|email| email.rsplit_once('@'))
+            .is_some_and(|(_, domain)| self.config.allowed_domains.iter().any(|d| d.eq_ignore_ascii_case(domain)))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StartQuery {
+    next: Option<String>,
+}
+
+/// Redirect to the identity provider
+pub async fn oidc_login(State(state): State<Arc<AppState>>, Query(query): Query<StartQuery>) -> Response {
+    let Some(oidc) = state.oidc.as_ref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let discovery = match oidc.discovery().await {
+        Ok(discovery) => discovery,
+        Err(e) => {
+            warn!("OIDC discovery failed: {}", e);
+            return login_error(&state, "The identity provider is unavailable");
+        }
+    };
+
+    let (login_state, challenge) = oidc.start(safe_next(query.next.as_deref()));
+    let scopes = oidc.config.scopes.join(" ");
+    let mut url = match reqwest::Url::parse(&discovery.authorization_endpoint) {
+        Ok(url) => url,
+        Err(e) => {
+            warn!("Invalid OIDC authorization endpoint: {}", e);
+            return login_error(&state, "The identity provider is misconfigured");
+        }
+    };
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &oidc.config.client_id)
+        .append_pair("redirect_uri", &oidc.config.redirect_url)
+        .append_pair("scope", &scopes)
+        .append_pair("state", &login_state)
+        .append_pair("code_challenge", &challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    let cookie = format!(
+        "{}={}; Path={}/auth/oidc; HttpOnly; SameSite=Lax; Max-Age={}{}",
+        STATE_COOKIE, login_state, state.base_path, LOGIN_TIMEOUT_SECS, secure_attr(&state)
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to(url.as_str())).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Complete the login: exchange the code, map the identity to a user and start a session
+pub async fn oidc_callback(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CallbackQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(oidc) = state.oidc.as_ref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Some(error) = query.error {
+        return login_error(&state, &format!("The identity provider refused the login: {}", error));
+    }
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return login_error(&state, "Incomplete response from the identity provider");
+    };
+
+    // The state must match the one this browser was given, not just any pending login
+    let cookie_state = super::auth::cookie(&headers, STATE_COOKIE);
+    if cookie_state != Some(login_state.as_str()) {
+        return login_error(&state, "Login session mismatch; please try again");
+    }
+    let Some(pending) = oidc.finish(&login_state) else {
+        return login_error(&state, "Login expired; please try again");
+    };
+
+    let info = match oidc.exchange(&code, &pending.verifier).await {
+        Ok(info) => info,
+        Err(e) => {
+            warn!("OIDC code exchange failed: {}", e);
+            return login_error(&state, "Could not complete the login with the identity provider");
+        }
+    };
+    if !oidc.allowed(&info) {
+        return login_error(&state, "This account is not allowed to use Panoptes");
+    }
+
+    let username = match map_user(&state, oidc, &info) {
+        Ok(Some(username)) => username,
+        Ok(None) => return login_error(&state, "No Panoptes user is linked to this account"),
+        Err(e) => {
+            warn!("Failed to map OIDC identity: {}", e);
+            return login_error(&state, "Could not create the user account");
+        }
+    };
+
+    let session = generate_token();
+    let hours = state.config().web.auth.session_hours;
+    let expires = Utc::now() + Duration::hours(hours as i64);
+    let subject = format!("{}{}", USER_SUBJECT_PREFIX, username);
+    if let Err(e) = state.db.create_web_session(&hash_secret(&session), &subject, expires) {
+        warn!("Failed to create session: {}", e);
+        return login_error(&state, "Could not start a session");
+    }
+    info!("User '{}' logged in via {}", username, oidc.config.provider_name);
+
+    let session_cookie = format!(
+        "{}={}; Path={}; HttpOnly; SameSite=Strict; Max-Age={}{}",
+        SESSION_COOKIE, session, cookie_path(&state), hours * 3600, secure_attr(&state)
+    );
+    let csrf = csrf_cookie(&state, Some(&session), hours * 3600);
+    let clear_state = format!("{}=; Path={}/auth/oidc; HttpOnly; Max-Age=0", STATE_COOKIE, state.base_path);
+
+    // A redirect would still count as part of the cross-site navigation from the
+    // provider, so the SameSite=Strict session cookie wouldn't be sent; continue
+    // from a same-site page instead
+    let next = escape_html(&format!("{}{}", state.base_path, pending.next));
+    let page = format!(
+        r#"<!DOCTYPE html><html><head><meta http-equiv="refresh" content="0; url={0}"></head><body><a href="{0}">Continue</a></body></html>"#,
+        next
+    );
+    let mut response = Html(page).into_response();
+    let response_headers = response.headers_mut();
+    for cookie in [session_cookie, csrf, clear_state] {
+        if let Ok(value) = cookie.parse() {
+            response_headers.append(header::SET_COOKIE, value);
+        }
+    }
+    response
+}
+
+/// Find the Panoptes user linked to an external identity, creating one if allowed
+fn map_user(state: &AppState, oidc: &OidcClient, info: &UserInfo) -> Result<Option<String>> {
+    let issuer = &oidc.config.issuer;
+    if let Some(username) = state.db.find_user_by_identity(issuer, &info.sub)? {
+        return Ok(Some(username));
+    }
+    if !oidc.config.auto_create {
+        return Ok(None);
+    }
+    if info.verified_email().is_none() {
+        warn!("Not creating a user for {} identity {}: no verified email", oidc.config.provider_name, info.sub);
+        return Ok(None);
+    }
+
+    let base = info.preferred_username.as_deref()
+        .or(info.email.as_deref())
+        .unwrap_or(&info.sub);
+    let username = state.db.create_user(base, info.email.as_deref(), info.name.as_deref(), oidc.config.default_role)?;
+    state.db.link_identity(issuer, &info.sub, &username)?;
+    info!("Created user '{}' for {} identity {}", username, oidc.config.provider_name, info.sub);
+    Ok(Some(username))
+}
+
+fn login_error(state: &AppState, message: &str) -> Response {
+    (StatusCode::UNAUTHORIZED, state.templates.render("login_failed.html", context! { message })).into_response()
+}
@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Dashboard: activity charts, upload analysis and recent files
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::web::AppState;
+
+/// Files shown under "Recent Files"
+const RECENT_FILES: usize = 10;
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let files = state.db.get_recent_files(RECENT_FILES).unwrap_or_default();
+    let categories = state.db.get_category_stats().unwrap_or_default();
+    let file_count = state.db.get_file_count().unwrap_or(0);
+
+    state.templates.render("dashboard.html", context! {
+        files,
+        categories,
+        file_count,
+        has_inbox => state.config().web.inbox.is_some(),
+    })
+}
@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! File browser with filters and paging
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::db::FileFilter;
+use crate::web::{AppState, FILES_PAGE_SIZE};
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let (files, total) = state.db.query_files(&FileFilter::default(), FILES_PAGE_SIZE, 0).unwrap_or_default();
+    let categories = state.db.get_category_stats().unwrap_or_default();
+
+    state.templates.render("files.html", context! {
+        files,
+        total,
+        categories,
+        page_size => FILES_PAGE_SIZE,
+    })
+}
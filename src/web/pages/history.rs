@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Rename history with single, selected and per-batch undo
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::history::History;
+use crate::web::AppState;
+
+/// Most recent events listed
+const HISTORY_ENTRIES: usize = 100;
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let history = History::new(state.db.clone());
+    let (entries, _) = history.get_page(HISTORY_ENTRIES, 0).unwrap_or_default();
+
+    state.templates.render("history.html", context! { entries })
+}
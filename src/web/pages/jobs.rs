@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Bulk operations and the watcher's job queue, refreshed from `/api/jobs`
+//! and `/api/queue`
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::web::AppState;
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let queue = state.db.list_jobs(None, 50).unwrap_or_default();
+    state.templates.render("jobs.html", context! { jobs => state.jobs.list(), queue => queue })
+}
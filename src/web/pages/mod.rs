@@ -0,0 +1,18 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! HTML pages
+//!
+//! Each handler gathers what its page shows and renders the template of the
+//! same name (see [`super::templates`]); the pages' scripts talk to the JSON API.
+
+pub mod dashboard;
+pub mod files;
+pub mod history;
+pub mod jobs;
+pub mod review;
+pub mod search;
+pub mod settings;
+pub mod similar;
+pub mod tags;
+pub mod watch;
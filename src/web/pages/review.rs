@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Review queue for low-confidence suggestions
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::db::ReviewStatus;
+use crate::renamer::auto_rename_threshold;
+use crate::web::{preview_type, AppState};
+
+/// Pending suggestions listed at once
+const REVIEW_ITEMS: usize = 100;
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let files = state.db.get_files_by_status(ReviewStatus::Pending, REVIEW_ITEMS).unwrap_or_default();
+    let files: Vec<_> = files.into_iter()
+        .map(|file| context! {
+            preview => preview_type(&file.new_path).is_some(),
+            file,
+        })
+        .collect();
+
+    let config = state.config();
+    state.templates.render("review.html", context! {
+        files,
+        suggest => config.rules.suggest_threshold,
+        auto_rename => auto_rename_threshold(&config),
+    })
+}
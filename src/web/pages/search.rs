@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Faceted search; results and facets are loaded from `/api/search`
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::web::AppState;
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    state.templates.render("search.html", context! {})
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Current settings, and the configuration editor for admins
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::renamer::auto_rename_threshold;
+use crate::web::AppState;
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let config = state.config();
+    state.templates.render("settings.html", context! {
+        auto_rename => auto_rename_threshold(&config),
+        config => &*config,
+    })
+}
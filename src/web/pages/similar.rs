@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! "More like this": the recorded files alike a file
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::similarity;
+use crate::web::AppState;
+
+pub async fn page(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> Response {
+    let Some(file) = state.db.get_file(&id).ok().flatten() else {
+        return (StatusCode::NOT_FOUND, "No such file").into_response();
+    };
+    let matches = match similarity::similar_to_record(&state.db, &file, &state.config()).await {
+        Ok(matches) => matches,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let matches: Vec<_> = matches.iter()
+        .map(|m| context! { file => m.file, score => m.score, likeness => m.likeness.as_str() })
+        .collect();
+    state.templates.render("similar.html", context! { file, matches })
+}
@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Tag overview and per-tag pages with rename/merge controls
+
+use axum::{
+    extract::{Path, State},
+    response::Response,
+};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::db::FileFilter;
+use crate::web::AppState;
+
+/// Files listed on a tag's page
+const TAG_FILES: usize = 500;
+
+/// Every tag with its file count
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let tags = state.db.get_tag_counts().unwrap_or_default();
+    state.templates.render("tags.html", context! { tags })
+}
+
+/// Files carrying a tag
+pub async fn tag_page(State(state): State<Arc<AppState>>, Path(tag): Path<String>) -> Response {
+    let filter = FileFilter { tag: Some(tag.clone()), ..FileFilter::default() };
+    let (files, total) = state.db.query_files(&filter, TAG_FILES, 0).unwrap_or_default();
+    state.templates.render("tag.html", context! { tag, files, total })
+}
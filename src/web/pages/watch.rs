@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Watch directories: their status, options, and adding or removing them
+
+use axum::{extract::State, response::Response};
+use minijinja::context;
+use std::sync::Arc;
+
+use crate::web::{admin, AppState};
+
+pub async fn page(State(state): State<Arc<AppState>>) -> Response {
+    let dirs = admin::watch_dirs(&state).unwrap_or_default();
+    let live = state.live.as_ref().map(|live| live.snapshot());
+    state.templates.render("watch.html", context! { dirs, live })
+}
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! The watcher's background job queue (see [`crate::jobs`])
+//!
+//! `GET /api/queue` lists the latest jobs, or with `?state=dead` those in
+//! one state. Dead and cancelled jobs can be retried, and queued ones
+//! cancelled; running ones are left to finish.
+
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::auth::Actor;
+use super::AppState;
+use crate::jobs::{Job, JobState};
+
+/// Jobs listed when no `limit` is given
+const DEFAULT_LIMIT: usize = 50;
+
+type QueueReply = (StatusCode, Json<Value>);
+
+fn queue_error(code: StatusCode, message: impl ToString) -> QueueReply {
+    (code, Json(json!({ "error": message.to_string() })))
+}
+
+#[derive(Deserialize)]
+pub struct QueueQuery {
+    state: Option<JobState>,
+    limit: Option<usize>,
+}
+
+fn require_job(state: &AppState, id: i64) -> Result<Job, QueueReply> {
+    state.db.get_job(id)
+        .map_err(|e| queue_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or_else(|| queue_error(StatusCode::NOT_FOUND, format!("No job {}", id)))
+}
+
+/// The latest jobs, newest first
+pub async fn api_list_queue(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<QueueQuery>,
+) -> Result<Json<Vec<Job>>, QueueReply> {
+    state.db.list_jobs(query.state, query.limit.unwrap_or(DEFAULT_LIMIT))
+        .map(Json)
+        .map_err(|e| queue_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+pub async fn api_get_queued_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Job>, QueueReply> {
+    require_job(&state, id).map(Json)
+}
+
+/// Queue a dead or cancelled job again, its attempts starting over
+pub async fn api_retry_queued_job(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<i64>,
+) -> Result<Json<Job>, QueueReply> {
+    let retried = state.db.retry_job(id).map_err(|e| queue_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let job = require_job(&state, id)?;
+    if !retried {
+        return Err(queue_error(StatusCode::CONFLICT,
+            format!("The job is {}; only dead and cancelled jobs can be retried", job.state.as_str())));
+    }
+    state.audit(&actor, "queue.retry", Some(&id.to_string()), json!({ "kind": job.task.kind(), "target": job.task.target() }));
+    Ok(Json(job))
+}
+
+/// Cancel a job that hasn't started
+pub async fn api_cancel_queued_job(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<i64>,
+) -> Result<Json<Job>, QueueReply> {
+    let cancelled = state.db.cancel_job(id).map_err(|e| queue_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let job = require_job(&state, id)?;
+    if !cancelled {
+        return Err(queue_error(StatusCode::CONFLICT,
+            format!("The job is {}; only queued jobs can be cancelled", job.state.as_str())));
+    }
+    state.audit(&actor, "queue.cancel", Some(&id.to_string()), json!({ "kind": job.task.kind(), "target": job.task.target() }));
+    Ok(Json(job))
+}
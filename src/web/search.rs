@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Faceted search
+//!
+//! `GET /api/search` takes a text query plus any number of facet values
+//! (`category`, `tag`, `ext`, `confidence`, `month`; each may repeat) and
+//! returns a page of matching files together with counts for every facet
+//! value. Values of one facet are alternatives, different facets must all
+//! match. A facet's counts ignore its own selection, so they show what
+//! checking another value would add.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{analyze_error, AppState};
+use crate::db::{FacetRow, FileRecord};
+
+/// Results per page when the request doesn't say
+const DEFAULT_LIMIT: usize = 50;
+
+/// Largest page of results
+const MAX_LIMIT: usize = 500;
+
+/// Tags listed in the tag facet (selected ones are always included)
+const MAX_TAG_VALUES: usize = 50;
+
+/// Confidence facet ranges, in percent
+const CONFIDENCE_STEP: u32 = 10;
+
+/// Something search results can be narrowed by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Facet {
+    Category,
+    Tag,
+    Ext,
+    Confidence,
+    Month,
+}
+
+const FACETS: [Facet; 5] = [Facet::Category, Facet::Tag, Facet::Ext, Facet::Confidence, Facet::Month];
+
+impl Facet {
+    /// Query parameter and response key
+    fn name(self) -> &'static str {
+        match self {
+            Self::Category => "category",
+            Self::Tag => "tag",
+            Self::Ext => "ext",
+            Self::Confidence => "confidence",
+            Self::Month => "month",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        FACETS.into_iter().find(|f| f.name() == name)
+    }
+
+    /// The values `row` has for this facet
+    fn values(self, row: &FacetRow) -> Vec<String> {
+        match self {
+            Self::Category => vec![row.category.clone().unwrap_or_else(|| "Uncategorized".to_string())],
+            Self::Tag => row.tags.clone(),
+            Self::Ext => vec![
+                Path::new(&row.path)
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_else(|| "(none)".to_string()),
+            ],
+            Self::Confidence => {
+                let percent = (row.confidence.clamp(0.0, 1.0) * 100.0) as u32;
+                let low = (percent / CONFIDENCE_STEP).min(100 / CONFIDENCE_STEP - 1) * CONFIDENCE_STEP;
+                vec![format!("{}-{}", low, low + CONFIDENCE_STEP)]
+            }
+            Self::Month => vec![row.created_at.format("%Y-%m").to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FacetValue {
+    pub value: String,
+    /// Matching files with this value
+    pub count: usize,
+    pub selected: bool,
+}
+
+#[derive(Serialize)]
+pub struct SearchResponse {
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+    pub files: Vec<FileRecord>,
+    /// Values and counts per facet, keyed by facet name
+    pub facets: HashMap<&'static str, Vec<FacetValue>>,
+}
+
+/// Parsed search request
+#[derive(Default)]
+struct Search {
+    text: String,
+    selected: HashMap<&'static str, BTreeSet<String>>,
+    limit: usize,
+    offset: usize,
+}
+
+impl Search {
+    fn parse(params: Vec<(String, String)>) -> Result<Self, String> {
+        let mut search = Search { limit: DEFAULT_LIMIT, ..Default::default() };
+        for (key, value) in params {
+            match key.as_str() {
+                "q" => search.text = value.trim().to_string(),
+                "limit" => search.limit = value.parse().map_err(|_| "limit must be a number")?,
+                "offset" => search.offset = value.parse().map_err(|_| "offset must be a number")?,
+                _ => match Facet::parse(&key) {
+                    Some(facet) if !value.is_empty() => {
+                        search.selected.entry(facet.name()).or_default().insert(value);
+                    }
+                    Some(_) => {}
+                    None => return Err(format!("Unknown search parameter: {}", key)),
+                },
+            }
+        }
+        search.limit = search.limit.min(MAX_LIMIT);
+        Ok(search)
+    }
+
+    /// Whether `row` matches every selected facet except `skip`
+    fn matches(&self, row: &FacetRow, skip: Option<Facet>) -> bool {
+        FACETS.into_iter()
+            .filter(|&facet| Some(facet) != skip)
+            .all(|facet| match self.selected.get(facet.name()) {
+                Some(wanted) => facet.values(row).iter().any(|v| wanted.contains(v)),
+                None => true,
+            })
+    }
+
+    /// Counts for `facet` over the rows matching the other facets
+    fn facet_values(&self, facet: Facet, rows: &[FacetRow]) -> Vec<FacetValue> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for row in rows.iter().filter(|row| self.matches(row, Some(facet))) {
+            for value in facet.values(row) {
+                *counts.entry(value).or_default() += 1;
+            }
+        }
+        let selected = self.selected.get(facet.name());
+        for value in selected.into_iter().flatten() {
+            counts.entry(value.clone()).or_default();
+        }
+
+        let mut values: Vec<FacetValue> = counts.into_iter()
+            .map(|(value, count)| FacetValue {
+                selected: selected.is_some_and(|s| s.contains(&value)),
+                value,
+                count,
+            })
+            .collect();
+        match facet {
+            // Ranges and months read best in order
+            Facet::Confidence => values.sort_by_key(|v| v.value.split('-').next().and_then(|n| n.parse::<u32>().ok())),
+            Facet::Month => values.sort_by(|a, b| b.value.cmp(&a.value)),
+            _ => values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value))),
+        }
+        if facet == Facet::Tag {
+            let mut kept = 0;
+            values.retain(|v| {
+                kept += 1;
+                v.selected || kept <= MAX_TAG_VALUES
+            });
+        }
+        values
+    }
+}
+
+/// Search with facet counts
+pub async fn api_search(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<Json<SearchResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let search = Search::parse(params).map_err(|e| analyze_error(StatusCode::BAD_REQUEST, e))?;
+    let rows = state.db.get_facet_rows(&search.text)
+        .map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let facets = FACETS.into_iter()
+        .map(|facet| (facet.name(), search.facet_values(facet, &rows)))
+        .collect();
+
+    let matching: Vec<&FacetRow> = rows.iter().filter(|row| search.matches(row, None)).collect();
+    let mut files = Vec::new();
+    for row in matching.iter().skip(search.offset).take(search.limit) {
+        if let Some(file) = state.db.get_file(&row.id).map_err(|e| analyze_error(StatusCode::INTERNAL_SERVER_ERROR, e))? {
+            files.push(file);
+        }
+    }
+
+    Ok(Json(SearchResponse {
+        total: matching.len(),
+        limit: search.limit,
+        offset: search.offset,
+        files,
+        facets,
+    }))
+}
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Browser protections
+//!
+//! Every response carries security headers: a same-origin Content Security
+//! Policy, no framing and no MIME sniffing. State-changing requests are
+//! protected against cross-site request forgery in two ways:
+//!
+//! - Browsers label requests with `Sec-Fetch-Site`; cross-site ones are refused
+//!   unless their `Origin` is in `web.cors_origins`. This also covers setups
+//!   without sessions (auth disabled, `allow_localhost`).
+//! - Requests authenticated by the session cookie must echo a CSRF token in the
+//!   `X-CSRF-Token` header, or the `csrf_token` field for the logout form. The
+//!   token is derived from the session and handed to the page scripts in a
+//!   cookie of its own, which other sites can't read.
+//!
+//! Requests with an API token carry no ambient credentials and need neither.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+
+use super::auth::{bearer_token, cookie, cookie_path, secure_attr, SESSION_COOKIE};
+use super::AppState;
+
+/// Cookie holding the CSRF token for the page scripts
+pub const CSRF_COOKIE: &str = "panoptes_csrf";
+
+/// Header state-changing requests from the UI carry the token in
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Policy for the UI: scripts, styles and images from Panoptes only. Inline
+/// `style` attributes are allowed, inline scripts and event handlers are not.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+    img-src 'self' data:; object-src 'none'; base-uri 'self'; form-action 'self'; frame-ancestors 'none'";
+
+/// Policy for GraphiQL, which loads its scripts from unpkg and starts inline
+pub const GRAPHIQL_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' 'unsafe-inline' https://unpkg.com; style-src 'self' 'unsafe-inline' https://unpkg.com; \
+    font-src 'self' data: https://unpkg.com; img-src 'self' data: https://unpkg.com; object-src 'none'; \
+    base-uri 'self'; frame-ancestors 'none'";
+
+/// The CSRF token for a session
+pub fn csrf_token(session: &str) -> String {
+    blake3::Hash::from(blake3::derive_key("panoptes 2025 csrf token", session.as_bytes())).to_hex().to_string()
+}
+
+/// `Set-Cookie` value handing the CSRF token for `session` to the page
+/// scripts; `None` clears it
+pub(crate) fn csrf_cookie(state: &AppState, session: Option<&str>, max_age: u64) -> String {
+    let (token, max_age) = match session {
+        Some(session) => (csrf_token(session), max_age),
+        None => (String::new(), 0),
+    };
+    // Not HttpOnly: the scripts read it to set the header
+    format!(
+        "{}={}; Path={}; SameSite=Strict; Max-Age={}{}",
+        CSRF_COOKIE, token, cookie_path(state), max_age, secure_attr(state)
+    )
+}
+
+/// Whether `presented` is the CSRF token for the request's session; true when
+/// there is no session to protect
+pub(crate) fn csrf_valid(headers: &HeaderMap, presented: Option<&str>) -> bool {
+    match cookie(headers, SESSION_COOKIE) {
+        Some(session) => presented.is_some_and(|token| {
+            // Compare as blake3 hashes, which compare in constant time
+            blake3::hash(token.as_bytes()) == blake3::hash(csrf_token(session).as_bytes())
+        }),
+        None => true,
+    }
+}
+
+fn forbidden(message: &str) -> Response {
+    (StatusCode::FORBIDDEN, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Whether a browser marked the request as coming from another site that isn't
+/// an allowed CORS origin
+fn is_cross_site(state: &AppState, headers: &HeaderMap) -> bool {
+    let site = headers.get("sec-fetch-site").and_then(|v| v.to_str().ok());
+    if !matches!(site, Some("cross-site" | "same-site")) {
+        return false;
+    }
+    let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    !origin.is_some_and(|origin| state.config().web.cors_origins.iter().any(|o| o == origin))
+}
+
+/// Middleware adding security headers to every response and refusing
+/// cross-site state changes
+pub async fn protect(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    if !request.method().is_safe() && bearer_token(headers).is_none() && is_cross_site(&state, headers) {
+        return with_security_headers(&state, forbidden("Cross-site request refused"));
+    }
+    let response = next.run(request).await;
+    with_security_headers(&state, response)
+}
+
+fn with_security_headers(state: &AppState, mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    // Handlers with other needs set their own policy
+    if !headers.contains_key(header::CONTENT_SECURITY_POLICY) {
+        headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(CONTENT_SECURITY_POLICY));
+    }
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("same-origin"));
+    if state.config().web.tls.is_some() {
+        headers.insert(header::STRICT_TRANSPORT_SECURITY, HeaderValue::from_static("max-age=31536000"));
+    }
+    response
+}
+
+/// Middleware requiring the CSRF token on state-changing requests made with the
+/// session cookie; runs alongside [`super::auth::require_auth`]
+pub async fn require_csrf(request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    if request.method().is_safe() || bearer_token(headers).is_some() {
+        return next.run(request).await;
+    }
+    let presented = headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok());
+    if !csrf_valid(headers, presented) {
+        return forbidden("Missing or invalid CSRF token");
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::AppConfig;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(header::HeaderName::from_bytes(name.as_bytes()).unwrap(), value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_csrf_token() {
+        let session = format!("{}=abc123; theme=dark", SESSION_COOKIE);
+        let with_session = headers(&[("cookie", &session)]);
+        assert!(csrf_valid(&with_session, Some(&csrf_token("abc123"))));
+        assert!(!csrf_valid(&with_session, Some(&csrf_token("other"))));
+        assert!(!csrf_valid(&with_session, Some("")));
+        assert!(!csrf_valid(&with_session, None));
+        // Nothing to forge without a session
+        assert!(csrf_valid(&HeaderMap::new(), None));
+    }
+
+    #[test]
+    fn test_cross_site() {
+        let mut config = AppConfig::default();
+        config.web.cors_origins = vec!["https://dash.example".to_string()];
+        let state = AppState::new(config, "config.json".into(), Database::in_memory().unwrap());
+
+        assert!(!is_cross_site(&state, &HeaderMap::new()));
+        assert!(!is_cross_site(&state, &headers(&[("sec-fetch-site", "same-origin")])));
+        assert!(!is_cross_site(&state, &headers(&[("sec-fetch-site", "none")])));
+        assert!(is_cross_site(&state, &headers(&[("sec-fetch-site", "cross-site"), ("origin", "https://evil.example")])));
+        assert!(is_cross_site(&state, &headers(&[("sec-fetch-site", "same-site")])));
+        assert!(!is_cross_site(&state, &headers(&[("sec-fetch-site", "cross-site"), ("origin", "https://dash.example")])));
+    }
+}
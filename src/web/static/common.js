@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Shared by every page: the CSRF token for state-changing requests, and
+// thumbnails that fail to load
+(() => {
+    const csrfToken = () => document.cookie.split('; ')
+        .find(c => c.startsWith('panoptes_csrf='))
+        ?.slice('panoptes_csrf='.length) || '';
+
+    // Send the token with every same-origin request that changes something
+    const send = window.fetch;
+    window.fetch = (resource, options = {}) => {
+        const method = (options.method || 'GET').toUpperCase();
+        if (!['GET', 'HEAD', 'OPTIONS'].includes(method) && csrfToken()) {
+            const headers = new Headers(options.headers);
+            headers.set('X-CSRF-Token', csrfToken());
+            options = { ...options, headers };
+        }
+        return send(resource, options);
+    };
+
+    // Plain form posts carry it as a field
+    document.addEventListener('submit', e => {
+        const field = e.target.querySelector('input[name="csrf_token"]');
+        if (field) field.value = csrfToken();
+    });
+
+    // Inline handlers are blocked by the Content Security Policy
+    document.addEventListener('error', e => {
+        if (e.target instanceof HTMLImageElement && e.target.classList.contains('thumb')) e.target.remove();
+    }, true);
+})();
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Dashboard charts from /api/stats/timeseries
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+const palette = ['#e94560', '#00d9a5', '#4cc9f0', '#f9c74f', '#b5179e', '#90be6d', '#f8961e', '#577590'];
+const svgNs = 'http://www.w3.org/2000/svg';
+
+function svgEl(tag, attrs, text) {
+    const el = document.createElementNS(svgNs, tag);
+    for (const [key, value] of Object.entries(attrs)) el.setAttribute(key, value);
+    if (text !== undefined) el.textContent = text;
+    return el;
+}
+
+// Stacked bar chart: one bar per label, one segment per series
+function drawBars(id, labels, series) {
+    const svg = document.getElementById(id);
+    svg.replaceChildren();
+    const width = 600, height = 160, top = 14, bottom = 16;
+    const totals = labels.map((_, i) => series.reduce((sum, s) => sum + s.values[i], 0));
+    const max = Math.max(1, ...totals);
+    const step = width / labels.length;
+    labels.forEach((label, i) => {
+        let y = height - bottom;
+        for (const s of series) {
+            const value = s.values[i];
+            if (!value) continue;
+            const h = value / max * (height - top - bottom);
+            y -= h;
+            const bar = svgEl('rect', { x: i * step + 1, y, width: Math.max(step - 2, 1), height: h, fill: s.color });
+            bar.appendChild(svgEl('title', {}, `${label}: ${s.name ? s.name + ' ' : ''}${value}`));
+            svg.appendChild(bar);
+        }
+    });
+    const style = { fill: 'var(--text-secondary)', 'font-size': 11 };
+    svg.appendChild(svgEl('text', { ...style, x: 2, y: 10 }, `max ${max}`));
+    svg.appendChild(svgEl('text', { ...style, x: 2, y: height - 3 }, labels[0]));
+    svg.appendChild(svgEl('text', { ...style, x: width - 2, y: height - 3, 'text-anchor': 'end' }, labels[labels.length - 1]));
+}
+
+async function loadCharts() {
+    const days = document.getElementById('chart-days').value;
+    const res = await fetch(`${base}/api/stats/timeseries?days=${days}`);
+    if (!res.ok) return;
+    const data = await res.json();
+
+    const processed = data.files.reduce((a, b) => a + b, 0);
+    document.getElementById('chart-summary').textContent =
+        `${processed} processed in the last ${data.days.length} days (${(processed / data.days.length).toFixed(1)} per day)`;
+
+    drawBars('chart-files', data.days, [{ values: data.files, color: palette[0] }]);
+    drawBars('chart-confidence',
+        data.confidence.map(b => `${Math.round(b.min * 100)}-${Math.round(b.max * 100)}%`),
+        [{ values: data.confidence.map(b => b.count), color: palette[1] }]);
+
+    const legend = document.getElementById('chart-legend');
+    legend.replaceChildren();
+    const series = Object.entries(data.categories).map(([name, values], i) => {
+        const color = palette[i % palette.length];
+        const item = document.createElement('span');
+        item.style.marginRight = '10px';
+        item.style.color = color;
+        item.textContent = `■ ${name}`;
+        legend.appendChild(item);
+        return { name, values, color };
+    });
+    drawBars('chart-categories', data.days, series);
+}
+
+document.getElementById('chart-days').onchange = loadCharts;
+loadCharts();
+
+// Analyze uploads, optionally saving them to the inbox
+const dropzone = document.getElementById('dropzone');
+const input = document.getElementById('upload-input');
+const output = document.getElementById('upload-result');
+dropzone.onclick = () => input.click();
+input.onchange = () => input.files.length && upload(input.files[0]);
+dropzone.ondragover = (e) => { e.preventDefault(); dropzone.style.borderColor = 'var(--accent)'; };
+dropzone.ondragleave = () => { dropzone.style.borderColor = 'var(--border)'; };
+dropzone.ondrop = (e) => {
+    e.preventDefault();
+    dropzone.style.borderColor = 'var(--border)';
+    if (e.dataTransfer.files.length) upload(e.dataTransfer.files[0]);
+};
+
+async function upload(file) {
+    const save = document.getElementById('save-upload');
+    const form = new FormData();
+    form.append('file', file);
+    output.textContent = `Analyzing ${file.name}...`;
+    const res = await fetch(`${base}/api/analyze?save=${save && save.checked}`, { method: 'POST', body: form });
+    const body = await res.json();
+    if (!res.ok) {
+        output.textContent = body.error;
+        return;
+    }
+    output.textContent = `${body.filename} → ${body.suggested_name} (${Math.round(body.confidence * 100)}%)`
+        + (body.saved_to ? `, saved to ${body.saved_to}` : '');
+}
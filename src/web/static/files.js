@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Filtering and paging for the files page
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+const container = document.getElementById('files-table');
+const pageSize = Number(container.dataset.pageSize);
+let offset = 0;
+let total = Number(container.dataset.total);
+
+function esc(value) {
+    const div = document.createElement('div');
+    div.textContent = value ?? '';
+    return div.innerHTML;
+}
+
+function baseName(path) {
+    return path.split(/[\\/]/).pop();
+}
+
+function renderRow(f) {
+    const state = f.rename_id ? (f.undone ? 'Undone' : 'Renamed') : 'Not renamed';
+    const date = new Date(f.created_at).toISOString().slice(0, 16).replace('T', ' ');
+    return `<tr>
+        <td><img src="${base}/api/files/${encodeURIComponent(f.id)}/thumbnail" alt="" loading="lazy" class="thumb"></td>
+        <td>${esc(f.suggested_name)}</td>
+        <td>${esc(baseName(f.original_path))}</td>
+        <td>${esc(baseName(f.new_path))}</td>
+        <td><span class="category-badge">${esc(f.category || 'Uncategorized')}</span></td>
+        <td><div class="confidence"><div class="confidence-fill" style="width: ${Math.round(f.confidence * 100)}%"></div></div></td>
+        <td>${state}</td>
+        <td>${date}</td>
+        <td><a href="${base}/files/${encodeURIComponent(f.id)}/similar">More like this</a></td>
+    </tr>`;
+}
+
+function updatePager() {
+    const last = Math.min(offset + pageSize, total);
+    document.getElementById('page-info').textContent =
+        total ? `${offset + 1}-${last} of ${total}` : 'No files';
+    document.getElementById('prev').disabled = offset === 0;
+    document.getElementById('next').disabled = last >= total;
+}
+
+async function loadPage(newOffset) {
+    const params = new URLSearchParams();
+    for (const [key, value] of new FormData(document.getElementById('filters'))) {
+        if (value) params.append(key, value);
+    }
+    params.set('limit', pageSize);
+    params.set('offset', Math.max(0, newOffset));
+    const res = await fetch(`${base}/api/files?${params}`);
+    if (!res.ok) {
+        alert((await res.text()) || 'Failed to load files');
+        return;
+    }
+    const page = await res.json();
+    offset = page.offset;
+    total = page.total;
+    const table = container.querySelector('table');
+    table.querySelectorAll('tr:not(:first-child)').forEach(row => row.remove());
+    table.insertAdjacentHTML('beforeend', page.files.map(renderRow).join(''));
+    updatePager();
+}
+
+document.getElementById('filters').addEventListener('submit', e => {
+    e.preventDefault();
+    loadPage(0);
+});
+document.getElementById('prev').onclick = () => loadPage(offset - pageSize);
+document.getElementById('next').onclick = () => loadPage(offset + pageSize);
+updatePager();
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Undoing single renames, selections and whole batches
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+const boxes = () => [...document.querySelectorAll('input.select')];
+const selectedIds = () => boxes().filter(b => b.checked).map(b => b.value);
+
+function updateButton() {
+    const count = selectedIds().length;
+    const button = document.getElementById('undo-selected');
+    button.disabled = count === 0;
+    button.textContent = count ? `Undo selected (${count})` : 'Undo selected';
+}
+
+function selectSession(session) {
+    for (const box of boxes()) {
+        if (box.closest('tr').dataset.session === session) box.checked = true;
+    }
+    updateButton();
+}
+
+async function undo(ids) {
+    if (!ids.length) return;
+    const res = await fetch(`${base}/api/history/undo`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ ids }),
+    });
+    const reply = await res.json().catch(() => ({}));
+    if (!res.ok) {
+        alert(reply.error || 'Undo failed');
+        return;
+    }
+    const rows = new Map([...document.querySelectorAll('tr[data-id]')].map(row => [row.dataset.id, row]));
+    const failures = [];
+    for (const result of reply.results) {
+        const row = rows.get(result.id);
+        if (result.status === 'reverted' && row) {
+            row.querySelector('.action').textContent = 'Undone';
+            row.querySelector('input.select')?.remove();
+        } else if (result.status !== 'reverted') {
+            failures.push(result.message);
+        }
+    }
+    updateButton();
+    if (failures.length) alert(failures.join('\n'));
+}
+
+document.addEventListener('click', e => {
+    if (e.target.id === 'undo-selected') {
+        undo(selectedIds());
+    } else if (e.target.matches('button.undo')) {
+        undo([e.target.closest('tr').dataset.id]);
+    } else if (e.target.matches('a.session')) {
+        e.preventDefault();
+        selectSession(e.target.closest('tr').dataset.session);
+    }
+});
+
+document.addEventListener('change', e => {
+    if (e.target.id === 'select-all') boxes().forEach(b => b.checked = e.target.checked);
+    updateButton();
+});
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Bulk jobs and the watcher's queue with cancel and retry, refreshed while anything is unfinished
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+const REFRESH_MS = 2000;
+
+function esc(value) {
+    const div = document.createElement('div');
+    div.textContent = value ?? '';
+    return div.innerHTML;
+}
+
+function duration(secs) {
+    if (secs < 60) return `${secs}s`;
+    if (secs < 3600) return `${Math.floor(secs / 60)}m ${secs % 60}s`;
+    return `${Math.floor(secs / 3600)}h ${Math.floor(secs % 3600 / 60)}m`;
+}
+
+function renderRow(job) {
+    const pct = job.total ? Math.round(job.processed / job.total * 100) : 100;
+    const running = job.state === 'queued' || job.state === 'running';
+    const retryable = !running && (job.failed > 0 || job.processed < job.total);
+    const errors = job.errors.length
+        ? `<details><summary>${job.failed} failed</summary><ul style="margin-left: 20px;">${
+            job.errors.map(e => `<li><code>${esc(e.id)}</code>: ${esc(e.error)}</li>`).join('')}</ul></details>`
+        : '';
+    return `<tr data-id="${esc(job.id)}">
+        <td title="${esc(job.id)}"><code>${esc(job.id.slice(0, 8))}</code>${job.retry_of ? ' (retry)' : ''}</td>
+        <td>${esc(job.action)}</td>
+        <td>${esc(job.state)}${job.current && job.state === 'running' ? `<br><small>on ${esc(job.current.slice(0, 8))}</small>` : ''}</td>
+        <td>
+            <div class="confidence"><div class="confidence-fill" style="width: ${pct}%"></div></div>
+            ${job.processed}/${job.total}, ${job.succeeded} ok ${errors}
+        </td>
+        <td>${job.started_at ? duration(job.elapsed_secs) : ''}</td>
+        <td>${new Date(job.queued_at).toISOString().slice(0, 16).replace('T', ' ')}</td>
+        <td>
+            ${running ? '<button data-action="cancel">Cancel</button>' : ''}
+            ${retryable ? '<button data-action="retry">Retry</button>' : ''}
+        </td>
+    </tr>`;
+}
+
+function renderQueueRow(job) {
+    const target = job.path || job.file_id || job.output || '';
+    return `<tr data-id="${job.id}">
+        <td>${job.id}</td>
+        <td>${esc(job.kind)}</td>
+        <td><code>${esc(target)}</code></td>
+        <td${job.last_error ? ` title="${esc(job.last_error)}"` : ''}>${esc(job.state)}</td>
+        <td>${job.attempts}/${job.max_attempts}</td>
+        <td>${new Date(job.created_at).toISOString().slice(0, 16).replace('T', ' ')}</td>
+        <td>
+            ${job.state === 'queued' ? '<button data-action="cancel">Cancel</button>' : ''}
+            ${job.state === 'dead' || job.state === 'cancelled' ? '<button data-action="retry">Retry</button>' : ''}
+        </td>
+    </tr>`;
+}
+
+// Fill the table with id `api` from `/api/<api>`; returns whether anything is unfinished
+async function refreshTable(api, render) {
+    const res = await fetch(`${base}/api/${api}`);
+    if (!res.ok) return false;
+    const jobs = await res.json();
+    const table = document.getElementById(api);
+    table.querySelectorAll('tr:not(:first-child)').forEach(row => row.remove());
+    table.insertAdjacentHTML('beforeend', jobs.length
+        ? jobs.map(render).join('')
+        : '<tr><td colspan="7">No jobs</td></tr>');
+    return jobs.some(job => job.state === 'queued' || job.state === 'running');
+}
+
+async function refresh() {
+    const [bulk, queue] = await Promise.all([refreshTable('jobs', renderRow), refreshTable('queue', renderQueueRow)]);
+    if (bulk || queue) {
+        setTimeout(refresh, REFRESH_MS);
+    }
+}
+
+document.addEventListener('click', async e => {
+    const action = e.target.dataset.action;
+    if (!action) return;
+    const api = e.target.closest('table').id;
+    const id = e.target.closest('tr').dataset.id;
+    const res = await fetch(`${base}/api/${api}/${encodeURIComponent(id)}/${action}`, { method: 'POST' });
+    if (!res.ok) {
+        const reply = await res.json().catch(() => ({}));
+        alert(reply.error || `Could not ${action} the job`);
+    }
+    refresh();
+});
+
+refresh();
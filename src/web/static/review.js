@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Approving (with an optional corrected name) and rejecting suggestions
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+async function review(card, action) {
+    const id = card.dataset.id;
+    const body = action === 'approve'
+        ? JSON.stringify({ name: card.querySelector('input.name').value })
+        : '{}';
+    const res = await fetch(`${base}/api/review/${encodeURIComponent(id)}/${action}`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body,
+    });
+    const reply = await res.json();
+    if (res.ok) {
+        card.remove();
+    } else {
+        alert(reply.message);
+    }
+}
+
+document.addEventListener('click', e => {
+    const action = e.target.dataset.action;
+    if (action) review(e.target.closest('.review'), action);
+});
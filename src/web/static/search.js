@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Faceted search; the query lives in the page URL so searches can be bookmarked
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+const pageSize = 50;
+const form = document.getElementById('search');
+let params = new URLSearchParams(location.search);
+
+function esc(value) {
+    const div = document.createElement('div');
+    div.textContent = value ?? '';
+    return div.innerHTML;
+}
+
+function renderRow(f) {
+    const date = new Date(f.created_at).toISOString().slice(0, 16).replace('T', ' ');
+    return `<tr>
+        <td><img src="${base}/api/files/${encodeURIComponent(f.id)}/thumbnail" alt="" loading="lazy" class="thumb"></td>
+        <td>${esc(f.suggested_name)}</td>
+        <td>${esc(f.new_path.split(/[\\/]/).pop())}</td>
+        <td><span class="category-badge">${esc(f.category || 'Uncategorized')}</span></td>
+        <td><div class="confidence"><div class="confidence-fill" style="width: ${Math.round(f.confidence * 100)}%"></div></div></td>
+        <td>${date}</td>
+    </tr>`;
+}
+
+function renderFacets(facets) {
+    for (const section of document.querySelectorAll('.facet')) {
+        const name = section.dataset.facet;
+        const values = facets[name] || [];
+        section.querySelector('.values').innerHTML = values.length
+            ? values.map(v => `<label style="display: block;">
+                <input type="checkbox" value="${esc(v.value)}"${v.selected ? ' checked' : ''}>
+                ${esc(v.value)} <span style="color: var(--text-secondary);">(${v.count})</span>
+              </label>`).join('')
+            : '<span style="color: var(--text-secondary);">None</span>';
+    }
+}
+
+async function load() {
+    const offset = Number(params.get('offset') || 0);
+    params.set('limit', pageSize);
+    history.replaceState(null, '', `?${params}`);
+    const res = await fetch(`${base}/api/search?${params}`);
+    const reply = await res.json().catch(() => ({}));
+    if (!res.ok) {
+        document.getElementById('summary').textContent = reply.error || 'Search failed';
+        return;
+    }
+    renderFacets(reply.facets);
+    document.getElementById('summary').textContent = `${reply.total} file(s)`;
+    const table = document.getElementById('results');
+    table.querySelectorAll('tr:not(:first-child)').forEach(row => row.remove());
+    table.insertAdjacentHTML('beforeend', reply.files.map(renderRow).join(''));
+    const last = Math.min(offset + pageSize, reply.total);
+    document.getElementById('page-info').textContent = reply.total ? `${offset + 1}-${last} of ${reply.total}` : '';
+    document.getElementById('prev').disabled = offset === 0;
+    document.getElementById('next').disabled = last >= reply.total;
+}
+
+function goTo(offset) {
+    params.set('offset', Math.max(0, offset));
+    load();
+}
+
+form.addEventListener('submit', e => {
+    e.preventDefault();
+    params.set('q', form.q.value.trim());
+    goTo(0);
+});
+
+document.getElementById('facets').addEventListener('change', e => {
+    const name = e.target.closest('.facet').dataset.facet;
+    const values = params.getAll(name).filter(v => v !== e.target.value);
+    if (e.target.checked) values.push(e.target.value);
+    params.delete(name);
+    values.forEach(v => params.append(name, v));
+    goTo(0);
+});
+
+document.getElementById('clear').onclick = () => {
+    params = new URLSearchParams({ q: params.get('q') || '' });
+    goTo(0);
+};
+document.getElementById('prev').onclick = () => goTo(Number(params.get('offset') || 0) - pageSize);
+document.getElementById('next').onclick = () => goTo(Number(params.get('offset') || 0) + pageSize);
+
+form.q.value = params.get('q') || '';
+load();
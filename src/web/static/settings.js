@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Configuration editor; only shown to admins, who can read /api/settings
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+function esc(value) {
+    const div = document.createElement('div');
+    div.textContent = value;
+    return div.innerHTML;
+}
+
+async function loadConfig() {
+    const res = await fetch(`${base}/api/settings`);
+    if (!res.ok) return;
+    document.getElementById('config').value = JSON.stringify(await res.json(), null, 2);
+    document.getElementById('editor').hidden = false;
+}
+
+function renderResult(reply, saved) {
+    const problems = (reply.problems || []).map(p => `<li>${esc(p)}</li>`).join('');
+    const changes = (reply.changes || []).map(c => `<tr>
+        <td><code>${esc(c.path)}</code></td>
+        <td>${esc(JSON.stringify(c.old))}</td>
+        <td>${esc(JSON.stringify(c.new))}</td>
+        <td>${c.restart_required ? 'restart' : ''}</td>
+    </tr>`).join('');
+    let html = '';
+    if (reply.error) html += `<p style="color: var(--accent);">${esc(reply.error)}</p>`;
+    if (problems) html += `<ul style="color: var(--accent); margin-left: 20px;">${problems}</ul>`;
+    if (changes) {
+        html += `<table><tr><th>Setting</th><th>Current</th><th>New</th><th></th></tr>${changes}</table>`;
+    } else if (!reply.error && !problems) {
+        html += '<p>No changes.</p>';
+    }
+    if (saved && changes) html = '<p style="color: var(--success);">Saved.</p>' + html;
+    document.getElementById('result').innerHTML = html;
+}
+
+async function submitConfig(action) {
+    let body;
+    try {
+        body = JSON.parse(document.getElementById('config').value);
+    } catch (e) {
+        renderResult({ error: `Not valid JSON: ${e.message}` });
+        return;
+    }
+    const res = await fetch(`${base}/api/settings${action === 'save' ? '' : '/preview'}`, {
+        method: action === 'save' ? 'PUT' : 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(body),
+    });
+    const reply = await res.json().catch(() => ({ error: 'Request failed' }));
+    renderResult(reply, action === 'save' && res.ok);
+    if (action === 'save' && res.ok) loadConfig();
+}
+
+document.getElementById('preview').onclick = () => submitConfig('preview');
+document.getElementById('save').onclick = () => submitConfig('save');
+loadConfig();
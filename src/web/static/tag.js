@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Renaming, merging and removing a tag
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+const tag = document.getElementById('tag-name').dataset.tag;
+
+async function send(url, method, body) {
+    const res = await fetch(url, {
+        method,
+        headers: { 'Content-Type': 'application/json' },
+        body: body === undefined ? undefined : JSON.stringify(body),
+    });
+    const reply = await res.json().catch(() => ({}));
+    if (!res.ok) alert(reply.error || 'Request failed');
+    return res.ok ? reply : null;
+}
+
+document.addEventListener('click', async e => {
+    if (!e.target.matches('button.untag')) return;
+    const row = e.target.closest('tr');
+    if (await send(`${base}/api/files/${encodeURIComponent(row.dataset.id)}/tags/${encodeURIComponent(tag)}`, 'DELETE')) {
+        row.remove();
+    }
+});
+
+document.getElementById('rename').addEventListener('submit', async e => {
+    e.preventDefault();
+    const name = new FormData(e.target).get('name').trim();
+    const reply = await send(`${base}/api/tags/${encodeURIComponent(tag)}`, 'PUT', { name });
+    if (reply) location.href = `${base}/tags/${encodeURIComponent(reply.tag)}`;
+});
+
+document.getElementById('merge').addEventListener('submit', async e => {
+    e.preventDefault();
+    const tags = new FormData(e.target).get('tags').split(',').map(t => t.trim()).filter(Boolean);
+    if (await send(`${base}/api/tags/merge`, 'POST', { tags, into: tag })) location.reload();
+});
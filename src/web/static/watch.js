@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+// Watch directories: add, remove and change options, with status kept current
+const base = document.body.dataset.base; // path prefix behind a reverse proxy, or ''
+
+const REFRESH_MS = 5000;
+
+function esc(value) {
+    const div = document.createElement('div');
+    div.textContent = value ?? '';
+    return div.innerHTML;
+}
+
+function renderRow(dir) {
+    const checked = on => on ? ' checked' : '';
+    const lastEvent = dir.last_event_at ? new Date(dir.last_event_at).toISOString().slice(0, 16).replace('T', ' ') : '';
+    return `<tr data-path="${esc(dir.path)}">
+        <td><code>${esc(dir.path)}</code></td>
+        <td>${esc(dir.state)}${dir.error ? `<br><small>${esc(dir.error)}</small>` : ''}</td>
+        <td>${lastEvent}</td>
+        <td><input type="checkbox" name="recursive"${checked(dir.recursive)}></td>
+        <td><input type="checkbox" name="dry_run"${checked(dir.dry_run)}></td>
+        <td><input type="text" name="destination" value="${esc(dir.destination)}" placeholder="rename in place"></td>
+        <td><input type="checkbox" name="organize" title="Sort into category directories"${checked(dir.organize)}></td>
+        <td><button data-action="remove">Remove</button></td>
+    </tr>`;
+}
+
+function render(dirs) {
+    const table = document.getElementById('dirs');
+    table.querySelectorAll('tr:not(:first-child)').forEach(row => row.remove());
+    table.insertAdjacentHTML('beforeend', dirs.length
+        ? dirs.map(renderRow).join('')
+        : '<tr><td colspan="8">No watch directories</td></tr>');
+}
+
+async function send(method, body, query = '') {
+    const res = await fetch(`${base}/api/watch-dirs${query}`, {
+        method,
+        headers: { 'Content-Type': 'application/json' },
+        body: body === undefined ? undefined : JSON.stringify(body),
+    });
+    const reply = await res.json().catch(() => ({}));
+    if (!res.ok) {
+        alert(reply.error || 'Request failed');
+        return false;
+    }
+    render(reply);
+    return true;
+}
+
+// Options as entered in a row or the add form
+function options(container) {
+    return {
+        recursive: container.querySelector('[name="recursive"]').checked,
+        dry_run: container.querySelector('[name="dry_run"]').checked,
+        destination: container.querySelector('[name="destination"]').value.trim() || null,
+        organize: container.querySelector('[name="organize"]').checked,
+    };
+}
+
+document.getElementById('add').addEventListener('submit', async e => {
+    e.preventDefault();
+    const form = e.target;
+    const path = form.elements.path.value.trim();
+    if (await send('POST', { path, ...options(form) })) form.reset();
+});
+
+// Options apply as soon as they change
+document.getElementById('dirs').addEventListener('change', e => {
+    const row = e.target.closest('tr[data-path]');
+    if (row) send('PUT', { path: row.dataset.path, ...options(row) });
+});
+
+document.getElementById('dirs').addEventListener('click', e => {
+    if (e.target.dataset.action !== 'remove') return;
+    const path = e.target.closest('tr').dataset.path;
+    if (confirm(`Stop watching ${path}?`)) send('DELETE', undefined, `?path=${encodeURIComponent(path)}`);
+});
+
+// Status of the watcher running alongside this server (`panoptes serve`)
+function renderLive(live) {
+    const fields = {
+        processed: live.processed,
+        failed: live.failed,
+        in_progress: live.in_progress.join(', '),
+        last_file: live.last_file,
+        engine: live.engine_available === null ? 'not checked yet'
+            : live.engine_available ? 'available' : `unavailable: ${live.engine_error}`,
+    };
+    for (const [name, value] of Object.entries(fields)) {
+        document.querySelector(`[data-live="${name}"]`).textContent = value ?? '';
+    }
+}
+
+// Keep the status current, without clobbering an option being edited
+setInterval(async () => {
+    if (document.getElementById('live')) {
+        const res = await fetch(`${base}/api/watch/live`);
+        if (res.ok) renderLive(await res.json());
+    }
+    if (document.activeElement?.closest('#dirs')) return;
+    const res = await fetch(`${base}/api/watch-dirs`);
+    if (res.ok) render(await res.json());
+}, REFRESH_MS);
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Tag curation: tagging files, renaming and merging tags
+
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::auth::Actor;
+use super::AppState;
+use crate::db::FileRecord;
+use crate::xattrs;
+
+type TagReply = (StatusCode, Json<Value>);
+
+fn tag_error(code: StatusCode, message: impl ToString) -> TagReply {
+    (code, Json(json!({ "error": message.to_string() })))
+}
+
+/// Trimmed tag name, rejecting empty ones
+fn tag_name(name: &str) -> Result<String, TagReply> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(tag_error(StatusCode::BAD_REQUEST, "Tag name is required"));
+    }
+    Ok(name.to_string())
+}
+
+fn require_file(state: &AppState, id: &str) -> Result<FileRecord, TagReply> {
+    state.db.get_file(id)
+        .map_err(|e| tag_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or_else(|| tag_error(StatusCode::NOT_FOUND, format!("No file with ID {}", id)))
+}
+
+fn file_tags(state: &AppState, id: &str) -> Result<Json<Vec<String>>, TagReply> {
+    state.db.get_file_tags(id)
+        .map(Json)
+        .map_err(|e| tag_error(StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+pub async fn api_get_file_tags(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<String>>, TagReply> {
+    require_file(&state, &id)?;
+    file_tags(&state, &id)
+}
+
+#[derive(Deserialize)]
+pub struct AddTagRequest {
+    tag: String,
+}
+
+pub async fn api_add_file_tag(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(id): Path<String>,
+    Json(request): Json<AddTagRequest>,
+) -> Result<Json<Vec<String>>, TagReply> {
+    require_file(&state, &id)?;
+    let tag = tag_name(&request.tag)?;
+    state.db.add_tag_to_file(&id, &tag)
+        .map_err(|e| tag_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    xattrs::tag_or_warn(&state.db, &id, &state.config());
+    state.audit(&actor, "file.tag", Some(&id), json!({ "tag": tag }));
+    file_tags(&state, &id)
+}
+
+pub async fn api_remove_file_tag(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path((id, tag)): Path<(String, String)>,
+) -> Result<Json<Vec<String>>, TagReply> {
+    require_file(&state, &id)?;
+    let removed = state.db.remove_tag_from_file(&id, &tag)
+        .map_err(|e| tag_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if !removed {
+        return Err(tag_error(StatusCode::NOT_FOUND, format!("File is not tagged '{}'", tag)));
+    }
+    xattrs::tag_or_warn(&state.db, &id, &state.config());
+    state.audit(&actor, "file.untag", Some(&id), json!({ "tag": tag }));
+    file_tags(&state, &id)
+}
+
+#[derive(Deserialize)]
+pub struct RenameTagRequest {
+    name: String,
+}
+
+/// Rename a tag everywhere; renaming onto an existing tag merges the two
+pub async fn api_rename_tag(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Path(tag): Path<String>,
+    Json(request): Json<RenameTagRequest>,
+) -> TagReply {
+    let name = match tag_name(&request.name) {
+        Ok(name) => name,
+        Err(reply) => return reply,
+    };
+    merge(&state, &actor, vec![tag], name, "tag.rename")
+}
+
+#[derive(Deserialize)]
+pub struct MergeTagsRequest {
+    tags: Vec<String>,
+    into: String,
+}
+
+/// Merge several tags into one
+pub async fn api_merge_tags(
+    State(state): State<Arc<AppState>>,
+    Extension(actor): Extension<Actor>,
+    Json(request): Json<MergeTagsRequest>,
+) -> TagReply {
+    let into = match tag_name(&request.into) {
+        Ok(name) => name,
+        Err(reply) => return reply,
+    };
+    if request.tags.is_empty() {
+        return tag_error(StatusCode::BAD_REQUEST, "No tags to merge");
+    }
+    merge(&state, &actor, request.tags, into, "tag.merge")
+}
+
+fn merge(state: &AppState, actor: &Actor, sources: Vec<String>, into: String, action: &str) -> TagReply {
+    for source in &sources {
+        match state.db.tag_exists(source) {
+            Ok(true) => {}
+            Ok(false) => return tag_error(StatusCode::NOT_FOUND, format!("No tag named '{}'", source)),
+            Err(e) => return tag_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+        }
+    }
+    let merged = state.db.tag_exists(&into).unwrap_or(false);
+
+    match state.db.merge_tags(&sources, &into) {
+        Ok(files) => {
+            state.audit(actor, action, Some(&into), json!({ "from": sources, "files": files }));
+            (StatusCode::OK, Json(json!({ "tag": into, "merged": merged, "files": files })))
+        }
+        Err(e) => tag_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
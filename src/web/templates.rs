@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! HTML templates and static assets
+//!
+//! Pages are minijinja templates, with the defaults (`src/web/templates`) and
+//! the CSS and scripts they use (`src/web/static`) embedded in the binary. A
+//! file of the same name in `web.templates_dir` takes precedence, as does
+//! `static/<name>` there for assets, so the UI can be customised without a
+//! rebuild. Templates are loaded on first use; restart to pick up edits.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use minijinja::{Environment, Error, ErrorKind};
+use rust_embed::RustEmbed;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+use super::auth::encode_segment;
+use super::{escape_html, AppState};
+
+#[derive(RustEmbed)]
+#[folder = "src/web/templates/"]
+struct DefaultTemplates;
+
+#[derive(RustEmbed)]
+#[folder = "src/web/static/"]
+struct DefaultAssets;
+
+/// Page templates, with user overrides
+pub struct Templates {
+    env: Environment<'static>,
+    overrides: PathBuf,
+}
+
+impl Templates {
+    /// Templates from `overrides` where present, the built-in ones otherwise;
+    /// `base` is the path prefix for links
+    pub fn new(overrides: impl Into<PathBuf>, base: &str) -> Self {
+        let overrides = overrides.into();
+        let mut env = Environment::new();
+        let dir = overrides.clone();
+        env.set_loader(move |name| load_template(&dir, name));
+        env.add_global("base", base.to_string());
+        env.add_filter("basename", basename);
+        env.add_filter("percent", percent);
+        env.add_filter("datetime", datetime);
+        env.add_filter("segment", segment);
+        Self { env, overrides }
+    }
+
+    /// Render `name` with `context` as an HTML response
+    pub fn render(&self, name: &str, context: impl Serialize) -> Response {
+        match self.env.get_template(name).and_then(|t| t.render(context)) {
+            Ok(html) => Html(html).into_response(),
+            Err(e) => {
+                warn!("Failed to render {}: {:#}", name, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Html(format!("<h1>Template error</h1><pre>{}</pre>", escape_html(&format!("{:#}", e)))),
+                ).into_response()
+            }
+        }
+    }
+
+    /// Overrides in `web.templates_dir` that don't compile, by name, with why
+    pub fn problems(&self) -> Vec<(String, String)> {
+        DefaultTemplates::iter()
+            .filter(|name| self.overrides.join(name.as_ref()).is_file())
+            .filter_map(|name| self.env.get_template(&name).err().map(|e| (name.to_string(), e.to_string())))
+            .collect()
+    }
+
+    /// Contents of a static asset, preferring `<templates_dir>/static/<path>`
+    fn asset(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        if !is_safe_name(path) {
+            return None;
+        }
+        match std::fs::read(self.overrides.join("static").join(path)) {
+            Ok(data) => Some(Cow::Owned(data)),
+            Err(_) => DefaultAssets::get(path).map(|file| file.data),
+        }
+    }
+}
+
+/// Relative names only, so overrides can't reach outside their directory
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('/')
+        && name.split(['/', '\\']).all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+fn load_template(dir: &FsPath, name: &str) -> Result<Option<String>, Error> {
+    if !is_safe_name(name) {
+        return Ok(None);
+    }
+    match std::fs::read_to_string(dir.join(name)) {
+        Ok(source) => return Ok(Some(source)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(Error::new(ErrorKind::InvalidOperation, format!("Could not read template override {}", name))
+                .with_source(e));
+        }
+    }
+    Ok(DefaultTemplates::get(name).map(|file| String::from_utf8_lossy(&file.data).into_owned()))
+}
+
+/// Final path component, for display
+fn basename(path: String) -> String {
+    FsPath::new(&path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .unwrap_or(path)
+}
+
+/// A 0-1 confidence as a whole percentage
+fn percent(value: f64) -> u32 {
+    (value * 100.0).round() as u32
+}
+
+/// An RFC 3339 timestamp as `YYYY-MM-DD HH:MM`
+fn datetime(value: String) -> String {
+    chrono::DateTime::parse_from_rfc3339(&value)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or(value)
+}
+
+/// Percent-encoding for a URL path segment
+fn segment(value: String) -> String {
+    encode_segment(&value)
+}
+
+/// Serve a static asset
+pub async fn static_asset(State(state): State<Arc<AppState>>, Path(path): Path<String>) -> Response {
+    let Some(data) = state.templates.asset(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let content_type = match path.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).as_deref() {
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    };
+    (
+        [(header::CONTENT_TYPE, content_type), (header::CACHE_CONTROL, "public, max-age=3600")],
+        data.into_owned(),
+    ).into_response()
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! HTTPS for the web server
+//!
+//! Certificates are read from `web.tls.cert_path`/`key_path`. With
+//! `self_signed` set, a certificate for `self_signed_names` is generated there
+//! on first run; browsers will warn about it until it is trusted.
+
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::Path;
+use tracing::info;
+
+use crate::config::TlsConfig;
+use crate::{PanoptesError, Result};
+
+/// Make sure the certificate and key exist, generating a self-signed pair if allowed
+pub fn ensure_certificate(tls: &TlsConfig) -> Result<()> {
+    let cert_path = Path::new(&tls.cert_path);
+    let key_path = Path::new(&tls.key_path);
+    if cert_path.exists() && key_path.exists() {
+        return Ok(());
+    }
+    if !tls.self_signed {
+        return Err(PanoptesError::Config(format!(
+            "TLS certificate {} or key {} not found (set web.tls.self_signed to generate one)",
+            tls.cert_path, tls.key_path
+        )));
+    }
+
+    let certified = rcgen::generate_simple_self_signed(tls.self_signed_names.clone())
+        .map_err(|e| PanoptesError::Config(format!("Failed to generate certificate: {}", e)))?;
+
+    for path in [cert_path, key_path] {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    std::fs::write(cert_path, certified.cert.pem())?;
+    write_private(key_path, &certified.key_pair.serialize_pem())?;
+
+    info!("Generated self-signed certificate for {} at {:?}", tls.self_signed_names.join(", "), cert_path);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Load the certificate and key for serving
+pub async fn rustls_config(tls: &TlsConfig) -> Result<RustlsConfig> {
+    // Only ring is compiled in, but rustls still wants a process-wide default
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await
+        .map_err(|e| PanoptesError::Config(format!("Failed to load TLS certificate: {}", e)))
+}
+
+/// Serve plain HTTP on `host:port`, redirecting every request to HTTPS on `https_port`
+pub async fn serve_redirect(host: String, port: u16, https_port: u16) -> Result<()> {
+    let app = Router::new().fallback(move |request: Request| async move {
+        redirect_to_https(&request, https_port)
+    });
+
+    let listener = tokio::net::TcpListener::bind((host.as_str(), port)).await?;
+    info!("Redirecting http://{}:{} to HTTPS", host, port);
+    axum::serve(listener, app).await
+        .map_err(|e| PanoptesError::Config(format!("Redirect server error: {}", e)))
+}
+
+fn redirect_to_https(request: &Request, https_port: u16) -> Response {
+    let Some(host) = request.headers().get(header::HOST).and_then(|h| h.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+    };
+    // Drop any port from the Host header, keeping bracketed IPv6 addresses intact
+    let hostname = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let path = request.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+    let location = if https_port == 443 {
+        format!("https://{}{}", hostname, path)
+    } else {
+        format!("https://{}:{}{}", hostname, https_port, path)
+    };
+    Redirect::permanent(&location).into_response()
+}
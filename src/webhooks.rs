@@ -0,0 +1,256 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Outbound webhooks for processing events
+//!
+//! Each configured URL receives a JSON `POST` for the events it subscribes to:
+//!
+//! ```json
+//! { "event": "renamed", "timestamp": "...", "data": { ... } }
+//! ```
+//!
+//! When the hook has a `secret`, the body is signed with HMAC-SHA256 and the
+//! signature sent as `X-Panoptes-Signature: sha256=<hex>`. Failed deliveries
+//! are retried with exponential backoff, and every attempt is logged in the
+//! `webhook_deliveries` table. Events also go to the [`EventSink`]s given,
+//! MQTT (see [`crate::mqtt`]) and D-Bus (see `crate::dbus`).
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::analyzers::AnalysisResult;
+use crate::config::WebhookConfig;
+use crate::db::Database;
+
+/// Header carrying the body's HMAC-SHA256 signature
+pub const SIGNATURE_HEADER: &str = "X-Panoptes-Signature";
+
+/// How long a receiver has to answer
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+/// Longest wait between retries
+const MAX_BACKOFF_SECS: u64 = 300;
+
+/// Something a webhook can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A file was renamed or moved
+    Renamed,
+    /// A suggestion was queued for review or skipped for low confidence
+    LowConfidence,
+    /// Analyzing or renaming a file failed
+    Error,
+    /// The review queue grew by `review.remind_every` files
+    ReviewQueue,
+    /// A rule in `rules.actions` with a `notify` action matched a file
+    Rule,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Renamed => "renamed",
+            Self::LowConfidence => "low_confidence",
+            Self::Error => "error",
+            Self::ReviewQueue => "review_queue",
+            Self::Rule => "rule",
+        }
+    }
+}
+
+/// Sends webhook deliveries in the background
+pub struct Webhooks {
+    http: reqwest::Client,
+    db: Database,
+    pending: Mutex<Vec<JoinHandle<()>>>,
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+/// Something told of every event, whichever hooks subscribe to it
+pub trait EventSink: Send + Sync {
+    /// Pass on `event`; must not wait
+    fn publish(&self, event: WebhookEvent, data: &serde_json::Value);
+}
+
+impl Webhooks {
+    pub fn new(db: Database) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            db,
+            pending: Mutex::new(Vec::new()),
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Also pass events on to `sink`
+    pub fn with_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Send `event` to every hook subscribed to it; returns immediately
+    pub fn emit(&self, hooks: &[WebhookConfig], event: WebhookEvent, data: serde_json::Value) {
+        for sink in &self.sinks {
+            sink.publish(event, &data);
+        }
+        let hooks: Vec<&WebhookConfig> = hooks.iter()
+            .filter(|h| h.events.is_empty() || h.events.contains(&event))
+            .collect();
+        if hooks.is_empty() {
+            return;
+        }
+
+        let payload = serde_json::json!({
+            "event": event,
+            "timestamp": Utc::now(),
+            "data": data,
+        });
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to encode webhook payload: {}", e);
+                return;
+            }
+        };
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|task| !task.is_finished());
+        for hook in hooks {
+            pending.push(tokio::spawn(deliver(
+                self.http.clone(),
+                self.db.clone(),
+                hook.clone(),
+                event,
+                body.clone(),
+            )));
+        }
+    }
+
+    /// Wait for deliveries still in flight, including their retries
+    pub async fn flush(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        for task in pending {
+            let _ = task.await;
+        }
+    }
+}
+
+/// `sha256=<hex>` signature of `body` with `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// Wait before retry number `attempt` (1 for the first retry)
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(2u64.saturating_pow(attempt.saturating_sub(1)).min(MAX_BACKOFF_SECS))
+}
+
+async fn deliver(http: reqwest::Client, db: Database, hook: WebhookConfig, event: WebhookEvent, body: Vec<u8>) {
+    let delivery_id = uuid::Uuid::new_v4().to_string();
+    let attempts = hook.max_retries + 1;
+
+    for attempt in 1..=attempts {
+        let mut request = http.post(&hook.url)
+            .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Panoptes-Event", event.as_str())
+            .header("X-Panoptes-Delivery", &delivery_id)
+            .body(body.clone());
+        if let Some(ref secret) = hook.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+        }
+
+        let (status, error) = match request.send().await {
+            Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+            Ok(response) => (Some(response.status().as_u16()), Some(format!("HTTP {}", response.status()))),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        if let Err(e) = db.record_webhook_delivery(&delivery_id, &hook.url, event.as_str(), attempt, status, error.as_deref()) {
+            warn!("Failed to log webhook delivery: {}", e);
+        }
+
+        match error {
+            None => {
+                debug!("Delivered {} webhook to {}", event.as_str(), hook.url);
+                return;
+            }
+            Some(error) if attempt < attempts => {
+                let wait = backoff(attempt);
+                debug!("Webhook to {} failed ({}), retrying in {:?}", hook.url, error, wait);
+                tokio::time::sleep(wait).await;
+            }
+            Some(error) => {
+                warn!("Giving up on {} webhook to {} after {} attempts: {}", event.as_str(), hook.url, attempts, error);
+            }
+        }
+    }
+}
+
+/// Payload for [`WebhookEvent::Renamed`]
+pub fn renamed(file_id: Option<&str>, from: &Path, to: &Path, result: &AnalysisResult) -> serde_json::Value {
+    serde_json::json!({
+        "file_id": file_id,
+        "original_path": from,
+        "new_path": to,
+        "suggested_name": result.suggested_name,
+        "category": result.category,
+        "tags": result.tags,
+        "confidence": result.confidence,
+    })
+}
+
+/// Payload for [`WebhookEvent::LowConfidence`]; `queued` tells whether the
+/// suggestion went to the review queue or was dropped
+pub fn low_confidence(file_id: Option<&str>, path: &Path, result: &AnalysisResult, queued: bool) -> serde_json::Value {
+    serde_json::json!({
+        "file_id": file_id,
+        "path": path,
+        "suggested_name": result.suggested_name,
+        "category": result.category,
+        "confidence": result.confidence,
+        "queued_for_review": queued,
+    })
+}
+
+/// Payload for [`WebhookEvent::ReviewQueue`]; `review_url` is the web UI's
+/// review page, when the web UI is enabled
+pub fn review_queue(pending: i64, review_url: Option<String>) -> serde_json::Value {
+    serde_json::json!({
+        "pending": pending,
+        "review_url": review_url,
+    })
+}
+
+/// Payload for [`WebhookEvent::Rule`]; `path` is where the file is now
+pub fn rule(file_id: Option<&str>, rule: &str, message: Option<&str>, path: &Path, result: &AnalysisResult) -> serde_json::Value {
+    serde_json::json!({
+        "rule": rule,
+        "message": message,
+        "file_id": file_id,
+        "path": path,
+        "suggested_name": result.suggested_name,
+        "category": result.category,
+        "tags": result.tags,
+        "confidence": result.confidence,
+    })
+}
+
+/// Payload for [`WebhookEvent::Error`]
+pub fn failed(path: &Path, error: &str) -> serde_json::Value {
+    serde_json::json!({
+        "path": path,
+        "error": error,
+    })
+}
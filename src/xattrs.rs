@@ -0,0 +1,177 @@
+// SPDX-License-Identifier: MIT
+// SPDX-FileCopyrightText: 2025 Jonathan D. A. Jewell <hyperpolymath>
+
+//! Tags and category in extended attributes, where file managers see them
+//!
+//! With `xattrs.enabled`, a file's tags go to `user.xdg.tags` (read by KDE
+//! and other XDG file managers) and, as Panoptes wrote them, to
+//! `user.panoptes.tags`; its category goes to `user.panoptes.category`. Tags
+//! another program put in `user.xdg.tags` are kept.
+//!
+//! `panoptes verify` reads them back: a file whose attributes were lost (say,
+//! copied by a tool that drops them) gets them again, and tags changed in a
+//! file manager since Panoptes wrote them are taken into the database.
+//! Attributes stay with a file through renames, and moves to another file
+//! system copy them. Elsewhere than Unix, nothing is written.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::config::AppConfig;
+use crate::db::Database;
+use crate::native_tags;
+use crate::Result;
+
+const TAGS: &str = "user.xdg.tags";
+const WRITTEN_TAGS: &str = "user.panoptes.tags";
+const CATEGORY: &str = "user.panoptes.category";
+
+/// What a file's attributes say
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTags {
+    /// Tags file managers show
+    pub tags: Vec<String>,
+    /// Tags as Panoptes last wrote them
+    pub written: Vec<String>,
+    pub category: Option<String>,
+}
+
+impl FileTags {
+    /// Tags added and removed in a file manager since Panoptes wrote them
+    pub fn edits(&self) -> (Vec<String>, Vec<String>) {
+        let now: BTreeSet<&String> = self.tags.iter().collect();
+        let written: BTreeSet<&String> = self.written.iter().collect();
+        (
+            now.difference(&written).map(|t| t.to_string()).collect(),
+            written.difference(&now).map(|t| t.to_string()).collect(),
+        )
+    }
+}
+
+/// A comma-separated list, as `user.xdg.tags` holds
+fn split(value: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(value)
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// The Panoptes attributes of `path`; `None` when it has none
+#[cfg(unix)]
+pub fn read(path: &Path) -> std::io::Result<Option<FileTags>> {
+    let written = xattr::get(path, WRITTEN_TAGS)?;
+    let category = xattr::get(path, CATEGORY)?;
+    if written.is_none() && category.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(FileTags {
+        tags: xattr::get(path, TAGS)?.as_deref().map(split).unwrap_or_default(),
+        written: written.as_deref().map(split).unwrap_or_default(),
+        category: category.map(|c| String::from_utf8_lossy(&c).into_owned()),
+    }))
+}
+
+#[cfg(not(unix))]
+pub fn read(_path: &Path) -> std::io::Result<Option<FileTags>> {
+    Ok(None)
+}
+
+/// Write `tags` and `category` to the attributes of `path`, keeping tags
+/// other programs added
+#[cfg(unix)]
+pub fn write(path: &Path, tags: &[String], category: Option<&str>) -> std::io::Result<()> {
+    let previous = read(path)?.unwrap_or_default();
+    let mut shown: Vec<String> = match xattr::get(path, TAGS)? {
+        Some(value) => split(&value).into_iter().filter(|t| !previous.written.contains(t)).collect(),
+        None => Vec::new(),
+    };
+    for tag in tags {
+        if !shown.contains(tag) {
+            shown.push(tag.clone());
+        }
+    }
+
+    xattr::set(path, TAGS, shown.join(",").as_bytes())?;
+    xattr::set(path, WRITTEN_TAGS, tags.join(",").as_bytes())?;
+    match category {
+        Some(category) => xattr::set(path, CATEGORY, category.as_bytes())?,
+        None if previous.category.is_some() => xattr::remove(path, CATEGORY)?,
+        None => {}
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn write(_path: &Path, _tags: &[String], _category: Option<&str>) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Copy the attributes of `from` to `to`, for a move that had to copy the file
+#[cfg(unix)]
+pub fn copy(from: &Path, to: &Path) {
+    for name in [TAGS, WRITTEN_TAGS, CATEGORY] {
+        if let Ok(Some(value)) = xattr::get(from, name) {
+            if let Err(e) = xattr::set(to, name, &value) {
+                warn!("Failed to copy {} to {:?}: {}", name, to, e);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn copy(_from: &Path, _to: &Path) {}
+
+/// A file with the tags and category the database has for it
+struct Recorded {
+    path: PathBuf,
+    tags: Vec<String>,
+    category: Option<String>,
+}
+
+/// A recorded file; `None` when it isn't there
+fn recorded_tags(db: &Database, file_id: &str) -> Result<Option<Recorded>> {
+    let Some(record) = db.get_file(file_id)?.filter(|record| !record.is_remote()) else {
+        return Ok(None);
+    };
+    let path = PathBuf::from(&record.new_path);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(Recorded { path, tags: db.get_file_tags(file_id)?, category: record.category }))
+}
+
+/// Write the tags and category the database has for a file to its
+/// attributes; returns whether the file was there to write to
+pub fn tag_record(db: &Database, file_id: &str) -> Result<bool> {
+    let Some(file) = recorded_tags(db, file_id)? else {
+        return Ok(false);
+    };
+    write(&file.path, &file.tags, file.category.as_deref())?;
+    Ok(true)
+}
+
+/// Show a file's tags and category where other programs see them: in its
+/// attributes if `xattrs.enabled`, and in the file manager if
+/// `native_tags.enabled`. Logs rather than fails.
+pub fn tag_or_warn(db: &Database, file_id: &str, config: &AppConfig) {
+    if !config.xattrs.enabled && !config.native_tags.enabled {
+        return;
+    }
+    let tagged = recorded_tags(db, file_id).and_then(|file| {
+        let Some(file) = file else {
+            return Ok(());
+        };
+        if config.xattrs.enabled {
+            write(&file.path, &file.tags, file.category.as_deref())?;
+        }
+        if config.native_tags.enabled {
+            native_tags::apply(&file.path, &file.tags, file.category.as_deref(), &config.native_tags)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = tagged {
+        warn!("Failed to tag {}: {}", file_id, e);
+    }
+}